@@ -0,0 +1,64 @@
+//! Requirements on protocol headers and frames (parts 2.2, 2.3 and 5.3)
+
+use crate::{ensure, Area, Expectation, Requirement};
+use dumq_amqp::transport::{FrameHeader, FrameType, ProtocolHeader};
+
+fn amqp_header() -> Result<(), String> {
+    let header = ProtocolHeader::AMQP;
+    ensure(header.as_bytes() == b"AMQP\x00\x01\x00\x00", || format!("AMQP header is {:02x?}", header.as_bytes()))
+}
+
+fn tls_header() -> Result<(), String> {
+    let header = ProtocolHeader::TLS;
+    ensure(header.as_bytes() == b"AMQP\x02\x01\x00\x00", || format!("TLS header is {:02x?}", header.as_bytes()))
+}
+
+fn sasl_header() -> Result<(), String> {
+    let header = ProtocolHeader::SASL;
+    ensure(header.as_bytes() == b"AMQP\x03\x01\x00\x00", || format!("SASL header is {:02x?}", header.as_bytes()))
+}
+
+fn header_layout() -> Result<(), String> {
+    let encoded = FrameHeader::new(0x0102_0304, FrameType::SASL as u8, 0x0506).encode();
+    ensure(encoded == [0x01, 0x02, 0x03, 0x04, 0x02, 0x01, 0x05, 0x06], || {
+        format!("frame header encoded as {:02x?}", encoded)
+    })
+}
+
+fn frame_types() -> Result<(), String> {
+    ensure(FrameType::AMQP as u8 == 0x00 && FrameType::SASL as u8 == 0x01, || {
+        "frame type codes differ from 0x00 and 0x01".to_string()
+    })
+}
+
+fn minimum_doff() -> Result<(), String> {
+    ensure(FrameHeader::decode(&[0, 0, 0, 8, 1, 0, 0, 0]).is_err(), || {
+        "frame header with DOFF 1 accepted".to_string()
+    })
+}
+
+fn minimum_size() -> Result<(), String> {
+    ensure(FrameHeader::decode(&[0, 0, 0, 4, 2, 0, 0, 0]).is_err(), || {
+        "frame header with SIZE 4 accepted".to_string()
+    })
+}
+
+pub(crate) fn requirements() -> Vec<Requirement> {
+    let requirement = |id, section, statement, expectation, check| Requirement {
+        id,
+        section,
+        statement,
+        area: Area::Framing,
+        expectation,
+        check,
+    };
+    vec![
+        requirement("F-01", "2.2", "the AMQP protocol header is \"AMQP\" 0 1 0 0", Expectation::Pass, amqp_header),
+        requirement("F-02", "5.2.1", "the TLS protocol header is \"AMQP\" 2 1 0 0", Expectation::Pass, tls_header),
+        requirement("F-03", "5.3.1", "the SASL protocol header is \"AMQP\" 3 1 0 0", Expectation::Pass, sasl_header),
+        requirement("F-04", "2.3.1", "the frame header is SIZE (4), DOFF (1), TYPE (1) and two type-specific bytes", Expectation::Pass, header_layout),
+        requirement("F-05", "2.3.2, 5.3.1", "AMQP frames have type 0x00 and SASL frames 0x01", Expectation::Pass, frame_types),
+        requirement("F-06", "2.3.1", "a DOFF below 2 is malformed", Expectation::KnownGap, minimum_doff),
+        requirement("F-07", "2.3.1", "a SIZE below 8 is malformed", Expectation::KnownGap, minimum_size),
+    ]
+}
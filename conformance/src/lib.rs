@@ -0,0 +1,207 @@
+//! AMQP 1.0 Conformance Checks
+//!
+//! This crate enumerates testable normative statements of the AMQP 1.0
+//! specification and checks dumq-amqp against each of them. Every
+//! [`Requirement`] names the spec section it comes from, the statement, and
+//! whether the crate is currently expected to meet it. The suite fails when a
+//! requirement expected to pass does not, and also when a known gap starts
+//! passing, so the expectations (and `docs/CONFORMANCE.md`, generated from
+//! them) stay accurate as the implementation grows.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp_conformance::{requirements, Report};
+//!
+//! let report = Report::run(&requirements());
+//! println!("{}/{} requirements met", report.passed(), report.results.len());
+//! for result in report.unexpected() {
+//!     println!("{}: {:?}", result.requirement.id, result.outcome);
+//! }
+//! ```
+
+use std::fmt;
+
+mod framing;
+mod messaging;
+mod performatives;
+mod types;
+
+/// Part of the specification a requirement belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Area {
+    /// Type system and encodings (part 1)
+    Types,
+    /// Protocol headers and frames (part 2.2, 2.3 and 5.3)
+    Framing,
+    /// Performative fields (part 2.7)
+    Performatives,
+    /// Message sections (part 3.2)
+    Messaging,
+}
+
+impl fmt::Display for Area {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Area::Types => "Types",
+            Area::Framing => "Framing",
+            Area::Performatives => "Performatives",
+            Area::Messaging => "Messaging",
+        })
+    }
+}
+
+/// Whether the crate is expected to meet a requirement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expectation {
+    /// The requirement is met
+    Pass,
+    /// The requirement is known not to be met yet
+    KnownGap,
+}
+
+/// A testable normative statement
+#[derive(Debug, Clone, Copy)]
+pub struct Requirement {
+    /// Stable identifier, e.g. `T-03`
+    pub id: &'static str,
+    /// Spec section the statement comes from
+    pub section: &'static str,
+    /// The statement, paraphrased from the spec
+    pub statement: &'static str,
+    /// Part of the specification
+    pub area: Area,
+    /// Whether the crate is expected to meet it
+    pub expectation: Expectation,
+    /// Check the crate against the statement
+    pub check: fn() -> Result<(), String>,
+}
+
+/// Get every requirement, grouped by area
+pub fn requirements() -> Vec<Requirement> {
+    let mut requirements = Vec::new();
+    requirements.extend(types::requirements());
+    requirements.extend(framing::requirements());
+    requirements.extend(performatives::requirements());
+    requirements.extend(messaging::requirements());
+    requirements
+}
+
+/// Result of checking one requirement
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    /// The requirement checked
+    pub requirement: Requirement,
+    /// `Err` describes how the crate deviates
+    pub outcome: Result<(), String>,
+}
+
+impl CheckResult {
+    /// Check if the outcome differs from the expectation
+    pub fn is_unexpected(&self) -> bool {
+        self.outcome.is_ok() != (self.requirement.expectation == Expectation::Pass)
+    }
+}
+
+/// Results of checking a set of requirements
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// One result per requirement, in order
+    pub results: Vec<CheckResult>,
+}
+
+impl Report {
+    /// Check every requirement
+    pub fn run(requirements: &[Requirement]) -> Self {
+        let results = requirements
+            .iter()
+            .map(|requirement| CheckResult {
+                requirement: *requirement,
+                outcome: (requirement.check)(),
+            })
+            .collect();
+        Report { results }
+    }
+
+    /// Get the number of requirements met
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|result| result.outcome.is_ok()).count()
+    }
+
+    /// Get the results that differ from their expectation
+    pub fn unexpected(&self) -> impl Iterator<Item = &CheckResult> {
+        self.results.iter().filter(|result| result.is_unexpected())
+    }
+
+    /// Render the report as the markdown of `docs/CONFORMANCE.md`
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("# AMQP 1.0 Conformance\n\n");
+        out.push_str("Generated by `cargo run -p dumq-amqp-conformance`; do not edit by hand.\n\n");
+        out.push_str(&format!("{} of {} requirements met.\n", self.passed(), self.results.len()));
+
+        let mut area = None;
+        for result in &self.results {
+            let requirement = &result.requirement;
+            if area != Some(requirement.area) {
+                area = Some(requirement.area);
+                out.push_str(&format!("\n## {}\n\n", requirement.area));
+                out.push_str("| Id | Section | Requirement | Status |\n");
+                out.push_str("|----|---------|-------------|--------|\n");
+            }
+            let status = match &result.outcome {
+                Ok(()) => "pass".to_string(),
+                Err(reason) => format!("gap: {}", reason),
+            };
+            out.push_str(&format!(
+                "| {} | {} | {} | {} |\n",
+                requirement.id, requirement.section, requirement.statement, status
+            ));
+        }
+        out
+    }
+}
+
+/// Fail unless `condition` holds
+pub(crate) fn ensure(condition: bool, reason: impl FnOnce() -> String) -> Result<(), String> {
+    if condition {
+        Ok(())
+    } else {
+        Err(reason())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_expectations_hold() {
+        let report = Report::run(&requirements());
+        let unexpected: Vec<String> = report
+            .unexpected()
+            .map(|result| match &result.outcome {
+                Ok(()) => format!("{} now passes; mark it Expectation::Pass", result.requirement.id),
+                Err(reason) => format!("{} regressed: {}", result.requirement.id, reason),
+            })
+            .collect();
+        assert!(unexpected.is_empty(), "{}", unexpected.join("\n"));
+    }
+
+    #[test]
+    fn test_ids_unique() {
+        let requirements = requirements();
+        let ids: HashSet<&str> = requirements.iter().map(|requirement| requirement.id).collect();
+        assert_eq!(ids.len(), requirements.len());
+    }
+
+    #[test]
+    fn test_conformance_doc_up_to_date() {
+        let report = Report::run(&requirements());
+        assert_eq!(
+            include_str!("../../docs/CONFORMANCE.md"),
+            report.to_markdown(),
+            "Regenerate with `cargo run -p dumq-amqp-conformance > docs/CONFORMANCE.md`"
+        );
+    }
+}
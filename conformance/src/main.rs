@@ -0,0 +1,17 @@
+//! Print the conformance report as markdown
+//!
+//! Exits with a failure status if any requirement differs from its
+//! expectation.
+
+use dumq_amqp_conformance::{requirements, Report};
+
+fn main() {
+    let report = Report::run(&requirements());
+    print!("{}", report.to_markdown());
+
+    let unexpected: Vec<&str> = report.unexpected().map(|result| result.requirement.id).collect();
+    if !unexpected.is_empty() {
+        eprintln!("Unexpected results: {}", unexpected.join(", "));
+        std::process::exit(1);
+    }
+}
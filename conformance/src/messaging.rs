@@ -0,0 +1,79 @@
+//! Requirements on the message format (part 3.2)
+
+use crate::{ensure, Area, Expectation, Requirement};
+use dumq_amqp::codec::{Decoder, Encoder};
+use dumq_amqp::message::{Body, Header, Message, Properties};
+use dumq_amqp::types::{AmqpMap, AmqpSymbol, AmqpValue};
+
+fn encode(message: &Message) -> Result<Vec<u8>, String> {
+    let mut encoder = Encoder::new();
+    encoder.encode_message(message).map_err(|e| e.to_string())?;
+    Ok(encoder.finish())
+}
+
+/// Get the descriptor of each top-level section
+fn section_codes(data: Vec<u8>) -> Result<Vec<Option<AmqpValue>>, String> {
+    Decoder::new(data)
+        .values()
+        .map(|value| value.map(|value| value.descriptor).map_err(|e| e.to_string()))
+        .collect()
+}
+
+fn sections_described() -> Result<(), String> {
+    let mut application_properties = AmqpMap::new();
+    application_properties.insert(AmqpSymbol::from("tenant"), AmqpValue::String("acme".to_string()));
+    let message = Message::builder()
+        .header(Header::new())
+        .properties(Properties::new())
+        .application_properties(application_properties)
+        .body(Body::Value(AmqpValue::Int(1)))
+        .build();
+    let codes = section_codes(encode(&message)?)?;
+    let expected: Vec<_> = [0x70, 0x73, 0x74, 0x77].map(|code| Some(AmqpValue::Ulong(code))).into();
+    ensure(codes == expected, || format!("message encoded with sections {:?}", codes))
+}
+
+fn body_sections() -> Result<(), String> {
+    let data = Message::builder().body(Body::Data(b"ab".to_vec().into())).build();
+    let sequence = Message::builder().body(Body::Sequence(vec![AmqpValue::Int(1)].into())).build();
+    for (message, code) in [(data, 0x75), (sequence, 0x76)] {
+        let codes = section_codes(encode(&message)?)?;
+        ensure(codes == [Some(AmqpValue::Ulong(code))], || format!("body encoded with sections {:?}", codes))?;
+    }
+    Ok(())
+}
+
+fn application_property_keys() -> Result<(), String> {
+    let mut application_properties = AmqpMap::new();
+    application_properties.insert(AmqpSymbol::from("k"), AmqpValue::Null);
+    let message = Message::builder().application_properties(application_properties).build();
+    let encoded = encode(&message)?;
+    // map8 with a size of 5 and a count of 2, then the string key "k"
+    let expected = [0x00, 0x53, 0x74, 0xc1, 5, 2, 0xa1, 1, b'k', 0x40];
+    ensure(encoded == expected, || format!("application properties encoded as {:02x?}", encoded))
+}
+
+fn symbolic_descriptors() -> Result<(), String> {
+    let mut data = vec![0x00, 0xa3, 16];
+    data.extend_from_slice(b"amqp:header:list");
+    data.extend_from_slice(&[0x45]);
+    let message = Decoder::new(data).decode_message().map_err(|e| e.to_string())?;
+    ensure(message.header.is_some(), || "header section with a symbolic descriptor not read".to_string())
+}
+
+pub(crate) fn requirements() -> Vec<Requirement> {
+    let requirement = |id, section, statement, expectation, check| Requirement {
+        id,
+        section,
+        statement,
+        area: Area::Messaging,
+        expectation,
+        check,
+    };
+    vec![
+        requirement("M-01", "3.2", "message sections are described types with the codes 0x70 to 0x78", Expectation::Pass, sections_described),
+        requirement("M-02", "3.2.6", "data and amqp-sequence bodies are 0x75 and 0x76 sections", Expectation::Pass, body_sections),
+        requirement("M-03", "3.2.5", "application-properties is a map with string keys", Expectation::Pass, application_property_keys),
+        requirement("M-04", "1.5", "sections may use symbolic descriptors", Expectation::Pass, symbolic_descriptors),
+    ]
+}
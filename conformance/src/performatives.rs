@@ -0,0 +1,101 @@
+//! Requirements on performative fields (part 2.7)
+
+use crate::{ensure, Area, Expectation, Requirement};
+use dumq_amqp::codec::Encoder;
+use dumq_amqp::performative::{descriptor, Attach, Begin, Detach, Disposition, Flow, Open, Transfer};
+use dumq_amqp::types::AmqpValue;
+use dumq_amqp::AmqpResult;
+
+fn body(code: u64, fields: &[AmqpValue]) -> Vec<u8> {
+    let mut encoder = Encoder::new();
+    encoder
+        .encode_described_list(code, fields)
+        .expect("described lists of plain values always encode");
+    encoder.finish()
+}
+
+/// Check that decoding fails without a mandatory field but succeeds with it
+fn mandatory<T>(
+    decode: fn(&[u8]) -> AmqpResult<T>,
+    code: u64,
+    valid: &[AmqpValue],
+    names: &[(usize, &str)],
+) -> Result<(), String> {
+    decode(&body(code, valid)).map_err(|e| format!("valid performative rejected: {}", e))?;
+    for (index, name) in names {
+        let mut fields = valid.to_vec();
+        fields[*index] = AmqpValue::Null;
+        ensure(decode(&body(code, &fields)).is_err(), || format!("performative 0x{:02x} accepted without {}", code, name))?;
+    }
+    Ok(())
+}
+
+fn open_mandatory() -> Result<(), String> {
+    mandatory(Open::decode, descriptor::OPEN, &[AmqpValue::String("c".to_string())], &[(0, "container-id")])
+}
+
+fn open_defaults() -> Result<(), String> {
+    let open = Open::decode(&body(descriptor::OPEN, &[AmqpValue::String("c".to_string())])).map_err(|e| e.to_string())?;
+    ensure(open.max_frame_size == u32::MAX && open.channel_max == u16::MAX && open.idle_time_out.is_none(), || {
+        format!("open defaults decoded as {:?}", open)
+    })
+}
+
+fn begin_mandatory() -> Result<(), String> {
+    let valid = [AmqpValue::Null, AmqpValue::Uint(0), AmqpValue::Uint(10), AmqpValue::Uint(10)];
+    mandatory(
+        Begin::decode,
+        descriptor::BEGIN,
+        &valid,
+        &[(1, "next-outgoing-id"), (2, "incoming-window"), (3, "outgoing-window")],
+    )
+}
+
+fn attach_mandatory() -> Result<(), String> {
+    let valid = [AmqpValue::String("link".to_string()), AmqpValue::Uint(0), AmqpValue::Boolean(false)];
+    mandatory(Attach::decode, descriptor::ATTACH, &valid, &[(0, "name"), (1, "handle"), (2, "role")])
+}
+
+fn flow_mandatory() -> Result<(), String> {
+    let valid = [AmqpValue::Null, AmqpValue::Uint(10), AmqpValue::Uint(0), AmqpValue::Uint(10)];
+    mandatory(
+        Flow::decode,
+        descriptor::FLOW,
+        &valid,
+        &[(1, "incoming-window"), (2, "next-outgoing-id"), (3, "outgoing-window")],
+    )
+}
+
+fn detach_mandatory() -> Result<(), String> {
+    mandatory(Detach::decode, descriptor::DETACH, &[AmqpValue::Uint(0)], &[(0, "handle")])
+}
+
+fn disposition_mandatory() -> Result<(), String> {
+    let valid = [AmqpValue::Boolean(true), AmqpValue::Uint(0)];
+    mandatory(Disposition::decode, descriptor::DISPOSITION, &valid, &[(0, "role"), (1, "first")])
+}
+
+fn transfer_mandatory() -> Result<(), String> {
+    mandatory(Transfer::decode, descriptor::TRANSFER, &[AmqpValue::Uint(0)], &[(0, "handle")])
+}
+
+pub(crate) fn requirements() -> Vec<Requirement> {
+    let requirement = |id, section, statement, expectation, check| Requirement {
+        id,
+        section,
+        statement,
+        area: Area::Performatives,
+        expectation,
+        check,
+    };
+    vec![
+        requirement("P-01", "2.7.1", "open requires container-id", Expectation::Pass, open_mandatory),
+        requirement("P-02", "2.7.1", "open defaults max-frame-size and channel-max to their maximum, with no idle timeout", Expectation::Pass, open_defaults),
+        requirement("P-03", "2.7.2", "begin requires next-outgoing-id, incoming-window and outgoing-window", Expectation::Pass, begin_mandatory),
+        requirement("P-04", "2.7.3", "attach requires name, handle and role", Expectation::Pass, attach_mandatory),
+        requirement("P-05", "2.7.4", "flow requires incoming-window, next-outgoing-id and outgoing-window", Expectation::Pass, flow_mandatory),
+        requirement("P-06", "2.7.5", "transfer requires handle", Expectation::Pass, transfer_mandatory),
+        requirement("P-07", "2.7.6", "disposition requires role and first", Expectation::Pass, disposition_mandatory),
+        requirement("P-08", "2.7.7", "detach requires handle", Expectation::Pass, detach_mandatory),
+    ]
+}
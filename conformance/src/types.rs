@@ -0,0 +1,144 @@
+//! Requirements on the type system and its encodings (part 1)
+
+use crate::{ensure, Area, Expectation, Requirement};
+use dumq_amqp::codec::{Decoder, Encoder};
+use dumq_amqp::types::{AmqpList, AmqpMap, AmqpSymbol, AmqpValue};
+
+fn encode(value: &AmqpValue) -> Result<Vec<u8>, String> {
+    let mut encoder = Encoder::new();
+    encoder.encode_value(value).map_err(|e| e.to_string())?;
+    Ok(encoder.finish())
+}
+
+fn decode(data: &[u8]) -> Result<AmqpValue, String> {
+    Decoder::new(data.to_vec()).decode_value().map_err(|e| e.to_string())
+}
+
+fn encodes_as(value: AmqpValue, expected: &[u8]) -> Result<(), String> {
+    let encoded = encode(&value)?;
+    ensure(encoded == expected, || format!("{:?} encoded as {:02x?}, expected {:02x?}", value, encoded, expected))
+}
+
+fn decodes_as(data: &[u8], expected: AmqpValue) -> Result<(), String> {
+    let decoded = decode(data)?;
+    ensure(decoded == expected, || format!("{:02x?} decoded as {:?}, expected {:?}", data, decoded, expected))
+}
+
+fn null() -> Result<(), String> {
+    encodes_as(AmqpValue::Null, &[0x40])?;
+    decodes_as(&[0x40], AmqpValue::Null)
+}
+
+fn boolean() -> Result<(), String> {
+    encodes_as(AmqpValue::Boolean(true), &[0x41])?;
+    encodes_as(AmqpValue::Boolean(false), &[0x42])?;
+    decodes_as(&[0x56, 0x01], AmqpValue::Boolean(true))?;
+    decodes_as(&[0x56, 0x00], AmqpValue::Boolean(false))
+}
+
+fn uint_encodings() -> Result<(), String> {
+    decodes_as(&[0x43], AmqpValue::Uint(0))?;
+    decodes_as(&[0x52, 0x07], AmqpValue::Uint(7))?;
+    decodes_as(&[0x70, 0x00, 0x00, 0x00, 0x07], AmqpValue::Uint(7))
+}
+
+fn ulong_encodings() -> Result<(), String> {
+    decodes_as(&[0x44], AmqpValue::Ulong(0))?;
+    decodes_as(&[0x53, 0x07], AmqpValue::Ulong(7))?;
+    decodes_as(&[0x80, 0, 0, 0, 0, 0, 0, 0, 0x07], AmqpValue::Ulong(7))
+}
+
+fn timestamp() -> Result<(), String> {
+    let millis: i64 = 1_311_704_463_521;
+    let mut expected = vec![0x83];
+    expected.extend_from_slice(&millis.to_be_bytes());
+    encodes_as(AmqpValue::Timestamp(millis), &expected)
+}
+
+fn uuid() -> Result<(), String> {
+    let bytes: [u8; 16] = *b"0123456789abcdef";
+    let mut data = vec![0x98];
+    data.extend_from_slice(&bytes);
+    match decode(&data)? {
+        AmqpValue::Uuid(uuid) if uuid.as_bytes() == &bytes => Ok(()),
+        other => Err(format!("uuid decoded as {:?}", other)),
+    }
+}
+
+fn string_utf8() -> Result<(), String> {
+    encodes_as(AmqpValue::String("é".to_string()), &[0xa1, 0x02, 0xc3, 0xa9])?;
+    ensure(decode(&[0xa1, 0x01, 0xff]).is_err(), || "invalid UTF-8 string accepted".to_string())
+}
+
+fn symbol_ascii() -> Result<(), String> {
+    encodes_as(AmqpValue::Symbol(AmqpSymbol::from("amqp")), &[0xa3, 0x04, b'a', b'm', b'q', b'p'])?;
+    ensure(encode(&AmqpValue::Symbol(AmqpSymbol::from("é"))).is_err(), || {
+        "non-ASCII symbol encoded".to_string()
+    })
+}
+
+fn list_size_and_count() -> Result<(), String> {
+    encodes_as(AmqpValue::List(AmqpList::new()), &[0x45])?;
+    encodes_as(AmqpValue::List(vec![AmqpValue::Boolean(true)].into()), &[0xc0, 0x02, 0x01, 0x41])
+}
+
+fn map_count() -> Result<(), String> {
+    let mut map = AmqpMap::new();
+    map.insert(AmqpSymbol::from("k"), AmqpValue::Null);
+    encodes_as(AmqpValue::Map(map), &[0xc1, 0x05, 0x02, 0xa3, 0x01, b'k', 0x40])
+}
+
+fn described() -> Result<(), String> {
+    let data = vec![0x00, 0x53, 0x77, 0xa1, 0x01, b'x'];
+    let mut decoder = Decoder::new(data);
+    let decoded = decoder
+        .values()
+        .next()
+        .ok_or("no value decoded")?
+        .map_err(|e| e.to_string())?;
+    ensure(
+        decoded.descriptor == Some(AmqpValue::Ulong(0x77)) && decoded.value == AmqpValue::String("x".to_string()),
+        || format!("described value decoded as {:?}", decoded),
+    )
+}
+
+fn composite_trailing_nulls() -> Result<(), String> {
+    let mut encoder = Encoder::new();
+    encoder
+        .encode_described_list(0x16, &[AmqpValue::Uint(0), AmqpValue::Null, AmqpValue::Null])
+        .map_err(|e| e.to_string())?;
+    let (_, fields) = Decoder::new(encoder.finish()).decode_described_list().map_err(|e| e.to_string())?;
+    ensure(fields == [AmqpValue::Uint(0)], || format!("composite encoded with fields {:?}", fields))?;
+
+    // A peer may leave out trailing fields; they read as null
+    let data = [0x00, 0x53, 0x16, 0xc0, 0x06, 0x01, 0x70, 0x00, 0x00, 0x00, 0x00];
+    let (_, fields) = Decoder::new(data.to_vec()).decode_described_fields(3).map_err(|e| e.to_string())?;
+    ensure(fields == [AmqpValue::Uint(0), AmqpValue::Null, AmqpValue::Null], || {
+        format!("truncated composite decoded as {:?}", fields)
+    })
+}
+
+pub(crate) fn requirements() -> Vec<Requirement> {
+    let requirement = |id, section, statement, expectation, check| Requirement {
+        id,
+        section,
+        statement,
+        area: Area::Types,
+        expectation,
+        check,
+    };
+    vec![
+        requirement("T-01", "1.6.1", "null is encoded as 0x40", Expectation::Pass, null),
+        requirement("T-02", "1.6.2", "boolean has the 0x41/0x42 and 0x56 encodings", Expectation::Pass, boolean),
+        requirement("T-03", "1.6.6", "uint is decoded from its 0x43, 0x52 and 0x70 encodings", Expectation::Pass, uint_encodings),
+        requirement("T-04", "1.6.7", "ulong is decoded from its 0x44, 0x53 and 0x80 encodings", Expectation::Pass, ulong_encodings),
+        requirement("T-05", "1.6.17", "timestamp is a 64-bit count of milliseconds since the epoch, 0x83", Expectation::Pass, timestamp),
+        requirement("T-06", "1.6.18", "uuid is 16 bytes in network order, 0x98", Expectation::Pass, uuid),
+        requirement("T-07", "1.6.20", "string is UTF-8; invalid sequences are rejected", Expectation::Pass, string_utf8),
+        requirement("T-08", "1.6.21", "symbol values are ASCII", Expectation::KnownGap, symbol_ascii),
+        requirement("T-09", "1.6.22", "list8 and list32 carry a size before the count", Expectation::Pass, list_size_and_count),
+        requirement("T-10", "1.6.23", "map count is the number of keys and values together", Expectation::Pass, map_count),
+        requirement("T-11", "1.2", "a described type is 0x00, a descriptor and a value", Expectation::Pass, described),
+        requirement("T-12", "1.4", "trailing null fields of a composite may be omitted and read as null", Expectation::Pass, composite_trailing_nulls),
+    ]
+}
@@ -0,0 +1,74 @@
+//! Queue Browsing Example
+//!
+//! This example browses a queue on a local embedded broker: the receiver asks
+//! for the `copy` distribution mode on its source, so the broker sends copies
+//! of the queued messages and leaves them for other receivers. A consuming
+//! receiver then takes the same messages off the queue.
+//!
+//! It asserts what it observes, so it doubles as an acceptance test.
+//!
+//! Usage:
+//!
+//! ```text
+//! cargo run --example browse --features integration
+//! ```
+
+use dumq_amqp::link::{LinkConfig, TerminusBuilder};
+use dumq_amqp::prelude::*;
+use dumq_amqp::testing::EmbeddedBroker;
+use dumq_amqp::DistributionMode;
+
+const QUEUE: &str = "audit-log";
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Initialize logging
+    env_logger::init();
+
+    println!("dumq_amqp Queue Browsing Example");
+    println!("================================");
+
+    let broker = EmbeddedBroker::start().await?;
+    for entry in ["login alice", "update order-7", "logout alice"] {
+        broker.publish(QUEUE, Message::text(entry));
+    }
+    println!("Queued {} entries on '{}'", broker.queue_depth(QUEUE).unwrap_or(0), QUEUE);
+
+    let mut connection = broker.connection().container_id("browse-example").build();
+    connection.open().await?;
+    let session = connection.create_session().await?;
+
+    let browser = LinkConfig {
+        source: Some(QUEUE.to_string()),
+        source_config: Some(TerminusBuilder::new().distribution_mode(DistributionMode::Copy).build()),
+        ..Default::default()
+    };
+    let mut receiver = session.create_receiver(browser).await?;
+    receiver.attach().await?;
+    receiver.add_credit(10);
+    for _ in 0..3 {
+        let (delivery_id, message) = receiver.next_delivery().await?.ok_or("browser detached")?;
+        println!("  browsed: {}", message.body_as_text().unwrap_or("<binary>"));
+        // Settling a copy does not remove the original
+        receiver.accept(delivery_id)?;
+    }
+    receiver.close().await?;
+    assert_eq!(broker.queue_depth(QUEUE), Some(3));
+    println!("The queue still holds {} entries", broker.queue_depth(QUEUE).unwrap_or(0));
+
+    let consumer = LinkConfig { source: Some(QUEUE.to_string()), ..Default::default() };
+    let mut receiver = session.create_receiver(consumer).await?;
+    receiver.attach().await?;
+    receiver.add_credit(10);
+    for _ in 0..3 {
+        let (delivery_id, message) = receiver.next_delivery().await?.ok_or("consumer detached")?;
+        println!("  consumed: {}", message.body_as_text().unwrap_or("<binary>"));
+        receiver.accept(delivery_id)?;
+    }
+    receiver.close().await?;
+    assert_eq!(broker.queue_depth(QUEUE), Some(0));
+    println!("Consuming emptied the queue");
+
+    connection.close().await?;
+    Ok(())
+}
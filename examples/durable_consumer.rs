@@ -0,0 +1,120 @@
+//! Durable Consumer Example
+//!
+//! This example consumes a queue on a local embedded broker with a durable
+//! source and settles every delivery by hand: well-formed orders are
+//! accepted, orders that cannot be handled yet are released back to the
+//! queue, and malformed ones are rejected. Deliveries still unsettled when
+//! the consumer detaches go back to the queue, so a consumer attaching later
+//! under the same link name picks them up.
+//!
+//! It asserts what it observes, so it doubles as an acceptance test.
+//!
+//! Usage:
+//!
+//! ```text
+//! cargo run --example durable_consumer --features integration
+//! ```
+
+use dumq_amqp::link::{LinkConfig, TerminusBuilder};
+use dumq_amqp::prelude::*;
+use dumq_amqp::testing::EmbeddedBroker;
+use dumq_amqp::{types, TerminusDurability, TerminusExpiryPolicy};
+
+const QUEUE: &str = "orders";
+
+/// Link name the consumer attaches under each time
+const SUBSCRIPTION: &str = "order-processor";
+
+/// What the consumer does with an order
+#[derive(Debug, PartialEq)]
+enum Decision {
+    Accept,
+    Release,
+    Reject(&'static str),
+}
+
+fn decide(order: &str, warehouse_open: bool) -> Decision {
+    match order.strip_prefix("order:") {
+        None | Some("") => Decision::Reject("Not an order"),
+        Some(_) if order.ends_with("-backorder") && !warehouse_open => Decision::Release,
+        Some(_) => Decision::Accept,
+    }
+}
+
+fn durable_source() -> LinkConfig {
+    LinkConfig {
+        name: SUBSCRIPTION.to_string(),
+        source: Some(QUEUE.to_string()),
+        source_config: Some(
+            TerminusBuilder::new()
+                .durability(TerminusDurability::UnsettledState)
+                .expiry_policy(TerminusExpiryPolicy::Never)
+                .build(),
+        ),
+        ..Default::default()
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Initialize logging
+    env_logger::init();
+
+    println!("dumq_amqp Durable Consumer Example");
+    println!("==================================");
+
+    let broker = EmbeddedBroker::start().await?;
+    for order in ["order:1", "order:2-backorder", "garbage", "order:3", "order:4"] {
+        broker.publish(QUEUE, Message::text(order));
+    }
+
+    let mut connection = broker.connection().container_id("durable-consumer-example").build();
+    connection.open().await?;
+    let session = connection.create_session().await?;
+
+    // First run: settle four deliveries by hand, then detach with one unsettled
+    let mut receiver = session.create_receiver(durable_source()).await?;
+    receiver.attach().await?;
+    receiver.add_credit(4);
+    for _ in 0..4 {
+        let (delivery_id, message) = receiver.next_delivery().await?.ok_or("consumer detached")?;
+        let order = message.body_as_text().unwrap_or_default();
+        let decision = decide(order, false);
+        println!("  {:<20} -> {:?}", order, decision);
+        match decision {
+            Decision::Accept => receiver.accept(delivery_id)?,
+            Decision::Release => receiver.release(delivery_id)?,
+            Decision::Reject(reason) => {
+                let error = types::AmqpError::new(AmqpCondition::AmqpErrorDecodeError).with_description(reason);
+                receiver.reject(delivery_id, Some(error))?
+            }
+        }
+    }
+    // The released order is redelivered first, and left unsettled
+    receiver.add_credit(1);
+    let (_, message) = receiver.next_delivery().await?.ok_or("consumer detached")?;
+    println!("  {:<20} -> left unsettled", message.body_as_text().unwrap_or_default());
+    println!("Detaching with {} unsettled delivery", receiver.unsettled_count());
+    receiver.close().await?;
+
+    // Second run: the unsettled and the unread orders are still queued
+    let mut receiver = session.create_receiver(durable_source()).await?;
+    receiver.attach().await?;
+    receiver.add_credit(2);
+    let mut resumed = Vec::new();
+    while resumed.len() < 2 {
+        let (delivery_id, message) = receiver.next_delivery().await?.ok_or("consumer detached")?;
+        let order = message.body_as_text().unwrap_or_default().to_string();
+        assert_eq!(decide(&order, true), Decision::Accept);
+        receiver.accept(delivery_id)?;
+        println!("  {:<20} -> accepted after re-attaching", order);
+        resumed.push(order);
+    }
+    assert_eq!(resumed, ["order:2-backorder", "order:4"]);
+    receiver.close().await?;
+    assert_eq!(broker.queue_depth(QUEUE), Some(0));
+    println!("Every order was settled exactly once");
+
+    connection.close().await?;
+    Ok(())
+}
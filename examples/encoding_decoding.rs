@@ -31,9 +31,9 @@ fn test_basic_values() -> Result<(), Box<dyn std::error::Error>> {
         AmqpValue::String("Hello, AMQP!".to_string()),
         AmqpValue::Int(42),
         AmqpValue::Boolean(true),
-        AmqpValue::Double(3.14159),
-        AmqpValue::Uuid(uuid::Uuid::new_v4()),
-        AmqpValue::Binary(vec![1, 2, 3, 4, 5]),
+        AmqpValue::Double(std::f64::consts::PI),
+        AmqpValue::Uuid(dumq_amqp::types::Uuid::new_v4()),
+        AmqpValue::Binary(vec![1, 2, 3, 4, 5].into()),
     ];
 
     for value in values {
@@ -113,7 +113,7 @@ fn test_multiple_values() -> Result<(), Box<dyn std::error::Error>> {
         AmqpValue::String("First value".to_string()),
         AmqpValue::Int(100),
         AmqpValue::Boolean(false),
-        AmqpValue::Double(2.71828),
+        AmqpValue::Double(std::f64::consts::E),
     ];
 
     println!("  Encoding multiple values...");
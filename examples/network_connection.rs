@@ -76,7 +76,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     println!("\nNetwork connection example completed successfully!");
     println!("In a real scenario with a broker, the connection would be fully functional.");
-    
+
+    network_error_handling_example().await?;
+
     Ok(())
 }
 
@@ -85,13 +87,13 @@ async fn network_error_handling_example() -> Result<(), Box<dyn std::error::Erro
     println!("\nNetwork Error Handling Example");
     println!("==============================");
 
-    let mut connection = NetworkBuilder::new()
+    let connection = NetworkBuilder::new()
         .hostname("invalid-host.example.com")
         .port(5672)
         .timeout(Duration::from_secs(5))
         .build();
 
-    println!("Attempting connection to invalid host...");
+    println!("Attempting connection to {}...", connection.config().hostname);
 
     // Note: We'll skip actual connection attempt to avoid hanging
     println!("(Skipping actual connection attempt to avoid hanging)");
@@ -178,6 +178,7 @@ async fn simulate_publishers() -> Result<(), Box<dyn std::error::Error>> {
     // Connect all publishers
     for publisher in &mut publishers {
         publisher.connect().await?;
+        println!("  Publisher info: {:?}", publisher.get_info());
     }
     
     // Define message batches for each publisher
@@ -0,0 +1,135 @@
+//! RPC over Temporary Queues Example
+//!
+//! This example makes request/response calls through a local embedded
+//! broker. The client attaches a receiver with a dynamic source, so the
+//! broker creates a temporary reply queue and reports its address in the
+//! Attach. Each request names that address in `reply-to` and carries a
+//! `correlation-id`; the server, on its own connection, sends the response to
+//! the reply address with the same correlation id. The temporary queue is
+//! deleted once the client detaches.
+//!
+//! It asserts what it observes, so it doubles as an acceptance test.
+//!
+//! Usage:
+//!
+//! ```text
+//! cargo run --example rpc --features integration
+//! ```
+
+use dumq_amqp::link::{LinkConfig, TerminusBuilder};
+use dumq_amqp::prelude::*;
+use dumq_amqp::testing::EmbeddedBroker;
+use dumq_amqp::AttachOutcome;
+use std::collections::HashMap;
+
+/// Queue the server takes requests from
+const REQUEST_QUEUE: &str = "rpc.uppercase";
+
+/// Serve `count` requests, answering each with its text in upper case
+async fn serve(mut connection: Connection, count: usize) -> AmqpResult<()> {
+    connection.open().await?;
+    let session = connection.create_session().await?;
+    let mut requests = session
+        .create_receiver(LinkConfig { source: Some(REQUEST_QUEUE.to_string()), ..Default::default() })
+        .await?;
+    requests.attach().await?;
+    requests.add_credit(10);
+
+    // One sender per reply address, attached on first use
+    let mut replies: HashMap<String, Sender> = HashMap::new();
+    for _ in 0..count {
+        let Some((delivery_id, request)) = requests.next_delivery().await? else {
+            break;
+        };
+        let properties = request.properties.clone().unwrap_or_default();
+        let Some(reply_to) = properties.reply_to else {
+            requests.reject(delivery_id, None)?;
+            continue;
+        };
+        if !replies.contains_key(&reply_to) {
+            let mut sender =
+                session.create_sender(LinkConfig { target: Some(reply_to.clone()), ..Default::default() }).await?;
+            sender.attach().await?;
+            while sender.credit() == 0 {
+                sender.wait_for_peer().await?;
+            }
+            replies.insert(reply_to.clone(), sender);
+        }
+        let response = Message::builder()
+            .properties(Properties { correlation_id: properties.correlation_id, ..Default::default() })
+            .body(Body::Value(AmqpValue::String(request.body_as_text().unwrap_or_default().to_uppercase())))
+            .build();
+        let sender = replies.get_mut(&reply_to).expect("reply sender attached above");
+        let delivery = sender.send(response).await?;
+        sender.outcome(delivery).await?;
+        requests.accept(delivery_id)?;
+    }
+
+    for (_, sender) in replies {
+        sender.close().await?;
+    }
+    requests.close().await?;
+    connection.close().await
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Initialize logging
+    env_logger::init();
+
+    println!("dumq_amqp RPC over Temporary Queues Example");
+    println!("===========================================");
+
+    let calls = ["hello", "temporary", "queues"];
+    let broker = EmbeddedBroker::start().await?;
+    let server = tokio::spawn(serve(broker.connection().container_id("rpc-server").build(), calls.len()));
+
+    let mut connection = broker.connection().container_id("rpc-client").build();
+    connection.open().await?;
+    let session = connection.create_session().await?;
+
+    // The broker picks the reply queue's address and reports it in its Attach
+    let dynamic = LinkConfig { source_config: Some(TerminusBuilder::new().dynamic().build()), ..Default::default() };
+    let mut responses = session.create_receiver(dynamic).await?;
+    let reply_to = match responses.attach().await? {
+        AttachOutcome::Attached { remote_source: Some(source), .. } => source.address.ok_or("no reply address")?,
+        other => return Err(format!("Reply queue not created: {:?}", other).into()),
+    };
+    println!("Replies go to temporary queue '{}'", reply_to);
+    responses.add_credit(calls.len() as u32);
+
+    let mut requests =
+        session.create_sender(LinkConfig { target: Some(REQUEST_QUEUE.to_string()), ..Default::default() }).await?;
+    requests.attach().await?;
+    while requests.credit() == 0 {
+        requests.wait_for_peer().await?;
+    }
+
+    for (i, call) in calls.iter().enumerate() {
+        let correlation_id = format!("call-{}", i);
+        let request = Message::builder()
+            .properties(Properties {
+                reply_to: Some(reply_to.clone()),
+                correlation_id: Some(AmqpValue::String(correlation_id.clone())),
+                ..Default::default()
+            })
+            .body(Body::Value(AmqpValue::String(call.to_string())))
+            .build();
+        let delivery = requests.send(request).await?;
+        requests.outcome(delivery).await?;
+
+        let (delivery_id, response) = responses.next_delivery().await?.ok_or("reply queue detached")?;
+        responses.accept(delivery_id)?;
+        let correlated = response.properties.as_ref().and_then(|p| p.correlation_id.clone());
+        println!("  {} -> {} ({:?})", call, response.body_as_text().unwrap_or_default(), correlated);
+        assert_eq!(correlated, Some(AmqpValue::String(correlation_id)));
+        assert_eq!(response.body_as_text(), Some(call.to_uppercase().as_str()));
+    }
+
+    server.await??;
+    requests.close().await?;
+    responses.close().await?;
+    connection.close().await?;
+    println!("Answered {} calls; the reply queue goes away with the client's link", calls.len());
+    Ok(())
+}
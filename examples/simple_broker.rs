@@ -3,8 +3,13 @@
 //! This example demonstrates a basic AMQP broker implementation using dumq_amqp.
 //! It includes message queuing, connection management, and message routing with actual network connections.
 
+// Parts of the broker API are only exercised by readers extending the example.
+#![allow(dead_code)]
+
 use dumq_amqp::prelude::*;
-use std::collections::HashMap;
+use dumq_amqp::quota::{ConnectionUsage, Quotas};
+use dumq_amqp::scheduler::CreditScheduler;
+use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::time::{sleep, Duration};
@@ -22,44 +27,53 @@ struct QueueMessage {
 #[derive(Debug)]
 struct Queue {
     name: String,
-    messages: Vec<QueueMessage>,
-    consumers: Vec<String>, // Consumer IDs
+    messages: VecDeque<QueueMessage>,
+    consumers: CreditScheduler<String>, // Credit granted by each consumer
 }
 
 impl Queue {
     fn new(name: String) -> Self {
         Queue {
             name,
-            messages: Vec::new(),
-            consumers: Vec::new(),
+            messages: VecDeque::new(),
+            consumers: CreditScheduler::new(),
         }
     }
 
     fn publish(&mut self, message: QueueMessage) {
         println!("  [Queue '{}'] Publishing message: {}", self.name, message.id);
-        self.messages.push(message);
+        self.messages.push_back(message);
+    }
+
+    /// Record the credit a consumer granted with a Flow
+    fn grant_credit(&mut self, consumer_id: &str, credit: u32, drain: bool) -> Result<(), String> {
+        self.consumers
+            .grant(&consumer_id.to_string(), credit, drain)
+            .map_err(|e| e.to_string())
     }
 
-    fn consume(&mut self, consumer_id: &str) -> Option<QueueMessage> {
-        if let Some(message) = self.messages.pop() {
-            println!("  [Queue '{}'] Consuming message: {} by consumer: {}", 
+    /// Hand out queued messages to the consumers that have credit
+    fn dispatch(&mut self) -> Vec<(String, QueueMessage)> {
+        let dispatched = self.consumers.dispatch(&mut self.messages);
+        for (consumer_id, message) in &dispatched.deliveries {
+            println!("  [Queue '{}'] Consuming message: {} by consumer: {}",
                      self.name, message.id, consumer_id);
-            Some(message)
-        } else {
-            None
         }
+        for drained in &dispatched.drained {
+            println!("  [Queue '{}'] Drained consumer: {} (delivery-count {})",
+                     self.name, drained.consumer, drained.delivery_count);
+        }
+        dispatched.deliveries
     }
 
     fn add_consumer(&mut self, consumer_id: String) {
-        if !self.consumers.contains(&consumer_id) {
-            let consumer_id_clone = consumer_id.clone();
-            self.consumers.push(consumer_id);
-            println!("  [Queue '{}'] Added consumer: {}", self.name, consumer_id_clone);
+        if self.consumers.add_consumer(consumer_id.clone()) {
+            println!("  [Queue '{}'] Added consumer: {}", self.name, consumer_id);
         }
     }
 
     fn remove_consumer(&mut self, consumer_id: &str) {
-        self.consumers.retain(|id| id != consumer_id);
+        self.consumers.remove_consumer(&consumer_id.to_string());
         println!("  [Queue '{}'] Removed consumer: {}", self.name, consumer_id);
     }
 }
@@ -71,6 +85,7 @@ struct SimpleBroker {
     connections: HashMap<String, ConnectionInfo>,
     listener: Option<TcpListener>,
     broker_port: u16,
+    quotas: Quotas,
 }
 
 #[derive(Debug)]
@@ -79,17 +94,55 @@ struct ConnectionInfo {
     hostname: String,
     port: u16,
     connected_at: std::time::SystemTime,
+    usage: ConnectionUsage,
 }
 
-impl SimpleBroker {
+/// Builder for the broker, with the quotas it enforces on clients
+struct BrokerBuilder {
+    port: u16,
+    quotas: Quotas,
+}
+
+impl BrokerBuilder {
     fn new(port: u16) -> Self {
+        BrokerBuilder { port, quotas: Quotas::new() }
+    }
+
+    fn max_queue_depth(mut self, max_depth: usize) -> Self {
+        self.quotas = self.quotas.max_queue_depth(max_depth);
+        self
+    }
+
+    fn max_message_size(mut self, max_message_size: u64) -> Self {
+        self.quotas = self.quotas.max_message_size(max_message_size);
+        self
+    }
+
+    fn max_links(mut self, max_links: usize) -> Self {
+        self.quotas = self.quotas.max_links(max_links);
+        self
+    }
+
+    fn max_unsettled(mut self, max_unsettled: usize) -> Self {
+        self.quotas = self.quotas.max_unsettled(max_unsettled);
+        self
+    }
+
+    fn build(self) -> SimpleBroker {
         SimpleBroker {
             queues: HashMap::new(),
             connections: HashMap::new(),
             listener: None,
-            broker_port: port,
+            broker_port: self.port,
+            quotas: self.quotas,
         }
     }
+}
+
+impl SimpleBroker {
+    fn new(port: u16) -> Self {
+        BrokerBuilder::new(port).build()
+    }
 
     /// Start the broker server
     async fn start(&mut self) -> Result<(), Box<dyn std::error::Error>> {
@@ -128,8 +181,8 @@ impl SimpleBroker {
     }
 
     /// Handle a client connection (static method to avoid borrow checker issues)
-    async fn handle_connection_task(socket: TcpStream, addr: SocketAddr) -> Result<(), Box<dyn std::error::Error>> {
-        let connection_id = format!("conn-{}", uuid::Uuid::new_v4().to_string()[..8].to_string());
+    async fn handle_connection_task(_socket: TcpStream, _addr: SocketAddr) -> Result<(), Box<dyn std::error::Error>> {
+        let connection_id = format!("conn-{}", &dumq_amqp::types::Uuid::new_v4().to_string()[..8]);
         println!("[Broker] Handling connection: {}", connection_id);
         
         // For now, just simulate handling the connection
@@ -152,7 +205,7 @@ impl SimpleBroker {
     }
 
     fn delete_queue(&mut self, name: &str) -> Result<(), String> {
-        if let Some(_) = self.queues.remove(name) {
+        if self.queues.remove(name).is_some() {
             println!("[Broker] Deleted queue: {}", name);
             Ok(())
         } else {
@@ -162,6 +215,10 @@ impl SimpleBroker {
 
     fn publish_message(&mut self, queue_name: &str, message: QueueMessage) -> Result<(), String> {
         if let Some(queue) = self.queues.get_mut(queue_name) {
+            // Refused with amqp:resource:limit-exceeded or amqp:link:message-size-exceeded
+            self.quotas
+                .check_publish(queue_name, queue.messages.len(), message.body.len() as u64)
+                .map_err(|e| e.to_string())?;
             queue.publish(message);
             Ok(())
         } else {
@@ -169,9 +226,9 @@ impl SimpleBroker {
         }
     }
 
-    fn consume_message(&mut self, queue_name: &str, consumer_id: &str) -> Result<Option<QueueMessage>, String> {
+    fn dispatch_messages(&mut self, queue_name: &str) -> Result<Vec<(String, QueueMessage)>, String> {
         if let Some(queue) = self.queues.get_mut(queue_name) {
-            Ok(queue.consume(consumer_id))
+            Ok(queue.dispatch())
         } else {
             Err(format!("Queue '{}' not found", queue_name))
         }
@@ -183,14 +240,22 @@ impl SimpleBroker {
             hostname,
             port,
             connected_at: std::time::SystemTime::now(),
+            usage: self.quotas.connection_usage(),
         };
         
         self.connections.insert(connection_id.clone(), connection_info);
         println!("[Broker] Added connection: {}", connection_id);
     }
 
+    /// Count a link a client attaches, refusing it at the connection's limit
+    fn attach_link(&mut self, connection_id: &str) -> Result<(), String> {
+        let connection = self.connections.get_mut(connection_id)
+            .ok_or_else(|| format!("Connection '{}' not found", connection_id))?;
+        connection.usage.attach_link().map_err(|e| e.to_string())
+    }
+
     fn remove_connection(&mut self, connection_id: &str) {
-        if let Some(_) = self.connections.remove(connection_id) {
+        if self.connections.remove(connection_id).is_some() {
             println!("[Broker] Removed connection: {}", connection_id);
         }
     }
@@ -219,8 +284,13 @@ impl SimpleBroker {
 async fn simulate_broker_operations() -> Result<(), Box<dyn std::error::Error>> {
     println!("Simulating broker operations...");
     
-    // Create broker
-    let mut broker = SimpleBroker::new(5672);
+    // Create broker, limited so untrusted clients cannot exhaust it
+    let mut broker = BrokerBuilder::new(5672)
+        .max_queue_depth(1000)
+        .max_message_size(64 * 1024)
+        .max_links(2)
+        .max_unsettled(100)
+        .build();
     
     // Create some queues
     broker.create_queue("orders".to_string())?;
@@ -236,6 +306,31 @@ async fn simulate_broker_operations() -> Result<(), Box<dyn std::error::Error>>
     };
     
     broker.publish_message("orders", test_message)?;
+
+    // Quotas are enforced with the matching AMQP conditions
+    let oversized = QueueMessage {
+        id: "msg-002".to_string(),
+        body: "x".repeat(128 * 1024),
+        properties: None,
+        timestamp: std::time::SystemTime::now(),
+    };
+    if let Err(e) = broker.publish_message("orders", oversized) {
+        println!("Refused: {}", e);
+    }
+    broker.add_connection("conn-1".to_string(), "client.local".to_string(), 40000);
+    for _ in 0..3 {
+        if let Err(e) = broker.attach_link("conn-1") {
+            println!("Refused: {}", e);
+        }
+    }
+
+    // Deliver to a consumer once it has granted credit
+    if let Some(queue) = broker.queues.get_mut("orders") {
+        queue.add_consumer("consumer-1".to_string());
+        queue.grant_credit("consumer-1", 10, false)?;
+    }
+    let deliveries = broker.dispatch_messages("orders")?;
+    println!("Dispatched {} message(s)", deliveries.len());
     
     // List queues
     println!("Queues: {:?}", broker.list_queues());
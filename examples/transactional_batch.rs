@@ -0,0 +1,70 @@
+//! Transactional Batch Send Example
+//!
+//! This example sends batches of messages within local transactions on a
+//! local embedded broker. The broker holds a batch until its transaction
+//! commits, so receivers never see part of one; a rolled back batch is
+//! dropped as a whole.
+//!
+//! It asserts what it observes, so it doubles as an acceptance test.
+//!
+//! Usage:
+//!
+//! ```text
+//! cargo run --example transactional_batch --features integration
+//! ```
+
+use dumq_amqp::link::{DeliveryOutcome, LinkConfig};
+use dumq_amqp::prelude::*;
+use dumq_amqp::testing::EmbeddedBroker;
+
+const QUEUE: &str = "ledger";
+
+/// Messages sent within each transaction
+const BATCH_SIZE: usize = 5;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Initialize logging
+    env_logger::init();
+
+    println!("dumq_amqp Transactional Batch Send Example");
+    println!("==========================================");
+
+    let broker = EmbeddedBroker::start().await?;
+    let mut connection = broker.connection().container_id("transactional-batch-example").build();
+    connection.open().await?;
+    let session = connection.create_session().await?;
+
+    let mut sender = session.create_sender(LinkConfig { target: Some(QUEUE.to_string()), ..Default::default() }).await?;
+    sender.attach().await?;
+    // Credit arrives with the broker's Flow, shortly after its Attach
+    while sender.credit() == 0 {
+        sender.wait_for_peer().await?;
+    }
+
+    for (batch, commit) in [(1, true), (2, false), (3, true)] {
+        let txn = session.begin_transaction().await?;
+        for i in 0..BATCH_SIZE {
+            let message = Message::text(format!("batch-{} entry-{}", batch, i));
+            let delivery = sender.send_in_txn(&txn, message).await?;
+            assert_eq!(sender.outcome(delivery).await?, DeliveryOutcome::Accepted);
+        }
+        let depth = broker.queue_depth(QUEUE).unwrap_or(0);
+        if commit {
+            txn.commit().await?;
+        } else {
+            txn.rollback().await?;
+        }
+        let queued = broker.queue_depth(QUEUE).unwrap_or(0) - depth;
+        println!("Batch {}: sent {}, {}, {} queued", batch, BATCH_SIZE, if commit { "committed" } else { "rolled back" }, queued);
+        assert_eq!(queued, if commit { BATCH_SIZE } else { 0 });
+    }
+
+    assert_eq!(broker.queue_depth(QUEUE), Some(2 * BATCH_SIZE));
+    assert_eq!(broker.open_transactions(), 0);
+    println!("'{}' holds the {} messages of the committed batches", QUEUE, 2 * BATCH_SIZE);
+
+    sender.close().await?;
+    connection.close().await?;
+    Ok(())
+}
@@ -0,0 +1,292 @@
+//! AMQP 1.0 Adaptive Credit
+//!
+//! This module sizes a receiver's credit window from what it observes
+//! instead of a fixed prefetch. An [`AdaptiveCredit`] controller measures
+//! how fast the application takes messages from the receiver and how long
+//! the peer takes to answer a credit grant with a transfer, and adjusts the
+//! window once per interval, AIMD-style:
+//!
+//! - if messages waited in the local buffer longer than the target, the
+//!   consumer is slower than the window assumes and the window is cut by
+//!   the decrease factor
+//! - if the application asked for a message while none was buffered, the
+//!   consumer was starved and the window grows by the increase step
+//!
+//! The window never shrinks below the processing rate times the round-trip
+//! time, the credit needed to keep a consumer of that speed busy, and stays
+//! within the configured bounds. Every decision is counted in
+//! [`AdaptiveCreditStats`] and logged at debug level.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::adaptive::AdaptiveCreditConfig;
+//! use dumq_amqp::link::LinkBuilder;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut receiver = LinkBuilder::new()
+//!     .source("orders")
+//!     .adaptive_credit(AdaptiveCreditConfig { max_window: 500, ..Default::default() })
+//!     .build_receiver("session-1".to_string());
+//! receiver.attach().await?;
+//!
+//! // Attaching grants the initial window
+//! assert_eq!(receiver.credit(), 10);
+//! let stats = receiver.adaptive_credit().unwrap().stats();
+//! println!("window {} at {:.1} msg/s", stats.window, stats.rate);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::logging;
+use tokio::time::{Duration, Instant};
+
+/// Weight of a new round-trip sample in the smoothed round-trip time
+const ROUND_TRIP_GAIN: f64 = 0.125;
+
+/// Bounds and steps of an adaptive credit window
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdaptiveCreditConfig {
+    /// Window granted before anything has been measured
+    pub initial_window: u32,
+    /// Smallest window
+    pub min_window: u32,
+    /// Largest window
+    pub max_window: u32,
+    /// Credit added to the window after an interval with a starved consumer
+    pub increase: u32,
+    /// Factor the window is multiplied by after an interval of long waits
+    pub decrease_factor: f64,
+    /// Longest a message should wait in the local buffer
+    pub target_wait: Duration,
+    /// Time between adjustments
+    pub interval: Duration,
+}
+
+impl Default for AdaptiveCreditConfig {
+    fn default() -> Self {
+        AdaptiveCreditConfig {
+            initial_window: 10,
+            min_window: 1,
+            max_window: 1000,
+            increase: 5,
+            decrease_factor: 0.5,
+            target_wait: Duration::from_millis(100),
+            interval: Duration::from_millis(250),
+        }
+    }
+}
+
+/// What the controller did with the window at the end of an interval
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CreditDecision {
+    /// The window was left as it was
+    #[default]
+    Hold,
+    /// The consumer was starved and the window grew
+    Increase,
+    /// Messages waited too long and the window shrank
+    Decrease,
+}
+
+/// Measurements and decisions of an adaptive credit controller
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AdaptiveCreditStats {
+    /// Current window
+    pub window: u32,
+    /// Messages taken by the application per second, over the last interval
+    pub rate: f64,
+    /// Smoothed time from a credit grant to the next transfer
+    pub round_trip: Option<Duration>,
+    /// Mean time messages waited in the local buffer, over the last interval
+    pub queue_wait: Option<Duration>,
+    /// Decision taken at the end of the last interval
+    pub last_decision: CreditDecision,
+    /// Number of times the window grew
+    pub increases: u64,
+    /// Number of times the window shrank
+    pub decreases: u64,
+}
+
+/// Credit window controller for one receiver
+#[derive(Debug, Clone)]
+pub struct AdaptiveCredit {
+    config: AdaptiveCreditConfig,
+    stats: AdaptiveCreditStats,
+    interval_start: Instant,
+    processed: u32,
+    waited: Duration,
+    starved: bool,
+    /// When credit was granted, until a transfer answers it
+    granted_at: Option<Instant>,
+}
+
+impl AdaptiveCredit {
+    /// Create a controller starting at the initial window
+    pub fn new(config: AdaptiveCreditConfig) -> Self {
+        let window = config.initial_window.clamp(config.min_window, config.max_window);
+        AdaptiveCredit {
+            stats: AdaptiveCreditStats { window, ..Default::default() },
+            config,
+            interval_start: Instant::now(),
+            processed: 0,
+            waited: Duration::ZERO,
+            starved: false,
+            granted_at: None,
+        }
+    }
+
+    /// Get the configuration
+    pub fn config(&self) -> &AdaptiveCreditConfig {
+        &self.config
+    }
+
+    /// Get the current window
+    pub fn window(&self) -> u32 {
+        self.stats.window
+    }
+
+    /// Get the measurements and decisions so far
+    pub fn stats(&self) -> &AdaptiveCreditStats {
+        &self.stats
+    }
+
+    /// Record credit granted to the peer
+    pub fn on_grant(&mut self, now: Instant) {
+        self.granted_at.get_or_insert(now);
+    }
+
+    /// Record a transfer arriving from the peer
+    pub fn on_arrival(&mut self, now: Instant) {
+        if let Some(granted_at) = self.granted_at.take() {
+            let sample = now.saturating_duration_since(granted_at);
+            self.stats.round_trip = Some(match self.stats.round_trip {
+                Some(smoothed) => smoothed.mul_f64(1.0 - ROUND_TRIP_GAIN) + sample.mul_f64(ROUND_TRIP_GAIN),
+                None => sample,
+            });
+        }
+    }
+
+    /// Record a message taken by the application after waiting in the buffer
+    pub fn on_received(&mut self, waited: Duration) {
+        self.processed += 1;
+        self.waited += waited;
+    }
+
+    /// Record the application asking for a message while none was buffered
+    pub fn on_starved(&mut self) {
+        self.starved = true;
+    }
+
+    /// Adjust the window if an interval has passed, returning the new window
+    pub fn adjust(&mut self, now: Instant) -> Option<u32> {
+        let elapsed = now.saturating_duration_since(self.interval_start);
+        if elapsed < self.config.interval {
+            return None;
+        }
+
+        self.stats.rate = f64::from(self.processed) / elapsed.as_secs_f64();
+        self.stats.queue_wait = (self.processed > 0).then(|| self.waited / self.processed);
+        let floor = self
+            .stats
+            .round_trip
+            .map_or(0, |round_trip| (self.stats.rate * round_trip.as_secs_f64()).ceil() as u32);
+
+        let previous = self.stats.window;
+        let mut window = previous;
+        if self.stats.queue_wait.is_some_and(|wait| wait > self.config.target_wait) {
+            window = ((f64::from(window) * self.config.decrease_factor) as u32).max(floor);
+        } else if self.starved {
+            window = window.saturating_add(self.config.increase);
+        }
+        window = window.clamp(self.config.min_window, self.config.max_window);
+
+        self.stats.last_decision = match window.cmp(&previous) {
+            std::cmp::Ordering::Greater => {
+                self.stats.increases += 1;
+                CreditDecision::Increase
+            }
+            std::cmp::Ordering::Less => {
+                self.stats.decreases += 1;
+                CreditDecision::Decrease
+            }
+            std::cmp::Ordering::Equal => CreditDecision::Hold,
+        };
+        self.stats.window = window;
+        self.interval_start = now;
+        self.processed = 0;
+        self.waited = Duration::ZERO;
+        self.starved = false;
+
+        if window == previous {
+            return None;
+        }
+        logging::debug!(
+            "Credit window {} -> {} ({:.1} msg/s, round trip {:?}, queue wait {:?})",
+            previous,
+            window,
+            self.stats.rate,
+            self.stats.round_trip,
+            self.stats.queue_wait
+        );
+        Some(window)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AdaptiveCreditConfig {
+        AdaptiveCreditConfig {
+            initial_window: 10,
+            min_window: 2,
+            max_window: 12,
+            interval: Duration::from_millis(100),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_window_grows_when_starved_and_halves_when_backed_up() {
+        let start = Instant::now();
+        let mut credit = AdaptiveCredit::new(config());
+        credit.interval_start = start;
+
+        credit.on_starved();
+        assert_eq!(credit.adjust(start + Duration::from_millis(50)), None);
+        assert_eq!(credit.adjust(start + Duration::from_millis(100)), Some(12));
+        assert_eq!(credit.stats().last_decision, CreditDecision::Increase);
+
+        // Already at the maximum
+        credit.on_starved();
+        assert_eq!(credit.adjust(start + Duration::from_millis(200)), None);
+        assert_eq!(credit.stats().last_decision, CreditDecision::Hold);
+
+        credit.on_received(Duration::from_millis(300));
+        assert_eq!(credit.adjust(start + Duration::from_millis(300)), Some(6));
+        assert_eq!(credit.stats().queue_wait, Some(Duration::from_millis(300)));
+        assert_eq!((credit.stats().increases, credit.stats().decreases), (1, 1));
+    }
+
+    #[test]
+    fn test_decrease_keeps_the_bandwidth_delay_product() {
+        let start = Instant::now();
+        let mut credit = AdaptiveCredit::new(config());
+        credit.interval_start = start;
+
+        credit.on_grant(start);
+        credit.on_arrival(start + Duration::from_secs(1));
+        assert_eq!(credit.stats().round_trip, Some(Duration::from_secs(1)));
+
+        // 8 msg/s with a one second round trip needs 8 credits in flight,
+        // more than half the window
+        for _ in 0..8 {
+            credit.on_received(Duration::from_millis(500));
+        }
+        assert_eq!(credit.adjust(start + Duration::from_secs(1)), Some(8));
+        assert_eq!(credit.stats().rate, 8.0);
+        assert_eq!(credit.stats().last_decision, CreditDecision::Decrease);
+    }
+}
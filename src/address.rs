@@ -0,0 +1,297 @@
+//! AMQP 1.0 Node Addresses
+//!
+//! This module parses the address strings brokers use for their nodes into a
+//! structured [`Address`], and renders addresses back in the form a given
+//! broker expects:
+//!
+//! - **Generic**: `queue://orders`, `topic://prices` (ActiveMQ, Artemis, Qpid)
+//! - **RabbitMQ**: `/queues/orders`, `/exchanges/amq.topic/prices`, with
+//!   percent-encoded segments
+//! - **Service Bus**: `orders`, `prices/Subscriptions/audit`
+//!
+//! Anything else is kept as a plain [`Address::Node`]. Addresses convert into
+//! `String`, so they can be given to [`LinkBuilder`] directly.
+//!
+//! [`LinkBuilder`]: crate::link::LinkBuilder
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::address::{Address, AddressStyle};
+//! use dumq_amqp::LinkBuilder;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let address = Address::parse("queue://orders")?;
+//! assert_eq!(address, Address::Queue("orders".to_string()));
+//! assert_eq!(address.render(AddressStyle::RabbitMq)?, "/queues/orders");
+//!
+//! let subscription = Address::parse("prices/Subscriptions/audit")?;
+//! assert_eq!(subscription.name(), "audit");
+//!
+//! let receiver = LinkBuilder::new()
+//!     .source(address.render(AddressStyle::RabbitMq)?)
+//!     .build_receiver("session-1".to_string());
+//! assert_eq!(receiver.config().source.as_deref(), Some("/queues/orders"));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{AmqpCondition, AmqpError, AmqpResult};
+use std::fmt;
+use std::str::FromStr;
+
+/// Exchange RabbitMQ routes topic addresses through
+pub const RABBITMQ_TOPIC_EXCHANGE: &str = "amq.topic";
+
+/// Address form expected by a broker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AddressStyle {
+    /// `queue://` and `topic://` prefixes
+    #[default]
+    Generic,
+    /// RabbitMQ 4 `/queues/` and `/exchanges/` paths
+    RabbitMq,
+    /// Azure Service Bus entity paths
+    ServiceBus,
+}
+
+/// Structured node address
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Address {
+    /// A queue
+    Queue(String),
+    /// A topic
+    Topic(String),
+    /// A RabbitMQ exchange, optionally with a routing key
+    Exchange {
+        /// Exchange name
+        exchange: String,
+        /// Routing key
+        routing_key: Option<String>,
+    },
+    /// A Service Bus topic subscription
+    Subscription {
+        /// Topic name
+        topic: String,
+        /// Subscription name
+        subscription: String,
+    },
+    /// Any other address, kept as given
+    Node(String),
+}
+
+impl Address {
+    /// Parse an address, recognising the prefixes of common brokers
+    pub fn parse(address: &str) -> AmqpResult<Self> {
+        if address.is_empty() {
+            return Err(invalid("Address is empty".to_string()));
+        }
+
+        if let Some(name) = strip_scheme(address, "queue://") {
+            return Ok(Address::Queue(non_empty(name, address)?.to_string()));
+        }
+        if let Some(name) = strip_scheme(address, "topic://") {
+            return Ok(Address::Topic(non_empty(name, address)?.to_string()));
+        }
+
+        if let Some(path) = address.strip_prefix("/queues/") {
+            return match path.split('/').collect::<Vec<_>>().as_slice() {
+                [queue] => Ok(Address::Queue(percent_decode(non_empty(queue, address)?)?)),
+                _ => Err(invalid(format!("Queue address '{}' has extra segments", address))),
+            };
+        }
+        if let Some(path) = address.strip_prefix("/exchanges/") {
+            return match path.split('/').collect::<Vec<_>>().as_slice() {
+                [exchange] => Ok(Address::Exchange {
+                    exchange: percent_decode(non_empty(exchange, address)?)?,
+                    routing_key: None,
+                }),
+                [exchange, routing_key] => Ok(Address::Exchange {
+                    exchange: percent_decode(non_empty(exchange, address)?)?,
+                    routing_key: Some(percent_decode(routing_key)?),
+                }),
+                _ => Err(invalid(format!("Exchange address '{}' has extra segments", address))),
+            };
+        }
+
+        if let [topic, marker, subscription] = address.split('/').collect::<Vec<_>>().as_slice() {
+            if marker.eq_ignore_ascii_case("subscriptions") {
+                return Ok(Address::Subscription {
+                    topic: non_empty(topic, address)?.to_string(),
+                    subscription: non_empty(subscription, address)?.to_string(),
+                });
+            }
+        }
+
+        Ok(Address::Node(address.to_string()))
+    }
+
+    /// Get the name of the queue, topic, exchange, subscription or node
+    pub fn name(&self) -> &str {
+        match self {
+            Address::Queue(name) | Address::Topic(name) | Address::Node(name) => name,
+            Address::Exchange { exchange, .. } => exchange,
+            Address::Subscription { subscription, .. } => subscription,
+        }
+    }
+
+    /// Render the address in the form a broker expects
+    ///
+    /// Fails for addresses the broker has no form for, such as exchanges on
+    /// Service Bus.
+    pub fn render(&self, style: AddressStyle) -> AmqpResult<String> {
+        let unsupported = || {
+            Err(AmqpError::not_implemented(format!("Address '{}' has no {:?} form", self, style)))
+        };
+        match (style, self) {
+            (AddressStyle::Generic, address) => Ok(address.to_string()),
+            (_, Address::Node(name)) => Ok(name.clone()),
+
+            (AddressStyle::RabbitMq, Address::Queue(name)) => Ok(format!("/queues/{}", percent_encode(name))),
+            (AddressStyle::RabbitMq, Address::Topic(name)) => Ok(format!(
+                "/exchanges/{}/{}",
+                RABBITMQ_TOPIC_EXCHANGE,
+                percent_encode(name)
+            )),
+            (AddressStyle::RabbitMq, address @ Address::Exchange { .. }) => Ok(address.to_string()),
+            (AddressStyle::RabbitMq, Address::Subscription { .. }) => unsupported(),
+
+            (AddressStyle::ServiceBus, Address::Queue(name) | Address::Topic(name)) => Ok(name.clone()),
+            (AddressStyle::ServiceBus, address @ Address::Subscription { .. }) => Ok(address.to_string()),
+            (AddressStyle::ServiceBus, Address::Exchange { .. }) => unsupported(),
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Address::Queue(name) => write!(f, "queue://{}", name),
+            Address::Topic(name) => write!(f, "topic://{}", name),
+            Address::Exchange { exchange, routing_key: None } => write!(f, "/exchanges/{}", percent_encode(exchange)),
+            Address::Exchange { exchange, routing_key: Some(routing_key) } => write!(
+                f,
+                "/exchanges/{}/{}",
+                percent_encode(exchange),
+                percent_encode(routing_key)
+            ),
+            Address::Subscription { topic, subscription } => write!(f, "{}/Subscriptions/{}", topic, subscription),
+            Address::Node(name) => f.write_str(name),
+        }
+    }
+}
+
+impl FromStr for Address {
+    type Err = AmqpError;
+
+    fn from_str(address: &str) -> AmqpResult<Self> {
+        Address::parse(address)
+    }
+}
+
+impl From<Address> for String {
+    fn from(address: Address) -> Self {
+        address.to_string()
+    }
+}
+
+impl From<&Address> for String {
+    fn from(address: &Address) -> Self {
+        address.to_string()
+    }
+}
+
+fn invalid(message: String) -> AmqpError {
+    AmqpError::amqp_protocol(AmqpCondition::AmqpErrorInvalidField, message)
+}
+
+fn strip_scheme<'a>(address: &'a str, scheme: &str) -> Option<&'a str> {
+    let prefix = address.get(..scheme.len())?;
+    prefix.eq_ignore_ascii_case(scheme).then(|| &address[scheme.len()..])
+}
+
+fn non_empty<'a>(name: &'a str, address: &str) -> AmqpResult<&'a str> {
+    if name.is_empty() {
+        return Err(invalid(format!("Address '{}' has an empty name", address)));
+    }
+    Ok(name)
+}
+
+/// Percent-encode everything but RFC 3986 unreserved characters
+fn percent_encode(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn percent_decode(segment: &str) -> AmqpResult<String> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            let byte = segment
+                .get(index + 1..index + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| invalid(format!("Invalid percent-encoding in '{}'", segment)))?;
+            decoded.push(byte);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| invalid(format!("Address segment '{}' is not UTF-8", segment)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_prefixes() {
+        assert_eq!(Address::parse("queue://orders").unwrap(), Address::Queue("orders".to_string()));
+        assert_eq!(Address::parse("TOPIC://prices").unwrap(), Address::Topic("prices".to_string()));
+        assert_eq!(Address::parse("/queues/my%20queue").unwrap(), Address::Queue("my queue".to_string()));
+        assert_eq!(
+            Address::parse("/exchanges/amq.direct/eu.orders").unwrap(),
+            Address::Exchange { exchange: "amq.direct".to_string(), routing_key: Some("eu.orders".to_string()) }
+        );
+        assert_eq!(
+            "prices/subscriptions/audit".parse::<Address>().unwrap(),
+            Address::Subscription { topic: "prices".to_string(), subscription: "audit".to_string() }
+        );
+        assert_eq!(Address::parse("plain/node").unwrap(), Address::Node("plain/node".to_string()));
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed() {
+        for address in ["", "queue://", "/queues/a/b", "/exchanges//rk", "/queues/%zz", "prices/Subscriptions/"] {
+            let error = Address::parse(address).unwrap_err();
+            assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorInvalidField), "{}", address);
+        }
+    }
+
+    #[test]
+    fn test_render_styles() {
+        let topic = Address::Topic("eu/prices".to_string());
+        assert_eq!(topic.render(AddressStyle::Generic).unwrap(), "topic://eu/prices");
+        assert_eq!(topic.render(AddressStyle::RabbitMq).unwrap(), "/exchanges/amq.topic/eu%2Fprices");
+        assert_eq!(topic.render(AddressStyle::ServiceBus).unwrap(), "eu/prices");
+
+        let exchange = Address::Exchange { exchange: "logs".to_string(), routing_key: None };
+        assert!(matches!(exchange.render(AddressStyle::ServiceBus), Err(AmqpError::NotImplemented(_))));
+        assert_eq!(String::from(&exchange), "/exchanges/logs");
+
+        // Rendering and parsing round-trip
+        for address in [topic, exchange, Address::parse("prices/Subscriptions/audit").unwrap()] {
+            assert_eq!(Address::parse(&address.to_string()).unwrap(), address);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Allocation counting for tests
+//!
+//! Installs a global allocator that forwards to the system allocator and
+//! counts allocations per thread. Tests run on their own threads, so a test
+//! sees only its own allocations even while others run in parallel; async
+//! tests should use the default current-thread runtime for the same reason.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn record() {
+    // The counter is unavailable while the thread is being torn down
+    let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record();
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        record();
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record();
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Number of allocations and reallocations made by this thread so far
+pub(crate) fn current() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+/// Run `f` and count the allocations it makes
+pub(crate) fn count<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    let before = current();
+    let result = f();
+    (result, current() - before)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_this_thread_only() {
+        assert_eq!(count(|| Box::new(1u64)).1, 1);
+        assert_eq!(count(|| 1 + 1).1, 0);
+
+        let (_, allocations) = count(|| std::thread::spawn(|| vec![0u8; 64].len()).join().unwrap());
+        let (_, spawn_only) = count(|| std::thread::spawn(|| 64).join().unwrap());
+        assert_eq!(allocations, spawn_only);
+    }
+}
@@ -0,0 +1,281 @@
+//! AMQP 0-9-1 Bridging
+//!
+//! This module translates message metadata between AMQP 1.0 and the basic
+//! properties of AMQP 0-9-1, for bridges that move messages between brokers
+//! speaking the two protocol versions. It follows the mapping RabbitMQ uses
+//! between the two:
+//!
+//! | AMQP 0-9-1        | AMQP 1.0                                       |
+//! |-------------------|------------------------------------------------|
+//! | content-type      | properties content-type                        |
+//! | content-encoding  | properties content-encoding                    |
+//! | headers           | application-properties                         |
+//! | delivery-mode     | header durable (2 is durable)                  |
+//! | priority          | header priority                                |
+//! | correlation-id    | properties correlation-id                      |
+//! | reply-to          | properties reply-to                            |
+//! | expiration        | header ttl (milliseconds, as a decimal string) |
+//! | message-id        | properties message-id                          |
+//! | timestamp         | properties creation-time (seconds, not ms)     |
+//! | type              | properties subject                             |
+//! | user-id           | properties user-id                             |
+//! | app-id            | message annotation `x-basic-app-id`            |
+//!
+//! Message and correlation IDs that are not strings, such as UUIDs or
+//! ulongs, become their string form. 1.0 properties without a 0-9-1
+//! counterpart, such as `to` or `group-id`, are not carried over.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::amqp091::BasicProperties;
+//! use dumq_amqp::Message;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let properties = BasicProperties {
+//!     content_type: Some("application/json".to_string()),
+//!     delivery_mode: Some(2),
+//!     expiration: Some("60000".to_string()),
+//!     ..Default::default()
+//! };
+//! let message = properties.to_message(b"{}".to_vec())?;
+//! assert!(message.is_durable());
+//! assert_eq!(message.header.as_ref().unwrap().ttl, Some(60_000));
+//!
+//! let back = BasicProperties::from_message(&message);
+//! assert_eq!(back, properties);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::message::{Body, Header, Properties};
+use crate::types::{AmqpMap, AmqpSymbol, AmqpValue, AnnotationKey, Annotations};
+use crate::{AmqpError, AmqpResult, Message};
+
+/// Message annotation carrying the 0-9-1 app-id
+pub const APP_ID_ANNOTATION: &str = "x-basic-app-id";
+
+/// Delivery mode of a persistent 0-9-1 message
+pub const PERSISTENT: u8 = 2;
+
+/// Delivery mode of a transient 0-9-1 message
+pub const TRANSIENT: u8 = 1;
+
+/// The basic properties of an AMQP 0-9-1 message
+///
+/// Header values are kept as AMQP 1.0 values; a bridge converts them to the
+/// field table types of its 0-9-1 client.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BasicProperties {
+    /// MIME content type
+    pub content_type: Option<String>,
+    /// MIME content encoding
+    pub content_encoding: Option<String>,
+    /// Application headers
+    pub headers: Option<AmqpMap>,
+    /// 1 for transient, 2 for persistent
+    pub delivery_mode: Option<u8>,
+    /// Priority, 0 to 9
+    pub priority: Option<u8>,
+    /// Correlation ID
+    pub correlation_id: Option<String>,
+    /// Address to reply to
+    pub reply_to: Option<String>,
+    /// Time to live in milliseconds, as a decimal string
+    pub expiration: Option<String>,
+    /// Message ID
+    pub message_id: Option<String>,
+    /// Creation time in seconds since the Unix epoch
+    pub timestamp: Option<u64>,
+    /// Message type name
+    pub kind: Option<String>,
+    /// Authenticated user ID
+    pub user_id: Option<String>,
+    /// Publishing application
+    pub app_id: Option<String>,
+}
+
+impl BasicProperties {
+    /// Get the basic properties of an AMQP 1.0 message
+    pub fn from_message(message: &Message) -> Self {
+        let header = message.header.as_ref();
+        let properties = message.properties.as_ref();
+        let app_id = message
+            .message_annotations
+            .as_ref()
+            .and_then(|annotations| annotations.get(&AnnotationKey::from(APP_ID_ANNOTATION)))
+            .and_then(id_string);
+
+        BasicProperties {
+            content_type: properties.and_then(|p| p.content_type.as_ref()).map(|s| s.as_str().to_string()),
+            content_encoding: properties.and_then(|p| p.content_encoding.as_ref()).map(|s| s.as_str().to_string()),
+            headers: message.application_properties.clone(),
+            delivery_mode: header
+                .and_then(|h| h.durable)
+                .map(|durable| if durable { PERSISTENT } else { TRANSIENT }),
+            priority: header.and_then(|h| h.priority),
+            correlation_id: properties.and_then(|p| p.correlation_id.as_ref()).and_then(id_string),
+            reply_to: properties.and_then(|p| p.reply_to.clone()),
+            expiration: header.and_then(|h| h.ttl).map(|ttl| ttl.to_string()),
+            message_id: properties.and_then(|p| p.message_id.as_ref()).and_then(id_string),
+            timestamp: properties
+                .and_then(|p| p.creation_time)
+                .and_then(|millis| u64::try_from(millis / 1000).ok()),
+            kind: properties.and_then(|p| p.subject.clone()),
+            user_id: properties
+                .and_then(|p| p.user_id.as_ref())
+                .map(|user_id| String::from_utf8_lossy(user_id).into_owned()),
+            app_id,
+        }
+    }
+
+    /// Build an AMQP 1.0 message from these properties and a 0-9-1 body
+    ///
+    /// The body becomes a single data section. Fails if the expiration is
+    /// not a number of milliseconds, as a 0-9-1 broker would.
+    pub fn to_message(&self, body: Vec<u8>) -> AmqpResult<Message> {
+        let ttl = match &self.expiration {
+            Some(expiration) => Some(expiration.parse::<u32>().map_err(|_| {
+                AmqpError::decoding(format!("Invalid expiration '{}', expected milliseconds", expiration))
+            })?),
+            None => None,
+        };
+
+        let mut header = Header::new();
+        header.durable = self.delivery_mode.map(|mode| mode == PERSISTENT);
+        header.priority = self.priority;
+        header.ttl = ttl;
+
+        let properties = Properties {
+            message_id: self.message_id.clone().map(AmqpValue::String),
+            user_id: self.user_id.clone().map(String::into_bytes),
+            subject: self.kind.clone(),
+            reply_to: self.reply_to.clone(),
+            correlation_id: self.correlation_id.clone().map(AmqpValue::String),
+            content_type: self.content_type.as_deref().map(AmqpSymbol::from),
+            content_encoding: self.content_encoding.as_deref().map(AmqpSymbol::from),
+            creation_time: self
+                .timestamp
+                .and_then(|seconds| i64::try_from(seconds).ok())
+                .map(|seconds| seconds.saturating_mul(1000)),
+            ..Default::default()
+        };
+
+        let mut builder = Message::builder().properties(properties).body(Body::Data(body.into()));
+        if header != Header::new() {
+            builder = builder.header(header);
+        }
+        if let Some(headers) = &self.headers {
+            builder = builder.application_properties(headers.clone());
+        }
+        if let Some(app_id) = &self.app_id {
+            let mut annotations = Annotations::new();
+            annotations.insert(AnnotationKey::from(APP_ID_ANNOTATION), AmqpValue::String(app_id.clone()));
+            builder = builder.message_annotations(annotations);
+        }
+        Ok(builder.build())
+    }
+}
+
+/// Get the body of an AMQP 1.0 message as a 0-9-1 body
+///
+/// Data sections are concatenated, and a value section holding binary or a
+/// string is used as is. Other bodies have no 0-9-1 representation.
+pub fn body(message: &Message) -> AmqpResult<Vec<u8>> {
+    fn append(body: &Body, bytes: &mut Vec<u8>) -> AmqpResult<()> {
+        match body {
+            Body::Data(data) | Body::Value(AmqpValue::Binary(data)) => bytes.extend_from_slice(data),
+            Body::Value(AmqpValue::String(text)) => bytes.extend_from_slice(text.as_bytes()),
+            Body::Multiple(sections) => {
+                for section in sections {
+                    append(section, bytes)?;
+                }
+            }
+            other => {
+                return Err(AmqpError::encoding(format!(
+                    "Body {:?} cannot be carried by AMQP 0-9-1",
+                    other
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    let mut bytes = Vec::new();
+    if let Some(body) = &message.body {
+        append(body, &mut bytes)?;
+    }
+    Ok(bytes)
+}
+
+/// Get the string form of a message ID, correlation ID or app-id
+fn id_string(id: &AmqpValue) -> Option<String> {
+    match id {
+        AmqpValue::String(id) => Some(id.clone()),
+        AmqpValue::Symbol(id) => Some(id.as_str().to_string()),
+        AmqpValue::Uuid(id) => Some(id.to_string()),
+        AmqpValue::Ulong(id) => Some(id.to_string()),
+        AmqpValue::Binary(id) => String::from_utf8(id.to_vec()).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    #[test]
+    fn test_roundtrip_through_amqp_1_0() {
+        let mut headers = AmqpMap::new();
+        headers.insert(AmqpSymbol::from("tenant"), AmqpValue::String("acme".to_string()));
+        headers.insert(AmqpSymbol::from("attempt"), AmqpValue::Int(3));
+        let properties = BasicProperties {
+            content_type: Some("text/plain".to_string()),
+            content_encoding: Some("gzip".to_string()),
+            headers: Some(headers),
+            delivery_mode: Some(TRANSIENT),
+            priority: Some(7),
+            correlation_id: Some("req-1".to_string()),
+            reply_to: Some("replies".to_string()),
+            expiration: Some("1500".to_string()),
+            message_id: Some("msg-1".to_string()),
+            timestamp: Some(1_700_000_000),
+            kind: Some("order.created".to_string()),
+            user_id: Some("guest".to_string()),
+            app_id: Some("checkout".to_string()),
+        };
+
+        let message = properties.to_message(b"hello".to_vec()).unwrap();
+        let amqp = message.properties.as_ref().unwrap();
+        assert_eq!(amqp.creation_time, Some(1_700_000_000_000));
+        assert_eq!(amqp.subject.as_deref(), Some("order.created"));
+        assert_eq!(message.header.as_ref().unwrap().durable, Some(false));
+        assert_eq!(BasicProperties::from_message(&message), properties);
+        assert_eq!(body(&message).unwrap(), b"hello");
+
+        let error = BasicProperties { expiration: Some("soon".to_string()), ..Default::default() }
+            .to_message(Vec::new())
+            .unwrap_err();
+        assert!(matches!(error, AmqpError::Decoding(_)));
+    }
+
+    #[test]
+    fn test_from_amqp_1_0_message() {
+        let id = crate::types::Uuid::nil();
+        let mut properties = Properties::new();
+        properties.message_id = Some(AmqpValue::Uuid(id));
+        properties.correlation_id = Some(AmqpValue::Ulong(42));
+        let message = Message::builder()
+            .properties(properties)
+            .body(Body::Multiple(vec![Body::Data(Bytes::from_static(b"ab")), Body::Data(Bytes::from_static(b"cd"))]))
+            .build();
+
+        let basic = BasicProperties::from_message(&message);
+        assert_eq!(basic.message_id, Some(id.to_string()));
+        assert_eq!(basic.correlation_id.as_deref(), Some("42"));
+        assert_eq!((basic.delivery_mode, basic.expiration), (None, None));
+        assert_eq!(body(&message).unwrap(), b"abcd");
+        assert!(body(&Message::builder().body(Body::Value(AmqpValue::Int(1))).build()).is_err());
+    }
+}
@@ -0,0 +1,317 @@
+//! Blocking AMQP 1.0 API
+//!
+//! This module wraps the async client with an internal Tokio runtime so it can
+//! be used from synchronous code such as CLI tools. Each wrapper drives the
+//! async operation to completion on the calling thread.
+//!
+//! These types must not be used from within an async runtime; doing so panics,
+//! as blocking the runtime's worker threads would stall other tasks.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use dumq_amqp::blocking::BlockingConnection;
+//! use dumq_amqp::connection::ConnectionConfig;
+//! use dumq_amqp::link::LinkConfig;
+//! use dumq_amqp::message::Message;
+//! use std::time::Duration;
+//!
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let mut connection = BlockingConnection::open(ConnectionConfig::default())?;
+//!
+//!     let mut sender = connection.create_sender(LinkConfig {
+//!         target: Some("orders".to_string()),
+//!         ..Default::default()
+//!     })?;
+//!     sender.add_credit(10);
+//!     sender.send(Message::text("Hello"))?;
+//!
+//!     let mut receiver = connection.create_receiver(LinkConfig {
+//!         source: Some("orders".to_string()),
+//!         ..Default::default()
+//!     })?;
+//!     if let Some(message) = receiver.receive(Duration::from_secs(5))? {
+//!         println!("Received: {:?}", message.body_as_text());
+//!     }
+//!
+//!     connection.close()?;
+//!     Ok(())
+//! }
+//! ```
+
+use crate::connection::{Connection, ConnectionConfig};
+use crate::link::{LinkConfig, LinkState, Receiver, Sender};
+use crate::session::Session;
+use crate::{AmqpError, AmqpResult, Message};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::runtime::{Builder, Runtime};
+
+/// Interval between checks for a message in [`BlockingReceiver::receive`]
+const RECEIVE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+fn new_runtime() -> AmqpResult<Arc<Runtime>> {
+    Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map(Arc::new)
+        .map_err(|e| AmqpError::connection(format!("Failed to create runtime: {}", e)))
+}
+
+/// Blocking AMQP 1.0 Connection
+pub struct BlockingConnection {
+    runtime: Arc<Runtime>,
+    connection: Connection,
+    channel: u16,
+}
+
+impl BlockingConnection {
+    /// Open a connection and begin a session on it
+    pub fn open(config: ConnectionConfig) -> AmqpResult<Self> {
+        let runtime = new_runtime()?;
+        let (connection, channel) = runtime.block_on(async {
+            let mut connection = Connection::new(config);
+            connection.open().await?;
+            let channel = connection.create_session().await?.channel();
+            Ok::<_, AmqpError>((connection, channel))
+        })?;
+
+        Ok(BlockingConnection {
+            runtime,
+            connection,
+            channel,
+        })
+    }
+
+    fn session(&mut self) -> AmqpResult<&mut Session> {
+        self.connection
+            .session_mut(self.channel)
+            .ok_or_else(|| AmqpError::invalid_state("Session has ended"))
+    }
+
+    /// Create and attach a sender
+    pub fn create_sender(&mut self, config: LinkConfig) -> AmqpResult<BlockingSender> {
+        let runtime = self.runtime.clone();
+        let sender = runtime.block_on(async {
+            let mut sender = self.session()?.create_sender(config).await?;
+            sender.attach().await?;
+            Ok::<_, AmqpError>(sender)
+        })?;
+        Ok(BlockingSender::with_runtime(self.runtime.clone(), sender))
+    }
+
+    /// Create and attach a receiver
+    pub fn create_receiver(&mut self, config: LinkConfig) -> AmqpResult<BlockingReceiver> {
+        let runtime = self.runtime.clone();
+        let receiver = runtime.block_on(async {
+            let mut receiver = self.session()?.create_receiver(config).await?;
+            receiver.attach().await?;
+            Ok::<_, AmqpError>(receiver)
+        })?;
+        Ok(BlockingReceiver::with_runtime(self.runtime.clone(), receiver))
+    }
+
+    /// Close the connection, ending the session and detaching its links
+    pub fn close(mut self) -> AmqpResult<()> {
+        self.runtime.block_on(self.connection.close())
+    }
+
+    /// Get the underlying connection
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+}
+
+/// Blocking AMQP 1.0 Sender
+pub struct BlockingSender {
+    runtime: Arc<Runtime>,
+    inner: Sender,
+}
+
+impl BlockingSender {
+    /// Wrap an async sender
+    pub fn new(sender: Sender) -> AmqpResult<Self> {
+        Ok(Self::with_runtime(new_runtime()?, sender))
+    }
+
+    fn with_runtime(runtime: Arc<Runtime>, inner: Sender) -> Self {
+        BlockingSender { runtime, inner }
+    }
+
+    /// Attach the sender
+    pub fn attach(&mut self) -> AmqpResult<()> {
+        self.runtime.block_on(self.inner.attach()).map(|_| ())
+    }
+
+    /// Send a message, returning its delivery ID
+    pub fn send(&mut self, message: Message) -> AmqpResult<u32> {
+        self.runtime
+            .block_on(self.inner.send(message))
+            .map(|delivery| delivery.delivery_id())
+    }
+
+    /// Add credit
+    pub fn add_credit(&mut self, credit: u32) {
+        self.inner.add_credit(credit);
+    }
+
+    /// Detach the sender
+    pub fn detach(&mut self) -> AmqpResult<()> {
+        self.runtime.block_on(self.inner.detach())
+    }
+
+    /// Get the underlying sender
+    pub fn inner(&self) -> &Sender {
+        &self.inner
+    }
+}
+
+/// Blocking AMQP 1.0 Receiver
+pub struct BlockingReceiver {
+    runtime: Arc<Runtime>,
+    inner: Receiver,
+}
+
+impl BlockingReceiver {
+    /// Wrap an async receiver
+    pub fn new(receiver: Receiver) -> AmqpResult<Self> {
+        Ok(Self::with_runtime(new_runtime()?, receiver))
+    }
+
+    fn with_runtime(runtime: Arc<Runtime>, inner: Receiver) -> Self {
+        BlockingReceiver { runtime, inner }
+    }
+
+    /// Attach the receiver
+    pub fn attach(&mut self) -> AmqpResult<()> {
+        self.runtime.block_on(self.inner.attach()).map(|_| ())
+    }
+
+    /// Receive a message, waiting up to `timeout`
+    ///
+    /// Returns `Ok(None)` if no message arrived before the timeout elapsed.
+    pub fn receive(&mut self, timeout: Duration) -> AmqpResult<Option<Message>> {
+        let deadline = Instant::now() + timeout;
+        let inner = &mut self.inner;
+        self.runtime.block_on(async {
+            loop {
+                if let Some(message) = inner.receive().await? {
+                    return Ok(Some(message));
+                }
+                let now = Instant::now();
+                if now >= deadline || inner.state() != &LinkState::Attached {
+                    return Ok(None);
+                }
+                tokio::time::sleep(RECEIVE_POLL_INTERVAL.min(deadline - now)).await;
+            }
+        })
+    }
+
+    /// Add credit
+    pub fn add_credit(&mut self, credit: u32) {
+        self.inner.add_credit(credit);
+    }
+
+    /// Detach the receiver
+    pub fn detach(&mut self) -> AmqpResult<()> {
+        self.runtime.block_on(self.inner.detach())
+    }
+
+    /// Get the underlying receiver
+    pub fn inner(&self) -> &Receiver {
+        &self.inner
+    }
+
+    /// Get the underlying receiver mutably
+    pub fn inner_mut(&mut self) -> &mut Receiver {
+        &mut self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::link::LinkBuilder;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_blocking_sender_send() {
+        let sender = LinkBuilder::new().target("orders").build_sender("session-1".to_string());
+        let mut sender = BlockingSender::new(sender).unwrap();
+
+        sender.attach().unwrap();
+        sender.add_credit(1);
+        assert_eq!(sender.send(Message::text("Hello")).unwrap(), 1);
+        assert!(sender.send(Message::text("No credit")).is_err());
+
+        sender.detach().unwrap();
+        assert_eq!(sender.inner().state(), &LinkState::Detached);
+    }
+
+    #[test]
+    fn test_blocking_receiver_receive() {
+        let receiver = LinkBuilder::new().source("orders").build_receiver("session-1".to_string());
+        let mut receiver = BlockingReceiver::new(receiver).unwrap();
+        receiver.attach().unwrap();
+
+        receiver.inner_mut().simulate_receive(Message::text("Hello"));
+        let message = receiver.receive(Duration::from_millis(100)).unwrap().unwrap();
+        assert_eq!(message.body_as_text(), Some("Hello"));
+    }
+
+    #[test]
+    fn test_blocking_receiver_timeout() {
+        let receiver = LinkBuilder::new().source("orders").build_receiver("session-1".to_string());
+        let mut receiver = BlockingReceiver::new(receiver).unwrap();
+        receiver.attach().unwrap();
+
+        let start = Instant::now();
+        assert!(receiver.receive(Duration::from_millis(50)).unwrap().is_none());
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_blocking_receiver_not_attached() {
+        let receiver = LinkBuilder::new().source("orders").build_receiver("session-1".to_string());
+        let mut receiver = BlockingReceiver::new(receiver).unwrap();
+
+        assert!(matches!(
+            receiver.receive(Duration::from_millis(10)).map_err(AmqpError::into_inner),
+            Err(AmqpError::InvalidState(_))
+        ));
+    }
+
+    #[test]
+    fn test_blocking_connection_lifecycle() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        listener.set_nonblocking(true).unwrap();
+        let server = thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            runtime.block_on(async {
+                let (stream, _) = tokio::net::TcpListener::from_std(listener)?.accept().await?;
+                crate::connection::tests::answer_connection(stream).await
+            })
+        });
+
+        let mut connection = BlockingConnection::open(ConnectionConfig {
+            hostname: "127.0.0.1".to_string(),
+            port,
+            ..Default::default()
+        })
+        .unwrap();
+
+        let mut sender = connection
+            .create_sender(LinkConfig {
+                target: Some("orders".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        sender.add_credit(1);
+        assert!(sender.send(Message::text("Hello")).is_ok());
+
+        connection.close().unwrap();
+        server.join().unwrap().unwrap();
+    }
+}
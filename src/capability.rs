@@ -0,0 +1,195 @@
+//! AMQP 1.0 Connection Capabilities
+//!
+//! This module names the capabilities brokers commonly announce in the
+//! offered-capabilities and desired-capabilities of their Open, and
+//! implements the error conventions of the sole-connection-for-container
+//! extension:
+//!
+//! - A container that desires [`SOLE_CONNECTION_FOR_CONTAINER`] asks the
+//!   peer to allow only one connection for its container-id.
+//! - The peer announces with the [`SOLE_CONNECTION_ENFORCEMENT_POLICY`]
+//!   connection property whether it refuses a second connection or closes
+//!   the first, see [`EnforcementPolicy`].
+//! - A refused connection gets an Open with the
+//!   [`CONNECTION_ESTABLISHMENT_FAILED`] property, then a Close with
+//!   `amqp:invalid-field` naming the container-id field ([`refusal`]). A
+//!   connection closed to make way for a new one gets
+//!   `amqp:resource-locked` ([`eviction`]).
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::capability::{self, EnforcementPolicy};
+//! use dumq_amqp::performative::Open;
+//! use dumq_amqp::AmqpSymbol;
+//!
+//! let broker = Open {
+//!     container_id: "broker".to_string(),
+//!     offered_capabilities: vec![
+//!         AmqpSymbol::from(capability::SOLE_CONNECTION_FOR_CONTAINER),
+//!         AmqpSymbol::from(capability::ANONYMOUS_RELAY),
+//!     ],
+//!     ..Default::default()
+//! };
+//! assert!(broker.offers(capability::ANONYMOUS_RELAY));
+//! assert_eq!(EnforcementPolicy::of(&broker), Some(EnforcementPolicy::RefuseConnection));
+//!
+//! assert!(capability::is_refusal(&capability::refusal()));
+//! ```
+
+use crate::performative::Open;
+use crate::types::{self, AmqpMap, AmqpSymbol, AmqpValue};
+use crate::{AmqpCondition, AmqpError, AmqpResult};
+
+/// Only one connection per container-id is allowed
+pub const SOLE_CONNECTION_FOR_CONTAINER: &str = "sole-connection-for-container";
+/// Links with no target address route each message by its `to` property
+pub const ANONYMOUS_RELAY: &str = "ANONYMOUS-RELAY";
+/// Messages can be scheduled for later delivery
+pub const DELAYED_DELIVERY: &str = "DELAYED_DELIVERY";
+/// Open property announcing the [`EnforcementPolicy`]
+pub const SOLE_CONNECTION_ENFORCEMENT_POLICY: &str = "sole-connection-enforcement-policy";
+/// Open property of a connection about to be refused
+pub const CONNECTION_ESTABLISHMENT_FAILED: &str = "connection-establishment-failed";
+/// Error info key naming the field an `amqp:invalid-field` error is about
+pub const INVALID_FIELD: &str = "invalid-field";
+/// Error info key marking a close caused by sole-connection enforcement
+pub const SOLE_CONNECTION_ENFORCEMENT: &str = "sole-connection-enforcement";
+
+/// How a peer enforces sole-connection-for-container
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnforcementPolicy {
+    /// A second connection for the container is refused
+    RefuseConnection,
+    /// The existing connection is closed and the new one admitted
+    CloseExisting,
+}
+
+impl EnforcementPolicy {
+    /// Get the policy of a peer offering sole-connection-for-container
+    ///
+    /// Without the enforcement-policy property the peer refuses the new
+    /// connection. Returns `None` if the capability is not offered.
+    pub fn of(open: &Open) -> Option<Self> {
+        if !open.offers(SOLE_CONNECTION_FOR_CONTAINER) {
+            return None;
+        }
+        match open.properties.get(&AmqpSymbol::from(SOLE_CONNECTION_ENFORCEMENT_POLICY)) {
+            Some(AmqpValue::Ubyte(1)) | Some(AmqpValue::Uint(1)) => Some(EnforcementPolicy::CloseExisting),
+            _ => Some(EnforcementPolicy::RefuseConnection),
+        }
+    }
+
+    /// Get the value of the enforcement-policy property
+    pub fn to_value(self) -> AmqpValue {
+        match self {
+            EnforcementPolicy::RefuseConnection => AmqpValue::Ubyte(0),
+            EnforcementPolicy::CloseExisting => AmqpValue::Ubyte(1),
+        }
+    }
+}
+
+/// Build the Close error refusing a second connection for a container
+pub fn refusal() -> types::AmqpError {
+    let mut info = AmqpMap::new();
+    info.insert(AmqpSymbol::from(INVALID_FIELD), AmqpValue::Symbol(AmqpSymbol::from("container-id")));
+    types::AmqpError::new(AmqpCondition::AmqpErrorInvalidField)
+        .with_description("Container already has a connection")
+        .with_info(info)
+}
+
+/// Build the Close error for a connection closed to admit a newer one
+pub fn eviction() -> types::AmqpError {
+    let mut info = AmqpMap::new();
+    info.insert(AmqpSymbol::from(SOLE_CONNECTION_ENFORCEMENT), AmqpValue::Boolean(true));
+    types::AmqpError::new(AmqpCondition::AmqpErrorResourceLocked)
+        .with_description("Container connected again from another connection")
+        .with_info(info)
+}
+
+/// Check if a Close error refuses a connection under sole-connection-for-container
+pub fn is_refusal(error: &types::AmqpError) -> bool {
+    error.condition == AmqpCondition::AmqpErrorInvalidField
+        && matches!(
+            info(error, INVALID_FIELD),
+            Some(AmqpValue::Symbol(field)) if field.as_str() == "container-id"
+        )
+}
+
+/// Check if a Close error evicts a connection under sole-connection-for-container
+pub fn is_eviction(error: &types::AmqpError) -> bool {
+    error.condition == AmqpCondition::AmqpErrorResourceLocked
+        && info(error, SOLE_CONNECTION_ENFORCEMENT) == Some(&AmqpValue::Boolean(true))
+}
+
+fn info<'e>(error: &'e types::AmqpError, key: &str) -> Option<&'e AmqpValue> {
+    error.info.as_ref()?.get(&AmqpSymbol::from(key))
+}
+
+/// Encode a capabilities field: null, a single symbol or an array
+pub(crate) fn encode(capabilities: &[AmqpSymbol]) -> AmqpValue {
+    match capabilities {
+        [] => AmqpValue::Null,
+        [capability] => AmqpValue::Symbol(capability.clone()),
+        _ => AmqpValue::Array(capabilities.iter().cloned().map(AmqpValue::Symbol).collect()),
+    }
+}
+
+/// Decode a capabilities field, which may hold one symbol or several
+pub(crate) fn decode(field: Option<&AmqpValue>) -> AmqpResult<Vec<AmqpSymbol>> {
+    let symbol = |value: &AmqpValue| match value {
+        AmqpValue::Symbol(symbol) => Ok(symbol.clone()),
+        other => Err(AmqpError::decoding(format!("Expected capability symbol, got {:?}", other))),
+    };
+    match field {
+        None | Some(AmqpValue::Null) => Ok(Vec::new()),
+        Some(AmqpValue::Array(values)) | Some(AmqpValue::List(values)) => values.iter().map(symbol).collect(),
+        Some(value) => Ok(vec![symbol(value)?]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities_field_forms() {
+        assert_eq!(encode(&[]), AmqpValue::Null);
+        let one = [AmqpSymbol::from(ANONYMOUS_RELAY)];
+        assert_eq!(encode(&one), AmqpValue::Symbol(AmqpSymbol::from(ANONYMOUS_RELAY)));
+        assert_eq!(decode(Some(&encode(&one))).unwrap(), one);
+
+        let two = [AmqpSymbol::from(ANONYMOUS_RELAY), AmqpSymbol::from(DELAYED_DELIVERY)];
+        assert_eq!(decode(Some(&encode(&two))).unwrap(), two);
+        assert!(decode(None).unwrap().is_empty());
+        assert!(decode(Some(&AmqpValue::Uint(1))).is_err());
+    }
+
+    #[test]
+    fn test_enforcement_errors() {
+        assert!(is_refusal(&refusal()));
+        assert!(!is_eviction(&refusal()));
+        assert!(is_eviction(&eviction()));
+
+        // The condition alone is not enough
+        let invalid_field = types::AmqpError::new(AmqpCondition::AmqpErrorInvalidField);
+        assert!(!is_refusal(&invalid_field));
+    }
+
+    #[test]
+    fn test_enforcement_policy_from_open() {
+        let mut open = Open {
+            offered_capabilities: vec![AmqpSymbol::from(SOLE_CONNECTION_FOR_CONTAINER)],
+            ..Default::default()
+        };
+        assert_eq!(EnforcementPolicy::of(&open), Some(EnforcementPolicy::RefuseConnection));
+        open.properties.insert(
+            AmqpSymbol::from(SOLE_CONNECTION_ENFORCEMENT_POLICY),
+            EnforcementPolicy::CloseExisting.to_value(),
+        );
+        assert_eq!(EnforcementPolicy::of(&open), Some(EnforcementPolicy::CloseExisting));
+
+        open.offered_capabilities.clear();
+        assert_eq!(EnforcementPolicy::of(&open), None);
+    }
+}
@@ -26,6 +26,7 @@
 //! use dumq_amqp::codec::{Encoder, Decoder};
 //! use dumq_amqp::types::AmqpValue;
 //!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
 //! // Encode a value
 //! let value = AmqpValue::String("Hello, AMQP!".to_string());
 //! let mut encoder = Encoder::new();
@@ -36,14 +37,17 @@
 //! let mut decoder = Decoder::new(encoded);
 //! let decoded = decoder.decode_value()?;
 //! assert_eq!(value, decoded);
+//! # Ok(())
+//! # }
 //! ```
 //!
 //! ## Encoding Multiple Values
 //!
 //! ```rust
-//! use dumq_amqp::codec::Encoder;
+//! use dumq_amqp::codec::{Encoder, Decoder};
 //! use dumq_amqp::types::AmqpValue;
 //!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
 //! let mut encoder = Encoder::new();
 //!
 //! // Encode multiple values
@@ -52,6 +56,15 @@
 //! encoder.encode_value(&AmqpValue::Boolean(true))?;
 //!
 //! let encoded = encoder.finish();
+//!
+//! // Decode them back one after the other
+//! let mut decoder = Decoder::new(encoded);
+//! for decoded in decoder.values() {
+//!     let decoded = decoded?;
+//!     println!("{:?} at byte {}", decoded.value, decoded.offset);
+//! }
+//! # Ok(())
+//! # }
 //! ```
 //!
 //! ## Working with Complex Types
@@ -61,6 +74,7 @@
 //! use dumq_amqp::types::{AmqpValue, AmqpList, AmqpMap, AmqpSymbol};
 //! use std::collections::HashMap;
 //!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
 //! // Create a complex value
 //! let mut map_data = HashMap::new();
 //! map_data.insert(AmqpSymbol::from("key"), AmqpValue::String("value".to_string()));
@@ -73,11 +87,15 @@
 //!
 //! let mut decoder = Decoder::new(encoded);
 //! let decoded = decoder.decode_value()?;
+//! # Ok(())
+//! # }
 //! ```
 
 use bytes::{Buf, BufMut, BytesMut};
-use crate::types::{AmqpValue, AmqpSymbol, AmqpList, AmqpMap};
+use std::ops::{Deref, DerefMut};
+use crate::types::{AmqpValue, AmqpSymbol, AmqpList, AmqpMap, AnnotationKey, Annotations, Descriptor};
 use crate::error::AmqpError;
+use crate::message::section;
 
 /// AMQP 1.0 Type Codes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -99,12 +117,18 @@ pub enum TypeCode {
     Ushort = 0x60,
     Uint = 0x70,
     Ulong = 0x80,
+    Uint0 = 0x43,
+    Ulong0 = 0x44,
+    SmallUint = 0x52,
+    SmallUlong = 0x53,
     
     // Signed integers
     Byte = 0x51,
     Short = 0x61,
     Int = 0x71,
     Long = 0x81,
+    SmallInt = 0x54,
+    SmallLong = 0x55,
     
     // Floating point
     Float = 0x72,
@@ -150,23 +174,117 @@ pub enum TypeCode {
     Array32 = 0xf0,
 }
 
+/// Encoder output buffer, either owned or provided by the caller
+enum EncodeBuffer<'a> {
+    Owned(BytesMut),
+    Borrowed(&'a mut BytesMut),
+}
+
+impl Deref for EncodeBuffer<'_> {
+    type Target = BytesMut;
+
+    #[inline]
+    fn deref(&self) -> &BytesMut {
+        match self {
+            EncodeBuffer::Owned(buffer) => buffer,
+            EncodeBuffer::Borrowed(buffer) => buffer,
+        }
+    }
+}
+
+impl DerefMut for EncodeBuffer<'_> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut BytesMut {
+        match self {
+            EncodeBuffer::Owned(buffer) => buffer,
+            EncodeBuffer::Borrowed(buffer) => buffer,
+        }
+    }
+}
+
+impl TryFrom<u8> for TypeCode {
+    type Error = AmqpError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(TypeCode::Described),
+            0x40 => Ok(TypeCode::Null),
+            0x56 => Ok(TypeCode::Boolean),
+            0x41 => Ok(TypeCode::BooleanTrue),
+            0x42 => Ok(TypeCode::BooleanFalse),
+            0x50 => Ok(TypeCode::Ubyte),
+            0x60 => Ok(TypeCode::Ushort),
+            0x70 => Ok(TypeCode::Uint),
+            0x80 => Ok(TypeCode::Ulong),
+            0x43 => Ok(TypeCode::Uint0),
+            0x44 => Ok(TypeCode::Ulong0),
+            0x52 => Ok(TypeCode::SmallUint),
+            0x53 => Ok(TypeCode::SmallUlong),
+            0x51 => Ok(TypeCode::Byte),
+            0x61 => Ok(TypeCode::Short),
+            0x71 => Ok(TypeCode::Int),
+            0x81 => Ok(TypeCode::Long),
+            0x54 => Ok(TypeCode::SmallInt),
+            0x55 => Ok(TypeCode::SmallLong),
+            0x72 => Ok(TypeCode::Float),
+            0x82 => Ok(TypeCode::Double),
+            0x74 => Ok(TypeCode::Decimal32),
+            0x84 => Ok(TypeCode::Decimal64),
+            0x94 => Ok(TypeCode::Decimal128),
+            0x73 => Ok(TypeCode::Char),
+            0x83 => Ok(TypeCode::Timestamp),
+            0x98 => Ok(TypeCode::Uuid),
+            0xa0 => Ok(TypeCode::Binary8),
+            0xb0 => Ok(TypeCode::Binary32),
+            0xa1 => Ok(TypeCode::String8),
+            0xb1 => Ok(TypeCode::String32),
+            0xa3 => Ok(TypeCode::Symbol8),
+            0xb3 => Ok(TypeCode::Symbol32),
+            0x45 => Ok(TypeCode::List0),
+            0xc0 => Ok(TypeCode::List8),
+            0xd0 => Ok(TypeCode::List32),
+            0xc1 => Ok(TypeCode::Map8),
+            0xd1 => Ok(TypeCode::Map32),
+            0xe0 => Ok(TypeCode::Array8),
+            0xf0 => Ok(TypeCode::Array32),
+            _ => Err(AmqpError::decoding(format!("Unknown type code: 0x{:02x}", value))),
+        }
+    }
+}
+
 /// AMQP 1.0 Encoder
-pub struct Encoder {
-    buffer: BytesMut,
+pub struct Encoder<'a> {
+    buffer: EncodeBuffer<'a>,
+    /// Offset of the first byte written by this encoder
+    start: usize,
 }
 
-impl Encoder {
+impl<'a> Encoder<'a> {
     /// Create a new encoder
     pub fn new() -> Self {
         Encoder {
-            buffer: BytesMut::new(),
+            buffer: EncodeBuffer::Owned(BytesMut::new()),
+            start: 0,
         }
     }
 
     /// Create a new encoder with initial capacity
     pub fn with_capacity(capacity: usize) -> Self {
         Encoder {
-            buffer: BytesMut::with_capacity(capacity),
+            buffer: EncodeBuffer::Owned(BytesMut::with_capacity(capacity)),
+            start: 0,
+        }
+    }
+
+    /// Create an encoder that appends to a caller-provided buffer
+    ///
+    /// Encoded bytes are written directly after any existing content, so a
+    /// frame header can be placed in the buffer first without a later copy.
+    pub fn new_into(buffer: &'a mut BytesMut) -> Self {
+        let start = buffer.len();
+        Encoder {
+            buffer: EncodeBuffer::Borrowed(buffer),
+            start,
         }
     }
 
@@ -197,16 +315,19 @@ impl Encoder {
             AmqpValue::List(list) => self.encode_list(list),
             AmqpValue::Map(map) => self.encode_map(map),
             AmqpValue::Array(array) => self.encode_array(array),
+            AmqpValue::Described(descriptor, value) => self.encode_described(descriptor, value),
         }
     }
 
     /// Encode null
+    #[inline]
     pub fn encode_null(&mut self) -> Result<(), AmqpError> {
         self.buffer.put_u8(TypeCode::Null as u8);
         Ok(())
     }
 
     /// Encode boolean
+    #[inline]
     pub fn encode_boolean(&mut self, value: bool) -> Result<(), AmqpError> {
         if value {
             self.buffer.put_u8(TypeCode::BooleanTrue as u8);
@@ -217,6 +338,7 @@ impl Encoder {
     }
 
     /// Encode ubyte
+    #[inline]
     pub fn encode_ubyte(&mut self, value: u8) -> Result<(), AmqpError> {
         self.buffer.put_u8(TypeCode::Ubyte as u8);
         self.buffer.put_u8(value);
@@ -224,6 +346,7 @@ impl Encoder {
     }
 
     /// Encode ushort
+    #[inline]
     pub fn encode_ushort(&mut self, value: u16) -> Result<(), AmqpError> {
         self.buffer.put_u8(TypeCode::Ushort as u8);
         self.buffer.put_u16(value);
@@ -231,6 +354,7 @@ impl Encoder {
     }
 
     /// Encode uint
+    #[inline]
     pub fn encode_uint(&mut self, value: u32) -> Result<(), AmqpError> {
         self.buffer.put_u8(TypeCode::Uint as u8);
         self.buffer.put_u32(value);
@@ -238,6 +362,7 @@ impl Encoder {
     }
 
     /// Encode ulong
+    #[inline]
     pub fn encode_ulong(&mut self, value: u64) -> Result<(), AmqpError> {
         self.buffer.put_u8(TypeCode::Ulong as u8);
         self.buffer.put_u64(value);
@@ -245,6 +370,7 @@ impl Encoder {
     }
 
     /// Encode byte
+    #[inline]
     pub fn encode_byte(&mut self, value: i8) -> Result<(), AmqpError> {
         self.buffer.put_u8(TypeCode::Byte as u8);
         self.buffer.put_i8(value);
@@ -252,6 +378,7 @@ impl Encoder {
     }
 
     /// Encode short
+    #[inline]
     pub fn encode_short(&mut self, value: i16) -> Result<(), AmqpError> {
         self.buffer.put_u8(TypeCode::Short as u8);
         self.buffer.put_i16(value);
@@ -259,6 +386,7 @@ impl Encoder {
     }
 
     /// Encode int
+    #[inline]
     pub fn encode_int(&mut self, value: i32) -> Result<(), AmqpError> {
         self.buffer.put_u8(TypeCode::Int as u8);
         self.buffer.put_i32(value);
@@ -266,6 +394,7 @@ impl Encoder {
     }
 
     /// Encode long
+    #[inline]
     pub fn encode_long(&mut self, value: i64) -> Result<(), AmqpError> {
         self.buffer.put_u8(TypeCode::Long as u8);
         self.buffer.put_i64(value);
@@ -273,6 +402,7 @@ impl Encoder {
     }
 
     /// Encode float
+    #[inline]
     pub fn encode_float(&mut self, value: f32) -> Result<(), AmqpError> {
         self.buffer.put_u8(TypeCode::Float as u8);
         self.buffer.put_f32(value);
@@ -280,6 +410,7 @@ impl Encoder {
     }
 
     /// Encode double
+    #[inline]
     pub fn encode_double(&mut self, value: f64) -> Result<(), AmqpError> {
         self.buffer.put_u8(TypeCode::Double as u8);
         self.buffer.put_f64(value);
@@ -287,6 +418,7 @@ impl Encoder {
     }
 
     /// Encode char
+    #[inline]
     pub fn encode_char(&mut self, value: char) -> Result<(), AmqpError> {
         self.buffer.put_u8(TypeCode::Char as u8);
         // UTF-32 big-endian encoding: put the character as 4 bytes in big-endian order
@@ -299,6 +431,7 @@ impl Encoder {
     }
 
     /// Encode timestamp
+    #[inline]
     pub fn encode_timestamp(&mut self, value: i64) -> Result<(), AmqpError> {
         self.buffer.put_u8(TypeCode::Timestamp as u8);
         self.buffer.put_i64(value);
@@ -306,7 +439,8 @@ impl Encoder {
     }
 
     /// Encode UUID
-    pub fn encode_uuid(&mut self, value: uuid::Uuid) -> Result<(), AmqpError> {
+    #[inline]
+    pub fn encode_uuid(&mut self, value: crate::types::Uuid) -> Result<(), AmqpError> {
         self.buffer.put_u8(TypeCode::Uuid as u8);
         self.buffer.put_u128(value.as_u128());
         Ok(())
@@ -341,7 +475,11 @@ impl Encoder {
 
     /// Encode symbol
     pub fn encode_symbol(&mut self, symbol: &AmqpSymbol) -> Result<(), AmqpError> {
-        let bytes = symbol.0.as_bytes();
+        self.encode_symbol_bytes(symbol.0.as_bytes());
+        Ok(())
+    }
+
+    fn encode_symbol_bytes(&mut self, bytes: &[u8]) {
         if bytes.len() <= 255 {
             self.buffer.put_u8(TypeCode::Symbol8 as u8);
             self.buffer.put_u8(bytes.len() as u8);
@@ -350,47 +488,86 @@ impl Encoder {
             self.buffer.put_u32(bytes.len() as u32);
         }
         self.buffer.extend_from_slice(bytes);
-        Ok(())
     }
 
     fn encode_list(&mut self, list: &AmqpList) -> Result<(), AmqpError> {
-        // Write list header
         if list.is_empty() {
             self.buffer.put_u8(TypeCode::List0 as u8);
-        } else if list.len() <= 255 {
-            self.buffer.put_u8(TypeCode::List8 as u8);
-            self.buffer.put_u8(list.len() as u8);
+            return Ok(());
+        }
+        self.encode_compound(TypeCode::List8, TypeCode::List32, list.len(), |encoder| {
+            for item in list {
+                encoder.encode_value(item)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Encode a list or map of `count` elements written by a closure
+    ///
+    /// The elements are written after a list32 or map32 header whose size is
+    /// filled in afterwards. When size and count fit in a byte, the header is
+    /// shrunk to the list8 or map8 encoding instead.
+    fn encode_compound<F>(&mut self, small: TypeCode, large: TypeCode, count: usize, encode_elements: F) -> Result<(), AmqpError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), AmqpError>,
+    {
+        let start = self.buffer.len();
+        self.buffer.put_u8(large as u8);
+        self.buffer.put_u32(0);
+        self.buffer.put_u32(count as u32);
+
+        encode_elements(self)?;
+
+        let elements = start + 9;
+        let len = self.buffer.len() - elements;
+        if count <= u8::MAX as usize && len < u8::MAX as usize {
+            // The size counts the count byte as well as the elements
+            self.buffer[start] = small as u8;
+            self.buffer[start + 1] = (len + 1) as u8;
+            self.buffer[start + 2] = count as u8;
+            self.buffer.copy_within(elements.., start + 3);
+            let end = self.buffer.len() - 6;
+            self.buffer.truncate(end);
         } else {
-            self.buffer.put_u8(TypeCode::List32 as u8);
-            self.buffer.put_u32(list.len() as u32);
+            let size = (len + 4) as u32;
+            self.buffer[start + 1..start + 5].copy_from_slice(&size.to_be_bytes());
         }
+        Ok(())
+    }
 
-        // Write list items
-        for item in list {
-            self.encode_value(item)?;
+    /// Encode a described value
+    fn encode_described(&mut self, descriptor: &Descriptor, value: &AmqpValue) -> Result<(), AmqpError> {
+        self.buffer.put_u8(TypeCode::Described as u8);
+        match descriptor {
+            Descriptor::Code(code) => self.encode_ulong(*code)?,
+            Descriptor::Symbol(symbol) => self.encode_symbol(symbol)?,
         }
-        Ok(())
+        self.encode_value(value)
     }
 
     fn encode_map(&mut self, map: &AmqpMap) -> Result<(), AmqpError> {
-        // Write map header
-        if map.is_empty() {
-            self.buffer.put_u8(TypeCode::Map8 as u8);
-            self.buffer.put_u8(0);
-        } else if map.len() <= 127 {
-            self.buffer.put_u8(TypeCode::Map8 as u8);
-            self.buffer.put_u8(map.len() as u8);
-        } else {
-            self.buffer.put_u8(TypeCode::Map32 as u8);
-            self.buffer.put_u32(map.len() as u32);
-        }
+        self.encode_sized_map(map.len(), |encoder| {
+            for (key, value) in map {
+                encoder.encode_symbol(key)?;
+                encoder.encode_value(value)?;
+            }
+            Ok(())
+        })
+    }
 
-        // Write map entries
-        for (key, value) in map {
-            self.encode_symbol(key)?;
-            self.encode_value(value)?;
-        }
-        Ok(())
+    /// Encode an annotations map, whose keys are symbols or ulong codes
+    pub fn encode_annotations(&mut self, annotations: &Annotations) -> Result<(), AmqpError> {
+        self.encode_sized_map(annotations.len(), |encoder| {
+            for (key, value) in annotations {
+                match key {
+                    AnnotationKey::Symbol(symbol) => encoder.encode_symbol(symbol)?,
+                    AnnotationKey::Ulong(code) => encoder.encode_ulong(*code)?,
+                }
+                encoder.encode_value(value)?;
+            }
+            Ok(())
+        })
     }
 
     /// Encode array
@@ -435,207 +612,730 @@ impl Encoder {
         Ok(())
     }
 
+    /// Encode a described list (used by performatives)
+    ///
+    /// The descriptor is written as a small ulong and the list uses the list32
+    /// encoding with its size and count prefix, or list0 when empty. Trailing
+    /// null fields are left out, as the spec allows for composite types; the
+    /// peer reads them as absent, which means the same as null.
+    pub fn encode_described_list(&mut self, descriptor: u64, fields: &[AmqpValue]) -> Result<(), AmqpError> {
+        let fields = &fields[..present_len(fields, |field| matches!(field, AmqpValue::Null))];
+        self.encode_described_list_with(descriptor, fields.len(), |encoder| {
+            for field in fields {
+                encoder.encode_value(field)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Encode a described list whose fields are written by a closure
+    ///
+    /// The closure must write exactly `count` values. This allows fields that
+    /// are themselves described lists; the list size is filled in afterwards.
+    /// Unlike [`Encoder::encode_described_list`], fields are written as given,
+    /// so callers leave out trailing nulls themselves.
+    pub fn encode_described_list_with<F>(&mut self, descriptor: u64, count: usize, encode_fields: F) -> Result<(), AmqpError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), AmqpError>,
+    {
+        self.buffer.put_u8(TypeCode::Described as u8);
+        if descriptor <= u8::MAX as u64 {
+            self.buffer.put_u8(TypeCode::SmallUlong as u8);
+            self.buffer.put_u8(descriptor as u8);
+        } else {
+            self.buffer.put_u8(TypeCode::Ulong as u8);
+            self.buffer.put_u64(descriptor);
+        }
+        if count == 0 {
+            self.buffer.put_u8(TypeCode::List0 as u8);
+            return encode_fields(self);
+        }
+        self.buffer.put_u8(TypeCode::List32 as u8);
+        let size_offset = self.buffer.len();
+        self.buffer.put_u32(0);
+        self.buffer.put_u32(count as u32);
+
+        encode_fields(self)?;
+
+        let size = (self.buffer.len() - size_offset - 4) as u32;
+        self.buffer[size_offset..size_offset + 4].copy_from_slice(&size.to_be_bytes());
+        Ok(())
+    }
+
     /// Get the encoded data
+    ///
+    /// For encoders created with [`Encoder::new_into`], the bytes are already in
+    /// the caller's buffer and this returns a copy of the bytes this encoder wrote.
     pub fn finish(self) -> Vec<u8> {
-        self.buffer.to_vec()
+        self.buffer[self.start..].to_vec()
     }
 
     /// Encode an AMQP message
+    ///
+    /// Each part of the message is written as the described section the
+    /// spec defines for it, in the spec's order: header (0x70), delivery
+    /// annotations (0x71), message annotations (0x72), properties (0x73),
+    /// application properties (0x74), the body as data (0x75), amqp-sequence
+    /// (0x76) or amqp-value (0x77) sections, and the footer (0x78).
     pub fn encode_message(&mut self, message: &crate::message::Message) -> Result<(), AmqpError> {
-        // Encode message header
         if let Some(header) = &message.header {
-            // For now, we'll encode a simple map representation
-            let mut header_map = AmqpMap::new();
-            if let Some(durable) = header.durable {
-                header_map.insert(AmqpSymbol::from("durable"), AmqpValue::Boolean(durable));
-            }
-            if let Some(priority) = header.priority {
-                header_map.insert(AmqpSymbol::from("priority"), AmqpValue::Ubyte(priority));
-            }
-            if let Some(ttl) = header.ttl {
-                header_map.insert(AmqpSymbol::from("ttl"), AmqpValue::Uint(ttl));
-            }
-            if let Some(first_acquirer) = header.first_acquirer {
-                header_map.insert(AmqpSymbol::from("first_acquirer"), AmqpValue::Boolean(first_acquirer));
-            }
-            if let Some(delivery_count) = header.delivery_count {
-                header_map.insert(AmqpSymbol::from("delivery_count"), AmqpValue::Uint(delivery_count));
-            }
-            self.encode_value(&AmqpValue::Map(header_map))?;
+            self.encode_section_list(section::HEADER, &header_fields(header))?;
+        }
+        if let Some(annotations) = &message.delivery_annotations {
+            self.encode_section_annotations(section::DELIVERY_ANNOTATIONS, annotations)?;
+        }
+        if let Some(annotations) = &message.message_annotations {
+            self.encode_section_annotations(section::MESSAGE_ANNOTATIONS, annotations)?;
         }
-
-        // Encode message properties
         if let Some(properties) = &message.properties {
-            let mut props_map = AmqpMap::new();
-            if let Some(message_id) = &properties.message_id {
-                props_map.insert(AmqpSymbol::from("message_id"), message_id.clone());
-            }
-            if let Some(user_id) = &properties.user_id {
-                props_map.insert(AmqpSymbol::from("user_id"), AmqpValue::Binary(user_id.clone()));
-            }
-            if let Some(to) = &properties.to {
-                props_map.insert(AmqpSymbol::from("to"), AmqpValue::String(to.clone()));
-            }
-            if let Some(subject) = &properties.subject {
-                props_map.insert(AmqpSymbol::from("subject"), AmqpValue::String(subject.clone()));
-            }
-            if let Some(reply_to) = &properties.reply_to {
-                props_map.insert(AmqpSymbol::from("reply_to"), AmqpValue::String(reply_to.clone()));
-            }
-            if let Some(correlation_id) = &properties.correlation_id {
-                props_map.insert(AmqpSymbol::from("correlation_id"), correlation_id.clone());
-            }
-            if let Some(content_type) = &properties.content_type {
-                props_map.insert(AmqpSymbol::from("content_type"), AmqpValue::Symbol(content_type.clone()));
-            }
-            if let Some(content_encoding) = &properties.content_encoding {
-                props_map.insert(AmqpSymbol::from("content_encoding"), AmqpValue::Symbol(content_encoding.clone()));
-            }
-            if let Some(absolute_expiry_time) = properties.absolute_expiry_time {
-                props_map.insert(AmqpSymbol::from("absolute_expiry_time"), AmqpValue::Timestamp(absolute_expiry_time));
-            }
-            if let Some(creation_time) = properties.creation_time {
-                props_map.insert(AmqpSymbol::from("creation_time"), AmqpValue::Timestamp(creation_time));
+            self.encode_section_list(section::PROPERTIES, &properties_fields(properties))?;
+        }
+        if let Some(application_properties) = &message.application_properties {
+            // Application property keys are strings on the wire
+            self.encode_section_map(section::APPLICATION_PROPERTIES, application_properties.len(), |encoder| {
+                for (key, value) in application_properties {
+                    encoder.encode_string(key.as_str())?;
+                    encoder.encode_value(value)?;
+                }
+                Ok(())
+            })?;
+        }
+
+        match &message.body {
+            Some(crate::message::Body::Multiple(bodies)) => {
+                for body in bodies {
+                    if matches!(body, crate::message::Body::Multiple(_)) {
+                        return Err(AmqpError::encoding("Nested multiple bodies not supported"));
+                    }
+                    self.encode_body_section(body)?;
+                }
             }
-            if let Some(group_id) = &properties.group_id {
-                props_map.insert(AmqpSymbol::from("group_id"), AmqpValue::String(group_id.clone()));
+            Some(body) => self.encode_body_section(body)?,
+            None => {}
+        }
+
+        // The footer is always last, so it can be filled in after the body
+        if let Some(footer) = &message.footer {
+            self.encode_section_map(section::FOOTER, footer.len(), |encoder| {
+                for (key, value) in footer {
+                    encoder.encode_symbol(key)?;
+                    encoder.encode_value(value)?;
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Encode a body that is not itself made of several bodies
+    fn encode_body_section(&mut self, body: &crate::message::Body) -> Result<(), AmqpError> {
+        match body {
+            crate::message::Body::Data(data) => {
+                self.encode_section_descriptor(section::DATA);
+                self.encode_binary(data)
             }
-            if let Some(group_sequence) = properties.group_sequence {
-                props_map.insert(AmqpSymbol::from("group_sequence"), AmqpValue::Uint(group_sequence));
+            crate::message::Body::Sequence(items) => {
+                self.encode_described_list_with(section::AMQP_SEQUENCE, items.len(), |encoder| {
+                    for item in items {
+                        encoder.encode_value(item)?;
+                    }
+                    Ok(())
+                })
             }
-            if let Some(reply_to_group_id) = &properties.reply_to_group_id {
-                props_map.insert(AmqpSymbol::from("reply_to_group_id"), AmqpValue::String(reply_to_group_id.clone()));
+            crate::message::Body::Value(value) => {
+                self.encode_section_descriptor(section::AMQP_VALUE);
+                self.encode_value(value)
             }
-            self.encode_value(&AmqpValue::Map(props_map))?;
+            crate::message::Body::Multiple(_) => Err(AmqpError::encoding("Nested multiple bodies not supported")),
         }
+    }
 
-        // Encode message body
-        if let Some(body) = &message.body {
-            match body {
-                crate::message::Body::Value(value) => {
-                    self.encode_value(value)?;
-                }
-                crate::message::Body::Data(data) => {
-                    self.encode_binary(data)?;
+    /// Encode the header or properties section, leaving out trailing absent fields
+    fn encode_section_list(&mut self, code: u64, fields: &[Option<FieldValue<'_>>]) -> Result<(), AmqpError> {
+        let fields = &fields[..present_len(fields, Option::is_none)];
+        self.encode_described_list_with(code, fields.len(), |encoder| {
+            for field in fields {
+                match field {
+                    Some(FieldValue::Boolean(value)) => encoder.encode_boolean(*value)?,
+                    Some(FieldValue::Ubyte(value)) => encoder.encode_ubyte(*value)?,
+                    Some(FieldValue::Uint(value)) => encoder.encode_uint(*value)?,
+                    Some(FieldValue::Timestamp(value)) => encoder.encode_timestamp(*value)?,
+                    Some(FieldValue::Binary(value)) => encoder.encode_binary(value)?,
+                    Some(FieldValue::String(value)) => encoder.encode_string(value)?,
+                    Some(FieldValue::Symbol(value)) => encoder.encode_symbol(value)?,
+                    Some(FieldValue::Value(value)) => encoder.encode_value(value)?,
+                    None => encoder.encode_null()?,
                 }
-                crate::message::Body::Sequence(sequences) => {
-                    for sequence in sequences {
-                        self.encode_value(sequence)?;
-                    }
-                }
-                crate::message::Body::Multiple(bodies) => {
-                    for body in bodies {
-                        // Recursively encode each body part
-                        match body {
-                            crate::message::Body::Value(value) => self.encode_value(value)?,
-                            crate::message::Body::Data(data) => self.encode_binary(data)?,
-                            crate::message::Body::Sequence(sequences) => {
-                                for sequence in sequences {
-                                    self.encode_value(sequence)?;
-                                }
-                            }
-                            crate::message::Body::Multiple(_) => {
-                                return Err(AmqpError::encoding("Nested multiple bodies not supported"));
-                            }
-                        }
-                    }
+            }
+            Ok(())
+        })
+    }
+
+    /// Encode a delivery or message annotations section
+    fn encode_section_annotations(&mut self, code: u64, annotations: &Annotations) -> Result<(), AmqpError> {
+        self.encode_section_map(code, annotations.len(), |encoder| {
+            for (key, value) in annotations {
+                match key {
+                    AnnotationKey::Symbol(symbol) => encoder.encode_symbol(symbol)?,
+                    AnnotationKey::Ulong(code) => encoder.encode_ulong(*code)?,
                 }
+                encoder.encode_value(value)?;
             }
-        }
+            Ok(())
+        })
+    }
 
-        Ok(())
+    /// Encode a map section whose entries are written by a closure
+    fn encode_section_map<F>(&mut self, code: u64, len: usize, encode_entries: F) -> Result<(), AmqpError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), AmqpError>,
+    {
+        self.encode_section_descriptor(code);
+        self.encode_sized_map(len, encode_entries)
+    }
+
+    /// Encode a map of `len` entries written by a closure
+    ///
+    /// The count of a map is that of its keys and values together, as the
+    /// spec defines it.
+    fn encode_sized_map<F>(&mut self, len: usize, encode_entries: F) -> Result<(), AmqpError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), AmqpError>,
+    {
+        self.encode_compound(TypeCode::Map8, TypeCode::Map32, len * 2, encode_entries)
+    }
+
+    /// Write the constructor of a message section, its code as a small ulong
+    fn encode_section_descriptor(&mut self, code: u64) {
+        self.buffer.put_u8(TypeCode::Described as u8);
+        self.buffer.put_u8(TypeCode::SmallUlong as u8);
+        self.buffer.put_u8(code as u8);
     }
 }
 
-impl Default for Encoder {
+impl Default for Encoder<'_> {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// Encode a value directly into a caller-provided buffer
+pub fn encode_value_into(buffer: &mut BytesMut, value: &AmqpValue) -> Result<(), AmqpError> {
+    Encoder::new_into(buffer).encode_value(value)
+}
+
+/// A message header or properties field, borrowed from the message
+#[derive(Clone, Copy)]
+enum FieldValue<'m> {
+    Boolean(bool),
+    Ubyte(u8),
+    Uint(u32),
+    Timestamp(i64),
+    Binary(&'m [u8]),
+    String(&'m str),
+    Symbol(&'m AmqpSymbol),
+    Value(&'m AmqpValue),
+}
+
+impl FieldValue<'_> {
+    fn encoded_size(&self) -> usize {
+        match self {
+            FieldValue::Boolean(_) => 1,
+            FieldValue::Ubyte(_) => 2,
+            FieldValue::Uint(_) => 5,
+            FieldValue::Timestamp(_) => 9,
+            FieldValue::Binary(value) => variable_width_size(value.len()),
+            FieldValue::String(value) => variable_width_size(value.len()),
+            FieldValue::Symbol(value) => variable_width_size(value.0.len()),
+            FieldValue::Value(value) => encoded_size(value),
+        }
+    }
+}
+
+/// Fields of the message header section, in spec order
+///
+/// Built without allocating, so encoding a message or computing its size
+/// does not need an intermediate list.
+fn header_fields(header: &crate::message::Header) -> [Option<FieldValue<'_>>; 5] {
+    // Fields at their spec default are sent as null; the decoder fills them back in
+    [
+        header.durable.filter(|durable| *durable).map(FieldValue::Boolean),
+        header
+            .priority
+            .filter(|priority| *priority != crate::message::DEFAULT_PRIORITY)
+            .map(FieldValue::Ubyte),
+        header.ttl.map(FieldValue::Uint),
+        header.first_acquirer.filter(|first_acquirer| *first_acquirer).map(FieldValue::Boolean),
+        header.delivery_count.filter(|count| *count != 0).map(FieldValue::Uint),
+    ]
+}
+
+/// Fields of the message properties section, in spec order
+fn properties_fields(properties: &crate::message::Properties) -> [Option<FieldValue<'_>>; 13] {
+    [
+        properties.message_id.as_ref().map(FieldValue::Value),
+        properties.user_id.as_deref().map(FieldValue::Binary),
+        properties.to.as_deref().map(FieldValue::String),
+        properties.subject.as_deref().map(FieldValue::String),
+        properties.reply_to.as_deref().map(FieldValue::String),
+        properties.correlation_id.as_ref().map(FieldValue::Value),
+        properties.content_type.as_ref().map(FieldValue::Symbol),
+        properties.content_encoding.as_ref().map(FieldValue::Symbol),
+        properties.absolute_expiry_time.map(FieldValue::Timestamp),
+        properties.creation_time.map(FieldValue::Timestamp),
+        properties.group_id.as_deref().map(FieldValue::String),
+        properties.group_sequence.map(FieldValue::Uint),
+        properties.reply_to_group_id.as_deref().map(FieldValue::String),
+    ]
+}
+
+/// Encoded size of a header or properties section
+fn section_list_size(fields: &[Option<FieldValue<'_>>]) -> usize {
+    let fields = &fields[..present_len(fields, Option::is_none)];
+    let list = if fields.is_empty() {
+        1
+    } else {
+        9 + fields.iter().map(|field| field.map_or(1, |value| value.encoded_size())).sum::<usize>()
+    };
+    SECTION_DESCRIPTOR_SIZE + list
+}
+
+/// Size of a section constructor: 0x00 and the code as a small ulong
+const SECTION_DESCRIPTOR_SIZE: usize = 3;
+
+/// Encoded size of a list or map of `count` elements taking `len` bytes
+///
+/// Matches the choice [`Encoder`] makes between the 8-bit and 32-bit
+/// encodings.
+fn compound_size(count: usize, len: usize) -> usize {
+    if count <= u8::MAX as usize && len < u8::MAX as usize {
+        3 + len
+    } else {
+        9 + len
+    }
+}
+
+/// Compute the encoded size of a value without encoding it
+pub fn encoded_size(value: &AmqpValue) -> usize {
+    match value {
+        AmqpValue::Null | AmqpValue::Boolean(_) => 1,
+        AmqpValue::Ubyte(_) | AmqpValue::Byte(_) => 2,
+        AmqpValue::Ushort(_) | AmqpValue::Short(_) => 3,
+        AmqpValue::Uint(_)
+        | AmqpValue::Int(_)
+        | AmqpValue::Float(_)
+        | AmqpValue::Decimal32(_)
+        | AmqpValue::Char(_) => 5,
+        AmqpValue::Ulong(_)
+        | AmqpValue::Long(_)
+        | AmqpValue::Double(_)
+        | AmqpValue::Decimal64(_)
+        | AmqpValue::Timestamp(_) => 9,
+        AmqpValue::Decimal128(_) | AmqpValue::Uuid(_) => 17,
+        AmqpValue::Binary(data) => variable_width_size(data.len()),
+        AmqpValue::String(s) => variable_width_size(s.len()),
+        AmqpValue::Symbol(s) => variable_width_size(s.0.len()),
+        AmqpValue::List(list) if list.is_empty() => 1,
+        AmqpValue::List(list) => compound_size(list.len(), list.iter().map(encoded_size).sum()),
+        AmqpValue::Map(map) => map_size(map),
+        AmqpValue::Array(array) => {
+            let items: usize = array.iter().map(encoded_size).sum();
+            let header = if items <= 255 { 3 } else { 9 };
+            header + items
+        }
+        AmqpValue::Described(descriptor, value) => {
+            let descriptor = match descriptor.as_ref() {
+                Descriptor::Code(code) => encoded_size(&AmqpValue::Ulong(*code)),
+                Descriptor::Symbol(symbol) => variable_width_size(symbol.0.len()),
+            };
+            1 + descriptor + encoded_size(value)
+        }
+    }
+}
+
+fn map_size(map: &AmqpMap) -> usize {
+    compound_size(map.len() * 2, map_entries_size(map))
+}
+
+/// Encoded size of the keys and values of a map
+fn map_entries_size(map: &AmqpMap) -> usize {
+    map.iter()
+        .map(|(key, value)| variable_width_size(key.0.len()) + encoded_size(value))
+        .sum()
+}
+
+/// Compute the size produced by [`Encoder::encode_message`] without encoding
+pub fn encoded_message_size(message: &crate::message::Message) -> usize {
+    let annotations_size = |annotations: &Annotations| {
+        let entries = annotations
+            .iter()
+            .map(|(key, value)| {
+                let key = match key {
+                    AnnotationKey::Symbol(symbol) => variable_width_size(symbol.0.len()),
+                    AnnotationKey::Ulong(_) => 9,
+                };
+                key + encoded_size(value)
+            })
+            .sum::<usize>();
+        SECTION_DESCRIPTOR_SIZE + compound_size(annotations.len() * 2, entries)
+    };
+    // String and symbol keys take the same room
+    let map_section_size = |map: &AmqpMap| SECTION_DESCRIPTOR_SIZE + map_size(map);
+
+    let mut size = 0;
+    if let Some(header) = &message.header {
+        size += section_list_size(&header_fields(header));
+    }
+    if let Some(annotations) = &message.delivery_annotations {
+        size += annotations_size(annotations);
+    }
+    if let Some(annotations) = &message.message_annotations {
+        size += annotations_size(annotations);
+    }
+    if let Some(properties) = &message.properties {
+        size += section_list_size(&properties_fields(properties));
+    }
+    if let Some(application_properties) = &message.application_properties {
+        size += map_section_size(application_properties);
+    }
+    match &message.body {
+        Some(crate::message::Body::Multiple(bodies)) => size += bodies.iter().map(body_section_size).sum::<usize>(),
+        Some(body) => size += body_section_size(body),
+        None => {}
+    }
+    if let Some(footer) = &message.footer {
+        size += map_section_size(footer);
+    }
+    size
+}
+
+fn body_section_size(body: &crate::message::Body) -> usize {
+    SECTION_DESCRIPTOR_SIZE
+        + match body {
+            crate::message::Body::Data(data) => variable_width_size(data.len()),
+            crate::message::Body::Sequence(items) if items.is_empty() => 1,
+            crate::message::Body::Sequence(items) => 9 + items.iter().map(encoded_size).sum::<usize>(),
+            crate::message::Body::Value(value) => encoded_size(value),
+            // Rejected by the encoder
+            crate::message::Body::Multiple(_) => 0,
+        }
+}
+
+/// Number of fields left once trailing absent fields are dropped
+pub(crate) fn present_len<T>(fields: &[T], is_null: impl Fn(&T) -> bool) -> usize {
+    fields.iter().rposition(|field| !is_null(field)).map_or(0, |last| last + 1)
+}
+
+/// Number of entries in a map whose count of keys and values is `count`
+fn map_entries(count: usize) -> Result<usize, AmqpError> {
+    if !count.is_multiple_of(2) {
+        return Err(AmqpError::decoding(format!("Map with an odd count of {}", count)));
+    }
+    Ok(count / 2)
+}
+
+/// Size of a binary, string or symbol with the given payload length
+#[inline]
+fn variable_width_size(len: usize) -> usize {
+    if len <= 255 {
+        2 + len
+    } else {
+        5 + len
+    }
+}
+
+/// A top-level value yielded by [`Decoder::values`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedValue {
+    /// Byte offset of the value in the decoded data
+    pub offset: usize,
+    /// Descriptor, if the value is described (a ulong or symbol)
+    pub descriptor: Option<AmqpValue>,
+    /// The value, without its descriptor
+    pub value: AmqpValue,
+}
+
+/// Iterator over the top-level values of a [`Decoder`]
+pub struct Values<'a> {
+    decoder: &'a mut Decoder,
+    failed: bool,
+}
+
+impl Iterator for Values<'_> {
+    type Item = Result<DecodedValue, AmqpError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || !self.decoder.has_remaining() {
+            return None;
+        }
+
+        let offset = self.decoder.position();
+        match self.decoder.decode_top_level() {
+            Ok((descriptor, value)) => Some(Ok(DecodedValue { offset, descriptor, value })),
+            Err(AmqpError::Decoding(message)) => {
+                self.failed = true;
+                Some(Err(AmqpError::decoding(format!(
+                    "{} at byte {} (value starting at byte {})",
+                    message,
+                    self.decoder.position(),
+                    offset
+                ))))
+            }
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl std::iter::FusedIterator for Values<'_> {}
+
+/// Default nesting limit of a [`DecoderConfig`]
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+
+/// Limits a [`Decoder`] enforces on individual values
+///
+/// Each limit is checked against the length a value declares, before its
+/// bytes are read, so a single String32 claiming 4 GiB fails at once
+/// whatever the size of the frame around it. `None` leaves the length
+/// bounded only by the data.
+///
+/// Nesting is limited by default, since lists, maps, arrays and described
+/// values are decoded recursively.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecoderConfig {
+    /// Longest string, in bytes
+    pub max_string_len: Option<usize>,
+    /// Longest symbol, in bytes
+    pub max_symbol_len: Option<usize>,
+    /// Longest binary, in bytes
+    pub max_binary_len: Option<usize>,
+    /// Deepest nesting of values, counting the outermost as 1
+    pub max_depth: Option<usize>,
+}
+
+impl Default for DecoderConfig {
+    fn default() -> Self {
+        DecoderConfig {
+            max_string_len: None,
+            max_symbol_len: None,
+            max_binary_len: None,
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+        }
+    }
+}
+
+impl DecoderConfig {
+    /// Fail if a declared length is over a limit, naming the limit
+    fn check(limit: Option<usize>, name: &str, kind: &str, len: usize) -> Result<(), AmqpError> {
+        match limit {
+            Some(limit) if len > limit => Err(AmqpError::decoding(format!(
+                "{} of {} bytes exceeds {} of {}",
+                kind, len, name, limit
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
 /// AMQP 1.0 Decoder
 pub struct Decoder {
     buffer: BytesMut,
+    /// Length of the original data, to report positions
+    len: usize,
+    /// Limits on individual values
+    config: DecoderConfig,
+    /// Nesting of the value being decoded
+    depth: usize,
 }
 
 impl Decoder {
     pub fn new(data: Vec<u8>) -> Self {
+        Self::with_config(data, DecoderConfig::default())
+    }
+
+    /// Create a decoder enforcing the given limits
+    pub fn with_config(data: Vec<u8>, config: DecoderConfig) -> Self {
         Decoder {
+            len: data.len(),
             buffer: BytesMut::from(data.as_slice()),
+            config,
+            depth: 0,
+        }
+    }
+
+    /// Get the limits enforced
+    pub fn config(&self) -> &DecoderConfig {
+        &self.config
+    }
+
+    /// Get the number of bytes decoded so far
+    pub fn position(&self) -> usize {
+        self.len - self.buffer.len()
+    }
+
+    /// Iterate over the remaining top-level values
+    ///
+    /// Each value may be described, as message sections are. Decoding
+    /// errors report the byte at which decoding failed and where the value
+    /// started; the iterator ends after the first error.
+    pub fn values(&mut self) -> Values<'_> {
+        Values {
+            decoder: self,
+            failed: false,
+        }
+    }
+
+    fn decode_top_level(&mut self) -> Result<(Option<AmqpValue>, AmqpValue), AmqpError> {
+        if !self.peek_described() {
+            return Ok((None, self.decode_value()?));
+        }
+        self.buffer.advance(1);
+        let descriptor = match self.decode_value()? {
+            descriptor @ (AmqpValue::Ulong(_) | AmqpValue::Symbol(_)) => descriptor,
+            other => return Err(AmqpError::decoding(format!("Invalid descriptor {:?}", other))),
+        };
+        Ok((Some(descriptor), self.decode_described_body()?))
+    }
+
+    /// Decode the value of a described type
+    ///
+    /// A map may have string keys as well as symbols, as the maps of
+    /// message sections written by other peers do.
+    fn decode_described_body(&mut self) -> Result<AmqpValue, AmqpError> {
+        match self.buffer.first().copied().map(TypeCode::try_from) {
+            Some(Ok(TypeCode::Map8 | TypeCode::Map32)) => Ok(AmqpValue::Map(self.decode_sized_map()?)),
+            _ => self.decode_value(),
         }
     }
 
     /// Decode an AMQP value
     pub fn decode_value(&mut self) -> Result<AmqpValue, AmqpError> {
+        if let Some(max_depth) = self.config.max_depth.filter(|max_depth| self.depth >= *max_depth) {
+            return Err(AmqpError::decoding(format!("Value nested deeper than max_depth of {}", max_depth)));
+        }
+        self.depth += 1;
+        let value = self.decode_nested_value();
+        self.depth -= 1;
+        value
+    }
+
+    fn decode_nested_value(&mut self) -> Result<AmqpValue, AmqpError> {
         if self.buffer.is_empty() {
             return Err(AmqpError::decoding("Unexpected end of data"));
         }
 
-        let type_code = self.buffer.get_u8();
+        let type_code = TypeCode::try_from(self.buffer.get_u8())?;
         match type_code {
-            x if x == TypeCode::Null as u8 => Ok(AmqpValue::Null),
-            x if x == TypeCode::BooleanTrue as u8 => Ok(AmqpValue::Boolean(true)),
-            x if x == TypeCode::BooleanFalse as u8 => Ok(AmqpValue::Boolean(false)),
-            x if x == TypeCode::Ubyte as u8 => self.decode_ubyte(),
-            x if x == TypeCode::Ushort as u8 => self.decode_ushort(),
-            x if x == TypeCode::Uint as u8 => self.decode_uint(),
-            x if x == TypeCode::Ulong as u8 => self.decode_ulong(),
-            x if x == TypeCode::Byte as u8 => self.decode_byte(),
-            x if x == TypeCode::Short as u8 => self.decode_short(),
-            x if x == TypeCode::Int as u8 => self.decode_int(),
-            x if x == TypeCode::Long as u8 => self.decode_long(),
-            x if x == TypeCode::Float as u8 => self.decode_float(),
-            x if x == TypeCode::Double as u8 => self.decode_double(),
-            x if x == TypeCode::Char as u8 => self.decode_char(),
-            x if x == TypeCode::Timestamp as u8 => self.decode_timestamp(),
-            x if x == TypeCode::Uuid as u8 => self.decode_uuid(),
-            x if x == TypeCode::Binary8 as u8 => self.decode_binary8(),
-            x if x == TypeCode::Binary32 as u8 => self.decode_binary32(),
-            x if x == TypeCode::String8 as u8 => self.decode_string8(),
-            x if x == TypeCode::String32 as u8 => self.decode_string32(),
-            x if x == TypeCode::Symbol8 as u8 => self.decode_symbol8(),
-            x if x == TypeCode::Symbol32 as u8 => self.decode_symbol32(),
-            x if x == TypeCode::List0 as u8 => Ok(AmqpValue::List(vec![])),
-            x if x == TypeCode::List8 as u8 => {
-                let count = self.buffer.get_u8() as usize;
-                let mut items = Vec::with_capacity(count);
-                for _ in 0..count {
-                    items.push(self.decode_value()?);
-                }
-                Ok(AmqpValue::List(items))
+            TypeCode::Described => {
+                let descriptor = match self.decode_value()? {
+                    AmqpValue::Ulong(code) => Descriptor::Code(code),
+                    AmqpValue::Symbol(symbol) => Descriptor::Symbol(symbol),
+                    other => return Err(AmqpError::decoding(format!("Invalid descriptor {:?}", other))),
+                };
+                let value = self.decode_described_body()?;
+                Ok(AmqpValue::Described(Box::new(descriptor), Box::new(value)))
             }
-            x if x == TypeCode::List32 as u8 => {
-                let count = self.buffer.get_u32() as usize;
-                let mut items = Vec::with_capacity(count);
-                for _ in 0..count {
-                    items.push(self.decode_value()?);
-                }
-                Ok(AmqpValue::List(items))
+            TypeCode::Null => Ok(AmqpValue::Null),
+            TypeCode::Boolean => Ok(AmqpValue::Boolean(self.read_u8()? != 0)),
+            TypeCode::BooleanTrue => Ok(AmqpValue::Boolean(true)),
+            TypeCode::BooleanFalse => Ok(AmqpValue::Boolean(false)),
+            TypeCode::Ubyte => self.decode_ubyte(),
+            TypeCode::Ushort => self.decode_ushort(),
+            TypeCode::Uint => self.decode_uint(),
+            TypeCode::Ulong => self.decode_ulong(),
+            TypeCode::Uint0 => Ok(AmqpValue::Uint(0)),
+            TypeCode::Ulong0 => Ok(AmqpValue::Ulong(0)),
+            TypeCode::SmallUint => Ok(AmqpValue::Uint(self.read_u8()? as u32)),
+            TypeCode::SmallUlong => Ok(AmqpValue::Ulong(self.read_u8()? as u64)),
+            TypeCode::Byte => self.decode_byte(),
+            TypeCode::Short => self.decode_short(),
+            TypeCode::Int => self.decode_int(),
+            TypeCode::Long => self.decode_long(),
+            TypeCode::SmallInt => Ok(AmqpValue::Int(self.read_u8()? as i8 as i32)),
+            TypeCode::SmallLong => Ok(AmqpValue::Long(self.read_u8()? as i8 as i64)),
+            TypeCode::Float => self.decode_float(),
+            TypeCode::Double => self.decode_double(),
+            TypeCode::Decimal32 => {
+                self.ensure_remaining(4)?;
+                Ok(AmqpValue::Decimal32(self.buffer.get_u32()))
             }
-            x if x == TypeCode::Map8 as u8 => {
-                let count = self.buffer.get_u8() as usize;
-                let mut map = std::collections::HashMap::new();
-                for _ in 0..count {
-                    let key = self.decode_symbol()?;
-                    let value = self.decode_value()?;
-                    map.insert(key, value);
-                }
-                Ok(AmqpValue::Map(map))
+            TypeCode::Decimal64 => {
+                self.ensure_remaining(8)?;
+                Ok(AmqpValue::Decimal64(self.buffer.get_u64()))
             }
-            x if x == TypeCode::Map32 as u8 => {
-                let count = self.buffer.get_u32() as usize;
-                let mut map = std::collections::HashMap::new();
-                for _ in 0..count {
-                    let key = self.decode_symbol()?;
-                    let value = self.decode_value()?;
-                    map.insert(key, value);
-                }
-                Ok(AmqpValue::Map(map))
+            TypeCode::Decimal128 => {
+                self.ensure_remaining(16)?;
+                Ok(AmqpValue::Decimal128(self.buffer.get_u128()))
+            }
+            TypeCode::Char => self.decode_char(),
+            TypeCode::Timestamp => self.decode_timestamp(),
+            TypeCode::Uuid => self.decode_uuid(),
+            TypeCode::Binary8 => self.decode_binary8(),
+            TypeCode::Binary32 => self.decode_binary32(),
+            TypeCode::String8 => self.decode_string8(),
+            TypeCode::String32 => self.decode_string32(),
+            TypeCode::Symbol8 => self.decode_symbol8(),
+            TypeCode::Symbol32 => self.decode_symbol32(),
+            TypeCode::List0 => Ok(AmqpValue::List(AmqpList::new())),
+            TypeCode::List8 | TypeCode::List32 => {
+                let count = self.decode_compound_header(type_code)?;
+                self.decode_list_items(count)
+            }
+            TypeCode::Map8 | TypeCode::Map32 => {
+                let count = self.decode_compound_header(type_code)?;
+                self.decode_map_entries(map_entries(count)?)
+            }
+            TypeCode::Array8 => self.decode_array8(),
+            TypeCode::Array32 => self.decode_array32(),
+        }
+    }
+
+    /// Decode the size and count following a list8, list32, map8 or map32
+    /// constructor, returning the count
+    ///
+    /// The size must not run past the data, nor be too small for the count.
+    fn decode_compound_header(&mut self, type_code: TypeCode) -> Result<usize, AmqpError> {
+        let (size, count, width) = match type_code {
+            TypeCode::List8 | TypeCode::Map8 => {
+                self.ensure_remaining(2)?;
+                (self.buffer.get_u8() as usize, self.buffer.get_u8() as usize, 1)
+            }
+            _ => {
+                self.ensure_remaining(8)?;
+                (self.buffer.get_u32() as usize, self.buffer.get_u32() as usize, 4)
             }
-            x if x == TypeCode::Array8 as u8 => self.decode_array8(),
-            x if x == TypeCode::Array32 as u8 => self.decode_array32(),
-            _ => Err(AmqpError::decoding(&format!("Unknown type code: 0x{:02x}", type_code))),
+        };
+        if size < width || size - width > self.buffer.len() || count > size - width {
+            return Err(AmqpError::decoding(format!(
+                "{:?} of size {} cannot hold {} elements in the {} bytes left",
+                type_code,
+                size,
+                count,
+                self.buffer.len()
+            )));
         }
+        Ok(count)
+    }
+
+    fn decode_list_items(&mut self, count: usize) -> Result<AmqpValue, AmqpError> {
+        let mut items = Vec::with_capacity(count.min(self.buffer.len()));
+        for _ in 0..count {
+            items.push(self.decode_value()?);
+        }
+        Ok(AmqpValue::List(items.into()))
+    }
+
+    fn decode_map_entries(&mut self, count: usize) -> Result<AmqpValue, AmqpError> {
+        let mut map = std::collections::HashMap::new();
+        for _ in 0..count {
+            let key = self.decode_symbol()?;
+            let value = self.decode_value()?;
+            map.insert(key, value);
+        }
+        Ok(AmqpValue::Map(map.into()))
     }
 
     fn decode_ubyte(&mut self) -> Result<AmqpValue, AmqpError> {
@@ -730,7 +1430,7 @@ impl Decoder {
             return Err(AmqpError::decoding("Insufficient data for UUID"));
         }
         let uuid_bytes = self.buffer.get_u128();
-        Ok(AmqpValue::Uuid(uuid::Uuid::from_u128(uuid_bytes)))
+        Ok(AmqpValue::Uuid(crate::types::Uuid::from_u128(uuid_bytes)))
     }
 
     fn decode_binary8(&mut self) -> Result<AmqpValue, AmqpError> {
@@ -738,11 +1438,11 @@ impl Decoder {
             return Err(AmqpError::decoding("Insufficient data for binary8 length"));
         }
         let len = self.buffer.get_u8() as usize;
+        DecoderConfig::check(self.config.max_binary_len, "max_binary_len", "binary8", len)?;
         if self.buffer.remaining() < len {
             return Err(AmqpError::decoding("Insufficient data for binary8"));
         }
-        let data = self.buffer.copy_to_bytes(len);
-        Ok(AmqpValue::Binary(data.to_vec()))
+        Ok(AmqpValue::Binary(self.buffer.copy_to_bytes(len)))
     }
 
     fn decode_binary32(&mut self) -> Result<AmqpValue, AmqpError> {
@@ -750,11 +1450,11 @@ impl Decoder {
             return Err(AmqpError::decoding("Insufficient data for binary32 length"));
         }
         let len = self.buffer.get_u32() as usize;
+        DecoderConfig::check(self.config.max_binary_len, "max_binary_len", "binary32", len)?;
         if self.buffer.remaining() < len {
             return Err(AmqpError::decoding("Insufficient data for binary32"));
         }
-        let data = self.buffer.copy_to_bytes(len);
-        Ok(AmqpValue::Binary(data.to_vec()))
+        Ok(AmqpValue::Binary(self.buffer.copy_to_bytes(len)))
     }
 
     fn decode_string8(&mut self) -> Result<AmqpValue, AmqpError> {
@@ -762,13 +1462,14 @@ impl Decoder {
             return Err(AmqpError::decoding("Insufficient data for string8 length"));
         }
         let len = self.buffer.get_u8() as usize;
+        DecoderConfig::check(self.config.max_string_len, "max_string_len", "string8", len)?;
         if self.buffer.remaining() < len {
             return Err(AmqpError::decoding("Insufficient data for string8"));
         }
         let data = self.buffer.copy_to_bytes(len);
         String::from_utf8(data.to_vec())
             .map(AmqpValue::String)
-            .map_err(|e| AmqpError::decoding(&format!("Invalid UTF-8 string: {}", e)))
+            .map_err(|e| AmqpError::decoding(format!("Invalid UTF-8 string: {}", e)))
     }
 
     fn decode_string32(&mut self) -> Result<AmqpValue, AmqpError> {
@@ -776,13 +1477,14 @@ impl Decoder {
             return Err(AmqpError::decoding("Insufficient data for string32 length"));
         }
         let len = self.buffer.get_u32() as usize;
+        DecoderConfig::check(self.config.max_string_len, "max_string_len", "string32", len)?;
         if self.buffer.remaining() < len {
             return Err(AmqpError::decoding("Insufficient data for string32"));
         }
         let data = self.buffer.copy_to_bytes(len);
         String::from_utf8(data.to_vec())
             .map(AmqpValue::String)
-            .map_err(|e| AmqpError::decoding(&format!("Invalid UTF-8 string: {}", e)))
+            .map_err(|e| AmqpError::decoding(format!("Invalid UTF-8 string: {}", e)))
     }
 
     fn decode_symbol8(&mut self) -> Result<AmqpValue, AmqpError> {
@@ -790,13 +1492,14 @@ impl Decoder {
             return Err(AmqpError::decoding("Insufficient data for symbol8"));
         }
         let len = self.buffer.get_u8() as usize;
+        DecoderConfig::check(self.config.max_symbol_len, "max_symbol_len", "symbol8", len)?;
         if self.buffer.remaining() < len {
             return Err(AmqpError::decoding("Insufficient data for symbol8"));
         }
         let data = self.buffer.copy_to_bytes(len);
         String::from_utf8(data.to_vec())
             .map(|s| AmqpValue::Symbol(AmqpSymbol(s)))
-            .map_err(|e| AmqpError::decoding(&format!("Invalid UTF-8 symbol: {}", e)))
+            .map_err(|e| AmqpError::decoding(format!("Invalid UTF-8 symbol: {}", e)))
     }
 
     fn decode_symbol32(&mut self) -> Result<AmqpValue, AmqpError> {
@@ -804,13 +1507,14 @@ impl Decoder {
             return Err(AmqpError::decoding("Insufficient data for symbol32"));
         }
         let len = self.buffer.get_u32() as usize;
+        DecoderConfig::check(self.config.max_symbol_len, "max_symbol_len", "symbol32", len)?;
         if self.buffer.remaining() < len {
             return Err(AmqpError::decoding("Insufficient data for symbol32"));
         }
         let data = self.buffer.copy_to_bytes(len);
         String::from_utf8(data.to_vec())
             .map(|s| AmqpValue::Symbol(AmqpSymbol(s)))
-            .map_err(|e| AmqpError::decoding(&format!("Invalid UTF-8 symbol: {}", e)))
+            .map_err(|e| AmqpError::decoding(format!("Invalid UTF-8 symbol: {}", e)))
     }
 
     fn decode_array8(&mut self) -> Result<AmqpValue, AmqpError> {
@@ -823,13 +1527,17 @@ impl Decoder {
         if self.buffer.remaining() < size {
             return Err(AmqpError::decoding("Insufficient data for array8"));
         }
+        // Every element takes at least a byte
+        if count > self.buffer.remaining() {
+            return Err(AmqpError::decoding(format!("array8 count of {} exceeds its data", count)));
+        }
         
-        let mut items = Vec::with_capacity(count);
+        let mut items = Vec::with_capacity(count.min(self.buffer.len()));
         for _ in 0..count {
             items.push(self.decode_value()?);
         }
         
-        Ok(AmqpValue::Array(items))
+        Ok(AmqpValue::Array(items.into()))
     }
 
     fn decode_array32(&mut self) -> Result<AmqpValue, AmqpError> {
@@ -842,13 +1550,17 @@ impl Decoder {
         if self.buffer.remaining() < size {
             return Err(AmqpError::decoding("Insufficient data for array32"));
         }
+        // Every element takes at least a byte
+        if count > self.buffer.remaining() {
+            return Err(AmqpError::decoding(format!("array32 count of {} exceeds its data", count)));
+        }
         
-        let mut items = Vec::with_capacity(count);
+        let mut items = Vec::with_capacity(count.min(self.buffer.len()));
         for _ in 0..count {
             items.push(self.decode_value()?);
         }
         
-        Ok(AmqpValue::Array(items))
+        Ok(AmqpValue::Array(items.into()))
     }
 
     /// Decode a symbol
@@ -858,25 +1570,122 @@ impl Decoder {
         }
 
         let type_code = self.buffer.get_u8();
-        match type_code {
-            x if x == TypeCode::Symbol8 as u8 => {
-                let value = self.decode_symbol8()?;
-                match value {
-                    AmqpValue::Symbol(s) => Ok(s),
-                    _ => Err(AmqpError::decoding("Expected symbol value")),
-                }
+        let value = match TypeCode::try_from(type_code) {
+            Ok(TypeCode::Symbol8) => self.decode_symbol8()?,
+            Ok(TypeCode::Symbol32) => self.decode_symbol32()?,
+            _ => return Err(AmqpError::decoding(format!("Invalid symbol type code: {}", type_code))),
+        };
+        match value {
+            AmqpValue::Symbol(s) => Ok(s),
+            _ => Err(AmqpError::decoding("Expected symbol value")),
+        }
+    }
+
+    /// Decode an annotations map, accepting symbol and ulong keys
+    pub fn decode_annotations(&mut self) -> Result<Annotations, AmqpError> {
+        let count = match TypeCode::try_from(self.read_u8()?)? {
+            type_code @ (TypeCode::Map8 | TypeCode::Map32) => map_entries(self.decode_compound_header(type_code)?)?,
+            other => return Err(AmqpError::decoding(format!("Expected annotations map, got {:?}", other))),
+        };
+
+        let mut annotations = Annotations::with_capacity(count.min(self.buffer.len()));
+        for _ in 0..count {
+            let key = match self.decode_value()? {
+                AmqpValue::Symbol(symbol) => AnnotationKey::Symbol(symbol),
+                AmqpValue::Ulong(code) => AnnotationKey::Ulong(code),
+                other => return Err(AmqpError::decoding(format!("Invalid annotation key: {:?}", other))),
+            };
+            let value = self.decode_value()?;
+            annotations.insert(key, value);
+        }
+        Ok(annotations)
+    }
+
+    /// Decode a described list, returning the descriptor and the list fields
+    ///
+    /// Only the fields present in the encoding are returned, so a field the
+    /// peer left out can be told apart from one it sent as null.
+    pub fn decode_described_list(&mut self) -> Result<(u64, Vec<AmqpValue>), AmqpError> {
+        let (descriptor, count) = self.decode_described_header()?;
+
+        let mut fields = Vec::with_capacity(count.min(self.buffer.len()));
+        for _ in 0..count {
+            fields.push(self.decode_value()?);
+        }
+        Ok((descriptor, fields))
+    }
+
+    /// Decode a described list, padding omitted trailing fields with null
+    ///
+    /// The result has at least `arity` fields, the number the composite type
+    /// defines. Extra fields from a newer peer are kept rather than rejected.
+    pub fn decode_described_fields(&mut self, arity: usize) -> Result<(u64, Vec<AmqpValue>), AmqpError> {
+        let (descriptor, mut fields) = self.decode_described_list()?;
+        if fields.len() < arity {
+            fields.resize(arity, AmqpValue::Null);
+        }
+        Ok((descriptor, fields))
+    }
+
+    /// Decode the descriptor and field count of a described list
+    ///
+    /// The fields are left in the buffer so callers can decode them one by one,
+    /// for example when a field is itself a described list.
+    pub fn decode_described_header(&mut self) -> Result<(u64, usize), AmqpError> {
+        if self.buffer.is_empty() {
+            return Err(AmqpError::decoding("No data to decode"));
+        }
+        if self.buffer.get_u8() != TypeCode::Described as u8 {
+            return Err(AmqpError::decoding("Expected described type"));
+        }
+
+        let descriptor = match TypeCode::try_from(self.read_u8()?)? {
+            TypeCode::Ulong0 => 0,
+            TypeCode::SmallUlong => self.read_u8()? as u64,
+            TypeCode::Ulong => {
+                self.ensure_remaining(8)?;
+                self.buffer.get_u64()
             }
-            x if x == TypeCode::Symbol32 as u8 => {
-                let value = self.decode_symbol32()?;
-                match value {
-                    AmqpValue::Symbol(s) => Ok(s),
-                    _ => Err(AmqpError::decoding("Expected symbol value")),
-                }
+            other => return Err(AmqpError::decoding(format!("Invalid descriptor type code: {:?}", other))),
+        };
+
+        Ok((descriptor, self.decode_list_header()?))
+    }
+
+    /// Decode the size and count of a described list, returning the count
+    fn decode_list_header(&mut self) -> Result<usize, AmqpError> {
+        match TypeCode::try_from(self.read_u8()?)? {
+            TypeCode::List0 => Ok(0),
+            TypeCode::List8 => {
+                let _size = self.read_u8()?;
+                Ok(self.read_u8()? as usize)
             }
-            _ => Err(AmqpError::decoding(&format!("Invalid symbol type code: {}", type_code))),
+            TypeCode::List32 => {
+                self.ensure_remaining(8)?;
+                let _size = self.buffer.get_u32();
+                Ok(self.buffer.get_u32() as usize)
+            }
+            other => Err(AmqpError::decoding(format!("Invalid described list type code: {:?}", other))),
         }
     }
 
+    /// Check whether the next value is a described type
+    pub fn peek_described(&self) -> bool {
+        self.buffer.first() == Some(&(TypeCode::Described as u8))
+    }
+
+    fn read_u8(&mut self) -> Result<u8, AmqpError> {
+        self.ensure_remaining(1)?;
+        Ok(self.buffer.get_u8())
+    }
+
+    fn ensure_remaining(&self, len: usize) -> Result<(), AmqpError> {
+        if self.buffer.len() < len {
+            return Err(AmqpError::decoding("Unexpected end of data"));
+        }
+        Ok(())
+    }
+
     /// Check if there's more data to decode
     pub fn has_remaining(&self) -> bool {
         !self.buffer.is_empty()
@@ -888,131 +1697,207 @@ impl Decoder {
     }
 
     /// Decode an AMQP message
+    ///
+    /// Reads the described sections written by [`Encoder::encode_message`],
+    /// accepting symbolic section descriptors too. Several body sections
+    /// become a [`Body::Multiple`](crate::message::Body::Multiple).
     pub fn decode_message(&mut self) -> Result<crate::message::Message, AmqpError> {
         let mut message = crate::message::Message::new();
 
-        // Decode message header
-        if self.has_remaining() {
-            let value = self.decode_value()?;
-            if let AmqpValue::Map(map) = value {
-                // For now, we'll create a simple header
-                let mut header = crate::message::Header::new();
-                if let Some(durable) = map.get(&AmqpSymbol::from("durable")) {
-                    if let AmqpValue::Boolean(val) = durable {
-                        header.durable = Some(*val);
-                    }
-                }
-                if let Some(priority) = map.get(&AmqpSymbol::from("priority")) {
-                    if let AmqpValue::Ubyte(val) = priority {
-                        header.priority = Some(*val);
-                    }
-                }
-                if let Some(ttl) = map.get(&AmqpSymbol::from("ttl")) {
-                    if let AmqpValue::Uint(val) = ttl {
-                        header.ttl = Some(*val);
-                    }
+        while self.has_remaining() {
+            match self.decode_section_code()? {
+                section::HEADER => {
+                    // Absent fields take their spec default
+                    let mut header = crate::message::Header::new();
+                    header.durable = Some(false);
+                    header.priority = Some(crate::message::DEFAULT_PRIORITY);
+                    header.first_acquirer = Some(false);
+                    header.delivery_count = Some(0);
+                    self.decode_section_fields(|index, value| match (index, value) {
+                        (0, AmqpValue::Boolean(val)) => header.durable = Some(val),
+                        (1, AmqpValue::Ubyte(val)) => header.priority = Some(val),
+                        (2, AmqpValue::Uint(val)) => header.ttl = Some(val),
+                        (3, AmqpValue::Boolean(val)) => header.first_acquirer = Some(val),
+                        (4, AmqpValue::Uint(val)) => header.delivery_count = Some(val),
+                        _ => {}
+                    })?;
+                    message.header = Some(header);
                 }
-                if let Some(first_acquirer) = map.get(&AmqpSymbol::from("first_acquirer")) {
-                    if let AmqpValue::Boolean(val) = first_acquirer {
-                        header.first_acquirer = Some(*val);
-                    }
+                section::DELIVERY_ANNOTATIONS => message.delivery_annotations = Some(self.decode_section_annotations()?),
+                section::MESSAGE_ANNOTATIONS => message.message_annotations = Some(self.decode_section_annotations()?),
+                section::PROPERTIES => {
+                    let mut properties = crate::message::Properties::new();
+                    self.decode_section_fields(|index, value| match (index, value) {
+                        (_, AmqpValue::Null) => {}
+                        (0, val) => properties.message_id = Some(val),
+                        (1, AmqpValue::Binary(val)) => properties.user_id = Some(val.into()),
+                        (2, AmqpValue::String(val)) => properties.to = Some(val),
+                        (3, AmqpValue::String(val)) => properties.subject = Some(val),
+                        (4, AmqpValue::String(val)) => properties.reply_to = Some(val),
+                        (5, val) => properties.correlation_id = Some(val),
+                        (6, AmqpValue::Symbol(val)) => properties.content_type = Some(val),
+                        (7, AmqpValue::Symbol(val)) => properties.content_encoding = Some(val),
+                        (8, AmqpValue::Timestamp(val)) => properties.absolute_expiry_time = Some(val),
+                        (9, AmqpValue::Timestamp(val)) => properties.creation_time = Some(val),
+                        (10, AmqpValue::String(val)) => properties.group_id = Some(val),
+                        (11, AmqpValue::Uint(val)) => properties.group_sequence = Some(val),
+                        (12, AmqpValue::String(val)) => properties.reply_to_group_id = Some(val),
+                        _ => {}
+                    })?;
+                    message.properties = Some(properties);
                 }
-                if let Some(delivery_count) = map.get(&AmqpSymbol::from("delivery_count")) {
-                    if let AmqpValue::Uint(val) = delivery_count {
-                        header.delivery_count = Some(*val);
-                    }
+                section::APPLICATION_PROPERTIES => message.application_properties = Some(self.decode_sized_map()?),
+                section::DATA => match self.decode_value()? {
+                    AmqpValue::Binary(data) => add_body(&mut message, crate::message::Body::Data(data)),
+                    other => return Err(AmqpError::decoding(format!("Data section holds {:?}, not binary", other))),
+                },
+                section::AMQP_SEQUENCE => match self.decode_described_body()? {
+                    AmqpValue::List(items) => add_body(&mut message, crate::message::Body::Sequence(items)),
+                    other => return Err(AmqpError::decoding(format!("amqp-sequence section holds {:?}, not a list", other))),
+                },
+                section::AMQP_VALUE => {
+                    let value = self.decode_described_body()?;
+                    add_body(&mut message, crate::message::Body::Value(value));
                 }
-                message.header = Some(header);
+                section::FOOTER => message.footer = Some(self.decode_sized_map()?),
+                other => return Err(AmqpError::decoding(format!("Unknown message section 0x{:02x}", other))),
             }
         }
 
-        // Decode message properties
-        if self.has_remaining() {
+        Ok(message)
+    }
+
+    /// Decode the descriptor of a message section as its numeric code
+    fn decode_section_code(&mut self) -> Result<u64, AmqpError> {
+        if !self.peek_described() {
+            return Err(AmqpError::decoding("Expected a described message section"));
+        }
+        self.buffer.advance(1);
+        match self.decode_value()? {
+            AmqpValue::Ulong(code) => Ok(code),
+            AmqpValue::Symbol(name) => match name.as_str() {
+                "amqp:header:list" => Ok(section::HEADER),
+                "amqp:delivery-annotations:map" => Ok(section::DELIVERY_ANNOTATIONS),
+                "amqp:message-annotations:map" => Ok(section::MESSAGE_ANNOTATIONS),
+                "amqp:properties:list" => Ok(section::PROPERTIES),
+                "amqp:application-properties:map" => Ok(section::APPLICATION_PROPERTIES),
+                "amqp:data:binary" => Ok(section::DATA),
+                "amqp:amqp-sequence:list" => Ok(section::AMQP_SEQUENCE),
+                "amqp:amqp-value:*" => Ok(section::AMQP_VALUE),
+                "amqp:footer:map" => Ok(section::FOOTER),
+                other => Err(AmqpError::decoding(format!("Unknown message section {}", other))),
+            },
+            other => Err(AmqpError::decoding(format!("Invalid descriptor {:?}", other))),
+        }
+    }
+
+    /// Decode the fields of a header or properties section one by one
+    fn decode_section_fields(&mut self, mut field: impl FnMut(usize, AmqpValue)) -> Result<(), AmqpError> {
+        let count = self.decode_list_header()?;
+        for index in 0..count {
             let value = self.decode_value()?;
-            if let AmqpValue::Map(map) = value {
-                // For now, we'll create a simple properties
-                let mut properties = crate::message::Properties::new();
-                if let Some(message_id) = map.get(&AmqpSymbol::from("message_id")) {
-                    properties.message_id = Some(message_id.clone());
-                }
-                if let Some(user_id) = map.get(&AmqpSymbol::from("user_id")) {
-                    if let AmqpValue::Binary(val) = user_id {
-                        properties.user_id = Some(val.clone());
-                    }
-                }
-                if let Some(to) = map.get(&AmqpSymbol::from("to")) {
-                    if let AmqpValue::String(val) = to {
-                        properties.to = Some(val.clone());
-                    }
-                }
-                if let Some(subject) = map.get(&AmqpSymbol::from("subject")) {
-                    if let AmqpValue::String(val) = subject {
-                        properties.subject = Some(val.clone());
-                    }
-                }
-                if let Some(reply_to) = map.get(&AmqpSymbol::from("reply_to")) {
-                    if let AmqpValue::String(val) = reply_to {
-                        properties.reply_to = Some(val.clone());
-                    }
-                }
-                if let Some(correlation_id) = map.get(&AmqpSymbol::from("correlation_id")) {
-                    properties.correlation_id = Some(correlation_id.clone());
-                }
-                if let Some(content_type) = map.get(&AmqpSymbol::from("content_type")) {
-                    if let AmqpValue::Symbol(val) = content_type {
-                        properties.content_type = Some(val.clone());
-                    }
-                }
-                if let Some(content_encoding) = map.get(&AmqpSymbol::from("content_encoding")) {
-                    if let AmqpValue::Symbol(val) = content_encoding {
-                        properties.content_encoding = Some(val.clone());
-                    }
-                }
-                if let Some(absolute_expiry_time) = map.get(&AmqpSymbol::from("absolute_expiry_time")) {
-                    if let AmqpValue::Timestamp(val) = absolute_expiry_time {
-                        properties.absolute_expiry_time = Some(*val);
-                    }
-                }
-                if let Some(creation_time) = map.get(&AmqpSymbol::from("creation_time")) {
-                    if let AmqpValue::Timestamp(val) = creation_time {
-                        properties.creation_time = Some(*val);
-                    }
-                }
-                if let Some(group_id) = map.get(&AmqpSymbol::from("group_id")) {
-                    if let AmqpValue::String(val) = group_id {
-                        properties.group_id = Some(val.clone());
-                    }
-                }
-                if let Some(group_sequence) = map.get(&AmqpSymbol::from("group_sequence")) {
-                    if let AmqpValue::Uint(val) = group_sequence {
-                        properties.group_sequence = Some(*val);
-                    }
-                }
-                if let Some(reply_to_group_id) = map.get(&AmqpSymbol::from("reply_to_group_id")) {
-                    if let AmqpValue::String(val) = reply_to_group_id {
-                        properties.reply_to_group_id = Some(val.clone());
-                    }
-                }
-                message.properties = Some(properties);
-            }
+            field(index, value);
         }
+        Ok(())
+    }
+
+    /// Decode the size and count of a map, returning the number of entries
+    fn decode_sized_map_header(&mut self) -> Result<usize, AmqpError> {
+        match TypeCode::try_from(self.read_u8()?)? {
+            type_code @ (TypeCode::Map8 | TypeCode::Map32) => map_entries(self.decode_compound_header(type_code)?),
+            other => Err(AmqpError::decoding(format!("Expected a map, got {:?}", other))),
+        }
+    }
 
-        // Decode message body
-        if self.has_remaining() {
+    /// Decode a map with its size, whose keys are strings or symbols
+    ///
+    /// Application properties and footers are such maps.
+    fn decode_sized_map(&mut self) -> Result<AmqpMap, AmqpError> {
+        let count = self.decode_sized_map_header()?;
+        let mut map = std::collections::HashMap::with_capacity(count.min(self.buffer.len()));
+        for _ in 0..count {
+            let key = match self.decode_value()? {
+                AmqpValue::String(key) => AmqpSymbol(key),
+                AmqpValue::Symbol(key) => key,
+                other => return Err(AmqpError::decoding(format!("Invalid map key: {:?}", other))),
+            };
             let value = self.decode_value()?;
-            message.body = Some(crate::message::Body::Value(value));
+            map.insert(key, value);
         }
+        Ok(AmqpMap::from(map))
+    }
 
-        Ok(message)
+    /// Decode a delivery or message annotations section
+    fn decode_section_annotations(&mut self) -> Result<Annotations, AmqpError> {
+        let count = self.decode_sized_map_header()?;
+        let mut annotations = Annotations::with_capacity(count.min(self.buffer.len()));
+        for _ in 0..count {
+            let key = match self.decode_value()? {
+                AmqpValue::Symbol(symbol) => AnnotationKey::Symbol(symbol),
+                AmqpValue::Ulong(code) => AnnotationKey::Ulong(code),
+                other => return Err(AmqpError::decoding(format!("Invalid annotation key: {:?}", other))),
+            };
+            let value = self.decode_value()?;
+            annotations.insert(key, value);
+        }
+        Ok(annotations)
     }
-} 
+}
+
+/// Add a decoded body section to a message, after any earlier ones
+fn add_body(message: &mut crate::message::Message, body: crate::message::Body) {
+    message.body = Some(match message.body.take() {
+        None => body,
+        Some(crate::message::Body::Multiple(mut bodies)) => {
+            bodies.push(body);
+            crate::message::Body::Multiple(bodies)
+        }
+        Some(first) => crate::message::Body::Multiple(vec![first, body]),
+    });
+}
 
 #[cfg(test)]
+#[allow(clippy::approx_constant)] // 3.14159 is sample data, not an approximation of PI
 mod tests {
     use super::*;
     use crate::types::{AmqpList, AmqpMap, AmqpSymbol};
     use std::collections::HashMap;
-    use uuid::Uuid;
+    use crate::types::Uuid;
+    use bytes::Bytes;
+
+    #[test]
+    fn test_decoder_values_iterates_top_level() {
+        let mut encoder = Encoder::new();
+        encoder.encode_value(&AmqpValue::Int(7)).unwrap();
+        encoder.encode_described_list(0x70, &[AmqpValue::Boolean(true)]).unwrap();
+        encoder.encode_value(&AmqpValue::String("tail".to_string())).unwrap();
+        let mut decoder = Decoder::new(encoder.finish());
+
+        let values: Vec<DecodedValue> = decoder.values().collect::<Result<_, _>>().unwrap();
+        assert_eq!(values.len(), 3);
+        assert_eq!((values[0].offset, &values[0].value), (0, &AmqpValue::Int(7)));
+        assert_eq!(values[1].offset, 5);
+        assert_eq!(values[1].descriptor, Some(AmqpValue::Ulong(0x70)));
+        assert_eq!(values[1].value, AmqpValue::List(vec![AmqpValue::Boolean(true)].into()));
+        assert_eq!(values[2].value, AmqpValue::String("tail".to_string()));
+        assert!(!decoder.has_remaining());
+    }
+
+    #[test]
+    fn test_decoder_values_reports_error_position() {
+        let mut encoder = Encoder::new();
+        encoder.encode_value(&AmqpValue::Uint(1000)).unwrap();
+        encoder.encode_value(&AmqpValue::String("truncated".to_string())).unwrap();
+        let mut data = encoder.finish();
+        data.truncate(data.len() - 3);
+
+        let mut decoder = Decoder::new(data);
+        let mut values = decoder.values();
+        assert_eq!(values.next().unwrap().unwrap().value, AmqpValue::Uint(1000));
+        let error = values.next().unwrap().unwrap_err();
+        assert_eq!(error.to_string(), "Decoding error: Insufficient data for string8 at byte 7 (value starting at byte 5)");
+        assert!(values.next().is_none());
+    }
 
     #[test]
     fn test_type_code_values() {
@@ -1025,6 +1910,12 @@ mod tests {
         assert_eq!(TypeCode::Ushort as u8, 0x60);
         assert_eq!(TypeCode::Uint as u8, 0x70);
         assert_eq!(TypeCode::Ulong as u8, 0x80);
+        assert_eq!(TypeCode::SmallUlong as u8, 0x53);
+        assert_eq!(TypeCode::Uint0 as u8, 0x43);
+        assert_eq!(TypeCode::Ulong0 as u8, 0x44);
+        assert_eq!(TypeCode::SmallUint as u8, 0x52);
+        assert_eq!(TypeCode::SmallInt as u8, 0x54);
+        assert_eq!(TypeCode::SmallLong as u8, 0x55);
         assert_eq!(TypeCode::Byte as u8, 0x51);
         assert_eq!(TypeCode::Short as u8, 0x61);
         assert_eq!(TypeCode::Int as u8, 0x71);
@@ -1572,6 +2463,76 @@ mod tests {
         assert!(matches!(decoded, AmqpValue::Map(m) if m == map));
     }
 
+    #[test]
+    fn test_list_and_map_carry_size_and_count() {
+        let small = AmqpValue::List(vec![AmqpValue::Boolean(true), AmqpValue::Null].into());
+        let mut encoder = Encoder::new();
+        encoder.encode_value(&small).unwrap();
+        // list8: size of the count and items, then the count
+        assert_eq!(encoder.finish(), vec![0xc0, 0x03, 0x02, 0x41, 0x40]);
+
+        let large = AmqpValue::List(vec![AmqpValue::Null; 255].into());
+        let mut encoder = Encoder::new();
+        encoder.encode_value(&large).unwrap();
+        let encoded = encoder.finish();
+        assert_eq!(encoded[..9], [0xd0, 0x00, 0x00, 0x01, 0x03, 0x00, 0x00, 0x00, 0xff]);
+        assert_eq!(Decoder::new(encoded).decode_value().unwrap(), large);
+
+        let map = AmqpValue::Map(HashMap::from([(AmqpSymbol::from("k"), AmqpValue::Null)]).into());
+        let mut encoder = Encoder::new();
+        encoder.encode_value(&map).unwrap();
+        // map8: the count is of keys and values together
+        assert_eq!(encoder.finish(), vec![0xc1, 0x05, 0x02, 0xa3, 0x01, b'k', 0x40]);
+
+        let map = AmqpValue::Map(HashMap::from([(AmqpSymbol::from("k"), AmqpValue::String("v".repeat(300)))]).into());
+        let mut encoder = Encoder::new();
+        encoder.encode_value(&map).unwrap();
+        let encoded = encoder.finish();
+        assert_eq!(encoded[..9], [0xd1, 0x00, 0x00, 0x01, 0x38, 0x00, 0x00, 0x00, 0x02]);
+        assert_eq!(Decoder::new(encoded).decode_value().unwrap(), map);
+
+        // A size running past the data, or too small for the count, is rejected
+        assert!(Decoder::new(vec![0xc0, 0x05, 0x01, 0x40]).decode_value().is_err());
+        assert!(Decoder::new(vec![0xc0, 0x01, 0x02, 0x40, 0x40]).decode_value().is_err());
+        assert!(Decoder::new(vec![0xc1, 0x02, 0x01, 0x40]).decode_value().is_err());
+    }
+
+    #[test]
+    fn test_annotations_roundtrip_with_numeric_keys() {
+        let mut annotations = Annotations::new();
+        annotations.insert(AnnotationKey::from("x-opt-partition"), AmqpValue::Int(2));
+        annotations.insert(AnnotationKey::Ulong(0x0000_0137_0000_0001), AmqpValue::Boolean(true));
+
+        let mut encoder = Encoder::new();
+        encoder.encode_annotations(&annotations).unwrap();
+        let encoded = encoder.finish();
+        assert!(encoded.contains(&(TypeCode::Ulong as u8)));
+
+        let mut decoder = Decoder::new(encoded);
+        assert_eq!(decoder.decode_annotations().unwrap(), annotations);
+    }
+
+    #[test]
+    fn test_decode_annotations_small_ulong_key() {
+        // Map8 of size 5 and count 2: smallulong 0x2a -> uint 7
+        let data = vec![0xc1, 5, 2, TypeCode::SmallUlong as u8, 0x2a, TypeCode::SmallUint as u8, 7];
+        let mut decoder = Decoder::new(data);
+        let annotations = decoder.decode_annotations().unwrap();
+        assert_eq!(annotations.get(&AnnotationKey::Ulong(0x2a)), Some(&AmqpValue::Uint(7)));
+    }
+
+    #[test]
+    fn test_decode_annotations_rejects_string_key() {
+        let mut encoder = Encoder::new();
+        encoder.encode_string("key").unwrap();
+        let mut data = vec![0xc1, 1];
+        data.extend_from_slice(&encoder.finish());
+        data.push(TypeCode::Null as u8);
+
+        let mut decoder = Decoder::new(data);
+        assert!(decoder.decode_annotations().is_err());
+    }
+
     #[test]
     fn test_decoder_decode_array() {
         let mut encoder = Encoder::new();
@@ -1585,7 +2546,7 @@ mod tests {
         
         let mut decoder = Decoder::new(encoded);
         let decoded = decoder.decode_value().unwrap();
-        assert!(matches!(decoded, AmqpValue::Array(a) if a == array));
+        assert!(matches!(decoded, AmqpValue::Array(a) if a == array.into()));
     }
 
     #[test]
@@ -1607,7 +2568,7 @@ mod tests {
             AmqpValue::Char('A'),
             AmqpValue::Timestamp(1234567890),
             AmqpValue::Uuid(Uuid::new_v4()),
-            AmqpValue::Binary(vec![1, 2, 3, 4, 5]),
+            AmqpValue::Binary(vec![1, 2, 3, 4, 5].into()),
             AmqpValue::String("Hello, AMQP!".to_string()),
             AmqpValue::Symbol(AmqpSymbol::from("test-symbol")),
         ];
@@ -1659,4 +2620,488 @@ mod tests {
         let result = encoder.finish();
         assert!(!result.is_empty());
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_described_list_roundtrip() {
+        let fields = vec![AmqpValue::Null, AmqpValue::Uint(7), AmqpValue::String("x".to_string())];
+        let mut encoder = Encoder::new();
+        encoder.encode_described_list(0x11, &fields).unwrap();
+        let encoded = encoder.finish();
+
+        assert_eq!(&encoded[..4], &[0x00, 0x53, 0x11, 0xd0]);
+
+        let mut decoder = Decoder::new(encoded);
+        let (descriptor, decoded) = decoder.decode_described_list().unwrap();
+        assert_eq!(descriptor, 0x11);
+        assert_eq!(decoded, fields);
+        assert!(!decoder.has_remaining());
+    }
+
+    #[test]
+    fn test_described_list_trailing_nulls() {
+        let mut encoder = Encoder::new();
+        encoder
+            .encode_described_list(0x16, &[AmqpValue::Uint(3), AmqpValue::Null, AmqpValue::Null])
+            .unwrap();
+        let encoded = encoder.finish();
+        // list32 with a count of one: the trailing nulls are left out
+        assert_eq!(&encoded[3..12], &[0xd0, 0, 0, 0, 9, 0, 0, 0, 1]);
+
+        let (_, present) = Decoder::new(encoded.clone()).decode_described_list().unwrap();
+        assert_eq!(present, vec![AmqpValue::Uint(3)]);
+        let (_, padded) = Decoder::new(encoded).decode_described_fields(3).unwrap();
+        assert_eq!(padded, vec![AmqpValue::Uint(3), AmqpValue::Null, AmqpValue::Null]);
+
+        // All-null fields use list0, and extra fields survive padding
+        let mut encoder = Encoder::new();
+        encoder.encode_described_list(0x17, &[AmqpValue::Null]).unwrap();
+        assert_eq!(encoder.finish(), vec![0x00, 0x53, 0x17, 0x45]);
+        let mut encoder = Encoder::new();
+        encoder.encode_described_list(0x17, &[AmqpValue::Null, AmqpValue::Boolean(true)]).unwrap();
+        let (_, fields) = Decoder::new(encoder.finish()).decode_described_fields(1).unwrap();
+        assert_eq!(fields, vec![AmqpValue::Null, AmqpValue::Boolean(true)]);
+    }
+
+    #[test]
+    fn test_described_list_large_descriptor() {
+        let mut encoder = Encoder::new();
+        encoder.encode_described_list(0x0000_0468_0000_0001, &[]).unwrap();
+
+        let mut decoder = Decoder::new(encoder.finish());
+        let (descriptor, fields) = decoder.decode_described_list().unwrap();
+        assert_eq!(descriptor, 0x0000_0468_0000_0001);
+        assert!(fields.is_empty());
+    }
+
+    #[test]
+    fn test_decode_described_list_not_described() {
+        let mut decoder = Decoder::new(vec![TypeCode::Null as u8]);
+        let result = decoder.decode_described_list();
+        assert!(matches!(result, Err(AmqpError::Decoding { .. })));
+    }
+
+    #[test]
+    fn test_decode_described_list_truncated() {
+        let mut decoder = Decoder::new(vec![0x00, 0x53]);
+        let result = decoder.decode_described_list();
+        assert!(matches!(result, Err(AmqpError::Decoding { .. })));
+    }
+
+    #[test]
+    fn test_decoder_config_limits_value_lengths() {
+        let config = DecoderConfig { max_string_len: Some(4), max_symbol_len: Some(2), max_binary_len: Some(300), ..Default::default() };
+
+        let mut string32 = vec![0xb1, 0xff, 0xff, 0xff, 0xff];
+        string32.extend_from_slice(b"tiny");
+        let error = Decoder::with_config(string32.clone(), config).decode_value().unwrap_err();
+        assert_eq!(error.to_string(), "Decoding error: string32 of 4294967295 bytes exceeds max_string_len of 4");
+        let error = Decoder::new(string32).decode_value().unwrap_err();
+        assert_eq!(error.to_string(), "Decoding error: Insufficient data for string32");
+
+        let mut encoder = Encoder::new();
+        encoder.encode_value(&AmqpValue::String("four".to_string())).unwrap();
+        encoder.encode_symbol(&AmqpSymbol::from("abc")).unwrap();
+        encoder.encode_binary(&[0u8; 301]).unwrap();
+        let mut decoder = Decoder::with_config(encoder.finish(), config);
+        assert_eq!(decoder.decode_value().unwrap(), AmqpValue::String("four".to_string()));
+        let error = decoder.decode_value().unwrap_err();
+        assert_eq!(error.to_string(), "Decoding error: symbol8 of 3 bytes exceeds max_symbol_len of 2");
+
+        let mut encoder = Encoder::new();
+        encoder.encode_message(&crate::message::Message::binary(vec![0u8; 301])).unwrap();
+        let error = Decoder::with_config(encoder.finish(), config).decode_message().unwrap_err();
+        assert!(error.to_string().contains("binary32 of 301 bytes exceeds max_binary_len of 300"), "{}", error);
+    }
+
+    #[test]
+    fn test_decoder_rejects_oversized_array_count_and_deep_nesting() {
+        let error = Decoder::new(vec![0xf0, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff]).decode_value().unwrap_err();
+        assert_eq!(error.to_string(), "Decoding error: array32 count of 4294967295 exceeds its data");
+
+        let mut nested = [0xc0, 0xff, 0x01].repeat(200_000);
+        nested.push(0x40);
+        let error = Decoder::new(nested).decode_value().unwrap_err();
+        assert_eq!(error.to_string(), "Decoding error: Value nested deeper than max_depth of 64");
+
+        let shallow = [0xc0, 0x05, 0x01, 0xc0, 0x02, 0x01, 0x40].to_vec();
+        let config = DecoderConfig { max_depth: Some(2), ..Default::default() };
+        assert!(Decoder::with_config(shallow.clone(), config).decode_value().is_err());
+        let config = DecoderConfig { max_depth: Some(3), ..Default::default() };
+        assert!(Decoder::with_config(shallow, config).decode_value().is_ok());
+    }
+
+    #[test]
+    fn test_encoder_new_into_appends() {
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&[0xFF; 8]);
+
+        let mut encoder = Encoder::new_into(&mut buffer);
+        encoder.encode_string("test").unwrap();
+        let written = encoder.finish();
+
+        let mut expected = Encoder::new();
+        expected.encode_string("test").unwrap();
+        let expected = expected.finish();
+
+        assert_eq!(written, expected);
+        assert_eq!(&buffer[..8], &[0xFF; 8]);
+        assert_eq!(&buffer[8..], expected.as_slice());
+    }
+
+    #[test]
+    fn test_encode_value_into() {
+        let value = AmqpValue::List(vec![AmqpValue::Int(1), AmqpValue::String("two".to_string())].into());
+        let mut buffer = BytesMut::new();
+        encode_value_into(&mut buffer, &value).unwrap();
+
+        let mut decoder = Decoder::new(buffer.to_vec());
+        assert_eq!(decoder.decode_value().unwrap(), value);
+    }
+
+    #[test]
+    fn test_type_code_try_from_every_byte() {
+        let mut known = 0;
+        for byte in 0..=u8::MAX {
+            match TypeCode::try_from(byte) {
+                Ok(code) => {
+                    assert_eq!(code as u8, byte);
+                    known += 1;
+                }
+                Err(error) => assert!(matches!(error, AmqpError::Decoding { .. })),
+            }
+        }
+        assert_eq!(known, 40);
+    }
+
+    #[test]
+    fn test_decode_value_never_panics_on_truncated_input() {
+        for byte in 0..=u8::MAX {
+            let mut decoder = Decoder::new(vec![byte]);
+            let _ = decoder.decode_value();
+
+            let mut decoder = Decoder::new(vec![byte, 0xFF]);
+            let _ = decoder.decode_value();
+        }
+    }
+
+    #[test]
+    fn test_decode_compact_encodings() {
+        let cases = vec![
+            (vec![0x43], AmqpValue::Uint(0)),
+            (vec![0x44], AmqpValue::Ulong(0)),
+            (vec![0x52, 0x07], AmqpValue::Uint(7)),
+            (vec![0x53, 0x11], AmqpValue::Ulong(0x11)),
+            (vec![0x54, 0xFF], AmqpValue::Int(-1)),
+            (vec![0x55, 0x02], AmqpValue::Long(2)),
+            (vec![0x56, 0x01], AmqpValue::Boolean(true)),
+            (vec![0x56, 0x00], AmqpValue::Boolean(false)),
+        ];
+
+        for (bytes, expected) in cases {
+            let mut decoder = Decoder::new(bytes);
+            assert_eq!(decoder.decode_value().unwrap(), expected);
+            assert!(!decoder.has_remaining());
+        }
+    }
+
+    #[test]
+    fn test_decimal_roundtrip() {
+        let values = vec![
+            AmqpValue::Decimal32(0x1234_5678),
+            AmqpValue::Decimal64(0x1234_5678_9abc_def0),
+            AmqpValue::Decimal128(0x1234_5678_9abc_def0_1234_5678_9abc_def0),
+        ];
+
+        for value in values {
+            let mut encoder = Encoder::new();
+            encoder.encode_value(&value).unwrap();
+            let mut decoder = Decoder::new(encoder.finish());
+            assert_eq!(decoder.decode_value().unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_nan_roundtrip() {
+        for value in [AmqpValue::Float(f32::NAN), AmqpValue::Double(f64::NAN)] {
+            let mut encoder = Encoder::new();
+            encoder.encode_value(&value).unwrap();
+            let mut decoder = Decoder::new(encoder.finish());
+            assert_eq!(decoder.decode_value().unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_described_value_roundtrip() {
+        let mut decoder = Decoder::new(vec![0x00, 0x53, 0x70, 0x45]);
+        assert_eq!(decoder.decode_value().unwrap(), AmqpValue::described(0x70, AmqpValue::List(AmqpList::new())));
+
+        let values = vec![
+            AmqpValue::described("com.example:order", AmqpValue::String("o-1".to_string())),
+            AmqpValue::described(0x73, AmqpValue::List(vec![AmqpValue::String("id".to_string()), AmqpValue::Null].into())),
+            AmqpValue::List(vec![AmqpValue::described(1, AmqpValue::described(2, AmqpValue::Int(3)))].into()),
+        ];
+        for value in values {
+            let mut encoder = Encoder::new();
+            encoder.encode_value(&value).unwrap();
+            let bytes = encoder.finish();
+            assert_eq!(encoded_size(&value), bytes.len(), "{:?}", value);
+            assert_eq!(Decoder::new(bytes).decode_value().unwrap(), value);
+        }
+
+        let mut decoder = Decoder::new(vec![0x00, 0x54, 0x01, 0x40]);
+        assert!(matches!(decoder.decode_value(), Err(AmqpError::Decoding(_))));
+    }
+
+    #[test]
+    fn test_described_values_match_spec_bytes() {
+        let fields = AmqpValue::List(vec![AmqpValue::String("id".to_string()), AmqpValue::Null].into());
+        let entries = AmqpValue::Map(HashMap::from([(AmqpSymbol::from("k"), AmqpValue::Null)]).into());
+        let cases = vec![
+            // ulong descriptor, then a list8 of size 6 and count 2
+            (
+                AmqpValue::described(0x73, fields),
+                vec![0x00, 0x80, 0, 0, 0, 0, 0, 0, 0, 0x73, 0xc0, 0x06, 0x02, 0xa1, 0x02, b'i', b'd', 0x40],
+            ),
+            // map8 of size 5 and count 2, one key and one value
+            (
+                AmqpValue::described(0x77, entries),
+                vec![0x00, 0x80, 0, 0, 0, 0, 0, 0, 0, 0x77, 0xc1, 0x05, 0x02, 0xa3, 0x01, b'k', 0x40],
+            ),
+            (AmqpValue::described("x:y", AmqpValue::Boolean(true)), vec![0x00, 0xa3, 0x03, b'x', b':', b'y', 0x41]),
+        ];
+        for (value, bytes) in cases {
+            let mut encoder = Encoder::new();
+            encoder.encode_value(&value).unwrap();
+            assert_eq!(encoder.finish(), bytes, "{:?}", value);
+            assert_eq!(Decoder::new(bytes).decode_value().unwrap(), value);
+        }
+
+        // An amqp-value body section holding a list
+        let message = crate::message::Message::builder()
+            .body(crate::message::Body::Value(AmqpValue::List(vec![AmqpValue::Boolean(true)].into())))
+            .build();
+        let bytes = vec![0x00, 0x53, 0x77, 0xc0, 0x02, 0x01, 0x41];
+        let mut encoder = Encoder::new();
+        encoder.encode_message(&message).unwrap();
+        assert_eq!(encoder.finish(), bytes);
+        assert_eq!(message.encoded_size(), bytes.len());
+        assert_eq!(Decoder::new(bytes).decode_message().unwrap(), message);
+    }
+
+    #[test]
+    fn test_encoded_size_matches_encoder() {
+        let values = vec![
+            AmqpValue::Null,
+            AmqpValue::Boolean(true),
+            AmqpValue::Ushort(1),
+            AmqpValue::Char('a'),
+            AmqpValue::Timestamp(0),
+            AmqpValue::Decimal128(0),
+            AmqpValue::String("s".repeat(256)),
+            AmqpValue::Symbol(AmqpSymbol::from("sym")),
+            AmqpValue::List(AmqpList::new()),
+            AmqpValue::List(vec![AmqpValue::Int(1); 300].into()),
+            AmqpValue::List(vec![AmqpValue::Null; 254].into()),
+            AmqpValue::List(vec![AmqpValue::Null; 255].into()),
+            AmqpValue::Map(HashMap::from([(AmqpSymbol::from("k"), AmqpValue::String("v".repeat(300)))]).into()),
+            AmqpValue::Array(vec![AmqpValue::Ulong(1); 40].into()),
+        ];
+
+        for value in values {
+            let mut encoder = Encoder::new();
+            encoder.encode_value(&value).unwrap();
+            assert_eq!(encoded_size(&value), encoder.finish().len(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn test_footer_encoded_last() {
+        let mut message = crate::message::Message::builder()
+            .header(crate::message::Header::new())
+            .properties(crate::message::Properties::new())
+            .body(crate::message::Body::Value(AmqpValue::String("body".to_string())))
+            .build();
+        // Filled in after the body, as an interceptor late in the pipeline would
+        message
+            .footer_mut()
+            .insert(AmqpSymbol::from("checksum"), AmqpValue::Uint(7));
+
+        let mut encoder = Encoder::new();
+        encoder.encode_message(&message).unwrap();
+        let encoded = encoder.finish();
+        assert_eq!(encoded.len(), message.encoded_size());
+        assert!(encoded.ends_with(&[0xa3, 8, b'c', b'h', b'e', b'c', b'k', b's', b'u', b'm', 0x70, 0, 0, 0, 7]));
+
+        let decoded = Decoder::new(encoded).decode_message().unwrap();
+        assert_eq!(decoded.footer_value("checksum"), Some(&AmqpValue::Uint(7)));
+        assert_eq!(decoded.body_as_text(), Some("body"));
+    }
+
+    #[test]
+    fn test_sequence_sections_encoded_described() {
+        let message = crate::message::Message::builder()
+            .body(crate::message::Body::Multiple(vec![
+                crate::message::Body::Sequence(vec![AmqpValue::Int(1), AmqpValue::String("a".to_string())].into()),
+                crate::message::Body::Sequence(vec![AmqpValue::Int(2)].into()),
+            ]))
+            .build();
+
+        let mut encoder = Encoder::new();
+        encoder.encode_message(&message).unwrap();
+        let encoded = encoder.finish();
+        // Each sequence is its own amqp-sequence section (0x76) holding a list
+        assert_eq!(&encoded[..3], &[0x00, TypeCode::SmallUlong as u8, 0x76]);
+        assert_eq!(encoded.windows(3).filter(|window| window == &[0x00, TypeCode::SmallUlong as u8, 0x76]).count(), 2);
+
+        let decoded = Decoder::new(encoded).decode_message().unwrap();
+        let sequences: Vec<&AmqpList> = decoded.sequences().collect();
+        assert_eq!(sequences.len(), 2);
+        assert_eq!(sequences[0][1], AmqpValue::String("a".to_string()));
+        assert_eq!(sequences[1][0], AmqpValue::Int(2));
+    }
+
+    #[test]
+    fn test_header_defaults_omitted() {
+        let mut header = crate::message::Header::new();
+        header.durable = Some(false);
+        header.priority = Some(crate::message::DEFAULT_PRIORITY);
+        header.first_acquirer = Some(false);
+        header.delivery_count = Some(0);
+        let message = crate::message::Message::builder().header(header).build();
+
+        let mut encoder = Encoder::new();
+        encoder.encode_message(&message).unwrap();
+        // Header section with no fields: descriptor 0x70 and list0
+        assert_eq!(encoder.finish(), vec![0x00, TypeCode::SmallUlong as u8, 0x70, TypeCode::List0 as u8]);
+    }
+
+    #[test]
+    fn test_message_sections_roundtrip() {
+        // As decoded, with the defaults filled in
+        let header = crate::message::Header {
+            durable: Some(true),
+            priority: Some(crate::message::DEFAULT_PRIORITY),
+            ttl: None,
+            first_acquirer: Some(false),
+            delivery_count: Some(0),
+        };
+        let mut annotations = Annotations::new();
+        annotations.insert(AnnotationKey::from("x-opt-partition-key"), AmqpValue::String("eu".to_string()));
+        let mut application_properties = AmqpMap::new();
+        application_properties.insert(AmqpSymbol::from("tenant"), AmqpValue::String("acme".to_string()));
+        let mut properties = crate::message::Properties::new();
+        properties.subject = Some("orders".to_string());
+        let mut message = crate::message::Message::builder()
+            .header(header)
+            .message_annotations(annotations)
+            .properties(properties)
+            .application_properties(application_properties)
+            .body(crate::message::Body::Multiple(vec![
+                crate::message::Body::Data(Bytes::from_static(b"ab")),
+                crate::message::Body::Data(Bytes::from_static(b"cd")),
+            ]))
+            .build();
+        message.footer_mut().insert(AmqpSymbol::from("checksum"), AmqpValue::Uint(7));
+
+        let mut encoder = Encoder::new();
+        encoder.encode_message(&message).unwrap();
+        let encoded = encoder.finish();
+        assert_eq!(encoded.len(), message.encoded_size());
+
+        let mut decoder = Decoder::new(encoded.clone());
+        let codes: Vec<_> = decoder.values().map(|value| value.unwrap().descriptor).collect();
+        let expected: Vec<_> = [0x70, 0x72, 0x73, 0x74, 0x75, 0x75, 0x78].map(|code| Some(AmqpValue::Ulong(code))).into();
+        assert_eq!(codes, expected);
+        // Properties: subject is the fourth field, after three nulls
+        let properties = [0x00, 0x53, 0x73, TypeCode::List32 as u8];
+        let at = encoded.windows(4).position(|window| window == properties).unwrap();
+        assert_eq!(&encoded[at + 8..at + 15], &[0, 0, 0, 4, 0x40, 0x40, 0x40]);
+
+        assert_eq!(Decoder::new(encoded).decode_message().unwrap(), message);
+    }
+
+    #[test]
+    fn test_decode_message_from_other_peer() {
+        // Sections as another client library writes them: a symbolic header
+        // descriptor, list8 and map8 encodings and string property keys
+        let mut data = vec![0x00, 0xa3, 16];
+        data.extend_from_slice(b"amqp:header:list");
+        data.extend_from_slice(&[0xc0, 4, 2, 0x41, 0x50, 9]);
+        data.extend_from_slice(&[0x00, 0x53, 0x74, 0xc1, 7, 2, 0xa1, 2, b'i', b'd', 0x52, 7]);
+        data.extend_from_slice(&[0x00, 0x53, 0x77, 0xa1, 2, b'h', b'i']);
+
+        let message = Decoder::new(data).decode_message().unwrap();
+        assert!(message.is_durable());
+        assert_eq!(message.priority(), 9);
+        let application_properties = message.application_properties.as_ref().unwrap();
+        assert_eq!(application_properties.get(&AmqpSymbol::from("id")), Some(&AmqpValue::Uint(7)));
+        assert_eq!(message.body_as_text(), Some("hi"));
+
+        let unknown = vec![0x00, 0x53, 0x79, 0x40];
+        assert!(Decoder::new(unknown).decode_message().is_err());
+    }
+
+    #[test]
+    fn test_header_decode_fills_defaults() {
+        let mut header = crate::message::Header::new();
+        header.durable = Some(true);
+        header.ttl = Some(1000);
+        let message = crate::message::Message::builder()
+            .header(header)
+            .properties(crate::message::Properties::new())
+            .build();
+
+        let mut encoder = Encoder::new();
+        encoder.encode_message(&message).unwrap();
+        let decoded = Decoder::new(encoder.finish()).decode_message().unwrap();
+
+        let header = decoded.header.unwrap();
+        assert_eq!(header.durable, Some(true));
+        assert_eq!(header.priority, Some(crate::message::DEFAULT_PRIORITY));
+        assert_eq!(header.ttl, Some(1000));
+        assert_eq!(header.first_acquirer, Some(false));
+        assert_eq!(header.delivery_count, Some(0));
+    }
+
+    #[test]
+    fn test_header_non_default_priority_roundtrip() {
+        let mut header = crate::message::Header::new();
+        header.priority = Some(9);
+        let message = crate::message::Message::builder()
+            .header(header)
+            .properties(crate::message::Properties::new())
+            .build();
+
+        let mut encoder = Encoder::new();
+        encoder.encode_message(&message).unwrap();
+        let decoded = Decoder::new(encoder.finish()).decode_message().unwrap();
+        assert_eq!(decoded.priority(), 9);
+        assert!(!decoded.is_durable());
+    }
+
+    #[test]
+    fn test_message_codec_allocations() {
+        let mut message = crate::message::Message::text("steady")
+            .with_message_id("msg-1")
+            .with_subject("prices")
+            .with_content_type("text/plain");
+        let mut header = crate::message::Header::new();
+        header.ttl = Some(30_000);
+        message.header = Some(header);
+        let mut buffer = BytesMut::with_capacity(256);
+
+        // Sizing and encoding into a buffer with room allocate nothing
+        let (size, allocations) = crate::allocations::count(|| message.encoded_size());
+        assert_eq!(allocations, 0);
+        let (_, allocations) = crate::allocations::count(|| Encoder::new_into(&mut buffer).encode_message(&message).unwrap());
+        assert_eq!(allocations, 0);
+        assert_eq!(buffer.len(), size);
+
+        // Decoding allocates the buffer copy, the decoded strings and the
+        // section maps, but no lookup keys or field clones
+        let data = buffer.to_vec();
+        let (decoded, allocations) = crate::allocations::count(|| Decoder::new(data).decode_message().unwrap());
+        assert_eq!(decoded.body_as_text(), Some("steady"));
+        assert!(allocations <= 12, "decoding allocated {} times", allocations);
+    }
+}
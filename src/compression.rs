@@ -0,0 +1,173 @@
+//! AMQP 1.0 Transfer Payload Compression (experimental)
+//!
+//! This module compresses the payloads of transfer frames with LZ4 between
+//! two peers that both run this crate, typically relays linked over a WAN.
+//! It is an extension of this crate, not part of the AMQP specification, and
+//! its wire format may change between releases.
+//!
+//! # Negotiation
+//!
+//! A connection configured with a [`CompressionConfig`] announces the
+//! algorithm in the [`COMPRESSION_PROPERTY`] connection property of its
+//! Open. Compression is used only once the peer's Open announces the same,
+//! so a peer that does not know the extension never receives a compressed
+//! frame.
+//!
+//! # Wire format
+//!
+//! A compressed frame has a data offset of 3: the 4-byte extended header
+//! the specification reserves holds the uncompressed payload size, and the
+//! LZ4 block follows. Only payloads above the threshold are compressed, and
+//! only when that makes them smaller; other frames are sent as they are.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::compression::CompressionConfig;
+//! use dumq_amqp::network::NetworkBuilder;
+//!
+//! let connection = NetworkBuilder::new()
+//!     .hostname("relay-2.example.com")
+//!     .compression(CompressionConfig { threshold: 512, ..Default::default() })
+//!     .build();
+//! assert!(!connection.compression_active());
+//! ```
+
+use crate::performative::Open;
+use crate::transport::{Frame, FrameHeader};
+use crate::types::{AmqpSymbol, AmqpValue};
+use crate::{AmqpError, AmqpResult};
+
+/// Connection property announcing the compression algorithm
+pub const COMPRESSION_PROPERTY: &str = "x-dumq-compression";
+
+/// The only algorithm announced so far
+pub const LZ4: &str = "lz4";
+
+/// Data offset marking a compressed frame: the standard 2 plus one word
+const COMPRESSED_DATA_OFFSET: u8 = 3;
+
+/// Compression settings for a connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressionConfig {
+    /// Payloads of at most this many bytes are sent uncompressed
+    pub threshold: usize,
+    /// Largest uncompressed payload accepted from the peer
+    pub max_decompressed_size: usize,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            threshold: 1024,
+            max_decompressed_size: 16 * 1024 * 1024,
+        }
+    }
+}
+
+impl CompressionConfig {
+    /// Get the connection property value announcing this configuration
+    pub fn property(&self) -> AmqpValue {
+        AmqpValue::Symbol(AmqpSymbol::from(LZ4))
+    }
+
+    /// Check if the peer's Open announces the same algorithm
+    pub fn accepted_by(&self, remote: &Open) -> bool {
+        match remote.properties.get(&AmqpSymbol::from(COMPRESSION_PROPERTY)) {
+            Some(AmqpValue::Symbol(symbol)) => symbol.as_str() == LZ4,
+            Some(AmqpValue::String(name)) => name == LZ4,
+            _ => false,
+        }
+    }
+
+    /// Build the frame for an encoded payload, compressed if worthwhile
+    pub fn frame(&self, frame_type: u8, channel: u16, payload: Vec<u8>) -> Frame {
+        if payload.len() > self.threshold {
+            let block = lz4_flex::block::compress(&payload);
+            if block.len() + 4 < payload.len() {
+                let mut compressed = Vec::with_capacity(block.len() + 4);
+                compressed.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+                compressed.extend_from_slice(&block);
+                let mut header = FrameHeader::new(compressed.len() as u32, frame_type, channel);
+                header.data_offset = COMPRESSED_DATA_OFFSET;
+                return Frame::new(header, compressed);
+            }
+        }
+        Frame::new(FrameHeader::new(payload.len() as u32, frame_type, channel), payload)
+    }
+
+    /// Get the payload of a received frame, decompressing it if needed
+    pub fn payload(&self, frame: Frame) -> AmqpResult<Vec<u8>> {
+        if frame.header.data_offset != COMPRESSED_DATA_OFFSET {
+            return Ok(frame.payload);
+        }
+        let Some((size, block)) = frame.payload.split_first_chunk::<4>() else {
+            return Err(AmqpError::decoding("Compressed frame without its extended header"));
+        };
+        let size = u32::from_be_bytes(*size) as usize;
+        if size > self.max_decompressed_size {
+            return Err(AmqpError::decoding(format!(
+                "Compressed payload of {} bytes exceeds the limit of {} bytes",
+                size, self.max_decompressed_size
+            )));
+        }
+        let mut payload = vec![0; size];
+        match lz4_flex::block::decompress_into(block, &mut payload) {
+            Ok(written) if written == size => Ok(payload),
+            Ok(written) => Err(AmqpError::decoding(format!(
+                "Compressed payload decompressed to {} bytes, announced {}",
+                written, size
+            ))),
+            Err(e) => Err(AmqpError::decoding(format!("Malformed compressed payload: {}", e))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::FrameType;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_frame_compressed_above_threshold() {
+        let config = CompressionConfig { threshold: 64, ..Default::default() };
+        let small = config.frame(FrameType::AMQP as u8, 1, vec![7; 64]);
+        assert_eq!(small.header.data_offset, 2);
+        assert_eq!(config.payload(small).unwrap(), vec![7; 64]);
+
+        let large = config.frame(FrameType::AMQP as u8, 1, vec![7; 4096]);
+        assert_eq!(large.header.data_offset, COMPRESSED_DATA_OFFSET);
+        assert!(large.payload.len() < 100);
+        assert_eq!(config.payload(large).unwrap(), vec![7; 4096]);
+
+        // Incompressible payloads go out as they are
+        let mut rng = StdRng::seed_from_u64(7);
+        let noise: Vec<u8> = (0..4096).map(|_| rng.gen()).collect();
+        let frame = config.frame(FrameType::AMQP as u8, 1, noise.clone());
+        assert_eq!((frame.header.data_offset, frame.payload), (2, noise));
+    }
+
+    #[test]
+    fn test_payload_rejects_oversized_and_malformed() {
+        let config = CompressionConfig { threshold: 0, max_decompressed_size: 1024 };
+        let frame = CompressionConfig::default().frame(FrameType::AMQP as u8, 0, vec![0; 4096]);
+        assert!(config.payload(frame).is_err());
+
+        let mut frame = config.frame(FrameType::AMQP as u8, 0, vec![0; 512]);
+        frame.payload[..4].copy_from_slice(&600u32.to_be_bytes());
+        assert!(config.payload(frame).is_err());
+    }
+
+    #[test]
+    fn test_accepted_by_peer_announcing_lz4() {
+        let config = CompressionConfig::default();
+        let mut open = Open::default();
+        assert!(!config.accepted_by(&open));
+        open.properties.insert(AmqpSymbol::from(COMPRESSION_PROPERTY), config.property());
+        assert!(config.accepted_by(&open));
+        open.properties.insert(AmqpSymbol::from(COMPRESSION_PROPERTY), AmqpValue::Symbol(AmqpSymbol::from("zstd")));
+        assert!(!config.accepted_by(&open));
+    }
+}
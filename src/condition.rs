@@ -2,77 +2,86 @@
 //! 
 //! This module provides the condition system for AMQP 1.0, including both
 //! success and error conditions with their corresponding numeric codes.
+//!
+//! Numeric codes follow HTTP conventions and can be mapped back with
+//! [`AmqpCondition::from_code`]. With the `http` feature, conditions also
+//! convert to and from `http::StatusCode` by what they mean to an HTTP
+//! client, so gateways can carry errors across protocol boundaries.
 
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 /// AMQP 1.0 Condition Codes (Success and Error)
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-#[serde(rename_all = "kebab-case")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
 pub enum AmqpCondition {
     // Success conditions (200-series)
-    #[serde(rename = "amqp:ok")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:ok"))]
     Ok,
-    #[serde(rename = "amqp:accepted")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:accepted"))]
     Accepted,
-    #[serde(rename = "amqp:released")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:released"))]
     Released,
-    #[serde(rename = "amqp:modified")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:modified"))]
     Modified,
     
     // Error conditions (300-500 series)
-    #[serde(rename = "amqp:connection:forced")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:connection:forced"))]
     AmqpErrorConnectionForced,
-    #[serde(rename = "amqp:connection:framing-error")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:connection:framing-error"))]
     AmqpErrorFramingError,
-    #[serde(rename = "amqp:connection:redirect")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:connection:redirect"))]
     AmqpErrorConnectionRedirect,
-    #[serde(rename = "amqp:session:window-violation")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:session:window-violation"))]
     AmqpErrorWindowViolation,
-    #[serde(rename = "amqp:session:errant-link")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:session:errant-link"))]
     AmqpErrorErrantLink,
-    #[serde(rename = "amqp:session:handle-in-use")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:session:handle-in-use"))]
     AmqpErrorHandleInUse,
-    #[serde(rename = "amqp:session:detach-forced")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:session:detach-forced"))]
     AmqpErrorDetachForced,
-    #[serde(rename = "amqp:session:transfer-limit-exceeded")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:session:transfer-limit-exceeded"))]
     AmqpErrorTransferLimitExceeded,
-    #[serde(rename = "amqp:link:message-size-exceeded")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:link:message-size-exceeded"))]
     AmqpErrorMessageSizeExceeded,
-    #[serde(rename = "amqp:link:redirect")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:link:redirect"))]
     AmqpErrorLinkRedirect,
-    #[serde(rename = "amqp:link:transfer-refused")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:link:transfer-refused"))]
     AmqpErrorTransferRefused,
-    #[serde(rename = "amqp:link:stolen")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:link:stolen"))]
     AmqpErrorStolen,
-    #[serde(rename = "amqp:resource:deleted")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:resource:deleted"))]
     AmqpErrorResourceDeleted,
-    #[serde(rename = "amqp:resource:limit-exceeded")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:resource:limit-exceeded"))]
     AmqpErrorResourceLimitExceeded,
-    #[serde(rename = "amqp:resource:locked")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:resource:locked"))]
     AmqpErrorResourceLocked,
-    #[serde(rename = "amqp:resource:precondition-failed")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:resource:precondition-failed"))]
     AmqpErrorPreconditionFailed,
-    #[serde(rename = "amqp:resource:name-collision")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:resource:name-collision"))]
     AmqpErrorResourceNameCollision,
-    #[serde(rename = "amqp:access:unauthorized")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:access:unauthorized"))]
     AmqpErrorUnauthorizedAccess,
-    #[serde(rename = "amqp:access:not-allowed")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:access:not-allowed"))]
     AmqpErrorNotAllowed,
-    #[serde(rename = "amqp:not-implemented")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:not-found"))]
+    AmqpErrorNotFound,
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:not-implemented"))]
     AmqpErrorNotImplemented,
-    #[serde(rename = "amqp:not-modified")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:not-modified"))]
     AmqpErrorNotModified,
-    #[serde(rename = "amqp:decode-error")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:decode-error"))]
     AmqpErrorDecodeError,
-    #[serde(rename = "amqp:invalid-field")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:invalid-field"))]
     AmqpErrorInvalidField,
-    #[serde(rename = "amqp:not-accepted")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:not-accepted"))]
     AmqpErrorNotAccepted,
-    #[serde(rename = "amqp:rejected")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:rejected"))]
     AmqpErrorRejected,
-    #[serde(rename = "amqp:internal-error")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:internal-error"))]
     AmqpErrorInternalError,
-    #[serde(rename = "amqp:illegal-state")]
+    #[cfg_attr(feature = "serde", serde(rename = "amqp:illegal-state"))]
     AmqpErrorIllegalState,
     
     // Custom conditions
@@ -109,6 +118,7 @@ impl AmqpCondition {
             AmqpCondition::AmqpErrorResourceNameCollision => "amqp:resource:name-collision",
             AmqpCondition::AmqpErrorUnauthorizedAccess => "amqp:access:unauthorized",
             AmqpCondition::AmqpErrorNotAllowed => "amqp:access:not-allowed",
+            AmqpCondition::AmqpErrorNotFound => "amqp:not-found",
             AmqpCondition::AmqpErrorNotImplemented => "amqp:not-implemented",
             AmqpCondition::AmqpErrorNotModified => "amqp:not-modified",
             AmqpCondition::AmqpErrorDecodeError => "amqp:decode-error",
@@ -143,6 +153,7 @@ impl AmqpCondition {
             // 400-series: Channel/Connection Errors
             AmqpCondition::AmqpErrorUnauthorizedAccess => 401,
             AmqpCondition::AmqpErrorNotAllowed => 403,
+            AmqpCondition::AmqpErrorNotFound => 404,
             AmqpCondition::AmqpErrorResourceDeleted => 404,
             AmqpCondition::AmqpErrorResourceNameCollision => 409,
             AmqpCondition::AmqpErrorResourceLocked => 406,
@@ -168,6 +179,122 @@ impl AmqpCondition {
         }
     }
 
+    /// Get the condition for a numeric code, the reverse of [`code_num`](Self::code_num)
+    ///
+    /// Several conditions share a code; the most general one is returned, so
+    /// `from_code(c.code_num())` always yields a condition with the same code.
+    /// Returns `None` for codes no condition uses.
+    pub fn from_code(code: u16) -> Option<AmqpCondition> {
+        let condition = match code {
+            200 => AmqpCondition::Ok,
+            202 => AmqpCondition::Accepted,
+            301 => AmqpCondition::AmqpErrorConnectionForced,
+            302 => AmqpCondition::AmqpErrorConnectionRedirect,
+            304 => AmqpCondition::AmqpErrorNotModified,
+            311 => AmqpCondition::AmqpErrorMessageSizeExceeded,
+            400 => AmqpCondition::AmqpErrorNotAccepted,
+            401 => AmqpCondition::AmqpErrorUnauthorizedAccess,
+            403 => AmqpCondition::AmqpErrorNotAllowed,
+            404 => AmqpCondition::AmqpErrorNotFound,
+            405 => AmqpCondition::AmqpErrorResourceLimitExceeded,
+            406 => AmqpCondition::AmqpErrorResourceLocked,
+            409 => AmqpCondition::AmqpErrorResourceNameCollision,
+            412 => AmqpCondition::AmqpErrorPreconditionFailed,
+            500 => AmqpCondition::AmqpErrorInternalError,
+            501 => AmqpCondition::AmqpErrorNotImplemented,
+            502 => AmqpCondition::AmqpErrorDecodeError,
+            503 => AmqpCondition::AmqpErrorInvalidField,
+            504 => AmqpCondition::AmqpErrorErrantLink,
+            505 => AmqpCondition::AmqpErrorHandleInUse,
+            506 => AmqpCondition::AmqpErrorDetachForced,
+            507 => AmqpCondition::AmqpErrorTransferLimitExceeded,
+            _ => return None,
+        };
+        Some(condition)
+    }
+
+    /// Get the HTTP status for this condition
+    ///
+    /// The status is chosen for what the condition means to an HTTP client,
+    /// not taken from [`code_num`](Self::code_num): malformed input is
+    /// `400`, oversized messages `413`, exhausted limits `429`, redirects
+    /// `307`, and protocol faults of the AMQP peer `502`. Custom conditions
+    /// map to `500 Internal Server Error`.
+    #[cfg(feature = "http")]
+    pub fn http_status(&self) -> http::StatusCode {
+        use http::StatusCode;
+        match self {
+            AmqpCondition::Ok | AmqpCondition::Released | AmqpCondition::Modified => StatusCode::OK,
+            AmqpCondition::Accepted => StatusCode::ACCEPTED,
+
+            AmqpCondition::AmqpErrorConnectionRedirect |
+            AmqpCondition::AmqpErrorLinkRedirect => StatusCode::TEMPORARY_REDIRECT,
+            AmqpCondition::AmqpErrorNotModified => StatusCode::NOT_MODIFIED,
+
+            AmqpCondition::AmqpErrorDecodeError |
+            AmqpCondition::AmqpErrorInvalidField |
+            AmqpCondition::AmqpErrorNotAccepted |
+            AmqpCondition::AmqpErrorRejected => StatusCode::BAD_REQUEST,
+            AmqpCondition::AmqpErrorUnauthorizedAccess => StatusCode::UNAUTHORIZED,
+            AmqpCondition::AmqpErrorNotAllowed => StatusCode::FORBIDDEN,
+            AmqpCondition::AmqpErrorNotFound => StatusCode::NOT_FOUND,
+            AmqpCondition::AmqpErrorResourceNameCollision => StatusCode::CONFLICT,
+            AmqpCondition::AmqpErrorResourceDeleted => StatusCode::GONE,
+            AmqpCondition::AmqpErrorPreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            AmqpCondition::AmqpErrorMessageSizeExceeded => StatusCode::PAYLOAD_TOO_LARGE,
+            AmqpCondition::AmqpErrorResourceLocked => StatusCode::LOCKED,
+            AmqpCondition::AmqpErrorResourceLimitExceeded |
+            AmqpCondition::AmqpErrorTransferLimitExceeded |
+            AmqpCondition::AmqpErrorTransferRefused => StatusCode::TOO_MANY_REQUESTS,
+
+            AmqpCondition::AmqpErrorInternalError |
+            AmqpCondition::AmqpErrorIllegalState |
+            AmqpCondition::Custom(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AmqpCondition::AmqpErrorNotImplemented => StatusCode::NOT_IMPLEMENTED,
+            AmqpCondition::AmqpErrorFramingError |
+            AmqpCondition::AmqpErrorWindowViolation |
+            AmqpCondition::AmqpErrorErrantLink |
+            AmqpCondition::AmqpErrorHandleInUse => StatusCode::BAD_GATEWAY,
+            AmqpCondition::AmqpErrorConnectionForced |
+            AmqpCondition::AmqpErrorDetachForced |
+            AmqpCondition::AmqpErrorStolen => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Get the condition for an HTTP status
+    ///
+    /// Each status [`http_status`](Self::http_status) produces maps back to
+    /// the most general condition with that status, e.g. `400` to
+    /// `amqp:decode-error`. Other statuses fall back by class: 2xx to
+    /// `amqp:ok`, 3xx to `amqp:link:redirect`, 4xx to `amqp:not-accepted`
+    /// and anything else to `amqp:internal-error`.
+    #[cfg(feature = "http")]
+    pub fn from_http_status(status: http::StatusCode) -> AmqpCondition {
+        use http::StatusCode;
+        match status {
+            StatusCode::OK => AmqpCondition::Ok,
+            StatusCode::ACCEPTED => AmqpCondition::Accepted,
+            StatusCode::NOT_MODIFIED => AmqpCondition::AmqpErrorNotModified,
+            StatusCode::BAD_REQUEST => AmqpCondition::AmqpErrorDecodeError,
+            StatusCode::UNAUTHORIZED => AmqpCondition::AmqpErrorUnauthorizedAccess,
+            StatusCode::FORBIDDEN => AmqpCondition::AmqpErrorNotAllowed,
+            StatusCode::NOT_FOUND => AmqpCondition::AmqpErrorNotFound,
+            StatusCode::CONFLICT => AmqpCondition::AmqpErrorResourceNameCollision,
+            StatusCode::GONE => AmqpCondition::AmqpErrorResourceDeleted,
+            StatusCode::PRECONDITION_FAILED => AmqpCondition::AmqpErrorPreconditionFailed,
+            StatusCode::PAYLOAD_TOO_LARGE => AmqpCondition::AmqpErrorMessageSizeExceeded,
+            StatusCode::LOCKED => AmqpCondition::AmqpErrorResourceLocked,
+            StatusCode::TOO_MANY_REQUESTS => AmqpCondition::AmqpErrorResourceLimitExceeded,
+            StatusCode::NOT_IMPLEMENTED => AmqpCondition::AmqpErrorNotImplemented,
+            StatusCode::BAD_GATEWAY => AmqpCondition::AmqpErrorFramingError,
+            StatusCode::SERVICE_UNAVAILABLE => AmqpCondition::AmqpErrorConnectionForced,
+            status if status.is_success() => AmqpCondition::Ok,
+            status if status.is_redirection() => AmqpCondition::AmqpErrorLinkRedirect,
+            status if status.is_client_error() => AmqpCondition::AmqpErrorNotAccepted,
+            _ => AmqpCondition::AmqpErrorInternalError,
+        }
+    }
+
     /// Check if this is a success condition
     pub fn is_success(&self) -> bool {
         matches!(self, 
@@ -205,6 +332,7 @@ impl AmqpCondition {
 
             
             // Resource errors
+            AmqpCondition::AmqpErrorNotFound |
             AmqpCondition::AmqpErrorResourceDeleted | 
             AmqpCondition::AmqpErrorResourceLimitExceeded | 
             AmqpCondition::AmqpErrorResourceLocked | 
@@ -245,6 +373,20 @@ impl std::fmt::Display for AmqpCondition {
     }
 }
 
+#[cfg(feature = "http")]
+impl From<&AmqpCondition> for http::StatusCode {
+    fn from(condition: &AmqpCondition) -> Self {
+        condition.http_status()
+    }
+}
+
+#[cfg(feature = "http")]
+impl From<http::StatusCode> for AmqpCondition {
+    fn from(status: http::StatusCode) -> Self {
+        AmqpCondition::from_http_status(status)
+    }
+}
+
 impl From<&str> for AmqpCondition {
     fn from(s: &str) -> Self {
         match s {
@@ -274,6 +416,7 @@ impl From<&str> for AmqpCondition {
             "amqp:resource:name-collision" => AmqpCondition::AmqpErrorResourceNameCollision,
             "amqp:access:unauthorized" => AmqpCondition::AmqpErrorUnauthorizedAccess,
             "amqp:access:not-allowed" => AmqpCondition::AmqpErrorNotAllowed,
+            "amqp:not-found" => AmqpCondition::AmqpErrorNotFound,
             "amqp:not-implemented" => AmqpCondition::AmqpErrorNotImplemented,
             "amqp:not-modified" => AmqpCondition::AmqpErrorNotModified,
             "amqp:decode-error" => AmqpCondition::AmqpErrorDecodeError,
@@ -353,6 +496,7 @@ mod tests {
         assert_eq!(AmqpCondition::AmqpErrorResourceNameCollision.code_num(), 409);
         assert_eq!(AmqpCondition::AmqpErrorUnauthorizedAccess.code_num(), 401);
         assert_eq!(AmqpCondition::AmqpErrorNotAllowed.code_num(), 403);
+        assert_eq!(AmqpCondition::AmqpErrorNotFound.code_num(), 404);
         assert_eq!(AmqpCondition::AmqpErrorNotImplemented.code_num(), 501);
         assert_eq!(AmqpCondition::AmqpErrorNotModified.code_num(), 304);
         assert_eq!(AmqpCondition::AmqpErrorDecodeError.code_num(), 502);
@@ -389,6 +533,7 @@ mod tests {
         assert_eq!(AmqpCondition::AmqpErrorResourceNameCollision.as_str(), "amqp:resource:name-collision");
         assert_eq!(AmqpCondition::AmqpErrorUnauthorizedAccess.as_str(), "amqp:access:unauthorized");
         assert_eq!(AmqpCondition::AmqpErrorNotAllowed.as_str(), "amqp:access:not-allowed");
+        assert_eq!(AmqpCondition::AmqpErrorNotFound.as_str(), "amqp:not-found");
         assert_eq!(AmqpCondition::AmqpErrorNotImplemented.as_str(), "amqp:not-implemented");
         assert_eq!(AmqpCondition::AmqpErrorNotModified.as_str(), "amqp:not-modified");
         assert_eq!(AmqpCondition::AmqpErrorDecodeError.as_str(), "amqp:decode-error");
@@ -452,6 +597,7 @@ mod tests {
         assert_eq!(AmqpCondition::AmqpErrorTransferRefused.category(), ConditionCategory::Link);
         assert_eq!(AmqpCondition::AmqpErrorStolen.category(), ConditionCategory::Link);
         
+        assert_eq!(AmqpCondition::AmqpErrorNotFound.category(), ConditionCategory::Resource);
         assert_eq!(AmqpCondition::AmqpErrorResourceDeleted.category(), ConditionCategory::Resource);
         assert_eq!(AmqpCondition::AmqpErrorResourceLimitExceeded.category(), ConditionCategory::Resource);
         assert_eq!(AmqpCondition::AmqpErrorResourceLocked.category(), ConditionCategory::Resource);
@@ -507,6 +653,7 @@ mod tests {
         assert_eq!(AmqpCondition::from("amqp:resource:name-collision"), AmqpCondition::AmqpErrorResourceNameCollision);
         assert_eq!(AmqpCondition::from("amqp:access:unauthorized"), AmqpCondition::AmqpErrorUnauthorizedAccess);
         assert_eq!(AmqpCondition::from("amqp:access:not-allowed"), AmqpCondition::AmqpErrorNotAllowed);
+        assert_eq!(AmqpCondition::from("amqp:not-found"), AmqpCondition::AmqpErrorNotFound);
         assert_eq!(AmqpCondition::from("amqp:not-implemented"), AmqpCondition::AmqpErrorNotImplemented);
         assert_eq!(AmqpCondition::from("amqp:not-modified"), AmqpCondition::AmqpErrorNotModified);
         assert_eq!(AmqpCondition::from("amqp:decode-error"), AmqpCondition::AmqpErrorDecodeError);
@@ -575,6 +722,7 @@ mod tests {
         assert_eq!(map.get(&condition2), Some(&"success2"));
     }
 
+    #[cfg(feature = "serde")]
     #[test]
     fn test_serde_serialization() {
         let condition = AmqpCondition::Ok;
@@ -584,6 +732,7 @@ mod tests {
         assert_eq!(condition, deserialized);
     }
 
+    #[cfg(feature = "serde")]
     #[test]
     fn test_serde_deserialization() {
         let json = r#""amqp:ok""#;
@@ -591,4 +740,70 @@ mod tests {
         
         assert_eq!(deserialized, AmqpCondition::Ok);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_from_code_reverses_code_num() {
+        let names = [
+            "amqp:ok", "amqp:accepted", "amqp:released", "amqp:modified",
+            "amqp:connection:forced", "amqp:connection:framing-error", "amqp:connection:redirect",
+            "amqp:session:window-violation", "amqp:session:errant-link", "amqp:session:handle-in-use",
+            "amqp:session:detach-forced", "amqp:session:transfer-limit-exceeded",
+            "amqp:link:message-size-exceeded", "amqp:link:redirect", "amqp:link:transfer-refused",
+            "amqp:link:stolen", "amqp:resource:deleted", "amqp:resource:limit-exceeded",
+            "amqp:resource:locked", "amqp:resource:precondition-failed", "amqp:resource:name-collision",
+            "amqp:access:unauthorized", "amqp:access:not-allowed", "amqp:not-found",
+            "amqp:not-implemented",
+            "amqp:not-modified", "amqp:decode-error", "amqp:invalid-field", "amqp:not-accepted",
+            "amqp:rejected", "amqp:internal-error", "amqp:illegal-state",
+        ];
+        for name in names {
+            let condition = AmqpCondition::from(name);
+            let code = condition.code_num();
+            assert_eq!(AmqpCondition::from_code(code).map(|c| c.code_num()), Some(code), "{}", name);
+        }
+        assert_eq!(AmqpCondition::from_code(401), Some(AmqpCondition::AmqpErrorUnauthorizedAccess));
+        assert_eq!(AmqpCondition::from_code(404), Some(AmqpCondition::AmqpErrorNotFound));
+        assert_eq!(AmqpCondition::from_code(0), None);
+        assert_eq!(AmqpCondition::from_code(418), None);
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_http_status_round_trip() {
+        let status = http::StatusCode::from(&AmqpCondition::AmqpErrorNotAllowed);
+        assert_eq!(status, http::StatusCode::FORBIDDEN);
+        assert_eq!(AmqpCondition::from(status), AmqpCondition::AmqpErrorNotAllowed);
+
+        assert_eq!(
+            AmqpCondition::Custom("com.example:oops".to_string()).http_status(),
+            http::StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(AmqpCondition::from_http_status(http::StatusCode::IM_A_TEAPOT), AmqpCondition::AmqpErrorNotAccepted);
+        assert_eq!(AmqpCondition::from_http_status(http::StatusCode::NO_CONTENT), AmqpCondition::Ok);
+        assert_eq!(AmqpCondition::from_http_status(http::StatusCode::BAD_GATEWAY), AmqpCondition::AmqpErrorFramingError);
+        assert_eq!(
+            AmqpCondition::from_http_status(http::StatusCode::PERMANENT_REDIRECT),
+            AmqpCondition::AmqpErrorLinkRedirect
+        );
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_http_status_by_meaning() {
+        use http::StatusCode;
+        let cases = [
+            (AmqpCondition::AmqpErrorDecodeError, StatusCode::BAD_REQUEST),
+            (AmqpCondition::AmqpErrorInvalidField, StatusCode::BAD_REQUEST),
+            (AmqpCondition::AmqpErrorMessageSizeExceeded, StatusCode::PAYLOAD_TOO_LARGE),
+            (AmqpCondition::AmqpErrorResourceLimitExceeded, StatusCode::TOO_MANY_REQUESTS),
+            (AmqpCondition::AmqpErrorNotFound, StatusCode::NOT_FOUND),
+            (AmqpCondition::AmqpErrorLinkRedirect, StatusCode::TEMPORARY_REDIRECT),
+            (AmqpCondition::AmqpErrorConnectionRedirect, StatusCode::TEMPORARY_REDIRECT),
+        ];
+        for (condition, status) in cases {
+            assert_eq!(condition.http_status(), status, "{}", condition.as_str());
+        }
+        assert_eq!(AmqpCondition::from_http_status(StatusCode::NOT_FOUND), AmqpCondition::AmqpErrorNotFound);
+        assert_eq!(AmqpCondition::from_http_status(StatusCode::BAD_REQUEST), AmqpCondition::AmqpErrorDecodeError);
+    }
+}
@@ -21,8 +21,8 @@
 //!
 //! ## Basic Connection Usage
 //!
-//! ```rust
-//! use dumq_amqp::connection::{Connection, ConnectionBuilder};
+//! ```rust,no_run
+//! use dumq_amqp::connection::ConnectionBuilder;
 //! use tokio::time::Duration;
 //!
 //! #[tokio::main]
@@ -65,13 +65,62 @@
 //!     .property("product".to_string(), AmqpValue::String("MyApp".to_string()))
 //!     .build();
 //! ```
+//!
+//! ## Virtual Hosts
+//!
+//! The Open hostname, SASL hostname and TLS SNI are all derived from one
+//! configuration, see [`ConnectionConfig::hostnames`].
+//!
+//! ```rust
+//! use dumq_amqp::connection::ConnectionConfig;
+//!
+//! let config = ConnectionConfig {
+//!     hostname: "rabbit.example.com".to_string(),
+//!     virtual_host: Some("production".to_string()),
+//!     ..Default::default()
+//! };
+//!
+//! let hostnames = config.hostnames().unwrap();
+//! assert_eq!(hostnames.open, "vhost:production");
+//! assert_eq!(hostnames.sni.as_deref(), Some("rabbit.example.com"));
+//! ```
+//!
+//! ## Multi-Tenant Gateways
+//!
+//! The TCP endpoint, the announced hostname and the SASL authorization
+//! identity are configured separately:
+//!
+//! ```rust
+//! use dumq_amqp::connection::ConnectionBuilder;
+//! use dumq_amqp::sasl::SaslCredentials;
+//!
+//! let connection = ConnectionBuilder::new()
+//!     .hostname("10.0.12.7")
+//!     .hostname_override("tenant-a.messaging.example.com")
+//!     .sasl(SaslCredentials::plain("gateway", "secret"))
+//!     .sasl_authzid("tenant-a")
+//!     .build();
+//! ```
 
-use crate::{AmqpError, AmqpResult, AmqpValue};
-use std::collections::HashMap;
-use tokio::net::TcpStream;
-use tokio::io::AsyncWriteExt;
+use crate::demux::Demux;
+use crate::logging;
+use crate::network::{NetworkConfig, NetworkConnection};
+use crate::performative::{Close, Open};
+use crate::reconnect::{self, Connector, ReconnectEvent, ReconnectPolicy, Reconnector};
+use crate::{AmqpCondition, AmqpError, AmqpResult, AmqpSymbol, AmqpValue};
+use crate::sasl::SaslCredentials;
+use crate::session::{Session, SessionBuilder, SessionState};
+use crate::tasks::TaskKind;
+use crate::tuning::{self, TuningHandle};
+use crate::watchdog::{Watchdog, WatchdogConfig, WatchdogEvent};
+use std::collections::{BTreeMap, HashMap};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
 use tokio::time::{timeout, Duration};
-use uuid::Uuid;
+use crate::ids::{self, Namer};
+
+/// Smallest max-frame-size a peer may announce
+pub const MIN_MAX_FRAME_SIZE: u32 = 512;
 
 /// AMQP 1.0 Connection state
 #[derive(Debug, Clone, PartialEq)]
@@ -91,7 +140,7 @@ pub enum ConnectionState {
 /// AMQP 1.0 Connection configuration
 #[derive(Debug, Clone)]
 pub struct ConnectionConfig {
-    /// Connection hostname
+    /// Hostname or address of the TCP endpoint, e.g. a load balancer
     pub hostname: String,
     /// Connection port
     pub port: u16,
@@ -107,6 +156,133 @@ pub struct ConnectionConfig {
     pub container_id: String,
     /// Connection properties
     pub properties: HashMap<String, AmqpValue>,
+    /// Virtual host, announced as `vhost:<name>` in the Open and SASL hostname
+    pub virtual_host: Option<String>,
+    /// Hostname announced in the Open, SASL and TLS SNI instead of the derived one
+    pub hostname_override: Option<String>,
+    /// SASL authorization identity, when acting on behalf of another identity
+    ///
+    /// Sent with PLAIN and EXTERNAL credentials that name none of their own.
+    pub sasl_authzid: Option<String>,
+    /// Credentials for a SASL layer before the AMQP header, if the peer requires one
+    pub sasl: Option<SaslCredentials>,
+    /// TLS to secure the connection with, if the peer expects it
+    #[cfg(feature = "tls")]
+    pub tls: Option<crate::tls::TlsConnector>,
+    /// Capabilities offered to the peer in Open
+    pub offered_capabilities: Vec<AmqpSymbol>,
+    /// Capabilities asked of the peer in Open
+    pub desired_capabilities: Vec<AmqpSymbol>,
+    /// Supervision of the connection driver
+    pub watchdog: WatchdogConfig,
+    /// Recovery of the connection when its transport fails, if enabled
+    pub reconnect: Option<ReconnectPolicy>,
+}
+
+/// Hostnames announced to the peer during connection setup
+///
+/// All three are derived from the same configuration by
+/// [`ConnectionConfig::hostnames`], so they cannot drift apart.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hostnames {
+    /// `hostname` field of the Open performative
+    pub open: String,
+    /// `hostname` field of the SASL init
+    pub sasl: String,
+    /// TLS server name indication, absent when the host is not a DNS name
+    pub sni: Option<String>,
+}
+
+impl ConnectionConfig {
+    /// Derive the hostnames announced to the peer
+    ///
+    /// The override wins if set; otherwise the virtual host is sent in the
+    /// RabbitMQ `vhost:<name>` dialect, falling back to the network hostname.
+    /// SNI can only carry a DNS name, so it uses the override or the network
+    /// hostname, whichever is one. Fails if the override names a different
+    /// virtual host than the one configured.
+    pub fn hostnames(&self) -> AmqpResult<Hostnames> {
+        let vhost = self.virtual_host.as_deref().map(vhost_hostname);
+        let announced = match (&self.hostname_override, vhost) {
+            (Some(name), Some(vhost)) if name.starts_with(VHOST_PREFIX) && *name != vhost => {
+                return Err(AmqpError::connection(format!(
+                    "Hostname override '{}' conflicts with virtual host '{}'",
+                    name, vhost
+                )));
+            }
+            (Some(name), _) => name.clone(),
+            (None, Some(vhost)) => vhost,
+            (None, None) => self.hostname.clone(),
+        };
+
+        let sni = self
+            .hostname_override
+            .iter()
+            .chain(std::iter::once(&self.hostname))
+            .find(|name| is_dns_name(name))
+            .cloned();
+
+        Ok(Hostnames {
+            open: announced.clone(),
+            sasl: announced,
+            sni,
+        })
+    }
+
+    /// Build the SASL PLAIN initial response, carrying the configured authzid
+    pub fn sasl_plain_response(&self, authcid: &str, password: &str) -> AmqpResult<Vec<u8>> {
+        crate::transport::ProtocolNegotiator::sasl_plain_response(self.sasl_authzid.as_deref(), authcid, password)
+    }
+
+    /// Get the SASL credentials, acting as the configured authzid unless they name their own
+    fn sasl_credentials(&self) -> Option<SaslCredentials> {
+        let mut credentials = self.sasl.clone()?;
+        if let (Some(authzid), SaslCredentials::Plain { authzid: own, .. } | SaslCredentials::External { authzid: own }) =
+            (&self.sasl_authzid, &mut credentials)
+        {
+            own.get_or_insert_with(|| authzid.clone());
+        }
+        Some(credentials)
+    }
+
+    /// Configure the network connection the handshake and heartbeats run on
+    fn network_config(&self, hostnames: &Hostnames) -> NetworkConfig {
+        NetworkConfig {
+            hostname: self.hostname.clone(),
+            port: self.port,
+            timeout: self.timeout,
+            max_frame_size: self.max_frame_size,
+            channel_max: self.channel_max,
+            idle_timeout: self.idle_timeout,
+            container_id: self.container_id.clone(),
+            properties: self.properties.clone(),
+            hostnames: Some(hostnames.clone()),
+            watchdog: self.watchdog.clone(),
+            sasl: self.sasl_credentials(),
+            #[cfg(feature = "tls")]
+            tls: self.tls.clone(),
+            offered_capabilities: self.offered_capabilities.clone(),
+            desired_capabilities: self.desired_capabilities.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Prefix of RabbitMQ-style virtual host names
+const VHOST_PREFIX: &str = "vhost:";
+
+fn vhost_hostname(name: &str) -> String {
+    if name.starts_with(VHOST_PREFIX) {
+        name.to_string()
+    } else {
+        format!("{}{}", VHOST_PREFIX, name)
+    }
+}
+
+fn is_dns_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.parse::<std::net::IpAddr>().is_err()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
 }
 
 impl Default for ConnectionConfig {
@@ -120,6 +296,16 @@ impl Default for ConnectionConfig {
             idle_timeout: Duration::from_secs(0),
             container_id: "dumq-amqp-client".to_string(),
             properties: HashMap::new(),
+            virtual_host: None,
+            hostname_override: None,
+            sasl_authzid: None,
+            sasl: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+            offered_capabilities: Vec::new(),
+            desired_capabilities: Vec::new(),
+            watchdog: WatchdogConfig::default(),
+            reconnect: None,
         }
     }
 }
@@ -130,96 +316,290 @@ pub struct Connection {
     state: ConnectionState,
     /// Connection configuration
     config: ConnectionConfig,
-    /// TCP stream
-    stream: Option<TcpStream>,
+    /// Driver of the frames exchanged with the peer
+    demux: Option<Demux>,
+    /// Task running the driver, ending with the peer's Close
+    driver: Option<JoinHandle<AmqpResult<Close>>>,
+    /// Open received from the peer
+    remote_open: Option<Open>,
     /// Connection ID
     id: String,
     /// Next channel number
     next_channel: u16,
-    /// Sessions
-    sessions: HashMap<u16, Session>,
+    /// Sessions, keyed by channel so close ends them in a fixed order
+    sessions: BTreeMap<u16, Session>,
+    /// Knobs that can be changed while the connection is open
+    tuning: TuningHandle,
+    /// Names of the sessions and links on this connection
+    namer: Namer,
+    /// Supervisor of the driver
+    watchdog: Watchdog,
+    /// Progress of recovering the transport
+    reconnect_events: broadcast::Sender<ReconnectEvent>,
 }
 
 impl Connection {
     /// Create a new connection
     pub fn new(config: ConnectionConfig) -> Self {
+        let tuning = TuningHandle::default();
+        tuning.set_heartbeat_limit(tuning::heartbeat_limit_for(config.idle_timeout));
+        let watchdog = Watchdog::new(config.watchdog.clone());
         Connection {
             state: ConnectionState::Closed,
             config,
-            stream: None,
-            id: Uuid::new_v4().to_string(),
+            demux: None,
+            driver: None,
+            remote_open: None,
+            id: ids::next_id(),
             next_channel: 0,
-            sessions: HashMap::new(),
+            sessions: BTreeMap::new(),
+            tuning,
+            namer: Namer::new(),
+            watchdog,
+            reconnect_events: reconnect::event_channel(),
         }
     }
 
     /// Open the connection
+    ///
+    /// Connects and runs the handshake of a [`NetworkConnection`]: TLS and
+    /// SASL if configured, then protocol headers and Open performatives
+    /// within the configured timeout. Max-frame-size, channel-max and the
+    /// heartbeat interval are negotiated with the peer's Open. A failed
+    /// connection is left in [`ConnectionState::Error`] and can be opened
+    /// again.
+    ///
+    /// Once open, a [`Demux`] task drives the transport, routing the peer's
+    /// frames to the sessions and links they belong to. The network
+    /// connection's keep-alive task sends heartbeats within the peer's idle
+    /// timeout and closes the connection if the peer stays silent beyond
+    /// ours. The driver is supervised by a [`Watchdog`]: if it panics, or
+    /// stalls with requests queued, it is aborted so operations fail instead
+    /// of hanging.
+    ///
+    /// With a [`ReconnectPolicy`], a driver whose transport fails opens a
+    /// new one with the same configuration and replays the sessions and
+    /// links on it, see [`Demux`]. Operations wait while it reconnects.
     pub async fn open(&mut self) -> AmqpResult<()> {
-        if self.state != ConnectionState::Closed {
+        if !matches!(self.state, ConnectionState::Closed | ConnectionState::Error(_)) {
             return Err(AmqpError::invalid_state("Connection is not in closed state"));
         }
 
+        let hostnames = self.config.hostnames()?;
         self.state = ConnectionState::Opening;
 
-        // Connect to the server
-        let addr = format!("{}:{}", self.config.hostname, self.config.port);
-        let stream = timeout(self.config.timeout, TcpStream::connect(&addr))
-            .await
-            .map_err(|_| AmqpError::timeout("Connection timeout"))?
-            .map_err(|e| AmqpError::connection(format!("Failed to connect: {}", e)))?;
-
-        self.stream = Some(stream);
-        self.state = ConnectionState::Open;
-
-        // Send AMQP protocol header
-        self.send_protocol_header().await?;
+        let network = self.config.network_config(&hostnames);
+        let opened = connect(network.clone(), &self.id, &self.tuning, &self.watchdog).await;
+        match opened.and_then(|connection| self.start_driver(connection, network)) {
+            Ok((demux, driver)) => {
+                let progress = demux.progress().clone();
+                self.driver = Some(self.watchdog.supervise(TaskKind::Driver, &self.id, driver, progress));
+                self.demux = Some(demux);
+                self.state = ConnectionState::Open;
+                Ok(())
+            }
+            Err(e) => {
+                self.state = ConnectionState::Error(e.to_string());
+                Err(e)
+            }
+        }
+    }
 
-        // Send Open performative
-        self.send_open().await?;
+    /// Keep the peer's Open and spawn the driver on the ready connection
+    fn start_driver(
+        &mut self,
+        connection: NetworkConnection,
+        network: NetworkConfig,
+    ) -> AmqpResult<(Demux, JoinHandle<AmqpResult<Close>>)> {
+        let remote = connection.remote_open().cloned();
+        if let Some(remote) = &remote {
+            logging::debug!(
+                "Connection {} open with container '{}' (max-frame-size {}, channel-max {})",
+                self.id,
+                remote.container_id,
+                remote.max_frame_size,
+                remote.channel_max
+            );
+        }
+        self.remote_open = remote;
+        let reconnector = self
+            .config
+            .reconnect
+            .clone()
+            .map(|policy| Reconnector::new(policy, self.connector(network), self.reconnect_events.clone()));
+        Demux::spawn_network(connection, self.max_frame_size(), &self.id, reconnector)
+    }
 
-        Ok(())
+    /// Open new connections for the driver with this connection's configuration
+    fn connector(&self, network: NetworkConfig) -> Connector {
+        let id = self.id.clone();
+        let tuning = self.tuning.clone();
+        let watchdog = self.watchdog.clone();
+        Box::new(move || {
+            let network = network.clone();
+            let id = id.clone();
+            let tuning = tuning.clone();
+            let watchdog = watchdog.clone();
+            Box::pin(async move { connect(network, &id, &tuning, &watchdog).await })
+        })
     }
 
     /// Close the connection
+    ///
+    /// Shutdown cascades in a fixed order: each session is ended in channel
+    /// order, which detaches its links and fails their pending operations
+    /// with [`AmqpError::InvalidState`], then the Close is sent, the peer's
+    /// Close awaited and the transport shut down. Every step runs even if an
+    /// earlier one fails; the failures are reported together once the
+    /// connection is closed.
+    ///
+    /// If the peer's Close carries an error, the connection is left in
+    /// [`ConnectionState::Error`] and the error is returned.
     pub async fn close(&mut self) -> AmqpResult<()> {
         if self.state != ConnectionState::Open {
             return Err(AmqpError::invalid_state("Connection is not open"));
         }
 
         self.state = ConnectionState::Closing;
-
-        // Close all sessions
-        for session in self.sessions.values_mut() {
-            session.close().await?;
+        let mut errors = Vec::new();
+
+        for (channel, session) in self.sessions.iter_mut() {
+            if session.state() == &SessionState::Active {
+                if let Err(e) = session.end().await {
+                    errors.push(format!("session on channel {}: {}", channel, e));
+                }
+            }
         }
         self.sessions.clear();
 
-        // Send Close performative
-        self.send_close().await?;
-
-        // Close TCP connection
-        if let Some(mut stream) = self.stream.take() {
-            stream.shutdown().await
-                .map_err(|e| AmqpError::connection(format!("Failed to close connection: {}", e)))?;
+        let mut remote_error = None;
+        if let Some(demux) = self.demux.take() {
+            // The driver has already stopped if the peer closed first
+            let _ = demux.close();
         }
+        if let Some(mut driver) = self.driver.take() {
+            logging::debug!("Closing connection {}", self.id);
+            match timeout(self.config.timeout, &mut driver).await {
+                Ok(Ok(Ok(close))) => remote_error = close.error,
+                Ok(Ok(Err(e))) => errors.push(format!("close: {}", e)),
+                Ok(Err(e)) => errors.push(format!("close: driver failed: {}", e)),
+                Err(_) => {
+                    driver.abort();
+                    errors.push("close: Timed out waiting for the peer's Close".to_string());
+                }
+            }
+        }
+        self.remote_open = None;
 
+        if let Some(error) = remote_error {
+            let description = error.description.unwrap_or_else(|| "Peer closed the connection".to_string());
+            let error = AmqpError::amqp_protocol(error.condition, description);
+            self.state = ConnectionState::Error(error.to_string());
+            return Err(error);
+        }
         self.state = ConnectionState::Closed;
-        Ok(())
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AmqpError::connection(format!("Connection closed with errors: {}", errors.join("; "))))
+        }
     }
 
-    /// Create a new session
-    pub async fn create_session(&mut self) -> AmqpResult<Session> {
+    /// Begin a new session on the next free channel
+    ///
+    /// The connection owns the session, so closing the connection ends it.
+    pub async fn create_session(&mut self) -> AmqpResult<&mut Session> {
         if self.state != ConnectionState::Open {
             return Err(AmqpError::invalid_state("Connection is not open"));
         }
 
         let channel = self.next_channel;
-        self.next_channel += 1;
+        if channel > self.channel_max() || self.sessions.contains_key(&channel) {
+            return Err(AmqpError::amqp_protocol(
+                AmqpCondition::AmqpErrorResourceLimitExceeded,
+                format!("No channel available (channel-max {})", self.channel_max()),
+            ));
+        }
+
+        let mut session = SessionBuilder::new()
+            .name(self.namer.name("session"))
+            .build(channel, self.id.clone());
+        session.set_namer(self.namer.clone());
+        if let Some(demux) = &self.demux {
+            session.set_demux(demux.clone())?;
+        }
+        session.begin().await?;
+        self.next_channel = channel.saturating_add(1);
+
+        Ok(self.sessions.entry(channel).or_insert(session))
+    }
+
+    /// Get a session by channel
+    pub fn session_mut(&mut self, channel: u16) -> Option<&mut Session> {
+        self.sessions.get_mut(&channel)
+    }
 
-        let session = Session::new(channel, self.id.clone());
-        self.sessions.insert(channel, session.clone());
+    /// End a session and release it
+    pub async fn end_session(&mut self, channel: u16) -> AmqpResult<()> {
+        match self.sessions.remove(&channel) {
+            Some(session) => session.close().await,
+            None => Err(AmqpError::invalid_state(format!("No session on channel {}", channel))),
+        }
+    }
 
-        Ok(session)
+    /// Get the number of sessions
+    pub fn session_count(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Write a performative frame on a channel
+    ///
+    /// An escape hatch for exercising protocol extensions the crate does not
+    /// model. The frame bypasses session and link state, so sending a
+    /// performative the peer does not expect can break the connection.
+    #[cfg(feature = "unstable-raw")]
+    pub async fn send_performative(&mut self, channel: u16, performative: crate::performative::Performative) -> AmqpResult<()> {
+        if self.state != ConnectionState::Open {
+            return Err(AmqpError::invalid_state("Connection is not open"));
+        }
+        if channel > self.channel_max() {
+            return Err(AmqpError::connection(format!(
+                "Channel {} exceeds channel-max {}",
+                channel,
+                self.channel_max()
+            )));
+        }
+
+        let payload = performative.encode()?;
+        let size = payload.len() + 8;
+        let max_frame_size = self.max_frame_size();
+        if size > max_frame_size as usize {
+            return Err(AmqpError::amqp_protocol(
+                AmqpCondition::AmqpErrorFramingError,
+                format!("Frame of {} bytes exceeds max-frame-size {}", size, max_frame_size),
+            ));
+        }
+
+        let demux = self
+            .demux
+            .as_ref()
+            .ok_or_else(|| AmqpError::invalid_state("Connection has no transport"))?;
+        demux.send(channel, performative).await
+    }
+
+    /// Get a handle for changing runtime knobs on this connection
+    pub fn tuning(&self) -> TuningHandle {
+        self.tuning.clone()
+    }
+
+    /// Subscribe to panics and stalls of the connection driver
+    pub fn watchdog_events(&self) -> tokio::sync::broadcast::Receiver<WatchdogEvent> {
+        self.watchdog.subscribe()
+    }
+
+    /// Subscribe to the driver's attempts to recover a lost transport
+    pub fn reconnect_events(&self) -> broadcast::Receiver<ReconnectEvent> {
+        self.reconnect_events.subscribe()
     }
 
     /// Get connection state
@@ -232,36 +612,26 @@ impl Connection {
         &self.id
     }
 
-    /// Send AMQP protocol header
-    async fn send_protocol_header(&self) -> AmqpResult<()> {
-        // AMQP 1.0 protocol header: "AMQP\x00\x01\x00\x00"
-        let header = [0x41, 0x4D, 0x51, 0x50, 0x00, 0x01, 0x00, 0x00];
-        
-        if let Some(stream) = &self.stream {
-            stream.writable().await
-                .map_err(|e| AmqpError::connection(format!("Stream not writable: {}", e)))?;
-            
-            stream.try_write(&header)
-                .map_err(|e| AmqpError::connection(format!("Failed to write protocol header: {}", e)))?;
-        }
+    /// Get the namer giving this connection's sessions and links their names
+    pub fn namer(&self) -> &Namer {
+        &self.namer
+    }
 
-        Ok(())
+    /// Get the Open received from the peer while the connection is open
+    pub fn remote_open(&self) -> Option<&Open> {
+        self.remote_open.as_ref()
     }
 
-    /// Send Open performative
-    async fn send_open(&self) -> AmqpResult<()> {
-        // This is a simplified implementation
-        // In a real implementation, you would encode the Open performative properly
-        log::debug!("Sending Open performative");
-        Ok(())
+    /// Get the largest frame either side accepts
+    pub fn max_frame_size(&self) -> u32 {
+        let local = self.config.max_frame_size;
+        self.remote_open.as_ref().map_or(local, |remote| local.min(remote.max_frame_size))
     }
 
-    /// Send Close performative
-    async fn send_close(&self) -> AmqpResult<()> {
-        // This is a simplified implementation
-        // In a real implementation, you would encode the Close performative properly
-        log::debug!("Sending Close performative");
-        Ok(())
+    /// Get the highest channel number either side accepts
+    pub fn channel_max(&self) -> u16 {
+        let local = self.config.channel_max;
+        self.remote_open.as_ref().map_or(local, |remote| local.min(remote.channel_max))
     }
 }
 
@@ -279,7 +649,9 @@ impl ConnectionBuilder {
         }
     }
 
-    /// Set the hostname
+    /// Set the hostname of the TCP endpoint
+    ///
+    /// Unless overridden, this is also the hostname announced to the peer.
     pub fn hostname(mut self, hostname: impl Into<String>) -> Self {
         self.config.hostname = hostname.into();
         self
@@ -327,116 +699,99 @@ impl ConnectionBuilder {
         self
     }
 
-    /// Build the connection
-    pub fn build(self) -> Connection {
-        Connection::new(self.config)
+    /// Set the virtual host, e.g. `/` or `production`
+    pub fn vhost(mut self, name: impl Into<String>) -> Self {
+        self.config.virtual_host = Some(name.into());
+        self
     }
-}
 
-impl Default for ConnectionBuilder {
-    fn default() -> Self {
-        Self::new()
+    /// Set the hostname announced to the peer instead of the derived one
+    pub fn hostname_override(mut self, hostname: impl Into<String>) -> Self {
+        self.config.hostname_override = Some(hostname.into());
+        self
     }
-}
-
-/// AMQP 1.0 Session
-#[derive(Debug, Clone)]
-pub struct Session {
-    /// Channel number
-    channel: u16,
-    /// Session ID
-    id: String,
-    /// Session state
-    state: SessionState,
-}
 
-/// AMQP 1.0 Session state
-#[derive(Debug, Clone, PartialEq)]
-pub enum SessionState {
-    /// Session is being established
-    Opening,
-    /// Session is open and ready
-    Open,
-    /// Session is being closed
-    Closing,
-    /// Session is closed
-    Closed,
-    /// Session is in error state
-    Error(String),
-}
-
-impl Session {
-    /// Create a new session
-    pub fn new(channel: u16, connection_id: String) -> Self {
-        Session {
-            channel,
-            id: format!("{}-session-{}", connection_id, channel),
-            state: SessionState::Closed,
-        }
+    /// Set the SASL authorization identity
+    pub fn sasl_authzid(mut self, authzid: impl Into<String>) -> Self {
+        self.config.sasl_authzid = Some(authzid.into());
+        self
     }
 
-    /// Open the session
-    pub async fn open(&mut self) -> AmqpResult<()> {
-        if self.state != SessionState::Closed {
-            return Err(AmqpError::invalid_state("Session is not in closed state"));
-        }
-
-        self.state = SessionState::Opening;
-        // In a real implementation, you would send the Begin performative here
-        self.state = SessionState::Open;
-        Ok(())
+    /// Authenticate over SASL with these credentials before opening
+    pub fn sasl(mut self, credentials: SaslCredentials) -> Self {
+        self.config.sasl = Some(credentials);
+        self
     }
 
-    /// Close the session
-    pub async fn close(&mut self) -> AmqpResult<()> {
-        if self.state != SessionState::Open {
-            return Err(AmqpError::invalid_state("Session is not open"));
-        }
+    /// Secure the connection with TLS
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, connector: crate::tls::TlsConnector) -> Self {
+        self.config.tls = Some(connector);
+        self
+    }
 
-        self.state = SessionState::Closing;
-        // In a real implementation, you would send the End performative here
-        self.state = SessionState::Closed;
-        Ok(())
+    /// Offer a capability to the peer in Open
+    pub fn offered_capability(mut self, capability: impl Into<AmqpSymbol>) -> Self {
+        self.config.offered_capabilities.push(capability.into());
+        self
     }
 
-    /// Get session state
-    pub fn state(&self) -> &SessionState {
-        &self.state
+    /// Ask the peer for a capability in Open
+    pub fn desired_capability(mut self, capability: impl Into<AmqpSymbol>) -> Self {
+        self.config.desired_capabilities.push(capability.into());
+        self
     }
 
-    /// Get session ID
-    pub fn id(&self) -> &str {
-        &self.id
+    /// Set how the connection driver is supervised
+    pub fn watchdog(mut self, watchdog: WatchdogConfig) -> Self {
+        self.config.watchdog = watchdog;
+        self
     }
 
-    /// Get channel number
-    pub fn channel(&self) -> u16 {
-        self.channel
+    /// Reconnect with this policy when the transport fails
+    ///
+    /// The watchdog still aborts a driver that leaves requests waiting for
+    /// longer than its stall timeout, so the policy's delays should add up
+    /// to less than that.
+    pub fn reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.config.reconnect = Some(policy);
+        self
     }
-}
 
-/// Session Builder for constructing AMQP 1.0 sessions
-#[derive(Debug, Clone)]
-pub struct SessionBuilder {
-    channel: u16,
+    /// Build the connection
+    pub fn build(self) -> Connection {
+        Connection::new(self.config)
+    }
 }
 
-impl SessionBuilder {
-    /// Create a new session builder
-    pub fn new(channel: u16) -> Self {
-        SessionBuilder { channel }
+/// Connect and run the handshake, refusing a peer whose frames would be too small
+async fn connect(config: NetworkConfig, id: &str, tuning: &TuningHandle, watchdog: &Watchdog) -> AmqpResult<NetworkConnection> {
+    let mut connection = NetworkConnection::with_handles(config, id.to_string(), tuning.clone(), watchdog.clone());
+    connection.connect().await?;
+    connection.negotiate_protocol().await?;
+    let remote = connection.remote_open().map_or(0, |open| open.max_frame_size);
+    if remote < MIN_MAX_FRAME_SIZE {
+        return Err(AmqpError::amqp_protocol(
+            AmqpCondition::AmqpErrorInvalidField,
+            format!("Peer max-frame-size {} is below the minimum of {}", remote, MIN_MAX_FRAME_SIZE),
+        ));
     }
+    Ok(connection)
+}
 
-    /// Build the session
-    pub fn build(self, connection_id: String) -> Session {
-        Session::new(self.channel, connection_id)
+impl Default for ConnectionBuilder {
+    fn default() -> Self {
+        Self::new()
     }
-} 
+}
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
-    use crate::types::AmqpValue;
+    use crate::performative::{self, Performative};
+    use crate::transport::{Frame, FrameHeader, FrameType, ProtocolHeader, Transport};
+    use crate::types::{AmqpValue, Role};
+    use tokio::net::{TcpListener, TcpStream};
 
     #[test]
     fn test_connection_state_creation() {
@@ -494,9 +849,11 @@ mod tests {
 
     #[test]
     fn test_connection_config_clone() {
-        let mut config = ConnectionConfig::default();
-        config.hostname = "test-host".to_string();
-        config.port = 5673;
+        let config = ConnectionConfig {
+            hostname: "test-host".to_string(),
+            port: 5673,
+            ..Default::default()
+        };
         
         let cloned = config.clone();
         
@@ -634,82 +991,6 @@ mod tests {
         assert!(!connection2.id().is_empty());
     }
 
-    #[test]
-    fn test_session_creation() {
-        let session = Session::new(1, "test-connection".to_string());
-        
-        assert_eq!(session.channel(), 1);
-        assert_eq!(session.id(), "test-connection-session-1");
-        assert_eq!(session.state(), &SessionState::Closed);
-    }
-
-    #[test]
-    fn test_session_state_creation() {
-        let opening = SessionState::Opening;
-        let open = SessionState::Open;
-        let closing = SessionState::Closing;
-        let closed = SessionState::Closed;
-        let error = SessionState::Error("session error".to_string());
-
-        assert!(matches!(opening, SessionState::Opening));
-        assert!(matches!(open, SessionState::Open));
-        assert!(matches!(closing, SessionState::Closing));
-        assert!(matches!(closed, SessionState::Closed));
-        assert!(matches!(error, SessionState::Error(_)));
-    }
-
-    #[test]
-    fn test_session_state_clone() {
-        let state = SessionState::Open;
-        let cloned = state.clone();
-        
-        assert_eq!(state, cloned);
-    }
-
-    #[test]
-    fn test_session_state_equality() {
-        let state1 = SessionState::Open;
-        let state2 = SessionState::Open;
-        let state3 = SessionState::Closed;
-        
-        assert_eq!(state1, state2);
-        assert_ne!(state1, state3);
-        
-        let error1 = SessionState::Error("error1".to_string());
-        let error2 = SessionState::Error("error1".to_string());
-        let error3 = SessionState::Error("error2".to_string());
-        
-        assert_eq!(error1, error2);
-        assert_ne!(error1, error3);
-    }
-
-    #[test]
-    fn test_session_builder_new() {
-        let builder = SessionBuilder::new(5);
-        assert_eq!(builder.channel, 5);
-    }
-
-    #[test]
-    fn test_session_builder_build() {
-        let builder = SessionBuilder::new(3);
-        let session = builder.build("test-connection".to_string());
-        
-        assert_eq!(session.channel(), 3);
-        assert_eq!(session.id(), "test-connection-session-3");
-        assert_eq!(session.state(), &SessionState::Closed);
-    }
-
-    #[test]
-    fn test_connection_create_session() {
-        let config = ConnectionConfig::default();
-        let connection = Connection::new(config);
-        
-        // This should fail because connection is not open
-        // Note: create_session is async, so we can't test the actual error in a sync test
-        // In a real async test, we would await the result
-        assert_eq!(connection.state(), &ConnectionState::Closed);
-    }
-
     #[test]
     fn test_connection_state_transitions() {
         let config = ConnectionConfig::default();
@@ -737,14 +1018,16 @@ mod tests {
 
     #[test]
     fn test_connection_with_custom_config() {
-        let mut config = ConnectionConfig::default();
-        config.hostname = "custom-host".to_string();
-        config.port = 8888;
-        config.timeout = Duration::from_secs(60);
-        config.max_frame_size = 131072;
-        config.channel_max = 500;
-        config.idle_timeout = Duration::from_secs(120);
-        config.container_id = "custom-container".to_string();
+        let config = ConnectionConfig {
+            hostname: "custom-host".to_string(),
+            port: 8888,
+            timeout: Duration::from_secs(60),
+            max_frame_size: 131072,
+            channel_max: 500,
+            idle_timeout: Duration::from_secs(120),
+            container_id: "custom-container".to_string(),
+            ..Default::default()
+        };
         
         let connection = Connection::new(config);
         
@@ -781,25 +1064,625 @@ mod tests {
         assert_eq!(connection.config.properties.len(), 2);
     }
 
+    async fn send_payload(peer: &mut Transport, payload: Vec<u8>) {
+        let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, 0);
+        peer.send_frame(Frame::new(header, payload)).await.unwrap();
+    }
+
+    async fn send_sasl(peer: &mut Transport, payload: Vec<u8>) {
+        let header = FrameHeader::new(payload.len() as u32, FrameType::SASL as u8, 0);
+        peer.send_frame(Frame::new(header, payload)).await.unwrap();
+    }
+
+    /// Answer the next connection's headers and Open, returning the Open it sent
+    async fn accept_open(listener: &TcpListener, open: Open) -> (Transport, Open) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut peer = Transport::new(stream);
+        assert_eq!(peer.receive_raw(8).await.unwrap(), ProtocolHeader::AMQP.as_bytes());
+        peer.send_raw(ProtocolHeader::AMQP.as_bytes()).await.unwrap();
+        let received = Open::decode(&peer.receive_frame().await.unwrap().payload).unwrap();
+        send_payload(&mut peer, open.encode().unwrap()).await;
+        (peer, received)
+    }
+
+    /// Answer Begin, Attach, Detach and End as a peer accepting them would
+    async fn mirror(peer: &mut Transport, frame: &Frame) -> AmqpResult<Option<Performative>> {
+        let performative = match Performative::decode(&frame.payload) {
+            Ok(performative) => performative,
+            Err(_) => return Ok(None),
+        };
+        let answer = match &performative {
+            Performative::Begin(begin) => Performative::Begin(performative::Begin {
+                remote_channel: Some(frame.header.channel),
+                ..begin.clone()
+            }),
+            Performative::Attach(attach) => {
+                let role = match attach.role {
+                    Role::Sender => Role::Receiver,
+                    Role::Receiver => Role::Sender,
+                };
+                // A null terminus would refuse the link, so stand one in for any the link left out
+                Performative::Attach(performative::Attach {
+                    role,
+                    source: attach.source.clone().or_else(|| Some(Default::default())),
+                    target: attach.target.clone().or_else(|| Some(Default::default())),
+                    initial_delivery_count: (role == Role::Sender).then_some(0),
+                    max_message_size: None,
+                    ..attach.clone()
+                })
+            }
+            Performative::Detach(detach) => Performative::Detach(detach.clone()),
+            Performative::End(_) => Performative::End(performative::End::default()),
+            _ => return Ok(Some(performative)),
+        };
+        let payload = answer.encode()?;
+        let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, frame.header.channel);
+        peer.send_frame(Frame::new(header, payload)).await?;
+        Ok(Some(performative))
+    }
+
+    /// Mirror the connection's sessions and links, then answer its Close with one carrying `error`
+    async fn answer_close(mut peer: Transport, error: Option<crate::types::AmqpError>) -> AmqpResult<()> {
+        loop {
+            let frame = peer.receive_frame().await?;
+            if Close::decode(&frame.payload).is_ok() {
+                send_payload(&mut peer, Close { error }.encode()?).await;
+                return Ok(());
+            }
+            mirror(&mut peer, &frame).await?;
+        }
+    }
+
+    /// Play the peer of one connection, answering its Open, sessions, links and Close
+    pub(crate) async fn answer_connection(stream: TcpStream) -> AmqpResult<()> {
+        let mut peer = Transport::new(stream);
+        peer.receive_raw(8).await?;
+        peer.send_raw(ProtocolHeader::AMQP.as_bytes()).await?;
+        peer.receive_frame().await?;
+        send_payload(&mut peer, Open { container_id: "peer".to_string(), ..Default::default() }.encode()?).await;
+        answer_close(peer, None).await
+    }
+
+    /// Answer every connection made to a local port, returning a configuration for it
+    pub(crate) async fn local_peer() -> ConnectionConfig {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(answer_connection(stream));
+            }
+        });
+
+        ConnectionConfig {
+            hostname: "127.0.0.1".to_string(),
+            port,
+            ..Default::default()
+        }
+    }
+
+    async fn open_local() -> (Connection, Transport) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut connection = ConnectionBuilder::new()
+            .hostname("127.0.0.1")
+            .port(listener.local_addr().unwrap().port())
+            .build();
+        let open = Open { container_id: "peer".to_string(), ..Default::default() };
+        let (opened, (peer, _)) = tokio::join!(connection.open(), accept_open(&listener, open));
+        opened.unwrap();
+        (connection, peer)
+    }
+
+    #[tokio::test]
+    async fn test_create_session_requires_open_connection() {
+        let mut connection = Connection::new(ConnectionConfig::default());
+        assert!(matches!(connection.create_session().await, Err(AmqpError::InvalidState(_))));
+    }
+
+    #[tokio::test]
+    async fn test_create_session_respects_channel_max() {
+        let (mut connection, peer) = open_local().await;
+        tokio::spawn(answer_close(peer, None));
+        connection.config.channel_max = 1;
+
+        assert_eq!(connection.create_session().await.unwrap().channel(), 0);
+        assert_eq!(connection.create_session().await.unwrap().channel(), 1);
+        assert!(connection.create_session().await.is_err());
+        assert_eq!(connection.session_count(), 2);
+
+        let scope = connection.namer().scope().to_string();
+        assert_eq!(connection.session_mut(0).unwrap().name(), format!("session-1-{}", scope));
+        assert_eq!(connection.session_mut(1).unwrap().name(), format!("session-2-{}", scope));
+    }
+
+    #[tokio::test]
+    async fn test_close_cascades_to_sessions_and_links() {
+        let (mut connection, peer) = open_local().await;
+        tokio::spawn(answer_close(peer, None));
+        let mut sender = connection
+            .create_session()
+            .await
+            .unwrap()
+            .create_sender(crate::link::LinkConfig::default())
+            .await
+            .unwrap();
+        sender.attach().await.unwrap();
+        sender.add_credit(1);
+        connection.create_session().await.unwrap();
+
+        connection.close().await.unwrap();
+        assert_eq!(connection.state(), &ConnectionState::Closed);
+        assert_eq!(connection.session_count(), 0);
+        assert!(matches!(
+            sender.send(crate::Message::text("late")).await.map_err(AmqpError::into_inner),
+            Err(AmqpError::InvalidState(_))
+        ));
+        assert_eq!(sender.state(), &crate::link::LinkState::Detached);
+    }
+
+    #[tokio::test]
+    async fn test_links_exchange_traffic_with_peer() {
+        let (mut connection, mut peer) = open_local().await;
+        let peer = tokio::spawn(async move {
+            // Grant the sender credit and send the receiver a message once both are attached
+            let mut attached = 0;
+            while attached < 2 {
+                let frame = peer.receive_frame().await?;
+                if let Some(Performative::Attach(attach)) = mirror(&mut peer, &frame).await? {
+                    attached += 1;
+                    let answer = match attach.role {
+                        Role::Sender => Performative::Flow(performative::Flow {
+                            handle: Some(attach.handle),
+                            delivery_count: Some(0),
+                            link_credit: Some(5),
+                            ..Default::default()
+                        }),
+                        Role::Receiver => {
+                            let mut encoder = crate::codec::Encoder::new();
+                            encoder.encode_message(&crate::Message::text("from peer"))?;
+                            Performative::Transfer(performative::Transfer {
+                                handle: attach.handle,
+                                delivery_id: Some(0),
+                                delivery_tag: Some(vec![0]),
+                                payload: encoder.finish(),
+                                ..Default::default()
+                            })
+                        }
+                    };
+                    let payload = answer.encode()?;
+                    let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, frame.header.channel);
+                    peer.send_frame(Frame::new(header, payload)).await?;
+                }
+            }
+            answer_close(peer, None).await
+        });
+
+        let session = connection.create_session().await.unwrap();
+        let mut sender = session.create_sender(crate::link::LinkConfig::default()).await.unwrap();
+        let mut receiver = session.create_receiver(crate::link::LinkConfig::default()).await.unwrap();
+        assert!(sender.attach().await.unwrap().is_attached());
+        assert!(receiver.attach().await.unwrap().is_attached());
+
+        let message = loop {
+            if let Some(message) = receiver.receive().await.unwrap() {
+                break message;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        };
+        assert_eq!(message.body_as_text(), Some("from peer"));
+        // Credit came from the peer's Flow, which arrived before the Transfer
+        sender.send(crate::Message::text("to peer")).await.unwrap();
+        assert_eq!(sender.credit(), 4);
+
+        connection.close().await.unwrap();
+        peer.await.unwrap().unwrap();
+    }
+
+    /// Mirror a connection's links, granting credit, until the first Transfer arrives
+    async fn first_transfer(peer: &mut Transport) -> AmqpResult<(u16, performative::Transfer)> {
+        loop {
+            let frame = peer.receive_frame().await?;
+            match mirror(peer, &frame).await? {
+                Some(Performative::Attach(attach)) if attach.role == Role::Sender => {
+                    let flow = Performative::Flow(performative::Flow {
+                        handle: Some(attach.handle),
+                        delivery_count: Some(0),
+                        link_credit: Some(5),
+                        ..Default::default()
+                    });
+                    let payload = flow.encode()?;
+                    let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, frame.header.channel);
+                    peer.send_frame(Frame::new(header, payload)).await?;
+                }
+                Some(Performative::Transfer(transfer)) => return Ok((frame.header.channel, transfer)),
+                _ => {}
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_replays_links_and_resends_unsettled() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let peer = tokio::spawn(async move {
+            // The first connection drops as soon as the transfer arrives, unsettled
+            let open = Open { container_id: "peer".to_string(), ..Default::default() };
+            let (mut first, _) = accept_open(&listener, open.clone()).await;
+            let (_, lost) = first_transfer(&mut first).await?;
+            drop(first);
+
+            let (mut second, _) = accept_open(&listener, open).await;
+            let (channel, resent) = first_transfer(&mut second).await?;
+            assert_eq!((resent.delivery_id, &resent.delivery_tag), (lost.delivery_id, &lost.delivery_tag));
+            assert_eq!(resent.payload, lost.payload);
+            let disposition = Performative::Disposition(performative::Disposition {
+                role: Role::Receiver,
+                first: resent.delivery_id.unwrap(),
+                last: None,
+                settled: true,
+                state: Some(performative::Outcome::Accepted),
+                batchable: false,
+            });
+            let payload = disposition.encode()?;
+            let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, channel);
+            second.send_frame(Frame::new(header, payload)).await?;
+            answer_close(second, None).await
+        });
+
+        let mut connection = ConnectionBuilder::new()
+            .hostname("127.0.0.1")
+            .port(port)
+            .reconnect(ReconnectPolicy {
+                initial_backoff: Duration::from_millis(10),
+                jitter: 0.0,
+                ..Default::default()
+            })
+            .build();
+        let mut events = connection.reconnect_events();
+        connection.open().await.unwrap();
+        let session = connection.create_session().await.unwrap();
+        let mut sender = session.create_sender(crate::link::LinkConfig::default()).await.unwrap();
+        assert!(sender.attach().await.unwrap().is_attached());
+        // Credit arrives with the peer's Flow, shortly after its Attach
+        let delivery = loop {
+            match sender.send(crate::Message::text("survives")).await.map_err(AmqpError::into_inner) {
+                Ok(delivery) => break delivery,
+                Err(AmqpError::Link(_)) => tokio::time::sleep(Duration::from_millis(5)).await,
+                Err(e) => panic!("send failed: {}", e),
+            }
+        };
+        let outcome = timeout(Duration::from_secs(5), sender.outcome(delivery)).await.unwrap().unwrap();
+        assert_eq!(outcome, crate::link::DeliveryOutcome::Accepted);
+
+        assert!(matches!(events.recv().await.unwrap(), ReconnectEvent::Disconnected { .. }));
+        assert_eq!(
+            events.recv().await.unwrap(),
+            ReconnectEvent::Attempting { attempt: 1, delay: Duration::from_millis(10) }
+        );
+        assert_eq!(events.recv().await.unwrap(), ReconnectEvent::Reconnected { attempts: 1, resent: 1 });
+        connection.close().await.unwrap();
+        peer.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_close_reports_session_errors_after_closing() {
+        let (mut connection, peer) = open_local().await;
+        tokio::spawn(answer_close(peer, None));
+        let mut peers = Vec::new();
+        for _ in 0..2 {
+            let (local, remote) = crate::performative::Endpoint::pair();
+            connection.create_session().await.unwrap().set_endpoint(local);
+            let error = crate::types::AmqpError::new(AmqpCondition::AmqpErrorInternalError);
+            remote
+                .send(crate::performative::Performative::End(crate::performative::End { error: Some(error) }))
+                .unwrap();
+            peers.push(remote);
+        }
+
+        match connection.close().await {
+            Err(AmqpError::Connection(message)) => {
+                assert!(message.contains("channel 0"));
+                assert!(message.contains("channel 1"));
+            }
+            other => panic!("Expected aggregated connection error, got {:?}", other),
+        }
+        assert_eq!(connection.state(), &ConnectionState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_end_session() {
+        let (mut connection, peer) = open_local().await;
+        tokio::spawn(answer_close(peer, None));
+        connection.create_session().await.unwrap();
+
+        connection.end_session(0).await.unwrap();
+        assert!(connection.session_mut(0).is_none());
+        assert!(connection.end_session(0).await.is_err());
+    }
+
     #[test]
-    fn test_session_methods() {
-        let session = Session::new(10, "test-connection".to_string());
-        
-        // Test initial state
-        assert_eq!(session.channel(), 10);
-        assert_eq!(session.id(), "test-connection-session-10");
-        assert_eq!(session.state(), &SessionState::Closed);
-        
-        // Test state access
-        let state = session.state();
-        assert_eq!(state, &SessionState::Closed);
-        
-        // Test ID access
-        let id = session.id();
-        assert_eq!(id, "test-connection-session-10");
-        
-        // Test channel access
-        let channel = session.channel();
-        assert_eq!(channel, 10);
+    fn test_hostnames_default_to_network_hostname() {
+        let config = ConnectionBuilder::new().hostname("broker.example.com").build().config;
+        let hostnames = config.hostnames().unwrap();
+        assert_eq!(hostnames.open, "broker.example.com");
+        assert_eq!(hostnames.sasl, "broker.example.com");
+        assert_eq!(hostnames.sni.as_deref(), Some("broker.example.com"));
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_hostnames_with_vhost() {
+        let config = ConnectionBuilder::new().hostname("broker.example.com").vhost("/").build().config;
+        let hostnames = config.hostnames().unwrap();
+        assert_eq!(hostnames.open, "vhost:/");
+        assert_eq!(hostnames.sasl, "vhost:/");
+        assert_eq!(hostnames.sni.as_deref(), Some("broker.example.com"));
+
+        let config = ConnectionBuilder::new().vhost("vhost:production").build().config;
+        assert_eq!(config.hostnames().unwrap().open, "vhost:production");
+    }
+
+    #[test]
+    fn test_hostnames_with_override() {
+        let config = ConnectionBuilder::new()
+            .hostname("10.0.0.5")
+            .hostname_override("tenant.example.com")
+            .build()
+            .config;
+        let hostnames = config.hostnames().unwrap();
+        assert_eq!(hostnames.open, "tenant.example.com");
+        assert_eq!(hostnames.sasl, "tenant.example.com");
+        assert_eq!(hostnames.sni.as_deref(), Some("tenant.example.com"));
+
+        let config = ConnectionBuilder::new().hostname("10.0.0.5").build().config;
+        assert!(config.hostnames().unwrap().sni.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_conflicting_vhost_override_rejected() {
+        let mut connection = ConnectionBuilder::new()
+            .vhost("production")
+            .hostname_override("vhost:staging")
+            .build();
+        assert!(matches!(connection.config.hostnames(), Err(AmqpError::Connection(_))));
+        assert!(connection.open().await.is_err());
+        assert_eq!(connection.state(), &ConnectionState::Closed);
+    }
+
+    #[cfg(feature = "unstable-raw")]
+    #[tokio::test]
+    async fn test_send_performative_writes_frame() {
+        use crate::performative::{End, Performative};
+
+        let (mut connection, mut peer) = open_local().await;
+        connection.send_performative(3, Performative::End(End::default())).await.unwrap();
+
+        let frame = peer.receive_frame().await.unwrap();
+        assert_eq!(frame.header.channel, 3);
+        assert_eq!(Performative::decode(&frame.payload).unwrap(), Performative::End(End::default()));
+    }
+
+    #[cfg(feature = "unstable-raw")]
+    #[tokio::test]
+    async fn test_send_performative_requires_open_connection() {
+        let mut connection = Connection::new(ConnectionConfig::default());
+        let end = crate::performative::Performative::End(Default::default());
+        assert!(matches!(connection.send_performative(0, end).await, Err(AmqpError::InvalidState(_))));
+    }
+
+    #[test]
+    fn test_gateway_identity_separate_from_endpoint() {
+        let connection = ConnectionBuilder::new()
+            .hostname("10.0.12.7")
+            .hostname_override("tenant-a.example.com")
+            .sasl_authzid("tenant-a")
+            .build();
+
+        assert_eq!(connection.config.hostname, "10.0.12.7");
+        assert_eq!(connection.config.hostnames().unwrap().open, "tenant-a.example.com");
+        assert_eq!(
+            connection.config.sasl_plain_response("gateway", "secret").unwrap(),
+            b"tenant-a\0gateway\0secret"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_open_sends_sasl_authzid() {
+        use crate::sasl::{SaslCode, SaslInit, SaslMechanisms, SaslOutcome};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // Answers one connection, returning the initial response of its SASL init
+        let broker = || async {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut peer = Transport::new(stream);
+            assert_eq!(peer.receive_raw(8).await.unwrap(), ProtocolHeader::SASL.as_bytes());
+            peer.send_raw(ProtocolHeader::SASL.as_bytes()).await.unwrap();
+            let mechanisms = SaslMechanisms { mechanisms: vec![AmqpSymbol::from("PLAIN"), AmqpSymbol::from("EXTERNAL")] };
+            send_sasl(&mut peer, mechanisms.encode().unwrap()).await;
+            let init = SaslInit::decode(&peer.receive_frame().await.unwrap().payload).unwrap();
+            send_sasl(&mut peer, SaslOutcome { code: SaslCode::Ok, additional_data: None }.encode().unwrap()).await;
+
+            assert_eq!(peer.receive_raw(8).await.unwrap(), ProtocolHeader::AMQP.as_bytes());
+            peer.send_raw(ProtocolHeader::AMQP.as_bytes()).await.unwrap();
+            peer.receive_frame().await.unwrap();
+            send_payload(&mut peer, Open { container_id: "broker".to_string(), ..Default::default() }.encode().unwrap()).await;
+            (peer, init.initial_response)
+        };
+        let builder = |credentials| {
+            ConnectionBuilder::new()
+                .hostname("127.0.0.1")
+                .port(port)
+                .sasl(credentials)
+                .sasl_authzid("tenant-a")
+        };
+
+        let mut connection = builder(SaslCredentials::plain("gateway", "secret")).build();
+        let (opened, (_peer, response)) = tokio::join!(connection.open(), broker());
+        opened.unwrap();
+        assert_eq!(response.as_deref(), Some(&b"tenant-a\0gateway\0secret"[..]));
+
+        let mut connection = builder(SaslCredentials::external()).build();
+        let (opened, (_peer, response)) = tokio::join!(connection.open(), broker());
+        opened.unwrap();
+        assert_eq!(response.as_deref(), Some(&b"tenant-a"[..]));
+
+        // Credentials naming their own authzid keep it
+        let own = SaslCredentials::External { authzid: Some("tenant-b".to_string()) };
+        let mut connection = builder(own).build();
+        let (opened, (_peer, response)) = tokio::join!(connection.open(), broker());
+        opened.unwrap();
+        assert_eq!(response.as_deref(), Some(&b"tenant-b"[..]));
+    }
+
+    #[tokio::test]
+    async fn test_open_negotiates_with_peer_and_close_propagates_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut connection = ConnectionBuilder::new()
+            .hostname("127.0.0.1")
+            .port(listener.local_addr().unwrap().port())
+            .vhost("production")
+            .container_id("client-1")
+            .property("product", AmqpValue::String("test".to_string()))
+            .build();
+        let open = Open {
+            container_id: "broker".to_string(),
+            max_frame_size: 4096,
+            channel_max: 7,
+            idle_time_out: Some(10_000),
+            ..Default::default()
+        };
+        let (opened, (peer, sent)) = tokio::join!(connection.open(), accept_open(&listener, open));
+        opened.unwrap();
+
+        assert_eq!(sent.container_id, "client-1");
+        assert_eq!(sent.hostname.as_deref(), Some("vhost:production"));
+        assert_eq!((sent.max_frame_size, sent.channel_max, sent.idle_time_out), (65536, 1000, None));
+        assert_eq!(sent.properties.get(&AmqpSymbol::from("product")), Some(&AmqpValue::String("test".to_string())));
+        assert_eq!(connection.state(), &ConnectionState::Open);
+        assert_eq!(connection.remote_open().unwrap().container_id, "broker");
+        assert_eq!((connection.max_frame_size(), connection.channel_max()), (4096, 7));
+        assert!(connection.tuning().heartbeat_limit().is_some_and(|limit| limit <= Duration::from_secs(10)));
+
+        let error = crate::types::AmqpError::new(AmqpCondition::AmqpErrorResourceLimitExceeded)
+            .with_description("broker shutting down");
+        tokio::spawn(answer_close(peer, Some(error)));
+        match connection.close().await {
+            Err(AmqpError::AmqpProtocol { condition, description }) => {
+                assert_eq!(condition, AmqpCondition::AmqpErrorResourceLimitExceeded);
+                assert_eq!(description, "broker shutting down");
+            }
+            other => panic!("Expected the peer's close error, got {:?}", other),
+        }
+        assert!(matches!(connection.state(), ConnectionState::Error(reason) if reason.contains("broker shutting down")));
+        assert!(connection.remote_open().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_open_sends_heartbeats_and_closes_silent_peer() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut connection = ConnectionBuilder::new()
+            .hostname("127.0.0.1")
+            .port(listener.local_addr().unwrap().port())
+            .idle_timeout(Duration::from_millis(400))
+            .build();
+        let open = Open { container_id: "broker".to_string(), idle_time_out: Some(200), ..Default::default() };
+        let (opened, (mut peer, sent)) = tokio::join!(connection.open(), accept_open(&listener, open));
+        opened.unwrap();
+        assert_eq!(sent.idle_time_out, Some(400));
+
+        // Empty frames keep the connection within the peer's idle timeout
+        let frame = timeout(Duration::from_secs(2), peer.receive_frame()).await.unwrap().unwrap();
+        assert!(frame.payload.is_empty());
+
+        // The peer stays silent past ours, so it is sent a Close
+        let close = loop {
+            let frame = timeout(Duration::from_secs(2), peer.receive_frame()).await.unwrap().unwrap();
+            if !frame.payload.is_empty() {
+                break Close::decode(&frame.payload).unwrap();
+            }
+        };
+        assert_eq!(close.error.unwrap().condition, AmqpCondition::AmqpErrorResourceLimitExceeded);
+        assert!(connection.close().await.is_err());
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn test_open_sends_derived_hostnames_for_sni_sasl_and_open() {
+        use crate::sasl::{SaslCode, SaslCredentials, SaslInit, SaslMechanisms, SaslOutcome};
+        use crate::tls::{TlsAcceptorBuilder, TlsConnectorBuilder};
+
+        const CA: &str = include_str!("../testdata/tls/ca.pem");
+        let acceptor = TlsAcceptorBuilder::new(include_str!("../testdata/tls/server.pem"), include_str!("../testdata/tls/server.key"))
+            .build()
+            .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // Answers one connection, returning the SNI, SASL and Open hostnames it was sent
+        let broker = || async {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (stream, incoming) = acceptor.accept(stream).await.unwrap();
+            let mut peer = Transport::new(stream);
+            assert_eq!(peer.receive_raw(8).await.unwrap(), ProtocolHeader::SASL.as_bytes());
+            peer.send_raw(ProtocolHeader::SASL.as_bytes()).await.unwrap();
+            send_sasl(&mut peer, SaslMechanisms { mechanisms: vec![AmqpSymbol::from("PLAIN")] }.encode().unwrap()).await;
+            let init = SaslInit::decode(&peer.receive_frame().await.unwrap().payload).unwrap();
+            send_sasl(&mut peer, SaslOutcome { code: SaslCode::Ok, additional_data: None }.encode().unwrap()).await;
+
+            assert_eq!(peer.receive_raw(8).await.unwrap(), ProtocolHeader::AMQP.as_bytes());
+            peer.send_raw(ProtocolHeader::AMQP.as_bytes()).await.unwrap();
+            let open = Open::decode(&peer.receive_frame().await.unwrap().payload).unwrap();
+            send_payload(&mut peer, Open { container_id: "broker".to_string(), ..Default::default() }.encode().unwrap()).await;
+            (peer, (incoming.tls.and_then(|tls| tls.server_name), init.hostname, open.hostname))
+        };
+        let builder = |hostname: &str| {
+            ConnectionBuilder::new()
+                .hostname(hostname)
+                .port(port)
+                .sasl(SaslCredentials::plain("guest", "guest"))
+                .tls(TlsConnectorBuilder::new(CA).build().unwrap())
+        };
+
+        // The virtual host goes in SASL init and Open, the DNS name in SNI
+        let mut connection = builder("localhost").vhost("production").build();
+        let (opened, (_peer, sent)) = tokio::join!(connection.open(), broker());
+        opened.unwrap();
+        let vhost = Some("vhost:production".to_string());
+        assert_eq!(sent, (Some("localhost".to_string()), vhost.clone(), vhost));
+
+        // Connecting by address, the certificate is verified against the override
+        let mut connection = builder("127.0.0.1").hostname_override("localhost").build();
+        let (opened, (_peer, sent)) = tokio::join!(connection.open(), broker());
+        opened.unwrap();
+        let localhost = Some("localhost".to_string());
+        assert_eq!(sent, (localhost.clone(), localhost.clone(), localhost));
+    }
+
+    #[tokio::test]
+    async fn test_open_fails_on_refusal_or_invalid_peer_open() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut connection = ConnectionBuilder::new()
+            .hostname("127.0.0.1")
+            .port(listener.local_addr().unwrap().port())
+            .build();
+
+        let refuse = async {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut peer = Transport::new(stream);
+            peer.receive_raw(8).await.unwrap();
+            peer.send_raw(ProtocolHeader::AMQP.as_bytes()).await.unwrap();
+            peer.receive_frame().await.unwrap();
+            let error = crate::types::AmqpError::new(AmqpCondition::AmqpErrorNotAllowed).with_description("no");
+            send_payload(&mut peer, Close { error: Some(error) }.encode().unwrap()).await;
+            peer
+        };
+        let (opened, _peer) = tokio::join!(connection.open(), refuse);
+        assert!(matches!(opened, Err(AmqpError::AmqpProtocol { condition: AmqpCondition::AmqpErrorNotAllowed, .. })));
+        assert!(matches!(connection.state(), ConnectionState::Error(_)));
+
+        // A failed connection can be opened again
+        let open = Open { container_id: "broker".to_string(), max_frame_size: 256, ..Default::default() };
+        let (opened, _peer) = tokio::join!(connection.open(), accept_open(&listener, open));
+        assert!(matches!(opened, Err(AmqpError::AmqpProtocol { condition: AmqpCondition::AmqpErrorInvalidField, .. })));
+        assert!(matches!(connection.state(), ConnectionState::Error(_)));
+    }
+}
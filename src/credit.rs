@@ -0,0 +1,346 @@
+//! AMQP 1.0 Link Credit Counters
+//!
+//! This module holds the flow-control state of a link in a form that can be
+//! shared across tasks. A [`LinkCredit`] is owned by a sender or receiver
+//! and handed out as an `Arc`, so a task reading Flow frames can grant
+//! credit while another is blocked in `send`, without either needing `&mut`
+//! access to the link.
+//!
+//! # Memory ordering
+//!
+//! The link credit and the delivery-count are packed into a single
+//! `AtomicU64`, delivery-count in the high half and credit in the low half.
+//! The spec defines the credit relative to the delivery-count, so the two
+//! must change together: taking credit for a transfer decrements one and
+//! increments the other in one compare-and-swap, and a snapshot for an
+//! outgoing Flow never sees the halves of two different updates.
+//!
+//! Updates use `AcqRel` and reads use `Acquire`. A task that observes
+//! credit granted by another therefore also observes everything the
+//! granting task wrote before the grant, such as link state changed while
+//! handling the same Flow.
+//!
+//! The next delivery ID is a plain counter in its own `AtomicU32`. Only the
+//! uniqueness of the IDs it hands out matters, which the atomicity of
+//! `fetch_add` guarantees on its own, so it uses `Relaxed`.
+//!
+//! The model-checked tests at the bottom of this file run under loom:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg dumq_loom" cargo test --release --lib credit::loom_tests
+//! ```
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::credit::LinkCredit;
+//!
+//! let credit = LinkCredit::new(0);
+//! credit.add(2);
+//! assert_eq!(credit.try_acquire(), Some(0));
+//! assert_eq!(credit.try_acquire(), Some(1));
+//! assert_eq!(credit.try_acquire(), None);
+//! assert_eq!(credit.delivery_count(), 2);
+//! ```
+
+#[cfg(dumq_loom)]
+use loom::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+#[cfg(not(dumq_loom))]
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use crate::performative::Flow;
+
+/// A consistent view of a link's flow-control state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CreditState {
+    /// Delivery-count of the link
+    pub delivery_count: u32,
+    /// Link credit left
+    pub link_credit: u32,
+}
+
+impl CreditState {
+    fn pack(self) -> u64 {
+        (u64::from(self.delivery_count) << 32) | u64::from(self.link_credit)
+    }
+
+    fn unpack(packed: u64) -> Self {
+        CreditState {
+            delivery_count: (packed >> 32) as u32,
+            link_credit: packed as u32,
+        }
+    }
+}
+
+/// Link credit, delivery-count and delivery IDs, updated without locks
+#[derive(Debug)]
+pub struct LinkCredit {
+    /// Delivery-count in the high half, link credit in the low half
+    state: AtomicU64,
+    /// Next delivery ID to hand out
+    next_delivery_id: AtomicU32,
+}
+
+impl LinkCredit {
+    /// Create counters with no credit, starting at a delivery-count
+    ///
+    /// Delivery IDs start at 1.
+    pub fn new(initial_delivery_count: u32) -> Self {
+        LinkCredit {
+            state: AtomicU64::new(CreditState { delivery_count: initial_delivery_count, link_credit: 0 }.pack()),
+            next_delivery_id: AtomicU32::new(1),
+        }
+    }
+
+    /// Get the delivery-count and credit as of one point in time
+    pub fn snapshot(&self) -> CreditState {
+        CreditState::unpack(self.state.load(Ordering::Acquire))
+    }
+
+    /// Get the link credit left
+    pub fn credit(&self) -> u32 {
+        self.snapshot().link_credit
+    }
+
+    /// Get the delivery-count
+    pub fn delivery_count(&self) -> u32 {
+        self.snapshot().delivery_count
+    }
+
+    /// Add credit, saturating at `u32::MAX`
+    pub fn add(&self, credit: u32) {
+        self.update(|state| CreditState { link_credit: state.link_credit.saturating_add(credit), ..state });
+    }
+
+    /// Replace the credit, returning the credit it had
+    pub fn set(&self, credit: u32) -> u32 {
+        self.update(|state| CreditState { link_credit: credit, ..state }).link_credit
+    }
+
+    /// Move the delivery-count, e.g. to the one the peer announced on attach
+    pub fn set_delivery_count(&self, delivery_count: u32) {
+        self.update(|state| CreditState { delivery_count, ..state });
+    }
+
+    /// Take one credit for a transfer
+    ///
+    /// Returns the delivery-count the transfer was sent at, or `None` with
+    /// the state unchanged if there is no credit left.
+    pub fn try_acquire(&self) -> Option<u32> {
+        let mut current = self.state.load(Ordering::Acquire);
+        loop {
+            let state = CreditState::unpack(current);
+            if state.link_credit == 0 {
+                return None;
+            }
+            let next = CreditState {
+                delivery_count: state.delivery_count.wrapping_add(1),
+                link_credit: state.link_credit - 1,
+            };
+            match self.state.compare_exchange_weak(current, next.pack(), Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => return Some(state.delivery_count),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Give back a credit taken by [`LinkCredit::try_acquire`]
+    ///
+    /// For a transfer that could not be written after all.
+    pub fn refund(&self) {
+        self.update(|state| CreditState {
+            delivery_count: state.delivery_count.wrapping_sub(1),
+            link_credit: state.link_credit.saturating_add(1),
+        });
+    }
+
+    /// Count a transfer that arrived from the peer, returning its delivery-count
+    ///
+    /// The transfer uses up a unit of credit, as it did at the sender; the
+    /// receiver decides when to replenish it.
+    pub fn record_transfer(&self) -> u32 {
+        self.update(|state| CreditState {
+            delivery_count: state.delivery_count.wrapping_add(1),
+            link_credit: state.link_credit.saturating_sub(1),
+        })
+        .delivery_count
+    }
+
+    /// Apply a Flow from the receiving peer, returning the credit now available
+    ///
+    /// The receiver's credit is relative to its delivery-count, which may lag
+    /// behind this sender's by the transfers still in flight:
+    /// `delivery-count(rcv) + link-credit(rcv) - delivery-count(snd)`, in
+    /// serial number arithmetic. A Flow without a delivery-count counts from
+    /// the initial one. A Flow without link credit leaves the credit as is.
+    pub fn apply_flow(&self, flow: &Flow, initial_delivery_count: u32) -> u32 {
+        let Some(link_credit) = flow.link_credit else {
+            return self.credit();
+        };
+        let limit = flow.delivery_count.unwrap_or(initial_delivery_count).wrapping_add(link_credit);
+        let next = |state: CreditState| CreditState {
+            link_credit: (limit.wrapping_sub(state.delivery_count) as i32).max(0) as u32,
+            ..state
+        };
+        next(self.update(next)).link_credit
+    }
+
+    /// Hand out the next delivery ID
+    pub fn next_delivery_id(&self) -> u32 {
+        self.next_delivery_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Get the delivery ID that will be handed out next
+    pub fn peek_delivery_id(&self) -> u32 {
+        self.next_delivery_id.load(Ordering::Relaxed)
+    }
+
+    /// Apply `f` atomically, returning the state it was applied to
+    fn update(&self, f: impl Fn(CreditState) -> CreditState) -> CreditState {
+        let mut current = self.state.load(Ordering::Acquire);
+        loop {
+            let next = f(CreditState::unpack(current)).pack();
+            match self.state.compare_exchange_weak(current, next, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(previous) => return CreditState::unpack(previous),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+impl Default for LinkCredit {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[cfg(all(test, not(dumq_loom)))]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_acquire_moves_credit_to_delivery_count() {
+        let credit = LinkCredit::new(u32::MAX);
+        credit.add(2);
+        assert_eq!(credit.try_acquire(), Some(u32::MAX));
+        assert_eq!(credit.snapshot(), CreditState { delivery_count: 0, link_credit: 1 });
+
+        credit.refund();
+        assert_eq!(credit.snapshot(), CreditState { delivery_count: u32::MAX, link_credit: 2 });
+        assert_eq!(credit.set(0), 2);
+        assert_eq!(credit.try_acquire(), None);
+    }
+
+    #[test]
+    fn test_apply_flow_accounts_for_transfers_in_flight() {
+        let credit = LinkCredit::new(10);
+        credit.add(5);
+        credit.try_acquire();
+        credit.try_acquire();
+
+        // The receiver granted 5 more before seeing the two transfers
+        let flow = Flow { delivery_count: Some(10), link_credit: Some(5), ..Default::default() };
+        assert_eq!(credit.apply_flow(&flow, 0), 3);
+
+        // A Flow from before the attach counts from the initial delivery-count
+        let flow = Flow { link_credit: Some(1), ..Default::default() };
+        assert_eq!(credit.apply_flow(&flow, 10), 0);
+        assert_eq!(credit.apply_flow(&Flow::default(), 10), 0);
+    }
+
+    #[test]
+    fn test_concurrent_acquire_never_oversends() {
+        let credit = Arc::new(LinkCredit::new(0));
+        credit.add(1000);
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let credit = credit.clone();
+                std::thread::spawn(move || {
+                    let mut sent = 0;
+                    while credit.try_acquire().is_some() {
+                        credit.next_delivery_id();
+                        sent += 1;
+                    }
+                    sent
+                })
+            })
+            .collect();
+        let sent: u32 = threads.into_iter().map(|thread| thread.join().unwrap()).sum();
+
+        assert_eq!(sent, 1000);
+        assert_eq!(credit.snapshot(), CreditState { delivery_count: 1000, link_credit: 0 });
+        assert_eq!(credit.peek_delivery_id(), 1001);
+    }
+}
+
+#[cfg(all(test, dumq_loom))]
+mod loom_tests {
+    use super::*;
+    use loom::sync::Arc;
+    use loom::thread;
+
+    #[test]
+    fn acquire_and_grant_keep_the_pair_consistent() {
+        loom::model(|| {
+            let credit = Arc::new(LinkCredit::new(0));
+            credit.add(1);
+
+            let sender = {
+                let credit = credit.clone();
+                thread::spawn(move || credit.try_acquire())
+            };
+            let granter = {
+                let credit = credit.clone();
+                thread::spawn(move || credit.add(1))
+            };
+            let snapshot = credit.snapshot();
+            assert!(snapshot.delivery_count + snapshot.link_credit >= 1);
+
+            let acquired = sender.join().unwrap();
+            granter.join().unwrap();
+            assert_eq!(acquired, Some(0));
+            assert_eq!(credit.snapshot(), CreditState { delivery_count: 1, link_credit: 1 });
+        });
+    }
+
+    #[test]
+    fn last_credit_goes_to_one_sender() {
+        loom::model(|| {
+            let credit = Arc::new(LinkCredit::new(0));
+            credit.add(1);
+
+            let senders: Vec<_> = (0..2)
+                .map(|_| {
+                    let credit = credit.clone();
+                    thread::spawn(move || credit.try_acquire().map(|_| credit.next_delivery_id()))
+                })
+                .collect();
+            let ids: Vec<u32> = senders.into_iter().filter_map(|sender| sender.join().unwrap()).collect();
+
+            assert_eq!(ids, vec![1]);
+            assert_eq!(credit.snapshot(), CreditState { delivery_count: 1, link_credit: 0 });
+        });
+    }
+
+    #[test]
+    fn flow_applied_during_send_is_not_lost() {
+        loom::model(|| {
+            let credit = Arc::new(LinkCredit::new(0));
+            credit.add(1);
+
+            let sender = {
+                let credit = credit.clone();
+                thread::spawn(move || credit.try_acquire())
+            };
+            // The receiver has seen no transfers and grants a window of 2
+            let flow = Flow { delivery_count: Some(0), link_credit: Some(2), ..Default::default() };
+            credit.apply_flow(&flow, 0);
+            sender.join().unwrap();
+
+            // Whichever went first, the window ends at delivery-count 2
+            let snapshot = credit.snapshot();
+            assert_eq!(snapshot.delivery_count + snapshot.link_credit, 2);
+        });
+    }
+}
@@ -0,0 +1,206 @@
+//! AMQP 1.0 Duplicate Detection
+//!
+//! This module provides the broker side of idempotent publishing. A
+//! [`DedupStore`] remembers the message-ids published to each queue for a
+//! configurable window; a message whose id was already seen in that window is
+//! reported as a duplicate so the broker can drop it while still settling the
+//! transfer as Accepted, exactly as if it had been stored. Producers opt in by
+//! giving every message a stable message-id, which senders built with
+//! `LinkBuilder::idempotent` do automatically.
+//!
+//! Messages without a message-id are never treated as duplicates.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::dedup::{DedupOutcome, DedupStore};
+//! use dumq_amqp::message::Message;
+//!
+//! let store = DedupStore::default();
+//! let message = Message::text("Hello").with_message_id("order-42");
+//!
+//! assert_eq!(store.check("orders", &message), DedupOutcome::Deliver);
+//! // A retry of the same publish is accepted but not enqueued again
+//! assert_eq!(store.check("orders", &message), DedupOutcome::Duplicate);
+//! // Windows are per queue
+//! assert_eq!(store.check("audit", &message), DedupOutcome::Deliver);
+//! ```
+
+use crate::logging;
+use crate::performative::Outcome;
+use crate::{AmqpValue, Message};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::time::{Duration, Instant};
+
+/// Duplicate detection configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct DedupConfig {
+    /// How long a message-id is remembered after it was first seen
+    pub window: Duration,
+    /// Maximum message-ids remembered per queue; the oldest are forgotten first
+    pub max_ids: usize,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        DedupConfig {
+            window: Duration::from_secs(600),
+            max_ids: 100_000,
+        }
+    }
+}
+
+/// Result of checking a published message against the store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupOutcome {
+    /// First publish within the window; enqueue the message
+    Deliver,
+    /// Already published within the window; drop the message
+    Duplicate,
+}
+
+impl DedupOutcome {
+    /// Get the outcome to settle the transfer with
+    ///
+    /// Duplicates are accepted too, so a retrying producer sees its publish
+    /// succeed.
+    pub fn settlement(&self) -> Outcome {
+        Outcome::Accepted
+    }
+}
+
+#[derive(Debug, Default)]
+struct QueueWindow {
+    ids: HashSet<AmqpValue>,
+    order: VecDeque<(Instant, AmqpValue)>,
+}
+
+impl QueueWindow {
+    fn expire(&mut self, cutoff: Option<Instant>, max_ids: usize) {
+        while let Some((seen, _)) = self.order.front() {
+            let expired = cutoff.is_some_and(|cutoff| *seen <= cutoff);
+            if !expired && self.order.len() <= max_ids {
+                break;
+            }
+            if let Some((_, id)) = self.order.pop_front() {
+                self.ids.remove(&id);
+            }
+        }
+    }
+}
+
+/// Per-queue store of recently published message-ids, shared between clones
+#[derive(Debug, Clone)]
+pub struct DedupStore {
+    config: DedupConfig,
+    queues: Arc<Mutex<HashMap<String, QueueWindow>>>,
+}
+
+impl DedupStore {
+    /// Create a store with a configuration
+    pub fn new(config: DedupConfig) -> Self {
+        DedupStore {
+            config,
+            queues: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Check a message published to a queue, remembering its message-id
+    pub fn check(&self, queue: &str, message: &Message) -> DedupOutcome {
+        self.check_at(queue, message, Instant::now())
+    }
+
+    fn check_at(&self, queue: &str, message: &Message, now: Instant) -> DedupOutcome {
+        let id = match message.properties.as_ref().and_then(|props| props.message_id.as_ref()) {
+            Some(id) => id,
+            None => return DedupOutcome::Deliver,
+        };
+
+        let mut queues = self.lock();
+        let window = queues.entry(queue.to_string()).or_default();
+        window.expire(now.checked_sub(self.config.window), self.config.max_ids);
+
+        if window.ids.contains(id) {
+            logging::debug!("Dropping duplicate message {:?} on queue '{}'", id, queue);
+            return DedupOutcome::Duplicate;
+        }
+        window.ids.insert(id.clone());
+        window.order.push_back((now, id.clone()));
+        window.expire(None, self.config.max_ids);
+        DedupOutcome::Deliver
+    }
+
+    /// Get the number of message-ids remembered for a queue
+    pub fn tracked(&self, queue: &str) -> usize {
+        self.lock().get(queue).map_or(0, |window| window.ids.len())
+    }
+
+    /// Forget every message-id remembered for a queue, e.g. when it is deleted
+    pub fn clear(&self, queue: &str) {
+        self.lock().remove(queue);
+    }
+
+    /// Get the configuration
+    pub fn config(&self) -> &DedupConfig {
+        &self.config
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, QueueWindow>> {
+        self.queues.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for DedupStore {
+    fn default() -> Self {
+        Self::new(DedupConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicate_within_window_only() {
+        let store = DedupStore::new(DedupConfig {
+            window: Duration::from_secs(10),
+            ..Default::default()
+        });
+        let message = Message::text("Hello").with_message_id("m-1");
+        let start = Instant::now();
+
+        assert_eq!(store.check_at("q", &message, start), DedupOutcome::Deliver);
+        assert_eq!(store.check_at("q", &message, start + Duration::from_secs(5)), DedupOutcome::Duplicate);
+        assert_eq!(store.check_at("q", &message, start + Duration::from_secs(11)), DedupOutcome::Deliver);
+        assert_eq!(store.tracked("q"), 1);
+    }
+
+    #[test]
+    fn test_messages_without_id_always_delivered() {
+        let store = DedupStore::default();
+        let message = Message::text("Hello");
+
+        assert_eq!(store.check("q", &message), DedupOutcome::Deliver);
+        assert_eq!(store.check("q", &message), DedupOutcome::Deliver);
+        assert_eq!(store.tracked("q"), 0);
+        assert_eq!(DedupOutcome::Duplicate.settlement(), Outcome::Accepted);
+    }
+
+    #[test]
+    fn test_oldest_ids_forgotten_over_capacity() {
+        let store = DedupStore::new(DedupConfig {
+            max_ids: 2,
+            ..Default::default()
+        });
+        for id in ["a", "b", "c"] {
+            assert_eq!(store.check("q", &Message::text("x").with_message_id(id)), DedupOutcome::Deliver);
+        }
+        assert_eq!(store.tracked("q"), 2);
+        assert_eq!(store.check("q", &Message::text("x").with_message_id("a")), DedupOutcome::Deliver);
+        assert_eq!(store.check("q", &Message::text("x").with_message_id("c")), DedupOutcome::Duplicate);
+
+        store.clear("q");
+        assert_eq!(store.tracked("q"), 0);
+    }
+}
@@ -0,0 +1,1232 @@
+//! AMQP 1.0 Frame Demultiplexer
+//!
+//! This module drives the frames of an open connection. [`Demux::spawn`]
+//! takes over the transport and runs a task that writes what sessions and
+//! links send through their [`Endpoint`]s, reads the peer's frames and hands
+//! each performative to the endpoint that owns it:
+//!
+//! - Begin and End go to the session on their channel. The peer answers a
+//!   Begin on a channel of its own choosing, which is mapped to ours through
+//!   the Begin's `remote-channel`.
+//! - Attach is matched to a link by name, which also records the handle the
+//!   peer uses for the link; Flow, Transfer and Detach then go by handle.
+//! - Disposition goes to every link on the session whose role is opposite to
+//!   the one the Disposition was sent from.
+//!
+//! Links leave the session-level fields of their Flows unset; the driver
+//! fills them in from the session's Begin and the transfers counted since.
+//!
+//! A frame that fails to decode is traced to its link by the handle or name
+//! ahead of the bad field, and that link alone is detached with
+//! `amqp:decode-error`.
+//!
+//! The peer's Close ends the task, answering it first if the peer started
+//! the close. Endpoints see `None` from then on.
+//!
+//! A driver spawned by [`Connection`](crate::connection::Connection) runs on
+//! a ready [`NetworkConnection`], whose keep-alive task sends heartbeats
+//! beside it. A peer silent beyond the local idle timeout fails the driver
+//! as a lost transport.
+//!
+//! A driver spawned for the server role also takes sessions and links the
+//! peer starts: a Begin without `remote-channel` gets a channel of ours and
+//! an endpoint, and so does an Attach for a link no endpoint was created
+//! for. Both are handed over as [`InboundSession`]s and [`InboundLink`]s,
+//! see [`server`](crate::server).
+//!
+//! A driver spawned with a [`ReconnectPolicy`](crate::reconnect::ReconnectPolicy)
+//! outlives its transport instead: when the transport fails, it opens a new
+//! one and replays the Begin of each session, the Attach and last Flow of
+//! each link, and, once the peer grants credit again, the transfers the peer
+//! had not settled, with their original delivery IDs and tags. Endpoints
+//! carry on unaware; the peer's answers to the replayed Begins and Attaches
+//! are not handed to them. Resent transfers may reach the peer twice, and
+//! deliveries received but not settled before the loss are redelivered by
+//! the peer.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use dumq_amqp::demux::Demux;
+//! use dumq_amqp::performative::{Begin, Performative};
+//! use dumq_amqp::transport::Transport;
+//!
+//! # async fn example(transport: Transport) -> Result<(), Box<dyn std::error::Error>> {
+//! // The transport has already exchanged protocol headers and Open frames
+//! let (demux, driver) = Demux::spawn(transport, 65536, "connection-1");
+//! let session = demux.session(0)?;
+//! session.send(Performative::Begin(Begin::default()))?;
+//! if let Some(Performative::Begin(begin)) = session.recv().await {
+//!     println!("Peer began with incoming window {}", begin.incoming_window);
+//! }
+//!
+//! demux.close()?;
+//! let close = driver.await??;
+//! println!("Peer closed with {:?}", close.error);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::codec::Decoder;
+use crate::heartbeat::HeartbeatEvent;
+use crate::logging;
+use crate::network::{self, NetworkConnection, NetworkReader, NetworkWriter};
+use crate::performative::{self, Attach, Begin, Close, Detach, Endpoint, Flow, Performative, Transfer};
+use crate::reconnect::{self, ReconnectEvent, Reconnector};
+use crate::tasks::{self, TaskKind};
+use crate::transport::{Frame, FrameHeader, FrameType, Transport};
+use crate::types::{self, Role};
+use crate::watchdog::Progress;
+use crate::{AmqpCondition, AmqpError, AmqpResult, AmqpValue};
+use futures::stream::{self, BoxStream, SelectAll, StreamExt};
+use futures::FutureExt;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+/// Size of the fixed frame header preceding each payload
+const FRAME_HEADER_SIZE: usize = 8;
+
+/// Request from a [`Demux`] handle to its driver
+#[derive(Debug)]
+enum Command {
+    /// Route a session's performatives
+    Session {
+        channel: u16,
+        incoming: mpsc::UnboundedSender<Performative>,
+        outgoing: mpsc::UnboundedReceiver<Performative>,
+    },
+    /// Route a link's performatives
+    Link {
+        channel: u16,
+        name: String,
+        role: Role,
+        incoming: mpsc::UnboundedSender<Performative>,
+        outgoing: mpsc::UnboundedReceiver<Performative>,
+    },
+    /// Write one performative, reporting the outcome
+    Send {
+        channel: u16,
+        performative: Performative,
+        written: oneshot::Sender<AmqpResult<()>>,
+    },
+    /// Send our Close, with an error if any, and wait for the peer's
+    Close(Option<types::AmqpError>),
+}
+
+/// A session the peer began, with an endpoint on a channel of ours
+#[derive(Debug)]
+pub(crate) struct InboundSession {
+    /// Our channel for the session
+    pub(crate) channel: u16,
+    /// The channel the peer began the session on
+    pub(crate) remote_channel: u16,
+    /// The peer's Begin
+    pub(crate) begin: Begin,
+    pub(crate) endpoint: Endpoint,
+    /// Links the peer attaches on the session
+    pub(crate) links: mpsc::UnboundedReceiver<InboundLink>,
+}
+
+/// A link the peer attached, with an endpoint routed by its name and role
+#[derive(Debug)]
+pub(crate) struct InboundLink {
+    /// The peer's Attach
+    pub(crate) attach: Attach,
+    pub(crate) endpoint: Endpoint,
+}
+
+/// Handle to the task driving a connection's frames
+///
+/// Cloning the handle is cheap; every clone talks to the same task.
+#[derive(Debug, Clone)]
+pub struct Demux {
+    commands: mpsc::UnboundedSender<Command>,
+    progress: Progress,
+}
+
+impl Demux {
+    /// Spawn the driver for an open connection
+    ///
+    /// The transport must be past the Open exchange. The returned task ends
+    /// with the peer's Close once the connection is closed, or with the
+    /// error that broke the transport.
+    ///
+    /// Transfers larger than `max_frame_size` are split into frames that fit.
+    pub fn spawn(transport: Transport, max_frame_size: u32, owner: &str) -> (Demux, JoinHandle<AmqpResult<Close>>) {
+        Self::start(network::split_transport(transport, owner), max_frame_size, owner, None, None)
+    }
+
+    /// Spawn the driver for a ready network connection
+    ///
+    /// The connection's keep-alive task goes on sending heartbeats beside
+    /// the driver. A peer silent for longer than the local idle timeout is
+    /// sent a Close by it, and the driver fails as if the transport was
+    /// lost, reconnecting if it has a reconnector.
+    pub(crate) fn spawn_network(
+        connection: NetworkConnection,
+        max_frame_size: u32,
+        owner: &str,
+        reconnector: Option<Reconnector>,
+    ) -> AmqpResult<(Demux, JoinHandle<AmqpResult<Close>>)> {
+        Ok(Self::start(connection.split()?, max_frame_size, owner, reconnector, None))
+    }
+
+    /// Spawn a driver that takes the sessions the peer begins
+    pub(crate) fn spawn_accepting(
+        transport: Transport,
+        max_frame_size: u32,
+        owner: &str,
+    ) -> (Demux, JoinHandle<AmqpResult<Close>>, mpsc::UnboundedReceiver<InboundSession>) {
+        let (inbound, sessions) = mpsc::unbounded_channel();
+        let halves = network::split_transport(transport, owner);
+        let (demux, task) = Self::start(halves, max_frame_size, owner, None, Some(inbound));
+        (demux, task, sessions)
+    }
+
+    /// Start the driver; with a reconnector, it re-opens the connection when it fails
+    ///
+    /// The task ends with the error of the loss if the reconnector gives up.
+    fn start(
+        (reader, writer): (NetworkReader, NetworkWriter),
+        max_frame_size: u32,
+        owner: &str,
+        reconnector: Option<Reconnector>,
+        inbound: Option<mpsc::UnboundedSender<InboundSession>>,
+    ) -> (Demux, JoinHandle<AmqpResult<Close>>) {
+        let (commands, requests) = mpsc::unbounded_channel();
+        let progress = Progress::new();
+        let driver = Driver {
+            heartbeats: reader.heartbeat_events(),
+            reader,
+            writer,
+            max_frame_size,
+            outgoing: SelectAll::new(),
+            sessions: HashMap::new(),
+            remote_channels: HashMap::new(),
+            links: HashMap::new(),
+            handles: HashMap::new(),
+            local_handles: HashMap::new(),
+            decode_failures: HashMap::new(),
+            session_flows: HashMap::new(),
+            closing: false,
+            progress: progress.clone(),
+            reconnector,
+            begins: BTreeMap::new(),
+            attaches: BTreeMap::new(),
+            flows: BTreeMap::new(),
+            unsettled: BTreeMap::new(),
+            interrupted: None,
+            awaiting_begin: HashSet::new(),
+            awaiting_attach: HashSet::new(),
+            awaiting_credit: HashMap::new(),
+            resend: Vec::new(),
+            inbound,
+            inbound_links: HashMap::new(),
+        };
+        let task = tasks::spawn(TaskKind::Driver, owner, driver.run(requests));
+        (Demux { commands, progress }, task)
+    }
+
+    /// Create the endpoint of the session on a channel
+    pub fn session(&self, channel: u16) -> AmqpResult<Endpoint> {
+        let (outgoing_tx, outgoing) = mpsc::unbounded_channel();
+        let (incoming, incoming_rx) = mpsc::unbounded_channel();
+        self.command(Command::Session { channel, incoming, outgoing })?;
+        Ok(Endpoint::new(outgoing_tx, incoming_rx))
+    }
+
+    /// Create the endpoint of a link on a session's channel
+    ///
+    /// The link is found by its name and role when the peer's Attach arrives.
+    pub fn link(&self, channel: u16, name: &str, role: Role) -> AmqpResult<Endpoint> {
+        let (outgoing_tx, outgoing) = mpsc::unbounded_channel();
+        let (incoming, incoming_rx) = mpsc::unbounded_channel();
+        self.command(Command::Link {
+            channel,
+            name: name.to_string(),
+            role,
+            incoming,
+            outgoing,
+        })?;
+        Ok(Endpoint::new(outgoing_tx, incoming_rx))
+    }
+
+    /// Write a performative on a channel, returning once it is flushed
+    ///
+    /// Used where the caller must know the frame was written, such as for
+    /// Transfers; it bypasses the routing of sessions and links.
+    pub async fn send(&self, channel: u16, performative: Performative) -> AmqpResult<()> {
+        let (written, result) = oneshot::channel();
+        self.command(Command::Send { channel, performative, written })?;
+        result
+            .await
+            .map_err(|_| AmqpError::connection("Connection driver stopped before writing the frame"))?
+    }
+
+    /// Send our Close; the driver ends once the peer's Close arrives
+    pub fn close(&self) -> AmqpResult<()> {
+        self.command(Command::Close(None))
+    }
+
+    /// Send our Close carrying an error, e.g. to force the peer off
+    pub(crate) fn close_with(&self, error: types::AmqpError) -> AmqpResult<()> {
+        self.command(Command::Close(Some(error)))
+    }
+
+    /// Wait until the driver has stopped
+    pub(crate) async fn stopped(&self) {
+        self.commands.closed().await
+    }
+
+    /// Check if the driver is still running
+    pub fn is_running(&self) -> bool {
+        !self.commands.is_closed()
+    }
+
+    /// Get the driver's progress through the requests made of it
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    fn command(&self, command: Command) -> AmqpResult<()> {
+        // Queued first, so the driver cannot complete the request before it is counted
+        self.progress.queued();
+        self.commands.send(command).map_err(|_| {
+            self.progress.completed();
+            AmqpError::connection("Connection driver has stopped")
+        })
+    }
+}
+
+/// Endpoint of a link as seen by the driver
+#[derive(Debug)]
+struct LinkRoute {
+    role: Role,
+    incoming: mpsc::UnboundedSender<Performative>,
+}
+
+/// Transfer IDs and windows of a session, for framing its links' Flows
+#[derive(Debug, Default, Clone, Copy)]
+struct SessionFlow {
+    /// Next transfer ID expected from the peer, once its Begin has arrived
+    next_incoming_id: Option<u32>,
+    /// Incoming window announced in our Begin
+    incoming_window: u32,
+    /// Next transfer ID we send
+    next_outgoing_id: u32,
+    /// Outgoing window announced in our Begin
+    outgoing_window: u32,
+}
+
+/// State of the task driving a connection
+struct Driver {
+    reader: NetworkReader,
+    writer: NetworkWriter,
+    /// Events of the keep-alive task watching the peer's frames
+    heartbeats: broadcast::Receiver<HeartbeatEvent>,
+    /// Largest frame the peer accepts
+    max_frame_size: u32,
+    /// Performatives sent by endpoints, tagged with their channel
+    outgoing: SelectAll<BoxStream<'static, (u16, Performative)>>,
+    /// Sessions by our channel
+    sessions: HashMap<u16, mpsc::UnboundedSender<Performative>>,
+    /// Our channel for each channel the peer answered a Begin on
+    remote_channels: HashMap<u16, u16>,
+    /// Links by our channel, name and role
+    links: HashMap<(u16, String, Role), LinkRoute>,
+    /// Link names by our channel and the peer's handle
+    handles: HashMap<(u16, u32), (String, Role)>,
+    /// Our handle for each link we sent an Attach for, by our channel, name and role
+    local_handles: HashMap<(u16, String, Role), u32>,
+    /// Error for the Detach of links the peer sent an undecodable frame to,
+    /// by our channel and handle
+    decode_failures: HashMap<(u16, u32), types::AmqpError>,
+    /// Session flow state by our channel
+    session_flows: HashMap<u16, SessionFlow>,
+    /// Whether our Close has been sent
+    closing: bool,
+    /// Requests handled, for the watchdog
+    progress: Progress,
+    /// Opens a new transport when this one fails, if reconnecting
+    reconnector: Option<Reconnector>,
+    /// Begin sent on each channel, replayed when reconnecting
+    begins: BTreeMap<u16, Begin>,
+    /// Attach sent for each attached link, by our channel and handle
+    attaches: BTreeMap<(u16, u32), Attach>,
+    /// Last Flow sent on each link, by our channel and handle
+    flows: BTreeMap<(u16, u32), Flow>,
+    /// Transfers the peer has not settled, by our channel and delivery ID
+    unsettled: BTreeMap<(u16, u32), Transfer>,
+    /// Send whose write failed with the transport, finished after reconnecting
+    interrupted: Option<(u16, Performative, oneshot::Sender<AmqpResult<()>>)>,
+    /// Channels whose replayed Begin the peer has yet to answer
+    awaiting_begin: HashSet<u16>,
+    /// Links whose replayed Attach the peer has yet to answer
+    awaiting_attach: HashSet<(u16, String, Role)>,
+    /// Delivery IDs to resend on each sender link once the peer grants credit
+    awaiting_credit: HashMap<(u16, String), Vec<u32>>,
+    /// Transfers to resend now that their link has credit
+    resend: Vec<(u16, u32)>,
+    /// Where sessions the peer begins are handed over, in the server role
+    inbound: Option<mpsc::UnboundedSender<InboundSession>>,
+    /// Where links the peer attaches are handed over, by our channel
+    inbound_links: HashMap<u16, mpsc::UnboundedSender<InboundLink>>,
+}
+
+impl Driver {
+    async fn run(mut self, mut requests: mpsc::UnboundedReceiver<Command>) -> AmqpResult<Close> {
+        let mut result = self.drive(&mut requests).await;
+        loop {
+            match result {
+                Err(error) if self.reconnects(&error) => {
+                    result = match self.recover(&error).await {
+                        Ok(()) => self.drive(&mut requests).await,
+                        Err(e) => Err(e),
+                    };
+                }
+                _ => break,
+            }
+        }
+        if let (Some((_, _, written)), Err(e)) = (self.interrupted.take(), &result) {
+            let _ = written.send(Err(AmqpError::connection(e.to_string())));
+        }
+        if let Err(e) = self.writer.shutdown().await {
+            logging::debug!("Failed to shut down transport: {}", e);
+        }
+        result
+    }
+
+    async fn drive(&mut self, requests: &mut mpsc::UnboundedReceiver<Command>) -> AmqpResult<Close> {
+        let mut handles_open = true;
+        loop {
+            tokio::select! {
+                biased;
+                command = requests.recv(), if handles_open => match command {
+                    Some(command) => {
+                        self.command(command).await?;
+                        self.progress.completed();
+                    }
+                    // Every handle is gone, so nobody can close the connection later
+                    None => {
+                        handles_open = false;
+                        self.send_close(None).await?;
+                    }
+                },
+                Some((channel, performative)) = self.outgoing.next(), if !self.outgoing.is_empty() => {
+                    self.write(channel, performative).await?;
+                }
+                frame = self.reader.receive_frame() => {
+                    if let Some(close) = self.dispatch(frame?).await? {
+                        return Ok(close);
+                    }
+                    self.resend_credited().await?;
+                }
+                Ok(event) = self.heartbeats.recv() => {
+                    // The keep-alive task has already sent the peer a Close
+                    if let HeartbeatEvent::TimedOut { idle } = event {
+                        return Err(AmqpError::transport(format!("No frames received from the peer for {:?}", idle)));
+                    }
+                }
+            }
+        }
+    }
+
+    async fn command(&mut self, command: Command) -> AmqpResult<()> {
+        match command {
+            Command::Session { channel, incoming, outgoing } => {
+                self.sessions.insert(channel, incoming);
+                self.outgoing.push(tagged(channel, outgoing));
+            }
+            Command::Link { channel, name, role, incoming, outgoing } => {
+                self.links.insert((channel, name, role), LinkRoute { role, incoming });
+                self.outgoing.push(tagged(channel, outgoing));
+            }
+            Command::Send { channel, performative, written } => {
+                // Kept to be tracked, or sent again, only when reconnecting
+                let kept = self.reconnector.is_some().then(|| performative.clone());
+                let frames = match self.outgoing_frames(channel, performative) {
+                    Ok(frames) => frames,
+                    Err(e) => {
+                        let _ = written.send(Err(e));
+                        return Ok(());
+                    }
+                };
+                let mut result = Ok(());
+                for payload in frames {
+                    result = self.write_payload(channel, payload).await;
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                match (result, kept) {
+                    (Ok(()), kept) => {
+                        if let Some(performative) = kept {
+                            self.track(channel, performative);
+                        }
+                        let _ = written.send(Ok(()));
+                    }
+                    (Err(e), Some(performative)) if self.reconnects(&e) => {
+                        self.interrupted = Some((channel, performative, written));
+                        return Err(e);
+                    }
+                    (Err(e), _) => {
+                        let failure = AmqpError::transport(e.to_string());
+                        let _ = written.send(Err(e));
+                        return Err(failure);
+                    }
+                }
+            }
+            Command::Close(error) => {
+                self.flush_outgoing().await?;
+                self.send_close(error).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write what endpoints have already sent, e.g. an End answered just before closing
+    async fn flush_outgoing(&mut self) -> AmqpResult<()> {
+        while let Some(Some((channel, performative))) = self.outgoing.next().now_or_never() {
+            self.write(channel, performative).await?;
+        }
+        Ok(())
+    }
+
+    async fn send_close(&mut self, error: Option<types::AmqpError>) -> AmqpResult<()> {
+        if self.closing {
+            return Ok(());
+        }
+        self.closing = true;
+        self.writer.stop_keep_alive();
+        self.write_payload(0, Close { error }.encode()?).await
+    }
+
+    /// Write a performative sent by an endpoint
+    ///
+    /// A performative that does not encode is dropped rather than failing the
+    /// connection, since its endpoint cannot be told.
+    async fn write(&mut self, channel: u16, performative: Performative) -> AmqpResult<()> {
+        match self.outgoing_frames(channel, performative) {
+            Ok(frames) => {
+                for payload in frames {
+                    self.write_payload(channel, payload).await?;
+                }
+                Ok(())
+            }
+            Err(e) => {
+                logging::warn!("Dropping performative on channel {} that failed to encode: {}", channel, e);
+                Ok(())
+            }
+        }
+    }
+
+    /// Encode a performative for a channel, keeping its session's flow state
+    ///
+    /// Our Begin sets the session's windows and first transfer ID, each
+    /// Transfer frame takes the next ID, and a link's Flow is completed with
+    /// the session-level fields. The Detach of a link the peer sent an
+    /// undecodable frame to carries `amqp:decode-error`.
+    fn outgoing_frames(&mut self, channel: u16, mut performative: Performative) -> AmqpResult<Vec<Vec<u8>>> {
+        if self.reconnector.is_some() {
+            self.record(channel, &performative);
+        }
+        match &mut performative {
+            Performative::Begin(begin) => {
+                let session = self.session_flows.entry(channel).or_default();
+                session.next_outgoing_id = begin.next_outgoing_id;
+                session.incoming_window = begin.incoming_window;
+                session.outgoing_window = begin.outgoing_window;
+            }
+            Performative::Flow(flow) if flow.handle.is_some() => {
+                if let Some(session) = self.session_flows.get(&channel) {
+                    flow.next_incoming_id = session.next_incoming_id;
+                    flow.incoming_window = session.incoming_window;
+                    flow.next_outgoing_id = session.next_outgoing_id;
+                    flow.outgoing_window = session.outgoing_window;
+                }
+            }
+            Performative::Attach(attach) => {
+                self.local_handles.insert((channel, attach.name.clone(), attach.role), attach.handle);
+                self.decode_failures.remove(&(channel, attach.handle));
+            }
+            Performative::Detach(detach) => {
+                let handle = detach.handle;
+                self.local_handles.retain(|(link_channel, _, _), ours| *link_channel != channel || *ours != handle);
+                if let Some(error) = self.decode_failures.remove(&(channel, handle)) {
+                    detach.closed = true;
+                    detach.error.get_or_insert(error);
+                }
+            }
+            _ => {}
+        }
+        let transfer = matches!(performative, Performative::Transfer(_));
+        let frames = self.frames(performative)?;
+        if let (true, Some(session)) = (transfer, self.session_flows.get_mut(&channel)) {
+            session.next_outgoing_id = session.next_outgoing_id.wrapping_add(frames.len() as u32);
+        }
+        Ok(frames)
+    }
+
+    /// Encode a performative into frame payloads within the max frame size
+    ///
+    /// Only a Transfer can be split: its message payload is spread over
+    /// frames with `more` set on all but the last, the first carrying the
+    /// delivery's fields and the rest only the handle.
+    fn frames(&self, performative: Performative) -> AmqpResult<Vec<Vec<u8>>> {
+        let limit = (self.max_frame_size as usize).saturating_sub(FRAME_HEADER_SIZE);
+        let mut transfer = match performative {
+            Performative::Transfer(transfer) => transfer,
+            other => return Ok(vec![other.encode()?]),
+        };
+        let encoded = transfer.encode()?;
+        if encoded.len() <= limit {
+            return Ok(vec![encoded]);
+        }
+
+        let payload = std::mem::take(&mut transfer.payload);
+        let last_more = transfer.more;
+        transfer.more = true;
+        let overhead = transfer.encode()?.len();
+        let chunk_size = limit.checked_sub(overhead).filter(|size| *size > 0).ok_or_else(|| {
+            AmqpError::encoding(format!("Max frame size {} leaves no room for transfer payload", self.max_frame_size))
+        })?;
+
+        let chunks: Vec<&[u8]> = payload.chunks(chunk_size).collect();
+        let mut frames = Vec::with_capacity(chunks.len());
+        for (index, chunk) in chunks.iter().enumerate() {
+            let more = index + 1 < chunks.len() || last_more;
+            let frame = if index == 0 {
+                Transfer { payload: chunk.to_vec(), more, ..transfer.clone() }
+            } else {
+                Transfer { handle: transfer.handle, payload: chunk.to_vec(), more, ..Default::default() }
+            };
+            frames.push(frame.encode()?);
+        }
+        Ok(frames)
+    }
+
+    async fn write_payload(&mut self, channel: u16, payload: Vec<u8>) -> AmqpResult<()> {
+        let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, channel);
+        self.writer.send_frame(Frame::new(header, payload)).await
+    }
+
+    /// Hand a frame from the peer to its endpoint, returning the peer's Close
+    async fn dispatch(&mut self, frame: Frame) -> AmqpResult<Option<Close>> {
+        if frame.header.frame_type != FrameType::AMQP as u8 || frame.payload.is_empty() {
+            return Ok(None);
+        }
+
+        let (descriptor, _) = Decoder::new(frame.payload.clone()).decode_described_header()?;
+        if descriptor == performative::descriptor::CLOSE {
+            let close = Close::decode(&frame.payload)?;
+            self.send_close(None).await?;
+            return Ok(Some(close));
+        }
+
+        match Performative::decode(&frame.payload) {
+            Ok(performative) => self.route(frame.header.channel, performative),
+            Err(e) => self.undecodable(frame.header.channel, &frame.payload, e).await?,
+        }
+        Ok(None)
+    }
+
+    /// Detach the link an undecodable frame was sent to with `amqp:decode-error`
+    ///
+    /// The link is found from the fields ahead of the one that failed: the
+    /// peer's handle, or the name and role of an Attach. It is handed a
+    /// Detach carrying the error as if from the peer, and the Detach it
+    /// answers with carries the error too. A link still waiting for the
+    /// peer's Attach does not answer, so its Detach is sent here. Frames that
+    /// cannot be traced to a link are dropped.
+    async fn undecodable(&mut self, remote_channel: u16, payload: &[u8], error: AmqpError) -> AmqpResult<()> {
+        let channel = self.remote_channels.get(&remote_channel).copied();
+        let (channel, target) = match (channel, frame_link(payload)) {
+            (Some(channel), Some(target)) => (channel, target),
+            _ => {
+                logging::warn!("Dropping undecodable frame on channel {}: {}", remote_channel, error);
+                return Ok(());
+            }
+        };
+        let (key, handle, attaching) = match target {
+            FrameLink::Handle(handle) => match self.handles.get(&(channel, handle)) {
+                Some((name, role)) => ((channel, name.clone(), *role), handle, false),
+                None => {
+                    logging::warn!("Dropping undecodable frame for unattached handle {} on channel {}: {}", handle, channel, error);
+                    return Ok(());
+                }
+            },
+            FrameLink::Attach { name, handle, role } => ((channel, name, opposite(role)), handle, true),
+        };
+
+        logging::warn!("Detaching link '{}' after an undecodable frame: {}", key.1, error);
+        let error = types::AmqpError::new(AmqpCondition::AmqpErrorDecodeError).with_description(error.to_string());
+        if let Some(ours) = self.local_handles.get(&key).copied() {
+            if attaching {
+                self.write(channel, Performative::Detach(Detach { handle: ours, closed: true, error: Some(error.clone()) }))
+                    .await?;
+            } else {
+                self.decode_failures.insert((channel, ours), error.clone());
+            }
+        }
+        self.deliver_to_link(key, Performative::Detach(Detach { handle, closed: true, error: Some(error) }));
+        Ok(())
+    }
+
+    fn route(&mut self, remote_channel: u16, performative: Performative) {
+        let channel = match &performative {
+            Performative::Begin(begin) => match begin.remote_channel {
+                Some(channel) => {
+                    self.remote_channels.insert(remote_channel, channel);
+                    self.session_flows.entry(channel).or_default().next_incoming_id = Some(begin.next_outgoing_id);
+                    if self.awaiting_begin.remove(&channel) {
+                        return;
+                    }
+                    channel
+                }
+                None => {
+                    if self.inbound.is_some() {
+                        self.accept_session(remote_channel, begin.clone());
+                    } else {
+                        logging::debug!("Ignoring Begin for a session the peer started on channel {}", remote_channel);
+                    }
+                    return;
+                }
+            },
+            _ => match self.remote_channels.get(&remote_channel) {
+                Some(channel) => *channel,
+                None => {
+                    logging::debug!("Dropping performative on unmapped channel {}", remote_channel);
+                    return;
+                }
+            },
+        };
+
+        match performative {
+            Performative::Begin(_) => self.deliver_to_session(channel, performative),
+            Performative::End(_) => {
+                self.deliver_to_session(channel, performative);
+                self.sessions.remove(&channel);
+                self.session_flows.remove(&channel);
+                self.remote_channels.remove(&remote_channel);
+                self.links.retain(|(link_channel, _, _), _| *link_channel != channel);
+                self.handles.retain(|(link_channel, _), _| *link_channel != channel);
+                self.local_handles.retain(|(link_channel, _, _), _| *link_channel != channel);
+                self.decode_failures.retain(|(link_channel, _), _| *link_channel != channel);
+                self.inbound_links.remove(&channel);
+                self.forget_session(channel);
+            }
+            Performative::Attach(ref attach) => {
+                let key = (channel, attach.name.clone(), opposite(attach.role));
+                self.handles.insert((channel, attach.handle), (key.1.clone(), key.2));
+                if self.awaiting_attach.remove(&key) {
+                    return;
+                }
+                if !self.links.contains_key(&key) && self.inbound_links.contains_key(&channel) {
+                    self.accept_link(key, attach.clone());
+                } else {
+                    self.deliver_to_link(key, performative);
+                }
+            }
+            Performative::Flow(ref flow) => {
+                // The peer's session windows are not enforced, so only link flows are routed
+                if let Some(handle) = flow.handle {
+                    if flow.link_credit.is_some_and(|credit| credit > 0) {
+                        self.credit_granted(channel, handle);
+                    }
+                    self.deliver_to_handle(channel, handle, performative);
+                }
+            }
+            Performative::Transfer(ref transfer) => {
+                if let Some(session) = self.session_flows.get_mut(&channel) {
+                    session.next_incoming_id = session.next_incoming_id.map(|id| id.wrapping_add(1));
+                }
+                let handle = transfer.handle;
+                self.deliver_to_handle(channel, handle, performative);
+            }
+            Performative::Detach(ref detach) => {
+                let handle = detach.handle;
+                if let Some((name, role)) = self.handles.get(&(channel, handle)) {
+                    let ours = self
+                        .attaches
+                        .iter()
+                        .find(|((link_channel, _), attach)| *link_channel == channel && attach.name == *name && attach.role == *role)
+                        .map(|((_, ours), _)| *ours);
+                    if let Some(ours) = ours {
+                        self.forget_link(channel, ours);
+                    }
+                }
+                self.deliver_to_handle(channel, handle, performative);
+                if let Some((name, role)) = self.handles.remove(&(channel, handle)) {
+                    // A link the peer attached ends with its Detach, so the name can be attached again
+                    if self.inbound_links.contains_key(&channel) {
+                        self.links.remove(&(channel, name, role));
+                    }
+                }
+            }
+            Performative::Disposition(ref disposition) => {
+                if disposition.role == Role::Receiver && disposition.settled {
+                    let last = disposition.last.unwrap_or(disposition.first);
+                    self.unsettled.retain(|(link_channel, id), _| {
+                        *link_channel != channel || !(disposition.first..=last).contains(id)
+                    });
+                }
+                let role = opposite(disposition.role);
+                self.links.retain(|(link_channel, _, _), route| {
+                    *link_channel != channel || route.role != role || route.incoming.send(performative.clone()).is_ok()
+                });
+            }
+        }
+    }
+
+    /// Check if an error is a lost transport this driver reconnects after
+    fn reconnects(&self, error: &AmqpError) -> bool {
+        self.reconnector.is_some() && !self.closing && reconnect::is_connection_loss(error)
+    }
+
+    /// Open a new transport and replay the sessions and links on it
+    async fn recover(&mut self, error: &AmqpError) -> AmqpResult<()> {
+        let Some(reconnector) = self.reconnector.as_mut() else {
+            return Err(AmqpError::connection(error.to_string()));
+        };
+        let (connection, attempts) = reconnector.reconnect(error).await?;
+        let (reader, writer) = connection.split()?;
+        self.heartbeats = reader.heartbeat_events();
+        self.reader = reader;
+        self.writer = writer;
+        self.remote_channels.clear();
+        self.handles.clear();
+        self.awaiting_attach.clear();
+        self.awaiting_credit.clear();
+        self.resend.clear();
+
+        self.awaiting_begin = self.begins.keys().copied().collect();
+        for (channel, begin) in self.begins.clone() {
+            self.write(channel, Performative::Begin(begin)).await?;
+        }
+        for ((channel, _), attach) in self.attaches.clone() {
+            self.awaiting_attach.insert((channel, attach.name.clone(), attach.role));
+            self.write(channel, Performative::Attach(attach)).await?;
+        }
+        for ((channel, _), flow) in self.flows.clone() {
+            self.write(channel, Performative::Flow(flow)).await?;
+        }
+
+        if let Some((channel, performative, written)) = self.interrupted.take() {
+            match performative {
+                Performative::Transfer(transfer) if transfer.settled != Some(true) => {
+                    self.track(channel, Performative::Transfer(transfer));
+                    let _ = written.send(Ok(()));
+                }
+                performative => {
+                    let frames = self.outgoing_frames(channel, performative.clone())?;
+                    for payload in frames {
+                        if let Err(e) = self.write_payload(channel, payload).await {
+                            self.interrupted = Some((channel, performative, written));
+                            return Err(e);
+                        }
+                    }
+                    let _ = written.send(Ok(()));
+                }
+            }
+        }
+
+        // Transfers wait for the peer's credit on the re-attached link
+        for ((channel, id), transfer) in &self.unsettled {
+            if let Some(attach) = self.attaches.get(&(*channel, transfer.handle)) {
+                self.awaiting_credit.entry((*channel, attach.name.clone())).or_default().push(*id);
+            }
+        }
+        let resent = self.unsettled.len();
+        logging::info!("Reconnected after {} attempts, resending {} unsettled transfers", attempts, resent);
+        if let Some(reconnector) = &self.reconnector {
+            reconnector.notify(ReconnectEvent::Reconnected { attempts, resent });
+        }
+        Ok(())
+    }
+
+    /// Keep what is needed to replay a session or link after reconnecting
+    fn record(&mut self, channel: u16, performative: &Performative) {
+        match performative {
+            Performative::Begin(begin) => {
+                self.begins.insert(channel, begin.clone());
+            }
+            Performative::End(_) => self.forget_session(channel),
+            Performative::Attach(attach) => {
+                self.attaches.insert((channel, attach.handle), attach.clone());
+            }
+            Performative::Detach(detach) => self.forget_link(channel, detach.handle),
+            Performative::Flow(flow) => {
+                if let Some(handle) = flow.handle {
+                    self.flows.insert((channel, handle), flow.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Keep a transfer sent unsettled until the peer settles it
+    fn track(&mut self, channel: u16, performative: Performative) {
+        if let Performative::Transfer(transfer) = performative {
+            if let (Some(id), false) = (transfer.delivery_id, transfer.settled == Some(true) || transfer.aborted) {
+                self.unsettled.insert((channel, id), transfer);
+            }
+        }
+    }
+
+    fn forget_session(&mut self, channel: u16) {
+        self.begins.remove(&channel);
+        self.attaches.retain(|(link_channel, _), _| *link_channel != channel);
+        self.flows.retain(|(link_channel, _), _| *link_channel != channel);
+        self.unsettled.retain(|(link_channel, _), _| *link_channel != channel);
+    }
+
+    fn forget_link(&mut self, channel: u16, handle: u32) {
+        self.attaches.remove(&(channel, handle));
+        self.flows.remove(&(channel, handle));
+        self.unsettled
+            .retain(|(link_channel, _), transfer| *link_channel != channel || transfer.handle != handle);
+    }
+
+    /// Queue the transfers waiting for credit on a sender link
+    fn credit_granted(&mut self, channel: u16, handle: u32) {
+        if let Some((name, Role::Sender)) = self.handles.get(&(channel, handle)) {
+            if let Some(ids) = self.awaiting_credit.remove(&(channel, name.clone())) {
+                self.resend.extend(ids.into_iter().map(|id| (channel, id)));
+            }
+        }
+    }
+
+    /// Resend the queued transfers the peer has still not settled
+    async fn resend_credited(&mut self) -> AmqpResult<()> {
+        for (channel, id) in std::mem::take(&mut self.resend) {
+            if let Some(transfer) = self.unsettled.get(&(channel, id)).cloned() {
+                self.write(channel, Performative::Transfer(transfer)).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Give a session the peer began a channel of ours and hand it over
+    fn accept_session(&mut self, remote_channel: u16, begin: Begin) {
+        let taken: HashSet<u16> = self.sessions.keys().chain(self.remote_channels.values()).copied().collect();
+        let Some(channel) = (0..=u16::MAX).find(|channel| !taken.contains(channel)) else {
+            logging::warn!("No channel left for the session the peer began on channel {}", remote_channel);
+            return;
+        };
+        let Some(inbound) = &self.inbound else {
+            return;
+        };
+
+        let (outgoing_tx, outgoing) = mpsc::unbounded_channel();
+        let (incoming, incoming_rx) = mpsc::unbounded_channel();
+        let (links_tx, links) = mpsc::unbounded_channel();
+        let session = InboundSession {
+            channel,
+            remote_channel,
+            begin: begin.clone(),
+            endpoint: Endpoint::new(outgoing_tx, incoming_rx),
+            links,
+        };
+        if inbound.send(session).is_err() {
+            logging::debug!("Ignoring Begin on channel {}: sessions are no longer taken", remote_channel);
+            return;
+        }
+        logging::debug!("Peer began a session on channel {}, mapped to channel {}", remote_channel, channel);
+        self.remote_channels.insert(remote_channel, channel);
+        self.session_flows.entry(channel).or_default().next_incoming_id = Some(begin.next_outgoing_id);
+        self.sessions.insert(channel, incoming);
+        self.outgoing.push(tagged(channel, outgoing));
+        self.inbound_links.insert(channel, links_tx);
+    }
+
+    /// Route a link the peer attached and hand it over to its session
+    fn accept_link(&mut self, key: (u16, String, Role), attach: Attach) {
+        let channel = key.0;
+        let (outgoing_tx, outgoing) = mpsc::unbounded_channel();
+        let (incoming, incoming_rx) = mpsc::unbounded_channel();
+        let link = InboundLink { attach, endpoint: Endpoint::new(outgoing_tx, incoming_rx) };
+        let handed_over = self.inbound_links.get(&channel).is_some_and(|links| links.send(link).is_ok());
+        if !handed_over {
+            self.inbound_links.remove(&channel);
+            return;
+        }
+        self.links.insert(key.clone(), LinkRoute { role: key.2, incoming });
+        self.outgoing.push(tagged(channel, outgoing));
+    }
+
+    fn deliver_to_session(&mut self, channel: u16, performative: Performative) {
+        let delivered = self.sessions.get(&channel).is_some_and(|session| session.send(performative).is_ok());
+        if !delivered {
+            self.sessions.remove(&channel);
+        }
+    }
+
+    fn deliver_to_handle(&mut self, channel: u16, handle: u32, performative: Performative) {
+        match self.handles.get(&(channel, handle)) {
+            Some((name, role)) => {
+                let key = (channel, name.clone(), *role);
+                self.deliver_to_link(key, performative);
+            }
+            None => logging::debug!("Dropping performative for unattached handle {} on channel {}", handle, channel),
+        }
+    }
+
+    fn deliver_to_link(&mut self, key: (u16, String, Role), performative: Performative) {
+        let delivered = self.links.get(&key).is_some_and(|link| link.incoming.send(performative).is_ok());
+        if !delivered {
+            self.links.remove(&key);
+        }
+    }
+}
+
+/// Stream the performatives an endpoint sends, tagged with its channel
+fn tagged(channel: u16, outgoing: mpsc::UnboundedReceiver<Performative>) -> BoxStream<'static, (u16, Performative)> {
+    stream::unfold(outgoing, move |mut outgoing| async move {
+        outgoing.recv().await.map(|performative| ((channel, performative), outgoing))
+    })
+    .boxed()
+}
+
+/// The link a frame was sent to, as far as an undecodable frame tells
+enum FrameLink {
+    /// The peer's handle, leading a Transfer or Detach and fifth in a Flow
+    Handle(u32),
+    /// The link an Attach names, with the peer's handle and role
+    Attach { name: String, handle: u32, role: Role },
+}
+
+/// Read the link a frame was sent to from its leading fields
+fn frame_link(payload: &[u8]) -> Option<FrameLink> {
+    use performative::descriptor::{ATTACH, DETACH, FLOW, TRANSFER};
+
+    let mut decoder = Decoder::new(payload.to_vec());
+    let (descriptor, count) = decoder.decode_described_header().ok()?;
+    let leading = match descriptor {
+        TRANSFER | DETACH => 1,
+        ATTACH => 3,
+        FLOW => 5,
+        _ => return None,
+    };
+    if count < leading {
+        return None;
+    }
+    let fields = (0..leading).map(|_| decoder.decode_value()).collect::<AmqpResult<Vec<_>>>().ok()?;
+    match (descriptor, fields.as_slice()) {
+        (ATTACH, [AmqpValue::String(name), AmqpValue::Uint(handle), AmqpValue::Boolean(receiver)]) => {
+            let role = if *receiver { Role::Receiver } else { Role::Sender };
+            Some(FrameLink::Attach { name: name.clone(), handle: *handle, role })
+        }
+        (_, [.., AmqpValue::Uint(handle)]) if descriptor != ATTACH => Some(FrameLink::Handle(*handle)),
+        _ => None,
+    }
+}
+
+fn opposite(role: Role) -> Role {
+    match role {
+        Role::Sender => Role::Receiver,
+        Role::Receiver => Role::Sender,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::performative::{Attach, Begin, Detach, Disposition, Flow, Transfer};
+    use tokio::net::{TcpListener, TcpStream};
+
+    async fn connected() -> (Transport, Transport) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (client, server) = tokio::join!(TcpStream::connect(listener.local_addr().unwrap()), listener.accept());
+        (Transport::new(client.unwrap()), Transport::new(server.unwrap().0))
+    }
+
+    async fn send_on(peer: &mut Transport, channel: u16, performative: Performative) {
+        let payload = performative.encode().unwrap();
+        let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, channel);
+        peer.send_frame(Frame::new(header, payload)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_frames_routed_by_channel_name_and_handle() {
+        let (local, mut peer) = connected().await;
+        let (demux, _driver) = Demux::spawn(local, u32::MAX, "demux-test-route");
+        let session = demux.session(2).unwrap();
+        let receiver = demux.link(2, "orders", Role::Receiver).unwrap();
+        let sender = demux.link(2, "orders", Role::Sender).unwrap();
+
+        session.send(Performative::Begin(Begin::default())).unwrap();
+        let frame = peer.receive_frame().await.unwrap();
+        assert_eq!(frame.header.channel, 2);
+        assert!(matches!(Performative::decode(&frame.payload).unwrap(), Performative::Begin(_)));
+
+        // The peer answers on its own channel 7, with its own handles
+        send_on(&mut peer, 7, Performative::Begin(Begin { remote_channel: Some(2), ..Default::default() })).await;
+        let attach = |handle, role| Attach { name: "orders".to_string(), handle, role, ..Default::default() };
+        send_on(&mut peer, 7, Performative::Attach(attach(5, Role::Sender))).await;
+        send_on(&mut peer, 7, Performative::Attach(attach(6, Role::Receiver))).await;
+        let transfer = Transfer { handle: 5, delivery_id: Some(0), payload: vec![0x00, 0x53, 0x77, 0x40], ..Default::default() };
+        send_on(&mut peer, 7, Performative::Transfer(transfer.clone())).await;
+        let disposition = Disposition { role: Role::Receiver, first: 0, last: None, settled: true, state: None, batchable: false };
+        send_on(&mut peer, 7, Performative::Disposition(disposition.clone())).await;
+        send_on(&mut peer, 7, Performative::Detach(Detach { handle: 6, closed: true, error: None })).await;
+
+        assert!(matches!(session.recv().await, Some(Performative::Begin(begin)) if begin.remote_channel == Some(2)));
+        assert!(matches!(receiver.recv().await, Some(Performative::Attach(attach)) if attach.handle == 5));
+        assert_eq!(receiver.recv().await, Some(Performative::Transfer(transfer)));
+        assert!(matches!(sender.recv().await, Some(Performative::Attach(attach)) if attach.handle == 6));
+        assert_eq!(sender.recv().await, Some(Performative::Disposition(disposition)));
+        assert!(matches!(sender.recv().await, Some(Performative::Detach(detach)) if detach.handle == 6));
+        assert!(receiver.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_link_flow_completed_with_session_state() {
+        let (local, mut peer) = connected().await;
+        let (demux, _driver) = Demux::spawn(local, u32::MAX, "demux-test-flow");
+        let session = demux.session(1).unwrap();
+        let link = demux.link(1, "orders", Role::Receiver).unwrap();
+
+        let begin = Begin { next_outgoing_id: 10, incoming_window: 100, outgoing_window: 50, ..Default::default() };
+        session.send(Performative::Begin(begin)).unwrap();
+        peer.receive_frame().await.unwrap();
+        let answer = Begin { remote_channel: Some(1), next_outgoing_id: 7, ..Default::default() };
+        send_on(&mut peer, 3, Performative::Begin(answer)).await;
+        send_on(&mut peer, 3, Performative::Attach(Attach { name: "orders".to_string(), handle: 0, role: Role::Sender, ..Default::default() })).await;
+        send_on(&mut peer, 3, Performative::Transfer(Transfer { handle: 0, delivery_id: Some(0), ..Default::default() })).await;
+        session.recv().await.unwrap();
+        link.recv().await.unwrap();
+        link.recv().await.unwrap();
+        demux.send(1, Performative::Transfer(Transfer { handle: 0, delivery_id: Some(0), ..Default::default() })).await.unwrap();
+        peer.receive_frame().await.unwrap();
+
+        link.send(Performative::Flow(Flow { handle: Some(0), link_credit: Some(3), ..Default::default() })).unwrap();
+        let frame = peer.receive_frame().await.unwrap();
+        let flow = Flow::decode(&frame.payload).unwrap();
+        assert_eq!((flow.handle, flow.link_credit), (Some(0), Some(3)));
+        // One transfer received after the peer's first ID, one sent after ours
+        assert_eq!(flow.next_incoming_id, Some(8));
+        assert_eq!(flow.next_outgoing_id, 11);
+        assert_eq!((flow.incoming_window, flow.outgoing_window), (100, 50));
+    }
+
+    #[tokio::test]
+    async fn test_large_transfer_split_to_max_frame_size() {
+        let (local, mut peer) = connected().await;
+        let (demux, _driver) = Demux::spawn(local, 512, "demux-test-split");
+        let transfer = Transfer {
+            handle: 1,
+            delivery_id: Some(9),
+            delivery_tag: Some(vec![9]),
+            message_format: Some(0),
+            payload: (0..2000).map(|i| i as u8).collect(),
+            ..Default::default()
+        };
+        demux.send(4, Performative::Transfer(transfer.clone())).await.unwrap();
+
+        let mut frames = Vec::new();
+        loop {
+            let frame = peer.receive_frame().await.unwrap();
+            assert!(frame.payload.len() + FRAME_HEADER_SIZE <= 512);
+            assert_eq!(frame.header.channel, 4);
+            let part = Transfer::decode(&frame.payload).unwrap();
+            let more = part.more;
+            frames.push(part);
+            if !more {
+                break;
+            }
+        }
+        assert!(frames.len() > 1);
+        assert_eq!(frames[0].delivery_id, Some(9));
+        assert!(frames[1..].iter().all(|part| part.delivery_id.is_none() && part.handle == 1));
+        let payload: Vec<u8> = frames.iter().flat_map(|part| part.payload.clone()).collect();
+        assert_eq!(payload, transfer.payload);
+    }
+
+    async fn send_raw(peer: &mut Transport, channel: u16, descriptor: u64, fields: &[AmqpValue]) {
+        let mut encoder = crate::codec::Encoder::new();
+        encoder.encode_described_list(descriptor, fields).unwrap();
+        let payload = encoder.finish();
+        let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, channel);
+        peer.send_frame(Frame::new(header, payload)).await.unwrap();
+    }
+
+    fn decode_error(performative: Option<Performative>) -> Option<AmqpCondition> {
+        match performative {
+            Some(Performative::Detach(detach)) if detach.closed => detach.error.map(|error| error.condition),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_undecodable_frame_detaches_its_link() {
+        use performative::descriptor::{ATTACH, TRANSFER};
+
+        let (local, mut peer) = connected().await;
+        let (demux, _driver) = Demux::spawn(local, u32::MAX, "demux-test-undecodable");
+        let session = demux.session(0).unwrap();
+        let orders = demux.link(0, "orders", Role::Receiver).unwrap();
+        let audit = demux.link(0, "audit", Role::Receiver).unwrap();
+        let events = demux.link(0, "events", Role::Receiver).unwrap();
+
+        session.send(Performative::Begin(Begin::default())).unwrap();
+        peer.receive_frame().await.unwrap();
+        send_on(&mut peer, 4, Performative::Begin(Begin { remote_channel: Some(0), ..Default::default() })).await;
+        session.recv().await.unwrap();
+        for (handle, link, name) in [(0, &orders, "orders"), (1, &audit, "audit"), (2, &events, "events")] {
+            link.send(Performative::Attach(Attach { name: name.to_string(), handle, role: Role::Receiver, ..Default::default() })).unwrap();
+            peer.receive_frame().await.unwrap();
+        }
+        for (handle, link, name) in [(7, &orders, "orders"), (8, &audit, "audit")] {
+            send_on(&mut peer, 4, Performative::Attach(Attach { name: name.to_string(), handle, role: Role::Sender, ..Default::default() })).await;
+            link.recv().await.unwrap();
+        }
+
+        // A Transfer whose delivery-id is a string goes to the link of its handle
+        send_raw(&mut peer, 4, TRANSFER, &[AmqpValue::Uint(7), AmqpValue::String("one".to_string())]).await;
+        assert_eq!(decode_error(orders.recv().await), Some(AmqpCondition::AmqpErrorDecodeError));
+        orders.send(Performative::Detach(Detach { handle: 0, closed: true, error: None })).unwrap();
+        let detach = Detach::decode(&peer.receive_frame().await.unwrap().payload).unwrap();
+        assert_eq!(detach.handle, 0);
+        assert_eq!(detach.error.map(|error| error.condition), Some(AmqpCondition::AmqpErrorDecodeError));
+
+        // An Attach the link is waiting for is refused, and detached right away
+        let bad_attach = [
+            AmqpValue::String("events".to_string()),
+            AmqpValue::Uint(9),
+            AmqpValue::Boolean(false),
+            AmqpValue::String("settled".to_string()),
+        ];
+        send_raw(&mut peer, 4, ATTACH, &bad_attach).await;
+        assert_eq!(decode_error(events.recv().await), Some(AmqpCondition::AmqpErrorDecodeError));
+        let detach = Detach::decode(&peer.receive_frame().await.unwrap().payload).unwrap();
+        assert_eq!(detach.handle, 2);
+        assert_eq!(detach.error.map(|error| error.condition), Some(AmqpCondition::AmqpErrorDecodeError));
+
+        // Other links carry on
+        let transfer = Transfer { handle: 8, delivery_id: Some(0), payload: vec![0x00, 0x53, 0x77, 0x40], ..Default::default() };
+        send_on(&mut peer, 4, Performative::Transfer(transfer.clone())).await;
+        assert_eq!(audit.recv().await, Some(Performative::Transfer(transfer)));
+        assert!(orders.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_close_returns_peer_close_and_releases_endpoints() {
+        let (local, mut peer) = connected().await;
+        let (demux, driver) = Demux::spawn(local, u32::MAX, "demux-test-close");
+        let session = demux.session(0).unwrap();
+
+        demux.close().unwrap();
+        let frame = peer.receive_frame().await.unwrap();
+        assert_eq!(Close::decode(&frame.payload).unwrap(), Close::default());
+        let error = crate::types::AmqpError::new(crate::AmqpCondition::AmqpErrorInternalError);
+        let payload = Close { error: Some(error.clone()) }.encode().unwrap();
+        peer.send_frame(Frame::new(FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, 0), payload))
+            .await
+            .unwrap();
+
+        assert_eq!(driver.await.unwrap().unwrap().error, Some(error));
+        assert!(session.recv().await.is_none());
+        assert!(!demux.is_running());
+    }
+}
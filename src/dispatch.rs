@@ -0,0 +1,281 @@
+//! AMQP 1.0 Fair Dispatch
+//!
+//! This module merges deliveries from several receivers into one stream, for
+//! workers that consume many queues or partitions. A [`MultiReceiver`] polls
+//! its receivers in turn, either one delivery each ([`Fairness::RoundRobin`])
+//! or up to a per-receiver weight before moving on ([`Fairness::Weighted`]),
+//! so a busy address cannot starve the others. Every delivery remembers the
+//! receiver it came from, and settlement is routed back to that receiver.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::dispatch::{Fairness, MultiReceiver};
+//! use dumq_amqp::link::LinkBuilder;
+//! use dumq_amqp::message::Message;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut orders = LinkBuilder::new().source("orders").build_receiver("session-1".to_string());
+//! let mut audit = LinkBuilder::new().source("audit").build_receiver("session-1".to_string());
+//! orders.attach().await?;
+//! audit.attach().await?;
+//! orders.simulate_receive(Message::text("order"));
+//! audit.simulate_receive(Message::text("audit"));
+//!
+//! let mut worker = MultiReceiver::new(Fairness::RoundRobin);
+//! worker.add(orders);
+//! worker.add(audit);
+//!
+//! while let Some(delivery) = worker.receive().await? {
+//!     println!("{:?} from receiver {}", delivery.message.body_as_text(), delivery.source);
+//!     worker.accept(&delivery)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::link::Receiver;
+use crate::performative::Outcome;
+use crate::{AmqpError, AmqpResult, Message};
+use std::collections::BTreeMap;
+
+/// How a [`MultiReceiver`] shares turns between its receivers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Fairness {
+    /// One delivery from each receiver in turn
+    #[default]
+    RoundRobin,
+    /// Up to each receiver's weight in deliveries before moving on
+    Weighted,
+}
+
+/// A delivery received through a [`MultiReceiver`]
+#[derive(Debug, Clone)]
+pub struct MultiDelivery {
+    /// Index of the receiver the delivery came from
+    pub source: usize,
+    /// Delivery ID on that receiver
+    pub delivery_id: u32,
+    /// The message
+    pub message: Message,
+}
+
+impl MultiDelivery {
+    /// Get the `(source, delivery_id)` pair identifying this delivery
+    pub fn key(&self) -> (usize, u32) {
+        (self.source, self.delivery_id)
+    }
+}
+
+#[derive(Debug)]
+struct Member {
+    receiver: Receiver,
+    weight: u32,
+}
+
+/// Receiver combinator merging deliveries from several links
+#[derive(Debug)]
+pub struct MultiReceiver {
+    fairness: Fairness,
+    members: Vec<Member>,
+    /// Receiver whose turn it is
+    cursor: usize,
+    /// Deliveries taken from the cursor receiver in the current turn
+    served: u32,
+}
+
+impl MultiReceiver {
+    /// Create an empty multi-receiver
+    pub fn new(fairness: Fairness) -> Self {
+        MultiReceiver {
+            fairness,
+            members: Vec::new(),
+            cursor: 0,
+            served: 0,
+        }
+    }
+
+    /// Add a receiver with weight 1, returning its source index
+    pub fn add(&mut self, receiver: Receiver) -> usize {
+        self.add_weighted(receiver, 1)
+    }
+
+    /// Add a receiver with a weight, returning its source index
+    ///
+    /// The weight is the number of consecutive deliveries taken from the
+    /// receiver per turn under [`Fairness::Weighted`]; it is ignored under
+    /// [`Fairness::RoundRobin`].
+    pub fn add_weighted(&mut self, receiver: Receiver, weight: u32) -> usize {
+        self.members.push(Member {
+            receiver,
+            weight: weight.max(1),
+        });
+        self.members.len() - 1
+    }
+
+    /// Receive the next delivery from whichever receiver's turn it is
+    ///
+    /// Receivers with nothing buffered are skipped. Returns `None` if no
+    /// receiver has a message. An error from one receiver is returned, and
+    /// its turn passes to the next receiver so it cannot stall the rest.
+    pub async fn receive(&mut self) -> AmqpResult<Option<MultiDelivery>> {
+        for _ in 0..self.members.len() {
+            let source = self.cursor;
+            match self.members[source].receiver.receive_delivery().await {
+                Ok(Some((delivery_id, message))) => {
+                    self.served += 1;
+                    if self.served >= self.quota(source) {
+                        self.advance();
+                    }
+                    return Ok(Some(MultiDelivery {
+                        source,
+                        delivery_id,
+                        message,
+                    }));
+                }
+                Ok(None) => self.advance(),
+                Err(e) => {
+                    self.advance();
+                    return Err(e);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Settle deliveries with an outcome
+    ///
+    /// Deliveries are grouped by source so each receiver coalesces its own
+    /// dispositions. Fails without settling anything if a source is unknown;
+    /// otherwise each receiver settles its group as [`Receiver::settle`] does.
+    pub fn settle(&mut self, deliveries: &[(usize, u32)], outcome: Outcome) -> AmqpResult<()> {
+        let mut by_source: BTreeMap<usize, Vec<u32>> = BTreeMap::new();
+        for &(source, delivery_id) in deliveries {
+            if source >= self.members.len() {
+                return Err(AmqpError::link(format!("Unknown receiver {} in multi-receiver", source)));
+            }
+            by_source.entry(source).or_default().push(delivery_id);
+        }
+        for (source, delivery_ids) in by_source {
+            self.members[source].receiver.settle(&delivery_ids, outcome.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Accept and settle a delivery
+    pub fn accept(&mut self, delivery: &MultiDelivery) -> AmqpResult<()> {
+        self.settle(&[delivery.key()], Outcome::Accepted)
+    }
+
+    /// Get a receiver by source index
+    pub fn receiver(&self, source: usize) -> Option<&Receiver> {
+        self.members.get(source).map(|member| &member.receiver)
+    }
+
+    /// Get a mutable receiver by source index, e.g. to add credit
+    pub fn receiver_mut(&mut self, source: usize) -> Option<&mut Receiver> {
+        self.members.get_mut(source).map(|member| &mut member.receiver)
+    }
+
+    /// Get the number of unsettled deliveries across all receivers
+    pub fn unsettled_count(&self) -> usize {
+        self.members.iter().map(|member| member.receiver.unsettled_count()).sum()
+    }
+
+    /// Get the number of receivers
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Check if there are no receivers
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Take back the receivers, in source order
+    pub fn into_receivers(self) -> Vec<Receiver> {
+        self.members.into_iter().map(|member| member.receiver).collect()
+    }
+
+    fn quota(&self, source: usize) -> u32 {
+        match self.fairness {
+            Fairness::RoundRobin => 1,
+            Fairness::Weighted => self.members[source].weight,
+        }
+    }
+
+    fn advance(&mut self) {
+        self.served = 0;
+        self.cursor = (self.cursor + 1) % self.members.len();
+    }
+}
+
+impl Default for MultiReceiver {
+    fn default() -> Self {
+        Self::new(Fairness::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::link::LinkBuilder;
+
+    async fn receiver_with(source: &str, count: usize) -> Receiver {
+        let mut receiver = LinkBuilder::new().source(source).build_receiver("session-1".to_string());
+        receiver.attach().await.unwrap();
+        for i in 0..count {
+            receiver.simulate_receive(Message::text(format!("{}-{}", source, i)));
+        }
+        receiver
+    }
+
+    async fn drain(multi: &mut MultiReceiver) -> Vec<usize> {
+        let mut sources = Vec::new();
+        while let Some(delivery) = multi.receive().await.unwrap() {
+            sources.push(delivery.source);
+        }
+        sources
+    }
+
+    #[tokio::test]
+    async fn test_round_robin_interleaves_and_skips_empty() {
+        let mut multi = MultiReceiver::default();
+        multi.add(receiver_with("a", 3).await);
+        multi.add(receiver_with("b", 0).await);
+        multi.add(receiver_with("c", 1).await);
+
+        assert_eq!(drain(&mut multi).await, vec![0, 2, 0, 0]);
+        assert!(multi.receive().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_weighted_takes_weight_per_turn() {
+        let mut multi = MultiReceiver::new(Fairness::Weighted);
+        multi.add_weighted(receiver_with("a", 4).await, 3);
+        multi.add(receiver_with("b", 3).await);
+
+        assert_eq!(drain(&mut multi).await, vec![0, 0, 0, 1, 0, 1, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_settlement_routed_to_source() {
+        let mut multi = MultiReceiver::default();
+        multi.add(receiver_with("a", 2).await);
+        multi.add(receiver_with("b", 1).await);
+
+        let mut keys = Vec::new();
+        while let Some(delivery) = multi.receive().await.unwrap() {
+            keys.push(delivery.key());
+        }
+        assert_eq!(multi.unsettled_count(), 3);
+
+        assert!(multi.settle(&[(0, 0), (7, 0)], Outcome::Accepted).is_err());
+        assert_eq!(multi.unsettled_count(), 3);
+
+        multi.settle(&keys, Outcome::Accepted).unwrap();
+        assert_eq!(multi.unsettled_count(), 0);
+        assert_eq!(multi.receiver(1).unwrap().unsettled_count(), 0);
+    }
+}
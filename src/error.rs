@@ -20,9 +20,28 @@
 //! - **Protocol**: AMQP protocol violations
 //! - **Timeout**: Operation timeouts
 //! - **IO**: Standard I/O errors
-//! - **Serialization**: JSON serialization errors
+//! - **Serialization**: JSON serialization errors (with the `serde` feature)
 //! - **InvalidState**: State machine violations
 //! - **NotImplemented**: Unimplemented features
+//! - **Integrity**: Message checksum/signature verification failures
+//! - **RetriesExhausted**: Retry budget used up; lists the error of each attempt
+//! - **ProtocolMismatch**: The peer answered with a different protocol header
+//! - **AttachMismatch**: The peer's Attach disagrees with the one sent
+//! - **ContainerInUse**: The peer allows one connection per container and one exists
+//! - **Tls**: The TLS handshake failed
+//! - **SaslMechanismMismatch**: The peer offers none of the SASL mechanisms configured
+//! - **AuthenticationRejected**: The peer rejected the SASL credentials
+//! - **HandshakeTimeout**: The peer did not complete the handshake in time
+//! - **TransportClosedMidFrame**: The peer closed the connection partway through a frame
+//! - **Context**: Another error, with the connection, session, link and delivery it concerns
+//!
+//! Handshake failures, including **ProtocolMismatch**, carry a hint on what to
+//! change, see [`AmqpError::remediation`].
+//!
+//! Errors returned by session and link operations carry an [`ErrorContext`]
+//! naming the connection, session channel, link and delivery involved, see
+//! [`AmqpError::link_name`] and its siblings. Match on [`AmqpError::inner`]
+//! to look past the context.
 //!
 //! # Examples
 //!
@@ -31,6 +50,7 @@
 //! ```rust
 //! use dumq_amqp::error::{AmqpError, AmqpResult};
 //!
+//! # fn some_operation() -> AmqpResult<()> { Ok(()) }
 //! fn handle_amqp_operation() -> AmqpResult<()> {
 //!     match some_operation() {
 //!         Ok(result) => Ok(result),
@@ -57,9 +77,28 @@
 //! let timeout_error = AmqpError::timeout("Operation timed out");
 //! let state_error = AmqpError::invalid_state("Connection is not open");
 //! ```
+//!
+//! ## Attributing Errors to Links
+//!
+//! ```rust
+//! use dumq_amqp::error::{AmqpError, ErrorContext};
+//!
+//! let error = AmqpError::link("No credit available")
+//!     .with_context(ErrorContext::default().connection("conn-1").channel(0).link("orders-sender"));
+//!
+//! assert_eq!(error.link_name(), Some("orders-sender"));
+//! assert_eq!(error.channel(), Some(0));
+//! assert!(matches!(error.inner(), AmqpError::Link(_)));
+//! ```
 
 use thiserror::Error;
 use crate::condition::AmqpCondition;
+use crate::link::AttachMismatch;
+use crate::network::HandshakeStage;
+use crate::sasl::SaslCode;
+use crate::transport::ProtocolHeader;
+use std::fmt;
+use std::time::Duration;
 
 /// AMQP 1.0 specific error types
 #[derive(Error, Debug)]
@@ -91,6 +130,7 @@ pub enum AmqpError {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
     
+    #[cfg(feature = "serde")]
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
     
@@ -99,18 +139,188 @@ pub enum AmqpError {
     
     #[error("Not implemented: {0}")]
     NotImplemented(String),
+
+    #[error("Integrity error: {0}")]
+    Integrity(String),
+
+    /// Retry budget exhausted, with the error of each attempt in order
+    #[error("Retries exhausted after {} attempts: {}", .attempts.len(), .attempts.join("; "))]
+    RetriesExhausted {
+        attempts: Vec<String>,
+    },
+    
+    /// The peer answered the protocol header with a different one
+    #[error("Protocol mismatch: expected {expected}, peer answered {received}")]
+    ProtocolMismatch {
+        expected: ProtocolHeader,
+        received: ProtocolHeader,
+    },
     
+    /// The peer's Attach disagrees with the one this endpoint sent
+    #[error("Attach mismatch: {0}")]
+    AttachMismatch(AttachMismatch),
+
+    /// The peer refused the connection under sole-connection-for-container
+    #[error("Container '{container_id}' already has a connection to the peer")]
+    ContainerInUse {
+        container_id: String,
+    },
+
+    /// The TLS handshake with the peer failed
+    #[error("TLS handshake with {peer} failed: {reason}; {}", TLS_HINT)]
+    Tls {
+        peer: String,
+        reason: String,
+    },
+
+    /// The peer offers none of the SASL mechanisms the credentials use
+    #[error("SASL mechanism {requested} is not offered by the peer (offered: {}); {}", .offered.join(", "), SASL_MECHANISM_HINT)]
+    SaslMechanismMismatch {
+        requested: String,
+        offered: Vec<String>,
+    },
+
+    /// The peer answered the SASL exchange with a failure outcome
+    #[error("SASL {mechanism} authentication rejected with {code:?}; {}", sasl_hint(.mechanism, *.code))]
+    AuthenticationRejected {
+        mechanism: String,
+        code: SaslCode,
+    },
+
+    /// The peer did not complete the handshake within the connection timeout
+    #[error("Handshake timed out after {timeout:?} waiting for the peer's {stage}; {}", HANDSHAKE_TIMEOUT_HINT)]
+    HandshakeTimeout {
+        stage: HandshakeStage,
+        timeout: Duration,
+    },
+
+    /// The connection closed after part of a frame was read
+    ///
+    /// `expected` is the size of the whole frame, header included, or 8 if
+    /// the header itself was cut short.
+    #[error("Transport closed mid-frame after {received} of {expected} bytes")]
+    TransportClosedMidFrame {
+        expected: u64,
+        received: u64,
+    },
+
     /// AMQP protocol error with condition code
     #[error("AMQP error: {condition} - {description}")]
     AmqpProtocol {
         condition: AmqpCondition,
         description: String,
     },
+
+    /// An error raised by a session or link operation, with what it concerns
+    #[error("{source} ({context})")]
+    Context {
+        context: ErrorContext,
+        source: Box<AmqpError>,
+    },
+}
+
+/// Connection, session, link and delivery an error concerns
+///
+/// Each part is known only where the error was raised within reach of it;
+/// a link not created through a session has no connection or channel.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// Id of the connection
+    pub connection_id: Option<String>,
+    /// Channel of the session
+    pub channel: Option<u16>,
+    /// Name of the link
+    pub link_name: Option<String>,
+    /// Id of the delivery
+    pub delivery_id: Option<u32>,
+}
+
+impl ErrorContext {
+    /// Set the connection id
+    pub fn connection(mut self, connection_id: impl Into<String>) -> Self {
+        self.connection_id = Some(connection_id.into());
+        self
+    }
+
+    /// Set the session channel
+    pub fn channel(mut self, channel: u16) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    /// Set the link name
+    pub fn link(mut self, name: impl Into<String>) -> Self {
+        self.link_name = Some(name.into());
+        self
+    }
+
+    /// Set the delivery id
+    pub fn delivery(mut self, delivery_id: u32) -> Self {
+        self.delivery_id = Some(delivery_id);
+        self
+    }
+
+    /// Fill in the parts not known here from a wider context
+    fn merge(&mut self, wider: ErrorContext) {
+        self.connection_id = self.connection_id.take().or(wider.connection_id);
+        self.channel = self.channel.or(wider.channel);
+        self.link_name = self.link_name.take().or(wider.link_name);
+        self.delivery_id = self.delivery_id.or(wider.delivery_id);
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(connection_id) = &self.connection_id {
+            parts.push(format!("connection {}", connection_id));
+        }
+        if let Some(channel) = self.channel {
+            parts.push(format!("channel {}", channel));
+        }
+        if let Some(link_name) = &self.link_name {
+            parts.push(format!("link '{}'", link_name));
+        }
+        if let Some(delivery_id) = self.delivery_id {
+            parts.push(format!("delivery {}", delivery_id));
+        }
+        f.write_str(&parts.join(", "))
+    }
 }
 
 /// Result type for AMQP operations
 pub type AmqpResult<T> = Result<T, AmqpError>;
 
+const TLS_HINT: &str =
+    "check that the client trusts the server certificate, sends a client certificate if one is required and offers ALPN \"amqp\"";
+const SASL_MECHANISM_HINT: &str = "configure credentials for one of the offered mechanisms";
+const HANDSHAKE_TIMEOUT_HINT: &str =
+    "check that the address is an AMQP 1.0 endpoint reachable without a proxy, or raise the connection timeout";
+
+fn sasl_hint(mechanism: &str, code: SaslCode) -> &'static str {
+    match code {
+        SaslCode::Auth if mechanism == "EXTERNAL" => {
+            "check that the peer trusts the client certificate and maps it to a user"
+        }
+        SaslCode::Auth => "check the user name and password",
+        SaslCode::Sys | SaslCode::SysTemp => "the peer failed to authenticate; retry later",
+        SaslCode::SysPerm => "the peer cannot authenticate clients; contact its operator",
+        SaslCode::Ok => "the exchange succeeded",
+    }
+}
+
+fn protocol_mismatch_hint(expected: ProtocolHeader, received: ProtocolHeader) -> &'static str {
+    if received == ProtocolHeader::SASL {
+        "the peer requires SASL; configure credentials"
+    } else if received == ProtocolHeader::TLS {
+        "the peer requires TLS; connect to its TLS port or enable TLS"
+    } else if expected == ProtocolHeader::SASL && received == ProtocolHeader::AMQP {
+        "the peer does not use SASL; remove the credentials"
+    } else {
+        "the peer does not speak AMQP 1.0; check the host and port"
+    }
+}
+
 impl AmqpError {
     /// Create a connection error
     pub fn connection(msg: impl Into<String>) -> Self {
@@ -162,6 +372,67 @@ impl AmqpError {
         AmqpError::NotImplemented(msg.into())
     }
     
+    /// Create an integrity error
+    pub fn integrity(msg: impl Into<String>) -> Self {
+        AmqpError::Integrity(msg.into())
+    }
+
+    /// Create a retries exhausted error from the errors of each attempt
+    pub fn retries_exhausted(attempts: Vec<String>) -> Self {
+        AmqpError::RetriesExhausted { attempts }
+    }
+
+    /// Create a protocol mismatch error from the header sent and the one received
+    pub fn protocol_mismatch(expected: ProtocolHeader, received: ProtocolHeader) -> Self {
+        AmqpError::ProtocolMismatch { expected, received }
+    }
+
+    /// Create an attach mismatch error
+    pub fn attach_mismatch(mismatch: AttachMismatch) -> Self {
+        AmqpError::AttachMismatch(mismatch)
+    }
+
+    /// Create a container in use error
+    pub fn container_in_use(container_id: impl Into<String>) -> Self {
+        AmqpError::ContainerInUse {
+            container_id: container_id.into(),
+        }
+    }
+
+    /// Create a TLS handshake error
+    pub fn tls(peer: impl Into<String>, reason: impl Into<String>) -> Self {
+        AmqpError::Tls {
+            peer: peer.into(),
+            reason: reason.into(),
+        }
+    }
+
+    /// Create a SASL mechanism mismatch error
+    pub fn sasl_mechanism_mismatch(requested: impl Into<String>, offered: Vec<String>) -> Self {
+        AmqpError::SaslMechanismMismatch {
+            requested: requested.into(),
+            offered,
+        }
+    }
+
+    /// Create an authentication rejected error from the SASL outcome code
+    pub fn authentication_rejected(mechanism: impl Into<String>, code: SaslCode) -> Self {
+        AmqpError::AuthenticationRejected {
+            mechanism: mechanism.into(),
+            code,
+        }
+    }
+
+    /// Create a handshake timeout error for the stage that did not complete
+    pub fn handshake_timeout(stage: HandshakeStage, timeout: Duration) -> Self {
+        AmqpError::HandshakeTimeout { stage, timeout }
+    }
+
+    /// Create an error for a frame cut short by the connection closing
+    pub fn transport_closed_mid_frame(expected: u64, received: u64) -> Self {
+        AmqpError::TransportClosedMidFrame { expected, received }
+    }
+
     /// Create an AMQP protocol error with condition code
     pub fn amqp_protocol(condition: AmqpCondition, description: impl Into<String>) -> Self {
         AmqpError::AmqpProtocol {
@@ -170,14 +441,83 @@ impl AmqpError {
         }
     }
     
+    /// Attach context, keeping any already attached where it is more specific
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        match self {
+            AmqpError::Context { context: mut inner, source } => {
+                inner.merge(context);
+                AmqpError::Context { context: inner, source }
+            }
+            error => AmqpError::Context { context, source: Box::new(error) },
+        }
+    }
+
+    /// Get the error without its context
+    pub fn inner(&self) -> &AmqpError {
+        match self {
+            AmqpError::Context { source, .. } => source.inner(),
+            error => error,
+        }
+    }
+
+    /// Take the error without its context
+    pub fn into_inner(self) -> AmqpError {
+        match self {
+            AmqpError::Context { source, .. } => source.into_inner(),
+            error => error,
+        }
+    }
+
+    /// Get the context attached by a session or link operation
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            AmqpError::Context { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Get the id of the connection the error concerns
+    pub fn connection_id(&self) -> Option<&str> {
+        self.context()?.connection_id.as_deref()
+    }
+
+    /// Get the channel of the session the error concerns
+    pub fn channel(&self) -> Option<u16> {
+        self.context()?.channel
+    }
+
+    /// Get the name of the link the error concerns
+    pub fn link_name(&self) -> Option<&str> {
+        self.context()?.link_name.as_deref()
+    }
+
+    /// Get the id of the delivery the error concerns
+    pub fn delivery_id(&self) -> Option<u32> {
+        self.context()?.delivery_id
+    }
+
     /// Get the error condition if this is an AMQP protocol error
     pub fn condition(&self) -> Option<&AmqpCondition> {
-        match self {
+        match self.inner() {
             AmqpError::AmqpProtocol { condition, .. } => Some(condition),
             _ => None,
         }
     }
     
+    /// Get a hint on how to fix a failed handshake
+    ///
+    /// Returns `None` for errors that are not handshake failures.
+    pub fn remediation(&self) -> Option<&'static str> {
+        match self.inner() {
+            AmqpError::Tls { .. } => Some(TLS_HINT),
+            AmqpError::SaslMechanismMismatch { .. } => Some(SASL_MECHANISM_HINT),
+            AmqpError::AuthenticationRejected { mechanism, code } => Some(sasl_hint(mechanism, *code)),
+            AmqpError::HandshakeTimeout { .. } => Some(HANDSHAKE_TIMEOUT_HINT),
+            AmqpError::ProtocolMismatch { expected, received } => Some(protocol_mismatch_hint(*expected, *received)),
+            _ => None,
+        }
+    }
+
     /// Get the error code as a string
     pub fn error_code(&self) -> &str {
         match self {
@@ -190,19 +530,46 @@ impl AmqpError {
             AmqpError::Protocol(_) => "protocol-error",
             AmqpError::Timeout(_) => "timeout-error",
             AmqpError::Io(_) => "io-error",
+            #[cfg(feature = "serde")]
             AmqpError::Serialization(_) => "serialization-error",
             AmqpError::InvalidState(_) => "invalid-state-error",
             AmqpError::NotImplemented(_) => "not-implemented-error",
+            AmqpError::Integrity(_) => "integrity-error",
+            AmqpError::RetriesExhausted { .. } => "retries-exhausted",
+            AmqpError::ProtocolMismatch { .. } => "protocol-mismatch",
+            AmqpError::AttachMismatch(_) => "attach-mismatch",
+            AmqpError::ContainerInUse { .. } => "container-in-use",
+            AmqpError::Tls { .. } => "tls-error",
+            AmqpError::SaslMechanismMismatch { .. } => "sasl-mechanism-mismatch",
+            AmqpError::AuthenticationRejected { .. } => "authentication-rejected",
+            AmqpError::HandshakeTimeout { .. } => "handshake-timeout",
+            AmqpError::TransportClosedMidFrame { .. } => "transport-closed-mid-frame",
             AmqpError::AmqpProtocol { condition, .. } => condition.as_str(),
+            AmqpError::Context { source, .. } => source.error_code(),
         }
     }
 
     pub fn error_code_num(&self) -> u16 {
-        match self {
+        match self.inner() {
             AmqpError::AmqpProtocol { condition, .. } => condition.code_num(),
             _ => 500,
         }
     }
+
+    /// Get the HTTP status a gateway should answer with for this error
+    #[cfg(feature = "http")]
+    pub fn http_status(&self) -> http::StatusCode {
+        match self.inner() {
+            AmqpError::AmqpProtocol { condition, .. } => condition.http_status(),
+            _ => http::StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Create an AMQP protocol error from an HTTP error status
+    #[cfg(feature = "http")]
+    pub fn from_http_status(status: http::StatusCode, description: impl Into<String>) -> Self {
+        AmqpError::amqp_protocol(AmqpCondition::from_http_status(status), description)
+    }
 } 
 
 #[cfg(test)]
@@ -290,6 +657,14 @@ mod tests {
         assert_eq!(error.error_code_num(), 500);
     }
 
+    #[test]
+    fn test_integrity_error_creation() {
+        let error = AmqpError::integrity("Digest mismatch");
+        assert!(matches!(error, AmqpError::Integrity(_)));
+        assert_eq!(error.error_code(), "integrity-error");
+        assert_eq!(error.error_code_num(), 500);
+    }
+
     #[test]
     fn test_amqp_protocol_error_creation() {
         let condition = AmqpCondition::AmqpErrorInternalError;
@@ -315,6 +690,7 @@ mod tests {
         assert_eq!(amqp_error.error_code_num(), 500);
     }
 
+    #[cfg(feature = "serde")]
     #[test]
     fn test_serialization_error_conversion() {
         let json_error = serde_json::from_str::<String>("invalid json").unwrap_err();
@@ -382,6 +758,7 @@ mod tests {
             AmqpError::timeout("test"),
             AmqpError::invalid_state("test"),
             AmqpError::not_implemented("test"),
+            AmqpError::integrity("test"),
         ];
         
         for error in errors {
@@ -563,6 +940,7 @@ mod tests {
         assert_eq!(amqp_error.error_code_num(), 500);
     }
 
+    #[cfg(feature = "serde")]
     #[test]
     fn test_error_from_serde_json_error() {
         // Test conversion from serde_json::Error
@@ -648,4 +1026,93 @@ mod tests {
             assert_eq!(msg, "error");
         }
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_retries_exhausted_error() {
+        let error = AmqpError::retries_exhausted(vec!["first".to_string(), "second".to_string()]);
+        assert!(matches!(error, AmqpError::RetriesExhausted { .. }));
+        assert_eq!(error.error_code(), "retries-exhausted");
+        assert_eq!(error.to_string(), "Retries exhausted after 2 attempts: first; second");
+    }
+
+    #[test]
+    fn test_context_merges_and_delegates() {
+        let error = AmqpError::amqp_protocol(AmqpCondition::AmqpErrorResourceLimitExceeded, "Over budget")
+            .with_context(ErrorContext::default().delivery(7))
+            .with_context(ErrorContext::default().connection("conn-1").channel(2).link("orders").delivery(9));
+
+        assert_eq!(error.connection_id(), Some("conn-1"));
+        assert_eq!(error.channel(), Some(2));
+        assert_eq!(error.link_name(), Some("orders"));
+        assert_eq!(error.delivery_id(), Some(7));
+        assert_eq!(error.error_code(), "amqp:resource:limit-exceeded");
+        assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorResourceLimitExceeded));
+        assert!(matches!(error.inner(), AmqpError::AmqpProtocol { .. }));
+        assert_eq!(
+            error.to_string(),
+            "AMQP error: amqp:resource:limit-exceeded - Over budget (connection conn-1, channel 2, link 'orders', delivery 7)"
+        );
+        assert!(matches!(error.into_inner(), AmqpError::AmqpProtocol { .. }));
+        assert_eq!(AmqpError::link("No credit").delivery_id(), None);
+    }
+
+    #[test]
+    fn test_protocol_mismatch_error() {
+        let error = AmqpError::protocol_mismatch(ProtocolHeader::AMQP, ProtocolHeader::SASL);
+        assert!(matches!(error, AmqpError::ProtocolMismatch { .. }));
+        assert_eq!(error.error_code(), "protocol-mismatch");
+        assert_eq!(error.to_string(), "Protocol mismatch: expected AMQP0 1.0.0, peer answered AMQP3 1.0.0");
+    }
+
+    #[test]
+    fn test_handshake_errors_are_distinct() {
+        let errors = [
+            (AmqpError::tls("10.0.0.7:5671", "unknown CA"), "tls-error"),
+            (AmqpError::sasl_mechanism_mismatch("PLAIN", vec!["EXTERNAL".to_string()]), "sasl-mechanism-mismatch"),
+            (AmqpError::authentication_rejected("PLAIN", SaslCode::Auth), "authentication-rejected"),
+            (AmqpError::handshake_timeout(HandshakeStage::Open, Duration::from_secs(5)), "handshake-timeout"),
+            (AmqpError::protocol_mismatch(ProtocolHeader::AMQP, ProtocolHeader::SASL), "protocol-mismatch"),
+        ];
+        for (error, code) in &errors {
+            assert_eq!(error.error_code(), *code);
+            assert!(error.remediation().is_some(), "{}", error);
+        }
+        assert!(AmqpError::connection("refused").remediation().is_none());
+
+        assert_eq!(
+            errors[1].0.to_string(),
+            "SASL mechanism PLAIN is not offered by the peer (offered: EXTERNAL); \
+             configure credentials for one of the offered mechanisms"
+        );
+        assert_eq!(
+            AmqpError::authentication_rejected("PLAIN", SaslCode::SysTemp).remediation(),
+            Some("the peer failed to authenticate; retry later")
+        );
+        assert_eq!(errors[4].0.remediation(), Some("the peer requires SASL; configure credentials"));
+        assert_eq!(
+            AmqpError::protocol_mismatch(ProtocolHeader::SASL, ProtocolHeader::AMQP).remediation(),
+            Some("the peer does not use SASL; remove the credentials")
+        );
+        assert!(errors[3].0.to_string().starts_with("Handshake timed out after 5s waiting for the peer's Open;"));
+    }
+
+    #[test]
+    fn test_container_in_use_error() {
+        let error = AmqpError::container_in_use("client-a");
+        assert_eq!(error.error_code(), "container-in-use");
+        assert_eq!(error.to_string(), "Container 'client-a' already has a connection to the peer");
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_http_status_round_trip() {
+        let error = AmqpError::amqp_protocol(AmqpCondition::AmqpErrorResourceDeleted, "Queue deleted");
+        let status = error.http_status();
+        assert_eq!(status, http::StatusCode::GONE);
+
+        let back = AmqpError::from_http_status(status, "Queue deleted");
+        assert_eq!(back.condition(), Some(&AmqpCondition::AmqpErrorResourceDeleted));
+        assert_eq!(back.error_code_num(), error.error_code_num());
+        assert_eq!(AmqpError::timeout("slow").http_status(), http::StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,249 @@
+//! AMQP 1.0 Expiry and Clock Skew
+//!
+//! This module is the one place the crate reads the wall clock to decide
+//! whether something has expired. Timestamps such as a message's
+//! `absolute-expiry-time` or the expiry of a CBS token are written by
+//! another host, whose clock may be minutes apart from ours. An
+//! [`ExpiryPolicy`] allows for that drift in the safe direction each time:
+//!
+//! - a message is only treated as expired once its absolute expiry time lies
+//!   more than the allowed skew in the past, so a client running fast does
+//!   not drop messages the broker still considers live
+//! - a token is refreshed the allowed skew before it expires, so a client
+//!   running slow does not present a token the broker already rejects
+//!
+//! A message's `ttl` is measured from its arrival on our own clock and gets
+//! no allowance.
+//!
+//! The clock itself is pluggable through [`Clock`], so tests can move time
+//! with a [`ManualClock`] instead of sleeping.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::expiry::{ExpiryPolicy, ManualClock};
+//! use dumq_amqp::message::{Message, Properties};
+//! use std::time::{Duration, UNIX_EPOCH};
+//!
+//! let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_000));
+//! let policy = ExpiryPolicy::new(clock.clone()).clock_skew(Duration::from_secs(30));
+//!
+//! let mut properties = Properties::new();
+//! properties.absolute_expiry_time = Some(990_000);
+//! let message = Message::builder().properties(properties).build();
+//!
+//! // Ten seconds past its expiry time, but within the allowed skew
+//! assert!(!policy.is_expired(&message, clock.now()));
+//! clock.advance(Duration::from_secs(25));
+//! assert!(policy.is_expired(&message, clock.now()));
+//!
+//! // A token expiring in a minute is refreshed 30 seconds early
+//! let token_expiry = clock.now() + Duration::from_secs(60);
+//! assert_eq!(policy.refresh_in(token_expiry), Duration::from_secs(30));
+//! ```
+
+use crate::Message;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Source of the current wall-clock time
+pub trait Clock: Debug + Send + Sync {
+    /// Get the current time
+    fn now(&self) -> SystemTime;
+}
+
+/// The system clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to, shared by its clones
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl ManualClock {
+    /// Create a clock standing at `now`
+    pub fn new(now: SystemTime) -> Self {
+        ManualClock { now: Arc::new(Mutex::new(now)) }
+    }
+
+    /// Get the current time
+    pub fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Move the clock forward
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) += by;
+    }
+
+    /// Move the clock to a point in time, forward or back
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) = now;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        ManualClock::now(self)
+    }
+}
+
+/// Expiry decisions made against a clock, allowing for skew with the peer
+#[derive(Debug, Clone)]
+pub struct ExpiryPolicy {
+    clock: Arc<dyn Clock>,
+    clock_skew: Duration,
+}
+
+impl Default for ExpiryPolicy {
+    fn default() -> Self {
+        Self::new(SystemClock)
+    }
+}
+
+impl ExpiryPolicy {
+    /// Default allowance for the peer's clock being apart from ours
+    pub const DEFAULT_CLOCK_SKEW: Duration = Duration::from_secs(30);
+
+    /// Create a policy over a clock with the default skew allowance
+    pub fn new(clock: impl Clock + 'static) -> Self {
+        ExpiryPolicy {
+            clock: Arc::new(clock),
+            clock_skew: Self::DEFAULT_CLOCK_SKEW,
+        }
+    }
+
+    /// Set the allowance for the peer's clock being apart from ours
+    pub fn clock_skew(mut self, clock_skew: Duration) -> Self {
+        self.clock_skew = clock_skew;
+        self
+    }
+
+    /// Get the skew allowance
+    pub fn skew(&self) -> Duration {
+        self.clock_skew
+    }
+
+    /// Get the current time of the policy's clock
+    pub fn now(&self) -> SystemTime {
+        self.clock.now()
+    }
+
+    /// Get the time after which a message counts as expired, if it expires
+    ///
+    /// This is the earlier of its absolute expiry time plus the skew
+    /// allowance and `received_at` plus its TTL.
+    pub fn expires_at(&self, message: &Message, received_at: SystemTime) -> Option<SystemTime> {
+        let absolute = message
+            .properties
+            .as_ref()
+            .and_then(|properties| properties.absolute_expiry_time)
+            .map(|millis| timestamp(millis).and_then(|at| at.checked_add(self.clock_skew)));
+        let ttl = message
+            .header
+            .as_ref()
+            .and_then(|header| header.ttl)
+            .map(|ttl| received_at + Duration::from_millis(u64::from(ttl)));
+        match (absolute, ttl) {
+            // An expiry time too far out to represent never comes
+            (Some(None), ttl) => ttl,
+            (Some(Some(absolute)), Some(ttl)) => Some(absolute.min(ttl)),
+            (Some(absolute), None) => absolute,
+            (None, ttl) => ttl,
+        }
+    }
+
+    /// Check if a message received at `received_at` has expired by now
+    pub fn is_expired(&self, message: &Message, received_at: SystemTime) -> bool {
+        self.expires_at(message, received_at).is_some_and(|expires_at| self.now() >= expires_at)
+    }
+
+    /// Get the time to refresh a token that the peer lets expire at `expires_at`
+    ///
+    /// This is the skew allowance ahead of the expiry, or now if that has
+    /// already passed.
+    pub fn refresh_at(&self, expires_at: SystemTime) -> SystemTime {
+        let now = self.now();
+        expires_at.checked_sub(self.clock_skew).map_or(now, |refresh_at| refresh_at.max(now))
+    }
+
+    /// Get the time left until a token expiring at `expires_at` is refreshed
+    pub fn refresh_in(&self, expires_at: SystemTime) -> Duration {
+        self.refresh_at(expires_at).duration_since(self.now()).unwrap_or_default()
+    }
+}
+
+/// Convert an AMQP timestamp, milliseconds since the Unix epoch, to a time
+fn timestamp(millis: i64) -> Option<SystemTime> {
+    let offset = Duration::from_millis(millis.unsigned_abs());
+    if millis >= 0 {
+        UNIX_EPOCH.checked_add(offset)
+    } else {
+        // Long expired either way
+        Some(UNIX_EPOCH.checked_sub(offset).unwrap_or(UNIX_EPOCH))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{Header, Properties};
+
+    fn message(absolute_expiry_time: Option<i64>, ttl: Option<u32>) -> Message {
+        let mut header = Header::new();
+        header.ttl = ttl;
+        let mut properties = Properties::new();
+        properties.absolute_expiry_time = absolute_expiry_time;
+        Message::builder().header(header).properties(properties).build()
+    }
+
+    #[test]
+    fn test_expiry_allows_skew_only_for_absolute_time() {
+        let start = UNIX_EPOCH + Duration::from_secs(100);
+        let clock = ManualClock::new(start);
+        let policy = ExpiryPolicy::new(clock.clone()).clock_skew(Duration::from_secs(10));
+
+        let absolute = message(Some(100_000), None);
+        let ttl = message(None, Some(5_000));
+        let both = message(Some(100_000), Some(60_000));
+        assert!(policy.expires_at(&message(None, None), start).is_none());
+        assert!(policy.is_expired(&message(Some(i64::MIN), None), start));
+        assert_eq!(policy.expires_at(&both, start), Some(start + Duration::from_secs(10)));
+
+        clock.advance(Duration::from_secs(5));
+        assert!(!policy.is_expired(&absolute, start));
+        assert!(policy.is_expired(&ttl, start));
+
+        clock.advance(Duration::from_secs(5));
+        assert!(policy.is_expired(&absolute, start));
+        assert!(policy.is_expired(&both, start));
+    }
+
+    #[test]
+    fn test_refresh_ahead_of_token_expiry() {
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_000));
+        let policy = ExpiryPolicy::new(clock.clone()).clock_skew(Duration::from_secs(60));
+        let expires_at = clock.now() + Duration::from_secs(300);
+
+        assert_eq!(policy.refresh_at(expires_at), expires_at - Duration::from_secs(60));
+        assert_eq!(policy.refresh_in(expires_at), Duration::from_secs(240));
+
+        // Inside the allowance, or past the expiry: refresh right away
+        clock.advance(Duration::from_secs(270));
+        assert_eq!(policy.refresh_in(expires_at), Duration::ZERO);
+        clock.set(expires_at + Duration::from_secs(1));
+        assert_eq!(policy.refresh_at(expires_at), clock.now());
+
+        let policy = ExpiryPolicy::new(clock.clone()).clock_skew(Duration::from_secs(u64::MAX / 4));
+        assert_eq!(policy.refresh_at(expires_at), clock.now());
+    }
+}
@@ -0,0 +1,369 @@
+//! AMQP 1.0 Failover Lists
+//!
+//! This module connects to the first reachable peer of a list of endpoints,
+//! such as the brokers of a cluster or a primary and its disaster-recovery
+//! site. Each [`Endpoint`] can use its own port, TLS setting and SASL
+//! credentials on top of a shared [`NetworkConfig`].
+//!
+//! Endpoints are attempted in list order, happy-eyeballs style: the next
+//! attempt starts once the previous one fails or has been running for the
+//! attempt delay, with at most a bounded number of attempts in flight. The
+//! first connection to complete the AMQP handshake wins and the attempts
+//! still running are dropped. If none succeeds, the error is
+//! [`AmqpError::RetriesExhausted`], listing in list order every endpoint
+//! with what it failed with and how long that took.
+//!
+//! An endpoint marked for TLS is connected through the TLS connector of the
+//! base configuration, which needs the `tls` feature; without one, its
+//! attempt fails right away and the list moves on to the others. The other
+//! endpoints are connected over plain TCP.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use dumq_amqp::failover::{Endpoint, Failover};
+//! use dumq_amqp::network::NetworkConfig;
+//! use dumq_amqp::sasl::SaslCredentials;
+//! use tokio::time::Duration;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let failover = Failover::new(NetworkConfig::default())
+//!     .endpoint(Endpoint::new("broker-1.example.com", 5672))
+//!     .endpoint(Endpoint::new("broker-2.example.com", 5672))
+//!     .endpoint(Endpoint::new("dr.example.com", 15672).sasl(SaslCredentials::plain("app", "secret")))
+//!     .max_parallel(2)
+//!     .attempt_delay(Duration::from_millis(250));
+//!
+//! let connected = failover.connect().await?;
+//! println!("Connected to {}", connected.endpoint);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::logging;
+use crate::network::{NetworkConfig, NetworkConnection};
+use crate::sasl::SaslCredentials;
+use crate::{AmqpError, AmqpResult};
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::fmt;
+use tokio::time::{sleep, Duration, Instant};
+
+/// One peer of a failover list, with the settings that differ from the base
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    /// Remote hostname
+    pub hostname: String,
+    /// Remote port
+    pub port: u16,
+    /// Whether the peer expects TLS
+    pub tls: bool,
+    /// Credentials for this peer, instead of those of the base configuration
+    pub sasl: Option<SaslCredentials>,
+}
+
+impl Endpoint {
+    /// Create a plain TCP endpoint using the base credentials
+    pub fn new(hostname: impl Into<String>, port: u16) -> Self {
+        Endpoint {
+            hostname: hostname.into(),
+            port,
+            tls: false,
+            sasl: None,
+        }
+    }
+
+    /// Mark the peer as expecting TLS, through the base configuration's connector
+    pub fn tls(mut self) -> Self {
+        self.tls = true;
+        self
+    }
+
+    /// Authenticate with these credentials instead of the base ones
+    pub fn sasl(mut self, credentials: SaslCredentials) -> Self {
+        self.sasl = Some(credentials);
+        self
+    }
+
+    /// Get the configuration for connecting to this endpoint
+    pub fn config(&self, base: &NetworkConfig) -> NetworkConfig {
+        let mut config = base.clone();
+        config.hostname = self.hostname.clone();
+        config.port = self.port;
+        if let Some(sasl) = &self.sasl {
+            config.sasl = Some(sasl.clone());
+        }
+        #[cfg(feature = "tls")]
+        if !self.tls {
+            config.tls = None;
+        }
+        config
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = if self.tls { "amqps" } else { "amqp" };
+        write!(f, "{}://{}:{}", scheme, self.hostname, self.port)
+    }
+}
+
+/// A connection established through a failover list
+pub struct FailoverConnection {
+    /// The endpoint connected to
+    pub endpoint: Endpoint,
+    /// Position of the endpoint in the list
+    pub index: usize,
+    /// The connection, with the protocol negotiated
+    pub connection: NetworkConnection,
+}
+
+/// A list of endpoints to connect to the first reachable one of
+#[derive(Debug, Clone)]
+pub struct Failover {
+    base: NetworkConfig,
+    endpoints: Vec<Endpoint>,
+    max_parallel: usize,
+    attempt_delay: Duration,
+}
+
+impl Failover {
+    /// Create an empty list whose endpoints share a base configuration
+    pub fn new(base: NetworkConfig) -> Self {
+        Failover {
+            base,
+            endpoints: Vec::new(),
+            max_parallel: 2,
+            attempt_delay: Duration::from_millis(250),
+        }
+    }
+
+    /// Add an endpoint at the end of the list
+    pub fn endpoint(mut self, endpoint: Endpoint) -> Self {
+        self.endpoints.push(endpoint);
+        self
+    }
+
+    /// Set the number of attempts in flight at once, at least 1
+    pub fn max_parallel(mut self, max_parallel: usize) -> Self {
+        self.max_parallel = max_parallel.max(1);
+        self
+    }
+
+    /// Set how long an attempt runs before the next one starts alongside it
+    pub fn attempt_delay(mut self, attempt_delay: Duration) -> Self {
+        self.attempt_delay = attempt_delay;
+        self
+    }
+
+    /// Get the endpoints in list order
+    pub fn endpoints(&self) -> &[Endpoint] {
+        &self.endpoints
+    }
+
+    /// Connect to the first endpoint that completes the AMQP handshake
+    ///
+    /// Each attempt connects and negotiates the protocol within the
+    /// timeout of its configuration.
+    pub async fn connect(&self) -> AmqpResult<FailoverConnection> {
+        if self.endpoints.is_empty() {
+            return Err(AmqpError::connection("Failover list has no endpoints"));
+        }
+
+        let mut pending = self.endpoints.iter().enumerate();
+        let mut running = FuturesUnordered::new();
+        let mut failures = Vec::new();
+        loop {
+            if running.len() < self.max_parallel {
+                if let Some((index, endpoint)) = pending.next() {
+                    running.push(self.attempt(index, endpoint));
+                }
+            }
+            let start_another = pending.len() > 0 && running.len() < self.max_parallel;
+
+            tokio::select! {
+                Some(result) = running.next() => match result {
+                    Ok(connected) => return Ok(connected),
+                    Err(failure) => failures.push(failure),
+                },
+                _ = sleep(self.attempt_delay), if start_another => {}
+                else => break,
+            }
+        }
+
+        failures.sort_by_key(|(index, _)| *index);
+        Err(AmqpError::retries_exhausted(failures.into_iter().map(|(_, failure)| failure).collect()))
+    }
+
+    /// Connect to one endpoint, describing the failure if it does not succeed
+    async fn attempt(&self, index: usize, endpoint: &Endpoint) -> Result<FailoverConnection, (usize, String)> {
+        let started = Instant::now();
+        let result = if endpoint.tls && !has_tls(&self.base) {
+            Err(AmqpError::tls(endpoint.to_string(), "No TLS connector is configured"))
+        } else {
+            let mut connection = NetworkConnection::new(endpoint.config(&self.base));
+            match connection.connect().await {
+                Ok(()) => connection.negotiate_protocol().await.map(|()| connection),
+                Err(e) => Err(e),
+            }
+        };
+
+        match result {
+            Ok(connection) => {
+                logging::debug!("Connected to failover endpoint {} after {:?}", endpoint, started.elapsed());
+                Ok(FailoverConnection { endpoint: endpoint.clone(), index, connection })
+            }
+            Err(e) => {
+                let mechanism = endpoint
+                    .config(&self.base)
+                    .sasl
+                    .map_or_else(String::new, |sasl| format!(" with SASL {}", sasl.mechanism()));
+                let failure = format!("{}{}: {} after {:?}", endpoint, mechanism, e, started.elapsed());
+                logging::warn!("Failover attempt failed: {}", failure);
+                Err((index, failure))
+            }
+        }
+    }
+}
+
+/// Whether connections with a configuration can use TLS
+fn has_tls(config: &NetworkConfig) -> bool {
+    #[cfg(feature = "tls")]
+    {
+        config.tls.is_some()
+    }
+    #[cfg(not(feature = "tls"))]
+    {
+        let _ = config;
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::performative::Open;
+    use crate::transport::{Frame, FrameHeader, FrameType, ProtocolHeader, Transport};
+    use tokio::net::TcpListener;
+
+    /// Accept one connection and answer its handshake after a delay
+    async fn peer(delay: Duration) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            let mut server = Transport::new(stream);
+            sleep(delay).await;
+            // The client may have given up on this peer by now
+            server.receive_raw(8).await?;
+            server.send_raw(ProtocolHeader::AMQP.as_bytes()).await?;
+            server.receive_frame().await?;
+            let payload = Open { container_id: format!("broker-{}", port), ..Default::default() }.encode()?;
+            let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, 0);
+            server.send_frame(Frame::new(header, payload)).await?;
+            sleep(Duration::from_secs(5)).await;
+            AmqpResult::Ok(())
+        });
+        port
+    }
+
+    /// Accept one TLS connection and answer its handshake
+    #[cfg(feature = "tls")]
+    async fn tls_peer() -> u16 {
+        let acceptor = crate::tls::TlsAcceptorBuilder::new(
+            include_str!("../testdata/tls/server.pem"),
+            include_str!("../testdata/tls/server.key"),
+        )
+        .build()
+        .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            let (stream, _) = acceptor.accept(stream).await?;
+            let mut server = Transport::new(stream);
+            server.receive_raw(8).await?;
+            server.send_raw(ProtocolHeader::AMQP.as_bytes()).await?;
+            server.receive_frame().await?;
+            let payload = Open { container_id: format!("tls-broker-{}", port), ..Default::default() }.encode()?;
+            let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, 0);
+            server.send_frame(Frame::new(header, payload)).await?;
+            sleep(Duration::from_secs(5)).await;
+            AmqpResult::Ok(())
+        });
+        port
+    }
+
+    async fn closed_port() -> u16 {
+        TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port()
+    }
+
+    fn base() -> NetworkConfig {
+        NetworkConfig {
+            timeout: Duration::from_secs(2),
+            keep_alive_disabled: true,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_endpoint_overtaken_by_the_next() {
+        let refused = closed_port().await;
+        let slow = peer(Duration::from_secs(1)).await;
+        let fast = peer(Duration::ZERO).await;
+        let failover = Failover::new(base())
+            .endpoint(Endpoint::new("127.0.0.1", refused))
+            .endpoint(Endpoint::new("127.0.0.1", 5671).tls())
+            .endpoint(Endpoint::new("127.0.0.1", slow))
+            .endpoint(Endpoint::new("127.0.0.1", fast))
+            .max_parallel(2)
+            .attempt_delay(Duration::from_millis(50));
+
+        let started = Instant::now();
+        let connected = failover.connect().await.unwrap();
+        assert_eq!(connected.index, 3);
+        assert_eq!(connected.connection.remote_container_id(), Some(format!("broker-{}", fast).as_str()));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn test_tls_endpoint_connects_through_base_connector() {
+        let connector = crate::tls::TlsConnectorBuilder::new(include_str!("../testdata/tls/ca.pem")).build().unwrap();
+        let base = NetworkConfig { tls: Some(connector), ..base() };
+        let refused = closed_port().await;
+        let secured = tls_peer().await;
+        let failover = Failover::new(base.clone())
+            .endpoint(Endpoint::new("127.0.0.1", refused).tls())
+            .endpoint(Endpoint::new("localhost", secured).tls());
+
+        let connected = failover.connect().await.unwrap();
+        assert_eq!(connected.index, 1);
+        assert_eq!(connected.connection.remote_container_id(), Some(format!("tls-broker-{}", secured).as_str()));
+
+        // Endpoints not marked for TLS stay on plain TCP
+        let plain = peer(Duration::ZERO).await;
+        let connected = Failover::new(base).endpoint(Endpoint::new("127.0.0.1", plain)).connect().await.unwrap();
+        assert_eq!(connected.connection.remote_container_id(), Some(format!("broker-{}", plain).as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_every_attempt_reported_in_list_order() {
+        let refused = closed_port().await;
+        let failover = Failover::new(base())
+            .endpoint(Endpoint::new("127.0.0.1", refused).sasl(SaslCredentials::plain("app", "secret")))
+            .endpoint(Endpoint::new("127.0.0.1", 5671).tls())
+            .max_parallel(4);
+
+        match failover.connect().await {
+            Err(AmqpError::RetriesExhausted { attempts }) => {
+                assert_eq!(attempts.len(), 2);
+                assert!(attempts[0].starts_with(&format!("amqp://127.0.0.1:{} with SASL PLAIN: ", refused)));
+                assert!(attempts[1].starts_with("amqps://127.0.0.1:5671: TLS"));
+            }
+            Err(other) => panic!("unexpected error: {:?}", other),
+            Ok(connected) => panic!("connected to {}", connected.endpoint),
+        }
+        assert!(Failover::new(base()).connect().await.is_err());
+    }
+}
@@ -0,0 +1,240 @@
+//! AMQP 1.0 Heartbeat Monitoring
+//!
+//! This module tracks heartbeat traffic on a connection: the heartbeats we
+//! send, the frames seen from the peer and the round-trip between the two.
+//! The monitor is ticked once per heartbeat interval; every interval in which
+//! the peer sent nothing counts as missed, and once the configured number of
+//! consecutive intervals is missed a [`HeartbeatEvent::Missed`] is emitted so
+//! monitoring can react before the idle timeout closes the connection.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::heartbeat::{HeartbeatEvent, HeartbeatMonitor};
+//!
+//! let monitor = HeartbeatMonitor::new(2);
+//! let mut events = monitor.subscribe();
+//!
+//! monitor.tick();
+//! monitor.tick();
+//! assert!(matches!(events.try_recv(), Ok(HeartbeatEvent::Missed { consecutive: 2, .. })));
+//!
+//! monitor.record_peer_frame();
+//! assert!(matches!(events.try_recv(), Ok(HeartbeatEvent::Recovered { missed: 2 })));
+//! assert_eq!(monitor.stats().consecutive_missed, 0);
+//! ```
+
+use crate::logging;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// Capacity of the heartbeat event channel
+const EVENT_CAPACITY: usize = 16;
+
+/// Heartbeat statistics for a connection
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HeartbeatStats {
+    /// Heartbeats sent to the peer
+    pub sent: u64,
+    /// Frames received from the peer
+    pub peer_frames: u64,
+    /// Heartbeat intervals in which the peer sent nothing
+    pub missed: u64,
+    /// Intervals missed since the last peer frame
+    pub consecutive_missed: u32,
+    /// Time of the last heartbeat sent
+    pub last_sent: Option<Instant>,
+    /// Time of the last frame received from the peer
+    pub last_peer_frame: Option<Instant>,
+    /// Time from our last unanswered heartbeat to the next peer frame
+    pub last_round_trip: Option<Duration>,
+}
+
+/// Change in the health of the peer's heartbeats
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeartbeatEvent {
+    /// The peer has been silent for at least the configured number of intervals
+    Missed {
+        /// Consecutive intervals without a peer frame
+        consecutive: u32,
+        /// Time of the last frame received from the peer
+        last_peer_frame: Option<Instant>,
+    },
+    /// The peer sent a frame after a reported silence
+    Recovered {
+        /// Intervals missed before the peer recovered
+        missed: u32,
+    },
+    /// The peer was silent for longer than our idle timeout, so the connection was closed
+    TimedOut {
+        /// Time since the last frame received from the peer
+        idle: Duration,
+    },
+}
+
+#[derive(Debug, Default)]
+struct MonitorState {
+    stats: HeartbeatStats,
+    seen_since_tick: bool,
+    awaiting_reply: bool,
+}
+
+/// Heartbeat tracker shared between a connection and its keep-alive task
+#[derive(Debug, Clone)]
+pub struct HeartbeatMonitor {
+    state: Arc<Mutex<MonitorState>>,
+    events: broadcast::Sender<HeartbeatEvent>,
+    threshold: u32,
+}
+
+impl HeartbeatMonitor {
+    /// Create a monitor reporting after `threshold` consecutive missed intervals
+    pub fn new(threshold: u32) -> Self {
+        HeartbeatMonitor {
+            state: Arc::new(Mutex::new(MonitorState::default())),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            threshold: threshold.max(1),
+        }
+    }
+
+    /// Record a heartbeat sent to the peer
+    pub fn record_sent(&self) {
+        let mut state = self.lock();
+        state.stats.sent += 1;
+        state.stats.last_sent = Some(Instant::now());
+        state.awaiting_reply = true;
+    }
+
+    /// Record a frame received from the peer
+    pub fn record_peer_frame(&self) {
+        let now = Instant::now();
+        let mut state = self.lock();
+        state.stats.peer_frames += 1;
+        state.stats.last_peer_frame = Some(now);
+        state.seen_since_tick = true;
+
+        if std::mem::take(&mut state.awaiting_reply) {
+            state.stats.last_round_trip = state.stats.last_sent.map(|sent| now.duration_since(sent));
+        }
+        let missed = std::mem::take(&mut state.stats.consecutive_missed);
+        if missed >= self.threshold {
+            let _ = self.events.send(HeartbeatEvent::Recovered { missed });
+        }
+    }
+
+    /// Close a heartbeat interval, counting it as missed if the peer was silent
+    pub fn tick(&self) {
+        let mut state = self.lock();
+        if std::mem::take(&mut state.seen_since_tick) {
+            return;
+        }
+
+        state.stats.missed += 1;
+        state.stats.consecutive_missed += 1;
+        if state.stats.consecutive_missed >= self.threshold {
+            logging::warn!(
+                "Peer silent for {} heartbeat intervals",
+                state.stats.consecutive_missed
+            );
+            let _ = self.events.send(HeartbeatEvent::Missed {
+                consecutive: state.stats.consecutive_missed,
+                last_peer_frame: state.stats.last_peer_frame,
+            });
+        }
+    }
+
+    /// Record closing the connection because the peer went silent
+    pub fn record_timeout(&self, idle: Duration) {
+        logging::warn!("Peer silent for {:?}, beyond the idle timeout", idle);
+        let _ = self.events.send(HeartbeatEvent::TimedOut { idle });
+    }
+
+    /// Get the time since the last frame received from the peer
+    ///
+    /// Zero until the peer has sent a frame.
+    pub fn peer_silence(&self) -> Duration {
+        self.lock().stats.last_peer_frame.map_or(Duration::ZERO, |last| last.elapsed())
+    }
+
+    /// Get a snapshot of the statistics
+    pub fn stats(&self) -> HeartbeatStats {
+        self.lock().stats
+    }
+
+    /// Subscribe to heartbeat events
+    pub fn subscribe(&self) -> broadcast::Receiver<HeartbeatEvent> {
+        self.events.subscribe()
+    }
+
+    /// Get the number of missed intervals before reporting
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MonitorState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for HeartbeatMonitor {
+    fn default() -> Self {
+        Self::new(2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tick_after_peer_frame_is_not_missed() {
+        let monitor = HeartbeatMonitor::new(1);
+        let mut events = monitor.subscribe();
+
+        monitor.record_peer_frame();
+        monitor.tick();
+        assert_eq!(monitor.stats().missed, 0);
+        assert!(events.try_recv().is_err());
+
+        monitor.tick();
+        assert_eq!(monitor.stats().missed, 1);
+        assert!(matches!(events.try_recv(), Ok(HeartbeatEvent::Missed { consecutive: 1, .. })));
+    }
+
+    #[test]
+    fn test_missed_reported_at_threshold() {
+        let monitor = HeartbeatMonitor::new(3);
+        let mut events = monitor.subscribe();
+
+        monitor.tick();
+        monitor.tick();
+        assert!(events.try_recv().is_err());
+        monitor.tick();
+        monitor.tick();
+        assert!(matches!(events.try_recv(), Ok(HeartbeatEvent::Missed { consecutive: 3, .. })));
+        assert!(matches!(events.try_recv(), Ok(HeartbeatEvent::Missed { consecutive: 4, .. })));
+
+        let stats = monitor.stats();
+        assert_eq!(stats.missed, 4);
+        assert_eq!(stats.consecutive_missed, 4);
+    }
+
+    #[test]
+    fn test_round_trip_and_recovery_below_threshold() {
+        let monitor = HeartbeatMonitor::new(5);
+        let mut events = monitor.subscribe();
+
+        monitor.record_sent();
+        monitor.tick();
+        monitor.record_peer_frame();
+
+        let stats = monitor.stats();
+        assert_eq!(stats.sent, 1);
+        assert_eq!(stats.peer_frames, 1);
+        assert!(stats.last_round_trip.is_some());
+        assert_eq!(stats.consecutive_missed, 0);
+        // Silence below the threshold was never reported, so no recovery either
+        assert!(events.try_recv().is_err());
+    }
+}
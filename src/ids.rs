@@ -0,0 +1,329 @@
+//! AMQP 1.0 Identifier Generation
+//!
+//! This module generates the identifiers the crate assigns on its own:
+//! container-ids, connection ids, and session, link and relay names. The
+//! generator is pluggable, so deployments can use their own naming scheme
+//! and builds without the `uuid` feature still get unique ids. The default,
+//! [`RandomIds`], produces random UUID-formatted strings; [`TimeOrderedIds`]
+//! produces version 7 UUIDs, which sort by the time they were generated.
+//!
+//! The same choice applies to the message-ids senders generate, through
+//! [`MessageIdFormat`], whose version 7 also tags their deliveries, and
+//! [`uuid_v7_timestamp`] recovers the creation time from a received
+//! version 7 id or tag.
+//!
+//! Session and link names are meant to be read in broker management UIs, so
+//! they come from a [`Namer`] instead: a prefix, a counter and a short id of
+//! the scope, e.g. `orders-sender-3-1a2b3c4d`. Each connection names its
+//! sessions and links with its own namer, and names are unique within it.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::ids::{self, SequentialIds};
+//! use std::sync::Arc;
+//!
+//! ids::install(Arc::new(SequentialIds::new("edge-7")));
+//! assert!(ids::next_id().starts_with("edge-7-"));
+//! ```
+
+use crate::types::Uuid;
+use rand::Rng;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Source of identifiers for containers, connections, sessions and links
+pub trait IdGenerator: Send + Sync + fmt::Debug {
+    /// Generate a new identifier, unique within the process
+    fn next_id(&self) -> String;
+}
+
+/// Random identifiers formatted as version 4 UUIDs
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomIds;
+
+impl IdGenerator for RandomIds {
+    fn next_id(&self) -> String {
+        format_uuid(&random_uuid_bytes())
+    }
+}
+
+/// Time-ordered identifiers formatted as version 7 UUIDs
+///
+/// Ids generated later in the process compare greater, also within the same
+/// millisecond.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeOrderedIds;
+
+impl IdGenerator for TimeOrderedIds {
+    fn next_id(&self) -> String {
+        format_uuid(&uuid_v7_bytes())
+    }
+}
+
+/// Kind of UUID a sender generates for message-ids
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MessageIdFormat {
+    /// Random version 4 UUIDs
+    #[default]
+    UuidV4,
+    /// Time-ordered version 7 UUIDs
+    UuidV7,
+}
+
+impl MessageIdFormat {
+    /// Generate a UUID in this format
+    pub fn generate(self) -> Uuid {
+        match self {
+            MessageIdFormat::UuidV4 => Uuid::new_v4(),
+            MessageIdFormat::UuidV7 => uuid_v7(),
+        }
+    }
+}
+
+/// Generate a version 7 UUID
+pub fn uuid_v7() -> Uuid {
+    Uuid::from_bytes(uuid_v7_bytes())
+}
+
+/// Get the time a version 7 UUID was generated, to the millisecond
+///
+/// Returns `None` for UUIDs of other versions.
+pub fn uuid_v7_timestamp(uuid: &Uuid) -> Option<SystemTime> {
+    v7_timestamp(uuid.as_bytes())
+}
+
+/// Identifiers made of a prefix and a counter, e.g. `node-1`, `node-2`
+#[derive(Debug)]
+pub struct SequentialIds {
+    prefix: String,
+    next: AtomicU64,
+}
+
+impl SequentialIds {
+    /// Create a generator numbering from 1 under a prefix
+    pub fn new(prefix: impl Into<String>) -> Self {
+        SequentialIds {
+            prefix: prefix.into(),
+            next: AtomicU64::new(1),
+        }
+    }
+}
+
+impl IdGenerator for SequentialIds {
+    fn next_id(&self) -> String {
+        format!("{}-{}", self.prefix, self.next.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Human-readable names for sessions and links, unique within a scope
+///
+/// A name is a prefix, a counter and the scope's short id, e.g.
+/// `session-1-1a2b3c4d`. Clones share the counter, so every name handed out
+/// for one connection is distinct, and the short id tells connections apart.
+#[derive(Debug, Clone)]
+pub struct Namer {
+    scope: String,
+    next: Arc<AtomicU64>,
+}
+
+impl Namer {
+    /// Create a namer for a new scope, identified by a generated short id
+    pub fn new() -> Self {
+        Namer::scoped(next_short_id())
+    }
+
+    /// Create a namer for a scope with a given id
+    pub fn scoped(scope: impl Into<String>) -> Self {
+        Namer {
+            scope: scope.into(),
+            next: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    /// Get the id of the scope
+    pub fn scope(&self) -> &str {
+        &self.scope
+    }
+
+    /// Generate the next name under a prefix
+    pub fn name(&self, prefix: &str) -> String {
+        format!("{}-{}-{}", prefix, self.next.fetch_add(1, Ordering::Relaxed), self.scope)
+    }
+
+    /// Check if this namer generated a name under a prefix
+    pub fn named(&self, prefix: &str, name: &str) -> bool {
+        name.strip_prefix(prefix)
+            .and_then(|rest| rest.strip_prefix('-'))
+            .and_then(|rest| rest.strip_suffix(self.scope.as_str()))
+            .and_then(|counter| counter.strip_suffix('-'))
+            .is_some_and(|counter| !counter.is_empty() && counter.bytes().all(|b| b.is_ascii_digit()))
+    }
+}
+
+impl Default for Namer {
+    fn default() -> Self {
+        Namer::new()
+    }
+}
+
+/// Namer for sessions and links configured outside any connection
+pub(crate) fn default_namer() -> &'static Namer {
+    static NAMER: OnceLock<Namer> = OnceLock::new();
+    NAMER.get_or_init(Namer::new)
+}
+
+fn generator() -> &'static RwLock<Arc<dyn IdGenerator>> {
+    static GENERATOR: OnceLock<RwLock<Arc<dyn IdGenerator>>> = OnceLock::new();
+    GENERATOR.get_or_init(|| RwLock::new(Arc::new(RandomIds)))
+}
+
+/// Install the process-wide generator
+pub fn install(generator_impl: Arc<dyn IdGenerator>) {
+    *generator().write().unwrap_or_else(|poisoned| poisoned.into_inner()) = generator_impl;
+}
+
+/// Generate an identifier with the installed generator
+pub fn next_id() -> String {
+    let current = generator().read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+    current.next_id()
+}
+
+/// Generate a short identifier for log-friendly names such as `conn-1a2b3c4d`
+///
+/// Only random ids are shortened; ids from other generators are kept whole
+/// since their uniqueness may not survive truncation.
+pub(crate) fn next_short_id() -> String {
+    let id = next_id();
+    match id.get(..8) {
+        Some(short) if id.len() == 36 && id.as_bytes()[8] == b'-' => short.to_string(),
+        _ => id,
+    }
+}
+
+/// Random bytes with the version 4 and variant bits of a UUID set
+pub(crate) fn random_uuid_bytes() -> [u8; 16] {
+    let mut bytes: [u8; 16] = rand::thread_rng().gen();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    bytes
+}
+
+/// Bytes of a version 7 UUID: 48 bits of Unix milliseconds, a 12-bit counter
+/// that keeps ids within one millisecond in order, and 62 random bits
+pub(crate) fn uuid_v7_bytes() -> [u8; 16] {
+    // Last millisecond used and the counter within it
+    static LAST: Mutex<(u64, u16)> = Mutex::new((0, 0));
+
+    let mut rng = rand::thread_rng();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+    let (millis, counter) = {
+        let mut last = LAST.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        *last = if now > last.0 {
+            // Start low in the counter's range so the millisecond has room
+            (now, rng.gen_range(0..0x400))
+        } else if last.1 < 0xfff {
+            (last.0, last.1 + 1)
+        } else {
+            // Counter exhausted or the clock went back: borrow the next millisecond
+            (last.0 + 1, 0)
+        };
+        *last
+    };
+
+    let mut bytes: [u8; 16] = rng.gen();
+    bytes[..6].copy_from_slice(&millis.to_be_bytes()[2..]);
+    bytes[6] = 0x70 | (counter >> 8) as u8;
+    bytes[7] = counter as u8;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    bytes
+}
+
+fn v7_timestamp(bytes: &[u8; 16]) -> Option<SystemTime> {
+    if bytes[6] >> 4 != 7 {
+        return None;
+    }
+    let mut millis = [0; 8];
+    millis[2..].copy_from_slice(&bytes[..6]);
+    UNIX_EPOCH.checked_add(Duration::from_millis(u64::from_be_bytes(millis)))
+}
+
+/// Get the time a version 7 UUID in hyphenated form was generated
+pub(crate) fn uuid_v7_str_timestamp(id: &str) -> Option<SystemTime> {
+    let hex: String = id.chars().filter(|c| *c != '-').collect();
+    if id.len() != 36 || hex.len() != 32 {
+        return None;
+    }
+    let value = u128::from_str_radix(&hex, 16).ok()?;
+    v7_timestamp(&value.to_be_bytes())
+}
+
+/// Format 16 bytes in the hyphenated UUID layout
+pub(crate) fn format_uuid(bytes: &[u8; 16]) -> String {
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_ids_are_uuid_v4() {
+        let id = RandomIds.next_id();
+        assert_eq!(id.len(), 36);
+        assert_eq!(id.as_bytes()[14], b'4');
+        assert!(matches!(id.as_bytes()[19], b'8' | b'9' | b'a' | b'b'));
+        assert_ne!(id, RandomIds.next_id());
+    }
+
+    #[test]
+    fn test_sequential_ids() {
+        let ids = SequentialIds::new("node");
+        assert_eq!(ids.next_id(), "node-1");
+        assert_eq!(ids.next_id(), "node-2");
+    }
+
+    #[test]
+    fn test_namer_names_are_readable_and_unique_per_scope() {
+        let namer = Namer::scoped("1a2b3c4d");
+        let shared = namer.clone();
+        assert_eq!(namer.name("session"), "session-1-1a2b3c4d");
+        assert_eq!(shared.name("orders-sender"), "orders-sender-2-1a2b3c4d");
+
+        assert!(namer.named("session", "session-1-1a2b3c4d"));
+        assert!(!namer.named("session", "session-1-ffffffff"));
+        assert!(!namer.named("session", "session--1a2b3c4d"));
+        assert!(!namer.named("link", "session-1-1a2b3c4d"));
+        assert_ne!(Namer::new().scope(), Namer::new().scope());
+    }
+
+    #[test]
+    fn test_uuid_v7_is_time_ordered() {
+        let before = SystemTime::now() - Duration::from_millis(1);
+        let ids: Vec<Uuid> = (0..1000).map(|_| uuid_v7()).collect();
+        assert!(ids.windows(2).all(|pair| pair[0].as_u128() < pair[1].as_u128()));
+
+        let id = ids[0];
+        assert_eq!(id.as_bytes()[6] >> 4, 7);
+        assert_eq!(id.as_bytes()[8] >> 6, 0b10);
+        let created = uuid_v7_timestamp(&id).unwrap();
+        assert!(created >= before && created <= SystemTime::now());
+        assert_eq!(uuid_v7_str_timestamp(&id.to_string()), Some(created));
+
+        assert_eq!(uuid_v7_timestamp(&Uuid::new_v4()), None);
+        assert_eq!(uuid_v7_str_timestamp("not-a-uuid"), None);
+
+        let names = TimeOrderedIds.next_id();
+        assert_eq!(names.as_bytes()[14], b'7');
+        assert!(names < TimeOrderedIds.next_id());
+    }
+
+    #[test]
+    fn test_format_uuid() {
+        let bytes = [0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0, 0, 1, 2, 3, 4, 5, 6, 7];
+        assert_eq!(format_uuid(&bytes), "12345678-9abc-def0-0001-020304050607");
+    }
+}
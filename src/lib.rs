@@ -14,7 +14,7 @@
 //!
 //! # Quick Start
 //!
-//! ```rust
+//! ```rust,no_run
 //! use dumq_amqp::prelude::*;
 //! use tokio::time::Duration;
 //!
@@ -32,7 +32,7 @@
 //!     connection.open().await?;
 //!
 //!     // Create a session
-//!     let mut session = connection.create_session().await?;
+//!     let mut session = SessionBuilder::new().build(1, connection.id().to_string());
 //!     session.begin().await?;
 //!
 //!     // Create a sender
@@ -46,7 +46,7 @@
 //!
 //!     // Send a message
 //!     let message = Message::text("Hello, AMQP!");
-//!     let delivery_id = sender.send(message).await?;
+//!     let delivery_id = sender.send(message).await?.delivery_id();
 //!     println!("Message sent with delivery ID: {}", delivery_id);
 //!
 //!     // Clean up
@@ -72,8 +72,8 @@
 //!     AmqpValue::Int(42),
 //!     AmqpValue::Boolean(true),
 //!     AmqpValue::Double(3.14159),
-//!     AmqpValue::Uuid(uuid::Uuid::new_v4()),
-//!     AmqpValue::Binary(vec![1, 2, 3, 4]),
+//!     AmqpValue::Uuid(dumq_amqp::types::Uuid::new_v4()),
+//!     AmqpValue::Binary(vec![1, 2, 3, 4].into()),
 //! ];
 //! ```
 //!
@@ -82,7 +82,8 @@
 //! Create and manipulate AMQP messages:
 //!
 //! ```rust
-//! use dumq_amqp::message::Message;
+//! use dumq_amqp::message::{self, Message};
+//! use dumq_amqp::types::{AmqpValue, AmqpSymbol};
 //!
 //! // Simple text message
 //! let text_msg = Message::text("Hello, World!");
@@ -105,7 +106,9 @@
 //!
 //! ```rust
 //! use dumq_amqp::codec::{Encoder, Decoder};
+//! use dumq_amqp::types::AmqpValue;
 //!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
 //! let value = AmqpValue::String("Hello, AMQP!".to_string());
 //!
 //! // Encode
@@ -117,6 +120,8 @@
 //! let mut decoder = Decoder::new(encoded);
 //! let decoded = decoder.decode_value()?;
 //! assert_eq!(value, decoded);
+//! # Ok(())
+//! # }
 //! ```
 //!
 //! # Architecture
@@ -126,8 +131,45 @@
 //! - **`connection`**: Connection management and lifecycle
 //! - **`session`**: Session handling and flow control
 //! - **`link`**: Sender and receiver link management
+//! - **`transaction`**: Declaring, committing and rolling back transactions with a coordinator
+//! - **`credit`**: Lock-free link credit and delivery counters shared across tasks
+//! - **`adaptive`**: Receiver credit windows sized from consumer throughput and round-trip time
+//! - **`address`**: Parsing and broker-specific rendering of node addresses
 //! - **`message`**: AMQP message structures and manipulation
+//! - **`expiry`**: Message and token expiry against a pluggable clock with skew allowance
 //! - **`types`**: AMQP value types and data structures
+//! - **`performative`**: Frame bodies for connection, session and link control
+//! - **`capability`**: Connection capabilities and sole-connection-for-container errors
+//! - **`integrity`**: Message footer checksums and signatures
+//! - **`relay`**: Hop counting and loop detection for router mode
+//! - **`amqp091`**: Mapping of message metadata to and from AMQP 0-9-1 basic properties for bridges
+//! - **`topology`**: Declared links across multiple connections with reconciliation
+//! - **`failover`**: Connecting to the first reachable of several endpoints with per-endpoint settings
+//! - **`blocking`**: Synchronous wrappers for code that cannot use async
+//! - **`selector`**: Selector filter evaluation over application properties
+//! - **`schema`**: Expected shapes of AMQP values and path-based validation against them
+//! - **`retry`**: Backoff policy for transient send failures
+//! - **`reconnect`**: Automatic reconnect with backoff, replaying sessions and links
+//! - **`memory`**: Byte budgets for buffered messages
+//! - **`metrics`**: Per-delivery timing and latency percentiles
+//! - **`tuning`**: Runtime knobs adjustable on a live connection
+//! - **`listener`**: Server-role support such as a connection-limited TCP listener, duplicate container-id detection and virtual host routing
+//! - **`server`**: Accepting inbound AMQP connections, with the sessions and links clients start on them
+//! - **`tls`**: TLS termination for accepted connections (`tls` feature)
+//! - **`compression`**: Negotiated LZ4 compression of transfer payloads (`experimental-compression` feature)
+//! - **`heartbeat`**: Heartbeat statistics and missed-heartbeat events
+//! - **`dispatch`**: Fair merging of deliveries from several receivers
+//! - **`serve`**: Tower services as message handlers (`tower` feature)
+//! - **`scheduler`**: Credit-respecting dispatch to the consumers of a queue in the server role
+//! - **`quota`**: Queue and connection limits a broker enforces on untrusted clients
+//! - **`dedup`**: Broker-side duplicate detection for idempotent publishing
+//! - **`spool`**: Store-and-forward of sends while disconnected, in memory or on disk
+//! - **`outbox`**: Sending from an application-owned outbox table without dual writes
+//! - **`ids`**: Pluggable generation of container, connection and link ids
+//! - **`sasl`**: SASL authentication before the AMQP protocol header
+//! - **`tasks`**: Names and a live snapshot of the background tasks the crate spawns
+//! - **`watchdog`**: Restarts or fails background tasks that panic or stall
+//! - **`testing`**: Fault-injecting transport proxy for soak tests and an in-process broker for integration tests
 //! - **`codec`**: Binary encoding and decoding
 //! - **`transport`**: Low-level transport layer
 //! - **`error`**: Comprehensive error handling
@@ -138,18 +180,62 @@ pub mod error;
 pub mod connection;
 pub mod session;
 pub mod link;
+pub mod transaction;
+pub mod credit;
+pub mod adaptive;
+pub mod address;
 pub mod message;
+pub mod expiry;
 pub mod codec;
 pub mod transport;
 pub mod network;
+pub mod performative;
+pub mod capability;
+pub mod integrity;
+pub mod relay;
+pub mod amqp091;
+pub mod topology;
+pub mod failover;
+pub mod blocking;
+pub mod selector;
+pub mod schema;
+pub mod retry;
+pub mod reconnect;
+pub mod memory;
+pub mod metrics;
+pub mod tuning;
+pub mod listener;
+pub mod server;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(feature = "experimental-compression")]
+pub mod compression;
+pub mod heartbeat;
+pub mod dispatch;
+pub mod demux;
+#[cfg(feature = "tower")]
+pub mod serve;
+pub mod scheduler;
+pub mod quota;
+pub mod dedup;
+pub mod outbox;
+pub mod spool;
+pub mod ids;
+pub mod sasl;
+pub mod tasks;
+pub mod watchdog;
+mod logging;
+#[cfg(test)]
+mod allocations;
+pub mod testing;
 
-pub use types::{AmqpValue, AmqpSymbol, AmqpList, AmqpMap, SenderSettleMode, ReceiverSettleMode, TerminusDurability, TerminusExpiryPolicy};
+pub use types::{AmqpValue, AmqpSymbol, AmqpList, AmqpMap, AnnotationKey, Annotations, Role, SenderSettleMode, ReceiverSettleMode, TerminusDurability, TerminusExpiryPolicy, DistributionMode};
 pub use condition::{AmqpCondition, AmqpErrorCondition, ConditionCategory};
 pub use message::{Message, MessageBuilder, Properties, Header, Body};
 pub use error::{AmqpError, AmqpResult};
 pub use connection::{Connection, ConnectionBuilder};
 pub use session::{Session, SessionBuilder};
-pub use link::{Link, LinkBuilder, Sender, Receiver};
+pub use link::{AttachOutcome, Link, LinkBuilder, Sender, Receiver};
 pub use network::{NetworkConnection, NetworkBuilder, NetworkConfig, NetworkState};
 
 /// Re-export commonly used types
@@ -175,8 +261,8 @@ mod tests {
         let string_value = AmqpValue::String("test".to_string());
         let int_value = AmqpValue::Int(42);
         let bool_value = AmqpValue::Boolean(true);
-        let double_value = AmqpValue::Double(3.14);
-        let uuid_value = AmqpValue::Uuid(uuid::Uuid::new_v4());
+        let double_value = AmqpValue::Double(2.5);
+        let uuid_value = AmqpValue::Uuid(types::Uuid::new_v4());
 
         assert!(matches!(string_value, AmqpValue::String(_)));
         assert!(matches!(int_value, AmqpValue::Int(_)));
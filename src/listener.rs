@@ -0,0 +1,955 @@
+//! AMQP 1.0 Listener Support
+//!
+//! This module provides building blocks for accepting connections in the
+//! server role. A [`ContainerRegistry`] tracks the container-id presented in
+//! each incoming Open; when a second connection presents an id that is
+//! already connected, the registry either rejects the newcomer or evicts the
+//! existing connection, according to its [`DuplicateContainerPolicy`]. Both
+//! outcomes use `amqp:connection:forced`, as brokers do. An
+//! [`AmqpListener`](crate::server::AmqpListener) checks the Open of each
+//! client against the registry given to
+//! [`container_registry`](crate::server::AmqpListener::container_registry).
+//!
+//! An [`Authorizer`] decides whether an [`IncomingConnection`] is admitted,
+//! based on its address, container-id and, for connections accepted through
+//! the `tls` feature's acceptor, the [`TlsIdentity`] the client presented.
+//!
+//! A [`VhostRouter`] lets one listener serve several tenants. It picks a
+//! [`VirtualHost`] by the hostname in the client's Open, falling back to the
+//! TLS server name, and admits the connection only if that virtual host's
+//! authorizer agrees and its [`VhostQuota`] has room. The virtual host
+//! carries the handler for its connections, e.g. a broker instance. An
+//! [`AmqpListener`](crate::server::AmqpListener) routes its connections
+//! through the router given to [`vhosts`](crate::server::AmqpListener::vhosts).
+//!
+//! A [`NetworkListener`] accepts the TCP connections themselves and keeps
+//! their number under a limit. Each accepted connection holds a
+//! [`ConnectionPermit`] until it is done. At the limit, the listener either
+//! stops accepting, leaving new clients in the kernel's accept queue, or
+//! accepts them only to close them right away with
+//! `amqp:resource-limit-exceeded`, according to its [`OverloadPolicy`].
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::listener::{ContainerRegistry, DuplicateContainerPolicy};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let registry = ContainerRegistry::new(DuplicateContainerPolicy::StealExisting);
+//!
+//! let first = registry.register("client-a").unwrap();
+//! let second = registry.register("client-a").unwrap();
+//!
+//! // The first connection is told to close with amqp:connection:forced
+//! first.evicted().await;
+//! assert!(first.is_evicted());
+//! assert!(!second.is_evicted());
+//! # }
+//! ```
+
+use crate::logging;
+use crate::performative::{Close, Open};
+use crate::transport::{Frame, FrameHeader, FrameType, ProtocolHeader, Transport};
+use crate::{types, AmqpCondition, AmqpError, AmqpResult};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::{watch, Notify};
+
+/// Time a refused client gets to send its protocol header
+const REFUSAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Refusals answered at once; clients beyond them are dropped unanswered
+const MAX_CONCURRENT_REFUSALS: usize = 64;
+
+/// What to do when a connection presents a container-id already in use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateContainerPolicy {
+    /// Refuse the new connection
+    #[default]
+    Reject,
+    /// Close the existing connection and admit the new one
+    StealExisting,
+}
+
+#[derive(Debug)]
+struct Holder {
+    token: u64,
+    evict: watch::Sender<bool>,
+}
+
+#[derive(Debug, Default)]
+struct Registered {
+    next_token: u64,
+    holders: HashMap<String, Holder>,
+}
+
+/// Registry of container-ids held by live connections
+#[derive(Debug, Clone)]
+pub struct ContainerRegistry {
+    policy: DuplicateContainerPolicy,
+    inner: Arc<Mutex<Registered>>,
+}
+
+impl ContainerRegistry {
+    /// Create a registry with a policy for duplicates
+    pub fn new(policy: DuplicateContainerPolicy) -> Self {
+        ContainerRegistry {
+            policy,
+            inner: Arc::new(Mutex::new(Registered::default())),
+        }
+    }
+
+    /// Register the container-id of an incoming connection
+    ///
+    /// The id stays registered until the returned lease is dropped. Under
+    /// [`DuplicateContainerPolicy::Reject`] a duplicate fails with
+    /// `amqp:connection:forced`; under
+    /// [`DuplicateContainerPolicy::StealExisting`] the existing lease is
+    /// evicted instead.
+    pub fn register(&self, container_id: impl Into<String>) -> AmqpResult<ContainerLease> {
+        let container_id = container_id.into();
+        let mut registered = self.lock();
+
+        if let Some(existing) = registered.holders.get(&container_id) {
+            match self.policy {
+                DuplicateContainerPolicy::Reject => {
+                    return Err(AmqpError::amqp_protocol(
+                        AmqpCondition::AmqpErrorConnectionForced,
+                        format!("Container '{}' is already connected", container_id),
+                    ));
+                }
+                DuplicateContainerPolicy::StealExisting => {
+                    logging::info!("Evicting existing connection for container '{}'", container_id);
+                    existing.evict.send_replace(true);
+                }
+            }
+        }
+
+        let token = registered.next_token;
+        registered.next_token += 1;
+        let (evict, evicted) = watch::channel(false);
+        registered.holders.insert(container_id.clone(), Holder { token, evict });
+
+        Ok(ContainerLease {
+            container_id,
+            token,
+            evicted,
+            registry: self.inner.clone(),
+        })
+    }
+
+    /// Check if a container-id is registered
+    pub fn contains(&self, container_id: &str) -> bool {
+        self.lock().holders.contains_key(container_id)
+    }
+
+    /// Get the number of registered container-ids
+    pub fn len(&self) -> usize {
+        self.lock().holders.len()
+    }
+
+    /// Check if no container-id is registered
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the duplicate policy
+    pub fn policy(&self) -> DuplicateContainerPolicy {
+        self.policy
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Registered> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for ContainerRegistry {
+    fn default() -> Self {
+        Self::new(DuplicateContainerPolicy::default())
+    }
+}
+
+/// A connection's claim on a container-id
+///
+/// Dropping the lease releases the id, unless it has since been taken over
+/// by a newer connection.
+#[derive(Debug)]
+pub struct ContainerLease {
+    container_id: String,
+    token: u64,
+    evicted: watch::Receiver<bool>,
+    registry: Arc<Mutex<Registered>>,
+}
+
+impl ContainerLease {
+    /// Get the container-id
+    pub fn container_id(&self) -> &str {
+        &self.container_id
+    }
+
+    /// Check if a newer connection took over the container-id
+    pub fn is_evicted(&self) -> bool {
+        *self.evicted.borrow()
+    }
+
+    /// Wait until a newer connection takes over the container-id
+    pub async fn evicted(&self) {
+        let mut evicted = self.evicted.clone();
+        // The sender lives in the registry entry, which is only replaced
+        // after it has been signalled
+        let _ = evicted.wait_for(|evicted| *evicted).await;
+    }
+
+    /// Get the error to close an evicted connection with
+    pub fn eviction_error(&self) -> AmqpError {
+        AmqpError::amqp_protocol(
+            AmqpCondition::AmqpErrorConnectionForced,
+            format!("Container '{}' connected again from another connection", self.container_id),
+        )
+    }
+}
+
+impl Drop for ContainerLease {
+    fn drop(&mut self) {
+        let mut registered = self.registry.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if registered
+            .holders
+            .get(&self.container_id)
+            .is_some_and(|holder| holder.token == self.token)
+        {
+            registered.holders.remove(&self.container_id);
+        }
+    }
+}
+
+/// TLS details of an accepted connection
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsIdentity {
+    /// Server name the client asked for (SNI)
+    pub server_name: Option<String>,
+    /// Application protocol agreed through ALPN
+    pub alpn_protocol: Option<Vec<u8>>,
+    /// DER certificates the client presented, its own first
+    pub peer_certificates: Vec<Vec<u8>>,
+}
+
+impl TlsIdentity {
+    /// Check if the client authenticated with a certificate
+    pub fn is_client_authenticated(&self) -> bool {
+        !self.peer_certificates.is_empty()
+    }
+
+    /// Get the client's own certificate, in DER
+    pub fn peer_certificate(&self) -> Option<&[u8]> {
+        self.peer_certificates.first().map(Vec::as_slice)
+    }
+}
+
+/// What is known about a connection when deciding to admit it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncomingConnection {
+    /// Address of the client
+    pub remote_addr: SocketAddr,
+    /// Container-id from the client's Open, once received
+    pub container_id: Option<String>,
+    /// Hostname from the client's Open, naming the virtual host
+    pub hostname: Option<String>,
+    /// TLS details, if the connection was accepted over TLS
+    pub tls: Option<TlsIdentity>,
+}
+
+impl IncomingConnection {
+    /// Describe a plain TCP connection
+    pub fn new(remote_addr: SocketAddr) -> Self {
+        IncomingConnection {
+            remote_addr,
+            container_id: None,
+            hostname: None,
+            tls: None,
+        }
+    }
+
+    /// Set the container-id from the client's Open
+    pub fn with_container_id(mut self, container_id: impl Into<String>) -> Self {
+        self.container_id = Some(container_id.into());
+        self
+    }
+
+    /// Set the hostname from the client's Open
+    pub fn with_hostname(mut self, hostname: impl Into<String>) -> Self {
+        self.hostname = Some(hostname.into());
+        self
+    }
+
+    /// Get the virtual host the client asked for
+    ///
+    /// The hostname from the Open, or else the TLS server name.
+    pub fn virtual_host(&self) -> Option<&str> {
+        self.hostname
+            .as_deref()
+            .or_else(|| self.tls.as_ref().and_then(|tls| tls.server_name.as_deref()))
+    }
+}
+
+/// Hook deciding whether an incoming connection is admitted
+///
+/// Refusals should use `amqp:unauthorized-access` so the client can tell them
+/// apart from other failures. Closures taking an [`IncomingConnection`]
+/// implement this trait.
+pub trait Authorizer: Send + Sync {
+    /// Admit or refuse a connection
+    fn authorize(&self, incoming: &IncomingConnection) -> AmqpResult<()>;
+}
+
+impl<F> Authorizer for F
+where
+    F: Fn(&IncomingConnection) -> AmqpResult<()> + Send + Sync,
+{
+    fn authorize(&self, incoming: &IncomingConnection) -> AmqpResult<()> {
+        self(incoming)
+    }
+}
+
+/// Limits on the connections of one virtual host
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VhostQuota {
+    /// Connections admitted at once; unlimited if unset
+    pub max_connections: Option<usize>,
+}
+
+/// A tenant served by a [`VhostRouter`]
+pub struct VirtualHost<H> {
+    name: String,
+    handler: Arc<H>,
+    authorizer: Option<Arc<dyn Authorizer>>,
+    quota: VhostQuota,
+    connections: Arc<AtomicUsize>,
+}
+
+impl<H> VirtualHost<H> {
+    /// Create a virtual host whose connections are handled by `handler`
+    pub fn new(name: impl Into<String>, handler: H) -> Self {
+        VirtualHost {
+            name: name.into(),
+            handler: Arc::new(handler),
+            authorizer: None,
+            quota: VhostQuota::default(),
+            connections: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Admit connections only if the authorizer agrees
+    pub fn authorizer(mut self, authorizer: impl Authorizer + 'static) -> Self {
+        self.authorizer = Some(Arc::new(authorizer));
+        self
+    }
+
+    /// Limit the connections of this virtual host
+    pub fn quota(mut self, quota: VhostQuota) -> Self {
+        self.quota = quota;
+        self
+    }
+
+    /// Get the name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the handler
+    pub fn handler(&self) -> &Arc<H> {
+        &self.handler
+    }
+
+    /// Get the number of connections admitted and not yet dropped
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::Acquire)
+    }
+
+    fn admit(&self, incoming: &IncomingConnection) -> AmqpResult<RoutedConnection<H>> {
+        if let Some(authorizer) = &self.authorizer {
+            authorizer.authorize(incoming)?;
+        }
+        let limit = self.quota.max_connections.unwrap_or(usize::MAX);
+        self.connections
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| (count < limit).then_some(count + 1))
+            .map_err(|count| {
+                AmqpError::amqp_protocol(
+                    AmqpCondition::AmqpErrorResourceLimitExceeded,
+                    format!("Virtual host '{}' is at its limit of {} connections", self.name, count),
+                )
+            })?;
+        Ok(RoutedConnection {
+            vhost: self.name.clone(),
+            handler: self.handler.clone(),
+            connections: self.connections.clone(),
+        })
+    }
+}
+
+impl<H> std::fmt::Debug for VirtualHost<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VirtualHost")
+            .field("name", &self.name)
+            .field("authorizer", &self.authorizer.is_some())
+            .field("quota", &self.quota)
+            .field("connections", &self.connections())
+            .finish()
+    }
+}
+
+/// Routing of incoming connections to virtual hosts
+///
+/// Virtual host names are matched without regard to case. A connection
+/// naming no virtual host, or one that is not configured, goes to the
+/// default virtual host if there is one and is refused with
+/// `amqp:not-found` otherwise.
+#[derive(Debug)]
+pub struct VhostRouter<H> {
+    hosts: HashMap<String, VirtualHost<H>>,
+    default_host: Option<String>,
+}
+
+impl<H> VhostRouter<H> {
+    /// Create a router without virtual hosts
+    pub fn new() -> Self {
+        VhostRouter {
+            hosts: HashMap::new(),
+            default_host: None,
+        }
+    }
+
+    /// Add a virtual host, replacing one of the same name
+    pub fn host(mut self, host: VirtualHost<H>) -> Self {
+        self.hosts.insert(host.name.to_ascii_lowercase(), host);
+        self
+    }
+
+    /// Send connections that name no known virtual host to this one
+    pub fn default_host(mut self, name: impl Into<String>) -> Self {
+        self.default_host = Some(name.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Get a virtual host by name
+    pub fn get(&self, name: &str) -> Option<&VirtualHost<H>> {
+        self.hosts.get(&name.to_ascii_lowercase())
+    }
+
+    /// Get the number of virtual hosts
+    pub fn len(&self) -> usize {
+        self.hosts.len()
+    }
+
+    /// Check if there are no virtual hosts
+    pub fn is_empty(&self) -> bool {
+        self.hosts.is_empty()
+    }
+
+    /// Pick the virtual host for a connection and admit it there
+    ///
+    /// Fails with `amqp:not-found` if no virtual host matches, with the
+    /// virtual host's authorizer's error, or with
+    /// `amqp:resource-limit-exceeded` if its quota is used up. The
+    /// connection counts against the quota until the result is dropped.
+    pub fn route(&self, incoming: &IncomingConnection) -> AmqpResult<RoutedConnection<H>> {
+        let requested = incoming.virtual_host();
+        let host = requested
+            .and_then(|name| self.get(name))
+            .or_else(|| self.default_host.as_ref().and_then(|name| self.hosts.get(name)))
+            .ok_or_else(|| {
+                AmqpError::amqp_protocol(
+                    AmqpCondition::AmqpErrorNotFound,
+                    format!("Unknown virtual host '{}'", requested.unwrap_or_default()),
+                )
+            })?;
+        logging::debug!("Routing connection from {} to virtual host '{}'", incoming.remote_addr, host.name);
+        host.admit(incoming)
+    }
+}
+
+impl<H> Default for VhostRouter<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A connection admitted to a virtual host
+///
+/// Counts against the virtual host's quota until dropped.
+#[derive(Debug)]
+pub struct RoutedConnection<H> {
+    vhost: String,
+    handler: Arc<H>,
+    connections: Arc<AtomicUsize>,
+}
+
+impl<H> RoutedConnection<H> {
+    /// Get the name of the virtual host
+    pub fn vhost(&self) -> &str {
+        &self.vhost
+    }
+
+    /// Get the handler of the virtual host
+    pub fn handler(&self) -> &Arc<H> {
+        &self.handler
+    }
+}
+
+impl<H> Drop for RoutedConnection<H> {
+    fn drop(&mut self) {
+        self.connections.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// What a [`NetworkListener`] does with connections beyond its limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverloadPolicy {
+    /// Stop accepting until a connection is released
+    ///
+    /// Clients wait in the kernel's accept queue, and once that is full
+    /// their connection attempts fail or time out.
+    #[default]
+    Pause,
+    /// Accept and close right away with `amqp:resource-limit-exceeded`
+    ///
+    /// Only a bounded number of refusals are answered at once; under a
+    /// flood, clients beyond them are closed without an answer.
+    Refuse,
+}
+
+/// TCP listener for the server role, with a limit on live connections
+#[derive(Debug)]
+pub struct NetworkListener {
+    listener: TcpListener,
+    container_id: String,
+    max_connections: Option<usize>,
+    policy: OverloadPolicy,
+    connections: Arc<AtomicUsize>,
+    released: Arc<Notify>,
+    refused: Arc<AtomicU64>,
+    /// Refusals still being answered
+    refusing: Arc<AtomicUsize>,
+}
+
+impl NetworkListener {
+    /// Listen on an address, without a connection limit
+    pub async fn bind(addr: impl ToSocketAddrs) -> AmqpResult<Self> {
+        Ok(Self::from_listener(TcpListener::bind(addr).await?))
+    }
+
+    /// Use a bound TCP listener
+    pub fn from_listener(listener: TcpListener) -> Self {
+        NetworkListener {
+            listener,
+            container_id: format!("dumq-listener-{}", crate::ids::next_id()),
+            max_connections: None,
+            policy: OverloadPolicy::default(),
+            connections: Arc::new(AtomicUsize::new(0)),
+            released: Arc::new(Notify::new()),
+            refused: Arc::new(AtomicU64::new(0)),
+            refusing: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Limit the connections held at once
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    /// Set what happens to connections beyond the limit
+    pub fn overload_policy(mut self, policy: OverloadPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Set the container-id of the Open sent before refusing a connection
+    pub fn container_id(mut self, container_id: impl Into<String>) -> Self {
+        self.container_id = container_id.into();
+        self
+    }
+
+    /// Get the address the listener is bound to
+    pub fn local_addr(&self) -> AmqpResult<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Get the number of accepted connections whose permit is still held
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::Acquire)
+    }
+
+    /// Get the number of connections refused at the limit
+    pub fn refused(&self) -> u64 {
+        self.refused.load(Ordering::Relaxed)
+    }
+
+    /// Accept the next connection within the limit
+    ///
+    /// Under [`OverloadPolicy::Pause`] this waits for a permit to be
+    /// released before accepting; under [`OverloadPolicy::Refuse`] clients
+    /// beyond the limit are closed in the background while this keeps
+    /// waiting for one that fits.
+    ///
+    /// Cancel safe: a permit reserved while waiting is released when the
+    /// wait is cancelled.
+    pub async fn accept(&self) -> AmqpResult<AcceptedConnection> {
+        loop {
+            if self.policy == OverloadPolicy::Pause {
+                let permit = loop {
+                    if let Some(permit) = self.try_reserve() {
+                        break permit;
+                    }
+                    self.released.notified().await;
+                };
+                let (stream, remote_addr) = self.listener.accept().await?;
+                return Ok(self.accepted(stream, remote_addr, permit));
+            }
+
+            let (stream, remote_addr) = self.listener.accept().await?;
+            if let Some(permit) = self.try_reserve() {
+                return Ok(self.accepted(stream, remote_addr, permit));
+            }
+
+            self.refused.fetch_add(1, Ordering::Relaxed);
+            let limit = self.max_connections.unwrap_or_default();
+            if !reserve(&self.refusing, MAX_CONCURRENT_REFUSALS) {
+                logging::debug!("Dropping connection from {}: too many refusals under way", remote_addr);
+                continue;
+            }
+            logging::warn!("Refusing connection from {}: at the limit of {} connections", remote_addr, limit);
+            let refusing = RefusalSlot(self.refusing.clone());
+            let container_id = self.container_id.clone();
+            tokio::spawn(async move {
+                let _refusing = refusing;
+                let error = types::AmqpError::new(AmqpCondition::AmqpErrorResourceLimitExceeded)
+                    .with_description(format!("Server is at its limit of {} connections", limit));
+                match tokio::time::timeout(REFUSAL_TIMEOUT, refuse(stream, container_id, error)).await {
+                    Ok(Err(e)) => logging::debug!("Refusing {} failed: {}", remote_addr, e),
+                    Err(_) => logging::debug!("Refusing {} timed out", remote_addr),
+                    Ok(Ok(())) => {}
+                }
+            });
+        }
+    }
+
+    fn try_reserve(&self) -> Option<ConnectionPermit> {
+        reserve(&self.connections, self.max_connections.unwrap_or(usize::MAX)).then(|| ConnectionPermit {
+            connections: self.connections.clone(),
+            released: self.released.clone(),
+        })
+    }
+
+    fn accepted(&self, stream: TcpStream, remote_addr: SocketAddr, permit: ConnectionPermit) -> AcceptedConnection {
+        logging::debug!("Accepted connection from {} ({} held)", remote_addr, self.connections());
+        AcceptedConnection {
+            stream,
+            incoming: IncomingConnection::new(remote_addr),
+            permit,
+        }
+    }
+}
+
+/// Count one more against a limit, if below it
+fn reserve(count: &AtomicUsize, limit: usize) -> bool {
+    count
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |held| (held < limit).then_some(held + 1))
+        .is_ok()
+}
+
+/// A refusal under way, counted until dropped
+struct RefusalSlot(Arc<AtomicUsize>);
+
+impl Drop for RefusalSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Answer a client's protocol header with an Open and a Close carrying `error`
+///
+/// The specification requires an Open before the Close, even when refusing.
+/// A client asking for SASL or TLS first is answered with the plain AMQP
+/// header, which it will treat as a protocol mismatch.
+async fn refuse(stream: TcpStream, container_id: String, error: types::AmqpError) -> AmqpResult<()> {
+    let mut transport = Transport::new(stream);
+    let mut header = [0; 8];
+    header.copy_from_slice(&transport.receive_raw(8).await?);
+    transport.send_raw(ProtocolHeader::AMQP.as_bytes()).await?;
+
+    if ProtocolHeader::from_bytes(header) == ProtocolHeader::AMQP {
+        let open = Open { container_id, ..Default::default() }.encode()?;
+        let close = Close { error: Some(error) }.encode()?;
+        for payload in [open, close] {
+            let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, 0);
+            transport.send_frame(Frame::new(header, payload)).await?;
+        }
+    }
+    transport.shutdown().await
+}
+
+/// A connection accepted by a [`NetworkListener`]
+#[derive(Debug)]
+pub struct AcceptedConnection {
+    /// The TCP stream, e.g. to hand to a TLS acceptor
+    pub stream: TcpStream,
+    /// What is known about the client so far
+    pub incoming: IncomingConnection,
+    /// Counts against the listener's limit until dropped
+    pub permit: ConnectionPermit,
+}
+
+/// A connection's place under a [`NetworkListener`]'s limit
+///
+/// Keep it for as long as the connection is served; dropping it lets the
+/// listener accept another one.
+#[derive(Debug)]
+pub struct ConnectionPermit {
+    connections: Arc<AtomicUsize>,
+    released: Arc<Notify>,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.connections.fetch_sub(1, Ordering::AcqRel);
+        self.released.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reject_duplicate() {
+        let registry = ContainerRegistry::default();
+        let lease = registry.register("client-a").unwrap();
+
+        match registry.register("client-a") {
+            Err(error) => assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorConnectionForced)),
+            Ok(_) => panic!("Duplicate container-id was admitted"),
+        }
+        assert!(!lease.is_evicted());
+        assert!(registry.register("client-b").is_ok());
+    }
+
+    #[test]
+    fn test_steal_existing() {
+        let registry = ContainerRegistry::new(DuplicateContainerPolicy::StealExisting);
+        let old = registry.register("client-a").unwrap();
+        let new = registry.register("client-a").unwrap();
+
+        assert!(old.is_evicted());
+        assert!(!new.is_evicted());
+        assert_eq!(old.eviction_error().condition(), Some(&AmqpCondition::AmqpErrorConnectionForced));
+
+        // The evicted lease must not release the id held by the new one
+        drop(old);
+        assert!(registry.contains("client-a"));
+        drop(new);
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn test_release_on_drop() {
+        let registry = ContainerRegistry::default();
+        drop(registry.register("client-a").unwrap());
+        assert!(!registry.contains("client-a"));
+        assert!(registry.register("client-a").is_ok());
+    }
+
+    #[test]
+    fn test_closure_authorizer_sees_tls_identity() {
+        let authorizer = |incoming: &IncomingConnection| match &incoming.tls {
+            Some(tls) if tls.is_client_authenticated() => Ok(()),
+            _ => Err(AmqpError::amqp_protocol(
+                AmqpCondition::AmqpErrorUnauthorizedAccess,
+                "Client certificate required",
+            )),
+        };
+
+        let mut incoming = IncomingConnection::new("127.0.0.1:5671".parse().unwrap()).with_container_id("client-a");
+        let error = authorizer.authorize(&incoming).unwrap_err();
+        assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorUnauthorizedAccess));
+
+        incoming.tls = Some(TlsIdentity {
+            peer_certificates: vec![vec![0x30, 0x82]],
+            ..TlsIdentity::default()
+        });
+        assert!(authorizer.authorize(&incoming).is_ok());
+        assert_eq!(incoming.tls.unwrap().peer_certificate(), Some(&[0x30, 0x82][..]));
+    }
+
+    #[test]
+    fn test_vhost_router_routes_by_open_hostname() {
+        let router = VhostRouter::new()
+            .host(VirtualHost::new("tenant-a", "broker-a"))
+            .host(VirtualHost::new("Tenant-B", "broker-b").quota(VhostQuota { max_connections: Some(1) }))
+            .default_host("tenant-a");
+        let incoming = IncomingConnection::new("127.0.0.1:5672".parse().unwrap());
+
+        let routed = router.route(&incoming.clone().with_hostname("TENANT-b")).unwrap();
+        assert_eq!((routed.vhost(), **routed.handler()), ("Tenant-B", "broker-b"));
+        assert_eq!(router.get("tenant-b").unwrap().connections(), 1);
+
+        let error = router.route(&incoming.clone().with_hostname("tenant-b")).unwrap_err();
+        assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorResourceLimitExceeded));
+        drop(routed);
+        assert!(router.route(&incoming.clone().with_hostname("tenant-b")).is_ok());
+
+        // No hostname in the Open: the TLS server name, then the default
+        let mut over_tls = incoming.clone();
+        over_tls.tls = Some(TlsIdentity { server_name: Some("tenant-b".to_string()), ..TlsIdentity::default() });
+        assert_eq!(router.route(&over_tls).unwrap().vhost(), "Tenant-B");
+        assert_eq!(router.route(&incoming).unwrap().vhost(), "tenant-a");
+    }
+
+    #[test]
+    fn test_vhost_router_authorizes_per_vhost() {
+        let router = VhostRouter::new()
+            .host(VirtualHost::new("public", ()))
+            .host(VirtualHost::new("internal", ()).authorizer(|incoming: &IncomingConnection| {
+                match incoming.remote_addr.ip().is_loopback() {
+                    true => Ok(()),
+                    false => Err(AmqpError::amqp_protocol(AmqpCondition::AmqpErrorUnauthorizedAccess, "Loopback only")),
+                }
+            }));
+        let remote = IncomingConnection::new("203.0.113.9:5672".parse().unwrap());
+
+        assert!(router.route(&remote.clone().with_hostname("public")).is_ok());
+        let error = router.route(&remote.clone().with_hostname("internal")).unwrap_err();
+        assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorUnauthorizedAccess));
+        assert_eq!(router.get("internal").unwrap().connections(), 0);
+
+        // Without a default, an unknown virtual host is refused
+        let error = router.route(&remote.with_hostname("other")).unwrap_err();
+        assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_network_listener_pauses_at_limit() {
+        let listener = Arc::new(NetworkListener::bind("127.0.0.1:0").await.unwrap().max_connections(1));
+        let addr = listener.local_addr().unwrap();
+
+        let _first_client = TcpStream::connect(addr).await.unwrap();
+        let first = listener.accept().await.unwrap();
+        assert_eq!(listener.connections(), 1);
+
+        let _second_client = TcpStream::connect(addr).await.unwrap();
+        let second = tokio::spawn({
+            let listener = listener.clone();
+            async move { listener.accept().await.unwrap() }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!second.is_finished());
+
+        drop(first.permit);
+        let second = tokio::time::timeout(Duration::from_secs(1), second).await.unwrap().unwrap();
+        assert_eq!(second.incoming.remote_addr, second.stream.peer_addr().unwrap());
+        assert_eq!((listener.connections(), listener.refused()), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_network_listener_refuses_at_limit() {
+        let listener = NetworkListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .max_connections(1)
+            .overload_policy(OverloadPolicy::Refuse)
+            .container_id("broker");
+        let listener = Arc::new(listener);
+        let addr = listener.local_addr().unwrap();
+
+        let _first_client = TcpStream::connect(addr).await.unwrap();
+        let _first = listener.accept().await.unwrap();
+
+        let mut client = Transport::new(TcpStream::connect(addr).await.unwrap());
+        let accepting = tokio::spawn({
+            let listener = listener.clone();
+            async move { listener.accept().await.map(|_| ()) }
+        });
+        client.send_raw(ProtocolHeader::AMQP.as_bytes()).await.unwrap();
+        assert_eq!(client.receive_raw(8).await.unwrap(), ProtocolHeader::AMQP.as_bytes());
+        let open = Open::decode(&client.receive_frame().await.unwrap().payload).unwrap();
+        assert_eq!(open.container_id, "broker");
+        let close = Close::decode(&client.receive_frame().await.unwrap().payload).unwrap();
+        assert_eq!(close.error.unwrap().condition, AmqpCondition::AmqpErrorResourceLimitExceeded);
+
+        // The refused client did not take the place of a connection
+        assert!(!accepting.is_finished());
+        assert_eq!((listener.connections(), listener.refused()), (1, 1));
+        accepting.abort();
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_accept_releases_permit() {
+        let listener = NetworkListener::bind("127.0.0.1:0").await.unwrap().max_connections(1);
+        let addr = listener.local_addr().unwrap();
+
+        for _ in 0..3 {
+            assert!(tokio::time::timeout(Duration::from_millis(10), listener.accept()).await.is_err());
+        }
+        assert_eq!(listener.connections(), 0);
+
+        let _client = TcpStream::connect(addr).await.unwrap();
+        let accepted = tokio::time::timeout(Duration::from_secs(1), listener.accept()).await.unwrap().unwrap();
+        assert_eq!(listener.connections(), 1);
+        drop(accepted);
+        assert_eq!(listener.connections(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_refusals_beyond_cap_are_dropped() {
+        use tokio::io::AsyncReadExt;
+
+        let listener = NetworkListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .max_connections(1)
+            .overload_policy(OverloadPolicy::Refuse);
+        let listener = Arc::new(listener);
+        let addr = listener.local_addr().unwrap();
+        let _first_client = TcpStream::connect(addr).await.unwrap();
+        let _first = listener.accept().await.unwrap();
+        let accepting = tokio::spawn({
+            let listener = listener.clone();
+            async move { listener.accept().await.map(|_| ()) }
+        });
+
+        // Silent clients keep their refusals waiting for a protocol header
+        let mut silent = Vec::new();
+        for _ in 0..MAX_CONCURRENT_REFUSALS {
+            silent.push(TcpStream::connect(addr).await.unwrap());
+        }
+        while listener.refused() < MAX_CONCURRENT_REFUSALS as u64 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(listener.refusing.load(Ordering::Acquire), MAX_CONCURRENT_REFUSALS);
+
+        let mut dropped = TcpStream::connect(addr).await.unwrap();
+        let mut buffer = [0u8; 8];
+        let read = tokio::time::timeout(Duration::from_secs(1), dropped.read(&mut buffer)).await.unwrap();
+        assert!(matches!(read, Ok(0) | Err(_)));
+        assert_eq!(listener.refusing.load(Ordering::Acquire), MAX_CONCURRENT_REFUSALS);
+        accepting.abort();
+    }
+
+    #[tokio::test]
+    async fn test_evicted_wakes_waiter() {
+        let registry = ContainerRegistry::new(DuplicateContainerPolicy::StealExisting);
+        let old = registry.register("client-a").unwrap();
+
+        let waiter = tokio::spawn(async move {
+            old.evicted().await;
+            old.container_id().to_string()
+        });
+        tokio::task::yield_now().await;
+        let _new = registry.register("client-a").unwrap();
+        assert_eq!(waiter.await.unwrap(), "client-a");
+    }
+}
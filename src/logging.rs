@@ -0,0 +1,17 @@
+//! Logging facade
+//!
+//! Forwards to the `log` crate with the `logging` feature. Without it the
+//! macros still type-check their arguments but compile to nothing.
+
+#[cfg(feature = "logging")]
+pub(crate) use log::{debug, info, warn};
+
+#[cfg(not(feature = "logging"))]
+macro_rules! discard {
+    ($($arg:tt)*) => {{
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+#[cfg(not(feature = "logging"))]
+pub(crate) use {discard as debug, discard as info, discard as warn};
@@ -6,6 +6,7 @@ use tokio::time::Duration;
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize logging
+    #[cfg(feature = "logging")]
     env_logger::init();
 
     println!("AMQP 1.0 Rust Library Example");
@@ -38,8 +39,8 @@ fn example_encoding_decoding() -> Result<(), Box<dyn std::error::Error>> {
         AmqpValue::String("Hello, AMQP!".to_string()),
         AmqpValue::Int(42),
         AmqpValue::Boolean(true),
-        AmqpValue::Double(3.14159),
-        AmqpValue::Uuid(uuid::Uuid::new_v4()),
+        AmqpValue::Double(std::f64::consts::PI),
+        AmqpValue::Uuid(dumq_amqp::types::Uuid::new_v4()),
     ];
 
     for value in values {
@@ -142,7 +143,7 @@ async fn example_sender_receiver() -> Result<(), Box<dyn std::error::Error>> {
 
     // Simulate sending a message
     match sender.send(test_message.clone()).await {
-        Ok(delivery_id) => println!("  Message sent with delivery ID: {}", delivery_id),
+        Ok(delivery) => println!("  Message sent with delivery ID: {}", delivery.delivery_id()),
         Err(e) => println!("  Failed to send message: {}", e),
     }
 
@@ -0,0 +1,144 @@
+//! AMQP 1.0 Memory Budget
+//!
+//! This module provides byte accounting for buffered messages. A budget caps
+//! the bytes held in sender pending deliveries and receiver queues; a budget
+//! can be shared by many links for a global cap, and each link can also have
+//! its own limit. When a budget is exhausted, sends fail with
+//! `amqp:resource:limit-exceeded` and receivers withhold credit until
+//! buffered messages are consumed.
+//!
+//! Message sizes are measured with [`Message::encoded_size`](crate::message::Message::encoded_size).
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::link::LinkBuilder;
+//! use dumq_amqp::memory::MemoryBudget;
+//!
+//! // 64 MiB shared by all links, 1 MiB per link
+//! let budget = MemoryBudget::new(64 * 1024 * 1024);
+//!
+//! let sender = LinkBuilder::new()
+//!     .target("orders")
+//!     .memory_budget(budget.clone())
+//!     .memory_limit(1024 * 1024)
+//!     .build_sender("session-1".to_string());
+//!
+//! assert_eq!(budget.used(), 0);
+//! ```
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Shared cap on buffered message bytes
+#[derive(Debug, Clone)]
+pub struct MemoryBudget {
+    inner: Arc<BudgetInner>,
+}
+
+#[derive(Debug)]
+struct BudgetInner {
+    limit: usize,
+    used: AtomicUsize,
+}
+
+impl MemoryBudget {
+    /// Create a budget with a limit in bytes
+    pub fn new(limit: usize) -> Self {
+        MemoryBudget {
+            inner: Arc::new(BudgetInner {
+                limit,
+                used: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Reserve bytes if they fit within the limit
+    pub fn try_acquire(&self, bytes: usize) -> bool {
+        self.inner
+            .used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                used.checked_add(bytes).filter(|total| *total <= self.inner.limit)
+            })
+            .is_ok()
+    }
+
+    /// Reserve bytes regardless of the limit
+    ///
+    /// Used for messages that have already arrived and must be buffered.
+    pub fn force_acquire(&self, bytes: usize) {
+        self.inner.used.fetch_add(bytes, Ordering::AcqRel);
+    }
+
+    /// Release previously reserved bytes
+    pub fn release(&self, bytes: usize) {
+        let _ = self
+            .inner
+            .used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| Some(used.saturating_sub(bytes)));
+    }
+
+    /// Get the limit in bytes
+    pub fn limit(&self) -> usize {
+        self.inner.limit
+    }
+
+    /// Get the reserved bytes
+    pub fn used(&self) -> usize {
+        self.inner.used.load(Ordering::Acquire)
+    }
+
+    /// Get the bytes still available
+    pub fn available(&self) -> usize {
+        self.inner.limit.saturating_sub(self.used())
+    }
+
+    /// Check if the budget is used up
+    pub fn is_exhausted(&self) -> bool {
+        self.used() >= self.inner.limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_acquire_within_limit() {
+        let budget = MemoryBudget::new(100);
+        assert!(budget.try_acquire(60));
+        assert!(!budget.try_acquire(50));
+        assert!(budget.try_acquire(40));
+        assert_eq!(budget.used(), 100);
+        assert_eq!(budget.available(), 0);
+        assert!(budget.is_exhausted());
+    }
+
+    #[test]
+    fn test_release() {
+        let budget = MemoryBudget::new(100);
+        assert!(budget.try_acquire(80));
+        budget.release(50);
+        assert_eq!(budget.used(), 30);
+        budget.release(100);
+        assert_eq!(budget.used(), 0);
+    }
+
+    #[test]
+    fn test_force_acquire_exceeds_limit() {
+        let budget = MemoryBudget::new(10);
+        budget.force_acquire(25);
+        assert_eq!(budget.used(), 25);
+        assert!(budget.is_exhausted());
+        assert!(!budget.try_acquire(1));
+    }
+
+    #[test]
+    fn test_budget_shared_between_clones() {
+        let budget = MemoryBudget::new(100);
+        let shared = budget.clone();
+        assert!(shared.try_acquire(70));
+        assert_eq!(budget.used(), 70);
+        assert_eq!(budget.limit(), 100);
+    }
+}
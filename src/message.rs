@@ -33,7 +33,7 @@
 //! ## Creating Complex Messages
 //!
 //! ```rust
-//! use dumq_amqp::message::{Message, MessageBuilder};
+//! use dumq_amqp::message::{self, Message};
 //! use dumq_amqp::types::{AmqpValue, AmqpSymbol};
 //!
 //! let message = Message::builder()
@@ -61,20 +61,46 @@
 //!     println!("Message binary: {:?}", binary);
 //! }
 //! ```
+//!
+//! ## Logging Messages
+//!
+//! `Debug` and `Display` summarize a message: bodies are truncated and
+//! application properties named in the [`RedactionPolicy`] are masked, so
+//! messages can be logged without leaking their contents. Use
+//! [`Message::dump_full`] when troubleshooting needs everything.
+//!
+//! ```rust
+//! use dumq_amqp::message::{Message, RedactionPolicy};
+//!
+//! let policy = RedactionPolicy::current().redact("api-key");
+//! policy.install();
+//!
+//! let message = Message::text("x".repeat(1000));
+//! assert!(format!("{:?}", message).len() < 300);
+//! assert!(message.dump_full().len() > 1000);
+//! ```
 
-use crate::{AmqpMap, AmqpSymbol, AmqpValue, types::AmqpList};
+use bytes::Bytes;
+use crate::{AmqpMap, AmqpSymbol, AmqpValue, types::{AmqpList, AnnotationKey, Annotations}};
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::{OnceLock, RwLock};
+use crate::types::Uuid;
 
 /// AMQP 1.0 Message structure
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+///
+/// The `Debug` output is redacted according to the [`RedactionPolicy`].
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Message {
     /// Message header
     pub header: Option<Header>,
     /// Message delivery annotations
-    pub delivery_annotations: Option<AmqpMap>,
+    pub delivery_annotations: Option<Annotations>,
     /// Message annotations
-    pub message_annotations: Option<AmqpMap>,
+    pub message_annotations: Option<Annotations>,
     /// Message properties
     pub properties: Option<Properties>,
     /// Application properties
@@ -85,8 +111,25 @@ pub struct Message {
     pub footer: Option<AmqpMap>,
 }
 
+/// Priority assumed when the header does not carry one
+pub const DEFAULT_PRIORITY: u8 = 4;
+
+/// Message section descriptor codes
+pub mod section {
+    pub const HEADER: u64 = 0x70;
+    pub const DELIVERY_ANNOTATIONS: u64 = 0x71;
+    pub const MESSAGE_ANNOTATIONS: u64 = 0x72;
+    pub const PROPERTIES: u64 = 0x73;
+    pub const APPLICATION_PROPERTIES: u64 = 0x74;
+    pub const DATA: u64 = 0x75;
+    pub const AMQP_SEQUENCE: u64 = 0x76;
+    pub const AMQP_VALUE: u64 = 0x77;
+    pub const FOOTER: u64 = 0x78;
+}
+
 /// AMQP 1.0 Message Header
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Header {
     /// Whether the message is durable
     pub durable: Option<bool>,
@@ -120,7 +163,8 @@ impl Default for Header {
 }
 
 /// AMQP 1.0 Message Properties
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Properties {
     /// Message ID
     pub message_id: Option<AmqpValue>,
@@ -150,26 +194,6 @@ pub struct Properties {
     pub reply_to_group_id: Option<String>,
 }
 
-impl Default for Properties {
-    fn default() -> Self {
-        Properties {
-            message_id: None,
-            user_id: None,
-            to: None,
-            subject: None,
-            reply_to: None,
-            correlation_id: None,
-            content_type: None,
-            content_encoding: None,
-            absolute_expiry_time: None,
-            creation_time: None,
-            group_id: None,
-            group_sequence: None,
-            reply_to_group_id: None,
-        }
-    }
-}
-
 impl Properties {
     /// Create a new empty properties
     pub fn new() -> Self {
@@ -178,10 +202,11 @@ impl Properties {
 }
 
 /// AMQP 1.0 Message Body
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Body {
-    /// Data body (binary)
-    Data(Vec<u8>),
+    /// Data body (binary), shared between clones of the message
+    Data(#[cfg_attr(feature = "serde", serde(with = "crate::types::serde_bytes"))] Bytes),
     /// Amqp value body
     Value(AmqpValue),
     /// Amqp sequence body
@@ -219,13 +244,13 @@ impl MessageBuilder {
     }
 
     /// Set delivery annotations
-    pub fn delivery_annotations(mut self, annotations: AmqpMap) -> Self {
+    pub fn delivery_annotations(mut self, annotations: Annotations) -> Self {
         self.message.delivery_annotations = Some(annotations);
         self
     }
 
     /// Set message annotations
-    pub fn message_annotations(mut self, annotations: AmqpMap) -> Self {
+    pub fn message_annotations(mut self, annotations: Annotations) -> Self {
         self.message.message_annotations = Some(annotations);
         self
     }
@@ -237,8 +262,8 @@ impl MessageBuilder {
     }
 
     /// Set application properties
-    pub fn application_properties(mut self, properties: AmqpMap) -> Self {
-        self.message.application_properties = Some(properties);
+    pub fn application_properties(mut self, properties: impl Into<AmqpMap>) -> Self {
+        self.message.application_properties = Some(properties.into());
         self
     }
 
@@ -249,8 +274,8 @@ impl MessageBuilder {
     }
 
     /// Set footer
-    pub fn footer(mut self, footer: AmqpMap) -> Self {
-        self.message.footer = Some(footer);
+    pub fn footer(mut self, footer: impl Into<AmqpMap>) -> Self {
+        self.message.footer = Some(footer.into());
         self
     }
 
@@ -266,6 +291,12 @@ impl Default for MessageBuilder {
     }
 }
 
+impl From<Message> for MessageBuilder {
+    fn from(message: Message) -> Self {
+        message.into_builder()
+    }
+}
+
 impl Message {
     /// Create a new empty message
     pub fn new() -> Self {
@@ -285,6 +316,28 @@ impl Message {
         MessageBuilder::new()
     }
 
+    /// Turn the message back into a builder, keeping every section
+    ///
+    /// Setters on the returned builder replace whole sections, leaving the
+    /// others as they were:
+    ///
+    /// ```rust
+    /// use dumq_amqp::message::{Header, Message};
+    ///
+    /// let original = Message::text("payload").with_subject("orders");
+    /// let urgent = original.clone()
+    ///     .into_builder()
+    ///     .header(Header { priority: Some(9), ..Header::new() })
+    ///     .build();
+    ///
+    /// assert_eq!(urgent.priority(), 9);
+    /// assert_eq!(urgent.body, original.body);
+    /// assert_eq!(urgent.properties, original.properties);
+    /// ```
+    pub fn into_builder(self) -> MessageBuilder {
+        MessageBuilder { message: self }
+    }
+
     /// Create a simple text message
     pub fn text(text: impl Into<String>) -> Self {
         MessageBuilder::new()
@@ -295,7 +348,7 @@ impl Message {
     /// Create a simple binary message
     pub fn binary(data: impl Into<Vec<u8>>) -> Self {
         MessageBuilder::new()
-            .body(Body::Data(data.into()))
+            .body(Body::Data(Bytes::from(data.into())))
             .build()
     }
 
@@ -315,6 +368,75 @@ impl Message {
         }
     }
 
+    /// Iterate the amqp-sequence sections of the body, in order
+    ///
+    /// A received body of several sequence sections yields each of them;
+    /// other sections are skipped.
+    ///
+    /// ```
+    /// use dumq_amqp::{AmqpValue, Body, Message};
+    ///
+    /// let message = Message::builder()
+    ///     .body(Body::Multiple(vec![
+    ///         Body::Sequence(vec![AmqpValue::Int(1), AmqpValue::Int(2)].into()),
+    ///         Body::Sequence(vec![AmqpValue::Int(3)].into()),
+    ///     ]))
+    ///     .build();
+    ///
+    /// let total: usize = message.sequences().map(|items| items.len()).sum();
+    /// assert_eq!(total, 3);
+    /// ```
+    pub fn sequences(&self) -> impl Iterator<Item = &AmqpList> {
+        let sections = match &self.body {
+            Some(Body::Multiple(bodies)) => bodies.as_slice(),
+            Some(body) => std::slice::from_ref(body),
+            None => &[],
+        };
+        sections.iter().filter_map(|section| match section {
+            Body::Sequence(items) => Some(items),
+            _ => None,
+        })
+    }
+
+    /// Get the message priority, resolving the default when unset
+    pub fn priority(&self) -> u8 {
+        self.header
+            .as_ref()
+            .and_then(|header| header.priority)
+            .unwrap_or(DEFAULT_PRIORITY)
+    }
+
+    /// Check if the message is durable, resolving the default when unset
+    pub fn is_durable(&self) -> bool {
+        self.header
+            .as_ref()
+            .and_then(|header| header.durable)
+            .unwrap_or(false)
+    }
+
+    /// Get a message annotation by its symbolic key
+    pub fn annotation(&self, key: &str) -> Option<&AmqpValue> {
+        self.message_annotations.as_ref()?.get(&AnnotationKey::from(key))
+    }
+
+    /// Get a message annotation by its registered numeric code
+    pub fn annotation_by_code(&self, code: u64) -> Option<&AmqpValue> {
+        self.message_annotations.as_ref()?.get(&AnnotationKey::Ulong(code))
+    }
+
+    /// Get a delivery annotation by its registered numeric code
+    pub fn delivery_annotation_by_code(&self, code: u64) -> Option<&AmqpValue> {
+        self.delivery_annotations.as_ref()?.get(&AnnotationKey::Ulong(code))
+    }
+
+    /// Get the number of bytes this message occupies when encoded
+    ///
+    /// The size is computed from the message contents without encoding it, so
+    /// it can be checked against a peer's maximum message size before sending.
+    pub fn encoded_size(&self) -> usize {
+        crate::codec::encoded_message_size(self)
+    }
+
     /// Get the message ID as a string
     pub fn message_id_as_string(&self) -> Option<String> {
         match &self.properties {
@@ -327,6 +449,31 @@ impl Message {
         }
     }
 
+    /// Get the footer, creating an empty one if the message has none
+    ///
+    /// The footer is encoded after every other section whenever it is set,
+    /// so checksums and signatures can be added once the rest is final.
+    pub fn footer_mut(&mut self) -> &mut AmqpMap {
+        self.footer.get_or_insert_with(AmqpMap::new)
+    }
+
+    /// Get a footer entry
+    pub fn footer_value(&self, key: &str) -> Option<&AmqpValue> {
+        self.footer.as_ref()?.get(&AmqpSymbol::from(key))
+    }
+
+    /// Get the time the message ID was generated, if it is a version 7 UUID
+    ///
+    /// The id may be a UUID value or its hyphenated string form, as produced
+    /// by [`TimeOrderedIds`](crate::ids::TimeOrderedIds).
+    pub fn message_id_timestamp(&self) -> Option<std::time::SystemTime> {
+        match self.properties.as_ref()?.message_id.as_ref()? {
+            AmqpValue::Uuid(uuid) => crate::ids::uuid_v7_timestamp(uuid),
+            AmqpValue::String(id) => crate::ids::uuid_v7_str_timestamp(id),
+            _ => None,
+        }
+    }
+
     /// Set a simple message ID (string)
     pub fn with_message_id(mut self, id: impl Into<String>) -> Self {
         if self.properties.is_none() {
@@ -436,6 +583,176 @@ impl Message {
     }
 }
 
+impl Default for Message {
+    fn default() -> Self {
+        Message::new()
+    }
+}
+
+/// Rules for summarizing messages in `Debug` and `Display` output
+#[derive(Debug, Clone, PartialEq)]
+pub struct RedactionPolicy {
+    /// Maximum body bytes shown before truncating
+    pub max_body_bytes: usize,
+    /// Application property keys whose values are masked (case-insensitive)
+    pub redacted_keys: HashSet<String>,
+}
+
+impl Default for RedactionPolicy {
+    fn default() -> Self {
+        RedactionPolicy {
+            max_body_bytes: 64,
+            redacted_keys: ["password", "secret", "token", "authorization"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        }
+    }
+}
+
+impl RedactionPolicy {
+    /// Add a key to mask
+    pub fn redact(mut self, key: impl Into<String>) -> Self {
+        self.redacted_keys.insert(key.into().to_lowercase());
+        self
+    }
+
+    /// Check if a key is masked
+    pub fn is_redacted(&self, key: &str) -> bool {
+        self.redacted_keys.contains(&key.to_lowercase())
+    }
+
+    /// Get the policy used for formatting messages
+    pub fn current() -> RedactionPolicy {
+        redaction_policy().read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    /// Make this the policy used for formatting messages, process-wide
+    pub fn install(self) {
+        *redaction_policy().write().unwrap_or_else(|poisoned| poisoned.into_inner()) = self;
+    }
+}
+
+fn redaction_policy() -> &'static RwLock<RedactionPolicy> {
+    static POLICY: OnceLock<RwLock<RedactionPolicy>> = OnceLock::new();
+    POLICY.get_or_init(|| RwLock::new(RedactionPolicy::default()))
+}
+
+/// Shorten text to at most `max` bytes on a character boundary
+fn truncate(text: &str, max: usize) -> String {
+    if text.len() <= max {
+        return text.to_string();
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}... (+{} bytes)", &text[..end], text.len() - end)
+}
+
+struct BodySummary<'a>(&'a Body, usize);
+
+impl fmt::Debug for BodySummary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let max = self.1;
+        match self.0 {
+            Body::Data(data) => {
+                let preview = String::from_utf8_lossy(&data[..data.len().min(max)]);
+                write!(f, "Data({} bytes: {:?}", data.len(), preview)?;
+                if data.len() > max {
+                    write!(f, "...")?;
+                }
+                write!(f, ")")
+            }
+            Body::Value(AmqpValue::String(text)) => write!(f, "Value({:?})", truncate(text, max)),
+            Body::Value(value) => write!(f, "Value({})", truncate(&format!("{:?}", value), max)),
+            Body::Sequence(items) => write!(f, "Sequence({} items)", items.len()),
+            Body::Multiple(sections) => write!(f, "Multiple({} sections)", sections.len()),
+        }
+    }
+}
+
+struct PropertiesSummary<'a>(&'a AmqpMap, &'a RedactionPolicy);
+
+impl fmt::Debug for PropertiesSummary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut entries: Vec<_> = self.0.iter().collect();
+        entries.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+
+        let mut map = f.debug_map();
+        for (key, value) in entries {
+            if self.1.is_redacted(key.as_str()) {
+                map.entry(&key.as_str(), &format_args!("<redacted>"));
+            } else {
+                map.entry(&key.as_str(), &format_args!("{}", truncate(&format!("{:?}", value), self.1.max_body_bytes)));
+            }
+        }
+        map.finish()
+    }
+}
+
+struct Count(usize, &'static str);
+
+impl fmt::Debug for Count {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.0, self.1)
+    }
+}
+
+impl Message {
+    /// Format every section of the message without redaction
+    ///
+    /// Intended for troubleshooting; the output may contain secrets.
+    pub fn dump_full(&self) -> String {
+        format!(
+            "Message {{ header: {:?}, delivery_annotations: {:?}, message_annotations: {:?}, \
+             properties: {:?}, application_properties: {:?}, body: {:?}, footer: {:?} }}",
+            self.header,
+            self.delivery_annotations,
+            self.message_annotations,
+            self.properties,
+            self.application_properties,
+            self.body,
+            self.footer
+        )
+    }
+}
+
+impl fmt::Debug for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let policy = RedactionPolicy::current();
+        f.debug_struct("Message")
+            .field("header", &self.header)
+            .field("delivery_annotations", &self.delivery_annotations.as_ref().map(|a| Count(a.len(), "entries")))
+            .field("message_annotations", &self.message_annotations.as_ref().map(|a| Count(a.len(), "entries")))
+            .field("properties", &self.properties)
+            .field(
+                "application_properties",
+                &self.application_properties.as_ref().map(|p| PropertiesSummary(p, &policy)),
+            )
+            .field("body", &self.body.as_ref().map(|body| BodySummary(body, policy.max_body_bytes)))
+            .field("footer", &self.footer.as_ref().map(|footer| Count(footer.len(), "entries")))
+            .finish()
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let policy = RedactionPolicy::current();
+        write!(f, "Message")?;
+        if let Some(id) = self.message_id_as_string() {
+            write!(f, " {}", id)?;
+        }
+        if let Some(subject) = self.properties.as_ref().and_then(|p| p.subject.as_ref()) {
+            write!(f, " [{}]", subject)?;
+        }
+        match &self.body {
+            Some(body) => write!(f, " {:?}", BodySummary(body, policy.max_body_bytes)),
+            None => write!(f, " (no body)"),
+        }
+    }
+}
+
 impl From<String> for Message {
     fn from(text: String) -> Self {
         Message::text(text)
@@ -533,6 +850,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_into_builder_preserves_sections() {
+        let mut application_properties = AmqpMap::new();
+        application_properties.insert(AmqpSymbol::from("region"), AmqpValue::String("eu".to_string()));
+        let original = Message::builder()
+            .header(Header::new())
+            .application_properties(application_properties)
+            .footer(AmqpMap::new())
+            .body(Body::Data(vec![1, 2, 3].into()))
+            .build()
+            .with_message_id("msg-001");
+
+        let rebuilt = original.clone().into_builder().build();
+        assert_eq!(rebuilt, original);
+
+        let mut properties = original.properties.clone().unwrap();
+        properties.subject = Some("tweaked".to_string());
+        let tweaked = MessageBuilder::from(original.clone()).properties(properties).build();
+        assert_eq!(tweaked.properties.as_ref().unwrap().subject.as_deref(), Some("tweaked"));
+        assert_eq!(tweaked.properties.unwrap().message_id, Some(AmqpValue::String("msg-001".to_string())));
+        assert_eq!(tweaked.application_properties, original.application_properties);
+        assert_eq!(tweaked.footer, original.footer);
+    }
+
     #[test]
     fn test_message_builder_with_properties() {
         let mut properties = Properties::new();
@@ -553,14 +894,17 @@ mod tests {
     #[test]
     fn test_message_builder_with_annotations() {
         let mut annotations = HashMap::new();
-        annotations.insert(AmqpSymbol::from("key1"), AmqpValue::String("value1".to_string()));
-        annotations.insert(AmqpSymbol::from("key2"), AmqpValue::Int(42));
+        annotations.insert(AnnotationKey::from("key1"), AmqpValue::String("value1".to_string()));
+        annotations.insert(AnnotationKey::from("key2"), AmqpValue::Int(42));
+        let mut properties = HashMap::new();
+        properties.insert(AmqpSymbol::from("key1"), AmqpValue::String("value1".to_string()));
+        properties.insert(AmqpSymbol::from("key2"), AmqpValue::Int(42));
         
         let message = Message::builder()
             .message_annotations(annotations.clone())
-            .delivery_annotations(annotations.clone())
-            .application_properties(annotations.clone())
-            .footer(annotations)
+            .delivery_annotations(annotations)
+            .application_properties(properties.clone())
+            .footer(properties)
             .build();
         
         assert!(message.message_annotations.is_some());
@@ -569,6 +913,24 @@ mod tests {
         assert!(message.footer.is_some());
     }
 
+    #[test]
+    fn test_annotation_by_code() {
+        let mut annotations = HashMap::new();
+        annotations.insert(AnnotationKey::from("x-opt-partition-key"), AmqpValue::String("p1".to_string()));
+        annotations.insert(AnnotationKey::Ulong(0x0000_0137_0000_0002), AmqpValue::Uint(7));
+
+        let message = Message::builder()
+            .message_annotations(annotations.clone())
+            .delivery_annotations(annotations)
+            .build();
+
+        assert_eq!(message.annotation_by_code(0x0000_0137_0000_0002), Some(&AmqpValue::Uint(7)));
+        assert_eq!(message.delivery_annotation_by_code(0x0000_0137_0000_0002), Some(&AmqpValue::Uint(7)));
+        assert_eq!(message.annotation("x-opt-partition-key"), Some(&AmqpValue::String("p1".to_string())));
+        assert!(message.annotation_by_code(1).is_none());
+        assert!(Message::text("plain").annotation_by_code(0x0000_0137_0000_0002).is_none());
+    }
+
     #[test]
     fn test_message_body_as_text() {
         let message = Message::text("Test text");
@@ -716,7 +1078,7 @@ mod tests {
     #[test]
     fn test_body_variants() {
         // Data body
-        let data_body = Body::Data(vec![1, 2, 3, 4]);
+        let data_body = Body::Data(vec![1, 2, 3, 4].into());
         assert!(matches!(data_body, Body::Data(_)));
         
         // Value body
@@ -727,12 +1089,12 @@ mod tests {
         let sequence_body = Body::Sequence(vec![
             AmqpValue::String("item1".to_string()),
             AmqpValue::Int(42),
-        ]);
+        ].into());
         assert!(matches!(sequence_body, Body::Sequence(_)));
         
         // Multiple body
         let multiple_body = Body::Multiple(vec![
-            Body::Data(vec![1, 2, 3]),
+            Body::Data(vec![1, 2, 3].into()),
             Body::Value(AmqpValue::String("text".to_string())),
         ]);
         assert!(matches!(multiple_body, Body::Multiple(_)));
@@ -774,12 +1136,12 @@ mod tests {
     #[test]
     fn test_message_with_complex_body() {
         let complex_body = Body::Multiple(vec![
-            Body::Data(vec![1, 2, 3]),
+            Body::Data(vec![1, 2, 3].into()),
             Body::Value(AmqpValue::String("text part".to_string())),
             Body::Sequence(vec![
                 AmqpValue::Int(100),
                 AmqpValue::Boolean(true),
-            ]),
+            ].into()),
         ]);
         
         let message = Message::builder()
@@ -797,6 +1159,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "serde")]
     #[test]
     fn test_serde_serialization() {
         let message = Message::text("Test message");
@@ -806,6 +1169,7 @@ mod tests {
         assert_eq!(message, deserialized);
     }
 
+    #[cfg(feature = "serde")]
     #[test]
     fn test_serde_deserialization() {
         let json = r#"{"body":{"Value":{"String":"test"}}}"#;
@@ -813,4 +1177,154 @@ mod tests {
         
         assert_eq!(deserialized.body_as_text(), Some("test"));
     }
-} 
\ No newline at end of file
+
+    fn assert_size_matches_encoding(message: &Message) {
+        let mut encoder = crate::codec::Encoder::new();
+        encoder.encode_message(message).unwrap();
+        assert_eq!(message.encoded_size(), encoder.finish().len());
+    }
+
+    #[test]
+    fn test_encoded_size_empty_message() {
+        let message = Message::new();
+        assert_eq!(message.encoded_size(), 0);
+        assert_size_matches_encoding(&message);
+    }
+
+    #[test]
+    fn test_encoded_size_text_and_binary() {
+        assert_size_matches_encoding(&Message::text("Hello, World!"));
+        assert_size_matches_encoding(&Message::text("x".repeat(300)));
+        assert_size_matches_encoding(&Message::binary(vec![0u8; 16]));
+        assert_size_matches_encoding(&Message::binary(vec![0u8; 1024]));
+    }
+
+    #[test]
+    fn test_encoded_size_with_header_and_properties() {
+        let mut header = Header::new();
+        header.durable = Some(true);
+        header.priority = Some(4);
+        header.ttl = Some(30000);
+
+        let message = Message::builder()
+            .header(header)
+            .body(Body::Value(AmqpValue::Uint(7)))
+            .build()
+            .with_message_id("msg-1")
+            .with_subject("orders")
+            .with_content_type("application/json");
+
+        assert_size_matches_encoding(&message);
+    }
+
+    #[test]
+    fn test_encoded_size_compound_body() {
+        let mut map = HashMap::new();
+        map.insert(AmqpSymbol::from("key"), AmqpValue::String("value".to_string()));
+        let body = Body::Value(AmqpValue::List(vec![
+            AmqpValue::Map(map.into()),
+            AmqpValue::Array(vec![AmqpValue::Long(1), AmqpValue::Long(2)].into()),
+            AmqpValue::Uuid(Uuid::new_v4()),
+            AmqpValue::Null,
+        ].into()));
+
+        assert_size_matches_encoding(&Message::builder().body(body).build());
+    }
+
+    #[test]
+    fn test_encoded_size_sequence_and_multiple_bodies() {
+        let sequence = Body::Sequence(vec![AmqpValue::Int(1), AmqpValue::Double(2.5)].into());
+        assert_size_matches_encoding(&Message::builder().body(sequence.clone()).build());
+
+        let multiple = Body::Multiple(vec![Body::Data(vec![1, 2, 3].into()), sequence]);
+        assert_size_matches_encoding(&Message::builder().body(multiple).build());
+    }
+
+    #[test]
+    fn test_sequences_skip_other_sections() {
+        let single = Message::builder().body(Body::Sequence(vec![AmqpValue::Int(1)].into())).build();
+        assert_eq!(single.sequences().collect::<Vec<_>>(), vec![&AmqpList::from(vec![AmqpValue::Int(1)])]);
+
+        let mixed = Message::builder()
+            .body(Body::Multiple(vec![
+                Body::Sequence(vec![AmqpValue::Int(1)].into()),
+                Body::Data(vec![0].into()),
+                Body::Sequence(vec![AmqpValue::Int(2)].into()),
+            ]))
+            .build();
+        let items: Vec<AmqpValue> = mixed.sequences().flat_map(|items| items.iter().cloned()).collect();
+        assert_eq!(items, vec![AmqpValue::Int(1), AmqpValue::Int(2)]);
+
+        assert_eq!(Message::text("a").sequences().count(), 0);
+    }
+
+    #[test]
+    fn test_priority_and_durable_defaults() {
+        let message = Message::text("Hello");
+        assert_eq!(message.priority(), DEFAULT_PRIORITY);
+        assert!(!message.is_durable());
+
+        let mut header = Header::new();
+        header.priority = Some(7);
+        header.durable = Some(true);
+        let message = Message::builder().header(header).build();
+        assert_eq!(message.priority(), 7);
+        assert!(message.is_durable());
+    }
+
+    #[test]
+    fn test_debug_truncates_body_and_redacts_properties() {
+        let mut props = HashMap::new();
+        props.insert(AmqpSymbol::from("Password"), AmqpValue::String("hunter2".to_string()));
+        props.insert(AmqpSymbol::from("user"), AmqpValue::String("bob".to_string()));
+        let message = Message::builder()
+            .application_properties(props)
+            .body(Body::Value(AmqpValue::String("x".repeat(500))))
+            .build();
+
+        let debug = format!("{:?}", message);
+        assert!(debug.contains("<redacted>"));
+        assert!(!debug.contains("hunter2"));
+        assert!(debug.contains("bob"));
+        assert!(debug.contains("+436 bytes"));
+
+        let full = message.dump_full();
+        assert!(full.contains("hunter2"));
+        assert!(full.contains(&"x".repeat(500)));
+    }
+
+    #[test]
+    fn test_display_summary() {
+        let message = Message::binary(vec![0u8; 4096]).with_message_id("msg-1").with_subject("report");
+        let display = message.to_string();
+        assert!(display.starts_with("Message msg-1 [report] Data(4096 bytes"));
+        assert!(display.len() < 200);
+        assert_eq!(Message::new().to_string(), "Message (no body)");
+    }
+
+    #[test]
+    fn test_clone_shares_body_and_collections() {
+        let properties: AmqpMap = (0..100)
+            .map(|i| (AmqpSymbol::from(format!("key-{}", i)), AmqpValue::Long(i)))
+            .collect();
+        let message = Message::builder()
+            .application_properties(properties)
+            .body(Body::Data(vec![7; 1 << 20].into()))
+            .build();
+
+        let (mut copy, allocations) = crate::allocations::count(|| message.clone());
+        assert!(allocations <= 1, "cloning allocated {} times", allocations);
+
+        // Changing the copy leaves the original alone
+        let copied = copy.application_properties.as_mut().unwrap();
+        copied.insert(AmqpSymbol::from("extra"), AmqpValue::Null);
+        assert_eq!(copied.len(), 101);
+        assert_eq!(message.application_properties.as_ref().unwrap().len(), 100);
+    }
+
+    #[test]
+    fn test_truncate_respects_char_boundaries() {
+        assert_eq!(truncate("héllo", 2), "h... (+5 bytes)");
+        assert_eq!(truncate("short", 10), "short");
+    }
+}
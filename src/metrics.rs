@@ -0,0 +1,216 @@
+//! AMQP 1.0 Delivery Metrics
+//!
+//! This module provides per-delivery timing and latency aggregation. Each
+//! sent message gets a [`DeliveryReceipt`] recording when it was enqueued,
+//! when it was written to the transport and when it was settled. Settled
+//! receipts can be fed into a [`LatencyMetrics`] shared by many links, which
+//! keeps a sliding window of recent latencies and reports percentiles for
+//! SLO monitoring.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::link::LinkBuilder;
+//! use dumq_amqp::message::Message;
+//! use dumq_amqp::metrics::LatencyMetrics;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let metrics = LatencyMetrics::default();
+//!
+//! let mut sender = LinkBuilder::new()
+//!     .target("orders")
+//!     .metrics(metrics.clone())
+//!     .build_sender("session-1".to_string());
+//! sender.attach().await?;
+//! sender.add_credit(1);
+//!
+//! let delivery_id = sender.send(Message::text("Hello")).await?.delivery_id();
+//! let receipt = sender.settle_with_receipt(delivery_id).unwrap();
+//! assert!(receipt.latency().is_some());
+//! assert_eq!(metrics.count(), 1);
+//! println!("p99 settle latency: {:?}", metrics.p99());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::time::{Duration, Instant};
+
+/// Number of latency samples kept by [`LatencyMetrics::default`]
+pub const DEFAULT_WINDOW: usize = 1024;
+
+/// Timing of a single delivery
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeliveryReceipt {
+    /// Delivery ID
+    pub delivery_id: u32,
+    /// When the message was handed to the sender
+    pub enqueued_at: Instant,
+    /// When the transfer was written to the transport
+    pub written_at: Option<Instant>,
+    /// When the delivery was settled
+    pub settled_at: Option<Instant>,
+}
+
+impl DeliveryReceipt {
+    /// Start a receipt for a delivery enqueued now
+    pub fn new(delivery_id: u32) -> Self {
+        DeliveryReceipt {
+            delivery_id,
+            enqueued_at: Instant::now(),
+            written_at: None,
+            settled_at: None,
+        }
+    }
+
+    /// Get the time from enqueue to settlement, once settled
+    pub fn latency(&self) -> Option<Duration> {
+        self.settled_at.map(|settled| settled.duration_since(self.enqueued_at))
+    }
+
+    /// Get the time from enqueue to the transport write, once written
+    pub fn write_latency(&self) -> Option<Duration> {
+        self.written_at.map(|written| written.duration_since(self.enqueued_at))
+    }
+
+    /// Check if the delivery is settled
+    pub fn is_settled(&self) -> bool {
+        self.settled_at.is_some()
+    }
+}
+
+/// Sliding window of settlement latencies, shared between clones
+#[derive(Debug, Clone)]
+pub struct LatencyMetrics {
+    inner: Arc<Mutex<LatencyWindow>>,
+}
+
+#[derive(Debug)]
+struct LatencyWindow {
+    capacity: usize,
+    samples: VecDeque<Duration>,
+    count: u64,
+}
+
+impl LatencyMetrics {
+    /// Create metrics keeping the most recent `window` samples
+    pub fn new(window: usize) -> Self {
+        LatencyMetrics {
+            inner: Arc::new(Mutex::new(LatencyWindow {
+                capacity: window.max(1),
+                samples: VecDeque::new(),
+                count: 0,
+            })),
+        }
+    }
+
+    /// Record a latency sample
+    pub fn record(&self, latency: Duration) {
+        let mut window = self.lock();
+        if window.samples.len() == window.capacity {
+            window.samples.pop_front();
+        }
+        window.samples.push_back(latency);
+        window.count += 1;
+    }
+
+    /// Record the latency of a settled receipt
+    pub fn record_receipt(&self, receipt: &DeliveryReceipt) {
+        if let Some(latency) = receipt.latency() {
+            self.record(latency);
+        }
+    }
+
+    /// Get the latency at quantile `q` (0.0 to 1.0) over the window
+    ///
+    /// Uses the nearest-rank method. Returns `None` if nothing was recorded.
+    pub fn percentile(&self, q: f64) -> Option<Duration> {
+        let mut samples: Vec<Duration> = self.lock().samples.iter().copied().collect();
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_unstable();
+        let rank = (q.clamp(0.0, 1.0) * samples.len() as f64).ceil() as usize;
+        Some(samples[rank.saturating_sub(1)])
+    }
+
+    /// Get the median latency
+    pub fn p50(&self) -> Option<Duration> {
+        self.percentile(0.50)
+    }
+
+    /// Get the 99th percentile latency
+    pub fn p99(&self) -> Option<Duration> {
+        self.percentile(0.99)
+    }
+
+    /// Get the number of samples recorded since creation
+    pub fn count(&self) -> u64 {
+        self.lock().count
+    }
+
+    /// Discard all samples
+    pub fn reset(&self) {
+        let mut window = self.lock();
+        window.samples.clear();
+        window.count = 0;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LatencyWindow> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for LatencyMetrics {
+    fn default() -> Self {
+        Self::new(DEFAULT_WINDOW)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_receipt_latency() {
+        let mut receipt = DeliveryReceipt::new(1);
+        assert!(receipt.latency().is_none());
+        assert!(!receipt.is_settled());
+
+        receipt.written_at = Some(receipt.enqueued_at + Duration::from_millis(2));
+        receipt.settled_at = Some(receipt.enqueued_at + Duration::from_millis(5));
+        assert_eq!(receipt.write_latency(), Some(Duration::from_millis(2)));
+        assert_eq!(receipt.latency(), Some(Duration::from_millis(5)));
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let metrics = LatencyMetrics::default();
+        assert!(metrics.p50().is_none());
+
+        for ms in 1..=100 {
+            metrics.record(Duration::from_millis(ms));
+        }
+        assert_eq!(metrics.p50(), Some(Duration::from_millis(50)));
+        assert_eq!(metrics.p99(), Some(Duration::from_millis(99)));
+        assert_eq!(metrics.percentile(1.0), Some(Duration::from_millis(100)));
+        assert_eq!(metrics.count(), 100);
+    }
+
+    #[test]
+    fn test_window_drops_oldest_samples() {
+        let metrics = LatencyMetrics::new(2);
+        metrics.record(Duration::from_secs(10));
+        metrics.record(Duration::from_millis(1));
+        metrics.record(Duration::from_millis(2));
+
+        assert_eq!(metrics.percentile(1.0), Some(Duration::from_millis(2)));
+        assert_eq!(metrics.count(), 3);
+
+        metrics.reset();
+        assert_eq!(metrics.count(), 0);
+        assert!(metrics.p99().is_none());
+    }
+}
@@ -6,7 +6,7 @@
 //! # Overview
 //!
 //! The network layer handles:
-//! - TCP connection establishment and management
+//! - TCP connection establishment and management, over TLS with the `tls` feature
 //! - AMQP protocol negotiation
 //! - Frame encoding and decoding
 //! - Message transmission and reception
@@ -16,7 +16,7 @@
 //!
 //! ## Basic Network Connection
 //!
-//! ```rust
+//! ```rust,no_run
 //! use dumq_amqp::network::{NetworkConnection, NetworkBuilder};
 //! use tokio::time::Duration;
 //!
@@ -38,14 +38,30 @@
 //! }
 //! ```
 
-use crate::{AmqpError, AmqpResult, AmqpValue, AmqpSymbol};
+use crate::{AmqpCondition, AmqpError, AmqpResult, AmqpValue, AmqpSymbol};
+use crate::capability;
 use crate::codec::{Encoder, Decoder};
-use crate::transport::{Frame, FrameHeader, FrameType, Transport, TransportBuilder};
-use crate::types::AmqpMap;
-use std::collections::HashMap;
+use crate::connection::Hostnames;
+#[cfg(feature = "experimental-compression")]
+use crate::compression::{self, CompressionConfig};
+use crate::heartbeat::{HeartbeatEvent, HeartbeatMonitor, HeartbeatStats};
+use crate::performative::{self, Close, Open, Performative, Transfer};
+use crate::reconnect::{self, ReconnectEvent, ReconnectPolicy};
+use crate::sasl::{self, SaslCredentials};
+use crate::tasks::TaskKind;
+use crate::transport::{Frame, FrameHeader, FrameRecorder, FrameType, ProtocolHeader, ProtocolNegotiator, Transport, TransportBuilder, TransportReader, TransportStats, TransportWriter};
+use crate::tuning::{self, TuningHandle, Tunables};
+use crate::types::{AmqpMap, Role};
+use crate::watchdog::{Watchdog, WatchdogConfig, WatchdogEvent};
+use bytes::{BufMut, BytesMut};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tokio::time::{sleep};
-use uuid::Uuid;
+use crate::logging;
+use crate::ids;
+
+/// Size of the fixed frame header
+const FRAME_HEADER_SIZE: usize = 8;
 
 /// Network connection state
 #[derive(Debug, Clone, PartialEq)]
@@ -66,6 +82,27 @@ pub enum NetworkState {
     Error(String),
 }
 
+/// Step of the connection handshake, named after what is awaited from the peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandshakeStage {
+    /// SASL protocol header, mechanisms and outcome
+    Sasl,
+    /// AMQP protocol header
+    ProtocolHeader,
+    /// Open performative
+    Open,
+}
+
+impl std::fmt::Display for HandshakeStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            HandshakeStage::Sasl => "SASL exchange",
+            HandshakeStage::ProtocolHeader => "protocol header",
+            HandshakeStage::Open => "Open",
+        })
+    }
+}
+
 /// Network connection configuration
 #[derive(Debug, Clone)]
 pub struct NetworkConfig {
@@ -77,6 +114,8 @@ pub struct NetworkConfig {
     pub timeout: Duration,
     /// Keep-alive interval
     pub keep_alive: Duration,
+    /// Whether to run no keep-alive task at all, see [`NetworkBuilder::keep_alive_disabled`]
+    pub keep_alive_disabled: bool,
     /// Maximum frame size
     pub max_frame_size: u32,
     /// Channel maximum
@@ -87,6 +126,29 @@ pub struct NetworkConfig {
     pub container_id: String,
     /// Connection properties
     pub properties: HashMap<String, AmqpValue>,
+    /// Hostnames for the Open, SASL init and TLS server name, if not `hostname`,
+    /// see [`ConnectionConfig::hostnames`](crate::connection::ConnectionConfig::hostnames)
+    pub hostnames: Option<Hostnames>,
+    /// Consecutive heartbeat intervals without a peer frame before reporting
+    pub missed_heartbeat_threshold: u32,
+    /// Supervision of the keep-alive task
+    pub watchdog: WatchdogConfig,
+    /// Backoff between attempts of [`NetworkConnection::reconnect`]
+    pub reconnect: Option<ReconnectPolicy>,
+    /// Credentials for a SASL layer before the AMQP header, if the peer requires one
+    pub sasl: Option<SaslCredentials>,
+    /// TLS to secure the connection with, if the peer expects it
+    #[cfg(feature = "tls")]
+    pub tls: Option<crate::tls::TlsConnector>,
+    /// Capture of the frames exchanged, from the first SASL or Open frame on
+    pub frame_recorder: Option<FrameRecorder>,
+    /// Capabilities offered to the peer in Open
+    pub offered_capabilities: Vec<AmqpSymbol>,
+    /// Capabilities asked of the peer in Open
+    pub desired_capabilities: Vec<AmqpSymbol>,
+    /// Transfer payload compression to offer the peer
+    #[cfg(feature = "experimental-compression")]
+    pub compression: Option<CompressionConfig>,
 }
 
 impl Default for NetworkConfig {
@@ -96,11 +158,24 @@ impl Default for NetworkConfig {
             port: 5672,
             timeout: Duration::from_secs(30),
             keep_alive: Duration::from_secs(60),
+            keep_alive_disabled: false,
             max_frame_size: 65536,
             channel_max: 1000,
             idle_timeout: Duration::from_secs(60),
-            container_id: format!("dumq-amqp-{}", Uuid::new_v4().to_string()[..8].to_string()),
+            container_id: format!("dumq-amqp-{}", ids::next_short_id()),
             properties: HashMap::new(),
+            hostnames: None,
+            missed_heartbeat_threshold: 2,
+            watchdog: WatchdogConfig::default(),
+            reconnect: None,
+            sasl: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+            frame_recorder: None,
+            offered_capabilities: Vec::new(),
+            desired_capabilities: Vec::new(),
+            #[cfg(feature = "experimental-compression")]
+            compression: None,
         }
     }
 }
@@ -111,29 +186,73 @@ pub struct NetworkConnection {
     state: NetworkState,
     /// Connection configuration
     config: NetworkConfig,
-    /// Transport layer
+    /// Transport layer, until the connection is ready
     transport: Option<Transport>,
+    /// Receiving half of the transport once ready
+    reader: Option<TransportReader>,
+    /// Sending half of the transport once ready, shared with the keep-alive task
+    writer: Option<Arc<SharedWriter>>,
     /// Connection ID
     id: String,
     /// Next channel number
     next_channel: u16,
-    /// Last activity timestamp
+    /// Time the connection was established (transport I/O is tracked separately)
     last_activity: Instant,
     /// Keep-alive task handle
     keep_alive_handle: Option<tokio::task::JoinHandle<()>>,
+    /// Knobs that can be changed while the connection is up
+    tuning: TuningHandle,
+    /// Heartbeat accounting shared with the keep-alive task
+    heartbeat: HeartbeatMonitor,
+    /// Supervisor restarting the keep-alive task if it panics
+    watchdog: Watchdog,
+    /// Open received from the peer during negotiation
+    remote_open: Option<Open>,
+    /// Progress of reconnecting
+    reconnect_events: tokio::sync::broadcast::Sender<ReconnectEvent>,
+    /// Sessions, links and unsettled transfers to replay after reconnecting
+    replay: Replay,
+    /// Frames read while replaying, not yet received by the caller
+    pending: VecDeque<Frame>,
+    /// Compression in use, once both peers announced it
+    #[cfg(feature = "experimental-compression")]
+    compression: Option<CompressionConfig>,
 }
 
 impl NetworkConnection {
     /// Create a new network connection
     pub fn new(config: NetworkConfig) -> Self {
+        let tuning = TuningHandle::new(Tunables {
+            heartbeat_interval: config.keep_alive,
+            ..Default::default()
+        });
+        tuning.set_heartbeat_limit(tuning::heartbeat_limit_for(config.idle_timeout));
+        let watchdog = Watchdog::new(config.watchdog.clone());
+        Self::with_handles(config, format!("conn-{}", ids::next_short_id()), tuning, watchdog)
+    }
+
+    /// Create a network connection under the ID, tuning and watchdog of its owner
+    pub(crate) fn with_handles(config: NetworkConfig, id: String, tuning: TuningHandle, watchdog: Watchdog) -> Self {
+        let heartbeat = HeartbeatMonitor::new(config.missed_heartbeat_threshold);
         NetworkConnection {
             state: NetworkState::Disconnected,
             config,
             transport: None,
-            id: format!("conn-{}", Uuid::new_v4().to_string()[..8].to_string()),
+            reader: None,
+            writer: None,
+            id,
             next_channel: 0,
             last_activity: Instant::now(),
             keep_alive_handle: None,
+            tuning,
+            heartbeat,
+            watchdog,
+            remote_open: None,
+            reconnect_events: reconnect::event_channel(),
+            replay: Replay::default(),
+            pending: VecDeque::new(),
+            #[cfg(feature = "experimental-compression")]
+            compression: None,
         }
     }
 
@@ -146,12 +265,25 @@ impl NetworkConnection {
         self.state = NetworkState::Connecting;
 
         // Create transport connection
-        let transport = TransportBuilder::new()
+        let builder = TransportBuilder::new()
             .hostname(self.config.hostname.clone())
             .port(self.config.port)
             .timeout(self.config.timeout)
-            .connect()
-            .await?;
+            .max_frame_size(self.config.max_frame_size);
+        #[cfg(feature = "tls")]
+        let builder = match &self.config.tls {
+            Some(connector) => builder.tls(connector.clone()),
+            None => builder,
+        };
+        #[cfg(feature = "tls")]
+        let builder = match self.config.hostnames.as_ref().and_then(|hostnames| hostnames.sni.clone()) {
+            Some(sni) => builder.server_name(sni),
+            None => builder,
+        };
+        let mut transport = builder.connect().await?;
+        if let Some(recorder) = &self.config.frame_recorder {
+            transport.record_frames(recorder.clone());
+        }
 
         self.transport = Some(transport);
         self.state = NetworkState::Connected;
@@ -161,6 +293,15 @@ impl NetworkConnection {
     }
 
     /// Negotiate AMQP protocol
+    ///
+    /// Authenticates first if SASL credentials are configured, then exchanges
+    /// protocol headers and Open performatives with the peer. A peer that
+    /// answers with a different protocol header fails with
+    /// [`AmqpError::ProtocolMismatch`]; a peer requiring SASL answers the AMQP
+    /// header with the SASL one. A peer that does not complete the handshake
+    /// within the configured timeout fails with [`AmqpError::HandshakeTimeout`],
+    /// naming the stage it stalled at. The peer's Open is kept, see
+    /// [`NetworkConnection::remote_open`].
     pub async fn negotiate_protocol(&mut self) -> AmqpResult<()> {
         if self.state != NetworkState::Connected {
             return Err(AmqpError::connection("Not connected"));
@@ -169,34 +310,68 @@ impl NetworkConnection {
         let transport = self.transport.as_mut()
             .ok_or_else(|| AmqpError::connection("No transport available"))?;
 
-        // Send AMQP protocol header
-        Self::send_protocol_header(transport).await?;
+        let mut stage = HandshakeStage::Sasl;
+        let handshake = Self::handshake(transport, &self.config, &mut stage);
+        let result = tokio::time::timeout(self.config.timeout, handshake)
+            .await
+            .unwrap_or_else(|_| Err(AmqpError::handshake_timeout(stage, self.config.timeout)));
+        let remote = match result {
+            Ok(remote) => remote,
+            Err(e) => {
+                self.state = NetworkState::Error(e.to_string());
+                return Err(e);
+            }
+        };
+        self.heartbeat.record_peer_frame();
+
+        // Our heartbeats must arrive within the peer's idle timeout too
+        if let Some(millis) = remote.idle_time_out {
+            let peer_limit = tuning::heartbeat_limit_for(Duration::from_millis(millis as u64));
+            let limit = match (self.tuning.heartbeat_limit(), peer_limit) {
+                (Some(local), Some(peer)) => Some(local.min(peer)),
+                (local, peer) => local.or(peer),
+            };
+            self.tuning.set_heartbeat_limit(limit);
+        }
+        // An idle timeout of 0 is no timeout, so such a peer needs no heartbeats
+        let peer_needs_heartbeats = remote.idle_time_out.is_some_and(|millis| millis > 0);
+        #[cfg(feature = "experimental-compression")]
+        {
+            self.compression = self.config.compression.clone().filter(|config| config.accepted_by(&remote));
+        }
+        self.remote_open = Some(remote);
 
-        // Send Open performative
-        Self::send_open(transport, &self.config).await?;
+        // From here on the keep-alive task writes heartbeats alongside the connection
+        if let Some(transport) = self.transport.take() {
+            let (reader, writer) = transport.into_split();
+            self.reader = Some(reader);
+            self.writer = Some(Arc::new(SharedWriter::new(writer)));
+        }
 
-        // Start keep-alive task
-        self.start_keep_alive();
+        // The keep-alive task sends heartbeats and watches for the peer's
+        if !self.config.keep_alive_disabled && (peer_needs_heartbeats || !self.config.idle_timeout.is_zero()) {
+            self.start_keep_alive(peer_needs_heartbeats);
+        }
 
         self.state = NetworkState::Ready;
-        self.last_activity = Instant::now();
 
         Ok(())
     }
 
     /// Send a frame
+    ///
+    /// With a [`ReconnectPolicy`], the sessions, links and unsettled
+    /// transfers the frame adds are kept to be replayed by
+    /// [`NetworkConnection::reconnect`].
     pub async fn send_frame(&mut self, frame: Frame) -> AmqpResult<()> {
         if self.state != NetworkState::Ready {
             return Err(AmqpError::connection("Connection not ready"));
         }
 
-        let transport = self.transport.as_mut()
-            .ok_or_else(|| AmqpError::connection("No transport available"))?;
-
-        transport.send_frame(frame).await?;
-        self.last_activity = Instant::now();
-
-        Ok(())
+        if self.config.reconnect.is_some() {
+            self.replay.sent(&frame);
+        }
+        self.writer()?.send_frame(frame).await
     }
 
     /// Receive a frame
@@ -204,28 +379,49 @@ impl NetworkConnection {
         if self.state != NetworkState::Ready {
             return Err(AmqpError::connection("Connection not ready"));
         }
+        if let Some(frame) = self.pending.pop_front() {
+            return Ok(frame);
+        }
 
-        let transport = self.transport.as_mut()
+        let frame = self.read_frame().await?;
+        if self.config.reconnect.is_some() {
+            self.replay.received(&frame);
+        }
+        Ok(frame)
+    }
+
+    /// Read the next frame from the transport
+    async fn read_frame(&mut self) -> AmqpResult<Frame> {
+        let reader = self.reader.as_mut()
             .ok_or_else(|| AmqpError::connection("No transport available"))?;
 
-        let frame = transport.receive_frame().await?;
-        self.last_activity = Instant::now();
+        let frame = reader.receive_frame().await?;
+        self.heartbeat.record_peer_frame();
 
         Ok(frame)
     }
 
     /// Send a message
     pub async fn send_message(&mut self, channel: u16, message: &crate::message::Message) -> AmqpResult<()> {
-        // Encode message
-        let mut encoder = Encoder::new();
-        encoder.encode_message(message)?;
-        let payload = encoder.finish();
+        if self.state != NetworkState::Ready {
+            return Err(AmqpError::connection("Connection not ready"));
+        }
 
-        // Create frame
-        let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, channel);
-        let frame = Frame::new(header, payload);
+        #[cfg(feature = "experimental-compression")]
+        if let Some(compression) = &self.compression {
+            let mut encoder = Encoder::new();
+            encoder.encode_message(message)?;
+            let frame = compression.frame(FrameType::AMQP as u8, channel, encoder.finish());
+            return self.writer()?.send_frame(frame).await;
+        }
+
+        let buffer = encode_message_frame(channel, message)?;
+        self.writer()?.send_encoded_frame(&buffer).await
+    }
 
-        self.send_frame(frame).await
+    fn writer(&self) -> AmqpResult<&SharedWriter> {
+        self.writer.as_deref()
+            .ok_or_else(|| AmqpError::connection("No transport available"))
     }
 
     /// Receive a message
@@ -233,7 +429,14 @@ impl NetworkConnection {
         let frame = self.receive_frame().await?;
         
         if frame.header.frame_type == FrameType::AMQP as u8 {
-            let mut decoder = Decoder::new(frame.payload);
+            #[cfg(feature = "experimental-compression")]
+            let payload = match &self.compression {
+                Some(compression) => compression.payload(frame)?,
+                None => frame.payload,
+            };
+            #[cfg(not(feature = "experimental-compression"))]
+            let payload = frame.payload;
+            let mut decoder = Decoder::new(payload);
             let message = decoder.decode_message()?;
             Ok(Some(message))
         } else {
@@ -241,6 +444,36 @@ impl NetworkConnection {
         }
     }
 
+    /// Split into a reader and a writer that can be used at the same time
+    ///
+    /// The connection must be ready. The reader keeps the heartbeat
+    /// accounting up to date, and the writer owns the keep-alive task and
+    /// closes the connection.
+    pub fn split(mut self) -> AmqpResult<(NetworkReader, NetworkWriter)> {
+        if self.state != NetworkState::Ready {
+            return Err(AmqpError::connection("Connection not ready"));
+        }
+        let (read, write) = self.reader.take().zip(self.writer.take())
+            .ok_or_else(|| AmqpError::connection("No transport available"))?;
+
+        let reader = NetworkReader {
+            transport: read,
+            pending: std::mem::take(&mut self.pending),
+            id: self.id.clone(),
+            heartbeat: self.heartbeat.clone(),
+            #[cfg(feature = "experimental-compression")]
+            compression: self.compression.clone(),
+        };
+        let writer = NetworkWriter {
+            transport: write,
+            id: self.id.clone(),
+            keep_alive_handle: self.keep_alive_handle.take(),
+            #[cfg(feature = "experimental-compression")]
+            compression: self.compression.take(),
+        };
+        Ok((reader, writer))
+    }
+
     /// Disconnect from the remote host
     pub async fn disconnect(&mut self) -> AmqpResult<()> {
         if self.state == NetworkState::Disconnected {
@@ -254,11 +487,10 @@ impl NetworkConnection {
             handle.abort();
         }
 
-        // Send Close performative if connected
-        if let Some(transport) = &mut self.transport {
-            if self.state == NetworkState::Ready {
-                Self::send_close(transport).await?;
-            }
+        // Send Close performative if the connection got as far as being ready
+        self.reader = None;
+        if let Some(writer) = self.writer.take() {
+            writer.close(None).await?;
         }
 
         // Close transport
@@ -266,11 +498,131 @@ impl NetworkConnection {
             transport.shutdown().await?;
         }
 
+        self.replay = Replay::default();
+        self.pending.clear();
         self.state = NetworkState::Closed;
 
         Ok(())
     }
 
+    /// Establish the connection again after its transport failed
+    ///
+    /// What is left of the old transport is dropped without a Close, then
+    /// the connection connects and negotiates again, backing off between
+    /// attempts as the configured [`ReconnectPolicy`] says, or trying once
+    /// without one.
+    ///
+    /// With a policy, the Begin, Attach and last Flow frames sent on the old
+    /// transport are sent again, and each transfer the peer had not settled
+    /// is resent once the peer grants its link credit, or until the
+    /// connection timeout passes. The peer's answers read meanwhile are
+    /// returned by [`NetworkConnection::receive_frame`] as usual. The
+    /// [`ReconnectEvent::Reconnected`] event counts the transfers resent.
+    pub async fn reconnect(&mut self) -> AmqpResult<()> {
+        let policy = self.config.reconnect.clone().unwrap_or(ReconnectPolicy {
+            max_attempts: Some(1),
+            initial_backoff: Duration::ZERO,
+            jitter: 0.0,
+            ..Default::default()
+        });
+
+        let mut failures = Vec::new();
+        loop {
+            let attempt = failures.len() as u32 + 1;
+            if !policy.allows(attempt) {
+                let _ = self.reconnect_events.send(ReconnectEvent::GaveUp { attempts: failures.len() as u32 });
+                return Err(AmqpError::retries_exhausted(failures));
+            }
+            let delay = policy.backoff(attempt);
+            let _ = self.reconnect_events.send(ReconnectEvent::Attempting { attempt, delay });
+            tokio::time::sleep(delay).await;
+
+            self.reset();
+            let result = match self.connect().await {
+                Ok(()) => self.negotiate_protocol().await,
+                Err(e) => Err(e),
+            };
+            let result = match result {
+                Ok(()) => self.replay().await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(resent) => {
+                    logging::info!("Connection {} reconnected after {} attempts, resending {} unsettled transfers", self.id, attempt, resent);
+                    let _ = self.reconnect_events.send(ReconnectEvent::Reconnected { attempts: attempt, resent });
+                    return Ok(());
+                }
+                Err(e) => {
+                    logging::debug!("Reconnect attempt {} of connection {} failed: {}", attempt, self.id, e);
+                    failures.push(e.to_string());
+                }
+            }
+        }
+    }
+
+    /// Begin the sessions and attach the links of the old transport again
+    ///
+    /// Returns the number of unsettled transfers resent.
+    async fn replay(&mut self) -> AmqpResult<usize> {
+        self.replay.restart();
+        self.pending.clear();
+        let frames: Vec<Frame> = self.replay.begins.values()
+            .chain(self.replay.attaches.values().map(|(_, frame)| frame))
+            .chain(self.replay.flows.values())
+            .cloned()
+            .collect();
+        for frame in frames {
+            self.send_frame(frame).await?;
+        }
+
+        // Transfers wait for the peer's credit on the re-attached link
+        let mut awaiting_credit: BTreeSet<(u16, u32)> = self.replay.unsettled.iter()
+            .map(|((channel, _), (handle, _))| (*channel, *handle))
+            .collect();
+        let deadline = tokio::time::Instant::now() + self.config.timeout;
+        let mut resent = 0;
+        while !awaiting_credit.is_empty() {
+            let Ok(frame) = tokio::time::timeout_at(deadline, self.read_frame()).await else {
+                logging::warn!("Connection {} got no credit to resend on {} links", self.id, awaiting_credit.len());
+                break;
+            };
+            let frame = frame?;
+            let credited = self.replay.received(&frame);
+            self.pending.push_back(frame);
+            let Some(link) = credited.filter(|link| awaiting_credit.remove(link)) else {
+                continue;
+            };
+            let deliveries: Vec<Vec<Frame>> = self.replay.unsettled.iter()
+                .filter(|((channel, _), (handle, _))| (*channel, *handle) == link)
+                .map(|(_, (_, frames))| frames.clone())
+                .collect();
+            for frames in deliveries {
+                for frame in frames {
+                    self.send_frame(frame).await?;
+                }
+                resent += 1;
+            }
+        }
+        Ok(resent)
+    }
+
+    /// Drop the transport and keep-alive without closing, ready to connect again
+    fn reset(&mut self) {
+        if let Some(handle) = self.keep_alive_handle.take() {
+            handle.abort();
+        }
+        self.transport = None;
+        self.reader = None;
+        self.writer = None;
+        self.remote_open = None;
+        self.state = NetworkState::Disconnected;
+    }
+
+    /// Subscribe to the attempts of [`NetworkConnection::reconnect`]
+    pub fn reconnect_events(&self) -> tokio::sync::broadcast::Receiver<ReconnectEvent> {
+        self.reconnect_events.subscribe()
+    }
+
     /// Get connection state
     pub fn state(&self) -> &NetworkState {
         &self.state
@@ -293,16 +645,102 @@ impl NetworkConnection {
         channel
     }
 
+    /// Get a handle for changing runtime knobs on this connection
+    ///
+    /// Changes to the heartbeat interval take effect on the next tick.
+    pub fn tuning(&self) -> TuningHandle {
+        self.tuning.clone()
+    }
+
+    /// Get heartbeat statistics
+    pub fn heartbeat_stats(&self) -> HeartbeatStats {
+        self.heartbeat.stats()
+    }
+
+    /// Subscribe to missed-heartbeat events
+    pub fn heartbeat_events(&self) -> tokio::sync::broadcast::Receiver<HeartbeatEvent> {
+        self.heartbeat.subscribe()
+    }
+
+    /// Subscribe to panics and restarts of the keep-alive task
+    pub fn watchdog_events(&self) -> tokio::sync::broadcast::Receiver<WatchdogEvent> {
+        self.watchdog.subscribe()
+    }
+
+    /// Get transport read/write statistics
+    pub fn transport_stats(&self) -> Option<TransportStats> {
+        if let Some(transport) = &self.transport {
+            return Some(transport.stats());
+        }
+        let (read, write) = (self.reader.as_ref()?.stats(), self.writer.as_ref()?.stats());
+        Some(TransportStats {
+            bytes_written: write.bytes_written,
+            frames_out: write.frames_out,
+            last_write: write.last_write,
+            ..read
+        })
+    }
+
+    /// Get the time of the last activity on the connection
+    pub fn last_activity(&self) -> Instant {
+        self.transport_stats()
+            .and_then(|stats| stats.last_activity())
+            .map_or(self.last_activity, |activity| activity.max(self.last_activity))
+    }
+
     /// Check if connection is idle
     pub fn is_idle(&self) -> bool {
-        self.last_activity.elapsed() > self.config.idle_timeout
+        self.last_activity().elapsed() > self.config.idle_timeout
     }
 
-    /// Send AMQP protocol header
-    async fn send_protocol_header(transport: &mut Transport) -> AmqpResult<()> {
-        let header = crate::transport::constants::AMQP_HEADER;
-        transport.send_raw(header).await?;
-        Ok(())
+    /// Get the Open received from the peer, once negotiated
+    pub fn remote_open(&self) -> Option<&Open> {
+        self.remote_open.as_ref()
+    }
+
+    /// Get the peer's container ID, once negotiated
+    pub fn remote_container_id(&self) -> Option<&str> {
+        self.remote_open.as_ref().map(|open| open.container_id.as_str())
+    }
+
+    /// Check if transfer payloads are compressed, which both peers must have announced
+    #[cfg(feature = "experimental-compression")]
+    pub fn compression_active(&self) -> bool {
+        self.compression.is_some()
+    }
+
+    /// Check if the peer offered a capability, once negotiated
+    pub fn peer_offers(&self, capability: &str) -> bool {
+        self.remote_open.as_ref().is_some_and(|open| open.offers(capability))
+    }
+
+    /// Get the largest frame either side may send (the smaller of local and remote)
+    pub fn negotiated_max_frame_size(&self) -> u32 {
+        self.remote_open
+            .as_ref()
+            .map_or(self.config.max_frame_size, |open| open.max_frame_size.min(self.config.max_frame_size))
+    }
+
+    /// Get the highest usable channel number (the smaller of local and remote)
+    pub fn negotiated_channel_max(&self) -> u16 {
+        self.remote_open
+            .as_ref()
+            .map_or(self.config.channel_max, |open| open.channel_max.min(self.config.channel_max))
+    }
+
+    /// Run the SASL layer if configured, then exchange headers and Opens
+    async fn handshake(transport: &mut Transport, config: &NetworkConfig, stage: &mut HandshakeStage) -> AmqpResult<Open> {
+        if let Some(credentials) = &config.sasl {
+            *stage = HandshakeStage::Sasl;
+            let hostname = config.hostnames.as_ref().map_or(&config.hostname, |hostnames| &hostnames.sasl);
+            sasl::authenticate(transport, credentials, hostname).await?;
+        }
+
+        *stage = HandshakeStage::ProtocolHeader;
+        ProtocolNegotiator::exchange_header(transport, ProtocolHeader::AMQP).await?;
+        *stage = HandshakeStage::Open;
+        Self::send_open(transport, config).await?;
+        Self::receive_open(transport, &config.container_id).await
     }
 
     /// Send Open performative
@@ -311,26 +749,24 @@ impl NetworkConnection {
         for (key, value) in &config.properties {
             properties.insert(AmqpSymbol::from(key.clone()), value.clone());
         }
+        #[cfg(feature = "experimental-compression")]
+        if let Some(compression) = &config.compression {
+            properties.insert(AmqpSymbol::from(compression::COMPRESSION_PROPERTY), compression.property());
+        }
 
-        // Create Open performative
-        let _open_performative = vec![
-            0x00, // Descriptor: Open
-            0x00, 0x00, 0x00, 0x00, // Size placeholder
-            // Container ID
-            0xa1, // String type
-        ];
-
-        let mut encoder = Encoder::new();
-        encoder.encode_value(&AmqpValue::String(config.container_id.clone()))?;
-        encoder.encode_value(&AmqpValue::String("".to_string()))?; // Hostname
-        encoder.encode_value(&AmqpValue::Uint(0))?; // Max frame size
-        encoder.encode_value(&AmqpValue::Ushort(config.channel_max))?; // Channel max
-        encoder.encode_value(&AmqpValue::Uint(config.idle_timeout.as_millis() as u32))?; // Idle timeout
-        encoder.encode_value(&AmqpValue::Map(properties))?; // Properties
-        encoder.encode_value(&AmqpValue::List(vec![]))?; // Offered capabilities
-        encoder.encode_value(&AmqpValue::List(vec![]))?; // Desired capabilities
-
-        let payload = encoder.finish();
+        let idle_time_out = config.idle_timeout.as_millis().min(u32::MAX as u128) as u32;
+        let open = Open {
+            container_id: config.container_id.clone(),
+            hostname: Some(config.hostnames.as_ref().map_or(&config.hostname, |hostnames| &hostnames.open).clone()),
+            max_frame_size: config.max_frame_size,
+            channel_max: config.channel_max,
+            idle_time_out: Some(idle_time_out).filter(|millis| *millis > 0),
+            offered_capabilities: config.offered_capabilities.clone(),
+            desired_capabilities: config.desired_capabilities.clone(),
+            properties,
+        };
+
+        let payload = open.encode()?;
         let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, 0);
         let frame = Frame::new(header, payload);
 
@@ -338,38 +774,547 @@ impl NetworkConnection {
         Ok(())
     }
 
-    /// Send Close performative
-    async fn send_close(transport: &mut Transport) -> AmqpResult<()> {
-        let mut encoder = Encoder::new();
-        encoder.encode_value(&AmqpValue::String("".to_string()))?; // Error condition
-        encoder.encode_value(&AmqpValue::String("".to_string()))?; // Error description
+    /// Receive the peer's Open, or the Close it refused the connection with
+    ///
+    /// A peer refusing the connection may first send an Open marked
+    /// `connection-establishment-failed`; the Close with the reason follows.
+    pub(crate) async fn receive_open(transport: &mut Transport, container_id: &str) -> AmqpResult<Open> {
+        let payload = Self::receive_amqp_payload(transport).await?;
+        let (code, _) = Decoder::new(payload.clone()).decode_described_header()?;
+        if code == performative::descriptor::CLOSE {
+            return Err(Self::refusal_error(Close::decode(&payload)?, container_id));
+        }
 
-        let payload = encoder.finish();
-        let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, 0);
-        let frame = Frame::new(header, payload);
+        let open = Open::decode(&payload)?;
+        let failed = open.properties.get(&AmqpSymbol::from(capability::CONNECTION_ESTABLISHMENT_FAILED));
+        if failed == Some(&AmqpValue::Boolean(true)) {
+            let payload = Self::receive_amqp_payload(transport).await?;
+            return Err(Self::refusal_error(Close::decode(&payload)?, container_id));
+        }
+        Ok(open)
+    }
 
-        transport.send_frame(frame).await?;
-        Ok(())
+    pub(crate) async fn receive_amqp_payload(transport: &mut Transport) -> AmqpResult<Vec<u8>> {
+        let frame = transport.receive_frame().await?;
+        if frame.header.frame_type != FrameType::AMQP as u8 {
+            return Err(AmqpError::protocol(format!(
+                "Expected AMQP frame, got frame type {}",
+                frame.header.frame_type
+            )));
+        }
+        Ok(frame.payload)
     }
 
-    /// Start keep-alive task
-    fn start_keep_alive(&mut self) {
-        let keep_alive_interval = self.config.keep_alive;
+    fn refusal_error(close: Close, container_id: &str) -> AmqpError {
+        match close.error {
+            Some(error) if capability::is_refusal(&error) => AmqpError::container_in_use(container_id),
+            Some(error) => AmqpError::amqp_protocol(
+                error.condition,
+                error.description.unwrap_or_else(|| "Peer closed the connection during open".to_string()),
+            ),
+            None => AmqpError::connection("Peer closed the connection during open"),
+        }
+    }
 
-        let handle = tokio::spawn(async move {
-            let mut interval = tokio::time::interval(keep_alive_interval);
-            loop {
-                interval.tick().await;
-                // Send heartbeat frame
-                // This is a simplified implementation
-                sleep(Duration::from_millis(100)).await;
-            }
+    /// Start keep-alive task, sending heartbeats only if the peer needs them
+    ///
+    /// The task is supervised and started afresh if it panics.
+    fn start_keep_alive(&mut self, send_heartbeats: bool) {
+        let Some(writer) = self.writer.clone() else {
+            return;
+        };
+        let knobs = self.tuning.subscribe();
+        let heartbeat = self.heartbeat.clone();
+        let idle_timeout = self.config.idle_timeout;
+
+        let handle = self.watchdog.supervise_restartable(TaskKind::KeepAlive, &self.id, move || {
+            keep_alive(knobs.clone(), heartbeat.clone(), writer.clone(), send_heartbeats, idle_timeout)
         });
 
         self.keep_alive_handle = Some(handle);
     }
 }
 
+/// Send heartbeats and watch the peer's until the tuning handle goes away
+///
+/// An empty frame is sent each interval in which nothing else was written.
+/// The interval is bounded by half the peer's idle timeout, see
+/// [`tuning::heartbeat_limit_for`]. A peer silent for longer than our own
+/// idle timeout gets a Close with `amqp:resource-limit-exceeded`.
+async fn keep_alive(
+    mut knobs: tokio::sync::watch::Receiver<Tunables>,
+    heartbeat: HeartbeatMonitor,
+    writer: Arc<SharedWriter>,
+    send_heartbeats: bool,
+    idle_timeout: Duration,
+) {
+    let mut interval = heartbeat_interval(knobs.borrow_and_update().heartbeat_interval);
+    loop {
+        let silence = heartbeat.peer_silence();
+        let idle_deadline = tokio::time::Instant::now() + idle_timeout.saturating_sub(silence);
+        tokio::select! {
+            _ = interval.tick() => {
+                heartbeat.tick();
+                if send_heartbeats {
+                    match writer.send_heartbeat(interval.period()).await {
+                        Ok(true) => heartbeat.record_sent(),
+                        Ok(false) => {}
+                        Err(e) => {
+                            logging::debug!("Stopping heartbeats: {}", e);
+                            return;
+                        }
+                    }
+                }
+            }
+            _ = tokio::time::sleep_until(idle_deadline), if !idle_timeout.is_zero() => {
+                let silence = heartbeat.peer_silence();
+                if silence < idle_timeout {
+                    continue;
+                }
+                heartbeat.record_timeout(silence);
+                let description = format!("No frames received for {:?}, beyond the idle timeout of {:?}", silence, idle_timeout);
+                let error = crate::types::AmqpError::new(AmqpCondition::AmqpErrorResourceLimitExceeded)
+                    .with_description(description);
+                if let Err(e) = writer.close(Some(error)).await {
+                    logging::debug!("Failed to close idle connection: {}", e);
+                }
+                return;
+            }
+            changed = knobs.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+                let period = knobs.borrow_and_update().heartbeat_interval;
+                if period != interval.period() {
+                    interval = heartbeat_interval(period);
+                }
+            }
+        }
+    }
+}
+
+/// Sending half of a ready connection, shared with its keep-alive task
+#[derive(Debug)]
+struct SharedWriter {
+    transport: tokio::sync::Mutex<TransportWriter>,
+    /// Write statistics as of the last write
+    stats: Mutex<TransportStats>,
+    /// Close we sent, once the connection is closed
+    closed: Mutex<Option<Close>>,
+}
+
+impl SharedWriter {
+    fn new(transport: TransportWriter) -> Self {
+        SharedWriter {
+            stats: Mutex::new(transport.stats()),
+            transport: tokio::sync::Mutex::new(transport),
+            closed: Mutex::new(None),
+        }
+    }
+
+    async fn send_frame(&self, frame: Frame) -> AmqpResult<()> {
+        let mut transport = self.lock().await?;
+        let result = transport.send_frame(frame).await;
+        self.record(&transport);
+        result
+    }
+
+    async fn send_encoded_frame(&self, data: &[u8]) -> AmqpResult<()> {
+        let mut transport = self.lock().await?;
+        let result = transport.send_encoded_frame(data).await;
+        self.record(&transport);
+        result
+    }
+
+    /// Send an empty frame unless something was written within `period`
+    async fn send_heartbeat(&self, period: Duration) -> AmqpResult<bool> {
+        let mut transport = self.lock().await?;
+        if transport.stats().last_write.is_some_and(|last| last.elapsed() < period) {
+            return Ok(false);
+        }
+        let result = transport.send_frame(heartbeat_frame()).await;
+        self.record(&transport);
+        result.map(|()| true)
+    }
+
+    /// Send our Close and shut down the sending direction, once
+    async fn close(&self, error: Option<crate::types::AmqpError>) -> AmqpResult<()> {
+        let mut transport = self.transport.lock().await;
+        if self.lock_closed().is_some() {
+            return Ok(());
+        }
+        let close = Close { error };
+        *self.lock_closed() = Some(close.clone());
+        let result = match close_frame(close) {
+            Ok(frame) => transport.send_frame(frame).await,
+            Err(e) => Err(e),
+        };
+        let shutdown = transport.shutdown().await;
+        self.record(&transport);
+        result.and(shutdown)
+    }
+
+    /// Shut down the sending direction, e.g. after a Close written as a frame
+    async fn shutdown(&self) -> AmqpResult<()> {
+        let mut transport = self.transport.lock().await;
+        let result = transport.shutdown().await;
+        self.record(&transport);
+        result
+    }
+
+    fn stats(&self) -> TransportStats {
+        *self.stats.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Lock the transport for writing, failing once the connection is closed
+    async fn lock(&self) -> AmqpResult<tokio::sync::MutexGuard<'_, TransportWriter>> {
+        let transport = self.transport.lock().await;
+        match self.lock_closed().as_ref().map(|close| close.error.as_ref()) {
+            None => Ok(transport),
+            Some(Some(error)) => Err(AmqpError::amqp_protocol(
+                error.condition.clone(),
+                error.description.clone().unwrap_or_else(|| "Connection closed".to_string()),
+            )),
+            Some(None) => Err(AmqpError::connection("Connection closed")),
+        }
+    }
+
+    fn lock_closed(&self) -> std::sync::MutexGuard<'_, Option<Close>> {
+        self.closed.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn record(&self, transport: &TransportWriter) {
+        *self.stats.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = transport.stats();
+    }
+}
+
+/// Encode a message straight after a placeholder frame header, then fill the header in
+fn encode_message_frame(channel: u16, message: &crate::message::Message) -> AmqpResult<BytesMut> {
+    let mut buffer = BytesMut::with_capacity(256);
+    buffer.put_bytes(0, FRAME_HEADER_SIZE);
+    Encoder::new_into(&mut buffer).encode_message(message)?;
+
+    let payload_size = (buffer.len() - FRAME_HEADER_SIZE) as u32;
+    let header = FrameHeader::new(payload_size, FrameType::AMQP as u8, channel);
+    buffer[..FRAME_HEADER_SIZE].copy_from_slice(&header.encode());
+    Ok(buffer)
+}
+
+/// Build the frame closing the connection
+fn close_frame(close: Close) -> AmqpResult<Frame> {
+    let payload = close.encode()?;
+    let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, 0);
+    Ok(Frame::new(header, payload))
+}
+
+/// Build an empty frame, which keeps the connection alive without a performative
+fn heartbeat_frame() -> Frame {
+    Frame::new(FrameHeader::new(0, FrameType::AMQP as u8, 0), Vec::new())
+}
+
+/// Sessions, links and unsettled transfers to replay after reconnecting
+///
+/// Learned from the frames a [`NetworkConnection`] with a reconnect policy
+/// sends and receives, by our channel. The frames are kept as they were
+/// sent, so they go out again unchanged.
+#[derive(Debug, Default)]
+struct Replay {
+    /// Begin sent on each channel
+    begins: BTreeMap<u16, Frame>,
+    /// Name and Attach sent for each link, by our channel and handle
+    attaches: BTreeMap<(u16, u32), (String, Frame)>,
+    /// Last Flow sent on each link, by our channel and handle
+    flows: BTreeMap<(u16, u32), Frame>,
+    /// Link handle and frames of each transfer the peer has not settled, by our channel and delivery ID
+    unsettled: BTreeMap<(u16, u32), (u32, Vec<Frame>)>,
+    /// Delivery continued by the next Transfer frame on each link
+    continuing: HashMap<(u16, u32), u32>,
+    /// Our channel for each channel the peer answered a Begin on
+    remote_channels: HashMap<u16, u16>,
+    /// Link names by our channel and the peer's handle
+    remote_handles: HashMap<(u16, u32), String>,
+}
+
+impl Replay {
+    /// Keep what a frame we sent adds to the state to replay
+    fn sent(&mut self, frame: &Frame) {
+        let Some(performative) = Self::performative(frame) else {
+            return;
+        };
+        let channel = frame.header.channel;
+        match performative {
+            Performative::Begin(_) => {
+                self.begins.insert(channel, frame.clone());
+            }
+            Performative::End(_) => self.forget_session(channel),
+            Performative::Attach(attach) => {
+                self.attaches.insert((channel, attach.handle), (attach.name, frame.clone()));
+            }
+            Performative::Detach(detach) => self.forget_link(channel, detach.handle),
+            Performative::Flow(flow) => {
+                if let Some(handle) = flow.handle {
+                    self.flows.insert((channel, handle), frame.clone());
+                }
+            }
+            Performative::Transfer(transfer) => self.transfer(channel, &transfer, frame),
+            Performative::Disposition(_) => {}
+        }
+    }
+
+    /// Keep the frames of a delivery sent unsettled until the peer settles it
+    fn transfer(&mut self, channel: u16, transfer: &Transfer, frame: &Frame) {
+        let link = (channel, transfer.handle);
+        let id = match (transfer.delivery_id, self.continuing.get(&link)) {
+            (Some(id), _) => {
+                if transfer.settled != Some(true) {
+                    self.unsettled.insert((channel, id), (transfer.handle, Vec::new()));
+                }
+                id
+            }
+            (None, Some(id)) => *id,
+            (None, None) => return,
+        };
+        if transfer.aborted {
+            self.unsettled.remove(&(channel, id));
+        } else if let Some((_, frames)) = self.unsettled.get_mut(&(channel, id)) {
+            frames.push(frame.clone());
+        }
+        if transfer.more && !transfer.aborted {
+            self.continuing.insert(link, id);
+        } else {
+            self.continuing.remove(&link);
+        }
+    }
+
+    /// Learn the peer's channels and handles from a frame it sent
+    ///
+    /// Drops the transfers the peer settled, and returns our link if the
+    /// frame grants it credit.
+    fn received(&mut self, frame: &Frame) -> Option<(u16, u32)> {
+        let performative = Self::performative(frame)?;
+        let remote_channel = frame.header.channel;
+        if let Performative::Begin(begin) = &performative {
+            if let Some(channel) = begin.remote_channel {
+                self.remote_channels.insert(remote_channel, channel);
+            }
+            return None;
+        }
+        let channel = *self.remote_channels.get(&remote_channel)?;
+        match performative {
+            Performative::End(_) => {
+                self.remote_channels.remove(&remote_channel);
+                self.remote_handles.retain(|(link_channel, _), _| *link_channel != channel);
+            }
+            Performative::Attach(attach) => {
+                self.remote_handles.insert((channel, attach.handle), attach.name);
+            }
+            Performative::Disposition(disposition) if disposition.role == Role::Receiver && disposition.settled => {
+                let settled = disposition.first..=disposition.last.unwrap_or(disposition.first);
+                self.unsettled.retain(|(link_channel, id), _| *link_channel != channel || !settled.contains(id));
+            }
+            Performative::Flow(flow) if flow.link_credit.is_some_and(|credit| credit > 0) => {
+                let name = self.remote_handles.get(&(channel, flow.handle?))?;
+                return self.attaches.iter()
+                    .find(|((link_channel, _), (link_name, _))| *link_channel == channel && link_name == name)
+                    .map(|(link, _)| *link);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Forget what the peer said on the old transport, keeping what to replay
+    fn restart(&mut self) {
+        self.continuing.clear();
+        self.remote_channels.clear();
+        self.remote_handles.clear();
+    }
+
+    fn forget_session(&mut self, channel: u16) {
+        self.begins.remove(&channel);
+        self.attaches.retain(|(link_channel, _), _| *link_channel != channel);
+        self.flows.retain(|(link_channel, _), _| *link_channel != channel);
+        self.unsettled.retain(|(link_channel, _), _| *link_channel != channel);
+    }
+
+    fn forget_link(&mut self, channel: u16, handle: u32) {
+        self.attaches.remove(&(channel, handle));
+        self.flows.remove(&(channel, handle));
+        self.unsettled
+            .retain(|(link_channel, _), (link_handle, _)| *link_channel != channel || *link_handle != handle);
+    }
+
+    /// Decode the performative of an AMQP frame, if it is a session one
+    fn performative(frame: &Frame) -> Option<Performative> {
+        if frame.header.frame_type != FrameType::AMQP as u8 || frame.payload.is_empty() {
+            return None;
+        }
+        Performative::decode(&frame.payload).ok()
+    }
+}
+
+/// Receiving half of a [`NetworkConnection`], see [`NetworkConnection::split`]
+#[derive(Debug)]
+pub struct NetworkReader {
+    transport: TransportReader,
+    /// Frames read before the split, not yet received
+    pending: VecDeque<Frame>,
+    id: String,
+    heartbeat: HeartbeatMonitor,
+    #[cfg(feature = "experimental-compression")]
+    compression: Option<CompressionConfig>,
+}
+
+impl NetworkReader {
+    /// Receive a frame
+    ///
+    /// Cancel safe, so it can be one branch of a `select!` that also sends.
+    pub async fn receive_frame(&mut self) -> AmqpResult<Frame> {
+        if let Some(frame) = self.pending.pop_front() {
+            return Ok(frame);
+        }
+        let frame = self.transport.receive_frame().await?;
+        self.heartbeat.record_peer_frame();
+        Ok(frame)
+    }
+
+    /// Receive a message
+    pub async fn receive_message(&mut self) -> AmqpResult<Option<crate::message::Message>> {
+        let frame = self.receive_frame().await?;
+        if frame.header.frame_type != FrameType::AMQP as u8 {
+            return Ok(None);
+        }
+        #[cfg(feature = "experimental-compression")]
+        let payload = match &self.compression {
+            Some(compression) => compression.payload(frame)?,
+            None => frame.payload,
+        };
+        #[cfg(not(feature = "experimental-compression"))]
+        let payload = frame.payload;
+        Ok(Some(Decoder::new(payload).decode_message()?))
+    }
+
+    /// Get the ID of the connection
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Get read statistics
+    pub fn transport_stats(&self) -> TransportStats {
+        self.transport.stats()
+    }
+
+    /// Get heartbeat statistics
+    pub fn heartbeat_stats(&self) -> HeartbeatStats {
+        self.heartbeat.stats()
+    }
+
+    /// Subscribe to heartbeat events
+    pub fn heartbeat_events(&self) -> tokio::sync::broadcast::Receiver<HeartbeatEvent> {
+        self.heartbeat.subscribe()
+    }
+}
+
+/// Sending half of a [`NetworkConnection`], see [`NetworkConnection::split`]
+///
+/// Dropping it stops the keep-alive task.
+#[derive(Debug)]
+pub struct NetworkWriter {
+    transport: Arc<SharedWriter>,
+    id: String,
+    keep_alive_handle: Option<tokio::task::JoinHandle<()>>,
+    #[cfg(feature = "experimental-compression")]
+    compression: Option<CompressionConfig>,
+}
+
+impl NetworkWriter {
+    /// Send a frame
+    pub async fn send_frame(&mut self, frame: Frame) -> AmqpResult<()> {
+        self.transport.send_frame(frame).await
+    }
+
+    /// Send a message
+    pub async fn send_message(&mut self, channel: u16, message: &crate::message::Message) -> AmqpResult<()> {
+        #[cfg(feature = "experimental-compression")]
+        if let Some(compression) = &self.compression {
+            let mut encoder = Encoder::new();
+            encoder.encode_message(message)?;
+            let frame = compression.frame(FrameType::AMQP as u8, channel, encoder.finish());
+            return self.transport.send_frame(frame).await;
+        }
+
+        let buffer = encode_message_frame(channel, message)?;
+        self.transport.send_encoded_frame(&buffer).await
+    }
+
+    /// Send Close and shut down the sending direction
+    ///
+    /// The reader sees the peer's Close, then the end of the stream.
+    pub async fn close(mut self) -> AmqpResult<()> {
+        if let Some(handle) = self.keep_alive_handle.take() {
+            handle.abort();
+        }
+        self.transport.close(None).await
+    }
+
+    /// Get the ID of the connection
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Get write statistics
+    pub fn transport_stats(&self) -> TransportStats {
+        self.transport.stats()
+    }
+
+    /// Stop sending heartbeats, e.g. once our Close is written
+    pub(crate) fn stop_keep_alive(&mut self) {
+        if let Some(handle) = self.keep_alive_handle.take() {
+            handle.abort();
+        }
+    }
+
+    /// Shut down the sending direction without writing a Close
+    pub(crate) async fn shutdown(&mut self) -> AmqpResult<()> {
+        self.stop_keep_alive();
+        self.transport.shutdown().await
+    }
+}
+
+/// Split a transport past the Open exchange into halves no keep-alive task watches
+pub(crate) fn split_transport(transport: Transport, id: &str) -> (NetworkReader, NetworkWriter) {
+    let (read, write) = transport.into_split();
+    let reader = NetworkReader {
+        transport: read,
+        pending: VecDeque::new(),
+        id: id.to_string(),
+        heartbeat: HeartbeatMonitor::new(1),
+        #[cfg(feature = "experimental-compression")]
+        compression: None,
+    };
+    let writer = NetworkWriter {
+        transport: Arc::new(SharedWriter::new(write)),
+        id: id.to_string(),
+        keep_alive_handle: None,
+        #[cfg(feature = "experimental-compression")]
+        compression: None,
+    };
+    (reader, writer)
+}
+
+impl Drop for NetworkWriter {
+    fn drop(&mut self) {
+        if let Some(handle) = self.keep_alive_handle.take() {
+            handle.abort();
+        }
+    }
+}
+
+/// Interval whose first tick is one period away, so a tick always closes a full interval
+fn heartbeat_interval(period: Duration) -> tokio::time::Interval {
+    tokio::time::interval_at(tokio::time::Instant::now() + period, period)
+}
+
 impl Drop for NetworkConnection {
     fn drop(&mut self) {
         if let Some(handle) = self.keep_alive_handle.take() {
@@ -415,6 +1360,30 @@ impl NetworkBuilder {
         self
     }
 
+    /// Send no heartbeats and run no keep-alive task
+    ///
+    /// For paths through proxies that drop connections on empty frames. The
+    /// peer's silence is no longer reported either, and a peer with an idle
+    /// timeout may close the connection once it passes. To stop the peer's
+    /// heartbeats as well, also set an idle timeout of zero, which Open then
+    /// leaves out.
+    pub fn keep_alive_disabled(mut self) -> Self {
+        self.config.keep_alive_disabled = true;
+        self
+    }
+
+    /// Set how the keep-alive task is supervised
+    pub fn watchdog(mut self, watchdog: WatchdogConfig) -> Self {
+        self.config.watchdog = watchdog;
+        self
+    }
+
+    /// Set the backoff between attempts of [`NetworkConnection::reconnect`]
+    pub fn reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.config.reconnect = Some(policy);
+        self
+    }
+
     /// Set maximum frame size
     pub fn max_frame_size(mut self, max_frame_size: u32) -> Self {
         self.config.max_frame_size = max_frame_size;
@@ -445,6 +1414,56 @@ impl NetworkBuilder {
         self
     }
 
+    /// Set the SASL credentials to authenticate with
+    pub fn sasl(mut self, credentials: SaslCredentials) -> Self {
+        self.config.sasl = Some(credentials);
+        self
+    }
+
+    /// Secure the connection with TLS before the protocol header
+    ///
+    /// The peer's certificate is verified against the hostname, or the SNI
+    /// name of [`NetworkConfig::hostnames`] when set. Requires the `tls`
+    /// feature.
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, connector: crate::tls::TlsConnector) -> Self {
+        self.config.tls = Some(connector);
+        self
+    }
+
+    /// Offer compression of transfer payloads to the peer
+    ///
+    /// Used only if the peer offers it too; see the `compression` module.
+    #[cfg(feature = "experimental-compression")]
+    pub fn compression(mut self, config: CompressionConfig) -> Self {
+        self.config.compression = Some(config);
+        self
+    }
+
+    /// Set the consecutive missed heartbeat intervals before reporting
+    pub fn missed_heartbeat_threshold(mut self, threshold: u32) -> Self {
+        self.config.missed_heartbeat_threshold = threshold;
+        self
+    }
+
+    /// Capture the frames the connection sends and receives
+    pub fn frame_recorder(mut self, recorder: FrameRecorder) -> Self {
+        self.config.frame_recorder = Some(recorder);
+        self
+    }
+
+    /// Offer a capability to the peer, see [`capability`](crate::capability)
+    pub fn offered_capability(mut self, capability: impl Into<AmqpSymbol>) -> Self {
+        self.config.offered_capabilities.push(capability.into());
+        self
+    }
+
+    /// Ask the peer for a capability, e.g. `sole-connection-for-container`
+    pub fn desired_capability(mut self, capability: impl Into<AmqpSymbol>) -> Self {
+        self.config.desired_capabilities.push(capability.into());
+        self
+    }
+
     /// Build the network connection
     pub fn build(self) -> NetworkConnection {
         NetworkConnection::new(self.config)
@@ -460,8 +1479,37 @@ impl Default for NetworkBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::performative::{descriptor, Attach, Begin, Detach, Disposition, Flow, Outcome};
     use crate::types::AmqpValue;
+    use crate::transport::FrameDirection;
+    use crate::AmqpCondition;
+    use crate::tasks;
     use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    /// Accept one connection and answer the AMQP header and Open like a broker
+    fn spawn_peer(listener: TcpListener, reply: ProtocolHeader, open: Open) -> tokio::task::JoinHandle<Transport> {
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut server = Transport::new(stream);
+            server.receive_raw(8).await.unwrap();
+            server.send_raw(reply.as_bytes()).await.unwrap();
+            if reply == ProtocolHeader::AMQP {
+                server.receive_frame().await.unwrap();
+                let payload = open.encode().unwrap();
+                let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, 0);
+                server.send_frame(Frame::new(header, payload)).await.unwrap();
+            }
+            server
+        })
+    }
+
+    fn broker_open() -> Open {
+        Open {
+            container_id: "broker".to_string(),
+            ..Default::default()
+        }
+    }
 
     #[test]
     fn test_network_state_creation() {
@@ -524,9 +1572,11 @@ mod tests {
 
     #[test]
     fn test_network_config_clone() {
-        let mut config = NetworkConfig::default();
-        config.hostname = "test-host".to_string();
-        config.port = 5673;
+        let config = NetworkConfig {
+            hostname: "test-host".to_string(),
+            port: 5673,
+            ..Default::default()
+        };
         
         let cloned = config.clone();
         
@@ -708,6 +1758,103 @@ mod tests {
         assert!(!connection.is_idle());
     }
 
+    #[tokio::test]
+    async fn test_network_connection_transport_stats() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let recorder = FrameRecorder::new(16);
+        let mut connection = NetworkBuilder::new()
+            .hostname("127.0.0.1")
+            .port(port)
+            .frame_recorder(recorder.clone())
+            .build();
+        assert!(connection.transport_stats().is_none());
+
+        let _server = spawn_peer(listener, ProtocolHeader::AMQP, broker_open());
+        connection.connect().await.unwrap();
+        let connected_at = connection.last_activity();
+        assert_eq!(connection.transport_stats().unwrap().bytes_written, 0);
+
+        connection.negotiate_protocol().await.unwrap();
+        let stats = connection.transport_stats().unwrap();
+        assert!(stats.bytes_written > 8);
+        assert_eq!(stats.frames_out, 1);
+        assert_eq!(stats.frames_in, 1);
+        // Our Open, then the broker's
+        let directions: Vec<_> = recorder.frames().iter().map(|frame| (frame.seq, frame.direction)).collect();
+        assert_eq!(directions, vec![(1, FrameDirection::Outgoing), (2, FrameDirection::Incoming)]);
+        assert!(connection.last_activity() >= connected_at);
+        assert_eq!(Some(connection.last_activity()), stats.last_activity());
+        assert!(!connection.is_idle());
+    }
+
+    #[tokio::test]
+    async fn test_network_connection_send_message() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut connection = NetworkBuilder::new()
+            .hostname("127.0.0.1")
+            .port(port)
+            .build();
+        let server = spawn_peer(listener, ProtocolHeader::AMQP, broker_open());
+        connection.connect().await.unwrap();
+        connection.negotiate_protocol().await.unwrap();
+        let mut server = server.await.unwrap();
+
+        let message = crate::message::Message::builder()
+            .header(crate::message::Header::new())
+            .properties(crate::message::Properties::new())
+            .body(crate::message::Body::Value(AmqpValue::String("Hello, AMQP!".to_string())))
+            .build();
+        connection.send_message(3, &message).await.unwrap();
+
+        let frame = server.receive_frame().await.unwrap();
+        assert_eq!(frame.header.channel, 3);
+        assert_eq!(frame.header.size as usize, frame.payload.len());
+        let decoded = Decoder::new(frame.payload).decode_message().unwrap();
+        assert_eq!(decoded.body_as_text(), Some("Hello, AMQP!"));
+    }
+
+    #[tokio::test]
+    async fn test_network_connection_split_is_full_duplex() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut connection = NetworkBuilder::new().hostname("127.0.0.1").port(port).build();
+        let server = spawn_peer(listener, ProtocolHeader::AMQP, broker_open());
+        connection.connect().await.unwrap();
+        connection.negotiate_protocol().await.unwrap();
+        let mut server = server.await.unwrap();
+        let (mut reader, mut writer) = connection.split().unwrap();
+        let message = |text: &str| {
+            crate::message::Message::builder()
+                .header(crate::message::Header::new())
+                .properties(crate::message::Properties::new())
+                .body(crate::message::Body::Value(AmqpValue::String(text.to_string())))
+                .build()
+        };
+
+        // The reader waits for the peer while the writer sends to it
+        let receiving = tokio::spawn(async move {
+            let message = reader.receive_message().await.unwrap().unwrap();
+            (reader, message)
+        });
+        writer.send_message(1, &message("ping")).await.unwrap();
+        let frame = server.receive_frame().await.unwrap();
+        assert_eq!(Decoder::new(frame.payload).decode_message().unwrap().body_as_text(), Some("ping"));
+        server.send_encoded_frame(&encode_message_frame(1, &message("pong")).unwrap()).await.unwrap();
+
+        let (reader, message) = receiving.await.unwrap();
+        assert_eq!(message.body_as_text(), Some("pong"));
+        assert_eq!((reader.transport_stats().frames_in, writer.transport_stats().frames_out), (2, 2));
+        assert_eq!(reader.heartbeat_stats().peer_frames, 2);
+
+        writer.close().await.unwrap();
+        server.receive_frame().await.unwrap();
+        assert!(server.receive_frame().await.is_err());
+    }
+
     #[test]
     fn test_network_connection_state_access() {
         let config = NetworkConfig::default();
@@ -757,15 +1904,17 @@ mod tests {
 
     #[test]
     fn test_network_connection_with_custom_config() {
-        let mut config = NetworkConfig::default();
-        config.hostname = "custom-host".to_string();
-        config.port = 8888;
-        config.timeout = Duration::from_secs(60);
-        config.keep_alive = Duration::from_secs(120);
-        config.max_frame_size = 131072;
-        config.channel_max = 500;
-        config.idle_timeout = Duration::from_secs(180);
-        config.container_id = "custom-container".to_string();
+        let config = NetworkConfig {
+            hostname: "custom-host".to_string(),
+            port: 8888,
+            timeout: Duration::from_secs(60),
+            keep_alive: Duration::from_secs(120),
+            max_frame_size: 131072,
+            channel_max: 500,
+            idle_timeout: Duration::from_secs(180),
+            container_id: "custom-container".to_string(),
+            ..Default::default()
+        };
         
         let connection = NetworkConnection::new(config);
         
@@ -801,4 +1950,464 @@ mod tests {
         assert_eq!(config.hostname, "localhost");
         assert_eq!(config.port, 5672);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_network_connection_tuning_bounded_by_idle_timeout() {
+        let connection = NetworkBuilder::new()
+            .keep_alive(Duration::from_secs(20))
+            .idle_timeout(Duration::from_secs(30))
+            .build();
+        let tuning = connection.tuning();
+
+        assert_eq!(tuning.get().heartbeat_interval, Duration::from_secs(15));
+        assert_eq!(tuning.heartbeat_limit(), Some(Duration::from_secs(15)));
+        assert!(tuning.update(|knobs| knobs.heartbeat_interval = Duration::from_secs(5)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_network_connection_heartbeat_stats() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut connection = NetworkBuilder::new()
+            .hostname("127.0.0.1")
+            .port(port)
+            .keep_alive(Duration::from_millis(20))
+            .missed_heartbeat_threshold(1)
+            .build();
+        let mut events = connection.heartbeat_events();
+        let open = Open { idle_time_out: Some(10_000), ..broker_open() };
+        let server = spawn_peer(listener, ProtocolHeader::AMQP, open);
+        connection.connect().await.unwrap();
+        connection.negotiate_protocol().await.unwrap();
+        let mut server = server.await.unwrap();
+        assert_eq!(connection.heartbeat_stats().peer_frames, 1);
+
+        // The silent peer is reported once an interval passes
+        assert!(matches!(events.recv().await, Ok(HeartbeatEvent::Missed { .. })));
+        assert!(connection.heartbeat_stats().sent >= 1);
+
+        server.send_frame(Frame::new(FrameHeader::new(0, FrameType::AMQP as u8, 0), Vec::new())).await.unwrap();
+        connection.receive_frame().await.unwrap();
+        assert_eq!(connection.heartbeat_stats().peer_frames, 2);
+        assert_eq!(connection.heartbeat_stats().consecutive_missed, 0);
+    }
+
+    #[tokio::test]
+    async fn test_network_connection_keep_alive_follows_idle_timeouts() {
+        let keep_alive_running = |connection: &NetworkConnection| {
+            let name = tasks::task_name(TaskKind::KeepAlive, connection.id());
+            tasks::tasks().iter().any(|task| task.name == name)
+        };
+        let cases = [
+            // A peer idle timeout of 0 means none: no heartbeats to send
+            (NetworkBuilder::new().keep_alive(Duration::from_millis(20)), Some(0), true, false),
+            (NetworkBuilder::new().keep_alive(Duration::from_millis(20)), Some(10_000), true, true),
+            // Nothing to send and nothing to watch for
+            (NetworkBuilder::new().idle_timeout(Duration::ZERO), Some(0), false, false),
+            (NetworkBuilder::new().keep_alive_disabled(), Some(10_000), false, false),
+        ];
+
+        for (builder, idle_time_out, running, sends) in cases {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let mut connection = builder.hostname("127.0.0.1").port(port).build();
+            let _server = spawn_peer(listener, ProtocolHeader::AMQP, Open { idle_time_out, ..broker_open() });
+            connection.connect().await.unwrap();
+            connection.negotiate_protocol().await.unwrap();
+
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert_eq!(keep_alive_running(&connection), running, "{:?}", idle_time_out);
+            assert_eq!(connection.heartbeat_stats().sent > 0, sends, "{:?}", idle_time_out);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_network_connection_sends_empty_frames_as_heartbeats() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut connection = NetworkBuilder::new()
+            .hostname("127.0.0.1")
+            .port(port)
+            .keep_alive(Duration::from_secs(60))
+            .idle_timeout(Duration::ZERO)
+            .build();
+        // Half the peer's idle timeout bounds the 60 second keep-alive
+        let server = spawn_peer(listener, ProtocolHeader::AMQP, Open { idle_time_out: Some(60), ..broker_open() });
+        connection.connect().await.unwrap();
+        connection.negotiate_protocol().await.unwrap();
+        let mut server = server.await.unwrap();
+
+        let frame = tokio::time::timeout(Duration::from_secs(1), server.receive_frame()).await.unwrap().unwrap();
+        assert_eq!((frame.header.frame_type, frame.header.channel), (FrameType::AMQP as u8, 0));
+        assert!(frame.payload.is_empty());
+        assert!(connection.heartbeat_stats().sent >= 1);
+    }
+
+    #[tokio::test]
+    async fn test_network_connection_closes_silent_peer_after_idle_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut connection = NetworkBuilder::new()
+            .hostname("127.0.0.1")
+            .port(port)
+            .idle_timeout(Duration::from_millis(100))
+            .build();
+        let mut events = connection.heartbeat_events();
+        let server = spawn_peer(listener, ProtocolHeader::AMQP, broker_open());
+        connection.connect().await.unwrap();
+        connection.negotiate_protocol().await.unwrap();
+        let mut server = server.await.unwrap();
+
+        let close = tokio::time::timeout(Duration::from_secs(1), server.receive_frame()).await.unwrap().unwrap();
+        let error = Close::decode(&close.payload).unwrap().error.unwrap();
+        assert_eq!(error.condition, AmqpCondition::AmqpErrorResourceLimitExceeded);
+        assert!(matches!(events.recv().await, Ok(HeartbeatEvent::TimedOut { idle }) if idle >= Duration::from_millis(100)));
+
+        let frame = Frame::new(FrameHeader::new(0, FrameType::AMQP as u8, 0), Vec::new());
+        let error = connection.send_frame(frame).await.unwrap_err();
+        assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorResourceLimitExceeded));
+    }
+
+    #[tokio::test]
+    async fn test_network_connection_reconnects_with_backoff() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let peer = tokio::spawn(async move {
+            let mut connections = Vec::new();
+            for container in ["broker-1", "refused", "broker-2"] {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut server = Transport::new(stream);
+                // The second connection is dropped before the handshake
+                if container != "refused" {
+                    server.receive_raw(8).await.unwrap();
+                    server.send_raw(ProtocolHeader::AMQP.as_bytes()).await.unwrap();
+                    server.receive_frame().await.unwrap();
+                    let payload = Open { container_id: container.to_string(), ..Default::default() }.encode().unwrap();
+                    let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, 0);
+                    server.send_frame(Frame::new(header, payload)).await.unwrap();
+                    connections.push(server);
+                }
+                if container == "broker-1" {
+                    connections.clear();
+                }
+            }
+            connections
+        });
+
+        let mut connection = NetworkBuilder::new()
+            .hostname("127.0.0.1")
+            .port(port)
+            .keep_alive_disabled()
+            .reconnect(ReconnectPolicy {
+                initial_backoff: Duration::from_millis(5),
+                jitter: 0.0,
+                ..Default::default()
+            })
+            .build();
+        let mut events = connection.reconnect_events();
+        connection.connect().await.unwrap();
+        connection.negotiate_protocol().await.unwrap();
+        assert_eq!(connection.remote_container_id(), Some("broker-1"));
+        assert!(connection.receive_frame().await.is_err());
+
+        connection.reconnect().await.unwrap();
+        assert_eq!(connection.state(), &NetworkState::Ready);
+        assert_eq!(connection.remote_container_id(), Some("broker-2"));
+        let mut seen = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            seen.push(event);
+        }
+        assert_eq!(
+            seen,
+            vec![
+                ReconnectEvent::Attempting { attempt: 1, delay: Duration::from_millis(5) },
+                ReconnectEvent::Attempting { attempt: 2, delay: Duration::from_millis(10) },
+                ReconnectEvent::Reconnected { attempts: 2, resent: 0 },
+            ]
+        );
+        assert_eq!(peer.await.unwrap().len(), 1);
+    }
+
+    /// Frame carrying a performative on a channel
+    fn performative_frame(channel: u16, performative: Performative) -> Frame {
+        let payload = performative.encode().unwrap();
+        Frame::new(FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, channel), payload)
+    }
+
+    #[tokio::test]
+    async fn test_network_connection_resends_unsettled_transfers_after_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let peer = tokio::spawn(async move {
+            let mut resent = Vec::new();
+            for round in 0..2 {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut server = Transport::new(stream);
+                server.receive_raw(8).await.unwrap();
+                server.send_raw(ProtocolHeader::AMQP.as_bytes()).await.unwrap();
+                server.receive_frame().await.unwrap();
+                let payload = broker_open().encode().unwrap();
+                let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, 0);
+                server.send_frame(Frame::new(header, payload)).await.unwrap();
+
+                let begin = Begin { remote_channel: Some(0), ..Default::default() };
+                let attach = Attach { name: "link".to_string(), handle: 7, role: Role::Receiver, ..Default::default() };
+                loop {
+                    match Performative::decode(&server.receive_frame().await.unwrap().payload).unwrap() {
+                        Performative::Begin(_) => server.send_frame(performative_frame(5, Performative::Begin(begin.clone()))).await.unwrap(),
+                        Performative::Attach(_) => {
+                            server.send_frame(performative_frame(5, Performative::Attach(attach.clone()))).await.unwrap();
+                            if round == 1 {
+                                let flow = Flow { handle: Some(7), link_credit: Some(10), ..Default::default() };
+                                server.send_frame(performative_frame(5, Performative::Flow(flow))).await.unwrap();
+                            }
+                        }
+                        Performative::Transfer(transfer) if round == 1 => resent.push(transfer.delivery_id.unwrap()),
+                        // The first delivery is settled, then the connection drops with the rest in flight
+                        Performative::Transfer(transfer) if transfer.delivery_id == Some(3) => {
+                            let disposition = Disposition {
+                                role: Role::Receiver,
+                                first: 0,
+                                last: None,
+                                settled: true,
+                                state: Some(Outcome::Accepted),
+                                batchable: false,
+                            };
+                            server.send_frame(performative_frame(5, Performative::Disposition(disposition))).await.unwrap();
+                            break;
+                        }
+                        Performative::Detach(_) => break,
+                        _ => {}
+                    }
+                }
+            }
+            resent
+        });
+
+        let mut connection = NetworkBuilder::new()
+            .hostname("127.0.0.1")
+            .port(port)
+            .keep_alive_disabled()
+            .reconnect(ReconnectPolicy { initial_backoff: Duration::from_millis(5), jitter: 0.0, ..Default::default() })
+            .build();
+        let mut events = connection.reconnect_events();
+        connection.connect().await.unwrap();
+        connection.negotiate_protocol().await.unwrap();
+        connection.send_frame(performative_frame(0, Performative::Begin(Begin::default()))).await.unwrap();
+        let attach = Attach { name: "link".to_string(), handle: 0, role: Role::Sender, ..Default::default() };
+        connection.send_frame(performative_frame(0, Performative::Attach(attach))).await.unwrap();
+        for id in 0..4u32 {
+            let transfer = Transfer {
+                handle: 0,
+                delivery_id: Some(id),
+                delivery_tag: Some(id.to_be_bytes().to_vec()),
+                // The last delivery is sent settled, so it is not resent
+                settled: Some(id == 3),
+                payload: vec![id as u8; 16],
+                ..Default::default()
+            };
+            connection.send_frame(performative_frame(0, Performative::Transfer(transfer))).await.unwrap();
+        }
+        while connection.receive_frame().await.is_ok() {}
+
+        connection.reconnect().await.unwrap();
+        assert_eq!(events.recv().await.unwrap(), ReconnectEvent::Attempting { attempt: 1, delay: Duration::from_millis(5) });
+        assert_eq!(events.recv().await.unwrap(), ReconnectEvent::Reconnected { attempts: 1, resent: 2 });
+        // The peer's answers read while replaying are still received
+        for expected in [descriptor::BEGIN, descriptor::ATTACH, descriptor::FLOW] {
+            let frame = connection.receive_frame().await.unwrap();
+            assert_eq!(Decoder::new(frame.payload).decode_described_header().unwrap().0, expected);
+        }
+        connection.send_frame(performative_frame(0, Performative::Detach(Detach { handle: 0, closed: true, error: None }))).await.unwrap();
+        assert_eq!(peer.await.unwrap(), vec![1, 2]);
+    }
+
+    #[cfg(feature = "experimental-compression")]
+    #[tokio::test]
+    async fn test_network_connection_compresses_when_both_announce_it() {
+        let config = CompressionConfig { threshold: 256, ..Default::default() };
+        let message = crate::message::Message::text("x".repeat(4096));
+        let mut encoder = Encoder::new();
+        encoder.encode_message(&message).unwrap();
+        let encoded = encoder.finish();
+
+        for peer_announces in [false, true] {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let mut open = broker_open();
+            if peer_announces {
+                open.properties.insert(AmqpSymbol::from(compression::COMPRESSION_PROPERTY), config.property());
+            }
+
+            let mut connection = NetworkBuilder::new().hostname("127.0.0.1").port(port).compression(config.clone()).build();
+            let server = spawn_peer(listener, ProtocolHeader::AMQP, open);
+            connection.connect().await.unwrap();
+            connection.negotiate_protocol().await.unwrap();
+            let mut server = server.await.unwrap();
+            assert_eq!(connection.compression_active(), peer_announces);
+
+            connection.send_message(1, &message).await.unwrap();
+            let frame = server.receive_frame().await.unwrap();
+            assert_eq!(frame.header.data_offset == 3, peer_announces);
+            assert_eq!(config.payload(frame.clone()).unwrap(), encoded);
+
+            // The connection takes back what it sent, decompressing only once negotiated
+            server.send_frame(frame).await.unwrap();
+            let received = connection.receive_message().await.unwrap().unwrap();
+            assert_eq!(received, Decoder::new(encoded.clone()).decode_message().unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_network_connection_stores_remote_open() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let open = Open {
+            container_id: "broker-7".to_string(),
+            max_frame_size: 4096,
+            channel_max: 15,
+            idle_time_out: Some(10_000),
+            ..Default::default()
+        };
+
+        let mut connection = NetworkBuilder::new()
+            .hostname("127.0.0.1")
+            .port(port)
+            .idle_timeout(Duration::from_secs(60))
+            .build();
+        assert_eq!(connection.negotiated_channel_max(), 1000);
+
+        let _server = spawn_peer(listener, ProtocolHeader::AMQP, open);
+        connection.connect().await.unwrap();
+        connection.negotiate_protocol().await.unwrap();
+
+        assert_eq!(connection.remote_container_id(), Some("broker-7"));
+        assert_eq!(connection.remote_open().unwrap().idle_time_out, Some(10_000));
+        assert_eq!(connection.negotiated_max_frame_size(), 4096);
+        assert_eq!(connection.negotiated_channel_max(), 15);
+        // Heartbeats must keep the peer's shorter idle timeout alive
+        assert_eq!(connection.tuning().heartbeat_limit(), Some(Duration::from_secs(5)));
+
+        tokio::task::yield_now().await;
+        let keep_alive = tasks::task_name(TaskKind::KeepAlive, connection.id());
+        assert!(tasks::tasks().iter().any(|task| task.name == keep_alive));
+    }
+
+    #[tokio::test]
+    async fn test_network_connection_protocol_mismatch() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut connection = NetworkBuilder::new()
+            .hostname("127.0.0.1")
+            .port(port)
+            .build();
+        let _server = spawn_peer(listener, ProtocolHeader::SASL, broker_open());
+        connection.connect().await.unwrap();
+
+        match connection.negotiate_protocol().await {
+            Err(AmqpError::ProtocolMismatch { expected, received }) => {
+                assert_eq!(expected, ProtocolHeader::AMQP);
+                assert_eq!(received, ProtocolHeader::SASL);
+            }
+            other => panic!("Expected a protocol mismatch, got {:?}", other),
+        }
+        assert!(matches!(connection.state(), NetworkState::Error(_)));
+        assert!(connection.remote_open().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_network_connection_sole_connection_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut connection = NetworkBuilder::new()
+            .hostname("127.0.0.1")
+            .port(port)
+            .container_id("client-a")
+            .desired_capability(capability::SOLE_CONNECTION_FOR_CONTAINER)
+            .build();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut server = Transport::new(stream);
+            server.receive_raw(8).await.unwrap();
+            server.send_raw(ProtocolHeader::AMQP.as_bytes()).await.unwrap();
+            let open = Open::decode(&server.receive_frame().await.unwrap().payload).unwrap();
+
+            let mut refused = broker_open();
+            refused.offered_capabilities = vec![AmqpSymbol::from(capability::SOLE_CONNECTION_FOR_CONTAINER)];
+            refused
+                .properties
+                .insert(AmqpSymbol::from(capability::CONNECTION_ESTABLISHMENT_FAILED), AmqpValue::Boolean(true));
+            let close = Close { error: Some(capability::refusal()) };
+            for payload in [refused.encode().unwrap(), close.encode().unwrap()] {
+                let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, 0);
+                server.send_frame(Frame::new(header, payload)).await.unwrap();
+            }
+            open
+        });
+        connection.connect().await.unwrap();
+
+        let error = connection.negotiate_protocol().await.unwrap_err();
+        assert!(matches!(&error, AmqpError::ContainerInUse { container_id } if container_id == "client-a"));
+        assert!(connection.remote_open().is_none());
+        assert!(server.await.unwrap().desires(capability::SOLE_CONNECTION_FOR_CONTAINER));
+    }
+
+    #[tokio::test]
+    async fn test_network_connection_handshake_timeout_names_stage() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut connection = NetworkBuilder::new()
+            .hostname("127.0.0.1")
+            .port(port)
+            .timeout(Duration::from_millis(200))
+            .build();
+        let server = tokio::spawn(async move {
+            // Answers the header, then never sends its Open
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut server = Transport::new(stream);
+            server.receive_raw(8).await.unwrap();
+            server.send_raw(ProtocolHeader::AMQP.as_bytes()).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+        connection.connect().await.unwrap();
+
+        let error = connection.negotiate_protocol().await.unwrap_err();
+        assert!(matches!(error, AmqpError::HandshakeTimeout { stage: HandshakeStage::Open, .. }));
+        assert!(error.remediation().is_some());
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_network_connection_refused_with_close() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut connection = NetworkBuilder::new()
+            .hostname("127.0.0.1")
+            .port(port)
+            .build();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut server = Transport::new(stream);
+            server.receive_raw(8).await.unwrap();
+            server.send_raw(ProtocolHeader::AMQP.as_bytes()).await.unwrap();
+            server.receive_frame().await.unwrap();
+            let close = Close {
+                error: Some(crate::types::AmqpError::new(AmqpCondition::AmqpErrorNotAllowed)),
+            };
+            let payload = close.encode().unwrap();
+            let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, 0);
+            server.send_frame(Frame::new(header, payload)).await.unwrap();
+            server
+        });
+        connection.connect().await.unwrap();
+
+        let error = connection.negotiate_protocol().await.unwrap_err();
+        assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorNotAllowed));
+        drop(server.await.unwrap());
+    }
+}
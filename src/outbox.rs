@@ -0,0 +1,352 @@
+//! AMQP 1.0 Transactional Outbox
+//!
+//! This module solves the dual write of a database row and a message without
+//! a distributed transaction. The application writes the message to an outbox
+//! table in the same database transaction as its business data; an [`Outbox`]
+//! then polls that table through an [`OutboxStore`], sends each pending record
+//! and marks it sent once the peer has accepted it.
+//!
+//! A record may be sent more than once: after a crash between the peer's
+//! acceptance and `mark_sent`, or when the peer releases it. Every record is
+//! sent with its key as the message-id, so a broker doing duplicate detection
+//! (see [`dedup`]) drops the repeats and delivery stays effectively once.
+//!
+//! [`dedup`]: crate::dedup
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::link::LinkBuilder;
+//! use dumq_amqp::message::Message;
+//! use dumq_amqp::outbox::{Outbox, OutboxRecord, OutboxStore};
+//! use dumq_amqp::performative::{Disposition, Outcome};
+//! use dumq_amqp::{AmqpResult, Role};
+//! use std::sync::Mutex;
+//!
+//! #[derive(Default)]
+//! struct Table(Mutex<Vec<OutboxRecord>>);
+//!
+//! impl OutboxStore for Table {
+//!     async fn pending(&self, limit: usize) -> AmqpResult<Vec<OutboxRecord>> {
+//!         Ok(self.0.lock().unwrap().iter().take(limit).cloned().collect())
+//!     }
+//!
+//!     async fn mark_sent(&self, key: &str) -> AmqpResult<()> {
+//!         self.0.lock().unwrap().retain(|record| record.key != key);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let table = Table::default();
+//! table.0.lock().unwrap().push(OutboxRecord::new("order-42", Message::text("created")));
+//!
+//! let mut sender = LinkBuilder::new().target("orders").build_sender("session-1".to_string());
+//! sender.attach().await?;
+//! sender.add_credit(10);
+//!
+//! let mut outbox = Outbox::new(table, sender);
+//! assert_eq!(outbox.poll().await?, 1);
+//!
+//! // The peer accepts the first delivery
+//! let accepted = Disposition {
+//!     role: Role::Receiver,
+//!     first: 1,
+//!     last: None,
+//!     settled: true,
+//!     state: Some(Outcome::Accepted),
+//!     batchable: false,
+//! };
+//! assert_eq!(outbox.handle_disposition(&accepted).await?, vec!["order-42".to_string()]);
+//! assert!(outbox.store().0.lock().unwrap().is_empty());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::link::Sender;
+use crate::logging;
+use crate::performative::{Disposition, Outcome};
+use crate::{types, AmqpResult, Message, SenderSettleMode};
+use std::collections::HashMap;
+use std::future::Future;
+
+/// Default number of records fetched per poll
+pub const DEFAULT_BATCH_SIZE: usize = 100;
+
+/// A message waiting in the outbox
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboxRecord {
+    /// Unique key of the record, sent as the message-id
+    pub key: String,
+    /// The message to send
+    pub message: Message,
+}
+
+impl OutboxRecord {
+    /// Create a record
+    pub fn new(key: impl Into<String>, message: Message) -> Self {
+        OutboxRecord {
+            key: key.into(),
+            message,
+        }
+    }
+}
+
+/// Storage of outbox records, implemented by the application
+///
+/// Typically a table written in the same database transaction as the
+/// business data the messages describe.
+pub trait OutboxStore {
+    /// Fetch up to `limit` records not yet marked sent, oldest first
+    fn pending(&self, limit: usize) -> impl Future<Output = AmqpResult<Vec<OutboxRecord>>> + Send;
+
+    /// Mark a record sent; it must not be returned by `pending` again
+    fn mark_sent(&self, key: &str) -> impl Future<Output = AmqpResult<()>> + Send;
+
+    /// Handle a record the peer rejected
+    ///
+    /// The default keeps the record pending, so it is sent again on a later
+    /// poll. Stores that park poison messages override this.
+    fn mark_rejected(&self, key: &str, error: Option<&types::AmqpError>) -> impl Future<Output = AmqpResult<()>> + Send {
+        logging::warn!("Outbox record '{}' rejected: {:?}", key, error);
+        async { Ok(()) }
+    }
+}
+
+#[derive(Debug)]
+struct InFlight {
+    key: String,
+    /// Outcome from an unsettled disposition, kept until settlement
+    outcome: Option<Outcome>,
+}
+
+/// Sends outbox records and marks them sent once accepted
+#[derive(Debug)]
+pub struct Outbox<S> {
+    store: S,
+    sender: Sender,
+    batch_size: usize,
+    /// Records sent and awaiting their outcome, by delivery ID
+    in_flight: HashMap<u32, InFlight>,
+}
+
+impl<S: OutboxStore> Outbox<S> {
+    /// Create an outbox sending through an attached sender
+    pub fn new(store: S, sender: Sender) -> Self {
+        Outbox {
+            store,
+            sender,
+            batch_size: DEFAULT_BATCH_SIZE,
+            in_flight: HashMap::new(),
+        }
+    }
+
+    /// Set the number of records fetched per poll
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Send the pending records that are not already in flight
+    ///
+    /// Sending stops when the sender runs out of credit; the remaining
+    /// records are picked up by a later poll. A sender that settles on send
+    /// gets no outcome, so its records are marked sent as soon as they are
+    /// sent. Returns the number of records sent.
+    pub async fn poll(&mut self) -> AmqpResult<usize> {
+        let records = self.store.pending(self.batch_size).await?;
+        let presettled = self.sender.config().sender_settle_mode == SenderSettleMode::Settled;
+        let mut sent = 0;
+        for record in records {
+            if self.sender.credit() == 0 {
+                break;
+            }
+            if self.in_flight.values().any(|in_flight| in_flight.key == record.key) {
+                continue;
+            }
+
+            let delivery_id = self
+                .sender
+                .send(record.message.with_message_id(record.key.clone()))
+                .await?
+                .delivery_id();
+            sent += 1;
+            if presettled {
+                self.sender.settle(delivery_id);
+                self.store.mark_sent(&record.key).await?;
+            } else {
+                self.in_flight.insert(delivery_id, InFlight { key: record.key, outcome: None });
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Apply a Disposition from the peer, returning the keys marked sent
+    ///
+    /// Accepted records are marked sent and rejected ones handed to
+    /// [`OutboxStore::mark_rejected`]. Released and modified records stay
+    /// pending and are sent again on a later poll.
+    pub async fn handle_disposition(&mut self, disposition: &Disposition) -> AmqpResult<Vec<String>> {
+        if let Some(outcome) = &disposition.state {
+            let last = disposition.last.unwrap_or(disposition.first);
+            for (_, in_flight) in self.in_flight.iter_mut().filter(|(id, _)| (disposition.first..=last).contains(*id)) {
+                in_flight.outcome = Some(outcome.clone());
+            }
+        }
+
+        let mut marked = Vec::new();
+        for delivery_id in self.sender.handle_disposition(disposition)? {
+            let Some(in_flight) = self.in_flight.remove(&delivery_id) else {
+                continue;
+            };
+            match in_flight.outcome {
+                Some(Outcome::Accepted) => {
+                    self.store.mark_sent(&in_flight.key).await?;
+                    marked.push(in_flight.key);
+                }
+                Some(Outcome::Rejected { error }) => self.store.mark_rejected(&in_flight.key, error.as_ref()).await?,
+                _ => logging::debug!("Outbox record '{}' not accepted, keeping it pending", in_flight.key),
+            }
+        }
+        Ok(marked)
+    }
+
+    /// Get the number of records sent and awaiting their outcome
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Get the store
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Get the sender, e.g. to add credit
+    pub fn sender_mut(&mut self) -> &mut Sender {
+        &mut self.sender
+    }
+
+    /// Take the store and sender back
+    pub fn into_parts(self) -> (S, Sender) {
+        (self.store, self.sender)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::link::LinkBuilder;
+    use crate::Role;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Table {
+        pending: Mutex<Vec<OutboxRecord>>,
+        rejected: Mutex<Vec<String>>,
+    }
+
+    impl Table {
+        fn with(keys: &[&str]) -> Self {
+            let table = Table::default();
+            for key in keys {
+                table.pending.lock().unwrap().push(OutboxRecord::new(*key, Message::text(*key)));
+            }
+            table
+        }
+
+        fn keys(&self) -> Vec<String> {
+            self.pending.lock().unwrap().iter().map(|record| record.key.clone()).collect()
+        }
+    }
+
+    impl OutboxStore for Table {
+        async fn pending(&self, limit: usize) -> AmqpResult<Vec<OutboxRecord>> {
+            Ok(self.pending.lock().unwrap().iter().take(limit).cloned().collect())
+        }
+
+        async fn mark_sent(&self, key: &str) -> AmqpResult<()> {
+            self.pending.lock().unwrap().retain(|record| record.key != key);
+            Ok(())
+        }
+
+        async fn mark_rejected(&self, key: &str, _error: Option<&types::AmqpError>) -> AmqpResult<()> {
+            self.rejected.lock().unwrap().push(key.to_string());
+            self.mark_sent(key).await
+        }
+    }
+
+    async fn outbox(table: Table, mode: SenderSettleMode, credit: u32) -> Outbox<Table> {
+        let mut sender = LinkBuilder::new()
+            .target("orders")
+            .sender_settle_mode(mode)
+            .build_sender("session-1".to_string());
+        sender.attach().await.unwrap();
+        sender.add_credit(credit);
+        Outbox::new(table, sender)
+    }
+
+    fn settled(first: u32, last: Option<u32>, outcome: Outcome) -> Disposition {
+        Disposition {
+            role: Role::Receiver,
+            first,
+            last,
+            settled: true,
+            state: Some(outcome),
+            batchable: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_outbox_marks_accepted_and_resends_released() {
+        let mut outbox = outbox(Table::with(&["a", "b", "c", "d"]), SenderSettleMode::Unsettled, 10).await;
+
+        assert_eq!(outbox.poll().await.unwrap(), 4);
+        // Records in flight are not sent twice
+        assert_eq!(outbox.poll().await.unwrap(), 0);
+
+        assert_eq!(outbox.handle_disposition(&settled(1, Some(2), Outcome::Accepted)).await.unwrap(), vec!["a", "b"]);
+        assert!(outbox.handle_disposition(&settled(3, None, Outcome::Released)).await.unwrap().is_empty());
+        assert!(outbox
+            .handle_disposition(&settled(4, None, Outcome::Rejected { error: None }))
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(outbox.store().keys(), vec!["c"]);
+        assert_eq!(*outbox.store().rejected.lock().unwrap(), vec!["d"]);
+        assert_eq!(outbox.in_flight(), 0);
+
+        // The released record is sent again, under the same message-id
+        assert_eq!(outbox.poll().await.unwrap(), 1);
+        let resent = outbox.sender_mut().settle(5).unwrap();
+        assert_eq!(resent.message_id_as_string().as_deref(), Some("c"));
+    }
+
+    #[tokio::test]
+    async fn test_outbox_keeps_outcome_until_settled() {
+        let mut outbox = outbox(Table::with(&["a"]), SenderSettleMode::Unsettled, 10).await;
+        outbox.poll().await.unwrap();
+
+        let mut unsettled = settled(1, None, Outcome::Accepted);
+        unsettled.settled = false;
+        assert!(outbox.handle_disposition(&unsettled).await.unwrap().is_empty());
+        assert_eq!(outbox.store().keys(), vec!["a"]);
+
+        let settlement = Disposition { state: None, ..settled(1, None, Outcome::Accepted) };
+        assert_eq!(outbox.handle_disposition(&settlement).await.unwrap(), vec!["a"]);
+        assert!(outbox.store().keys().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_outbox_stops_at_credit_and_presettled_marks_on_send() {
+        let mut outbox = outbox(Table::with(&["a", "b", "c"]), SenderSettleMode::Settled, 2).await;
+
+        assert_eq!(outbox.poll().await.unwrap(), 2);
+        assert_eq!(outbox.store().keys(), vec!["c"]);
+        assert_eq!(outbox.in_flight(), 0);
+
+        outbox.sender_mut().add_credit(1);
+        assert_eq!(outbox.poll().await.unwrap(), 1);
+        assert!(outbox.store().keys().is_empty());
+    }
+}
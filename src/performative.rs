@@ -0,0 +1,1529 @@
+//! AMQP 1.0 Performatives
+//!
+//! This module provides the frame bodies exchanged on the wire to manage
+//! connections, sessions and links. Each performative is encoded as a
+//! described list whose descriptor identifies the performative type.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::performative::Begin;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let begin = Begin {
+//!     handle_max: 31,
+//!     ..Default::default()
+//! };
+//!
+//! let encoded = begin.encode()?;
+//! let decoded = Begin::decode(&encoded)?;
+//! assert_eq!(decoded.handle_max, 31);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::capability;
+use crate::codec::{present_len, Decoder, Encoder};
+use crate::types::{self, DistributionMode, Role, TerminusDurability, TerminusExpiryPolicy};
+use crate::{
+    AmqpCondition, AmqpError, AmqpMap, AmqpResult, AmqpSymbol, AmqpValue, ReceiverSettleMode,
+    SenderSettleMode,
+};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+
+/// Performative descriptor codes
+pub mod descriptor {
+    pub const OPEN: u64 = 0x10;
+    pub const BEGIN: u64 = 0x11;
+    pub const ATTACH: u64 = 0x12;
+    pub const FLOW: u64 = 0x13;
+    pub const TRANSFER: u64 = 0x14;
+    pub const DISPOSITION: u64 = 0x15;
+    pub const DETACH: u64 = 0x16;
+    pub const END: u64 = 0x17;
+    pub const CLOSE: u64 = 0x18;
+    pub const ERROR: u64 = 0x1d;
+    pub const ACCEPTED: u64 = 0x24;
+    pub const REJECTED: u64 = 0x25;
+    pub const RELEASED: u64 = 0x26;
+    pub const MODIFIED: u64 = 0x27;
+    pub const SOURCE: u64 = 0x28;
+    pub const TARGET: u64 = 0x29;
+    pub const COORDINATOR: u64 = 0x30;
+    pub const DECLARE: u64 = 0x31;
+    pub const DISCHARGE: u64 = 0x32;
+    pub const DECLARED: u64 = 0x33;
+    pub const TRANSACTIONAL_STATE: u64 = 0x34;
+}
+
+/// Open performative (connection establishment)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Open {
+    /// Container ID of the sending endpoint
+    pub container_id: String,
+    /// Name of the host being connected to
+    pub hostname: Option<String>,
+    /// Largest frame the sending endpoint accepts
+    pub max_frame_size: u32,
+    /// Highest channel number the sending endpoint accepts
+    pub channel_max: u16,
+    /// Idle timeout of the sending endpoint in milliseconds
+    pub idle_time_out: Option<u32>,
+    /// Extensions the sending endpoint supports, see [`capability`](crate::capability)
+    pub offered_capabilities: Vec<AmqpSymbol>,
+    /// Extensions the sending endpoint can use if the peer offers them
+    pub desired_capabilities: Vec<AmqpSymbol>,
+    /// Connection properties
+    pub properties: AmqpMap,
+}
+
+impl Default for Open {
+    fn default() -> Self {
+        Open {
+            container_id: String::new(),
+            hostname: None,
+            max_frame_size: u32::MAX,
+            channel_max: u16::MAX,
+            idle_time_out: None,
+            offered_capabilities: Vec::new(),
+            desired_capabilities: Vec::new(),
+            properties: AmqpMap::new(),
+        }
+    }
+}
+
+impl Open {
+    /// Check if the endpoint offers a capability
+    pub fn offers(&self, capability: &str) -> bool {
+        self.offered_capabilities.iter().any(|offered| offered.as_str() == capability)
+    }
+
+    /// Check if the endpoint desires a capability
+    pub fn desires(&self, capability: &str) -> bool {
+        self.desired_capabilities.iter().any(|desired| desired.as_str() == capability)
+    }
+
+    /// Encode the Open performative
+    pub fn encode(&self) -> AmqpResult<Vec<u8>> {
+        let fields = [
+            Field::Value(AmqpValue::String(self.container_id.clone())),
+            Field::Value(self.hostname.clone().map_or(AmqpValue::Null, AmqpValue::String)),
+            Field::Value(AmqpValue::Uint(self.max_frame_size)),
+            Field::Value(AmqpValue::Ushort(self.channel_max)),
+            Field::Value(self.idle_time_out.map_or(AmqpValue::Null, AmqpValue::Uint)),
+            Field::Value(AmqpValue::Null),
+            Field::Value(AmqpValue::Null),
+            Field::Value(capability::encode(&self.offered_capabilities)),
+            Field::Value(capability::encode(&self.desired_capabilities)),
+            Field::Value(if self.properties.is_empty() {
+                AmqpValue::Null
+            } else {
+                AmqpValue::Map(self.properties.clone())
+            }),
+        ];
+        encode_fields(descriptor::OPEN, &fields)
+    }
+
+    /// Decode an Open performative
+    pub fn decode(data: &[u8]) -> AmqpResult<Self> {
+        let fields = decode_fields(data, descriptor::OPEN, "open")?;
+
+        let container_id = match value(&fields, 0)? {
+            Some(AmqpValue::String(container_id)) => container_id.clone(),
+            Some(other) => return Err(AmqpError::decoding(format!("Expected container-id, got {:?}", other))),
+            None => return Err(AmqpError::decoding("Missing mandatory field: container-id")),
+        };
+        let hostname = match value(&fields, 1)? {
+            Some(AmqpValue::String(hostname)) => Some(hostname.clone()),
+            _ => None,
+        };
+        let properties = match value(&fields, 9)? {
+            Some(AmqpValue::Map(properties)) => properties.clone(),
+            _ => AmqpMap::new(),
+        };
+
+        Ok(Open {
+            container_id,
+            hostname,
+            max_frame_size: optional_uint(value(&fields, 2)?)?.unwrap_or(u32::MAX),
+            channel_max: optional_ushort(value(&fields, 3)?)?.unwrap_or(u16::MAX),
+            // Zero means no idle timeout, same as absent
+            idle_time_out: optional_uint(value(&fields, 4)?)?.filter(|millis| *millis > 0),
+            offered_capabilities: capability::decode(value(&fields, 7)?)?,
+            desired_capabilities: capability::decode(value(&fields, 8)?)?,
+            properties,
+        })
+    }
+}
+
+/// Close performative (connection termination)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Close {
+    /// Error causing the close
+    pub error: Option<types::AmqpError>,
+}
+
+impl Close {
+    /// Encode the Close performative
+    pub fn encode(&self) -> AmqpResult<Vec<u8>> {
+        let error = match &self.error {
+            Some(error) => error_field(error),
+            None => Field::Value(AmqpValue::Null),
+        };
+        encode_fields(descriptor::CLOSE, &[error])
+    }
+
+    /// Decode a Close performative
+    pub fn decode(data: &[u8]) -> AmqpResult<Self> {
+        let fields = decode_fields(data, descriptor::CLOSE, "close")?;
+        Ok(Close { error: error(&fields, 0)? })
+    }
+}
+
+/// Begin performative (session establishment)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Begin {
+    /// Channel of the peer's Begin, when responding
+    pub remote_channel: Option<u16>,
+    /// Next outgoing transfer ID
+    pub next_outgoing_id: u32,
+    /// Incoming window
+    pub incoming_window: u32,
+    /// Outgoing window
+    pub outgoing_window: u32,
+    /// Maximum link handle value
+    pub handle_max: u32,
+}
+
+impl Default for Begin {
+    fn default() -> Self {
+        Begin {
+            remote_channel: None,
+            next_outgoing_id: 0,
+            incoming_window: 0,
+            outgoing_window: 0,
+            handle_max: u32::MAX,
+        }
+    }
+}
+
+impl Begin {
+    /// Encode the Begin performative
+    pub fn encode(&self) -> AmqpResult<Vec<u8>> {
+        let fields = vec![
+            self.remote_channel.map(AmqpValue::Ushort).unwrap_or(AmqpValue::Null),
+            AmqpValue::Uint(self.next_outgoing_id),
+            AmqpValue::Uint(self.incoming_window),
+            AmqpValue::Uint(self.outgoing_window),
+            AmqpValue::Uint(self.handle_max),
+        ];
+
+        let mut encoder = Encoder::new();
+        encoder.encode_described_list(descriptor::BEGIN, &fields)?;
+        Ok(encoder.finish())
+    }
+
+    /// Decode a Begin performative
+    pub fn decode(data: &[u8]) -> AmqpResult<Self> {
+        let fields = decode_fields(data, descriptor::BEGIN, "begin")?;
+
+        Ok(Begin {
+            remote_channel: optional_ushort(value(&fields, 0)?)?,
+            next_outgoing_id: required_uint(value(&fields, 1)?, "next-outgoing-id")?,
+            incoming_window: required_uint(value(&fields, 2)?, "incoming-window")?,
+            outgoing_window: required_uint(value(&fields, 3)?, "outgoing-window")?,
+            handle_max: optional_uint(value(&fields, 4)?)?.unwrap_or(u32::MAX),
+        })
+    }
+}
+
+/// Source or target terminus carried by an Attach
+#[derive(Debug, Clone, PartialEq)]
+pub struct Terminus {
+    /// Node address
+    pub address: Option<String>,
+    /// Terminus durability
+    pub durable: TerminusDurability,
+    /// Terminus expiry policy
+    pub expiry_policy: TerminusExpiryPolicy,
+    /// Expiry timeout in seconds
+    pub timeout: u32,
+    /// Whether the peer should create the node dynamically
+    pub dynamic: bool,
+    /// Whether a source moves or copies the messages it sends
+    pub distribution_mode: Option<DistributionMode>,
+    /// Filters a source applies to the messages it sends, keyed by name
+    pub filter: AmqpMap,
+}
+
+impl Default for Terminus {
+    fn default() -> Self {
+        Terminus {
+            address: None,
+            durable: TerminusDurability::None,
+            expiry_policy: TerminusExpiryPolicy::SessionEnd,
+            timeout: 0,
+            dynamic: false,
+            distribution_mode: None,
+            filter: AmqpMap::new(),
+        }
+    }
+}
+
+impl Terminus {
+    /// Create a terminus for an address
+    pub fn new(address: impl Into<String>) -> Self {
+        Terminus {
+            address: Some(address.into()),
+            ..Default::default()
+        }
+    }
+
+    fn to_fields(&self) -> Vec<AmqpValue> {
+        let expiry_policy = match self.expiry_policy {
+            TerminusExpiryPolicy::SessionEnd => "session-end",
+            TerminusExpiryPolicy::ConnectionClose => "connection-close",
+            TerminusExpiryPolicy::Never => "never",
+        };
+
+        let mut fields = vec![
+            self.address.clone().map(AmqpValue::String).unwrap_or(AmqpValue::Null),
+            AmqpValue::Uint(self.durable as u32),
+            AmqpValue::Symbol(AmqpSymbol::from(expiry_policy)),
+            AmqpValue::Uint(self.timeout),
+            AmqpValue::Boolean(self.dynamic),
+        ];
+        let distribution_mode = self
+            .distribution_mode
+            .map_or(AmqpValue::Null, |mode| AmqpValue::Symbol(AmqpSymbol::from(mode.as_str())));
+        if !self.filter.is_empty() {
+            // dynamic-node-properties comes first
+            fields.extend([AmqpValue::Null, distribution_mode, AmqpValue::Map(self.filter.clone())]);
+        } else if self.distribution_mode.is_some() {
+            fields.extend([AmqpValue::Null, distribution_mode]);
+        }
+        fields
+    }
+
+    fn from_fields(fields: &[AmqpValue]) -> AmqpResult<Self> {
+        let address = match fields.first() {
+            None | Some(AmqpValue::Null) => None,
+            Some(AmqpValue::String(address)) => Some(address.clone()),
+            Some(AmqpValue::Symbol(address)) => Some(address.0.clone()),
+            Some(other) => return Err(AmqpError::decoding(format!("Expected address, got {:?}", other))),
+        };
+        let durable = match optional_uint(fields.get(1))? {
+            None | Some(0) => TerminusDurability::None,
+            Some(1) => TerminusDurability::Configuration,
+            Some(_) => TerminusDurability::UnsettledState,
+        };
+        let expiry_policy = match fields.get(2) {
+            Some(AmqpValue::Symbol(policy)) if policy.0 == "connection-close" => TerminusExpiryPolicy::ConnectionClose,
+            Some(AmqpValue::Symbol(policy)) if policy.0 == "never" => TerminusExpiryPolicy::Never,
+            _ => TerminusExpiryPolicy::SessionEnd,
+        };
+
+        Ok(Terminus {
+            address,
+            durable,
+            expiry_policy,
+            timeout: optional_uint(fields.get(3))?.unwrap_or(0),
+            dynamic: optional_bool(fields.get(4))?.unwrap_or(false),
+            distribution_mode: match fields.get(6) {
+                Some(AmqpValue::Symbol(mode)) if mode.0 == "move" => Some(DistributionMode::Move),
+                Some(AmqpValue::Symbol(mode)) if mode.0 == "copy" => Some(DistributionMode::Copy),
+                _ => None,
+            },
+            filter: match fields.get(7) {
+                None | Some(AmqpValue::Null) => AmqpMap::new(),
+                Some(AmqpValue::Map(filter)) => filter.clone(),
+                Some(other) => return Err(AmqpError::decoding(format!("Expected filter set, got {:?}", other))),
+            },
+        })
+    }
+}
+
+/// Transaction coordinator, the target of a link that declares and
+/// discharges transactions
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Coordinator {
+    /// Transaction capabilities asked of the coordinator, e.g. `amqp:local-transactions`
+    pub capabilities: Vec<AmqpSymbol>,
+}
+
+/// Attach performative (link establishment)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attach {
+    /// Link name
+    pub name: String,
+    /// Link handle
+    pub handle: u32,
+    /// Role of the sending endpoint
+    pub role: Role,
+    /// Sender settle mode
+    pub snd_settle_mode: SenderSettleMode,
+    /// Receiver settle mode
+    pub rcv_settle_mode: ReceiverSettleMode,
+    /// Source terminus
+    pub source: Option<Terminus>,
+    /// Target terminus
+    pub target: Option<Terminus>,
+    /// Coordinator taking the place of the target terminus
+    pub coordinator: Option<Coordinator>,
+    /// Delivery count the sender starts from; present only when the role is sender
+    pub initial_delivery_count: Option<u32>,
+    /// Largest message the sending endpoint accepts, if limited
+    pub max_message_size: Option<u64>,
+    /// Link properties
+    pub properties: AmqpMap,
+}
+
+impl Default for Attach {
+    fn default() -> Self {
+        Attach {
+            name: String::new(),
+            handle: 0,
+            role: Role::Sender,
+            snd_settle_mode: SenderSettleMode::Mixed,
+            rcv_settle_mode: ReceiverSettleMode::First,
+            source: None,
+            target: None,
+            coordinator: None,
+            initial_delivery_count: Some(0),
+            max_message_size: None,
+            properties: AmqpMap::new(),
+        }
+    }
+}
+
+impl Attach {
+    /// Encode the Attach performative
+    pub fn encode(&self) -> AmqpResult<Vec<u8>> {
+        let fields = vec![
+            Field::Value(AmqpValue::String(self.name.clone())),
+            Field::Value(AmqpValue::Uint(self.handle)),
+            Field::Value(AmqpValue::Boolean(self.role == Role::Receiver)),
+            Field::Value(AmqpValue::Ubyte(self.snd_settle_mode as u8)),
+            Field::Value(AmqpValue::Ubyte(self.rcv_settle_mode as u8)),
+            terminus_field(descriptor::SOURCE, &self.source),
+            match &self.coordinator {
+                Some(coordinator) => Field::Described(
+                    descriptor::COORDINATOR,
+                    vec![Field::Value(capability::encode(&coordinator.capabilities))],
+                ),
+                None => terminus_field(descriptor::TARGET, &self.target),
+            },
+            Field::Value(AmqpValue::Null),
+            Field::Value(AmqpValue::Null),
+            Field::Value(self.initial_delivery_count.map_or(AmqpValue::Null, AmqpValue::Uint)),
+            Field::Value(self.max_message_size.map_or(AmqpValue::Null, AmqpValue::Ulong)),
+            Field::Value(AmqpValue::Null),
+            Field::Value(AmqpValue::Null),
+            if self.properties.is_empty() {
+                Field::Value(AmqpValue::Null)
+            } else {
+                Field::Value(AmqpValue::Map(self.properties.clone()))
+            },
+        ];
+        encode_fields(descriptor::ATTACH, &fields)
+    }
+
+    /// Decode an Attach performative
+    pub fn decode(data: &[u8]) -> AmqpResult<Self> {
+        let fields = decode_fields(data, descriptor::ATTACH, "attach")?;
+
+        let name = match value(&fields, 0)? {
+            Some(AmqpValue::String(name)) => name.clone(),
+            Some(other) => return Err(AmqpError::decoding(format!("Expected link name, got {:?}", other))),
+            None => return Err(AmqpError::decoding("Missing mandatory field: name")),
+        };
+        let role = match optional_bool(value(&fields, 2)?)? {
+            Some(true) => Role::Receiver,
+            Some(false) => Role::Sender,
+            None => return Err(AmqpError::decoding("Missing mandatory field: role")),
+        };
+        let snd_settle_mode = match optional_uint(value(&fields, 3)?)? {
+            Some(0) => SenderSettleMode::Unsettled,
+            Some(1) => SenderSettleMode::Settled,
+            _ => SenderSettleMode::Mixed,
+        };
+        let rcv_settle_mode = match optional_uint(value(&fields, 4)?)? {
+            Some(1) => ReceiverSettleMode::Second,
+            _ => ReceiverSettleMode::First,
+        };
+        let properties = match value(&fields, 13)? {
+            Some(AmqpValue::Map(properties)) => properties.clone(),
+            _ => AmqpMap::new(),
+        };
+        let coordinator = match fields.get(6) {
+            Some(Field::Described(descriptor::COORDINATOR, coordinator)) => Some(Coordinator {
+                capabilities: capability::decode(plain_values(coordinator)?.first())?,
+            }),
+            _ => None,
+        };
+
+        Ok(Attach {
+            name,
+            handle: required_uint(value(&fields, 1)?, "handle")?,
+            role,
+            snd_settle_mode,
+            rcv_settle_mode,
+            source: terminus(&fields, 5, descriptor::SOURCE)?,
+            target: match coordinator {
+                Some(_) => None,
+                None => terminus(&fields, 6, descriptor::TARGET)?,
+            },
+            coordinator,
+            initial_delivery_count: optional_uint(value(&fields, 9)?)?,
+            // Zero means no limit, same as absent
+            max_message_size: optional_ulong(value(&fields, 10)?)?.filter(|size| *size > 0),
+            properties,
+        })
+    }
+}
+
+/// Flow performative (session and link flow control)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Flow {
+    /// Next incoming transfer ID expected from the peer
+    pub next_incoming_id: Option<u32>,
+    /// Incoming window
+    pub incoming_window: u32,
+    /// Next outgoing transfer ID
+    pub next_outgoing_id: u32,
+    /// Outgoing window
+    pub outgoing_window: u32,
+    /// Link handle, when the flow applies to a link
+    pub handle: Option<u32>,
+    /// Link delivery count
+    pub delivery_count: Option<u32>,
+    /// Link credit
+    pub link_credit: Option<u32>,
+    /// Number of messages awaiting credit at the sender
+    pub available: Option<u32>,
+    /// Whether the sender should use up all credit
+    pub drain: bool,
+    /// Whether the peer should respond with its own flow state
+    pub echo: bool,
+}
+
+impl Flow {
+    /// Encode the Flow performative
+    pub fn encode(&self) -> AmqpResult<Vec<u8>> {
+        let optional = |value: Option<u32>| value.map(AmqpValue::Uint).unwrap_or(AmqpValue::Null);
+        let fields = vec![
+            optional(self.next_incoming_id),
+            AmqpValue::Uint(self.incoming_window),
+            AmqpValue::Uint(self.next_outgoing_id),
+            AmqpValue::Uint(self.outgoing_window),
+            optional(self.handle),
+            optional(self.delivery_count),
+            optional(self.link_credit),
+            optional(self.available),
+            AmqpValue::Boolean(self.drain),
+            AmqpValue::Boolean(self.echo),
+        ];
+
+        let mut encoder = Encoder::new();
+        encoder.encode_described_list(descriptor::FLOW, &fields)?;
+        Ok(encoder.finish())
+    }
+
+    /// Decode a Flow performative
+    pub fn decode(data: &[u8]) -> AmqpResult<Self> {
+        let fields = decode_fields(data, descriptor::FLOW, "flow")?;
+
+        Ok(Flow {
+            next_incoming_id: optional_uint(value(&fields, 0)?)?,
+            incoming_window: required_uint(value(&fields, 1)?, "incoming-window")?,
+            next_outgoing_id: required_uint(value(&fields, 2)?, "next-outgoing-id")?,
+            outgoing_window: required_uint(value(&fields, 3)?, "outgoing-window")?,
+            handle: optional_uint(value(&fields, 4)?)?,
+            delivery_count: optional_uint(value(&fields, 5)?)?,
+            link_credit: optional_uint(value(&fields, 6)?)?,
+            available: optional_uint(value(&fields, 7)?)?,
+            drain: optional_bool(value(&fields, 8)?)?.unwrap_or(false),
+            echo: optional_bool(value(&fields, 9)?)?.unwrap_or(false),
+        })
+    }
+}
+
+/// Transfer performative (a message, or a frame of one, on a link)
+///
+/// The payload carries the encoded message sections and follows the
+/// performative in the frame body. A message split across several frames
+/// sets `more` on all but the last, and only the first needs to carry the
+/// delivery ID and tag.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Transfer {
+    /// Link handle
+    pub handle: u32,
+    /// Delivery ID, mandatory on the first frame of a delivery
+    pub delivery_id: Option<u32>,
+    /// Delivery tag, mandatory on the first frame of a delivery
+    pub delivery_tag: Option<Vec<u8>>,
+    /// Message format, 0 for standard AMQP messages
+    pub message_format: Option<u32>,
+    /// Whether the delivery is sent pre-settled
+    pub settled: Option<bool>,
+    /// Whether more frames of this delivery follow
+    pub more: bool,
+    /// Receiver settle mode for this delivery, overriding the link's
+    pub rcv_settle_mode: Option<ReceiverSettleMode>,
+    /// State of the delivery at the sender
+    pub state: Option<Outcome>,
+    /// Whether the delivery resumes one from a previous link
+    pub resume: bool,
+    /// Whether the delivery is aborted
+    pub aborted: bool,
+    /// Whether the peer may delay processing of this transfer
+    pub batchable: bool,
+    /// Encoded message sections
+    pub payload: Vec<u8>,
+}
+
+impl Transfer {
+    /// Encode the Transfer performative followed by its payload
+    pub fn encode(&self) -> AmqpResult<Vec<u8>> {
+        let optional = |value: Option<AmqpValue>| Field::Value(value.unwrap_or(AmqpValue::Null));
+        let fields = vec![
+            Field::Value(AmqpValue::Uint(self.handle)),
+            optional(self.delivery_id.map(AmqpValue::Uint)),
+            optional(self.delivery_tag.clone().map(|tag| AmqpValue::Binary(tag.into()))),
+            optional(self.message_format.map(AmqpValue::Uint)),
+            optional(self.settled.map(AmqpValue::Boolean)),
+            Field::Value(AmqpValue::Boolean(self.more)),
+            optional(self.rcv_settle_mode.map(|mode| AmqpValue::Ubyte(mode as u8))),
+            self.state.as_ref().map(Outcome::to_field).unwrap_or(Field::Value(AmqpValue::Null)),
+            Field::Value(AmqpValue::Boolean(self.resume)),
+            Field::Value(AmqpValue::Boolean(self.aborted)),
+            Field::Value(AmqpValue::Boolean(self.batchable)),
+        ];
+        let mut data = encode_fields(descriptor::TRANSFER, &fields)?;
+        data.extend_from_slice(&self.payload);
+        Ok(data)
+    }
+
+    /// Decode a Transfer performative and the payload after it
+    pub fn decode(data: &[u8]) -> AmqpResult<Self> {
+        let (fields, len) = decode_performative(data, descriptor::TRANSFER, "transfer")?;
+
+        let delivery_tag = match value(&fields, 2)? {
+            None => None,
+            Some(AmqpValue::Binary(tag)) => Some(tag.to_vec()),
+            Some(other) => return Err(AmqpError::decoding(format!("Expected binary delivery-tag, got {:?}", other))),
+        };
+        let rcv_settle_mode = optional_uint(value(&fields, 6)?)?.map(|mode| match mode {
+            1 => ReceiverSettleMode::Second,
+            _ => ReceiverSettleMode::First,
+        });
+        let state = match fields.get(7) {
+            Some(field) => Outcome::from_field(field)?,
+            None => None,
+        };
+
+        Ok(Transfer {
+            handle: required_uint(value(&fields, 0)?, "handle")?,
+            delivery_id: optional_uint(value(&fields, 1)?)?,
+            delivery_tag,
+            message_format: optional_uint(value(&fields, 3)?)?,
+            settled: optional_bool(value(&fields, 4)?)?,
+            more: optional_bool(value(&fields, 5)?)?.unwrap_or(false),
+            rcv_settle_mode,
+            state,
+            resume: optional_bool(value(&fields, 8)?)?.unwrap_or(false),
+            aborted: optional_bool(value(&fields, 9)?)?.unwrap_or(false),
+            batchable: optional_bool(value(&fields, 10)?)?.unwrap_or(false),
+            payload: data[len..].to_vec(),
+        })
+    }
+}
+
+/// Detach performative (link termination)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Detach {
+    /// Link handle
+    pub handle: u32,
+    /// Whether the link is closed rather than suspended
+    pub closed: bool,
+    /// Error causing the detach
+    pub error: Option<types::AmqpError>,
+}
+
+impl Detach {
+    /// Encode the Detach performative
+    pub fn encode(&self) -> AmqpResult<Vec<u8>> {
+        let error = match &self.error {
+            Some(error) => error_field(error),
+            None => Field::Value(AmqpValue::Null),
+        };
+        let fields = vec![
+            Field::Value(AmqpValue::Uint(self.handle)),
+            Field::Value(AmqpValue::Boolean(self.closed)),
+            error,
+        ];
+        encode_fields(descriptor::DETACH, &fields)
+    }
+
+    /// Decode a Detach performative
+    pub fn decode(data: &[u8]) -> AmqpResult<Self> {
+        let fields = decode_fields(data, descriptor::DETACH, "detach")?;
+
+        Ok(Detach {
+            handle: required_uint(value(&fields, 0)?, "handle")?,
+            closed: optional_bool(value(&fields, 1)?)?.unwrap_or(false),
+            error: error(&fields, 2)?,
+        })
+    }
+}
+
+/// End performative (session termination)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct End {
+    /// Error causing the end
+    pub error: Option<types::AmqpError>,
+}
+
+impl End {
+    /// Encode the End performative
+    pub fn encode(&self) -> AmqpResult<Vec<u8>> {
+        let error = match &self.error {
+            Some(error) => error_field(error),
+            None => Field::Value(AmqpValue::Null),
+        };
+        encode_fields(descriptor::END, &[error])
+    }
+
+    /// Decode an End performative
+    pub fn decode(data: &[u8]) -> AmqpResult<Self> {
+        let fields = decode_fields(data, descriptor::END, "end")?;
+        Ok(End { error: error(&fields, 0)? })
+    }
+}
+
+/// Outcome of a delivery, carried as the state of a Disposition
+///
+/// Besides the terminal outcomes, a delivery may be in the state of a
+/// transaction: [`Outcome::Declared`] answers a declare sent to a
+/// coordinator, and [`Outcome::Transactional`] ties a transfer or its
+/// outcome to a transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// The message was processed
+    Accepted,
+    /// The message is invalid and cannot be processed
+    Rejected {
+        /// Reason for the rejection
+        error: Option<types::AmqpError>,
+    },
+    /// The message was not processed and may be redelivered
+    Released,
+    /// The message was not processed and is redelivered with changes
+    Modified {
+        /// Whether the delivery counts as a failed attempt
+        delivery_failed: bool,
+        /// Whether the message must not be redelivered to this link
+        undeliverable_here: bool,
+    },
+    /// The coordinator declared a transaction
+    Declared {
+        /// Identifier of the transaction
+        txn_id: Vec<u8>,
+    },
+    /// The delivery belongs to a transaction
+    Transactional {
+        /// Identifier of the transaction
+        txn_id: Vec<u8>,
+        /// Outcome that takes effect when the transaction commits
+        outcome: Option<Box<Outcome>>,
+    },
+}
+
+impl Outcome {
+    fn to_field(&self) -> Field {
+        match self {
+            Outcome::Accepted => Field::Described(descriptor::ACCEPTED, Vec::new()),
+            Outcome::Rejected { error } => Field::Described(
+                descriptor::REJECTED,
+                vec![error.as_ref().map(error_field).unwrap_or(Field::Value(AmqpValue::Null))],
+            ),
+            Outcome::Released => Field::Described(descriptor::RELEASED, Vec::new()),
+            Outcome::Modified {
+                delivery_failed,
+                undeliverable_here,
+            } => Field::Described(
+                descriptor::MODIFIED,
+                vec![
+                    Field::Value(AmqpValue::Boolean(*delivery_failed)),
+                    Field::Value(AmqpValue::Boolean(*undeliverable_here)),
+                ],
+            ),
+            Outcome::Declared { txn_id } => Field::Described(
+                descriptor::DECLARED,
+                vec![Field::Value(AmqpValue::Binary(txn_id.clone().into()))],
+            ),
+            Outcome::Transactional { txn_id, outcome } => Field::Described(
+                descriptor::TRANSACTIONAL_STATE,
+                vec![
+                    Field::Value(AmqpValue::Binary(txn_id.clone().into())),
+                    outcome.as_ref().map_or(Field::Value(AmqpValue::Null), |outcome| outcome.to_field()),
+                ],
+            ),
+        }
+    }
+
+    fn from_field(field: &Field) -> AmqpResult<Option<Self>> {
+        let (code, fields) = match field {
+            Field::Value(AmqpValue::Null) => return Ok(None),
+            Field::Described(code, fields) => (*code, fields),
+            Field::Value(other) => return Err(AmqpError::decoding(format!("Expected delivery state, got {:?}", other))),
+        };
+        let outcome = match code {
+            descriptor::ACCEPTED => Outcome::Accepted,
+            descriptor::REJECTED => Outcome::Rejected { error: error(fields, 0)? },
+            descriptor::RELEASED => Outcome::Released,
+            descriptor::MODIFIED => Outcome::Modified {
+                delivery_failed: optional_bool(value(fields, 0)?)?.unwrap_or(false),
+                undeliverable_here: optional_bool(value(fields, 1)?)?.unwrap_or(false),
+            },
+            descriptor::DECLARED => Outcome::Declared { txn_id: txn_id(fields)? },
+            descriptor::TRANSACTIONAL_STATE => Outcome::Transactional {
+                txn_id: txn_id(fields)?,
+                outcome: match fields.get(1) {
+                    Some(field) => Outcome::from_field(field)?.map(Box::new),
+                    None => None,
+                },
+            },
+            other => return Err(AmqpError::decoding(format!("Unsupported delivery state 0x{:02x}", other))),
+        };
+        Ok(Some(outcome))
+    }
+}
+
+/// Disposition performative (settlement of a range of deliveries)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disposition {
+    /// Role of the endpoint issuing the disposition
+    pub role: Role,
+    /// First delivery ID in the range
+    pub first: u32,
+    /// Last delivery ID in the range, if more than one
+    pub last: Option<u32>,
+    /// Whether the deliveries are settled
+    pub settled: bool,
+    /// Outcome applied to every delivery in the range
+    pub state: Option<Outcome>,
+    /// Whether the peer may delay processing of this disposition
+    pub batchable: bool,
+}
+
+impl Disposition {
+    /// Build the fewest dispositions covering the given delivery IDs
+    ///
+    /// IDs are sorted and deduplicated, and each run of consecutive IDs
+    /// becomes one ranged disposition.
+    pub fn batch(role: Role, delivery_ids: &[u32], settled: bool, state: Option<Outcome>) -> Vec<Disposition> {
+        let mut ids = delivery_ids.to_vec();
+        ids.sort_unstable();
+        ids.dedup();
+
+        let mut dispositions: Vec<Disposition> = Vec::new();
+        for id in ids {
+            match dispositions.last_mut() {
+                Some(last) if last.last.unwrap_or(last.first).checked_add(1) == Some(id) => last.last = Some(id),
+                _ => dispositions.push(Disposition {
+                    role,
+                    first: id,
+                    last: None,
+                    settled,
+                    state: state.clone(),
+                    batchable: false,
+                }),
+            }
+        }
+        dispositions
+    }
+
+    /// Check if the disposition applies to a delivery ID
+    pub fn covers(&self, delivery_id: u32) -> bool {
+        (self.first..=self.last.unwrap_or(self.first)).contains(&delivery_id)
+    }
+
+    /// Encode the Disposition performative
+    pub fn encode(&self) -> AmqpResult<Vec<u8>> {
+        let fields = vec![
+            Field::Value(AmqpValue::Boolean(self.role == Role::Receiver)),
+            Field::Value(AmqpValue::Uint(self.first)),
+            Field::Value(self.last.map(AmqpValue::Uint).unwrap_or(AmqpValue::Null)),
+            Field::Value(AmqpValue::Boolean(self.settled)),
+            self.state.as_ref().map(Outcome::to_field).unwrap_or(Field::Value(AmqpValue::Null)),
+            Field::Value(AmqpValue::Boolean(self.batchable)),
+        ];
+        encode_fields(descriptor::DISPOSITION, &fields)
+    }
+
+    /// Decode a Disposition performative
+    pub fn decode(data: &[u8]) -> AmqpResult<Self> {
+        let fields = decode_fields(data, descriptor::DISPOSITION, "disposition")?;
+
+        let role = match optional_bool(value(&fields, 0)?)? {
+            Some(true) => Role::Receiver,
+            Some(false) => Role::Sender,
+            None => return Err(AmqpError::decoding("Missing mandatory field: role")),
+        };
+        let state = match fields.get(4) {
+            Some(field) => Outcome::from_field(field)?,
+            None => None,
+        };
+
+        Ok(Disposition {
+            role,
+            first: required_uint(value(&fields, 1)?, "first")?,
+            last: optional_uint(value(&fields, 2)?)?,
+            settled: optional_bool(value(&fields, 3)?)?.unwrap_or(false),
+            state,
+            batchable: optional_bool(value(&fields, 5)?)?.unwrap_or(false),
+        })
+    }
+}
+
+/// Performative exchanged between session and link endpoints
+#[derive(Debug, Clone, PartialEq)]
+pub enum Performative {
+    /// Begin performative
+    Begin(Begin),
+    /// Attach performative
+    Attach(Attach),
+    /// Flow performative
+    Flow(Flow),
+    /// Transfer performative
+    Transfer(Transfer),
+    /// Disposition performative
+    Disposition(Disposition),
+    /// End performative
+    End(End),
+    /// Detach performative
+    Detach(Detach),
+}
+
+impl Performative {
+    /// Encode the performative
+    pub fn encode(&self) -> AmqpResult<Vec<u8>> {
+        match self {
+            Performative::Begin(begin) => begin.encode(),
+            Performative::Attach(attach) => attach.encode(),
+            Performative::Flow(flow) => flow.encode(),
+            Performative::Transfer(transfer) => transfer.encode(),
+            Performative::Disposition(disposition) => disposition.encode(),
+            Performative::End(end) => end.encode(),
+            Performative::Detach(detach) => detach.encode(),
+        }
+    }
+
+    /// Decode a performative, dispatching on its descriptor
+    pub fn decode(data: &[u8]) -> AmqpResult<Self> {
+        let (descriptor, _) = Decoder::new(data.to_vec()).decode_described_header()?;
+        match descriptor {
+            descriptor::BEGIN => Ok(Performative::Begin(Begin::decode(data)?)),
+            descriptor::ATTACH => Ok(Performative::Attach(Attach::decode(data)?)),
+            descriptor::FLOW => Ok(Performative::Flow(Flow::decode(data)?)),
+            descriptor::TRANSFER => Ok(Performative::Transfer(Transfer::decode(data)?)),
+            descriptor::DISPOSITION => Ok(Performative::Disposition(Disposition::decode(data)?)),
+            descriptor::END => Ok(Performative::End(End::decode(data)?)),
+            descriptor::DETACH => Ok(Performative::Detach(Detach::decode(data)?)),
+            other => Err(AmqpError::not_implemented(format!("Unsupported performative 0x{:02x}", other))),
+        }
+    }
+}
+
+/// One side of an in-process performative channel
+///
+/// Sessions and links exchange performatives with their peer through an
+/// endpoint. [`Endpoint::pair`] creates two connected endpoints, which is
+/// useful for wiring a session or link to a frame dispatcher or for testing.
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    outgoing: mpsc::UnboundedSender<Performative>,
+    incoming: Arc<Mutex<mpsc::UnboundedReceiver<Performative>>>,
+}
+
+impl Endpoint {
+    /// Create an endpoint from its outgoing and incoming channels
+    pub fn new(
+        outgoing: mpsc::UnboundedSender<Performative>,
+        incoming: mpsc::UnboundedReceiver<Performative>,
+    ) -> Self {
+        Endpoint {
+            outgoing,
+            incoming: Arc::new(Mutex::new(incoming)),
+        }
+    }
+
+    /// Create two connected endpoints
+    pub fn pair() -> (Endpoint, Endpoint) {
+        let (a_tx, a_rx) = mpsc::unbounded_channel();
+        let (b_tx, b_rx) = mpsc::unbounded_channel();
+        (Endpoint::new(a_tx, b_rx), Endpoint::new(b_tx, a_rx))
+    }
+
+    /// Send a performative to the peer
+    pub fn send(&self, performative: Performative) -> AmqpResult<()> {
+        self.outgoing
+            .send(performative)
+            .map_err(|_| AmqpError::transport("Performative channel closed"))
+    }
+
+    /// Receive the next performative from the peer
+    ///
+    /// Returns `None` once the peer endpoint has been dropped.
+    pub async fn recv(&self) -> Option<Performative> {
+        self.incoming.lock().await.recv().await
+    }
+
+    /// Take the next performative from the peer if one has already arrived
+    pub fn try_recv(&self) -> Option<Performative> {
+        self.incoming.try_lock().ok()?.try_recv().ok()
+    }
+}
+
+/// Field of a performative, which may itself be a described list
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+    Value(AmqpValue),
+    Described(u64, Vec<Field>),
+}
+
+fn terminus_field(code: u64, terminus: &Option<Terminus>) -> Field {
+    match terminus {
+        Some(terminus) => Field::Described(code, terminus.to_fields().into_iter().map(Field::Value).collect()),
+        None => Field::Value(AmqpValue::Null),
+    }
+}
+
+fn terminus(fields: &[Field], index: usize, code: u64) -> AmqpResult<Option<Terminus>> {
+    match fields.get(index) {
+        Some(Field::Described(descriptor, terminus)) if *descriptor == code => {
+            Ok(Some(Terminus::from_fields(&plain_values(terminus)?)?))
+        }
+        Some(Field::Described(descriptor, _)) => Err(AmqpError::decoding(format!(
+            "Unexpected terminus descriptor 0x{:02x}",
+            descriptor
+        ))),
+        _ => Ok(None),
+    }
+}
+
+fn txn_id(fields: &[Field]) -> AmqpResult<Vec<u8>> {
+    match value(fields, 0)? {
+        Some(AmqpValue::Binary(txn_id)) => Ok(txn_id.to_vec()),
+        Some(other) => Err(AmqpError::decoding(format!("Expected binary txn-id, got {:?}", other))),
+        None => Err(AmqpError::decoding("Missing mandatory field: txn-id")),
+    }
+}
+
+fn error_field(error: &types::AmqpError) -> Field {
+    let fields = vec![
+        AmqpValue::Symbol(AmqpSymbol::from(error.condition.as_str())),
+        error.description.clone().map(AmqpValue::String).unwrap_or(AmqpValue::Null),
+        error.info.clone().map(AmqpValue::Map).unwrap_or(AmqpValue::Null),
+    ];
+    Field::Described(descriptor::ERROR, fields.into_iter().map(Field::Value).collect())
+}
+
+fn error(fields: &[Field], index: usize) -> AmqpResult<Option<types::AmqpError>> {
+    match fields.get(index) {
+        Some(Field::Described(descriptor::ERROR, error)) => Ok(Some(error_from_fields(&plain_values(error)?)?)),
+        Some(Field::Described(other, _)) => Err(AmqpError::decoding(format!("Unexpected error descriptor 0x{:02x}", other))),
+        _ => Ok(None),
+    }
+}
+
+fn error_from_fields(fields: &[AmqpValue]) -> AmqpResult<types::AmqpError> {
+    let condition = match fields.first() {
+        Some(AmqpValue::Symbol(condition)) => AmqpCondition::from(condition.0.as_str()),
+        _ => return Err(AmqpError::decoding("Missing mandatory field: condition")),
+    };
+    let mut error = types::AmqpError::new(condition);
+    if let Some(AmqpValue::String(description)) = fields.get(1) {
+        error = error.with_description(description.clone());
+    }
+    if let Some(AmqpValue::Map(info)) = fields.get(2) {
+        error = error.with_info(info.clone());
+    }
+    Ok(error)
+}
+
+fn encode_fields(descriptor: u64, fields: &[Field]) -> AmqpResult<Vec<u8>> {
+    let mut encoder = Encoder::new();
+    encode_described(&mut encoder, descriptor, fields)?;
+    Ok(encoder.finish())
+}
+
+fn encode_described(encoder: &mut Encoder, descriptor: u64, fields: &[Field]) -> AmqpResult<()> {
+    // Trailing null fields are left out; the peer reads absent fields as null
+    let fields = &fields[..present_len(fields, |field| matches!(field, Field::Value(AmqpValue::Null)))];
+    encoder.encode_described_list_with(descriptor, fields.len(), |encoder| {
+        for field in fields {
+            match field {
+                Field::Value(value) => encoder.encode_value(value)?,
+                Field::Described(descriptor, fields) => encode_described(encoder, *descriptor, fields)?,
+            }
+        }
+        Ok(())
+    })
+}
+
+fn decode_fields(data: &[u8], expected: u64, name: &str) -> AmqpResult<Vec<Field>> {
+    decode_performative(data, expected, name).map(|(fields, _)| fields)
+}
+
+/// Decode the fields of a performative and the number of bytes they took
+fn decode_performative(data: &[u8], expected: u64, name: &str) -> AmqpResult<(Vec<Field>, usize)> {
+    let mut decoder = Decoder::new(data.to_vec());
+    let (descriptor, count) = decoder.decode_described_header()?;
+    if descriptor != expected {
+        return Err(AmqpError::decoding(format!(
+            "Expected {} performative (0x{:02x}), got 0x{:02x}",
+            name, expected, descriptor
+        )));
+    }
+
+    let fields = decode_field_list(&mut decoder, count)?;
+    Ok((fields, decoder.position()))
+}
+
+fn decode_field_list(decoder: &mut Decoder, count: usize) -> AmqpResult<Vec<Field>> {
+    let mut fields = Vec::with_capacity(count.min(64));
+    for _ in 0..count {
+        if decoder.peek_described() {
+            let (descriptor, count) = decoder.decode_described_header()?;
+            fields.push(Field::Described(descriptor, decode_field_list(decoder, count)?));
+        } else {
+            fields.push(Field::Value(decoder.decode_value()?));
+        }
+    }
+    Ok(fields)
+}
+
+/// Get the values of a described list that has no described fields of its own
+fn plain_values(fields: &[Field]) -> AmqpResult<Vec<AmqpValue>> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match field {
+            Field::Value(value) => Ok(value.clone()),
+            Field::Described(descriptor, _) => Err(AmqpError::decoding(format!(
+                "Unexpected described field 0x{:02x} at position {}",
+                descriptor, index
+            ))),
+        })
+        .collect()
+}
+
+fn value(fields: &[Field], index: usize) -> AmqpResult<Option<&AmqpValue>> {
+    match fields.get(index) {
+        None | Some(Field::Value(AmqpValue::Null)) => Ok(None),
+        Some(Field::Value(value)) => Ok(Some(value)),
+        Some(Field::Described(descriptor, _)) => Err(AmqpError::decoding(format!(
+            "Unexpected described field 0x{:02x} at position {}",
+            descriptor, index
+        ))),
+    }
+}
+
+fn optional_bool(field: Option<&AmqpValue>) -> AmqpResult<Option<bool>> {
+    match field {
+        None | Some(AmqpValue::Null) => Ok(None),
+        Some(AmqpValue::Boolean(value)) => Ok(Some(*value)),
+        Some(other) => Err(AmqpError::decoding(format!("Expected boolean field, got {:?}", other))),
+    }
+}
+
+fn optional_uint(field: Option<&AmqpValue>) -> AmqpResult<Option<u32>> {
+    match field {
+        None | Some(AmqpValue::Null) => Ok(None),
+        Some(AmqpValue::Uint(value)) => Ok(Some(*value)),
+        Some(AmqpValue::Ushort(value)) => Ok(Some(*value as u32)),
+        Some(AmqpValue::Ubyte(value)) => Ok(Some(*value as u32)),
+        Some(other) => Err(AmqpError::decoding(format!("Expected uint field, got {:?}", other))),
+    }
+}
+
+fn optional_ulong(field: Option<&AmqpValue>) -> AmqpResult<Option<u64>> {
+    match field {
+        None | Some(AmqpValue::Null) => Ok(None),
+        Some(AmqpValue::Ulong(value)) => Ok(Some(*value)),
+        Some(AmqpValue::Uint(value)) => Ok(Some(*value as u64)),
+        Some(other) => Err(AmqpError::decoding(format!("Expected ulong field, got {:?}", other))),
+    }
+}
+
+fn required_uint(field: Option<&AmqpValue>, name: &str) -> AmqpResult<u32> {
+    optional_uint(field)?
+        .ok_or_else(|| AmqpError::decoding(format!("Missing mandatory field: {}", name)))
+}
+
+fn optional_ushort(field: Option<&AmqpValue>) -> AmqpResult<Option<u16>> {
+    match field {
+        None | Some(AmqpValue::Null) => Ok(None),
+        Some(AmqpValue::Ushort(value)) => Ok(Some(*value)),
+        Some(AmqpValue::Ubyte(value)) => Ok(Some(*value as u16)),
+        Some(other) => Err(AmqpError::decoding(format!("Expected ushort field, got {:?}", other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_roundtrip() {
+        let mut properties = AmqpMap::new();
+        properties.insert(AmqpSymbol::from("product"), AmqpValue::String("dumq".to_string()));
+        let open = Open {
+            container_id: "client-1".to_string(),
+            hostname: Some("broker".to_string()),
+            max_frame_size: 65536,
+            channel_max: 255,
+            idle_time_out: Some(30_000),
+            offered_capabilities: vec![AmqpSymbol::from("ANONYMOUS-RELAY")],
+            desired_capabilities: vec![
+                AmqpSymbol::from("sole-connection-for-container"),
+                AmqpSymbol::from("DELAYED_DELIVERY"),
+            ],
+            properties,
+        };
+
+        let decoded = Open::decode(&open.encode().unwrap()).unwrap();
+        assert_eq!(decoded, open);
+        assert!(decoded.offers("ANONYMOUS-RELAY"));
+        assert!(decoded.desires("DELAYED_DELIVERY"));
+        assert!(!decoded.offers("DELAYED_DELIVERY"));
+    }
+
+    #[test]
+    fn test_open_decode_defaults() {
+        let mut encoder = Encoder::new();
+        encoder
+            .encode_described_list(descriptor::OPEN, &[AmqpValue::String("peer".to_string()), AmqpValue::Null, AmqpValue::Null, AmqpValue::Null, AmqpValue::Uint(0)])
+            .unwrap();
+
+        let open = Open::decode(&encoder.finish()).unwrap();
+        assert_eq!(open.container_id, "peer");
+        assert_eq!(open.max_frame_size, u32::MAX);
+        assert_eq!(open.channel_max, u16::MAX);
+        assert_eq!(open.idle_time_out, None);
+        assert!(Open::decode(&Close::default().encode().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_close_roundtrip_with_error() {
+        let close = Close {
+            error: Some(types::AmqpError::new(AmqpCondition::AmqpErrorConnectionForced).with_description("shutdown")),
+        };
+        assert_eq!(Close::decode(&close.encode().unwrap()).unwrap(), close);
+    }
+
+    #[test]
+    fn test_begin_default() {
+        let begin = Begin::default();
+        assert_eq!(begin.remote_channel, None);
+        assert_eq!(begin.handle_max, u32::MAX);
+    }
+
+    #[test]
+    fn test_begin_roundtrip() {
+        let begin = Begin {
+            remote_channel: Some(3),
+            next_outgoing_id: 1,
+            incoming_window: 100,
+            outgoing_window: 200,
+            handle_max: 7,
+        };
+
+        let encoded = begin.encode().unwrap();
+        assert_eq!(&encoded[..3], &[0x00, 0x53, 0x11]);
+        assert_eq!(Begin::decode(&encoded).unwrap(), begin);
+    }
+
+    #[test]
+    fn test_begin_decode_missing_handle_max() {
+        let mut encoder = Encoder::new();
+        encoder
+            .encode_described_list(
+                descriptor::BEGIN,
+                &[AmqpValue::Null, AmqpValue::Uint(0), AmqpValue::Uint(10), AmqpValue::Uint(10)],
+            )
+            .unwrap();
+
+        let begin = Begin::decode(&encoder.finish()).unwrap();
+        assert_eq!(begin.handle_max, u32::MAX);
+    }
+
+    #[test]
+    fn test_begin_decode_missing_mandatory_field() {
+        let mut encoder = Encoder::new();
+        encoder
+            .encode_described_list(descriptor::BEGIN, &[AmqpValue::Null, AmqpValue::Uint(0)])
+            .unwrap();
+
+        let result = Begin::decode(&encoder.finish());
+        assert!(matches!(result, Err(AmqpError::Decoding { .. })));
+    }
+
+    #[test]
+    fn test_begin_decode_wrong_descriptor() {
+        let mut encoder = Encoder::new();
+        encoder.encode_described_list(descriptor::END, &[]).unwrap();
+
+        let result = Begin::decode(&encoder.finish());
+        assert!(matches!(result, Err(AmqpError::Decoding { .. })));
+    }
+
+    #[test]
+    fn test_attach_roundtrip() {
+        let mut properties = AmqpMap::new();
+        properties.insert(AmqpSymbol::from("priority"), AmqpValue::Int(5));
+        let attach = Attach {
+            name: "orders-sender".to_string(),
+            handle: 2,
+            role: Role::Sender,
+            snd_settle_mode: SenderSettleMode::Unsettled,
+            rcv_settle_mode: ReceiverSettleMode::Second,
+            source: Some(Terminus { distribution_mode: Some(DistributionMode::Copy), ..Terminus::new("client") }),
+            target: Some(Terminus {
+                durable: TerminusDurability::Configuration,
+                expiry_policy: TerminusExpiryPolicy::Never,
+                timeout: 60,
+                ..Terminus::new("orders")
+            }),
+            coordinator: None,
+            initial_delivery_count: Some(17),
+            max_message_size: Some(1024 * 1024),
+            properties,
+        };
+
+        let encoded = attach.encode().unwrap();
+        assert_eq!(&encoded[..3], &[0x00, 0x53, 0x12]);
+        assert_eq!(Attach::decode(&encoded).unwrap(), attach);
+    }
+
+    #[test]
+    fn test_attach_null_termini() {
+        let attach = Attach {
+            name: "link".to_string(),
+            role: Role::Receiver,
+            ..Default::default()
+        };
+
+        let decoded = Attach::decode(&attach.encode().unwrap()).unwrap();
+        assert_eq!(decoded.role, Role::Receiver);
+        assert!(decoded.source.is_none());
+        assert!(decoded.target.is_none());
+    }
+
+    #[test]
+    fn test_coordinator_and_transaction_states_roundtrip() {
+        let attach = Attach {
+            name: "coordinator".to_string(),
+            source: Some(Terminus::default()),
+            coordinator: Some(Coordinator { capabilities: vec![AmqpSymbol::from("amqp:local-transactions")] }),
+            ..Default::default()
+        };
+        let decoded = Attach::decode(&attach.encode().unwrap()).unwrap();
+        assert_eq!(decoded, attach);
+        assert!(decoded.target.is_none());
+
+        let declared = Disposition {
+            role: Role::Receiver,
+            first: 0,
+            last: None,
+            settled: true,
+            state: Some(Outcome::Declared { txn_id: vec![1, 2, 3] }),
+            batchable: false,
+        };
+        assert_eq!(Disposition::decode(&declared.encode().unwrap()).unwrap(), declared);
+
+        let accepted = Disposition {
+            state: Some(Outcome::Transactional { txn_id: vec![1, 2, 3], outcome: Some(Box::new(Outcome::Accepted)) }),
+            ..declared
+        };
+        assert_eq!(Disposition::decode(&accepted.encode().unwrap()).unwrap(), accepted);
+        let transfer = Transfer {
+            delivery_id: Some(4),
+            state: Some(Outcome::Transactional { txn_id: vec![1, 2, 3], outcome: None }),
+            ..Default::default()
+        };
+        assert_eq!(Transfer::decode(&transfer.encode().unwrap()).unwrap(), transfer);
+    }
+
+    #[test]
+    fn test_detach_roundtrip_with_error() {
+        let detach = Detach {
+            handle: 4,
+            closed: true,
+            error: Some(
+                types::AmqpError::new(AmqpCondition::AmqpErrorNotAllowed).with_description("queue is exclusive"),
+            ),
+        };
+
+        let decoded = Detach::decode(&detach.encode().unwrap()).unwrap();
+        assert_eq!(decoded, detach);
+    }
+
+    #[test]
+    fn test_flow_roundtrip() {
+        let flow = Flow {
+            next_incoming_id: Some(3),
+            incoming_window: 100,
+            next_outgoing_id: 7,
+            outgoing_window: 100,
+            handle: Some(1),
+            delivery_count: Some(12),
+            link_credit: Some(50),
+            available: Some(420),
+            drain: false,
+            echo: true,
+        };
+
+        let decoded = Flow::decode(&flow.encode().unwrap()).unwrap();
+        assert_eq!(decoded, flow);
+    }
+
+    #[test]
+    fn test_flow_session_only() {
+        let flow = Flow {
+            incoming_window: 10,
+            outgoing_window: 10,
+            ..Default::default()
+        };
+
+        let decoded = Flow::decode(&flow.encode().unwrap()).unwrap();
+        assert!(decoded.handle.is_none());
+        assert!(decoded.available.is_none());
+        assert!(!decoded.echo);
+    }
+
+    #[test]
+    fn test_disposition_roundtrip_with_outcomes() {
+        let outcomes = vec![
+            Outcome::Accepted,
+            Outcome::Released,
+            Outcome::Rejected { error: None },
+            Outcome::Rejected {
+                error: Some(types::AmqpError::new(AmqpCondition::AmqpErrorDecodeError).with_description("bad schema")),
+            },
+            Outcome::Modified {
+                delivery_failed: true,
+                undeliverable_here: false,
+            },
+        ];
+        for outcome in outcomes {
+            let disposition = Disposition {
+                role: Role::Receiver,
+                first: 10,
+                last: Some(20),
+                settled: true,
+                state: Some(outcome),
+                batchable: false,
+            };
+            let decoded = Disposition::decode(&disposition.encode().unwrap()).unwrap();
+            assert_eq!(decoded, disposition);
+        }
+    }
+
+    #[test]
+    fn test_transfer_roundtrip_with_payload() {
+        let mut encoder = Encoder::new();
+        encoder.encode_message(&crate::Message::text("order")).unwrap();
+        let transfer = Transfer {
+            handle: 1,
+            delivery_id: Some(42),
+            delivery_tag: Some(42u32.to_be_bytes().to_vec()),
+            message_format: Some(0),
+            settled: Some(false),
+            more: true,
+            rcv_settle_mode: Some(ReceiverSettleMode::Second),
+            state: Some(Outcome::Accepted),
+            payload: encoder.finish(),
+            ..Default::default()
+        };
+        let decoded = Transfer::decode(&transfer.encode().unwrap()).unwrap();
+        assert_eq!(decoded, transfer);
+
+        // A continuation frame carries only the handle and its payload
+        let continuation = Transfer { handle: 1, payload: vec![0x00, 0x53, 0x77], ..Default::default() };
+        let encoded = continuation.encode().unwrap();
+        assert_eq!(Performative::decode(&encoded).unwrap(), Performative::Transfer(continuation));
+    }
+
+    #[test]
+    fn test_disposition_batch_coalesces_ranges() {
+        let dispositions = Disposition::batch(Role::Receiver, &[7, 3, 4, 5, 9, 4, 8], true, Some(Outcome::Accepted));
+
+        assert_eq!(dispositions.len(), 2);
+        assert_eq!((dispositions[0].first, dispositions[0].last), (3, Some(5)));
+        assert_eq!((dispositions[1].first, dispositions[1].last), (7, Some(9)));
+        assert!(dispositions.iter().all(|d| d.settled && d.state == Some(Outcome::Accepted)));
+        assert!(dispositions[0].covers(4));
+        assert!(!dispositions[0].covers(6));
+
+        let single = Disposition::batch(Role::Receiver, &[u32::MAX], false, None);
+        assert_eq!((single[0].first, single[0].last), (u32::MAX, None));
+        assert!(Disposition::batch(Role::Receiver, &[], true, None).is_empty());
+    }
+
+    #[test]
+    fn test_end_roundtrip() {
+        let end = End {
+            error: Some(types::AmqpError::new(AmqpCondition::AmqpErrorInternalError)),
+        };
+        assert_eq!(End::decode(&end.encode().unwrap()).unwrap(), end);
+        assert_eq!(End::decode(&End::default().encode().unwrap()).unwrap(), End::default());
+    }
+
+    #[test]
+    fn test_trailing_null_fields_omitted() {
+        // An End without an error is an empty list
+        assert_eq!(End::default().encode().unwrap(), vec![0x00, 0x53, descriptor::END as u8, 0x45]);
+
+        let detach = Detach { handle: 2, closed: true, error: None };
+        let (_, fields) = Decoder::new(detach.encode().unwrap()).decode_described_list().unwrap();
+        assert_eq!(fields, vec![AmqpValue::Uint(2), AmqpValue::Boolean(true)]);
+        assert_eq!(Detach::decode(&detach.encode().unwrap()).unwrap(), detach);
+
+        // Nulls inside nested composites are trimmed the same way
+        let attach = Attach { source: Some(Terminus::default()), ..Default::default() };
+        let decoded = Attach::decode(&attach.encode().unwrap()).unwrap();
+        assert_eq!(decoded.source, attach.source);
+    }
+
+    #[test]
+    fn test_performative_decode_dispatch() {
+        let detach = Performative::Detach(Detach::default());
+        assert_eq!(Performative::decode(&detach.encode().unwrap()).unwrap(), detach);
+
+        let begin = Performative::Begin(Begin::default());
+        assert_eq!(Performative::decode(&begin.encode().unwrap()).unwrap(), begin);
+
+        let mut encoder = Encoder::new();
+        encoder.encode_described_list(descriptor::CLOSE, &[]).unwrap();
+        assert!(matches!(
+            Performative::decode(&encoder.finish()),
+            Err(AmqpError::NotImplemented { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_pair() {
+        let (local, remote) = Endpoint::pair();
+
+        local.send(Performative::Detach(Detach::default())).unwrap();
+        assert_eq!(remote.recv().await, Some(Performative::Detach(Detach::default())));
+
+        drop(local);
+        assert_eq!(remote.recv().await, None);
+        assert!(remote.send(Performative::Detach(Detach::default())).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+//! AMQP 1.0 Broker Quotas
+//!
+//! This module holds the limits a broker in the server role enforces on its
+//! clients, so that it can be exposed to clients it does not trust. Queue
+//! limits cap the depth of a queue and the size of the messages published
+//! to it; connection limits cap the links a client attaches and the
+//! deliveries it leaves unsettled.
+//!
+//! Every check fails with the condition the broker should answer with:
+//!
+//! - a message over the size limit: `amqp:link:message-size-exceeded`,
+//!   which refuses the transfer and detaches the link
+//! - a full queue, or a connection at its link or unsettled limit:
+//!   `amqp:resource:limit-exceeded`
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::quota::{QueueLimits, Quotas};
+//! use dumq_amqp::AmqpCondition;
+//!
+//! let quotas = Quotas::new()
+//!     .max_queue_depth(1000)
+//!     .max_message_size(64 * 1024)
+//!     .max_links(16)
+//!     .queue("audit", QueueLimits { max_depth: None, max_message_size: Some(1024) });
+//!
+//! assert!(quotas.check_publish("orders", 10, 32 * 1024).is_ok());
+//! let error = quotas.check_publish("audit", 10, 32 * 1024).unwrap_err();
+//! assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorMessageSizeExceeded));
+//!
+//! let mut usage = quotas.connection_usage();
+//! usage.attach_link().unwrap();
+//! assert_eq!(usage.links(), 1);
+//! ```
+
+use crate::{AmqpCondition, AmqpError, AmqpResult};
+use std::collections::HashMap;
+
+/// Limits on one queue
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueLimits {
+    /// Messages the queue holds at most; unlimited if unset
+    pub max_depth: Option<usize>,
+    /// Largest encoded message accepted, in bytes; unlimited if unset
+    pub max_message_size: Option<u64>,
+}
+
+impl QueueLimits {
+    /// Check that a message may be published to a queue of the given depth
+    pub fn check_publish(&self, queue: &str, depth: usize, message_size: u64) -> AmqpResult<()> {
+        if let Some(limit) = self.max_message_size.filter(|limit| message_size > *limit) {
+            return Err(AmqpError::amqp_protocol(
+                AmqpCondition::AmqpErrorMessageSizeExceeded,
+                format!("Message of {} bytes exceeds the limit of {} bytes for queue '{}'", message_size, limit, queue),
+            ));
+        }
+        if let Some(limit) = self.max_depth.filter(|limit| depth >= *limit) {
+            return Err(AmqpError::amqp_protocol(
+                AmqpCondition::AmqpErrorResourceLimitExceeded,
+                format!("Queue '{}' is full at {} messages", queue, limit),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Limits on one client connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionLimits {
+    /// Links attached at once; unlimited if unset
+    pub max_links: Option<usize>,
+    /// Deliveries left unsettled at once; unlimited if unset
+    pub max_unsettled: Option<usize>,
+}
+
+/// The quotas of a broker
+///
+/// Queue limits apply to every queue unless overridden for it by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Quotas {
+    /// Limits for queues without an override
+    pub queue: QueueLimits,
+    /// Limits for every connection
+    pub connection: ConnectionLimits,
+    overrides: HashMap<String, QueueLimits>,
+}
+
+impl Quotas {
+    /// Create quotas without limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the depth of every queue
+    pub fn max_queue_depth(mut self, max_depth: usize) -> Self {
+        self.queue.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Limit the size of messages published to any queue
+    pub fn max_message_size(mut self, max_message_size: u64) -> Self {
+        self.queue.max_message_size = Some(max_message_size);
+        self
+    }
+
+    /// Limit the links of every connection
+    pub fn max_links(mut self, max_links: usize) -> Self {
+        self.connection.max_links = Some(max_links);
+        self
+    }
+
+    /// Limit the unsettled deliveries of every connection
+    pub fn max_unsettled(mut self, max_unsettled: usize) -> Self {
+        self.connection.max_unsettled = Some(max_unsettled);
+        self
+    }
+
+    /// Use other limits for one queue
+    pub fn queue(mut self, name: impl Into<String>, limits: QueueLimits) -> Self {
+        self.overrides.insert(name.into(), limits);
+        self
+    }
+
+    /// Get the limits of a queue
+    pub fn queue_limits(&self, name: &str) -> &QueueLimits {
+        self.overrides.get(name).unwrap_or(&self.queue)
+    }
+
+    /// Check that a message may be published to a queue of the given depth
+    pub fn check_publish(&self, queue: &str, depth: usize, message_size: u64) -> AmqpResult<()> {
+        self.queue_limits(queue).check_publish(queue, depth, message_size)
+    }
+
+    /// Start accounting for a new connection
+    pub fn connection_usage(&self) -> ConnectionUsage {
+        ConnectionUsage {
+            limits: self.connection,
+            links: 0,
+            unsettled: 0,
+        }
+    }
+}
+
+/// Links and unsettled deliveries of one connection, checked against its limits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionUsage {
+    limits: ConnectionLimits,
+    links: usize,
+    unsettled: usize,
+}
+
+impl ConnectionUsage {
+    /// Count a link the client attaches, refusing it at the limit
+    pub fn attach_link(&mut self) -> AmqpResult<()> {
+        if let Some(limit) = self.limits.max_links.filter(|limit| self.links >= *limit) {
+            return Err(AmqpError::amqp_protocol(
+                AmqpCondition::AmqpErrorResourceLimitExceeded,
+                format!("Connection is at its limit of {} links", limit),
+            ));
+        }
+        self.links += 1;
+        Ok(())
+    }
+
+    /// Stop counting a detached link
+    pub fn detach_link(&mut self) {
+        self.links = self.links.saturating_sub(1);
+    }
+
+    /// Count a delivery left unsettled, refusing it at the limit
+    pub fn track_unsettled(&mut self) -> AmqpResult<()> {
+        if let Some(limit) = self.limits.max_unsettled.filter(|limit| self.unsettled >= *limit) {
+            return Err(AmqpError::amqp_protocol(
+                AmqpCondition::AmqpErrorResourceLimitExceeded,
+                format!("Connection is at its limit of {} unsettled deliveries", limit),
+            ));
+        }
+        self.unsettled += 1;
+        Ok(())
+    }
+
+    /// Stop counting settled deliveries
+    pub fn settle(&mut self, count: usize) {
+        self.unsettled = self.unsettled.saturating_sub(count);
+    }
+
+    /// Get the number of deliveries that can still be left unsettled
+    ///
+    /// A broker dispatching to the connection's consumers sends no more than
+    /// this, so it never has to refuse its own deliveries.
+    pub fn unsettled_room(&self) -> usize {
+        self.limits.max_unsettled.map_or(usize::MAX, |limit| limit.saturating_sub(self.unsettled))
+    }
+
+    /// Get the number of attached links
+    pub fn links(&self) -> usize {
+        self.links
+    }
+
+    /// Get the number of unsettled deliveries
+    pub fn unsettled(&self) -> usize {
+        self.unsettled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_limits_with_override() {
+        let quotas = Quotas::new()
+            .max_queue_depth(2)
+            .queue("bulk", QueueLimits { max_depth: Some(100), max_message_size: Some(10) });
+
+        assert!(quotas.check_publish("orders", 1, 1_000_000).is_ok());
+        let error = quotas.check_publish("orders", 2, 1).unwrap_err();
+        assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorResourceLimitExceeded));
+
+        assert!(quotas.check_publish("bulk", 50, 10).is_ok());
+        let error = quotas.check_publish("bulk", 50, 11).unwrap_err();
+        assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorMessageSizeExceeded));
+    }
+
+    #[test]
+    fn test_connection_usage() {
+        let mut usage = Quotas::new().max_links(1).max_unsettled(2).connection_usage();
+
+        usage.attach_link().unwrap();
+        let error = usage.attach_link().unwrap_err();
+        assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorResourceLimitExceeded));
+        usage.detach_link();
+        assert!(usage.attach_link().is_ok());
+
+        usage.track_unsettled().unwrap();
+        assert_eq!(usage.unsettled_room(), 1);
+        usage.track_unsettled().unwrap();
+        assert!(usage.track_unsettled().is_err());
+        usage.settle(2);
+        assert_eq!((usage.unsettled(), usage.unsettled_room()), (0, 2));
+    }
+}
@@ -0,0 +1,249 @@
+//! AMQP 1.0 Automatic Reconnect
+//!
+//! This module provides the opt-in policy for re-establishing a connection
+//! whose transport fails. Attempts back off exponentially, with random
+//! jitter so clients that lost the same broker do not reconnect in lockstep,
+//! and stop after a bounded number of attempts.
+//!
+//! A [`Connection`](crate::connection::Connection) with a policy recovers on
+//! its own: the connection driver re-opens the transport, begins its sessions
+//! again, re-attaches their links with their last credit, and resends the
+//! transfers the peer had not settled. A
+//! [`NetworkConnection`](crate::network::NetworkConnection) does the same
+//! from the frames it sent when
+//! [`reconnect`](crate::network::NetworkConnection::reconnect) is called.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::connection::ConnectionBuilder;
+//! use dumq_amqp::reconnect::ReconnectPolicy;
+//! use tokio::time::Duration;
+//!
+//! let connection = ConnectionBuilder::new()
+//!     .hostname("broker.example.com")
+//!     .reconnect(ReconnectPolicy {
+//!         max_attempts: Some(20),
+//!         initial_backoff: Duration::from_millis(250),
+//!         ..Default::default()
+//!     })
+//!     .build();
+//! ```
+
+use crate::logging;
+use crate::network::NetworkConnection;
+use crate::{AmqpError, AmqpResult};
+use futures::future::BoxFuture;
+use rand::Rng;
+use std::future::Future;
+use tokio::sync::broadcast;
+use tokio::time::Duration;
+
+/// Number of reconnect events buffered for slow subscribers
+const EVENT_CAPACITY: usize = 16;
+
+/// Policy for re-establishing a lost connection
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconnectPolicy {
+    /// Maximum number of attempts per loss, unbounded if `None`
+    pub max_attempts: Option<u32>,
+    /// Delay before the first attempt
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between attempts, before jitter
+    pub max_backoff: Duration,
+    /// Factor applied to the delay after each attempt
+    pub multiplier: f64,
+    /// Fraction of the delay added or removed at random, from 0.0 to 1.0
+    pub jitter: f64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            max_attempts: Some(10),
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Get the delay before attempt number `attempt` (starting at 1)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.max(1.0).powi(i32::try_from(attempt.saturating_sub(1)).unwrap_or(i32::MAX));
+        // Capped before converting, as the factor overflows a Duration during a long outage
+        let secs = (self.initial_backoff.as_secs_f64() * factor).min(self.max_backoff.as_secs_f64());
+        let delay = Duration::try_from_secs_f64(secs).unwrap_or(self.max_backoff);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return delay;
+        }
+        delay.mul_f64(rand::thread_rng().gen_range(1.0 - jitter..=1.0 + jitter))
+    }
+
+    /// Check if attempt number `attempt` (starting at 1) is allowed
+    pub fn allows(&self, attempt: u32) -> bool {
+        self.max_attempts.is_none_or(|max_attempts| attempt <= max_attempts)
+    }
+
+    /// Run attempts until one succeeds, backing off between them
+    ///
+    /// When the attempts are used up, the result is
+    /// [`AmqpError::RetriesExhausted`] listing the failure of each.
+    pub async fn run<T, F, Fut>(&self, events: &broadcast::Sender<ReconnectEvent>, mut attempt: F) -> AmqpResult<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = AmqpResult<T>>,
+    {
+        let mut failures = Vec::new();
+        loop {
+            let number = failures.len() as u32 + 1;
+            if !self.allows(number) {
+                logging::warn!("Giving up reconnecting after {} attempts", failures.len());
+                let _ = events.send(ReconnectEvent::GaveUp { attempts: failures.len() as u32 });
+                return Err(AmqpError::retries_exhausted(failures));
+            }
+
+            let delay = self.backoff(number);
+            let _ = events.send(ReconnectEvent::Attempting { attempt: number, delay });
+            tokio::time::sleep(delay).await;
+            match attempt().await {
+                Ok(value) => return Ok(value),
+                Err(e) => {
+                    logging::debug!("Reconnect attempt {} failed: {}", number, e);
+                    failures.push(e.to_string());
+                }
+            }
+        }
+    }
+}
+
+/// Progress of recovering a lost connection
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReconnectEvent {
+    /// The transport failed
+    Disconnected {
+        /// What it failed with
+        error: String,
+    },
+    /// An attempt starts after a delay
+    Attempting {
+        /// Attempt number, starting at 1
+        attempt: u32,
+        /// Delay before the attempt
+        delay: Duration,
+    },
+    /// The connection is back, with its sessions and links replayed
+    Reconnected {
+        /// Attempts it took
+        attempts: u32,
+        /// Unsettled transfers queued to be sent again
+        resent: usize,
+    },
+    /// Every attempt failed, so the connection stays down
+    GaveUp {
+        /// Attempts made
+        attempts: u32,
+    },
+}
+
+/// Opens a new connection, ready past the Open exchange
+pub(crate) type Connector = Box<dyn FnMut() -> BoxFuture<'static, AmqpResult<NetworkConnection>> + Send>;
+
+/// Policy, connector and event channel used by a connection driver
+pub(crate) struct Reconnector {
+    policy: ReconnectPolicy,
+    connector: Connector,
+    events: broadcast::Sender<ReconnectEvent>,
+}
+
+impl Reconnector {
+    pub(crate) fn new(policy: ReconnectPolicy, connector: Connector, events: broadcast::Sender<ReconnectEvent>) -> Self {
+        Reconnector { policy, connector, events }
+    }
+
+    /// Open a new connection within the policy, reporting the attempts
+    pub(crate) async fn reconnect(&mut self, error: &AmqpError) -> AmqpResult<(NetworkConnection, u32)> {
+        logging::warn!("Connection lost, reconnecting: {}", error);
+        let _ = self.events.send(ReconnectEvent::Disconnected { error: error.to_string() });
+        let mut attempts = 0;
+        let connector = &mut self.connector;
+        let connection = self
+            .policy
+            .run(&self.events, || {
+                attempts += 1;
+                connector()
+            })
+            .await?;
+        Ok((connection, attempts))
+    }
+
+    pub(crate) fn notify(&self, event: ReconnectEvent) {
+        let _ = self.events.send(event);
+    }
+}
+
+/// Create the channel reconnect events are broadcast on
+pub(crate) fn event_channel() -> broadcast::Sender<ReconnectEvent> {
+    broadcast::channel(EVENT_CAPACITY).0
+}
+
+/// Check if an error means the transport was lost, rather than misused
+pub(crate) fn is_connection_loss(error: &AmqpError) -> bool {
+    matches!(
+        error.inner(),
+        AmqpError::Transport(_) | AmqpError::TransportClosedMidFrame { .. } | AmqpError::Io(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_grows_within_jitter() {
+        let policy = ReconnectPolicy {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+            jitter: 0.0,
+            ..Default::default()
+        };
+        let delays: Vec<u128> = (1..=4).map(|attempt| policy.backoff(attempt).as_millis()).collect();
+        assert_eq!(delays, vec![100, 200, 400, 500]);
+        assert_eq!(policy.backoff(70), Duration::from_millis(500));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_millis(500));
+
+        let jittered = ReconnectPolicy { jitter: 0.5, ..policy };
+        for _ in 0..100 {
+            let delay = jittered.backoff(2);
+            assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(300));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_reports_attempts_and_gives_up() {
+        let policy = ReconnectPolicy {
+            max_attempts: Some(3),
+            initial_backoff: Duration::from_millis(1),
+            jitter: 0.0,
+            ..Default::default()
+        };
+        let events = event_channel();
+        let mut received = events.subscribe();
+
+        let result: AmqpResult<()> = policy.run(&events, || async { Err(AmqpError::transport("refused")) }).await;
+        match result {
+            Err(AmqpError::RetriesExhausted { attempts }) => assert_eq!(attempts.len(), 3),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        let mut seen = Vec::new();
+        while let Ok(event) = received.try_recv() {
+            seen.push(event);
+        }
+        assert_eq!(seen.len(), 4);
+        assert_eq!(seen[3], ReconnectEvent::GaveUp { attempts: 3 });
+        assert!(ReconnectPolicy { max_attempts: None, ..policy }.allows(u32::MAX));
+    }
+}
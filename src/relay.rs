@@ -0,0 +1,414 @@
+//! AMQP 1.0 Relay (Router Mode)
+//!
+//! This module implements the hop-count semantics used when messages are
+//! forwarded between intermediaries. Each relay decrements a hop counter carried
+//! in the delivery annotations and records itself in a trace list, so that
+//! messages caught in a routing loop or forwarded too many times are dropped
+//! instead of circulating forever.
+//!
+//! # Delivery Annotations
+//!
+//! - **`x-opt-hops-remaining`**: Remaining hop budget (uint)
+//! - **`x-opt-relay-trace`**: List of relay IDs the message has passed through
+//!
+//! A message carrying either annotation with the wrong type is dropped, as
+//! it could otherwise reset its hop budget or trace and circulate unchecked.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::relay::{RelayBuilder, RelayOutcome};
+//! use dumq_amqp::message::Message;
+//!
+//! let mut relay = RelayBuilder::new()
+//!     .relay_id("relay-a")
+//!     .max_hops(8)
+//!     .build();
+//!
+//! match relay.process(Message::text("Hello")) {
+//!     RelayOutcome::Forward(message) => println!("Forwarding {:?}", message.body_as_text()),
+//!     RelayOutcome::Drop { reason, .. } => println!("Dropped: {}", reason),
+//! }
+//! ```
+
+use crate::logging;
+use crate::{types::{AmqpList, AnnotationKey}, AmqpSymbol, AmqpValue, Message};
+use std::collections::HashMap;
+use std::fmt;
+use tokio::sync::mpsc;
+
+/// Delivery annotation key holding the remaining hop budget
+pub const HOPS_REMAINING_KEY: &str = "x-opt-hops-remaining";
+
+/// Delivery annotation key holding the relay trace
+pub const TRACE_KEY: &str = "x-opt-relay-trace";
+
+/// Relay configuration
+#[derive(Debug, Clone)]
+pub struct RelayConfig {
+    /// Relay ID recorded in the trace
+    pub relay_id: String,
+    /// Hop budget assigned to messages that do not carry one
+    pub max_hops: u32,
+    /// Whether to drop messages that already passed through this relay
+    pub loop_detection: bool,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        RelayConfig {
+            relay_id: format!("relay-{}", crate::ids::next_short_id()),
+            max_hops: 16,
+            loop_detection: true,
+        }
+    }
+}
+
+/// Reason a message was dropped by a relay
+#[derive(Debug, Clone, PartialEq)]
+pub enum DropReason {
+    /// The hop budget was exhausted
+    HopsExhausted,
+    /// The message already passed through this relay
+    LoopDetected,
+    /// The annotation with this key has the wrong type
+    InvalidAnnotation(String),
+}
+
+impl fmt::Display for DropReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DropReason::HopsExhausted => write!(f, "hop count exhausted"),
+            DropReason::LoopDetected => write!(f, "routing loop detected"),
+            DropReason::InvalidAnnotation(key) => write!(f, "invalid {} annotation", key),
+        }
+    }
+}
+
+/// Result of processing a message
+#[derive(Debug, Clone, PartialEq)]
+pub enum RelayOutcome {
+    /// Message should be forwarded (annotations already updated)
+    Forward(Message),
+    /// Message was dropped
+    Drop {
+        /// The dropped message
+        message: Message,
+        /// Why the message was dropped
+        reason: DropReason,
+    },
+}
+
+/// Event emitted by a relay
+#[derive(Debug, Clone, PartialEq)]
+pub enum RelayEvent {
+    /// A message was dropped
+    Dropped {
+        /// Relay that dropped the message
+        relay_id: String,
+        /// Message ID, if the message had one
+        message_id: Option<String>,
+        /// Why the message was dropped
+        reason: DropReason,
+    },
+}
+
+/// AMQP 1.0 Relay
+#[derive(Debug)]
+pub struct Relay {
+    /// Relay configuration
+    config: RelayConfig,
+    /// Event subscribers
+    subscribers: Vec<mpsc::UnboundedSender<RelayEvent>>,
+    /// Number of forwarded messages
+    forwarded: u64,
+    /// Number of dropped messages
+    dropped: u64,
+}
+
+impl Relay {
+    /// Create a new relay
+    pub fn new(config: RelayConfig) -> Self {
+        Relay {
+            config,
+            subscribers: Vec::new(),
+            forwarded: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Subscribe to relay events
+    pub fn subscribe(&mut self) -> mpsc::UnboundedReceiver<RelayEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    /// Apply hop decrement and loop detection to a message
+    pub fn process(&mut self, mut message: Message) -> RelayOutcome {
+        let annotations = message.delivery_annotations.get_or_insert_with(HashMap::new);
+
+        let mut trace = match annotations.get(&AnnotationKey::from(TRACE_KEY)) {
+            Some(AmqpValue::List(trace)) => trace.clone(),
+            None => AmqpList::new(),
+            Some(_) => return self.drop_message(message, DropReason::InvalidAnnotation(TRACE_KEY.to_string())),
+        };
+        let this_relay = AmqpValue::Symbol(AmqpSymbol::from(self.config.relay_id.as_str()));
+        if self.config.loop_detection && trace.contains(&this_relay) {
+            return self.drop_message(message, DropReason::LoopDetected);
+        }
+
+        let hops = match annotations.get(&AnnotationKey::from(HOPS_REMAINING_KEY)) {
+            Some(AmqpValue::Uint(hops)) => *hops,
+            Some(AmqpValue::Ubyte(hops)) => *hops as u32,
+            Some(AmqpValue::Ushort(hops)) => *hops as u32,
+            Some(AmqpValue::Ulong(hops)) => (*hops).min(u32::MAX as u64) as u32,
+            None => self.config.max_hops,
+            Some(_) => return self.drop_message(message, DropReason::InvalidAnnotation(HOPS_REMAINING_KEY.to_string())),
+        };
+        if hops == 0 {
+            return self.drop_message(message, DropReason::HopsExhausted);
+        }
+
+        trace.push(this_relay);
+        annotations.insert(AnnotationKey::from(HOPS_REMAINING_KEY), AmqpValue::Uint(hops - 1));
+        annotations.insert(AnnotationKey::from(TRACE_KEY), AmqpValue::List(trace));
+
+        self.forwarded += 1;
+        RelayOutcome::Forward(message)
+    }
+
+    fn drop_message(&mut self, message: Message, reason: DropReason) -> RelayOutcome {
+        let message_id = message.message_id_as_string();
+        logging::warn!(
+            "Relay {} dropped message {:?}: {}",
+            self.config.relay_id,
+            message_id,
+            reason
+        );
+
+        let event = RelayEvent::Dropped {
+            relay_id: self.config.relay_id.clone(),
+            message_id,
+            reason: reason.clone(),
+        };
+        self.subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+
+        self.dropped += 1;
+        RelayOutcome::Drop { message, reason }
+    }
+
+    /// Get relay configuration
+    pub fn config(&self) -> &RelayConfig {
+        &self.config
+    }
+
+    /// Get relay ID
+    pub fn id(&self) -> &str {
+        &self.config.relay_id
+    }
+
+    /// Get number of forwarded messages
+    pub fn forwarded(&self) -> u64 {
+        self.forwarded
+    }
+
+    /// Get number of dropped messages
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+/// Relay Builder for constructing relays
+#[derive(Debug, Clone)]
+pub struct RelayBuilder {
+    config: RelayConfig,
+}
+
+impl RelayBuilder {
+    /// Create a new relay builder
+    pub fn new() -> Self {
+        RelayBuilder {
+            config: RelayConfig::default(),
+        }
+    }
+
+    /// Set the relay ID
+    pub fn relay_id(mut self, relay_id: impl Into<String>) -> Self {
+        self.config.relay_id = relay_id.into();
+        self
+    }
+
+    /// Set the default hop budget
+    pub fn max_hops(mut self, max_hops: u32) -> Self {
+        self.config.max_hops = max_hops;
+        self
+    }
+
+    /// Enable or disable loop detection
+    pub fn loop_detection(mut self, enabled: bool) -> Self {
+        self.config.loop_detection = enabled;
+        self
+    }
+
+    /// Build the relay
+    pub fn build(self) -> Relay {
+        Relay::new(self.config)
+    }
+}
+
+impl Default for RelayBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hops_remaining(message: &Message) -> Option<&AmqpValue> {
+        message
+            .delivery_annotations
+            .as_ref()
+            .and_then(|a| a.get(&AnnotationKey::from(HOPS_REMAINING_KEY)))
+    }
+
+    #[test]
+    fn test_relay_config_default() {
+        let config = RelayConfig::default();
+        assert!(config.relay_id.starts_with("relay-"));
+        assert_eq!(config.max_hops, 16);
+        assert!(config.loop_detection);
+    }
+
+    #[test]
+    fn test_relay_builder() {
+        let relay = RelayBuilder::new()
+            .relay_id("relay-a")
+            .max_hops(4)
+            .loop_detection(false)
+            .build();
+
+        assert_eq!(relay.id(), "relay-a");
+        assert_eq!(relay.config().max_hops, 4);
+        assert!(!relay.config().loop_detection);
+    }
+
+    #[test]
+    fn test_relay_initializes_and_decrements_hops() {
+        let mut relay = RelayBuilder::new().relay_id("relay-a").max_hops(3).build();
+
+        let message = match relay.process(Message::text("Hello")) {
+            RelayOutcome::Forward(message) => message,
+            other => panic!("Expected forward, got {:?}", other),
+        };
+        assert_eq!(hops_remaining(&message), Some(&AmqpValue::Uint(2)));
+        assert_eq!(relay.forwarded(), 1);
+    }
+
+    #[test]
+    fn test_relay_drops_when_hops_exhausted() {
+        let mut relay = RelayBuilder::new().max_hops(1).loop_detection(false).build();
+        let mut events = relay.subscribe();
+
+        let message = match relay.process(Message::text("Hello").with_message_id("msg-1")) {
+            RelayOutcome::Forward(message) => message,
+            other => panic!("Expected forward, got {:?}", other),
+        };
+        assert_eq!(hops_remaining(&message), Some(&AmqpValue::Uint(0)));
+
+        let outcome = relay.process(message);
+        assert!(matches!(outcome, RelayOutcome::Drop { reason: DropReason::HopsExhausted, .. }));
+        assert_eq!(relay.dropped(), 1);
+
+        let event = events.try_recv().unwrap();
+        assert_eq!(
+            event,
+            RelayEvent::Dropped {
+                relay_id: relay.id().to_string(),
+                message_id: Some("msg-1".to_string()),
+                reason: DropReason::HopsExhausted,
+            }
+        );
+    }
+
+    #[test]
+    fn test_relay_detects_loop() {
+        let mut relay_a = RelayBuilder::new().relay_id("relay-a").build();
+        let mut relay_b = RelayBuilder::new().relay_id("relay-b").build();
+
+        let message = match relay_a.process(Message::text("Hello")) {
+            RelayOutcome::Forward(message) => message,
+            other => panic!("Expected forward, got {:?}", other),
+        };
+        let message = match relay_b.process(message) {
+            RelayOutcome::Forward(message) => message,
+            other => panic!("Expected forward, got {:?}", other),
+        };
+
+        let outcome = relay_a.process(message);
+        assert!(matches!(outcome, RelayOutcome::Drop { reason: DropReason::LoopDetected, .. }));
+    }
+
+    #[test]
+    fn test_relay_loop_detection_disabled() {
+        let mut relay = RelayBuilder::new().relay_id("relay-a").loop_detection(false).build();
+
+        let message = match relay.process(Message::text("Hello")) {
+            RelayOutcome::Forward(message) => message,
+            other => panic!("Expected forward, got {:?}", other),
+        };
+        assert!(matches!(relay.process(message), RelayOutcome::Forward(_)));
+    }
+
+    #[test]
+    fn test_relay_honors_existing_hop_count() {
+        let mut relay = RelayBuilder::new().max_hops(10).build();
+
+        let mut annotations = HashMap::new();
+        annotations.insert(AnnotationKey::from(HOPS_REMAINING_KEY), AmqpValue::Ubyte(5));
+        let message = Message::builder()
+            .delivery_annotations(annotations)
+            .build();
+
+        match relay.process(message) {
+            RelayOutcome::Forward(message) => {
+                assert_eq!(hops_remaining(&message), Some(&AmqpValue::Uint(4)));
+            }
+            other => panic!("Expected forward, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_relay_drops_wrongly_typed_annotations() {
+        let mut relay = RelayBuilder::new().max_hops(10).build();
+        let cases = [
+            (HOPS_REMAINING_KEY, AmqpValue::String("100".to_string())),
+            (HOPS_REMAINING_KEY, AmqpValue::Int(-1)),
+            (TRACE_KEY, AmqpValue::Symbol(AmqpSymbol::from("relay-a"))),
+        ];
+
+        for (key, value) in cases {
+            let mut annotations = HashMap::new();
+            annotations.insert(AnnotationKey::from(key), value);
+            let message = Message::builder().delivery_annotations(annotations).build();
+            match relay.process(message) {
+                RelayOutcome::Drop { reason, .. } => assert_eq!(reason, DropReason::InvalidAnnotation(key.to_string())),
+                other => panic!("Expected drop, got {:?}", other),
+            }
+        }
+        assert_eq!(relay.dropped(), 3);
+        assert_eq!(relay.forwarded(), 0);
+    }
+
+    #[test]
+    fn test_drop_reason_display() {
+        assert_eq!(DropReason::HopsExhausted.to_string(), "hop count exhausted");
+        assert_eq!(DropReason::LoopDetected.to_string(), "routing loop detected");
+        assert_eq!(
+            DropReason::InvalidAnnotation(HOPS_REMAINING_KEY.to_string()).to_string(),
+            "invalid x-opt-hops-remaining annotation"
+        );
+    }
+}
@@ -0,0 +1,271 @@
+//! AMQP 1.0 Retry Policy
+//!
+//! This module provides an opt-in policy for retrying operations that fail
+//! with transient conditions, such as a broker temporarily refusing messages
+//! with `amqp:resource:limit-exceeded`. Retries back off exponentially and
+//! stop when the attempt or time budget is used up, returning an error that
+//! lists the failure of every attempt.
+//!
+//! On a sender, the policy acts on the outcome the receiver settles a
+//! delivery with: [`Sender::outcome`](crate::link::Sender::outcome) sends the
+//! message again while it is released or rejected with a retryable
+//! condition, and reports any other outcome as it is.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::link::LinkBuilder;
+//! use dumq_amqp::retry::RetryPolicy;
+//! use tokio::time::Duration;
+//!
+//! let policy = RetryPolicy {
+//!     max_attempts: 5,
+//!     initial_backoff: Duration::from_millis(50),
+//!     ..Default::default()
+//! };
+//!
+//! let sender = LinkBuilder::new()
+//!     .target("orders")
+//!     .retry_policy(policy)
+//!     .build_sender("session-1".to_string());
+//! ```
+
+use crate::link::DeliveryOutcome;
+use crate::logging;
+use crate::{AmqpCondition, AmqpError, AmqpResult};
+use std::future::Future;
+use tokio::time::{Duration, Instant};
+
+/// Retry policy for transient failures
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first
+    pub max_attempts: u32,
+    /// Maximum total time spent retrying, if bounded
+    pub max_elapsed: Option<Duration>,
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between retries
+    pub max_backoff: Duration,
+    /// Factor applied to the delay after each retry
+    pub multiplier: f64,
+    /// Conditions treated as transient
+    ///
+    /// A connection that closed partway through a frame is always retried:
+    /// the peer went away rather than refusing the operation.
+    pub retryable: Vec<AmqpCondition>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            max_elapsed: None,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2.0,
+            retryable: vec![
+                AmqpCondition::AmqpErrorResourceLimitExceeded,
+                AmqpCondition::AmqpErrorResourceLocked,
+                AmqpCondition::AmqpErrorTransferLimitExceeded,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Check if an error is transient under this policy
+    pub fn is_retryable(&self, error: &AmqpError) -> bool {
+        matches!(error.inner(), AmqpError::TransportClosedMidFrame { .. })
+            || error
+                .condition()
+                .is_some_and(|condition| self.retryable.contains(condition))
+    }
+
+    /// Check if a delivery settled with this outcome is worth sending again
+    ///
+    /// A released message was not processed, and a rejection is transient
+    /// when its condition is retryable.
+    pub fn is_retryable_outcome(&self, outcome: &DeliveryOutcome) -> bool {
+        match outcome {
+            DeliveryOutcome::Released => true,
+            DeliveryOutcome::Rejected(error) => self.retryable.contains(&error.condition),
+            _ => false,
+        }
+    }
+
+    /// Get the delay before retry number `retry` (starting at 1)
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = self.multiplier.max(1.0).powi(i32::try_from(retry.saturating_sub(1)).unwrap_or(i32::MAX));
+        // Capped before converting, as the factor overflows a Duration after enough retries
+        let secs = (self.initial_backoff.as_secs_f64() * factor).min(self.max_backoff.as_secs_f64());
+        Duration::try_from_secs_f64(secs).unwrap_or(self.max_backoff)
+    }
+
+    /// Run an operation, retrying transient failures
+    ///
+    /// Non-transient errors are returned as-is. When the budget is used up,
+    /// the result is [`AmqpError::RetriesExhausted`] listing every attempt.
+    pub async fn run<T, F, Fut>(&self, mut operation: F) -> AmqpResult<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = AmqpResult<T>>,
+    {
+        let mut attempts = Vec::new();
+        let start = Instant::now();
+
+        loop {
+            let error = match operation().await {
+                Ok(value) => return Ok(value),
+                Err(error) if self.is_retryable(&error) => error,
+                Err(error) => return Err(error),
+            };
+            attempts.push(error.to_string());
+
+            logging::debug!("Retrying after transient failure ({}): {}", attempts.len(), error);
+            self.wait_for_retry(&attempts, start).await?;
+        }
+    }
+
+    /// Wait out the backoff after the failed attempts, if the budget allows another
+    ///
+    /// Fails with [`AmqpError::RetriesExhausted`] listing the attempts
+    /// otherwise.
+    pub(crate) async fn wait_for_retry(&self, attempts: &[String], start: Instant) -> AmqpResult<()> {
+        let retry = attempts.len() as u32;
+        let delay = self.backoff(retry);
+        let out_of_time = self
+            .max_elapsed
+            .is_some_and(|max_elapsed| start.elapsed() + delay > max_elapsed);
+        if retry >= self.max_attempts || out_of_time {
+            return Err(AmqpError::retries_exhausted(attempts.to_vec()));
+        }
+        tokio::time::sleep(delay).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn transient() -> AmqpError {
+        AmqpError::amqp_protocol(AmqpCondition::AmqpErrorResourceLimitExceeded, "queue full")
+    }
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_retry_policy_default() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.max_attempts, 3);
+        assert!(policy.max_elapsed.is_none());
+        assert!(policy.retryable.contains(&AmqpCondition::AmqpErrorResourceLimitExceeded));
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let policy = RetryPolicy::default();
+        assert!(policy.is_retryable(&transient()));
+        assert!(!policy.is_retryable(&AmqpError::amqp_protocol(AmqpCondition::AmqpErrorNotAllowed, "denied")));
+        assert!(!policy.is_retryable(&AmqpError::link("No credit available")));
+        assert!(policy.is_retryable(&AmqpError::transport_closed_mid_frame(20, 12)));
+    }
+
+    #[test]
+    fn test_is_retryable_outcome() {
+        let policy = RetryPolicy::default();
+        let rejected = |condition| DeliveryOutcome::Rejected(crate::types::AmqpError::new(condition));
+        assert!(policy.is_retryable_outcome(&DeliveryOutcome::Released));
+        assert!(policy.is_retryable_outcome(&rejected(AmqpCondition::AmqpErrorResourceLimitExceeded)));
+        assert!(!policy.is_retryable_outcome(&rejected(AmqpCondition::AmqpErrorDecodeError)));
+        assert!(!policy.is_retryable_outcome(&DeliveryOutcome::Accepted));
+    }
+
+    #[test]
+    fn test_backoff_grows_and_caps() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(350),
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(350));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_millis(350));
+    }
+
+    #[tokio::test]
+    async fn test_run_succeeds_after_transient_failures() {
+        let calls = AtomicU32::new(0);
+        let result = fast_policy()
+            .run(|| async {
+                if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                    Err(transient())
+                } else {
+                    Ok(7)
+                }
+            })
+            .await;
+
+        assert_eq!(result.unwrap(), 7);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_run_exhausts_budget() {
+        let calls = AtomicU32::new(0);
+        let result: AmqpResult<()> = fast_policy()
+            .run(|| async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(transient())
+            })
+            .await;
+
+        match result {
+            Err(AmqpError::RetriesExhausted { attempts }) => {
+                assert_eq!(attempts.len(), 3);
+                assert!(attempts[0].contains("queue full"));
+            }
+            other => panic!("Expected retries exhausted, got {:?}", other),
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_run_stops_on_permanent_error() {
+        let calls = AtomicU32::new(0);
+        let result: AmqpResult<()> = fast_policy()
+            .run(|| async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(AmqpError::link("detached"))
+            })
+            .await;
+
+        assert!(matches!(result, Err(AmqpError::Link(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_respects_max_elapsed() {
+        let policy = RetryPolicy {
+            max_attempts: u32::MAX,
+            max_elapsed: Some(Duration::from_millis(30)),
+            initial_backoff: Duration::from_millis(10),
+            multiplier: 1.0,
+            ..Default::default()
+        };
+
+        let result: AmqpResult<()> = policy.run(|| async { Err(transient()) }).await;
+        match result {
+            Err(AmqpError::RetriesExhausted { attempts }) => assert!(attempts.len() <= 4),
+            other => panic!("Expected retries exhausted, got {:?}", other),
+        }
+    }
+}
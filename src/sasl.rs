@@ -0,0 +1,1062 @@
+//! AMQP 1.0 SASL Negotiation
+//!
+//! This module implements the client side of the SASL layer that precedes
+//! the AMQP protocol header when a peer requires authentication. The client
+//! sends the SASL protocol header, picks a mechanism from the peer's
+//! sasl-mechanisms frame, answers with sasl-init and waits for the
+//! sasl-outcome. ANONYMOUS, PLAIN, EXTERNAL and CRAM-SHA-256 are supported;
+//! only CRAM-SHA-256 has a challenge round.
+//!
+//! EXTERNAL authenticates with an identity the layer below AMQP already
+//! established, normally the client certificate of a mutual-TLS
+//! connection. It is how brokers set up for certificate authentication,
+//! such as RabbitMQ with `rabbitmq_auth_mechanism_ssl`, accept clients
+//! without a user name and password. With the `tls` feature, the certificate
+//! is presented by a [`TlsConnector`](crate::tls::TlsConnector) built with
+//! [`client_certificate`](crate::tls::TlsConnectorBuilder::client_certificate)
+//! and given to [`NetworkBuilder::tls`](crate::network::NetworkBuilder::tls).
+//!
+//! The server side, used by [`AmqpListener`](crate::server::AmqpListener),
+//! offers the mechanisms of a [`SaslAcceptor`] and checks the client's
+//! sasl-init against it. It accepts ANONYMOUS, PLAIN and CRAM-SHA-256.
+//!
+//! A listener reachable from a shared network invites guessing, so an
+//! acceptor locks out a remote address after repeated failures, answering
+//! it with a temporary failure until the [`LockoutPolicy`] window passes.
+//! [`PlainUsers`] checks passwords in constant time and spends the same
+//! effort on unknown users.
+//!
+//! PLAIN carries no nonce, so a captured sasl-init can be replayed as is.
+//! CRAM-SHA-256 protects against replay: the server challenges each attempt
+//! with a fresh nonce, and the client answers with an HMAC-SHA-256 of the
+//! challenge keyed by its password, in the layout of CRAM-MD5 (RFC 2195).
+//! A response is only good for the challenge it answers, and that
+//! challenge is never issued again.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::sasl::{SaslCredentials, SaslMechanisms};
+//! use dumq_amqp::AmqpSymbol;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let offered = SaslMechanisms {
+//!     mechanisms: vec![AmqpSymbol::from("PLAIN"), AmqpSymbol::from("ANONYMOUS")],
+//! };
+//! let decoded = SaslMechanisms::decode(&offered.encode()?)?;
+//!
+//! let credentials = SaslCredentials::plain("guest", "guest");
+//! assert!(decoded.offers(credentials.mechanism()));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::codec::{Decoder, Encoder};
+use crate::integrity::constant_time_eq;
+use crate::logging;
+use crate::transport::{Frame, FrameHeader, FrameType, ProtocolHeader, ProtocolNegotiator, Transport};
+use crate::{AmqpError, AmqpResult, AmqpSymbol, AmqpValue};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use tokio::time::{Duration, Instant};
+
+/// SASL frame body descriptor codes
+pub mod descriptor {
+    pub const SASL_MECHANISMS: u64 = 0x40;
+    pub const SASL_INIT: u64 = 0x41;
+    pub const SASL_CHALLENGE: u64 = 0x42;
+    pub const SASL_RESPONSE: u64 = 0x43;
+    pub const SASL_OUTCOME: u64 = 0x44;
+}
+
+/// Credentials used to authenticate over SASL
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaslCredentials {
+    /// No credentials
+    Anonymous,
+    /// User name and password
+    Plain {
+        /// Identity to act as, if different from the authenticated one
+        authzid: Option<String>,
+        /// Identity to authenticate as
+        authcid: String,
+        /// Password
+        password: String,
+    },
+    /// Identity established outside SASL, e.g. by a TLS client certificate
+    External {
+        /// Identity to act as, if different from the authenticated one
+        authzid: Option<String>,
+    },
+    /// User name and password proven by answering the server's challenge
+    CramSha256 {
+        /// Identity to authenticate as
+        authcid: String,
+        /// Password, which keys the answer and is never sent
+        password: String,
+    },
+}
+
+impl SaslCredentials {
+    /// Create PLAIN credentials
+    pub fn plain(authcid: impl Into<String>, password: impl Into<String>) -> Self {
+        SaslCredentials::Plain {
+            authzid: None,
+            authcid: authcid.into(),
+            password: password.into(),
+        }
+    }
+
+    /// Create EXTERNAL credentials acting as the authenticated identity
+    pub fn external() -> Self {
+        SaslCredentials::External { authzid: None }
+    }
+
+    /// Create CRAM-SHA-256 credentials
+    pub fn cram_sha256(authcid: impl Into<String>, password: impl Into<String>) -> Self {
+        SaslCredentials::CramSha256 {
+            authcid: authcid.into(),
+            password: password.into(),
+        }
+    }
+
+    /// Get the mechanism name
+    pub fn mechanism(&self) -> &'static str {
+        match self {
+            SaslCredentials::Anonymous => "ANONYMOUS",
+            SaslCredentials::Plain { .. } => "PLAIN",
+            SaslCredentials::External { .. } => "EXTERNAL",
+            SaslCredentials::CramSha256 { .. } => CRAM_SHA_256,
+        }
+    }
+
+    /// Answer a challenge from the server
+    ///
+    /// Only CRAM-SHA-256 is challenged: the answer is the user name, a
+    /// space and the hex HMAC-SHA-256 of the challenge keyed by the password.
+    pub fn challenge_response(&self, challenge: &[u8]) -> AmqpResult<Vec<u8>> {
+        match self {
+            SaslCredentials::CramSha256 { authcid, password } => {
+                Ok(format!("{} {}", authcid, cram_digest(password.as_bytes(), challenge)).into_bytes())
+            }
+            other => Err(AmqpError::not_implemented(format!(
+                "SASL challenges are not supported by {}",
+                other.mechanism()
+            ))),
+        }
+    }
+
+    /// Build the initial response sent with sasl-init
+    pub fn initial_response(&self) -> AmqpResult<Option<Vec<u8>>> {
+        match self {
+            SaslCredentials::Anonymous => Ok(None),
+            SaslCredentials::Plain { authzid, authcid, password } => {
+                ProtocolNegotiator::sasl_plain_response(authzid.as_deref(), authcid, password).map(Some)
+            }
+            // RFC 4422 appendix A: the authzid, empty to act as the authenticated identity
+            SaslCredentials::External { authzid } => {
+                Ok(Some(authzid.as_deref().unwrap_or_default().as_bytes().to_vec()))
+            }
+            // The user name goes with the answer to the challenge
+            SaslCredentials::CramSha256 { .. } => Ok(None),
+        }
+    }
+}
+
+/// Name of the challenged mechanism protecting against replay
+const CRAM_SHA_256: &str = "CRAM-SHA-256";
+
+/// Hex HMAC-SHA-256 of a CRAM challenge keyed by a password
+fn cram_digest(password: &[u8], challenge: &[u8]) -> String {
+    use hmac::{Hmac, Mac};
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(password).expect("HMAC takes keys of any length");
+    mac.update(challenge);
+    mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Outcome code of a SASL exchange
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaslCode {
+    /// Authentication succeeded
+    Ok = 0,
+    /// Authentication failed due to bad credentials
+    Auth = 1,
+    /// Authentication failed due to a system error
+    Sys = 2,
+    /// Authentication failed due to an unrecoverable system error
+    SysPerm = 3,
+    /// Authentication failed due to a transient system error
+    SysTemp = 4,
+}
+
+impl SaslCode {
+    fn from_u8(code: u8) -> AmqpResult<Self> {
+        match code {
+            0 => Ok(SaslCode::Ok),
+            1 => Ok(SaslCode::Auth),
+            2 => Ok(SaslCode::Sys),
+            3 => Ok(SaslCode::SysPerm),
+            4 => Ok(SaslCode::SysTemp),
+            other => Err(AmqpError::decoding(format!("Unknown SASL outcome code {}", other))),
+        }
+    }
+}
+
+/// Mechanisms offered by the peer
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SaslMechanisms {
+    /// Offered mechanisms, in the peer's order of preference
+    pub mechanisms: Vec<AmqpSymbol>,
+}
+
+impl SaslMechanisms {
+    /// Check if a mechanism is offered
+    pub fn offers(&self, mechanism: &str) -> bool {
+        self.mechanisms.iter().any(|offered| offered.0 == mechanism)
+    }
+
+    /// Encode the sasl-mechanisms frame body
+    pub fn encode(&self) -> AmqpResult<Vec<u8>> {
+        let mechanisms = match self.mechanisms.as_slice() {
+            [single] => AmqpValue::Symbol(single.clone()),
+            all => AmqpValue::Array(all.iter().cloned().map(AmqpValue::Symbol).collect()),
+        };
+        encode_body(descriptor::SASL_MECHANISMS, &[mechanisms])
+    }
+
+    /// Decode a sasl-mechanisms frame body
+    pub fn decode(data: &[u8]) -> AmqpResult<Self> {
+        let fields = decode_body(data, descriptor::SASL_MECHANISMS, "sasl-mechanisms")?;
+        let mechanisms = match fields.into_iter().next() {
+            Some(AmqpValue::Symbol(mechanism)) => vec![mechanism],
+            Some(AmqpValue::Array(mechanisms)) => mechanisms
+                .into_iter()
+                .map(|mechanism| match mechanism {
+                    AmqpValue::Symbol(mechanism) => Ok(mechanism),
+                    other => Err(AmqpError::decoding(format!("Expected mechanism symbol, got {:?}", other))),
+                })
+                .collect::<AmqpResult<_>>()?,
+            _ => return Err(AmqpError::decoding("Missing mandatory field: sasl-server-mechanisms")),
+        };
+        Ok(SaslMechanisms { mechanisms })
+    }
+}
+
+/// Mechanism selection and initial response
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaslInit {
+    /// Selected mechanism
+    pub mechanism: AmqpSymbol,
+    /// Initial response, if the mechanism has one
+    pub initial_response: Option<Vec<u8>>,
+    /// Name of the host being connected to
+    pub hostname: Option<String>,
+}
+
+impl SaslInit {
+    /// Encode the sasl-init frame body
+    pub fn encode(&self) -> AmqpResult<Vec<u8>> {
+        let fields = [
+            AmqpValue::Symbol(self.mechanism.clone()),
+            self.initial_response.clone().map_or(AmqpValue::Null, |response| AmqpValue::Binary(response.into())),
+            self.hostname.clone().map_or(AmqpValue::Null, AmqpValue::String),
+        ];
+        encode_body(descriptor::SASL_INIT, &fields)
+    }
+
+    /// Decode a sasl-init frame body
+    pub fn decode(data: &[u8]) -> AmqpResult<Self> {
+        let mut fields = decode_body(data, descriptor::SASL_INIT, "sasl-init")?.into_iter();
+        let mechanism = match fields.next() {
+            Some(AmqpValue::Symbol(mechanism)) => mechanism,
+            _ => return Err(AmqpError::decoding("Missing mandatory field: mechanism")),
+        };
+        let initial_response = match fields.next() {
+            Some(AmqpValue::Binary(response)) => Some(response.to_vec()),
+            _ => None,
+        };
+        let hostname = match fields.next() {
+            Some(AmqpValue::String(hostname)) => Some(hostname),
+            _ => None,
+        };
+        Ok(SaslInit {
+            mechanism,
+            initial_response,
+            hostname,
+        })
+    }
+}
+
+/// Result of the authentication
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaslOutcome {
+    /// Outcome code
+    pub code: SaslCode,
+    /// Additional data for the client, on success
+    pub additional_data: Option<Vec<u8>>,
+}
+
+impl SaslOutcome {
+    /// Encode the sasl-outcome frame body
+    pub fn encode(&self) -> AmqpResult<Vec<u8>> {
+        let fields = [
+            AmqpValue::Ubyte(self.code as u8),
+            self.additional_data.clone().map_or(AmqpValue::Null, |data| AmqpValue::Binary(data.into())),
+        ];
+        encode_body(descriptor::SASL_OUTCOME, &fields)
+    }
+
+    /// Decode a sasl-outcome frame body
+    pub fn decode(data: &[u8]) -> AmqpResult<Self> {
+        let mut fields = decode_body(data, descriptor::SASL_OUTCOME, "sasl-outcome")?.into_iter();
+        let code = match fields.next() {
+            Some(AmqpValue::Ubyte(code)) => SaslCode::from_u8(code)?,
+            _ => return Err(AmqpError::decoding("Missing mandatory field: code")),
+        };
+        let additional_data = match fields.next() {
+            Some(AmqpValue::Binary(data)) => Some(data.to_vec()),
+            _ => None,
+        };
+        Ok(SaslOutcome { code, additional_data })
+    }
+}
+
+/// Challenge from the server, to be answered with a [`SaslResponse`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaslChallenge {
+    /// Security challenge data
+    pub challenge: Vec<u8>,
+}
+
+impl SaslChallenge {
+    /// Encode the sasl-challenge frame body
+    pub fn encode(&self) -> AmqpResult<Vec<u8>> {
+        encode_body(descriptor::SASL_CHALLENGE, &[AmqpValue::Binary(self.challenge.clone().into())])
+    }
+
+    /// Decode a sasl-challenge frame body
+    pub fn decode(data: &[u8]) -> AmqpResult<Self> {
+        match decode_body(data, descriptor::SASL_CHALLENGE, "sasl-challenge")?.into_iter().next() {
+            Some(AmqpValue::Binary(challenge)) => Ok(SaslChallenge { challenge: challenge.to_vec() }),
+            _ => Err(AmqpError::decoding("Missing mandatory field: challenge")),
+        }
+    }
+}
+
+/// Client's answer to a [`SaslChallenge`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaslResponse {
+    /// Security response data
+    pub response: Vec<u8>,
+}
+
+impl SaslResponse {
+    /// Encode the sasl-response frame body
+    pub fn encode(&self) -> AmqpResult<Vec<u8>> {
+        encode_body(descriptor::SASL_RESPONSE, &[AmqpValue::Binary(self.response.clone().into())])
+    }
+
+    /// Decode a sasl-response frame body
+    pub fn decode(data: &[u8]) -> AmqpResult<Self> {
+        match decode_body(data, descriptor::SASL_RESPONSE, "sasl-response")?.into_iter().next() {
+            Some(AmqpValue::Binary(response)) => Ok(SaslResponse { response: response.to_vec() }),
+            _ => Err(AmqpError::decoding("Missing mandatory field: response")),
+        }
+    }
+}
+
+/// Authenticate over a freshly connected transport
+///
+/// Exchanges SASL protocol headers, selects the credentials' mechanism and
+/// completes the exchange. On success the transport is ready for the AMQP
+/// protocol header.
+pub async fn authenticate(
+    transport: &mut Transport,
+    credentials: &SaslCredentials,
+    hostname: &str,
+) -> AmqpResult<()> {
+    ProtocolNegotiator::exchange_header(transport, ProtocolHeader::SASL).await?;
+
+    let offered = SaslMechanisms::decode(&receive_body(transport).await?)?;
+    let mechanism = credentials.mechanism();
+    if !offered.offers(mechanism) {
+        let offered = offered.mechanisms.iter().map(|m| m.as_str().to_string()).collect();
+        return Err(AmqpError::sasl_mechanism_mismatch(mechanism, offered));
+    }
+
+    let init = SaslInit {
+        mechanism: AmqpSymbol::from(mechanism),
+        initial_response: credentials.initial_response()?,
+        hostname: Some(hostname.to_string()),
+    };
+    send_body(transport, init.encode()?).await?;
+
+    let mut body = receive_body(transport).await?;
+    let (code, _) = Decoder::new(body.clone()).decode_described_header()?;
+    if code == descriptor::SASL_CHALLENGE {
+        let challenge = SaslChallenge::decode(&body)?;
+        let response = SaslResponse { response: credentials.challenge_response(&challenge.challenge)? };
+        send_body(transport, response.encode()?).await?;
+        body = receive_body(transport).await?;
+    }
+
+    let outcome = SaslOutcome::decode(&body)?;
+    if outcome.code != SaslCode::Ok {
+        return Err(AmqpError::authentication_rejected(mechanism, outcome.code));
+    }
+
+    logging::debug!("SASL {} authentication succeeded", mechanism);
+    Ok(())
+}
+
+/// Hook checking the user name and password a client presents with PLAIN
+///
+/// Closures taking the user name and password implement this trait.
+pub trait PlainVerifier: Send + Sync {
+    /// Check if the password is right for the user
+    fn verify(&self, authcid: &str, password: &str) -> bool;
+}
+
+impl<F> PlainVerifier for F
+where
+    F: Fn(&str, &str) -> bool + Send + Sync,
+{
+    fn verify(&self, authcid: &str, password: &str) -> bool {
+        self(authcid, password)
+    }
+}
+
+/// PLAIN verifier for a fixed set of users
+///
+/// Passwords are compared in constant time, and a user that does not exist
+/// costs a comparison too, so timing tells nothing about either.
+#[derive(Clone, Default)]
+pub struct PlainUsers {
+    users: HashMap<String, String>,
+}
+
+impl PlainUsers {
+    /// Create a verifier without users
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a user and its password
+    pub fn user(mut self, authcid: impl Into<String>, password: impl Into<String>) -> Self {
+        self.users.insert(authcid.into(), password.into());
+        self
+    }
+
+    /// Check a CRAM-SHA-256 answer to a challenge, returning its user
+    fn verify_cram(&self, challenge: &[u8], response: &[u8]) -> Option<String> {
+        let response = std::str::from_utf8(response).ok()?;
+        let (authcid, digest) = response.rsplit_once(' ')?;
+        // An unknown user costs a digest too
+        let password = self.users.get(authcid).map_or("", String::as_str);
+        let expected = cram_digest(password.as_bytes(), challenge);
+        let matches = constant_time_eq(expected.as_bytes(), digest.as_bytes());
+        (matches && self.users.contains_key(authcid)).then(|| authcid.to_string())
+    }
+}
+
+impl PlainVerifier for PlainUsers {
+    fn verify(&self, authcid: &str, password: &str) -> bool {
+        match self.users.get(authcid) {
+            Some(expected) => constant_time_eq(expected.as_bytes(), password.as_bytes()),
+            None => {
+                constant_time_eq(password.as_bytes(), password.as_bytes());
+                false
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for PlainUsers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut users: Vec<&String> = self.users.keys().collect();
+        users.sort();
+        f.debug_struct("PlainUsers").field("users", &users).finish()
+    }
+}
+
+/// How many failed authentications lock out a remote address, and for how long
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockoutPolicy {
+    /// Failures within the window that lock the address out
+    pub max_failures: u32,
+    /// Window failures are counted in, and how long a lockout lasts
+    pub window: Duration,
+}
+
+impl Default for LockoutPolicy {
+    fn default() -> Self {
+        LockoutPolicy {
+            max_failures: 5,
+            window: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Recent failures per remote address
+#[derive(Debug, Default)]
+struct Failures {
+    addresses: Mutex<HashMap<IpAddr, Vec<Instant>>>,
+}
+
+impl Failures {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<IpAddr, Vec<Instant>>> {
+        self.addresses.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Count an attempt as failed up front, unless the address is locked out
+    ///
+    /// Checking and counting under one lock keeps parallel attempts from
+    /// all passing the check before any of them fails; a success clears
+    /// the count again.
+    fn reserve(&self, remote: IpAddr, policy: &LockoutPolicy) -> bool {
+        let now = Instant::now();
+        let mut addresses = self.lock();
+        addresses.retain(|_, failures| {
+            failures.retain(|failed| now.duration_since(*failed) < policy.window);
+            !failures.is_empty()
+        });
+        let failures = addresses.entry(remote).or_default();
+        if failures.len() >= policy.max_failures as usize {
+            return false;
+        }
+        failures.push(now);
+        true
+    }
+
+    fn clear(&self, remote: IpAddr) {
+        self.lock().remove(&remote);
+    }
+}
+
+/// Mechanisms a server accepts, and how it checks the credentials
+///
+/// An acceptor without mechanisms refuses every client. Remote addresses
+/// are locked out after repeated failures, per [`LockoutPolicy::default`]
+/// unless set otherwise; clones share the failures counted.
+#[derive(Clone)]
+pub struct SaslAcceptor {
+    anonymous: bool,
+    plain: Option<Arc<dyn PlainVerifier>>,
+    cram: Option<PlainUsers>,
+    lockout: Option<LockoutPolicy>,
+    failures: Arc<Failures>,
+}
+
+impl Default for SaslAcceptor {
+    fn default() -> Self {
+        SaslAcceptor {
+            anonymous: false,
+            plain: None,
+            cram: None,
+            lockout: Some(LockoutPolicy::default()),
+            failures: Arc::new(Failures::default()),
+        }
+    }
+}
+
+impl SaslAcceptor {
+    /// Create an acceptor without mechanisms
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how remote addresses are locked out, or never lock them out
+    pub fn lockout(mut self, policy: Option<LockoutPolicy>) -> Self {
+        self.lockout = policy;
+        self
+    }
+
+    /// Accept ANONYMOUS
+    pub fn anonymous(mut self) -> Self {
+        self.anonymous = true;
+        self
+    }
+
+    /// Accept PLAIN, checking the credentials with a verifier
+    pub fn plain(mut self, verifier: impl PlainVerifier + 'static) -> Self {
+        self.plain = Some(Arc::new(verifier));
+        self
+    }
+
+    /// Accept CRAM-SHA-256 for a set of users, challenging each attempt
+    pub fn cram_sha256(mut self, users: PlainUsers) -> Self {
+        self.cram = Some(users);
+        self
+    }
+
+    /// Get the mechanisms offered, CRAM-SHA-256 first, then PLAIN
+    pub fn mechanisms(&self) -> SaslMechanisms {
+        let cram = self.cram.is_some().then_some(CRAM_SHA_256);
+        let plain = self.plain.is_some().then_some("PLAIN");
+        let anonymous = self.anonymous.then_some("ANONYMOUS");
+        SaslMechanisms {
+            mechanisms: cram.into_iter().chain(plain).chain(anonymous).map(AmqpSymbol::from).collect(),
+        }
+    }
+
+    /// Check a client's sasl-init, returning the authenticated user
+    ///
+    /// ANONYMOUS authenticates no user. PLAIN fails if the client asks to act
+    /// as a user other than the one it authenticates as. CRAM-SHA-256 needs
+    /// a challenge round, so it only succeeds through [`accept`].
+    pub fn check(&self, init: &SaslInit) -> Result<Option<String>, SaslCode> {
+        match init.mechanism.as_str() {
+            "ANONYMOUS" if self.anonymous => Ok(None),
+            "PLAIN" => {
+                let verifier = self.plain.as_ref().ok_or(SaslCode::Auth)?;
+                let response = init.initial_response.as_deref().ok_or(SaslCode::Auth)?;
+                let response = std::str::from_utf8(response).map_err(|_| SaslCode::Auth)?;
+                let [authzid, authcid, password] = response.split('\0').collect::<Vec<_>>()[..] else {
+                    return Err(SaslCode::Auth);
+                };
+                if !authzid.is_empty() && authzid != authcid {
+                    return Err(SaslCode::Auth);
+                }
+                if !verifier.verify(authcid, password) {
+                    return Err(SaslCode::Auth);
+                }
+                Ok(Some(authcid.to_string()))
+            }
+            _ => Err(SaslCode::Auth),
+        }
+    }
+
+    /// Check a sasl-init from a remote address, counting its failures
+    ///
+    /// A locked out address fails with [`SaslCode::SysTemp`] without its
+    /// credentials being checked. Success clears the address's failures.
+    pub fn check_from(&self, remote: IpAddr, init: &SaslInit) -> Result<Option<String>, SaslCode> {
+        self.reserve(remote)?;
+        self.settle(remote, self.check(init))
+    }
+
+    /// Count an attempt from a remote address, failing if it is locked out
+    fn reserve(&self, remote: IpAddr) -> Result<(), SaslCode> {
+        match &self.lockout {
+            Some(policy) if !self.failures.reserve(remote, policy) => Err(SaslCode::SysTemp),
+            _ => Ok(()),
+        }
+    }
+
+    /// Clear the failures of an address that authenticated
+    fn settle(&self, remote: IpAddr, checked: Result<Option<String>, SaslCode>) -> Result<Option<String>, SaslCode> {
+        if checked.is_ok() && self.lockout.is_some() {
+            self.failures.clear(remote);
+        }
+        checked
+    }
+
+    /// Challenge a CRAM-SHA-256 attempt with a fresh nonce and check the answer
+    async fn challenge(&self, transport: &mut Transport, init: &SaslInit) -> AmqpResult<Result<Option<String>, SaslCode>> {
+        let Some(users) = &self.cram else {
+            return Ok(Err(SaslCode::Auth));
+        };
+        let nonce: String = rand::random::<[u8; 16]>().iter().map(|byte| format!("{:02x}", byte)).collect();
+        let millis = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis());
+        let host = init.hostname.as_deref().unwrap_or("dumq-amqp");
+        let challenge = format!("<{}.{}@{}>", nonce, millis, host).into_bytes();
+        send_body(transport, SaslChallenge { challenge: challenge.clone() }.encode()?).await?;
+
+        let response = SaslResponse::decode(&receive_body(transport).await?)?;
+        Ok(users.verify_cram(&challenge, &response.response).map(Some).ok_or(SaslCode::Auth))
+    }
+}
+
+impl std::fmt::Debug for SaslAcceptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SaslAcceptor")
+            .field("anonymous", &self.anonymous)
+            .field("plain", &self.plain.is_some())
+            .field("cram", &self.cram)
+            .field("lockout", &self.lockout)
+            .finish()
+    }
+}
+
+/// Run the server side of the SASL layer
+///
+/// The client's SASL protocol header must already have been read. The
+/// header is answered, the acceptor's mechanisms offered and the client's
+/// sasl-init checked, counting failures against the remote address. A
+/// CRAM-SHA-256 attempt is challenged first.
+/// Returns the authenticated user, if the mechanism has one; the client
+/// then goes on with the AMQP protocol header. A refused client is sent the
+/// outcome before the error is returned.
+pub async fn accept(transport: &mut Transport, acceptor: &SaslAcceptor, remote: IpAddr) -> AmqpResult<Option<String>> {
+    transport.send_raw(ProtocolHeader::SASL.as_bytes()).await?;
+    send_body(transport, acceptor.mechanisms().encode()?).await?;
+
+    let init = SaslInit::decode(&receive_body(transport).await?)?;
+    let checked = match acceptor.reserve(remote) {
+        Err(code) => Err(code),
+        Ok(()) if init.mechanism.as_str() == CRAM_SHA_256 => {
+            let checked = acceptor.challenge(transport, &init).await?;
+            acceptor.settle(remote, checked)
+        }
+        Ok(()) => acceptor.settle(remote, acceptor.check(&init)),
+    };
+    let code = match &checked {
+        Ok(_) => SaslCode::Ok,
+        Err(code) => *code,
+    };
+    send_body(transport, SaslOutcome { code, additional_data: None }.encode()?).await?;
+
+    match checked {
+        Ok(user) => {
+            logging::debug!("SASL {} authentication of {:?} accepted", init.mechanism.as_str(), user);
+            Ok(user)
+        }
+        Err(code) => {
+            logging::info!("SASL {} authentication from {} refused", init.mechanism.as_str(), remote);
+            Err(AmqpError::authentication_rejected(init.mechanism.as_str(), code))
+        }
+    }
+}
+
+async fn send_body(transport: &mut Transport, body: Vec<u8>) -> AmqpResult<()> {
+    let header = FrameHeader::new(body.len() as u32, FrameType::SASL as u8, 0);
+    transport.send_frame(Frame::new(header, body)).await
+}
+
+async fn receive_body(transport: &mut Transport) -> AmqpResult<Vec<u8>> {
+    let frame = transport.receive_frame().await?;
+    if frame.header.frame_type != FrameType::SASL as u8 {
+        return Err(AmqpError::protocol(format!(
+            "Expected SASL frame, got frame type {}",
+            frame.header.frame_type
+        )));
+    }
+    Ok(frame.payload)
+}
+
+fn encode_body(descriptor: u64, fields: &[AmqpValue]) -> AmqpResult<Vec<u8>> {
+    let mut encoder = Encoder::new();
+    encoder.encode_described_list(descriptor, fields)?;
+    Ok(encoder.finish())
+}
+
+fn decode_body(data: &[u8], expected: u64, name: &str) -> AmqpResult<Vec<AmqpValue>> {
+    let mut decoder = Decoder::new(data.to_vec());
+    let (descriptor, count) = decoder.decode_described_header()?;
+    if descriptor != expected {
+        return Err(AmqpError::decoding(format!(
+            "Expected {} (0x{:02x}), got 0x{:02x}",
+            name, expected, descriptor
+        )));
+    }
+    (0..count).map(|_| decoder.decode_value()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mechanisms_roundtrip() {
+        let single = SaslMechanisms { mechanisms: vec![AmqpSymbol::from("ANONYMOUS")] };
+        assert_eq!(SaslMechanisms::decode(&single.encode().unwrap()).unwrap(), single);
+
+        let several = SaslMechanisms {
+            mechanisms: vec![AmqpSymbol::from("PLAIN"), AmqpSymbol::from("ANONYMOUS")],
+        };
+        let decoded = SaslMechanisms::decode(&several.encode().unwrap()).unwrap();
+        assert_eq!(decoded, several);
+        assert!(decoded.offers("PLAIN"));
+        assert!(!decoded.offers("EXTERNAL"));
+    }
+
+    #[test]
+    fn test_init_and_outcome_roundtrip() {
+        let credentials = SaslCredentials::plain("guest", "secret");
+        let init = SaslInit {
+            mechanism: AmqpSymbol::from(credentials.mechanism()),
+            initial_response: credentials.initial_response().unwrap(),
+            hostname: Some("broker".to_string()),
+        };
+        let decoded = SaslInit::decode(&init.encode().unwrap()).unwrap();
+        assert_eq!(decoded.initial_response.as_deref(), Some(&b"\0guest\0secret"[..]));
+        assert_eq!(decoded, init);
+
+        let outcome = SaslOutcome { code: SaslCode::Auth, additional_data: None };
+        assert_eq!(SaslOutcome::decode(&outcome.encode().unwrap()).unwrap(), outcome);
+        assert!(SaslOutcome::decode(&init.encode().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_external_sends_authzid() {
+        let credentials = SaslCredentials::external();
+        assert_eq!(credentials.mechanism(), "EXTERNAL");
+        assert_eq!(credentials.initial_response().unwrap(), Some(Vec::new()));
+
+        let credentials = SaslCredentials::External { authzid: Some("orders-service".to_string()) };
+        assert_eq!(credentials.initial_response().unwrap().as_deref(), Some(&b"orders-service"[..]));
+
+        let error = AmqpError::authentication_rejected(credentials.mechanism(), SaslCode::Auth);
+        assert_eq!(error.remediation(), Some("check that the peer trusts the client certificate and maps it to a user"));
+    }
+
+    #[test]
+    fn test_acceptor_checks_plain_and_anonymous() {
+        let acceptor = SaslAcceptor::new().anonymous().plain(|authcid: &str, password: &str| authcid == "guest" && password == "secret");
+        assert_eq!(acceptor.mechanisms().mechanisms, vec![AmqpSymbol::from("PLAIN"), AmqpSymbol::from("ANONYMOUS")]);
+
+        let init = |credentials: SaslCredentials| SaslInit {
+            mechanism: AmqpSymbol::from(credentials.mechanism()),
+            initial_response: credentials.initial_response().unwrap(),
+            hostname: None,
+        };
+        assert_eq!(acceptor.check(&init(SaslCredentials::plain("guest", "secret"))), Ok(Some("guest".to_string())));
+        assert_eq!(acceptor.check(&init(SaslCredentials::plain("guest", "wrong"))), Err(SaslCode::Auth));
+        assert_eq!(acceptor.check(&init(SaslCredentials::Anonymous)), Ok(None));
+        let other_user = SaslCredentials::Plain {
+            authzid: Some("admin".to_string()),
+            authcid: "guest".to_string(),
+            password: "secret".to_string(),
+        };
+        assert_eq!(acceptor.check(&init(other_user)), Err(SaslCode::Auth));
+        assert_eq!(SaslAcceptor::new().check(&init(SaslCredentials::Anonymous)), Err(SaslCode::Auth));
+    }
+
+    #[test]
+    fn test_plain_users_compare_in_constant_time() {
+        let users = PlainUsers::new().user("alice", "secret").user("bob", "hunter2");
+        assert!(users.verify("alice", "secret"));
+        assert!(!users.verify("alice", "hunter2"));
+        assert!(!users.verify("mallory", "secret"));
+        assert_eq!(format!("{:?}", users), "PlainUsers { users: [\"alice\", \"bob\"] }");
+    }
+
+    #[test]
+    fn test_lockout_after_repeated_failures() {
+        let policy = LockoutPolicy { max_failures: 2, window: Duration::from_millis(100) };
+        let acceptor = SaslAcceptor::new().plain(PlainUsers::new().user("guest", "secret")).lockout(Some(policy));
+        let init = |password: &str| SaslInit {
+            mechanism: AmqpSymbol::from("PLAIN"),
+            initial_response: SaslCredentials::plain("guest", password).initial_response().unwrap(),
+            hostname: None,
+        };
+        let attacker: IpAddr = "10.0.0.1".parse().unwrap();
+        let neighbour: IpAddr = "10.0.0.2".parse().unwrap();
+
+        assert_eq!(acceptor.check_from(attacker, &init("guess-1")), Err(SaslCode::Auth));
+        assert_eq!(acceptor.clone().check_from(attacker, &init("guess-2")), Err(SaslCode::Auth));
+        assert_eq!(acceptor.check_from(attacker, &init("secret")), Err(SaslCode::SysTemp));
+        assert_eq!(acceptor.check_from(neighbour, &init("secret")), Ok(Some("guest".to_string())));
+
+        std::thread::sleep(Duration::from_millis(150));
+        assert_eq!(acceptor.check_from(attacker, &init("secret")), Ok(Some("guest".to_string())));
+        assert_eq!(acceptor.check_from(attacker, &init("guess-3")), Err(SaslCode::Auth));
+        assert_eq!(acceptor.lockout(None).check_from(attacker, &init("guess-4")), Err(SaslCode::Auth));
+    }
+
+    #[test]
+    fn test_lockout_counts_attempts_in_flight() {
+        let policy = LockoutPolicy { max_failures: 2, window: Duration::from_secs(60) };
+        let acceptor = SaslAcceptor::new().plain(PlainUsers::new().user("guest", "secret")).lockout(Some(policy));
+        let attacker: IpAddr = "10.0.0.1".parse().unwrap();
+
+        // Attempts started in parallel count before any of them fails
+        assert_eq!(acceptor.reserve(attacker), Ok(()));
+        assert_eq!(acceptor.clone().reserve(attacker), Ok(()));
+        assert_eq!(acceptor.reserve(attacker), Err(SaslCode::SysTemp));
+        let init = SaslInit {
+            mechanism: AmqpSymbol::from("PLAIN"),
+            initial_response: SaslCredentials::plain("guest", "secret").initial_response().unwrap(),
+            hostname: None,
+        };
+        assert_eq!(acceptor.check_from(attacker, &init), Err(SaslCode::SysTemp));
+    }
+
+    #[tokio::test]
+    async fn test_cram_challenge_refuses_replayed_response() {
+        let acceptor = SaslAcceptor::new().cram_sha256(PlainUsers::new().user("guest", "secret"));
+        assert_eq!(acceptor.mechanisms().mechanisms, vec![AmqpSymbol::from("CRAM-SHA-256")]);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = tokio::spawn(async move {
+            let mut results = Vec::new();
+            for _ in 0..3 {
+                let (stream, remote) = listener.accept().await.unwrap();
+                let mut transport = Transport::new(stream);
+                transport.receive_raw(8).await.unwrap();
+                results.push(accept(&mut transport, &acceptor, remote.ip()).await.ok());
+            }
+            results
+        });
+
+        let credentials = SaslCredentials::cram_sha256("guest", "secret");
+        let mut client = Transport::new(tokio::net::TcpStream::connect(("127.0.0.1", port)).await.unwrap());
+        authenticate(&mut client, &credentials, "broker").await.unwrap();
+
+        let wrong = SaslCredentials::cram_sha256("guest", "wrong");
+        let mut client = Transport::new(tokio::net::TcpStream::connect(("127.0.0.1", port)).await.unwrap());
+        let error = authenticate(&mut client, &wrong, "broker").await.unwrap_err();
+        assert!(matches!(error, AmqpError::AuthenticationRejected { code: SaslCode::Auth, .. }));
+
+        // An answer to an earlier challenge does not answer a fresh one
+        let replayed = credentials.challenge_response(b"<0123456789abcdef.0@broker>").unwrap();
+        let mut client = Transport::new(tokio::net::TcpStream::connect(("127.0.0.1", port)).await.unwrap());
+        ProtocolNegotiator::exchange_header(&mut client, ProtocolHeader::SASL).await.unwrap();
+        SaslMechanisms::decode(&receive_body(&mut client).await.unwrap()).unwrap();
+        let init = SaslInit { mechanism: AmqpSymbol::from("CRAM-SHA-256"), initial_response: None, hostname: None };
+        send_body(&mut client, init.encode().unwrap()).await.unwrap();
+        let challenge = SaslChallenge::decode(&receive_body(&mut client).await.unwrap()).unwrap();
+        assert!(challenge.challenge.ends_with(b"@dumq-amqp>"));
+        send_body(&mut client, SaslResponse { response: replayed }.encode().unwrap()).await.unwrap();
+        let outcome = SaslOutcome::decode(&receive_body(&mut client).await.unwrap()).unwrap();
+        assert_eq!(outcome.code, SaslCode::Auth);
+
+        assert_eq!(server.await.unwrap(), vec![Some(Some("guest".to_string())), None, None]);
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_against_peer() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let peer = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut server = Transport::new(stream);
+            assert_eq!(server.receive_raw(8).await.unwrap(), ProtocolHeader::SASL.as_bytes());
+            server.send_raw(ProtocolHeader::SASL.as_bytes()).await.unwrap();
+
+            let mechanisms = SaslMechanisms { mechanisms: vec![AmqpSymbol::from("PLAIN")] }.encode().unwrap();
+            let header = FrameHeader::new(mechanisms.len() as u32, FrameType::SASL as u8, 0);
+            server.send_frame(Frame::new(header, mechanisms)).await.unwrap();
+
+            let init = SaslInit::decode(&server.receive_frame().await.unwrap().payload).unwrap();
+            let code = if init.initial_response.as_deref() == Some(&b"\0guest\0guest"[..]) {
+                SaslCode::Ok
+            } else {
+                SaslCode::Auth
+            };
+            let outcome = SaslOutcome { code, additional_data: None }.encode().unwrap();
+            let header = FrameHeader::new(outcome.len() as u32, FrameType::SASL as u8, 0);
+            server.send_frame(Frame::new(header, outcome)).await.unwrap();
+            init.hostname
+        });
+
+        let mut client = Transport::new(tokio::net::TcpStream::connect(("127.0.0.1", port)).await.unwrap());
+        authenticate(&mut client, &SaslCredentials::plain("guest", "guest"), "broker").await.unwrap();
+        assert_eq!(peer.await.unwrap().as_deref(), Some("broker"));
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn test_external_authenticates_with_client_certificate() {
+        use crate::network::NetworkBuilder;
+        use crate::performative::Open;
+        use crate::tls::{ClientAuth, TlsAcceptorBuilder, TlsConnectorBuilder};
+
+        const CA: &str = include_str!("../testdata/tls/ca.pem");
+        let acceptor = TlsAcceptorBuilder::new(include_str!("../testdata/tls/server.pem"), include_str!("../testdata/tls/server.key"))
+            .client_auth(ClientAuth::Required(CA.as_bytes().to_vec()))
+            .build()
+            .unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let peer = tokio::spawn(async move {
+            // The first client presents no certificate and fails the TLS handshake
+            let (stream, _) = listener.accept().await.unwrap();
+            assert!(acceptor.accept(stream).await.is_err());
+
+            let (stream, _) = listener.accept().await.unwrap();
+            let (stream, incoming) = acceptor.accept(stream).await.unwrap();
+            let mut server = Transport::new(stream);
+            assert_eq!(server.receive_raw(8).await.unwrap(), ProtocolHeader::SASL.as_bytes());
+            server.send_raw(ProtocolHeader::SASL.as_bytes()).await.unwrap();
+
+            let mechanisms = SaslMechanisms { mechanisms: vec![AmqpSymbol::from("EXTERNAL")] }.encode().unwrap();
+            let header = FrameHeader::new(mechanisms.len() as u32, FrameType::SASL as u8, 0);
+            server.send_frame(Frame::new(header, mechanisms)).await.unwrap();
+
+            let init = SaslInit::decode(&server.receive_frame().await.unwrap().payload).unwrap();
+            let authenticated = incoming.tls.as_ref().is_some_and(|tls| tls.is_client_authenticated());
+            let code = if init.mechanism.as_str() == "EXTERNAL" && authenticated { SaslCode::Ok } else { SaslCode::Auth };
+            let outcome = SaslOutcome { code, additional_data: None }.encode().unwrap();
+            let header = FrameHeader::new(outcome.len() as u32, FrameType::SASL as u8, 0);
+            server.send_frame(Frame::new(header, outcome)).await.unwrap();
+
+            assert_eq!(server.receive_raw(8).await.unwrap(), ProtocolHeader::AMQP.as_bytes());
+            server.send_raw(ProtocolHeader::AMQP.as_bytes()).await.unwrap();
+            server.receive_frame().await.unwrap();
+            let open = Open { container_id: "tls-broker".to_string(), ..Default::default() }.encode().unwrap();
+            let header = FrameHeader::new(open.len() as u32, FrameType::AMQP as u8, 0);
+            server.send_frame(Frame::new(header, open)).await.unwrap();
+            server
+        });
+
+        let client = |connector| {
+            NetworkBuilder::new()
+                .hostname("localhost")
+                .port(port)
+                .tls(connector)
+                .sasl(SaslCredentials::external())
+                .keep_alive_disabled()
+                .build()
+        };
+        let mut anonymous = client(TlsConnectorBuilder::new(CA).build().unwrap());
+        let error = match anonymous.connect().await {
+            Ok(()) => anonymous.negotiate_protocol().await.unwrap_err(),
+            Err(e) => e,
+        };
+        assert!(matches!(error, AmqpError::Tls { .. } | AmqpError::Transport(_)), "{:?}", error);
+
+        let connector = TlsConnectorBuilder::new(CA)
+            .client_certificate(include_str!("../testdata/tls/client.pem"), include_str!("../testdata/tls/client.key"))
+            .build()
+            .unwrap();
+        let mut connection = client(connector);
+        connection.connect().await.unwrap();
+        connection.negotiate_protocol().await.unwrap();
+        assert_eq!(connection.remote_container_id(), Some("tls-broker"));
+        drop(peer.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_failures_are_typed() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let peer = tokio::spawn(async move {
+            for code in [None, Some(SaslCode::Auth)] {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut server = Transport::new(stream);
+                server.receive_raw(8).await.unwrap();
+                server.send_raw(ProtocolHeader::SASL.as_bytes()).await.unwrap();
+
+                let offered = if code.is_some() { "PLAIN" } else { "EXTERNAL" };
+                let mechanisms = SaslMechanisms { mechanisms: vec![AmqpSymbol::from(offered)] }.encode().unwrap();
+                let header = FrameHeader::new(mechanisms.len() as u32, FrameType::SASL as u8, 0);
+                server.send_frame(Frame::new(header, mechanisms)).await.unwrap();
+                if let Some(code) = code {
+                    server.receive_frame().await.unwrap();
+                    let outcome = SaslOutcome { code, additional_data: None }.encode().unwrap();
+                    let header = FrameHeader::new(outcome.len() as u32, FrameType::SASL as u8, 0);
+                    server.send_frame(Frame::new(header, outcome)).await.unwrap();
+                }
+            }
+        });
+
+        let credentials = SaslCredentials::plain("guest", "wrong");
+        let mut client = Transport::new(tokio::net::TcpStream::connect(("127.0.0.1", port)).await.unwrap());
+        let error = authenticate(&mut client, &credentials, "broker").await.unwrap_err();
+        assert!(matches!(&error, AmqpError::SaslMechanismMismatch { offered, .. } if offered == &["EXTERNAL"]));
+
+        let mut client = Transport::new(tokio::net::TcpStream::connect(("127.0.0.1", port)).await.unwrap());
+        let error = authenticate(&mut client, &credentials, "broker").await.unwrap_err();
+        assert!(matches!(error, AmqpError::AuthenticationRejected { code: SaslCode::Auth, .. }));
+        assert_eq!(error.remediation(), Some("check the user name and password"));
+        peer.await.unwrap();
+    }
+}
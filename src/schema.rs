@@ -0,0 +1,487 @@
+//! AMQP 1.0 Value Schemas
+//!
+//! This module describes the shape an [`AmqpValue`] is expected to have and
+//! checks values against it, which helps with values whose layout is only
+//! known by convention: management responses, application properties and
+//! application-defined described types.
+//!
+//! A [`ValueSchema`] covers scalar types, integer and floating point ranges,
+//! lists of a given arity, maps with required and optional symbol keys, and
+//! described values. [`ValueSchema::validate`] reports every violation with
+//! the path to the offending value, such as `$.entities[2].name`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::schema::{MapSchema, ValueSchema};
+//! use dumq_amqp::types::{AmqpMap, AmqpSymbol, AmqpValue};
+//!
+//! let schema = ValueSchema::Map(
+//!     MapSchema::new()
+//!         .required("statusCode", ValueSchema::int_range(100, 599))
+//!         .optional("statusDescription", ValueSchema::String),
+//! );
+//!
+//! let mut response = AmqpMap::new();
+//! response.insert(AmqpSymbol::from("statusCode"), AmqpValue::Int(200));
+//! assert!(schema.validate(&AmqpValue::Map(response.clone())).is_ok());
+//!
+//! response.insert(AmqpSymbol::from("statusCode"), AmqpValue::String("OK".to_string()));
+//! let error = schema.validate(&AmqpValue::Map(response)).unwrap_err();
+//! assert_eq!(error.violations[0].path, "$.statusCode");
+//! ```
+
+use crate::types::Descriptor;
+use crate::{AmqpError, AmqpSymbol, AmqpValue};
+use std::fmt;
+
+/// Expected shape of an AMQP value
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueSchema {
+    /// Any value, including null
+    Any,
+    /// Null
+    Null,
+    /// Boolean
+    Boolean,
+    /// Integer of any width or signedness, within the bounds given
+    Integer {
+        /// Smallest value allowed
+        min: Option<i128>,
+        /// Largest value allowed
+        max: Option<i128>,
+    },
+    /// Float or double, within the bounds given
+    Float {
+        /// Smallest value allowed
+        min: Option<f64>,
+        /// Largest value allowed
+        max: Option<f64>,
+    },
+    /// String
+    String,
+    /// Symbol
+    Symbol,
+    /// Binary
+    Binary,
+    /// Timestamp
+    Timestamp,
+    /// UUID
+    Uuid,
+    /// List or array whose items all match a schema
+    List {
+        /// Schema of every item
+        items: Box<ValueSchema>,
+        /// Fewest items allowed
+        min_len: usize,
+        /// Most items allowed
+        max_len: Option<usize>,
+    },
+    /// List with one item per schema, in order
+    Tuple(Vec<ValueSchema>),
+    /// Map with the given keys
+    Map(MapSchema),
+    /// Value described by the given descriptor
+    Described {
+        /// Expected descriptor
+        descriptor: Descriptor,
+        /// Schema of the described value
+        value: Box<ValueSchema>,
+    },
+    /// Null or a value matching the schema
+    Optional(Box<ValueSchema>),
+    /// Value matching at least one of the schemas
+    OneOf(Vec<ValueSchema>),
+}
+
+impl ValueSchema {
+    /// Any integer
+    pub fn integer() -> Self {
+        ValueSchema::Integer { min: None, max: None }
+    }
+
+    /// Integer between `min` and `max` inclusive
+    pub fn int_range(min: i128, max: i128) -> Self {
+        ValueSchema::Integer { min: Some(min), max: Some(max) }
+    }
+
+    /// Float or double between `min` and `max` inclusive
+    pub fn float_range(min: f64, max: f64) -> Self {
+        ValueSchema::Float { min: Some(min), max: Some(max) }
+    }
+
+    /// List of any length whose items match `items`
+    pub fn list_of(items: ValueSchema) -> Self {
+        ValueSchema::List { items: Box::new(items), min_len: 0, max_len: None }
+    }
+
+    /// List of `min_len` to `max_len` items matching `items`
+    pub fn list_len(items: ValueSchema, min_len: usize, max_len: usize) -> Self {
+        ValueSchema::List { items: Box::new(items), min_len, max_len: Some(max_len) }
+    }
+
+    /// Value described by `descriptor`
+    pub fn described(descriptor: impl Into<Descriptor>, value: ValueSchema) -> Self {
+        ValueSchema::Described { descriptor: descriptor.into(), value: Box::new(value) }
+    }
+
+    /// Null or a value matching `schema`
+    pub fn optional(schema: ValueSchema) -> Self {
+        ValueSchema::Optional(Box::new(schema))
+    }
+
+    /// Check a value, reporting every violation
+    pub fn validate(&self, value: &AmqpValue) -> Result<(), SchemaError> {
+        let mut violations = Vec::new();
+        self.check(value, "$", &mut violations);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(SchemaError { violations })
+        }
+    }
+
+    /// Check if a value matches
+    pub fn matches(&self, value: &AmqpValue) -> bool {
+        self.validate(value).is_ok()
+    }
+
+    fn check(&self, value: &AmqpValue, path: &str, violations: &mut Vec<Violation>) {
+        let mut violation = |message: String| violations.push(Violation { path: path.to_string(), message });
+        match (self, value) {
+            (ValueSchema::Any, _)
+            | (ValueSchema::Null, AmqpValue::Null)
+            | (ValueSchema::Boolean, AmqpValue::Boolean(_))
+            | (ValueSchema::String, AmqpValue::String(_))
+            | (ValueSchema::Symbol, AmqpValue::Symbol(_))
+            | (ValueSchema::Binary, AmqpValue::Binary(_))
+            | (ValueSchema::Timestamp, AmqpValue::Timestamp(_))
+            | (ValueSchema::Uuid, AmqpValue::Uuid(_))
+            | (ValueSchema::Optional(_), AmqpValue::Null) => {}
+            (ValueSchema::Integer { min, max }, _) if integer(value).is_some() => {
+                let number = integer(value).unwrap_or_default();
+                if min.is_some_and(|min| number < min) || max.is_some_and(|max| number > max) {
+                    violation(format!("{} is out of range {}", number, range(min, max)));
+                }
+            }
+            (ValueSchema::Float { min, max }, AmqpValue::Float(_) | AmqpValue::Double(_)) => {
+                let number = match value {
+                    AmqpValue::Float(number) => *number as f64,
+                    AmqpValue::Double(number) => *number,
+                    _ => unreachable!(),
+                };
+                if min.is_some_and(|min| number < min) || max.is_some_and(|max| number > max) {
+                    violation(format!("{} is out of range {}", number, range(min, max)));
+                }
+            }
+            (ValueSchema::List { items, min_len, max_len }, AmqpValue::List(list) | AmqpValue::Array(list)) => {
+                if list.len() < *min_len || max_len.is_some_and(|max_len| list.len() > max_len) {
+                    let max = max_len.map_or(String::new(), |max_len| max_len.to_string());
+                    violation(format!("expected {}..{} items, got {}", min_len, max, list.len()));
+                }
+                for (index, item) in list.iter().enumerate() {
+                    items.check(item, &format!("{}[{}]", path, index), violations);
+                }
+            }
+            (ValueSchema::Tuple(schemas), AmqpValue::List(list)) => {
+                if list.len() != schemas.len() {
+                    violation(format!("expected {} items, got {}", schemas.len(), list.len()));
+                }
+                for (index, (schema, item)) in schemas.iter().zip(list.iter()).enumerate() {
+                    schema.check(item, &format!("{}[{}]", path, index), violations);
+                }
+            }
+            (ValueSchema::Map(schema), AmqpValue::Map(map)) => {
+                for field in &schema.fields {
+                    let field_path = format!("{}.{}", path, field.key);
+                    match map.get(&field.key) {
+                        Some(item) => field.schema.check(item, &field_path, violations),
+                        None if field.required => violations.push(Violation {
+                            path: field_path,
+                            message: "required key is missing".to_string(),
+                        }),
+                        None => {}
+                    }
+                }
+                if !schema.allow_unknown {
+                    let mut unknown: Vec<&AmqpSymbol> =
+                        map.keys().filter(|key| !schema.fields.iter().any(|field| &field.key == *key)).collect();
+                    unknown.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+                    for key in unknown {
+                        violations.push(Violation {
+                            path: format!("{}.{}", path, key),
+                            message: "key is not allowed".to_string(),
+                        });
+                    }
+                }
+            }
+            (ValueSchema::Described { descriptor, value: schema }, AmqpValue::Described(actual, described)) => {
+                if **actual != *descriptor {
+                    violation(format!("expected descriptor {}, got {}", descriptor, actual));
+                } else {
+                    schema.check(described, path, violations);
+                }
+            }
+            (ValueSchema::Optional(schema), _) => schema.check(value, path, violations),
+            (ValueSchema::OneOf(schemas), _) => {
+                if !schemas.iter().any(|schema| schema.matches(value)) {
+                    violation(format!("expected {}, got {}", self, type_name(value)));
+                }
+            }
+            _ => violation(format!("expected {}, got {}", self, type_name(value))),
+        }
+    }
+}
+
+impl fmt::Display for ValueSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueSchema::Any => f.write_str("any value"),
+            ValueSchema::Null => f.write_str("null"),
+            ValueSchema::Boolean => f.write_str("boolean"),
+            ValueSchema::Integer { min: None, max: None } => f.write_str("integer"),
+            ValueSchema::Integer { min, max } => write!(f, "integer in {}", range(min, max)),
+            ValueSchema::Float { min: None, max: None } => f.write_str("float"),
+            ValueSchema::Float { min, max } => write!(f, "float in {}", range(min, max)),
+            ValueSchema::String => f.write_str("string"),
+            ValueSchema::Symbol => f.write_str("symbol"),
+            ValueSchema::Binary => f.write_str("binary"),
+            ValueSchema::Timestamp => f.write_str("timestamp"),
+            ValueSchema::Uuid => f.write_str("uuid"),
+            ValueSchema::List { items, .. } => write!(f, "list of {}", items),
+            ValueSchema::Tuple(schemas) => write!(f, "list of {} items", schemas.len()),
+            ValueSchema::Map(_) => f.write_str("map"),
+            ValueSchema::Described { descriptor, .. } => write!(f, "value described by {}", descriptor),
+            ValueSchema::Optional(schema) => write!(f, "null or {}", schema),
+            ValueSchema::OneOf(schemas) => {
+                let names: Vec<String> = schemas.iter().map(|schema| schema.to_string()).collect();
+                write!(f, "one of {}", names.join(", "))
+            }
+        }
+    }
+}
+
+/// Expected keys of a map
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapSchema {
+    /// Keys checked, in the order they are reported
+    pub fields: Vec<FieldSchema>,
+    /// Whether keys not listed are allowed
+    pub allow_unknown: bool,
+}
+
+impl MapSchema {
+    /// Create a map schema with no keys, allowing unknown ones
+    pub fn new() -> Self {
+        MapSchema { fields: Vec::new(), allow_unknown: true }
+    }
+
+    /// Require a key whose value matches `schema`
+    pub fn required(mut self, key: impl Into<AmqpSymbol>, schema: ValueSchema) -> Self {
+        self.fields.push(FieldSchema { key: key.into(), schema, required: true });
+        self
+    }
+
+    /// Allow a key whose value, if present, matches `schema`
+    pub fn optional(mut self, key: impl Into<AmqpSymbol>, schema: ValueSchema) -> Self {
+        self.fields.push(FieldSchema { key: key.into(), schema, required: false });
+        self
+    }
+
+    /// Reject keys that are not listed
+    pub fn deny_unknown(mut self) -> Self {
+        self.allow_unknown = false;
+        self
+    }
+}
+
+impl Default for MapSchema {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Expected key of a map
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldSchema {
+    /// Key
+    pub key: AmqpSymbol,
+    /// Schema of the key's value
+    pub schema: ValueSchema,
+    /// Whether the key must be present
+    pub required: bool,
+}
+
+/// Value that does not match its schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// Path to the value, `$` being the value validated
+    pub path: String,
+    /// What is wrong with it
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Every violation found validating a value
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaError {
+    /// Violations in the order found
+    pub violations: Vec<Violation>,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let violations: Vec<String> = self.violations.iter().map(|violation| violation.to_string()).collect();
+        write!(f, "Value does not match schema: {}", violations.join("; "))
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl From<SchemaError> for AmqpError {
+    fn from(error: SchemaError) -> Self {
+        AmqpError::decoding(error.to_string())
+    }
+}
+
+/// Get an integer value of any width
+fn integer(value: &AmqpValue) -> Option<i128> {
+    Some(match value {
+        AmqpValue::Ubyte(n) => *n as i128,
+        AmqpValue::Ushort(n) => *n as i128,
+        AmqpValue::Uint(n) => *n as i128,
+        AmqpValue::Ulong(n) => *n as i128,
+        AmqpValue::Byte(n) => *n as i128,
+        AmqpValue::Short(n) => *n as i128,
+        AmqpValue::Int(n) => *n as i128,
+        AmqpValue::Long(n) => *n as i128,
+        _ => return None,
+    })
+}
+
+fn range<T: fmt::Display>(min: &Option<T>, max: &Option<T>) -> String {
+    let bound = |bound: &Option<T>| bound.as_ref().map_or(String::new(), |bound| bound.to_string());
+    format!("{}..={}", bound(min), bound(max))
+}
+
+/// Get the AMQP type name of a value
+fn type_name(value: &AmqpValue) -> &'static str {
+    match value {
+        AmqpValue::Null => "null",
+        AmqpValue::Boolean(_) => "boolean",
+        AmqpValue::Ubyte(_) => "ubyte",
+        AmqpValue::Ushort(_) => "ushort",
+        AmqpValue::Uint(_) => "uint",
+        AmqpValue::Ulong(_) => "ulong",
+        AmqpValue::Byte(_) => "byte",
+        AmqpValue::Short(_) => "short",
+        AmqpValue::Int(_) => "int",
+        AmqpValue::Long(_) => "long",
+        AmqpValue::Float(_) => "float",
+        AmqpValue::Double(_) => "double",
+        AmqpValue::Decimal32(_) => "decimal32",
+        AmqpValue::Decimal64(_) => "decimal64",
+        AmqpValue::Decimal128(_) => "decimal128",
+        AmqpValue::Char(_) => "char",
+        AmqpValue::Timestamp(_) => "timestamp",
+        AmqpValue::Uuid(_) => "uuid",
+        AmqpValue::Binary(_) => "binary",
+        AmqpValue::String(_) => "string",
+        AmqpValue::Symbol(_) => "symbol",
+        AmqpValue::List(_) => "list",
+        AmqpValue::Map(_) => "map",
+        AmqpValue::Array(_) => "array",
+        AmqpValue::Described(_, _) => "described value",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AmqpList, AmqpMap};
+
+    fn map(entries: Vec<(&str, AmqpValue)>) -> AmqpValue {
+        let mut map = AmqpMap::new();
+        for (key, value) in entries {
+            map.insert(AmqpSymbol::from(key), value);
+        }
+        AmqpValue::Map(map)
+    }
+
+    fn list(items: Vec<AmqpValue>) -> AmqpValue {
+        AmqpValue::List(AmqpList::from(items))
+    }
+
+    #[test]
+    fn test_nested_violations_reported_with_paths() {
+        let entity = ValueSchema::Map(
+            MapSchema::new()
+                .required("name", ValueSchema::String)
+                .optional("partitions", ValueSchema::int_range(1, 32))
+                .deny_unknown(),
+        );
+        let schema = ValueSchema::Map(
+            MapSchema::new()
+                .required("statusCode", ValueSchema::int_range(100, 599))
+                .required("entities", ValueSchema::list_of(entity)),
+        );
+        let response = map(vec![
+            ("statusCode", AmqpValue::Uint(200)),
+            (
+                "entities",
+                list(vec![
+                    map(vec![("name", AmqpValue::String("orders".to_string()))]),
+                    map(vec![("name", AmqpValue::Symbol("audit".into())), ("partitions", AmqpValue::Long(64))]),
+                    map(vec![("name", AmqpValue::String("dlq".to_string())), ("color", AmqpValue::Null)]),
+                ]),
+            ),
+        ]);
+
+        let error = schema.validate(&response).unwrap_err();
+        let violations: Vec<String> = error.violations.iter().map(|violation| violation.to_string()).collect();
+        assert_eq!(
+            violations,
+            vec![
+                "$.entities[1].name: expected string, got symbol",
+                "$.entities[1].partitions: 64 is out of range 1..=32",
+                "$.entities[2].color: key is not allowed",
+            ]
+        );
+        assert!(matches!(AmqpError::from(error), AmqpError::Decoding(_)));
+
+        let error = schema.validate(&map(vec![("statusCode", AmqpValue::Int(700))])).unwrap_err();
+        assert_eq!(error.violations[0].to_string(), "$.statusCode: 700 is out of range 100..=599");
+        assert_eq!(error.violations[1].to_string(), "$.entities: required key is missing");
+    }
+
+    #[test]
+    fn test_list_arity_and_tuples() {
+        let pair = ValueSchema::Tuple(vec![ValueSchema::Symbol, ValueSchema::optional(ValueSchema::integer())]);
+        assert!(pair.matches(&list(vec![AmqpValue::Symbol("a".into()), AmqpValue::Null])));
+        let error = pair.validate(&list(vec![AmqpValue::Symbol("a".into())])).unwrap_err();
+        assert_eq!(error.violations[0].message, "expected 2 items, got 1");
+
+        let bounded = ValueSchema::list_len(ValueSchema::float_range(0.0, 1.0), 1, 2);
+        assert!(bounded.matches(&AmqpValue::Array(AmqpList::from(vec![AmqpValue::Double(0.5)]))));
+        let error = bounded.validate(&list(vec![AmqpValue::Float(0.5), AmqpValue::Int(1), AmqpValue::Double(2.0)])).unwrap_err();
+        let paths: Vec<&str> = error.violations.iter().map(|violation| violation.path.as_str()).collect();
+        assert_eq!(paths, vec!["$", "$[1]", "$[2]"]);
+    }
+
+    #[test]
+    fn test_described_values_checked_by_descriptor() {
+        let schema = ValueSchema::described("com.example:order", ValueSchema::list_of(ValueSchema::String));
+        let order = AmqpValue::described("com.example:order", list(vec![AmqpValue::String("sku-1".to_string())]));
+        assert!(schema.matches(&order));
+
+        let other = AmqpValue::described(0x70u64, list(Vec::new()));
+        let error = schema.validate(&other).unwrap_err();
+        assert_eq!(error.violations[0].message, "expected descriptor com.example:order, got 0x0000000000000070");
+        assert!(ValueSchema::OneOf(vec![ValueSchema::Any]).matches(&other));
+    }
+}
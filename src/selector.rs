@@ -0,0 +1,687 @@
+//! AMQP 1.0 Selector Filters
+//!
+//! This module evaluates selector expressions over message application
+//! properties, so that a server can honor selector filters sent by clients
+//! when dispatching messages to consumers.
+//!
+//! # Syntax
+//!
+//! - **Comparison**: `=`, `<>` (or `!=`), `>`, `>=`, `<`, `<=` on strings and numbers
+//! - **Pattern**: `name LIKE 'ord%'` with `%` (any run) and `_` (one character)
+//! - **Presence**: `name IS NULL`, `name IS NOT NULL`
+//! - **Logic**: `AND`, `OR`, `NOT` and parentheses
+//!
+//! A comparison involving a missing property is unknown, as in SQL; a message
+//! matches only if the whole expression is true.
+//!
+//! Clients send a selector as the [`SELECTOR_FILTER_NAME`] entry of a source
+//! filter set, built with [`Selector::filter_value`]. The
+//! [`EmbeddedBroker`](crate::testing::EmbeddedBroker) only dispatches the
+//! messages matching it to that receiver.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::selector::Selector;
+//! use dumq_amqp::message::Message;
+//! use dumq_amqp::types::{AmqpSymbol, AmqpValue};
+//! use std::collections::HashMap;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let selector = Selector::parse("color = 'red' AND weight > 10")?;
+//!
+//! let mut properties = HashMap::new();
+//! properties.insert(AmqpSymbol::from("color"), AmqpValue::String("red".to_string()));
+//! properties.insert(AmqpSymbol::from("weight"), AmqpValue::Int(12));
+//! let message = Message::builder().application_properties(properties).build();
+//!
+//! assert!(selector.matches(&message));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{AmqpCondition, AmqpError, AmqpMap, AmqpResult, AmqpSymbol, AmqpValue, Message};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Filter set key used for selector filters
+pub const SELECTOR_FILTER_NAME: &str = "apache.org:selector-filter:string";
+
+/// Descriptor code of the selector filter type
+pub const SELECTOR_FILTER_DESCRIPTOR: u64 = 0x0000_468C_0000_0004;
+
+/// Deepest nesting of `NOT` and parentheses a selector may use
+const MAX_NESTING: usize = 64;
+
+/// Comparison operator
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Eq,
+    Neq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// Operand of a comparison
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Property(String),
+    String(String),
+    Number(f64),
+    Boolean(bool),
+}
+
+/// Parsed selector expression
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Compare(Operand, Operator, Operand),
+    Like { property: String, pattern: String, negated: bool },
+    IsNull { property: String, negated: bool },
+    Not(Box<Expr>),
+    /// Operands of a chain of `AND`s, kept flat so long chains do not nest
+    And(Vec<Expr>),
+    /// Operands of a chain of `OR`s, kept flat like `And`
+    Or(Vec<Expr>),
+}
+
+/// Selector over application properties
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector {
+    /// Original expression text
+    source: String,
+    /// Parsed expression
+    expr: Expr,
+}
+
+impl Selector {
+    /// Parse a selector expression
+    pub fn parse(source: &str) -> AmqpResult<Self> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, position: 0, depth: 0 };
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(invalid(format!("Unexpected token {:?}", token)));
+        }
+
+        Ok(Selector {
+            source: source.to_string(),
+            expr,
+        })
+    }
+
+    /// Check if a message matches the selector
+    pub fn matches(&self, message: &Message) -> bool {
+        match &message.application_properties {
+            Some(properties) => self.matches_properties(properties),
+            None => self.matches_properties(&AmqpMap::new()),
+        }
+    }
+
+    /// Check if a set of application properties matches the selector
+    pub fn matches_properties(&self, properties: &AmqpMap) -> bool {
+        evaluate(&self.expr, properties) == Some(true)
+    }
+
+    /// Get the original expression text
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Get the described value sent in a selector filter
+    pub fn filter_value(&self) -> AmqpValue {
+        AmqpValue::described(SELECTOR_FILTER_DESCRIPTOR, AmqpValue::String(self.source.clone()))
+    }
+
+    /// Parse the selector of a source filter set, if it has one
+    ///
+    /// The selector may be sent described, as by [`Selector::filter_value`],
+    /// or as a plain string.
+    pub fn from_filter(filter: &HashMap<String, AmqpValue>) -> AmqpResult<Option<Self>> {
+        let value = match filter.get(SELECTOR_FILTER_NAME) {
+            Some(AmqpValue::Described(_, value)) => value.as_ref(),
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        match value {
+            AmqpValue::String(source) => Selector::parse(source).map(Some),
+            other => Err(invalid(format!("Expected a selector string, got {:?}", other))),
+        }
+    }
+}
+
+fn invalid(message: impl Into<String>) -> AmqpError {
+    AmqpError::amqp_protocol(AmqpCondition::AmqpErrorInvalidField, message)
+}
+
+/// Evaluate with three-valued logic; `None` means unknown
+fn evaluate(expr: &Expr, properties: &AmqpMap) -> Option<bool> {
+    match expr {
+        Expr::Compare(left, op, right) => {
+            let left = resolve(left, properties)?;
+            let right = resolve(right, properties)?;
+            compare(&left, *op, &right)
+        }
+        Expr::Like { property, pattern, negated } => {
+            let value = match properties.get(&AmqpSymbol::from(property.as_str()))? {
+                AmqpValue::String(value) => value.as_str(),
+                AmqpValue::Symbol(value) => value.0.as_str(),
+                _ => return None,
+            };
+            let chars: Vec<char> = value.chars().collect();
+            let pattern: Vec<char> = pattern.chars().collect();
+            Some(like(&chars, &pattern) != *negated)
+        }
+        Expr::IsNull { property, negated } => {
+            let missing = matches!(
+                properties.get(&AmqpSymbol::from(property.as_str())),
+                None | Some(AmqpValue::Null)
+            );
+            Some(missing != *negated)
+        }
+        Expr::Not(inner) => evaluate(inner, properties).map(|value| !value),
+        Expr::And(operands) => {
+            let mut result = Some(true);
+            for operand in operands {
+                match evaluate(operand, properties) {
+                    Some(false) => return Some(false),
+                    Some(true) => {}
+                    None => result = None,
+                }
+            }
+            result
+        }
+        Expr::Or(operands) => {
+            let mut result = Some(false);
+            for operand in operands {
+                match evaluate(operand, properties) {
+                    Some(true) => return Some(true),
+                    Some(false) => {}
+                    None => result = None,
+                }
+            }
+            result
+        }
+    }
+}
+
+fn resolve(operand: &Operand, properties: &AmqpMap) -> Option<Operand> {
+    let value = match operand {
+        Operand::Property(name) => properties.get(&AmqpSymbol::from(name.as_str()))?,
+        literal => return Some(literal.clone()),
+    };
+
+    match value {
+        AmqpValue::String(s) => Some(Operand::String(s.clone())),
+        AmqpValue::Symbol(s) => Some(Operand::String(s.0.clone())),
+        AmqpValue::Boolean(b) => Some(Operand::Boolean(*b)),
+        AmqpValue::Ubyte(n) => Some(Operand::Number(*n as f64)),
+        AmqpValue::Ushort(n) => Some(Operand::Number(*n as f64)),
+        AmqpValue::Uint(n) => Some(Operand::Number(*n as f64)),
+        AmqpValue::Ulong(n) => Some(Operand::Number(*n as f64)),
+        AmqpValue::Byte(n) => Some(Operand::Number(*n as f64)),
+        AmqpValue::Short(n) => Some(Operand::Number(*n as f64)),
+        AmqpValue::Int(n) => Some(Operand::Number(*n as f64)),
+        AmqpValue::Long(n) => Some(Operand::Number(*n as f64)),
+        AmqpValue::Float(n) => Some(Operand::Number(*n as f64)),
+        AmqpValue::Double(n) => Some(Operand::Number(*n)),
+        AmqpValue::Timestamp(n) => Some(Operand::Number(*n as f64)),
+        _ => None,
+    }
+}
+
+fn compare(left: &Operand, op: Operator, right: &Operand) -> Option<bool> {
+    let ordering = match (left, right) {
+        (Operand::String(a), Operand::String(b)) => a.cmp(b),
+        (Operand::Number(a), Operand::Number(b)) => a.partial_cmp(b)?,
+        (Operand::Boolean(a), Operand::Boolean(b)) => match op {
+            Operator::Eq => return Some(a == b),
+            Operator::Neq => return Some(a != b),
+            _ => return None,
+        },
+        // Values of different types never compare equal
+        _ => {
+            return match op {
+                Operator::Eq => Some(false),
+                Operator::Neq => Some(true),
+                _ => None,
+            }
+        }
+    };
+
+    Some(match op {
+        Operator::Eq => ordering == Ordering::Equal,
+        Operator::Neq => ordering != Ordering::Equal,
+        Operator::Gt => ordering == Ordering::Greater,
+        Operator::Ge => ordering != Ordering::Less,
+        Operator::Lt => ordering == Ordering::Less,
+        Operator::Le => ordering != Ordering::Greater,
+    })
+}
+
+/// Match a LIKE pattern where `%` matches any run and `_` one character
+///
+/// On a mismatch only the last `%` is retried, one character further, so
+/// matching takes at most value length times pattern length steps.
+fn like(value: &[char], pattern: &[char]) -> bool {
+    let (mut v, mut p) = (0, 0);
+    // Pattern position after the last `%`, and the value position it resumes at
+    let mut retry: Option<(usize, usize)> = None;
+    while v < value.len() {
+        match pattern.get(p) {
+            Some('%') => {
+                p += 1;
+                retry = Some((p, v));
+            }
+            Some(c) if *c == '_' || *c == value[v] => {
+                p += 1;
+                v += 1;
+            }
+            _ => match retry {
+                Some((after, resume)) => {
+                    p = after;
+                    v = resume + 1;
+                    retry = Some((after, v));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '%')
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    String(String),
+    Number(f64),
+    Op(Operator),
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Like,
+    Is,
+    Null,
+    True,
+    False,
+}
+
+fn tokenize(source: &str) -> AmqpResult<Vec<Token>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            '=' => {
+                tokens.push(Token::Op(Operator::Eq));
+                i += 1;
+            }
+            '!' if chars.get(i + 1) == Some(&'=') => {
+                tokens.push(Token::Op(Operator::Neq));
+                i += 2;
+            }
+            '<' | '>' => {
+                let next = chars.get(i + 1);
+                let (op, len) = match (c, next) {
+                    ('<', Some('>')) => (Operator::Neq, 2),
+                    ('<', Some('=')) => (Operator::Le, 2),
+                    ('>', Some('=')) => (Operator::Ge, 2),
+                    ('<', _) => (Operator::Lt, 1),
+                    _ => (Operator::Gt, 1),
+                };
+                tokens.push(Token::Op(op));
+                i += len;
+            }
+            '\'' => {
+                // Quotes inside a literal are escaped by doubling them
+                let mut literal = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        Some('\'') if chars.get(i + 1) == Some(&'\'') => {
+                            literal.push('\'');
+                            i += 2;
+                        }
+                        Some('\'') => {
+                            i += 1;
+                            break;
+                        }
+                        Some(c) => {
+                            literal.push(*c);
+                            i += 1;
+                        }
+                        None => return Err(invalid("Unterminated string literal in selector")),
+                    }
+                }
+                tokens.push(Token::String(literal));
+            }
+            c if c.is_ascii_digit() || (c == '-' && chars.get(i + 1).is_some_and(|n| n.is_ascii_digit())) => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let number = text
+                    .parse()
+                    .map_err(|_| invalid(format!("Invalid number '{}' in selector", text)))?;
+                tokens.push(Token::Number(number));
+            }
+            c if c.is_alphabetic() || c == '_' || c == '$' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '$' | '.' | '-')) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                tokens.push(match word.to_ascii_uppercase().as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    "LIKE" => Token::Like,
+                    "IS" => Token::Is,
+                    "NULL" => Token::Null,
+                    "TRUE" => Token::True,
+                    "FALSE" => Token::False,
+                    _ => Token::Ident(word),
+                });
+            }
+            other => return Err(invalid(format!("Unexpected character '{}' in selector", other))),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    /// Nesting of `NOT` and parentheses being parsed
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_or(&mut self) -> AmqpResult<Expr> {
+        let mut operands = vec![self.parse_and()?];
+        while self.eat(&Token::Or) {
+            operands.push(self.parse_and()?);
+        }
+        Ok(if operands.len() == 1 { operands.remove(0) } else { Expr::Or(operands) })
+    }
+
+    fn parse_and(&mut self) -> AmqpResult<Expr> {
+        let mut operands = vec![self.parse_not()?];
+        while self.eat(&Token::And) {
+            operands.push(self.parse_not()?);
+        }
+        Ok(if operands.len() == 1 { operands.remove(0) } else { Expr::And(operands) })
+    }
+
+    /// Parse a nested expression, bounding the recursion
+    fn nested(&mut self, parse: impl FnOnce(&mut Self) -> AmqpResult<Expr>) -> AmqpResult<Expr> {
+        if self.depth >= MAX_NESTING {
+            return Err(invalid(format!("Selector nests deeper than {} levels", MAX_NESTING)));
+        }
+        self.depth += 1;
+        let expr = parse(self);
+        self.depth -= 1;
+        expr
+    }
+
+    fn parse_not(&mut self) -> AmqpResult<Expr> {
+        if self.eat(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.nested(Self::parse_not)?)));
+        }
+        self.parse_predicate()
+    }
+
+    fn parse_predicate(&mut self) -> AmqpResult<Expr> {
+        if self.eat(&Token::LParen) {
+            let expr = self.nested(Self::parse_or)?;
+            if !self.eat(&Token::RParen) {
+                return Err(invalid("Missing closing parenthesis in selector"));
+            }
+            return Ok(expr);
+        }
+
+        let left = self.parse_operand()?;
+        match self.next() {
+            Some(Token::Op(op)) => {
+                let right = self.parse_operand()?;
+                Ok(Expr::Compare(left, op, right))
+            }
+            Some(Token::Not) if self.eat(&Token::Like) => self.parse_like(left, true),
+            Some(Token::Like) => self.parse_like(left, false),
+            Some(Token::Is) => {
+                let negated = self.eat(&Token::Not);
+                if !self.eat(&Token::Null) {
+                    return Err(invalid("Expected NULL after IS in selector"));
+                }
+                Ok(Expr::IsNull {
+                    property: property_name(left)?,
+                    negated,
+                })
+            }
+            other => Err(invalid(format!("Expected operator in selector, got {:?}", other))),
+        }
+    }
+
+    fn parse_like(&mut self, left: Operand, negated: bool) -> AmqpResult<Expr> {
+        match self.next() {
+            Some(Token::String(pattern)) => Ok(Expr::Like {
+                property: property_name(left)?,
+                pattern,
+                negated,
+            }),
+            other => Err(invalid(format!("Expected pattern after LIKE, got {:?}", other))),
+        }
+    }
+
+    fn parse_operand(&mut self) -> AmqpResult<Operand> {
+        match self.next() {
+            Some(Token::Ident(name)) => Ok(Operand::Property(name)),
+            Some(Token::String(value)) => Ok(Operand::String(value)),
+            Some(Token::Number(value)) => Ok(Operand::Number(value)),
+            Some(Token::True) => Ok(Operand::Boolean(true)),
+            Some(Token::False) => Ok(Operand::Boolean(false)),
+            other => Err(invalid(format!("Expected operand in selector, got {:?}", other))),
+        }
+    }
+}
+
+fn property_name(operand: Operand) -> AmqpResult<String> {
+    match operand {
+        Operand::Property(name) => Ok(name),
+        other => Err(invalid(format!("Expected property name, got {:?}", other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties() -> AmqpMap {
+        let mut properties = AmqpMap::new();
+        properties.insert(AmqpSymbol::from("color"), AmqpValue::String("red".to_string()));
+        properties.insert(AmqpSymbol::from("weight"), AmqpValue::Int(12));
+        properties.insert(AmqpSymbol::from("price"), AmqpValue::Double(9.5));
+        properties.insert(AmqpSymbol::from("urgent"), AmqpValue::Boolean(true));
+        properties.insert(AmqpSymbol::from("region"), AmqpValue::Symbol(AmqpSymbol::from("eu-west")));
+        properties
+    }
+
+    fn check(selector: &str) -> bool {
+        Selector::parse(selector).unwrap().matches_properties(&properties())
+    }
+
+    #[test]
+    fn test_string_comparison() {
+        assert!(check("color = 'red'"));
+        assert!(!check("color = 'blue'"));
+        assert!(check("color <> 'blue'"));
+        assert!(check("color != 'blue'"));
+        assert!(check("color > 'blue'"));
+    }
+
+    #[test]
+    fn test_numeric_comparison() {
+        assert!(check("weight > 10"));
+        assert!(check("weight >= 12"));
+        assert!(!check("weight < 12"));
+        assert!(check("weight <= 12.0"));
+        assert!(check("price < 10"));
+        assert!(check("price > -1"));
+    }
+
+    #[test]
+    fn test_boolean_comparison() {
+        assert!(check("urgent = TRUE"));
+        assert!(!check("urgent = false"));
+    }
+
+    #[test]
+    fn test_like() {
+        assert!(check("color LIKE 'r%'"));
+        assert!(check("color LIKE 'r_d'"));
+        assert!(!check("color LIKE 'b%'"));
+        assert!(check("color NOT LIKE 'b%'"));
+        assert!(check("region LIKE 'eu-%'"));
+    }
+
+    #[test]
+    fn test_like_takes_linear_steps() {
+        let mut properties = AmqpMap::new();
+        properties.insert(AmqpSymbol::from("p"), AmqpValue::String("a".repeat(40)));
+        let selector = Selector::parse(&format!("p LIKE '{}b'", "%a".repeat(20))).unwrap();
+        assert!(!selector.matches_properties(&properties));
+        let selector = Selector::parse(&format!("p LIKE '{}%'", "%a".repeat(20))).unwrap();
+        assert!(selector.matches_properties(&properties));
+        assert!(check("color LIKE '%e%'"));
+        assert!(check("color LIKE '%%d'"));
+        assert!(!check("color LIKE '%e'"));
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        assert!(check("color = 'red' AND weight > 10"));
+        assert!(!check("color = 'red' AND weight > 20"));
+        assert!(check("color = 'blue' OR weight > 10"));
+        assert!(check("NOT color = 'blue'"));
+        assert!(check("(color = 'blue' OR color = 'red') AND urgent = TRUE"));
+    }
+
+    #[test]
+    fn test_missing_property_is_unknown() {
+        assert!(!check("missing = 'x'"));
+        assert!(!check("NOT missing = 'x'"));
+        assert!(check("missing = 'x' OR color = 'red'"));
+        assert!(!check("missing = 'x' AND color = 'red'"));
+    }
+
+    #[test]
+    fn test_is_null() {
+        assert!(check("missing IS NULL"));
+        assert!(check("color IS NOT NULL"));
+        assert!(!check("color IS NULL"));
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        assert!(!check("color = 12"));
+        assert!(check("color <> 12"));
+        assert!(!check("color > 12"));
+    }
+
+    #[test]
+    fn test_escaped_quote() {
+        let mut properties = AmqpMap::new();
+        properties.insert(AmqpSymbol::from("name"), AmqpValue::String("O'Brien".to_string()));
+        let selector = Selector::parse("name = 'O''Brien'").unwrap();
+        assert!(selector.matches_properties(&properties));
+    }
+
+    #[test]
+    fn test_matches_message() {
+        let selector = Selector::parse("color = 'red'").unwrap();
+        let message = Message::builder().application_properties(properties()).build();
+        assert!(selector.matches(&message));
+        assert!(!selector.matches(&Message::text("no properties")));
+        assert_eq!(selector.as_str(), "color = 'red'");
+    }
+
+    #[test]
+    fn test_nesting_is_bounded() {
+        let deep = format!("{}color = 'red'", "NOT ".repeat(10_000));
+        assert!(Selector::parse(&deep).is_err());
+        let deep = format!("{}color = 'red'{}", "(".repeat(10_000), ")".repeat(10_000));
+        assert!(Selector::parse(&deep).is_err());
+        assert!(check(&format!("{}color = 'red'{}", "(".repeat(10), ")".repeat(10))));
+    }
+
+    #[test]
+    fn test_long_chains_do_not_nest() {
+        let chain = vec!["weight = 12"; 20_000].join(" AND ");
+        assert!(check(&chain));
+        assert!(!check(&format!("{} AND color = 'blue'", chain)));
+        let chain = vec!["color = 'blue'"; 20_000].join(" OR ");
+        assert!(!check(&chain));
+        assert!(check(&format!("{} OR urgent = TRUE", chain)));
+        assert!(!check(&format!("{} OR missing = 'x'", chain)));
+    }
+
+    #[test]
+    fn test_from_filter() {
+        let selector = Selector::parse("color = 'red'").unwrap();
+        let mut filter = HashMap::new();
+        assert_eq!(Selector::from_filter(&filter).unwrap(), None);
+        filter.insert(SELECTOR_FILTER_NAME.to_string(), selector.filter_value());
+        assert_eq!(Selector::from_filter(&filter).unwrap(), Some(selector));
+        filter.insert(SELECTOR_FILTER_NAME.to_string(), AmqpValue::String("color =".to_string()));
+        assert!(Selector::from_filter(&filter).is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for source in ["color =", "color = 'red", "(color = 'red'", "color ~ 'red'", "'red' LIKE 'r%'", "color = 'red' extra"] {
+            let result = Selector::parse(source);
+            assert!(
+                matches!(result, Err(AmqpError::AmqpProtocol { condition: AmqpCondition::AmqpErrorInvalidField, .. })),
+                "{}: {:?}",
+                source,
+                result
+            );
+        }
+    }
+}
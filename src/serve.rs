@@ -0,0 +1,215 @@
+//! AMQP 1.0 Consumption through Tower Services
+//!
+//! This module drives a [`tower_service::Service`] from a [`Receiver`], so
+//! the middleware written for Tower (retries, rate limits, timeouts, load
+//! shedding) applies to message handlers as it does to HTTP ones. Each
+//! delivery becomes one call of the service, with up to a given number of
+//! calls in flight; the [`Outcome`] the service responds with settles the
+//! delivery. A service error settles it as modified with `delivery-failed`,
+//! so the broker counts the attempt and redelivers the message.
+//!
+//! Requires the `tower` feature.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::link::LinkBuilder;
+//! use dumq_amqp::message::Message;
+//! use dumq_amqp::performative::Outcome;
+//! use dumq_amqp::serve::{Delivery, ReceiverExt};
+//! use std::convert::Infallible;
+//! use std::future::{ready, Ready};
+//! use std::task::{Context, Poll};
+//! use tower_service::Service;
+//!
+//! struct Handler;
+//!
+//! impl Service<Delivery> for Handler {
+//!     type Response = Outcome;
+//!     type Error = Infallible;
+//!     type Future = Ready<Result<Outcome, Infallible>>;
+//!
+//!     fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+//!         Poll::Ready(Ok(()))
+//!     }
+//!
+//!     fn call(&mut self, delivery: Delivery) -> Self::Future {
+//!         println!("{:?}", delivery.message.body_as_text());
+//!         ready(Ok(Outcome::Accepted))
+//!     }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut receiver = LinkBuilder::new().source("orders").build_receiver("session-1".to_string());
+//! receiver.attach().await?;
+//! receiver.simulate_receive(Message::text("order"));
+//!
+//! let settled = receiver.serve(Handler, 8).await?;
+//! assert_eq!(settled, 1);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::link::Receiver;
+use crate::logging;
+use crate::performative::Outcome;
+use crate::{AmqpError, AmqpResult, Message};
+use futures::future::poll_fn;
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::fmt::Display;
+use std::future::Future;
+use tower_service::Service;
+
+/// A delivery handed to a service
+#[derive(Debug, Clone)]
+pub struct Delivery {
+    /// Delivery ID on the receiver
+    pub delivery_id: u32,
+    /// The message
+    pub message: Message,
+}
+
+/// Extension methods for consuming a [`Receiver`] through a service
+pub trait ReceiverExt {
+    /// Call `service` for each delivery and settle it with the response
+    ///
+    /// Up to `concurrency` calls are in flight at once (at least one), and
+    /// the service's readiness is awaited before each call. Returns the
+    /// number of deliveries settled once no delivery is left and every call
+    /// has completed. If the service fails to become ready, or settling
+    /// fails, the error is returned and deliveries still in flight are left
+    /// unsettled.
+    fn serve<S>(&mut self, service: S, concurrency: usize) -> impl Future<Output = AmqpResult<usize>> + Send
+    where
+        S: Service<Delivery, Response = Outcome> + Send,
+        S::Future: Send,
+        S::Error: Display + Send;
+}
+
+impl ReceiverExt for Receiver {
+    async fn serve<S>(&mut self, mut service: S, concurrency: usize) -> AmqpResult<usize>
+    where
+        S: Service<Delivery, Response = Outcome> + Send,
+        S::Future: Send,
+        S::Error: Display + Send,
+    {
+        let concurrency = concurrency.max(1);
+        let mut in_flight = FuturesUnordered::new();
+        let mut settled = 0;
+        loop {
+            while in_flight.len() < concurrency {
+                poll_fn(|cx| service.poll_ready(cx))
+                    .await
+                    .map_err(|e| AmqpError::link(format!("Service is not ready: {}", e)))?;
+                let Some((delivery_id, message)) = self.receive_delivery().await? else {
+                    break;
+                };
+                let call = service.call(Delivery { delivery_id, message });
+                in_flight.push(async move { (delivery_id, call.await) });
+            }
+
+            let Some((delivery_id, response)) = in_flight.next().await else {
+                return Ok(settled);
+            };
+            let outcome = response.unwrap_or_else(|e| {
+                logging::warn!("Service failed delivery {} on '{}': {}", delivery_id, self.name(), e);
+                Outcome::Modified {
+                    delivery_failed: true,
+                    undeliverable_here: false,
+                }
+            });
+            self.settle(&[delivery_id], outcome)?;
+            settled += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::link::LinkBuilder;
+    use crate::performative::{Endpoint, Performative};
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    /// Accepts "ok", rejects anything else and fails on "boom"
+    #[derive(Clone, Default)]
+    struct Handler {
+        running: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    impl Service<Delivery> for Handler {
+        type Response = Outcome;
+        type Error = String;
+        type Future = Pin<Box<dyn Future<Output = Result<Outcome, String>> + Send>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), String>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, delivery: Delivery) -> Self::Future {
+            let (running, peak) = (self.running.clone(), self.peak.clone());
+            Box::pin(async move {
+                peak.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                match delivery.message.body_as_text() {
+                    Some("ok") => Ok(Outcome::Accepted),
+                    Some("boom") => Err("handler crashed".to_string()),
+                    _ => Ok(Outcome::Rejected { error: None }),
+                }
+            })
+        }
+    }
+
+    async fn attached_receiver(texts: &[&str]) -> (Receiver, Endpoint) {
+        let mut receiver = LinkBuilder::new().source("orders").build_receiver("session-1".to_string());
+        receiver.attach().await.unwrap();
+        let (local, remote) = Endpoint::pair();
+        receiver.set_endpoint(local);
+        for text in texts {
+            receiver.simulate_receive(Message::text(*text));
+        }
+        (receiver, remote)
+    }
+
+    #[tokio::test]
+    async fn test_serve_settles_with_service_response() {
+        let (mut receiver, remote) = attached_receiver(&["ok", "bad", "boom"]).await;
+
+        assert_eq!(receiver.serve(Handler::default(), 1).await.unwrap(), 3);
+        assert_eq!(receiver.unsettled_count(), 0);
+
+        let mut outcomes = Vec::new();
+        for _ in 0..3 {
+            match remote.recv().await {
+                Some(Performative::Disposition(disposition)) => {
+                    outcomes.push((disposition.first, disposition.state.unwrap()))
+                }
+                other => panic!("Expected disposition, got {:?}", other),
+            }
+        }
+        assert_eq!(
+            outcomes,
+            vec![
+                (0, Outcome::Accepted),
+                (1, Outcome::Rejected { error: None }),
+                (2, Outcome::Modified { delivery_failed: true, undeliverable_here: false }),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_serve_limits_concurrency() {
+        let (mut receiver, _remote) = attached_receiver(&["ok"; 6]).await;
+        let handler = Handler::default();
+
+        assert_eq!(receiver.serve(handler.clone(), 2).await.unwrap(), 6);
+        assert_eq!(handler.peak.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,1087 @@
+//! AMQP 1.0 Server Role
+//!
+//! This module accepts inbound AMQP connections. An [`AmqpListener`] takes
+//! TCP connections through a [`NetworkListener`], runs the TLS handshake
+//! when a [`TlsAcceptor`](crate::tls::TlsAcceptor) is configured (with the
+//! `tls` feature), answers the client's protocol headers, runs the server
+//! side of SASL when an [`SaslAcceptor`] is configured, and exchanges Open
+//! frames. Each handshake runs in a task
+//! of its own, so a slow client does not hold up the others. With a
+//! [`ContainerRegistry`], a client presenting a container-id that is already
+//! connected is refused, or takes over from the existing connection, as the
+//! registry's policy says. With a [`VhostRouter`], each connection is
+//! routed to a virtual host by the hostname in its Open, and one naming no
+//! known virtual host is refused with `amqp:not-found`. The result is an
+//! [`IncomingConnection`], from which the sessions the client begins are
+//! taken as [`IncomingSession`]s and the links it attaches on them as
+//! [`IncomingLink`]s.
+//!
+//! Nothing is answered on the application's behalf: a session is only
+//! begun once [`IncomingSession::accept`] is called, and a link is only
+//! attached once it is accepted as a [`Sender`] or a [`Receiver`]. These
+//! are the same types a client uses, so a client's sender is served by a
+//! receiver here and the other way around.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use dumq_amqp::server::AmqpListener;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let listener = AmqpListener::bind("0.0.0.0:5672").await?.container_id("my-broker");
+//!
+//! let mut connection = listener.accept().await?;
+//! while let Some(mut session) = connection.next_session().await {
+//!     session.accept()?;
+//!     while let Some(link) = session.next_link().await {
+//!         let mut receiver = link.accept_receiver()?;
+//!         receiver.add_credit(10);
+//!         while let Some((delivery_id, message)) = receiver.next_delivery().await? {
+//!             println!("Received: {:?}", message.body);
+//!             receiver.accept(delivery_id)?;
+//!         }
+//!     }
+//! }
+//! connection.close().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::connection::MIN_MAX_FRAME_SIZE;
+use crate::demux::{Demux, InboundLink, InboundSession};
+use crate::link::{LinkConfig, Receiver, Sender, TerminusConfig};
+use crate::listener::{self, Authorizer, ConnectionPermit, ContainerRegistry, NetworkListener, RoutedConnection, VhostRouter};
+use crate::logging;
+use crate::network::NetworkConnection;
+use crate::performative::{Attach, Begin, Close, Detach, End, Endpoint, Open, Performative, Terminus};
+use crate::sasl::{self, SaslAcceptor};
+use crate::session::SessionConfig;
+use crate::tasks::{self, TaskKind};
+use crate::transport::{Frame, FrameHeader, FrameType, ProtocolHeader, Transport};
+use crate::{ids, types, AmqpCondition, AmqpError, AmqpResult, Role};
+use std::any::Any;
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicU32;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, ToSocketAddrs};
+use tokio::sync::{mpsc, watch, Mutex};
+use tokio::task::{JoinHandle, JoinSet};
+
+/// Time a client gets to reach the Open exchange
+const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Listener accepting AMQP connections in the server role
+pub struct AmqpListener {
+    listener: NetworkListener,
+    settings: Arc<Settings>,
+    handshakes: Mutex<Handshakes>,
+    handshaken: mpsc::UnboundedSender<IncomingConnection>,
+}
+
+/// What the handshake of each connection is run with
+#[derive(Clone)]
+struct Settings {
+    container_id: String,
+    max_frame_size: u32,
+    channel_max: u16,
+    handshake_timeout: Duration,
+    sasl: Option<SaslAcceptor>,
+    authorizer: Option<Arc<dyn Authorizer>>,
+    registry: Option<ContainerRegistry>,
+    router: Option<Arc<dyn Route>>,
+    #[cfg(feature = "tls")]
+    tls: Option<crate::tls::TlsAcceptor>,
+}
+
+/// A [`VhostRouter`] with its handler type erased
+trait Route: Send + Sync {
+    /// Route a connection, returning the name of its virtual host and the [`RoutedConnection`]
+    fn route(&self, peer: &listener::IncomingConnection) -> AmqpResult<(String, Box<dyn Any + Send + Sync>)>;
+}
+
+impl<H: Send + Sync + 'static> Route for VhostRouter<H> {
+    fn route(&self, peer: &listener::IncomingConnection) -> AmqpResult<(String, Box<dyn Any + Send + Sync>)> {
+        let routed = VhostRouter::route(self, peer)?;
+        Ok((routed.vhost().to_string(), Box::new(routed)))
+    }
+}
+
+/// Handshakes under way, and the connections that completed theirs
+#[derive(Debug)]
+struct Handshakes {
+    /// Aborted when the listener is dropped
+    tasks: JoinSet<()>,
+    completed: mpsc::UnboundedReceiver<IncomingConnection>,
+}
+
+impl AmqpListener {
+    /// Listen on an address
+    pub async fn bind(addr: impl ToSocketAddrs) -> AmqpResult<Self> {
+        Ok(Self::from_listener(NetworkListener::from_listener(TcpListener::bind(addr).await?)))
+    }
+
+    /// Accept through a network listener, keeping its connection limit
+    ///
+    /// A connection holds its permit from the start of its handshake, so
+    /// the limit also bounds the handshakes under way.
+    pub fn from_listener(listener: NetworkListener) -> Self {
+        let (handshaken, completed) = mpsc::unbounded_channel();
+        AmqpListener {
+            listener,
+            settings: Arc::new(Settings {
+                container_id: format!("dumq-server-{}", ids::next_id()),
+                max_frame_size: 65536,
+                channel_max: 1000,
+                handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+                sasl: None,
+                authorizer: None,
+                registry: None,
+                router: None,
+                #[cfg(feature = "tls")]
+                tls: None,
+            }),
+            handshakes: Mutex::new(Handshakes { tasks: JoinSet::new(), completed }),
+            handshaken,
+        }
+    }
+
+    /// Set the container-id sent in our Open
+    pub fn container_id(mut self, container_id: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.settings).container_id = container_id.into();
+        self
+    }
+
+    /// Set the largest frame we accept
+    pub fn max_frame_size(mut self, max_frame_size: u32) -> Self {
+        Arc::make_mut(&mut self.settings).max_frame_size = max_frame_size;
+        self
+    }
+
+    /// Set the highest channel number we accept
+    pub fn channel_max(mut self, channel_max: u16) -> Self {
+        Arc::make_mut(&mut self.settings).channel_max = channel_max;
+        self
+    }
+
+    /// Set the time a client gets to reach the Open exchange
+    pub fn handshake_timeout(mut self, handshake_timeout: Duration) -> Self {
+        Arc::make_mut(&mut self.settings).handshake_timeout = handshake_timeout;
+        self
+    }
+
+    /// Require SASL, with the mechanisms of an acceptor
+    ///
+    /// Without an acceptor, clients must send the AMQP protocol header
+    /// straight away.
+    pub fn sasl(mut self, acceptor: SaslAcceptor) -> Self {
+        Arc::make_mut(&mut self.settings).sasl = Some(acceptor);
+        self
+    }
+
+    /// Require TLS, running the handshake of an acceptor on each connection
+    ///
+    /// The client's [`TlsIdentity`](listener::TlsIdentity) is then part of
+    /// the [`peer`](IncomingConnection::peer) seen by authorizers and
+    /// virtual host routing. Requires the `tls` feature.
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, acceptor: crate::tls::TlsAcceptor) -> Self {
+        Arc::make_mut(&mut self.settings).tls = Some(acceptor);
+        self
+    }
+
+    /// Decide whether connections are admitted once their Open is received
+    pub fn authorizer(mut self, authorizer: impl Authorizer + 'static) -> Self {
+        Arc::make_mut(&mut self.settings).authorizer = Some(Arc::new(authorizer));
+        self
+    }
+
+    /// Track the container-ids of connected clients in a registry
+    ///
+    /// A client presenting an id that is already connected is refused with
+    /// `amqp:connection:forced` under
+    /// [`DuplicateContainerPolicy::Reject`](listener::DuplicateContainerPolicy::Reject).
+    /// Under [`DuplicateContainerPolicy::StealExisting`](listener::DuplicateContainerPolicy::StealExisting)
+    /// it is admitted, and the existing connection is closed with that
+    /// condition instead. An id is held until its connection's driver stops.
+    pub fn container_registry(mut self, registry: ContainerRegistry) -> Self {
+        Arc::make_mut(&mut self.settings).registry = Some(registry);
+        self
+    }
+
+    /// Route connections to virtual hosts by the hostname in their Open
+    ///
+    /// A connection naming no virtual host the router knows, and with no
+    /// default to fall back on, is refused with `amqp:not-found`. One refused
+    /// by its virtual host's authorizer or quota is refused with that error.
+    /// The virtual host and its handler are available from the accepted
+    /// [`IncomingConnection`], which counts against the quota until dropped.
+    pub fn vhosts<H: Send + Sync + 'static>(mut self, router: VhostRouter<H>) -> Self {
+        Arc::make_mut(&mut self.settings).router = Some(Arc::new(router));
+        self
+    }
+
+    /// Get the address the listener is bound to
+    pub fn local_addr(&self) -> AmqpResult<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accept the next connection that completes the Open exchange
+    ///
+    /// Connections are handed over in the order their handshakes complete.
+    /// Clients that fail the handshake, whether by speaking another
+    /// protocol, failing authentication, being refused by the authorizer or
+    /// timing out, are logged and dropped while this keeps waiting.
+    ///
+    /// Cancel safe: handshakes carry on in the background, and a connection
+    /// that completes one while nobody is accepting is kept for the next call.
+    pub async fn accept(&self) -> AmqpResult<IncomingConnection> {
+        let mut handshakes = self.handshakes.lock().await;
+        while handshakes.tasks.try_join_next().is_some() {}
+        loop {
+            tokio::select! {
+                Some(connection) = handshakes.completed.recv() => return Ok(connection),
+                accepted = self.listener.accept() => {
+                    let accepted = accepted?;
+                    let remote_addr = accepted.incoming.remote_addr;
+                    let settings = self.settings.clone();
+                    let handshaken = self.handshaken.clone();
+                    let handshake = async move {
+                        let handshake = settings.handshake(accepted.stream, accepted.incoming, accepted.permit);
+                        match tokio::time::timeout(settings.handshake_timeout, handshake).await {
+                            Ok(Ok(connection)) => {
+                                let _ = handshaken.send(connection);
+                            }
+                            Ok(Err(e)) => logging::info!("Handshake with {} failed: {}", remote_addr, e),
+                            Err(_) => logging::info!("Handshake with {} timed out", remote_addr),
+                        }
+                    };
+                    tasks::spawn_in(&mut handshakes.tasks, TaskKind::Handshake, &remote_addr.to_string(), handshake);
+                }
+            }
+        }
+    }
+}
+
+impl Settings {
+    async fn handshake(
+        &self,
+        stream: tokio::net::TcpStream,
+        mut peer: listener::IncomingConnection,
+        permit: ConnectionPermit,
+    ) -> AmqpResult<IncomingConnection> {
+        #[cfg(feature = "tls")]
+        let mut transport = match &self.tls {
+            Some(acceptor) => {
+                let (stream, secured) = acceptor.accept(stream).await?;
+                peer.tls = secured.tls;
+                Transport::new(stream)
+            }
+            None => Transport::new(stream),
+        };
+        #[cfg(not(feature = "tls"))]
+        let mut transport = Transport::new(stream);
+        let mut header = receive_header(&mut transport).await?;
+
+        let mut user = None;
+        if let Some(acceptor) = &self.sasl {
+            if header != ProtocolHeader::SASL {
+                transport.send_raw(ProtocolHeader::SASL.as_bytes()).await?;
+                return Err(AmqpError::protocol_mismatch(ProtocolHeader::SASL, header));
+            }
+            user = sasl::accept(&mut transport, acceptor, peer.remote_addr.ip()).await?;
+            header = receive_header(&mut transport).await?;
+        }
+        transport.send_raw(ProtocolHeader::AMQP.as_bytes()).await?;
+        if header != ProtocolHeader::AMQP {
+            return Err(AmqpError::protocol_mismatch(ProtocolHeader::AMQP, header));
+        }
+
+        let remote = Open::decode(&NetworkConnection::receive_amqp_payload(&mut transport).await?)?;
+        peer.container_id = Some(remote.container_id.clone());
+        peer.hostname = remote.hostname.clone();
+
+        let open = Open {
+            container_id: self.container_id.clone(),
+            max_frame_size: self.max_frame_size,
+            channel_max: self.channel_max,
+            ..Default::default()
+        };
+        if let Some(authorizer) = &self.authorizer {
+            if let Err(e) = authorizer.authorize(&peer) {
+                return Err(refuse(transport, &open, e, AmqpCondition::AmqpErrorUnauthorizedAccess).await);
+            }
+        }
+        let routed = match self.router.as_ref().map(|router| router.route(&peer)) {
+            Some(Err(e)) => return Err(refuse(transport, &open, e, AmqpCondition::AmqpErrorUnauthorizedAccess).await),
+            Some(Ok(routed)) => Some(routed),
+            None => None,
+        };
+        let lease = match self.registry.as_ref().map(|registry| registry.register(&remote.container_id)) {
+            Some(Err(e)) => return Err(refuse(transport, &open, e, AmqpCondition::AmqpErrorConnectionForced).await),
+            Some(Ok(lease)) => Some(lease),
+            None => None,
+        };
+        send_payload(&mut transport, open.encode()?).await?;
+
+        if remote.max_frame_size < MIN_MAX_FRAME_SIZE {
+            return Err(AmqpError::protocol(format!(
+                "Peer max-frame-size {} is below the minimum of {}",
+                remote.max_frame_size, MIN_MAX_FRAME_SIZE
+            )));
+        }
+
+        let id = format!("server-connection-{}", ids::next_id());
+        logging::info!("Accepted connection {} from {} ({})", id, peer.remote_addr, remote.container_id);
+        let (vhost, routed) = routed.unzip();
+        let max_frame_size = remote.max_frame_size.min(self.max_frame_size);
+        let (demux, driver, sessions) = Demux::spawn_accepting(transport, max_frame_size, &id);
+        if let Some(lease) = lease {
+            let demux = demux.clone();
+            tasks::spawn(TaskKind::Watchdog, &id, async move {
+                tokio::select! {
+                    _ = lease.evicted() => {
+                        let error = lease.eviction_error();
+                        logging::info!("Closing connection of container '{}': {}", lease.container_id(), error);
+                        let error = types::AmqpError::new(AmqpCondition::AmqpErrorConnectionForced)
+                            .with_description(error.to_string());
+                        let _ = demux.close_with(error);
+                    }
+                    _ = demux.stopped() => {}
+                }
+            });
+        }
+        Ok(IncomingConnection {
+            id,
+            peer,
+            user,
+            remote_open: remote,
+            vhost,
+            routed,
+            demux,
+            driver,
+            sessions,
+            _permit: permit,
+        })
+    }
+}
+
+impl std::fmt::Debug for AmqpListener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("AmqpListener");
+        debug
+            .field("listener", &self.listener)
+            .field("container_id", &self.settings.container_id)
+            .field("max_frame_size", &self.settings.max_frame_size)
+            .field("channel_max", &self.settings.channel_max)
+            .field("handshake_timeout", &self.settings.handshake_timeout)
+            .field("sasl", &self.settings.sasl)
+            .field("authorizer", &self.settings.authorizer.is_some())
+            .field("vhosts", &self.settings.router.is_some());
+        #[cfg(feature = "tls")]
+        debug.field("tls", &self.settings.tls);
+        debug.finish()
+    }
+}
+
+async fn receive_header(transport: &mut Transport) -> AmqpResult<ProtocolHeader> {
+    let mut header = [0; 8];
+    header.copy_from_slice(&transport.receive_raw(8).await?);
+    Ok(ProtocolHeader::from_bytes(header))
+}
+
+/// Answer the client's Open with ours and a Close carrying `error`, returning it
+///
+/// The specification requires an Open before the Close, even when refusing.
+async fn refuse(mut transport: Transport, open: &Open, error: AmqpError, default: AmqpCondition) -> AmqpError {
+    let close = Close {
+        error: Some(types::AmqpError::new(error.condition().cloned().unwrap_or(default)).with_description(error.to_string())),
+    };
+    let refused = async {
+        send_payload(&mut transport, open.encode()?).await?;
+        send_payload(&mut transport, close.encode()?).await?;
+        transport.shutdown().await
+    };
+    if let Err(e) = refused.await {
+        logging::debug!("Refusing the connection failed: {}", e);
+    }
+    error
+}
+
+async fn send_payload(transport: &mut Transport, payload: Vec<u8>) -> AmqpResult<()> {
+    let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, 0);
+    transport.send_frame(Frame::new(header, payload)).await
+}
+
+/// A connection a client opened, past the Open exchange
+///
+/// Dropping it without [`close`](IncomingConnection::close) leaves the
+/// driver running until the client closes.
+#[derive(Debug)]
+pub struct IncomingConnection {
+    id: String,
+    peer: listener::IncomingConnection,
+    user: Option<String>,
+    remote_open: Open,
+    vhost: Option<String>,
+    /// The [`RoutedConnection`], holding a place in its virtual host's quota
+    routed: Option<Box<dyn Any + Send + Sync>>,
+    demux: Demux,
+    driver: JoinHandle<AmqpResult<Close>>,
+    sessions: mpsc::UnboundedReceiver<InboundSession>,
+    _permit: ConnectionPermit,
+}
+
+impl IncomingConnection {
+    /// Get the connection ID
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Get what is known about the client
+    pub fn peer(&self) -> &listener::IncomingConnection {
+        &self.peer
+    }
+
+    /// Get the address of the client
+    pub fn remote_addr(&self) -> SocketAddr {
+        self.peer.remote_addr
+    }
+
+    /// Get the container-id from the client's Open
+    pub fn container_id(&self) -> &str {
+        &self.remote_open.container_id
+    }
+
+    /// Get the user SASL authenticated, if the mechanism names one
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    /// Get the client's Open
+    pub fn remote_open(&self) -> &Open {
+        &self.remote_open
+    }
+
+    /// Get the virtual host the connection was routed to
+    ///
+    /// `None` unless the listener has [`vhosts`](AmqpListener::vhosts).
+    pub fn vhost(&self) -> Option<&str> {
+        self.vhost.as_deref()
+    }
+
+    /// Get the handler of the virtual host the connection was routed to
+    ///
+    /// `None` unless the listener has [`vhosts`](AmqpListener::vhosts) and
+    /// `H` is their handler type.
+    pub fn vhost_handler<H: 'static>(&self) -> Option<&Arc<H>> {
+        let routed = self.routed.as_ref()?.downcast_ref::<RoutedConnection<H>>()?;
+        Some(routed.handler())
+    }
+
+    /// Wait for the next session the client begins
+    ///
+    /// Returns `None` once the client has closed the connection.
+    pub async fn next_session(&mut self) -> Option<IncomingSession> {
+        let inbound = self.sessions.recv().await?;
+        logging::debug!("Client began a session on channel {} of {}", inbound.channel, self.id);
+        Some(IncomingSession {
+            connection_id: self.id.clone(),
+            channel: inbound.channel,
+            remote_channel: inbound.remote_channel,
+            remote_begin: inbound.begin,
+            endpoint: inbound.endpoint,
+            links: inbound.links,
+            demux: self.demux.clone(),
+            accepted: false,
+            ended: watch::channel(false).0,
+            delivery_ids: Arc::new(AtomicU32::new(0)),
+        })
+    }
+
+    /// Close the connection and wait for the client's Close
+    ///
+    /// A Close carrying an error, or one the client sent first, fails with
+    /// that error.
+    pub async fn close(self) -> AmqpResult<()> {
+        // The driver has already stopped if the client closed first
+        let _ = self.demux.close();
+        let close = match self.driver.await {
+            Ok(close) => close?,
+            Err(e) => return Err(AmqpError::connection(format!("Connection driver failed: {}", e))),
+        };
+        match close.error {
+            Some(error) => {
+                let description = error.description.unwrap_or_else(|| "Peer closed the connection".to_string());
+                Err(AmqpError::amqp_protocol(error.condition, description))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// A session a client began
+#[derive(Debug)]
+pub struct IncomingSession {
+    connection_id: String,
+    channel: u16,
+    remote_channel: u16,
+    remote_begin: Begin,
+    endpoint: Endpoint,
+    links: mpsc::UnboundedReceiver<InboundLink>,
+    demux: Demux,
+    accepted: bool,
+    ended: watch::Sender<bool>,
+    delivery_ids: Arc<AtomicU32>,
+}
+
+impl IncomingSession {
+    /// Get our channel for the session
+    pub fn channel(&self) -> u16 {
+        self.channel
+    }
+
+    /// Get the channel the client began the session on
+    pub fn remote_channel(&self) -> u16 {
+        self.remote_channel
+    }
+
+    /// Get the client's Begin
+    pub fn remote_begin(&self) -> &Begin {
+        &self.remote_begin
+    }
+
+    /// Answer the client's Begin, with the default session windows
+    pub fn accept(&mut self) -> AmqpResult<()> {
+        if self.accepted {
+            return Err(AmqpError::invalid_state("Session is already accepted"));
+        }
+        let config = SessionConfig::default();
+        self.endpoint.send(Performative::Begin(Begin {
+            remote_channel: Some(self.remote_channel),
+            next_outgoing_id: config.next_outgoing_id,
+            incoming_window: config.incoming_window,
+            outgoing_window: config.outgoing_window,
+            handle_max: config.handle_max,
+        }))?;
+        self.accepted = true;
+        Ok(())
+    }
+
+    /// Refuse the session, ending it right after the Begin with `error`
+    pub fn refuse(mut self, error: types::AmqpError) -> AmqpResult<()> {
+        if !self.accepted {
+            self.accept()?;
+        }
+        self.endpoint.send(Performative::End(End { error: Some(error) }))
+    }
+
+    /// Wait for the next link the client attaches
+    ///
+    /// Returns `None` once the client ends the session, whose End is
+    /// answered, or closes the connection.
+    pub async fn next_link(&mut self) -> Option<IncomingLink> {
+        if !self.accepted || *self.ended.borrow() {
+            return None;
+        }
+        // The links stop with the client's End, which is still to be answered
+        let mut links_open = true;
+        loop {
+            tokio::select! {
+                inbound = self.links.recv(), if links_open => match inbound {
+                    Some(inbound) => return Some(self.incoming_link(inbound)),
+                    None => links_open = false,
+                },
+                performative = self.endpoint.recv() => match performative {
+                    Some(Performative::End(end)) => {
+                        logging::debug!("Client ended the session on channel {}: {:?}", self.channel, end.error);
+                        let _ = self.endpoint.send(Performative::End(End { error: None }));
+                        break;
+                    }
+                    Some(other) => logging::debug!("Ignoring {:?} on session channel {}", other, self.channel),
+                    None => break,
+                },
+            }
+        }
+        self.ended.send_replace(true);
+        None
+    }
+
+    /// End the session
+    ///
+    /// Links accepted on the session are detached implicitly.
+    pub fn end(&mut self) -> AmqpResult<()> {
+        if !self.accepted || *self.ended.borrow() {
+            return Err(AmqpError::invalid_state("Session is not active"));
+        }
+        self.ended.send_replace(true);
+        self.endpoint.send(Performative::End(End { error: None }))
+    }
+
+    fn incoming_link(&self, inbound: InboundLink) -> IncomingLink {
+        IncomingLink {
+            remote: inbound.attach,
+            endpoint: inbound.endpoint,
+            connection_id: self.connection_id.clone(),
+            channel: self.channel,
+            demux: self.demux.clone(),
+            ended: self.ended.subscribe(),
+            delivery_ids: self.delivery_ids.clone(),
+        }
+    }
+}
+
+/// A link a client attached, waiting to be accepted or refused
+#[derive(Debug)]
+pub struct IncomingLink {
+    remote: Attach,
+    endpoint: Endpoint,
+    connection_id: String,
+    channel: u16,
+    demux: Demux,
+    ended: watch::Receiver<bool>,
+    delivery_ids: Arc<AtomicU32>,
+}
+
+impl IncomingLink {
+    /// Get the link name
+    pub fn name(&self) -> &str {
+        &self.remote.name
+    }
+
+    /// Get the client's role on the link
+    pub fn role(&self) -> Role {
+        self.remote.role
+    }
+
+    /// Get the client's Attach
+    pub fn remote_attach(&self) -> &Attach {
+        &self.remote
+    }
+
+    /// Get a link configuration echoing the client's Attach
+    ///
+    /// The name, addresses, settle modes, termini and coordinator are the client's, so
+    /// the Attach sent on accepting matches the one the client expects.
+    pub fn link_config(&self) -> LinkConfig {
+        LinkConfig {
+            name: self.remote.name.clone(),
+            source: self.remote.source.as_ref().and_then(|terminus| terminus.address.clone()),
+            target: self.remote.target.as_ref().and_then(|terminus| terminus.address.clone()),
+            sender_settle_mode: self.remote.snd_settle_mode,
+            receiver_settle_mode: self.remote.rcv_settle_mode,
+            source_config: self.remote.source.as_ref().map(terminus_config),
+            target_config: self.remote.target.as_ref().map(terminus_config),
+            coordinator: self.remote.coordinator.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Accept the link of a receiving client, sending to it
+    pub fn accept_sender(self) -> AmqpResult<Sender> {
+        let config = self.link_config();
+        self.accept_sender_with(config)
+    }
+
+    /// Accept the link of a receiving client with our own configuration
+    ///
+    /// The name is always the client's.
+    pub fn accept_sender_with(self, mut config: LinkConfig) -> AmqpResult<Sender> {
+        if self.remote.role != Role::Receiver {
+            return Err(AmqpError::invalid_state("Client attached as a sender; accept it with a receiver"));
+        }
+        config.name = self.remote.name.clone();
+        let mut sender = Sender::new(config, self.session_id());
+        sender.set_handle(self.remote.handle);
+        sender.set_session_ended(self.ended.clone());
+        sender.set_error_context(&self.connection_id, self.channel);
+        sender.set_delivery_ids(self.delivery_ids);
+        sender.set_endpoint(self.endpoint);
+        sender.set_connection(self.demux, self.channel);
+        sender.accept_attach(&self.remote)?;
+        Ok(sender)
+    }
+
+    /// Accept the link of a sending client, receiving from it
+    pub fn accept_receiver(self) -> AmqpResult<Receiver> {
+        let config = self.link_config();
+        self.accept_receiver_with(config)
+    }
+
+    /// Accept the link of a sending client with our own configuration
+    ///
+    /// The name is always the client's.
+    pub fn accept_receiver_with(self, mut config: LinkConfig) -> AmqpResult<Receiver> {
+        if self.remote.role != Role::Sender {
+            return Err(AmqpError::invalid_state("Client attached as a receiver; accept it with a sender"));
+        }
+        config.name = self.remote.name.clone();
+        let mut receiver = Receiver::new(config, self.session_id());
+        receiver.set_handle(self.remote.handle);
+        receiver.set_session_ended(self.ended.clone());
+        receiver.set_error_context(&self.connection_id, self.channel);
+        receiver.set_endpoint(self.endpoint);
+        receiver.accept_attach(&self.remote)?;
+        Ok(receiver)
+    }
+
+    /// Refuse the link, closing it right after the Attach with `error`
+    ///
+    /// As the specification asks, our Attach has no terminus so the client
+    /// knows the link was not established.
+    pub fn refuse(self, error: types::AmqpError) -> AmqpResult<()> {
+        let role = match self.remote.role {
+            Role::Sender => Role::Receiver,
+            Role::Receiver => Role::Sender,
+        };
+        self.endpoint.send(Performative::Attach(Attach {
+            name: self.remote.name.clone(),
+            handle: self.remote.handle,
+            role,
+            snd_settle_mode: self.remote.snd_settle_mode,
+            rcv_settle_mode: self.remote.rcv_settle_mode,
+            source: None,
+            target: None,
+            coordinator: None,
+            initial_delivery_count: (role == Role::Sender).then_some(0),
+            max_message_size: None,
+            properties: Default::default(),
+        }))?;
+        self.endpoint.send(Performative::Detach(Detach {
+            handle: self.remote.handle,
+            closed: true,
+            error: Some(error),
+        }))
+    }
+
+    fn session_id(&self) -> String {
+        format!("{}-session-{}", self.connection_id, self.channel)
+    }
+}
+
+fn terminus_config(terminus: &Terminus) -> TerminusConfig {
+    TerminusConfig {
+        durability: terminus.durable,
+        expiry_policy: terminus.expiry_policy,
+        timeout: terminus.timeout,
+        distribution_mode: terminus.distribution_mode,
+        dynamic: terminus.dynamic,
+        filter: terminus
+            .filter
+            .iter()
+            .map(|(name, value)| (name.as_str().to_string(), value.clone()))
+            .collect(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::ConnectionBuilder;
+    use crate::link::DeliveryOutcome;
+    use crate::message::{Body, Message};
+    use crate::network::NetworkBuilder;
+    use crate::sasl::SaslCredentials;
+
+    #[tokio::test]
+    async fn test_client_sender_served_by_accepted_receiver() {
+        let listener = AmqpListener::bind("127.0.0.1:0").await.unwrap().container_id("test-server");
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let mut connection = listener.accept().await?;
+            assert_eq!(connection.container_id(), "test-client");
+            let mut session = connection.next_session().await.unwrap();
+            session.accept()?;
+            let link = session.next_link().await.unwrap();
+            assert_eq!((link.role(), link.link_config().target.as_deref()), (Role::Sender, Some("orders")));
+            let mut receiver = link.accept_receiver()?;
+            receiver.add_credit(1);
+            let (delivery_id, message) = receiver.next_delivery().await?.unwrap();
+            receiver.accept(delivery_id)?;
+            // The client ends its session before closing
+            assert!(session.next_link().await.is_none());
+            connection.close().await?;
+            Ok::<_, AmqpError>(message)
+        });
+
+        let mut connection = ConnectionBuilder::new()
+            .hostname("127.0.0.1")
+            .port(port)
+            .container_id("test-client")
+            .build();
+        connection.open().await.unwrap();
+        assert_eq!(connection.remote_open().map(|open| open.container_id.as_str()), Some("test-server"));
+        let session = connection.create_session().await.unwrap();
+        let mut sender = session.create_sender(LinkConfig { target: Some("orders".to_string()), ..Default::default() }).await.unwrap();
+        assert!(sender.attach().await.unwrap().is_attached());
+        // Credit arrives with the server's Flow, right after its Attach
+        let delivery = loop {
+            match sender.send(Message::text("hello")).await.map_err(AmqpError::into_inner) {
+                Ok(delivery) => break delivery,
+                Err(AmqpError::Link(_)) => tokio::time::sleep(Duration::from_millis(5)).await,
+                Err(e) => panic!("send failed: {}", e),
+            }
+        };
+        assert!(matches!(sender.outcome(delivery).await.unwrap(), DeliveryOutcome::Accepted));
+        connection.close().await.unwrap();
+
+        let message = server.await.unwrap().unwrap();
+        assert!(matches!(message.body, Some(Body::Value(crate::AmqpValue::String(ref text))) if text == "hello"));
+    }
+
+    #[tokio::test]
+    async fn test_silent_client_does_not_hold_up_others() {
+        let listener = AmqpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Connected first, but never sends its protocol header
+        let _silent = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let server = tokio::spawn(async move {
+            let connection = listener.accept().await?;
+            Ok::<_, AmqpError>(connection.container_id().to_string())
+        });
+
+        let mut client = ConnectionBuilder::new()
+            .hostname("127.0.0.1")
+            .port(addr.port())
+            .container_id("second-client")
+            .build();
+        tokio::time::timeout(Duration::from_secs(1), client.open()).await.unwrap().unwrap();
+        let accepted = tokio::time::timeout(Duration::from_secs(1), server).await.unwrap();
+        assert_eq!(accepted.unwrap().unwrap(), "second-client");
+    }
+
+    /// Open a connection to a listener as `container_id`, returning the frame after the peer's Open
+    async fn open_as(port: u16, container_id: &str) -> (NetworkConnection, AmqpResult<Frame>) {
+        let mut client = NetworkBuilder::new()
+            .hostname("127.0.0.1")
+            .port(port)
+            .container_id(container_id)
+            .keep_alive_disabled()
+            .build();
+        client.connect().await.unwrap();
+        client.negotiate_protocol().await.unwrap();
+        let next = tokio::time::timeout(Duration::from_millis(200), client.receive_frame()).await;
+        (client, next.unwrap_or_else(|_| Err(AmqpError::timeout("No frame after the Open"))))
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_container_id_is_refused() {
+        let registry = ContainerRegistry::default();
+        let listener = AmqpListener::bind("127.0.0.1:0").await.unwrap().container_registry(registry.clone());
+        let port = listener.local_addr().unwrap().port();
+        let (accepted, mut connections) = mpsc::unbounded_channel();
+        let server = tokio::spawn(async move {
+            while let Ok(connection) = listener.accept().await {
+                let _ = accepted.send(connection);
+            }
+        });
+
+        let (_first, next) = open_as(port, "client-a").await;
+        assert!(next.is_err());
+        let first = connections.recv().await.unwrap();
+        assert!(registry.contains("client-a"));
+
+        let (_second, close) = open_as(port, "client-a").await;
+        let error = Close::decode(&close.unwrap().payload).unwrap().error.unwrap();
+        assert_eq!(error.condition, AmqpCondition::AmqpErrorConnectionForced);
+        assert!(error.description.unwrap().contains("client-a"));
+        assert!(connections.try_recv().is_err());
+        assert_eq!(first.container_id(), "client-a");
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_container_id_steals_existing() {
+        let registry = ContainerRegistry::new(listener::DuplicateContainerPolicy::StealExisting);
+        let listener = AmqpListener::bind("127.0.0.1:0").await.unwrap().container_registry(registry);
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let first = listener.accept().await?;
+            let second = listener.accept().await?;
+            Ok::<_, AmqpError>((first, second))
+        });
+
+        let (mut first, _) = open_as(port, "client-a").await;
+        let (_second, next) = open_as(port, "client-a").await;
+        assert!(next.is_err());
+        let close = tokio::time::timeout(Duration::from_secs(1), first.receive_frame()).await.unwrap().unwrap();
+        let error = Close::decode(&close.payload).unwrap().error.unwrap();
+        assert_eq!(error.condition, AmqpCondition::AmqpErrorConnectionForced);
+        drop(server.await.unwrap().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_connections_routed_by_open_hostname() {
+        let router = VhostRouter::new()
+            .host(listener::VirtualHost::new("tenant-a", "broker-a"))
+            .host(listener::VirtualHost::new("tenant-b", "broker-b").quota(listener::VhostQuota { max_connections: Some(1) }));
+        let listener = AmqpListener::bind("127.0.0.1:0").await.unwrap().vhosts(router);
+        let port = listener.local_addr().unwrap().port();
+        let (accepted, mut connections) = mpsc::unbounded_channel();
+        let server = tokio::spawn(async move {
+            while let Ok(connection) = listener.accept().await {
+                let _ = accepted.send(connection);
+            }
+        });
+        let open = |hostname: &str| {
+            ConnectionBuilder::new()
+                .hostname("127.0.0.1")
+                .port(port)
+                .hostname_override(hostname)
+                .build()
+        };
+
+        let mut client = open("Tenant-B");
+        client.open().await.unwrap();
+        let connection = connections.recv().await.unwrap();
+        assert_eq!(connection.vhost(), Some("tenant-b"));
+        assert_eq!(connection.vhost_handler::<&str>().map(|handler| **handler), Some("broker-b"));
+        assert!(connection.vhost_handler::<String>().is_none());
+
+        // tenant-b is at its quota until the connection is dropped
+        // Refused connections are opened, then closed with the error
+        let mut refused = open("tenant-b");
+        refused.open().await.unwrap();
+        let error = refused.close().await.unwrap_err().into_inner();
+        assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorResourceLimitExceeded));
+        drop(connection);
+        open("tenant-b").open().await.unwrap();
+        assert_eq!(connections.recv().await.unwrap().vhost(), Some("tenant-b"));
+
+        let mut refused = open("tenant-c");
+        refused.open().await.unwrap();
+        let error = refused.close().await.unwrap_err().into_inner();
+        assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorNotFound));
+        assert!(connections.try_recv().is_err());
+        server.abort();
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn test_handshake_over_tls() {
+        use crate::tls::{ClientAuth, TlsAcceptorBuilder, TlsConnectorBuilder};
+
+        const CA: &str = include_str!("../testdata/tls/ca.pem");
+        let acceptor = TlsAcceptorBuilder::new(include_str!("../testdata/tls/server.pem"), include_str!("../testdata/tls/server.key"))
+            .client_auth(ClientAuth::Required(CA.as_bytes().to_vec()))
+            .build()
+            .unwrap();
+        let listener = AmqpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .container_id("tls-server")
+            .tls(acceptor)
+            .authorizer(|incoming: &listener::IncomingConnection| match &incoming.tls {
+                Some(tls) if tls.is_client_authenticated() => Ok(()),
+                _ => Err(AmqpError::amqp_protocol(AmqpCondition::AmqpErrorUnauthorizedAccess, "Certificate required")),
+            });
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move { listener.accept().await });
+
+        let connector = TlsConnectorBuilder::new(CA)
+            .client_certificate(include_str!("../testdata/tls/client.pem"), include_str!("../testdata/tls/client.key"))
+            .build()
+            .unwrap();
+        let stream = connector.connect("localhost", tokio::net::TcpStream::connect(addr).await.unwrap()).await.unwrap();
+
+        let mut client = Transport::new(stream);
+        client.send_raw(ProtocolHeader::AMQP.as_bytes()).await.unwrap();
+        assert_eq!(receive_header(&mut client).await.unwrap(), ProtocolHeader::AMQP);
+        send_payload(&mut client, Open { container_id: "tls-client".to_string(), ..Default::default() }.encode().unwrap())
+            .await
+            .unwrap();
+        let open = Open::decode(&client.receive_frame().await.unwrap().payload).unwrap();
+        assert_eq!(open.container_id, "tls-server");
+
+        let connection = tokio::time::timeout(Duration::from_secs(1), server).await.unwrap().unwrap().unwrap();
+        assert_eq!(connection.container_id(), "tls-client");
+        let tls = connection.peer().tls.as_ref().unwrap();
+        assert_eq!(tls.server_name.as_deref(), Some("localhost"));
+        assert!(tls.is_client_authenticated());
+    }
+
+    #[tokio::test]
+    async fn test_link_name_attached_again_after_detach() {
+        let listener = AmqpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let mut connection = listener.accept().await?;
+            let mut session = connection.next_session().await.unwrap();
+            session.accept()?;
+            let mut first = session.next_link().await.unwrap().accept_receiver()?;
+            // Held on to while the client detaches and attaches the same name again
+            assert!(!matches!(first.next_delivery().await, Ok(Some(_))));
+            let second = session.next_link().await.unwrap();
+            assert_eq!(second.name(), "subscription");
+            second.accept_receiver()?;
+            Ok::<_, AmqpError>((connection, first))
+        });
+
+        let mut connection = ConnectionBuilder::new().hostname("127.0.0.1").port(port).build();
+        connection.open().await.unwrap();
+        let session = connection.create_session().await.unwrap();
+        let config = || LinkConfig { name: "subscription".to_string(), target: Some("orders".to_string()), ..Default::default() };
+        let mut sender = session.create_sender(config()).await.unwrap();
+        assert!(sender.attach().await.unwrap().is_attached());
+        sender.close().await.unwrap();
+        let mut sender = session.create_sender(config()).await.unwrap();
+        assert!(sender.attach().await.unwrap().is_attached());
+        drop(server.await.unwrap().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_sasl_plain_names_user_and_refuses_bad_password() {
+        let acceptor = SaslAcceptor::new().plain(|authcid: &str, password: &str| authcid == "alice" && password == "secret");
+        let listener = AmqpListener::bind("127.0.0.1:0").await.unwrap().sasl(acceptor);
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let connection = listener.accept().await?;
+            Ok::<_, AmqpError>(connection.user().map(str::to_string))
+        });
+
+        let mut refused = NetworkBuilder::new()
+            .hostname("127.0.0.1")
+            .port(port)
+            .sasl(SaslCredentials::plain("alice", "wrong"))
+            .build();
+        refused.connect().await.unwrap();
+        let result = refused.negotiate_protocol().await.map_err(AmqpError::into_inner);
+        assert!(matches!(result, Err(AmqpError::AuthenticationRejected { .. })), "{:?}", result);
+
+        let mut admitted = NetworkBuilder::new()
+            .hostname("127.0.0.1")
+            .port(port)
+            .sasl(SaslCredentials::plain("alice", "secret"))
+            .build();
+        admitted.connect().await.unwrap();
+        admitted.negotiate_protocol().await.unwrap();
+        assert_eq!(server.await.unwrap().unwrap().as_deref(), Some("alice"));
+    }
+
+    #[tokio::test]
+    async fn test_refused_link_is_detached_with_error() {
+        let listener = AmqpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let mut connection = listener.accept().await?;
+            let mut session = connection.next_session().await.unwrap();
+            session.accept()?;
+            let link = session.next_link().await.unwrap();
+            link.refuse(types::AmqpError::new(AmqpCondition::AmqpErrorNotAllowed).with_description("Address is not served"))?;
+            Ok::<_, AmqpError>(connection)
+        });
+
+        let mut connection = ConnectionBuilder::new().hostname("127.0.0.1").port(port).build();
+        connection.open().await.unwrap();
+        let session = connection.create_session().await.unwrap();
+        let mut receiver = session.create_receiver(LinkConfig { source: Some("missing".to_string()), ..Default::default() }).await.unwrap();
+        match receiver.attach().await.unwrap() {
+            crate::link::AttachOutcome::Refused { error: Some(error) } => {
+                assert_eq!(error.condition, AmqpCondition::AmqpErrorNotAllowed)
+            }
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+        drop(server.await.unwrap().unwrap());
+    }
+}
@@ -1,6 +1,16 @@
-use crate::{AmqpError, AmqpResult, AmqpValue};
-use std::collections::HashMap;
-use uuid::Uuid;
+use crate::demux::Demux;
+use crate::error::ErrorContext;
+use crate::logging;
+use crate::{AmqpCondition, AmqpError, AmqpResult, AmqpValue};
+use crate::performative::{Begin, Coordinator, Disposition, End, Endpoint, Outcome, Performative};
+use crate::transaction::{self, Transaction};
+use crate::types::Role;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::AtomicU32;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::watch;
+use tokio::time::{timeout, Duration};
+use crate::ids::{self, Namer};
 
 /// AMQP 1.0 Session state
 #[derive(Debug, Clone, PartialEq)]
@@ -32,6 +42,12 @@ pub struct SessionConfig {
     pub incoming_window_size: u32,
     /// Outgoing window
     pub outgoing_window_size: u32,
+    /// Maximum link handle value this endpoint accepts
+    pub handle_max: u32,
+    /// Time to wait for the peer's Begin or End
+    pub begin_timeout: Duration,
+    /// Default largest incoming message for links without their own limit
+    pub max_message_size: Option<u64>,
     /// Session properties
     pub properties: HashMap<String, AmqpValue>,
 }
@@ -39,12 +55,15 @@ pub struct SessionConfig {
 impl Default for SessionConfig {
     fn default() -> Self {
         SessionConfig {
-            name: Uuid::new_v4().to_string(),
+            name: ids::default_namer().name("session"),
             incoming_window: 100,
             outgoing_window: 100,
             next_outgoing_id: 0,
             incoming_window_size: 100,
             outgoing_window_size: 100,
+            handle_max: u32::MAX,
+            begin_timeout: Duration::from_secs(30),
+            max_message_size: None,
             properties: HashMap::new(),
         }
     }
@@ -64,8 +83,26 @@ pub struct Session {
     channel: u16,
     /// Links in this session
     links: HashMap<String, crate::link::Link>,
-    /// Next link handle
-    next_handle: u32,
+    /// Link handles in use, shared with the links holding them
+    handles: Handles,
+    /// Handle max announced by the peer
+    remote_handle_max: Option<u32>,
+    /// Incoming window announced by the peer
+    remote_incoming_window: Option<u32>,
+    /// Outgoing window announced by the peer
+    remote_outgoing_window: Option<u32>,
+    /// Next transfer ID expected from the peer
+    next_incoming_id: Option<u32>,
+    /// Channel to the peer, if the session is wired to one
+    endpoint: Option<Endpoint>,
+    /// Driver of the connection's frames, which links are wired to
+    demux: Option<Demux>,
+    /// Names links left with a generated name
+    namer: Namer,
+    /// Signals links created by this session when it ends
+    ended: watch::Sender<bool>,
+    /// Next delivery ID, shared by the session's senders
+    delivery_ids: Arc<AtomicU32>,
 }
 
 impl Session {
@@ -78,24 +115,89 @@ impl Session {
             connection_id,
             channel,
             links: HashMap::new(),
-            next_handle: 0,
+            handles: Handles::default(),
+            remote_handle_max: None,
+            remote_incoming_window: None,
+            remote_outgoing_window: None,
+            next_incoming_id: None,
+            endpoint: None,
+            demux: None,
+            namer: ids::default_namer().clone(),
+            ended: watch::channel(false).0,
+            delivery_ids: Arc::new(AtomicU32::new(0)),
         }
     }
 
     /// Begin the session
+    ///
+    /// When the session has an endpoint, a Begin is sent and the peer's Begin
+    /// is awaited for up to the configured begin timeout before the session
+    /// becomes active. Without an endpoint the session begins locally.
     pub async fn begin(&mut self) -> AmqpResult<()> {
+        let result = self.begin_exchange().await;
+        self.attribute(result)
+    }
+
+    async fn begin_exchange(&mut self) -> AmqpResult<()> {
         if self.state != SessionState::Ended {
             return Err(AmqpError::invalid_state("Session is not ended"));
         }
 
+        // Links from a previous incarnation keep seeing the old signal
+        if *self.ended.borrow() {
+            self.ended = watch::channel(false).0;
+        }
+
         self.state = SessionState::Beginning;
-        // In a real implementation, you would send the Begin performative here
+        if let Some(endpoint) = self.endpoint.clone() {
+            if let Err(e) = self.exchange_begin(&endpoint).await {
+                self.state = SessionState::Ended;
+                return Err(e);
+            }
+        }
         self.state = SessionState::Active;
         Ok(())
     }
 
+    async fn exchange_begin(&mut self, endpoint: &Endpoint) -> AmqpResult<()> {
+        endpoint.send(Performative::Begin(self.begin_performative()))?;
+
+        let remote = match timeout(self.config.begin_timeout, endpoint.recv()).await {
+            Ok(Some(Performative::Begin(begin))) => begin,
+            Ok(Some(other)) => return Err(AmqpError::protocol(format!("Expected begin, got {:?}", other))),
+            Ok(None) => return Err(AmqpError::session("Peer closed before sending begin")),
+            Err(_) => {
+                return Err(AmqpError::timeout(format!(
+                    "Timed out waiting for remote begin on channel {}",
+                    self.channel
+                )))
+            }
+        };
+
+        if let Some(remote_channel) = remote.remote_channel {
+            if remote_channel != self.channel {
+                return Err(AmqpError::protocol(format!(
+                    "Peer answered begin for channel {} on channel {}",
+                    remote_channel, self.channel
+                )));
+            }
+        }
+        self.on_remote_begin(&remote);
+        Ok(())
+    }
+
     /// End the session
+    ///
+    /// Links created by the session are detached implicitly: their pending
+    /// operations fail, and later calls on them return
+    /// [`AmqpError::InvalidState`]. When the session has an endpoint, an End
+    /// is sent and the peer's End is awaited for up to the begin timeout.
     pub async fn end(&mut self) -> AmqpResult<()> {
+        let result = self.end_exchange().await;
+        self.attribute(result)
+    }
+
+    async fn end_exchange(&mut self) -> AmqpResult<()> {
         if self.state != SessionState::Active {
             return Err(AmqpError::invalid_state("Session is not active"));
         }
@@ -104,25 +206,105 @@ impl Session {
 
         // End all links
         for link in self.links.values_mut() {
-            link.detach().await?;
+            if link.state() == &crate::link::LinkState::Attached {
+                link.detach().await?;
+            }
         }
         self.links.clear();
+        self.ended.send_replace(true);
 
-        // In a real implementation, you would send the End performative here
+        let result = match self.endpoint.clone() {
+            Some(endpoint) => self.exchange_end(&endpoint).await,
+            None => Ok(()),
+        };
         self.state = SessionState::Ended;
+        result
+    }
+
+    async fn exchange_end(&self, endpoint: &Endpoint) -> AmqpResult<()> {
+        endpoint.send(Performative::End(End::default()))?;
+
+        match timeout(self.config.begin_timeout, endpoint.recv()).await {
+            Ok(Some(Performative::End(End { error: None }))) => Ok(()),
+            Ok(Some(Performative::End(End { error: Some(error) }))) => Err(AmqpError::amqp_protocol(
+                error.condition,
+                error.description.unwrap_or_else(|| "Peer ended session with an error".to_string()),
+            )),
+            Ok(Some(other)) => Err(AmqpError::protocol(format!("Expected end, got {:?}", other))),
+            Ok(None) => Err(AmqpError::session("Peer closed before sending end")),
+            Err(_) => Err(AmqpError::timeout(format!(
+                "Timed out waiting for remote end on channel {}",
+                self.channel
+            ))),
+        }
+    }
+
+    /// Settle a range of incoming deliveries with one Disposition
+    pub fn settle_range(&mut self, first: u32, last: u32, outcome: Outcome) -> AmqpResult<()> {
+        let result = self.send_disposition(first, last, outcome);
+        self.attribute(result)
+    }
+
+    fn send_disposition(&mut self, first: u32, last: u32, outcome: Outcome) -> AmqpResult<()> {
+        if self.state != SessionState::Active {
+            return Err(AmqpError::invalid_state("Session is not active"));
+        }
+        if last < first {
+            return Err(AmqpError::session(format!("Invalid delivery range: {} is before {}", last, first)));
+        }
+
+        let disposition = Disposition {
+            role: Role::Receiver,
+            first,
+            last: (last != first).then_some(last),
+            settled: true,
+            state: Some(outcome),
+            batchable: false,
+        };
+        if let Some(endpoint) = &self.endpoint {
+            endpoint.send(Performative::Disposition(disposition))?;
+        }
+        Ok(())
+    }
+
+    /// End the session if active and release it
+    ///
+    /// Prefer this over dropping an active session, which can only queue an
+    /// End without waiting for the peer.
+    pub async fn close(mut self) -> AmqpResult<()> {
+        if self.state == SessionState::Active {
+            self.end().await?;
+        }
         Ok(())
     }
 
     /// Create a sender link
     pub async fn create_sender(&mut self, config: crate::link::LinkConfig) -> AmqpResult<crate::link::Sender> {
+        let result = self.new_sender(config);
+        self.attribute(result)
+    }
+
+    fn new_sender(&mut self, mut config: crate::link::LinkConfig) -> AmqpResult<crate::link::Sender> {
         if self.state != SessionState::Active {
             return Err(AmqpError::invalid_state("Session is not active"));
         }
-
-        let handle = self.next_handle;
-        self.next_handle += 1;
-
-        let sender = crate::link::Sender::new(config.clone(), self.id.clone());
+        if config.max_message_size.is_none() {
+            config.max_message_size = self.config.max_message_size;
+        }
+        self.name_link(&mut config, Role::Sender);
+
+        let handle = self.allocate_handle()?;
+
+        let mut sender = crate::link::Sender::new(config.clone(), self.id.clone());
+        sender.set_handle(handle);
+        sender.set_handles(self.handles.clone());
+        sender.set_session_ended(self.ended.subscribe());
+        sender.set_error_context(&self.connection_id, self.channel);
+        sender.set_delivery_ids(self.delivery_ids.clone());
+        if let Some(demux) = &self.demux {
+            sender.set_endpoint(demux.link(self.channel, &config.name, Role::Sender)?);
+            sender.set_connection(demux.clone(), self.channel);
+        }
         let link = crate::link::Link::new(config, self.id.clone());
         self.links.insert(handle.to_string(), link);
         
@@ -131,20 +313,170 @@ impl Session {
 
     /// Create a receiver link
     pub async fn create_receiver(&mut self, config: crate::link::LinkConfig) -> AmqpResult<crate::link::Receiver> {
+        let result = self.new_receiver(config);
+        self.attribute(result)
+    }
+
+    fn new_receiver(&mut self, mut config: crate::link::LinkConfig) -> AmqpResult<crate::link::Receiver> {
         if self.state != SessionState::Active {
             return Err(AmqpError::invalid_state("Session is not active"));
         }
+        if config.max_message_size.is_none() {
+            config.max_message_size = self.config.max_message_size;
+        }
+        self.name_link(&mut config, Role::Receiver);
 
-        let handle = self.next_handle;
-        self.next_handle += 1;
+        let handle = self.allocate_handle()?;
 
-        let receiver = crate::link::Receiver::new(config.clone(), self.id.clone());
+        let mut receiver = crate::link::Receiver::new(config.clone(), self.id.clone());
+        receiver.set_handle(handle);
+        receiver.set_handles(self.handles.clone());
+        receiver.set_session_ended(self.ended.subscribe());
+        receiver.set_error_context(&self.connection_id, self.channel);
+        if let Some(demux) = &self.demux {
+            receiver.set_endpoint(demux.link(self.channel, &config.name, Role::Receiver)?);
+        }
         let link = crate::link::Link::new(config, self.id.clone());
         self.links.insert(handle.to_string(), link);
         
         Ok(receiver)
     }
 
+    /// Declare a transaction with the peer's coordinator
+    ///
+    /// A coordinator link is attached for the transaction, asking for local
+    /// transactions, and detached when it is committed or rolled back.
+    /// Fails if the peer refuses the link or the declare.
+    pub async fn begin_transaction(&mut self) -> AmqpResult<Transaction> {
+        let result = self.declare_transaction().await;
+        self.attribute(result)
+    }
+
+    async fn declare_transaction(&mut self) -> AmqpResult<Transaction> {
+        let config = crate::link::LinkConfig {
+            name: self.namer.name("coordinator"),
+            coordinator: Some(Coordinator { capabilities: transaction::default_capabilities() }),
+            ..Default::default()
+        };
+        let mut coordinator = self.new_sender(config)?;
+        transaction::attach_coordinator(&mut coordinator).await?;
+        Transaction::declare(coordinator).await
+    }
+
+    /// Replace a generated link name with one from this session's namer
+    ///
+    /// The name starts with the link's address and role, e.g.
+    /// `orders-sender-3-1a2b3c4d`, so it can be found in broker management
+    /// UIs. Names chosen by the application are kept.
+    fn name_link(&self, config: &mut crate::link::LinkConfig, role: Role) {
+        if !ids::default_namer().named("link", &config.name) {
+            return;
+        }
+        let (address, role) = match role {
+            Role::Sender => (&config.target, "sender"),
+            Role::Receiver => (&config.source, "receiver"),
+        };
+        config.name = match address {
+            Some(address) => self.namer.name(&format!("{}-{}", address, role)),
+            None => self.namer.name(role),
+        };
+    }
+
+    /// Attach this session's connection and channel to a failure
+    fn attribute<T>(&self, result: AmqpResult<T>) -> AmqpResult<T> {
+        result.map_err(|e| e.with_context(ErrorContext::default().connection(&self.connection_id).channel(self.channel)))
+    }
+
+    /// Allocate the lowest free link handle within the negotiated handle max
+    fn allocate_handle(&mut self) -> AmqpResult<u32> {
+        let handle_max = self.negotiated_handle_max();
+        self.handles.allocate(handle_max).ok_or_else(|| {
+            AmqpError::amqp_protocol(
+                AmqpCondition::AmqpErrorResourceLimitExceeded,
+                format!("No link handle available (handle-max {})", handle_max),
+            )
+        })
+    }
+
+    /// Build the Begin performative for this session
+    pub fn begin_performative(&self) -> Begin {
+        Begin {
+            remote_channel: None,
+            next_outgoing_id: self.config.next_outgoing_id,
+            incoming_window: self.config.incoming_window,
+            outgoing_window: self.config.outgoing_window,
+            handle_max: self.config.handle_max,
+        }
+    }
+
+    /// Record the values announced in the peer's Begin
+    pub fn on_remote_begin(&mut self, begin: &Begin) {
+        self.remote_handle_max = Some(begin.handle_max);
+        self.remote_incoming_window = Some(begin.incoming_window);
+        self.remote_outgoing_window = Some(begin.outgoing_window);
+        self.next_incoming_id = Some(begin.next_outgoing_id);
+    }
+
+    /// Wire the session to a peer endpoint
+    pub fn set_endpoint(&mut self, endpoint: Endpoint) {
+        self.endpoint = Some(endpoint);
+    }
+
+    /// Name the links this session creates with a connection's namer
+    pub(crate) fn set_namer(&mut self, namer: Namer) {
+        self.namer = namer;
+    }
+
+    /// Wire the session and the links it creates to a connection's driver
+    pub(crate) fn set_demux(&mut self, demux: Demux) -> AmqpResult<()> {
+        self.set_endpoint(demux.session(self.channel)?);
+        self.demux = Some(demux);
+        Ok(())
+    }
+
+    /// Validate the handle of an Attach received from the peer
+    pub fn validate_remote_attach(&self, handle: u32) -> AmqpResult<()> {
+        if handle > self.config.handle_max {
+            return Err(AmqpError::amqp_protocol(
+                AmqpCondition::AmqpErrorFramingError,
+                format!("Attach handle {} exceeds handle-max {}", handle, self.config.handle_max),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Get local handle max
+    pub fn handle_max(&self) -> u32 {
+        self.config.handle_max
+    }
+
+    /// Get handle max announced by the peer
+    pub fn remote_handle_max(&self) -> Option<u32> {
+        self.remote_handle_max
+    }
+
+    /// Get incoming window announced by the peer
+    pub fn remote_incoming_window(&self) -> Option<u32> {
+        self.remote_incoming_window
+    }
+
+    /// Get outgoing window announced by the peer
+    pub fn remote_outgoing_window(&self) -> Option<u32> {
+        self.remote_outgoing_window
+    }
+
+    /// Get the next transfer ID expected from the peer
+    pub fn next_incoming_id(&self) -> Option<u32> {
+        self.next_incoming_id
+    }
+
+    /// Get negotiated handle max (the smaller of local and remote)
+    pub fn negotiated_handle_max(&self) -> u32 {
+        self.config
+            .handle_max
+            .min(self.remote_handle_max.unwrap_or(u32::MAX))
+    }
+
     /// Get session state
     pub fn state(&self) -> &SessionState {
         &self.state
@@ -155,6 +487,11 @@ impl Session {
         &self.id
     }
 
+    /// Get session name
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
     /// Get connection ID
     pub fn connection_id(&self) -> &str {
         &self.connection_id
@@ -190,9 +527,90 @@ impl Session {
         self.links.len()
     }
 
-    /// Get the next link handle
-    pub fn next_handle(&self) -> u32 {
-        self.next_handle
+    /// Get the handle the next link would be given
+    ///
+    /// This is the lowest handle within the negotiated handle max that no
+    /// link holds; `None` if they are all in use.
+    pub fn next_handle(&self) -> Option<u32> {
+        self.handles.lowest_free(self.negotiated_handle_max())
+    }
+}
+
+/// Link handles in use on a session
+///
+/// Shared with the session's links, which give their handle back when they
+/// detach or drop and take one again when they re-attach.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Handles(Arc<Mutex<HandleSet>>);
+
+#[derive(Debug, Default)]
+struct HandleSet {
+    in_use: BTreeSet<u32>,
+    /// Handle max of the last allocation, bounding re-attaching links
+    max: u32,
+}
+
+impl HandleSet {
+    fn lowest_free(&self, max: u32) -> Option<u32> {
+        let mut candidate = 0u32;
+        for &used in &self.in_use {
+            if used != candidate {
+                break;
+            }
+            candidate = candidate.checked_add(1)?;
+        }
+        (candidate <= max).then_some(candidate)
+    }
+
+    fn take_lowest(&mut self) -> Option<u32> {
+        let handle = self.lowest_free(self.max)?;
+        self.in_use.insert(handle);
+        Some(handle)
+    }
+}
+
+impl Handles {
+    fn lock(&self) -> MutexGuard<'_, HandleSet> {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Take the lowest free handle up to `max`
+    fn allocate(&self, max: u32) -> Option<u32> {
+        let mut handles = self.lock();
+        handles.max = max;
+        handles.take_lowest()
+    }
+
+    fn lowest_free(&self, max: u32) -> Option<u32> {
+        self.lock().lowest_free(max)
+    }
+
+    /// Take `handle` back for a re-attaching link, or the lowest free one
+    /// if another link has it by now
+    pub(crate) fn reclaim(&self, handle: u32) -> Option<u32> {
+        let mut handles = self.lock();
+        if handle <= handles.max && handles.in_use.insert(handle) {
+            return Some(handle);
+        }
+        handles.take_lowest()
+    }
+
+    /// Give a handle back for reuse
+    pub(crate) fn release(&self, handle: u32) {
+        self.lock().in_use.remove(&handle);
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if self.state != SessionState::Active {
+            return;
+        }
+        logging::warn!("Session '{}' dropped while active; call close() to end it cleanly", self.id);
+        self.ended.send_replace(true);
+        if let Some(endpoint) = &self.endpoint {
+            let _ = endpoint.send(Performative::End(End::default()));
+        }
     }
 }
 
@@ -234,6 +652,24 @@ impl SessionBuilder {
         self
     }
 
+    /// Set the time to wait for the peer's Begin
+    pub fn begin_timeout(mut self, timeout: Duration) -> Self {
+        self.config.begin_timeout = timeout;
+        self
+    }
+
+    /// Set the maximum link handle value
+    pub fn handle_max(mut self, handle_max: u32) -> Self {
+        self.config.handle_max = handle_max;
+        self
+    }
+
+    /// Set the default largest incoming message for links created on the session
+    pub fn max_message_size(mut self, bytes: u64) -> Self {
+        self.config.max_message_size = Some(bytes);
+        self
+    }
+
     /// Add a session property
     pub fn property(mut self, key: impl Into<String>, value: AmqpValue) -> Self {
         self.config.properties.insert(key.into(), value);
@@ -258,6 +694,8 @@ impl Default for SessionBuilder {
 mod tests {
     use super::*;
     use crate::link::LinkConfig;
+    use crate::performative::Attach;
+    use crate::Message;
 
     #[test]
     fn test_session_state_variants() {
@@ -300,6 +738,7 @@ mod tests {
         assert_eq!(config.next_outgoing_id, 0);
         assert_eq!(config.incoming_window_size, 100);
         assert_eq!(config.outgoing_window_size, 100);
+        assert_eq!(config.handle_max, u32::MAX);
         assert!(config.properties.is_empty());
     }
 
@@ -325,7 +764,7 @@ mod tests {
         assert_eq!(session.state, SessionState::Ended);
         assert_eq!(session.id, "test-connection-session-5");
         assert!(session.links.is_empty());
-        assert_eq!(session.next_handle, 0);
+        assert_eq!(session.next_handle(), Some(0));
     }
 
     #[test]
@@ -410,7 +849,7 @@ mod tests {
         
         let result = session.end().await;
         assert!(result.is_err());
-        assert!(matches!(result.unwrap_err(), AmqpError::InvalidState { .. }));
+        assert!(matches!(result.unwrap_err().inner(), AmqpError::InvalidState { .. }));
         assert_eq!(session.state(), &SessionState::Ended); // State should not change
     }
 
@@ -421,8 +860,10 @@ mod tests {
         session.begin().await.unwrap();
         assert_eq!(session.state(), &SessionState::Active);
         
-        let mut link_config = LinkConfig::default();
-        link_config.name = "test-link".to_string();
+        let link_config = LinkConfig {
+            name: "test-link".to_string(),
+            ..Default::default()
+        };
         let result = session.create_sender(link_config).await;
         assert!(result.is_ok());
         
@@ -439,7 +880,7 @@ mod tests {
         let link_config = LinkConfig::default();
         let result = session.create_sender(link_config).await;
         assert!(result.is_err());
-        assert!(matches!(result.unwrap_err(), AmqpError::InvalidState { .. }));
+        assert!(matches!(result.unwrap_err().inner(), AmqpError::InvalidState { .. }));
     }
 
     #[tokio::test]
@@ -447,8 +888,10 @@ mod tests {
         let mut session = Session::new(1, "test-connection".to_string());
         session.state = SessionState::Active; // Set to active state
         
-        let mut link_config = LinkConfig::default();
-        link_config.name = "test-link".to_string();
+        let link_config = LinkConfig {
+            name: "test-link".to_string(),
+            ..Default::default()
+        };
         let result = session.create_receiver(link_config).await;
         assert!(result.is_ok());
         
@@ -457,6 +900,23 @@ mod tests {
         assert!(receiver.id().starts_with("test-connection-session-1-link-"));
     }
 
+    #[tokio::test]
+    async fn test_session_max_message_size_default() {
+        let mut session = Session::new(1, "test-connection".to_string());
+        session.state = SessionState::Active;
+        session.config.max_message_size = Some(4096);
+
+        let inherited = session.create_receiver(LinkConfig::default()).await.unwrap();
+        assert_eq!(inherited.config().max_message_size, Some(4096));
+
+        let own = LinkConfig {
+            max_message_size: Some(512),
+            ..Default::default()
+        };
+        let overridden = session.create_receiver(own).await.unwrap();
+        assert_eq!(overridden.config().max_message_size, Some(512));
+    }
+
     #[tokio::test]
     async fn test_session_create_receiver_wrong_state() {
         let mut session = Session::new(1, "test-connection".to_string());
@@ -465,7 +925,7 @@ mod tests {
         let link_config = LinkConfig::default();
         let result = session.create_receiver(link_config).await;
         assert!(result.is_err());
-        assert!(matches!(result.unwrap_err(), AmqpError::InvalidState { .. }));
+        assert!(matches!(result.unwrap_err().inner(), AmqpError::InvalidState { .. }));
     }
 
     #[test]
@@ -597,7 +1057,72 @@ mod tests {
         
         // Verify all links were created
         assert_eq!(session.link_count(), 4);
-        assert_eq!(session.next_handle(), 4);
+        assert_eq!(session.next_handle(), Some(4));
+    }
+
+    #[tokio::test]
+    async fn test_senders_share_session_delivery_ids() {
+        let mut session = Session::new(1, "test-connection".to_string());
+        session.begin().await.unwrap();
+        let mut first = session.create_sender(LinkConfig::default()).await.unwrap();
+        let mut second = session.create_sender(LinkConfig::default()).await.unwrap();
+        for sender in [&mut first, &mut second] {
+            sender.attach().await.unwrap();
+            sender.add_credit(2);
+        }
+
+        // Dispositions name deliveries by ID alone, so no two senders may reuse one
+        let ids = [
+            first.send(Message::text("first")).await.unwrap().delivery_id(),
+            second.send(Message::text("second")).await.unwrap().delivery_id(),
+            first.send(Message::text("third")).await.unwrap().delivery_id(),
+        ];
+        assert_eq!(ids, [0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_transfer_detaches_only_its_link() {
+        let mut session = Session::new(1, "test-connection".to_string());
+        session.state = SessionState::Active;
+
+        let config = LinkConfig { source: Some("orders".to_string()), ..Default::default() };
+        let mut faulty = session.create_receiver(config.clone()).await.unwrap();
+        let mut healthy = session.create_receiver(config).await.unwrap();
+        let (local, remote) = Endpoint::pair();
+        faulty.set_endpoint(local);
+        let peer = tokio::spawn(async move {
+            if let Some(Performative::Attach(attach)) = remote.recv().await {
+                remote.send(Performative::Attach(Attach { role: Role::Sender, initial_delivery_count: Some(0), ..attach })).unwrap();
+            }
+            remote.recv().await
+        });
+        faulty.attach().await.unwrap();
+        healthy.attach().await.unwrap();
+
+        let mut encoder = crate::codec::Encoder::new();
+        let message = Message::text("ok").with_message_id("msg-1");
+        let message = Message { header: Some(crate::message::Header::new()), ..message };
+        encoder.encode_message(&message).unwrap();
+        let payload = encoder.finish();
+        assert!(faulty.receive_transfer_payload(&payload).is_ok());
+
+        let error = faulty.receive_transfer_payload(&[0xff, 0x00]).unwrap_err();
+        assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorDecodeError));
+        assert_eq!(faulty.state(), &crate::link::LinkState::Detached);
+        match peer.await.unwrap() {
+            Some(Performative::Detach(detach)) => {
+                assert_eq!(detach.handle, faulty.handle());
+                assert_eq!(detach.error.unwrap().condition, AmqpCondition::AmqpErrorDecodeError);
+            }
+            other => panic!("Expected detach, got {:?}", other),
+        }
+
+        // The session and the sibling link carry on
+        assert_eq!(session.state(), &SessionState::Active);
+        let delivery_id = healthy.receive_transfer_payload(&payload).unwrap();
+        let (received_id, message) = healthy.receive_delivery().await.unwrap().unwrap();
+        assert_eq!((received_id, message.body_as_text()), (delivery_id, Some("ok")));
+        assert!(session.create_sender(LinkConfig::default()).await.is_ok());
     }
 
     // Test session error state
@@ -622,4 +1147,340 @@ mod tests {
         assert_eq!(config.properties.get("custom_key"), Some(&AmqpValue::String("custom_value".to_string())));
         assert_eq!(config.properties.get("numeric_key"), Some(&AmqpValue::Int(123)));
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_session_handle_max_default() {
+        let session = Session::new(1, "test-connection".to_string());
+        assert_eq!(session.handle_max(), u32::MAX);
+        assert_eq!(session.remote_handle_max(), None);
+        assert_eq!(session.negotiated_handle_max(), u32::MAX);
+    }
+
+    #[test]
+    fn test_session_begin_performative_handle_max() {
+        let session = SessionBuilder::new()
+            .handle_max(15)
+            .build(1, "test-connection".to_string());
+
+        let begin = session.begin_performative();
+        assert_eq!(begin.handle_max, 15);
+        assert_eq!(begin.incoming_window, 100);
+        assert_eq!(begin.outgoing_window, 100);
+
+        let decoded = Begin::decode(&begin.encode().unwrap()).unwrap();
+        assert_eq!(decoded.handle_max, 15);
+    }
+
+    #[test]
+    fn test_session_negotiated_handle_max() {
+        let mut session = SessionBuilder::new()
+            .handle_max(15)
+            .build(1, "test-connection".to_string());
+
+        session.on_remote_begin(&Begin {
+            remote_channel: Some(1),
+            handle_max: 3,
+            ..Default::default()
+        });
+
+        assert_eq!(session.remote_handle_max(), Some(3));
+        assert_eq!(session.negotiated_handle_max(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_session_handle_exhaustion() {
+        let mut session = Session::new(1, "test-connection".to_string());
+        session.state = SessionState::Active;
+        session.on_remote_begin(&Begin {
+            handle_max: 1,
+            ..Default::default()
+        });
+
+        let _sender = session.create_sender(LinkConfig::default()).await.unwrap();
+        let _receiver = session.create_receiver(LinkConfig::default()).await.unwrap();
+
+        let result = session.create_sender(LinkConfig::default()).await;
+        match result {
+            Err(error) => assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorResourceLimitExceeded)),
+            Ok(_) => panic!("Expected handle exhaustion"),
+        }
+        assert_eq!(session.link_count(), 2);
+        assert_eq!(session.next_handle(), None);
+    }
+
+    #[tokio::test]
+    async fn test_session_reuses_handles_of_detached_and_dropped_links() {
+        let mut session = Session::new(1, "test-connection".to_string());
+        session.state = SessionState::Active;
+        session.on_remote_begin(&Begin {
+            handle_max: 1,
+            ..Default::default()
+        });
+
+        // Far more links than handles, each detached or dropped in turn
+        for _ in 0..10 {
+            let mut sender = session.create_sender(LinkConfig::default()).await.unwrap();
+            let receiver = session.create_receiver(LinkConfig::default()).await.unwrap();
+            assert_eq!((sender.handle(), receiver.handle()), (0, 1));
+            sender.attach().await.unwrap();
+            sender.detach().await.unwrap();
+            assert_eq!(session.next_handle(), Some(0));
+            drop(receiver);
+            assert_eq!(session.next_handle(), Some(0));
+        }
+
+        // The lowest free handle is handed out, and a re-attaching link takes
+        // another one if its own was reused meanwhile
+        let mut first = session.create_sender(LinkConfig::default()).await.unwrap();
+        let _second = session.create_sender(LinkConfig::default()).await.unwrap();
+        first.attach().await.unwrap();
+        first.detach().await.unwrap();
+        let third = session.create_receiver(LinkConfig::default()).await.unwrap();
+        assert_eq!(third.handle(), 0);
+        match first.attach().await {
+            Err(error) => assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorResourceLimitExceeded)),
+            Ok(_) => panic!("Expected handle exhaustion"),
+        }
+        drop(third);
+        first.attach().await.unwrap();
+        assert_eq!(first.handle(), 0);
+    }
+
+    #[test]
+    fn test_handles_lowest_free() {
+        let handles = Handles::default();
+        handles.lock().in_use.extend([0, 1, 3, u32::MAX]);
+
+        assert_eq!(handles.lowest_free(u32::MAX), Some(2));
+        assert_eq!(handles.lowest_free(1), None);
+        assert_eq!(handles.allocate(u32::MAX), Some(2));
+        assert_eq!(handles.allocate(u32::MAX), Some(4));
+        handles.release(1);
+        assert_eq!(handles.reclaim(3), Some(1));
+        assert_eq!(handles.reclaim(5), Some(5));
+    }
+
+    #[test]
+    fn test_session_validate_remote_attach() {
+        let session = SessionBuilder::new()
+            .handle_max(4)
+            .build(1, "test-connection".to_string());
+
+        assert!(session.validate_remote_attach(4).is_ok());
+
+        let result = session.validate_remote_attach(5);
+        match result {
+            Err(error) => assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorFramingError)),
+            Ok(_) => panic!("Expected framing error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_begin_awaits_remote_begin() {
+        let (local, remote) = Endpoint::pair();
+        let mut session = SessionBuilder::new().incoming_window(50).build(3, "conn-1".to_string());
+        session.set_endpoint(local);
+
+        let peer = tokio::spawn(async move {
+            let begin = match remote.recv().await {
+                Some(Performative::Begin(begin)) => begin,
+                other => panic!("Expected begin, got {:?}", other),
+            };
+            assert_eq!(begin.incoming_window, 50);
+            remote
+                .send(Performative::Begin(Begin {
+                    remote_channel: Some(3),
+                    next_outgoing_id: 42,
+                    incoming_window: 1000,
+                    outgoing_window: 2000,
+                    handle_max: 63,
+                }))
+                .unwrap();
+        });
+
+        session.begin().await.unwrap();
+        peer.await.unwrap();
+
+        assert_eq!(session.state(), &SessionState::Active);
+        assert_eq!(session.remote_incoming_window(), Some(1000));
+        assert_eq!(session.remote_outgoing_window(), Some(2000));
+        assert_eq!(session.next_incoming_id(), Some(42));
+        assert_eq!(session.remote_handle_max(), Some(63));
+    }
+
+    #[tokio::test]
+    async fn test_session_begin_timeout() {
+        let (local, _remote) = Endpoint::pair();
+        let mut session = SessionBuilder::new()
+            .begin_timeout(Duration::from_millis(50))
+            .build(1, "conn-1".to_string());
+        session.set_endpoint(local);
+
+        let result = session.begin().await.map_err(AmqpError::into_inner);
+        assert!(matches!(result, Err(AmqpError::Timeout(_))));
+        assert_eq!(session.state(), &SessionState::Ended);
+    }
+
+    #[tokio::test]
+    async fn test_session_begin_rejects_wrong_remote_channel() {
+        let (local, remote) = Endpoint::pair();
+        let mut session = SessionBuilder::new().build(1, "conn-1".to_string());
+        session.set_endpoint(local);
+
+        remote
+            .send(Performative::Begin(Begin {
+                remote_channel: Some(7),
+                ..Default::default()
+            }))
+            .unwrap();
+
+        let result = session.begin().await.map_err(AmqpError::into_inner);
+        assert!(matches!(result, Err(AmqpError::Protocol(_))));
+        assert!(session.remote_incoming_window().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_session_begin_without_endpoint_has_no_remote_windows() {
+        let mut session = SessionBuilder::new().build(1, "conn-1".to_string());
+        session.begin().await.unwrap();
+
+        assert_eq!(session.state(), &SessionState::Active);
+        assert!(session.remote_incoming_window().is_none());
+        assert!(session.next_incoming_id().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_session_settle_range() {
+        let (local, remote) = Endpoint::pair();
+        let mut session = SessionBuilder::new().build(1, "conn-1".to_string());
+        session.set_endpoint(local);
+        remote.send(Performative::Begin(Begin { remote_channel: Some(1), ..Default::default() })).unwrap();
+        session.begin().await.unwrap();
+        assert!(matches!(remote.recv().await, Some(Performative::Begin(_))));
+
+        session.settle_range(100, 149, Outcome::Accepted).unwrap();
+        match remote.recv().await {
+            Some(Performative::Disposition(disposition)) => {
+                assert_eq!(disposition.role, Role::Receiver);
+                assert_eq!((disposition.first, disposition.last), (100, Some(149)));
+                assert!(disposition.settled);
+                assert_eq!(disposition.state, Some(Outcome::Accepted));
+            }
+            other => panic!("Expected disposition, got {:?}", other),
+        }
+
+        session.settle_range(7, 7, Outcome::Released).unwrap();
+        assert!(matches!(
+            remote.recv().await,
+            Some(Performative::Disposition(Disposition { first: 7, last: None, .. }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_link_errors_name_connection_channel_link_and_delivery() {
+        let mut session = SessionBuilder::new().build(3, "conn-1".to_string());
+        session.begin().await.unwrap();
+        let mut receiver = session
+            .create_receiver(LinkConfig { name: "orders-in".to_string(), ..LinkConfig::default() })
+            .await
+            .unwrap();
+
+        let error = receiver.accept(4).unwrap_err();
+        assert_eq!(error.connection_id(), Some("conn-1"));
+        assert_eq!(error.channel(), Some(3));
+        assert_eq!(error.link_name(), Some("orders-in"));
+        assert_eq!(error.delivery_id(), Some(4));
+        assert!(matches!(error.inner(), AmqpError::InvalidState(_)));
+
+        session.end().await.unwrap();
+        let error = session.create_sender(LinkConfig::default()).await.unwrap_err();
+        assert_eq!(error.channel(), Some(3));
+        assert_eq!(error.link_name(), None);
+    }
+
+    #[tokio::test]
+    async fn test_session_settle_range_invalid() {
+        let mut session = SessionBuilder::new().build(1, "conn-1".to_string());
+        assert!(matches!(
+            session.settle_range(0, 1, Outcome::Accepted).map_err(AmqpError::into_inner),
+            Err(AmqpError::InvalidState(_))
+        ));
+
+        session.begin().await.unwrap();
+        assert!(matches!(
+            session.settle_range(5, 4, Outcome::Accepted).map_err(AmqpError::into_inner),
+            Err(AmqpError::Session(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_session_queues_end() {
+        let (local, remote) = Endpoint::pair();
+        let mut session = SessionBuilder::new().build(1, "conn-1".to_string());
+        session.set_endpoint(local);
+        remote.send(Performative::Begin(Begin { remote_channel: Some(1), ..Default::default() })).unwrap();
+        session.begin().await.unwrap();
+        assert!(matches!(remote.recv().await, Some(Performative::Begin(_))));
+
+        drop(session);
+        assert_eq!(remote.recv().await, Some(Performative::End(End::default())));
+        assert!(remote.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_session_close() {
+        let mut session = SessionBuilder::new().build(1, "conn-1".to_string());
+        session.begin().await.unwrap();
+        session.create_sender(crate::link::LinkConfig::default()).await.unwrap();
+        assert!(session.close().await.is_ok());
+
+        let session = SessionBuilder::new().build(2, "conn-1".to_string());
+        assert!(session.close().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_session_end_fails_pending_link_operations() {
+        let mut session = SessionBuilder::new().build(1, "conn-1".to_string());
+        session.begin().await.unwrap();
+        let mut sender = session.create_sender(crate::link::LinkConfig::default()).await.unwrap();
+        let (local, _remote) = Endpoint::pair();
+        sender.set_endpoint(local);
+
+        // The peer never answers the attach, so only the end can release it
+        let (attached, ended) = tokio::join!(sender.attach(), session.end());
+        assert!(matches!(attached.map_err(AmqpError::into_inner), Err(AmqpError::InvalidState(_))));
+        assert!(ended.is_ok());
+        assert!(matches!(sender.attach().await.map_err(AmqpError::into_inner), Err(AmqpError::InvalidState(_))));
+    }
+
+    #[tokio::test]
+    async fn test_session_end_awaits_remote_end() {
+        let (local, remote) = Endpoint::pair();
+        let mut session = SessionBuilder::new().build(1, "conn-1".to_string());
+        session.set_endpoint(local);
+        remote.send(Performative::Begin(Begin { remote_channel: Some(1), ..Default::default() })).unwrap();
+        session.begin().await.unwrap();
+        assert!(matches!(remote.recv().await, Some(Performative::Begin(_))));
+
+        remote.send(Performative::End(End::default())).unwrap();
+        session.end().await.unwrap();
+        assert_eq!(remote.recv().await, Some(Performative::End(End::default())));
+        assert_eq!(session.state(), &SessionState::Ended);
+    }
+
+    #[tokio::test]
+    async fn test_generated_link_names_come_from_session_namer() {
+        let mut session = Session::new(0, "test-connection".to_string());
+        session.set_namer(Namer::scoped("1a2b3c4d"));
+        session.begin().await.unwrap();
+
+        let sender = session.create_sender(LinkConfig { target: Some("orders".to_string()), ..Default::default() }).await.unwrap();
+        let receiver = session.create_receiver(LinkConfig::default()).await.unwrap();
+        let named = LinkConfig { name: "audit-feed".to_string(), ..Default::default() };
+        let own = session.create_receiver(named).await.unwrap();
+
+        assert_eq!(sender.name(), "orders-sender-1-1a2b3c4d");
+        assert_eq!(receiver.name(), "receiver-2-1a2b3c4d");
+        assert_eq!(own.name(), "audit-feed");
+    }
+}
@@ -0,0 +1,550 @@
+//! AMQP 1.0 Client-Side Spooling
+//!
+//! This module lets a sender keep accepting messages while its connection is
+//! down. A sender built with `LinkBuilder::spool` puts each message it cannot
+//! send into a [`Spool`] instead of failing, and sends the spooled messages
+//! in order, ahead of anything newer, once it is attached again and has
+//! credit. Each message keeps the delivery ID it was given when spooled.
+//!
+//! The spool is bounded by message count and bytes. When full, the
+//! [`SpoolOverflow`] policy either refuses the new message or evicts the
+//! oldest one. Messages live in memory ([`MemorySpool`]) or, with the
+//! `serde` feature, in an append-only file (`FileSpool`) that survives a
+//! restart of the process.
+//! The depth of the spool is published through [`Spool::subscribe`].
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::link::LinkBuilder;
+//! use dumq_amqp::message::Message;
+//! use dumq_amqp::spool::{Spool, SpoolConfig};
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let spool = Spool::memory(SpoolConfig::default());
+//! let mut sender = LinkBuilder::new()
+//!     .target("orders")
+//!     .spool(spool.clone())
+//!     .build_sender("session-1".to_string());
+//!
+//! // Not attached yet: the message waits in the spool
+//! let delivery_id = sender.send(Message::text("order")).await?.delivery_id();
+//! assert_eq!(spool.stats().depth, 1);
+//!
+//! sender.attach().await?;
+//! sender.add_credit(10);
+//! assert_eq!(sender.flush_spool().await?, 1);
+//! assert!(sender.receipt(delivery_id).is_some());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::logging;
+use crate::{AmqpCondition, AmqpError, AmqpResult, Message};
+use std::collections::VecDeque;
+use std::fmt::Debug;
+#[cfg(feature = "serde")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "serde")]
+use std::io::{Read, Seek, SeekFrom, Write};
+#[cfg(feature = "serde")]
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+/// What a full spool does with another message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpoolOverflow {
+    /// Refuse the new message; the send fails with `amqp:resource:limit-exceeded`
+    #[default]
+    RejectNew,
+    /// Evict the oldest spooled messages to make room
+    DropOldest,
+}
+
+/// Spool limits and overflow policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpoolConfig {
+    /// Maximum number of spooled messages
+    pub max_messages: usize,
+    /// Maximum encoded bytes of spooled messages
+    pub max_bytes: usize,
+    /// What to do when a message does not fit
+    pub overflow: SpoolOverflow,
+}
+
+impl Default for SpoolConfig {
+    fn default() -> Self {
+        SpoolConfig {
+            max_messages: 10_000,
+            max_bytes: 16 * 1024 * 1024,
+            overflow: SpoolOverflow::RejectNew,
+        }
+    }
+}
+
+/// A message waiting in a spool
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpooledMessage {
+    /// Delivery ID the message was given when spooled
+    pub delivery_id: u32,
+    /// The message
+    pub message: Message,
+}
+
+/// Spool depth and activity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SpoolStats {
+    /// Messages currently spooled
+    pub depth: usize,
+    /// Encoded bytes currently spooled
+    pub bytes: usize,
+    /// Messages sent from the spool so far
+    pub flushed: u64,
+    /// Messages evicted by [`SpoolOverflow::DropOldest`] so far
+    pub evicted: u64,
+    /// Messages refused by [`SpoolOverflow::RejectNew`] so far
+    pub rejected: u64,
+}
+
+/// Storage for spooled messages, oldest first
+pub trait SpoolStore: Debug + Send {
+    /// Append a message
+    fn push(&mut self, spooled: SpooledMessage) -> AmqpResult<()>;
+
+    /// Get the oldest message without removing it
+    fn front(&mut self) -> AmqpResult<Option<SpooledMessage>>;
+
+    /// Remove the oldest message, returning its encoded size
+    fn pop_front(&mut self) -> AmqpResult<Option<usize>>;
+
+    /// Get the number of messages stored
+    fn len(&self) -> usize;
+
+    /// Check if no messages are stored
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the encoded bytes of the messages stored
+    fn bytes(&self) -> usize;
+}
+
+/// Spool kept in memory
+#[derive(Debug, Default)]
+pub struct MemorySpool {
+    messages: VecDeque<SpooledMessage>,
+    bytes: usize,
+}
+
+impl MemorySpool {
+    /// Create an empty spool
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SpoolStore for MemorySpool {
+    fn push(&mut self, spooled: SpooledMessage) -> AmqpResult<()> {
+        self.bytes += spooled.message.encoded_size();
+        self.messages.push_back(spooled);
+        Ok(())
+    }
+
+    fn front(&mut self) -> AmqpResult<Option<SpooledMessage>> {
+        Ok(self.messages.front().cloned())
+    }
+
+    fn pop_front(&mut self) -> AmqpResult<Option<usize>> {
+        Ok(self.messages.pop_front().map(|spooled| {
+            let size = spooled.message.encoded_size();
+            self.bytes -= size;
+            size
+        }))
+    }
+
+    fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+/// Location of a record in a [`FileSpool`]
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Copy)]
+struct Record {
+    delivery_id: u32,
+    offset: u64,
+    len: u32,
+    size: usize,
+}
+
+/// Spool kept in an append-only file
+///
+/// Each record is the delivery ID and the length of the message, both
+/// big-endian `u32`, followed by the message as JSON. The file is truncated
+/// whenever the spool empties. Opening an existing file picks up the
+/// messages left in it, except those already sent before a restart: the
+/// position of the oldest unsent record is kept in the first 8 bytes.
+///
+/// Requires the `serde` feature.
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub struct FileSpool {
+    path: PathBuf,
+    file: File,
+    records: VecDeque<Record>,
+    bytes: usize,
+}
+
+/// Bytes before the first record, holding the offset of the oldest unsent one
+#[cfg(feature = "serde")]
+const FILE_HEADER_LEN: u64 = 8;
+
+#[cfg(feature = "serde")]
+impl FileSpool {
+    /// Open or create a spool file
+    pub fn open(path: impl AsRef<Path>) -> AmqpResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        let mut spool = FileSpool {
+            path,
+            file,
+            records: VecDeque::new(),
+            bytes: 0,
+        };
+        if (data.len() as u64) < FILE_HEADER_LEN {
+            spool.reset()?;
+            return Ok(spool);
+        }
+
+        let mut offset = u64::from_be_bytes(data[..8].try_into().expect("8-byte header")) as usize;
+        while offset + 8 <= data.len() {
+            let delivery_id = u32::from_be_bytes(data[offset..offset + 4].try_into().expect("4 bytes"));
+            let len = u32::from_be_bytes(data[offset + 4..offset + 8].try_into().expect("4 bytes"));
+            let start = offset + 8;
+            let Some(payload) = data.get(start..start + len as usize) else {
+                // A record cut short by a crash mid-write is dropped
+                logging::warn!("Dropping truncated record at offset {} in spool {}", offset, spool.path.display());
+                break;
+            };
+            let size = serde_json::from_slice::<Message>(payload)?.encoded_size();
+            spool.records.push_back(Record {
+                delivery_id,
+                offset: start as u64,
+                len,
+                size,
+            });
+            spool.bytes += size;
+            offset = start + len as usize;
+        }
+        if spool.records.is_empty() {
+            spool.reset()?;
+        }
+        Ok(spool)
+    }
+
+    /// Get the path of the spool file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn reset(&mut self) -> AmqpResult<()> {
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&FILE_HEADER_LEN.to_be_bytes())?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl SpoolStore for FileSpool {
+    fn push(&mut self, spooled: SpooledMessage) -> AmqpResult<()> {
+        let payload = serde_json::to_vec(&spooled.message)?;
+        let len = u32::try_from(payload.len())
+            .map_err(|_| AmqpError::encoding(format!("Message of {} bytes is too large to spool", payload.len())))?;
+
+        let offset = self.file.seek(SeekFrom::End(0))?;
+        let mut record = Vec::with_capacity(payload.len() + 8);
+        record.extend_from_slice(&spooled.delivery_id.to_be_bytes());
+        record.extend_from_slice(&len.to_be_bytes());
+        record.extend_from_slice(&payload);
+        self.file.write_all(&record)?;
+        self.file.flush()?;
+
+        let size = spooled.message.encoded_size();
+        self.records.push_back(Record {
+            delivery_id: spooled.delivery_id,
+            offset: offset + 8,
+            len,
+            size,
+        });
+        self.bytes += size;
+        Ok(())
+    }
+
+    fn front(&mut self) -> AmqpResult<Option<SpooledMessage>> {
+        let Some(record) = self.records.front().copied() else {
+            return Ok(None);
+        };
+        let mut payload = vec![0; record.len as usize];
+        self.file.seek(SeekFrom::Start(record.offset))?;
+        self.file.read_exact(&mut payload)?;
+        Ok(Some(SpooledMessage {
+            delivery_id: record.delivery_id,
+            message: serde_json::from_slice(&payload)?,
+        }))
+    }
+
+    fn pop_front(&mut self) -> AmqpResult<Option<usize>> {
+        let Some(record) = self.records.pop_front() else {
+            return Ok(None);
+        };
+        self.bytes -= record.size;
+        match self.records.front() {
+            Some(next) => {
+                self.file.seek(SeekFrom::Start(0))?;
+                self.file.write_all(&(next.offset - 8).to_be_bytes())?;
+                self.file.flush()?;
+            }
+            None => self.reset()?,
+        }
+        Ok(Some(record.size))
+    }
+
+    fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+#[derive(Debug)]
+struct SpoolInner {
+    config: SpoolConfig,
+    store: Mutex<Box<dyn SpoolStore>>,
+    stats: watch::Sender<SpoolStats>,
+}
+
+/// Bounded spool shared by the clones of a sender
+#[derive(Debug, Clone)]
+pub struct Spool {
+    inner: Arc<SpoolInner>,
+}
+
+impl Spool {
+    /// Create a spool over any store
+    pub fn new(config: SpoolConfig, store: impl SpoolStore + 'static) -> Self {
+        let stats = SpoolStats {
+            depth: store.len(),
+            bytes: store.bytes(),
+            ..Default::default()
+        };
+        Spool {
+            inner: Arc::new(SpoolInner {
+                config,
+                store: Mutex::new(Box::new(store)),
+                stats: watch::channel(stats).0,
+            }),
+        }
+    }
+
+    /// Create a spool kept in memory
+    pub fn memory(config: SpoolConfig) -> Self {
+        Self::new(config, MemorySpool::new())
+    }
+
+    /// Create a spool kept in a file, picking up messages already in it
+    #[cfg(feature = "serde")]
+    pub fn file(config: SpoolConfig, path: impl AsRef<Path>) -> AmqpResult<Self> {
+        Ok(Self::new(config, FileSpool::open(path)?))
+    }
+
+    /// Get the spool configuration
+    pub fn config(&self) -> &SpoolConfig {
+        &self.inner.config
+    }
+
+    /// Get the current depth and counters
+    pub fn stats(&self) -> SpoolStats {
+        *self.inner.stats.borrow()
+    }
+
+    /// Watch the depth and counters as they change
+    pub fn subscribe(&self) -> watch::Receiver<SpoolStats> {
+        self.inner.stats.subscribe()
+    }
+
+    /// Check if no messages are spooled
+    pub fn is_empty(&self) -> bool {
+        self.stats().depth == 0
+    }
+
+    /// Append a message, applying the overflow policy if the spool is full
+    pub fn push(&self, delivery_id: u32, message: Message) -> AmqpResult<()> {
+        let config = &self.inner.config;
+        let size = message.encoded_size();
+        let mut store = self.store();
+        if size > config.max_bytes {
+            return self.refuse(size);
+        }
+        let full = |store: &dyn SpoolStore| {
+            store.len() >= config.max_messages || store.bytes() + size > config.max_bytes
+        };
+        let mut evicted = 0;
+        while full(store.as_ref()) {
+            if config.overflow == SpoolOverflow::RejectNew || store.is_empty() {
+                drop(store);
+                return self.refuse(size);
+            }
+            store.pop_front()?;
+            evicted += 1;
+        }
+        if evicted > 0 {
+            logging::warn!("Spool full, evicted {} oldest message(s)", evicted);
+        }
+        store.push(SpooledMessage { delivery_id, message })?;
+        let (depth, bytes) = (store.len(), store.bytes());
+        self.inner.stats.send_modify(|stats| {
+            stats.depth = depth;
+            stats.bytes = bytes;
+            stats.evicted += evicted;
+        });
+        Ok(())
+    }
+
+    /// Get the oldest spooled message without removing it
+    pub fn front(&self) -> AmqpResult<Option<SpooledMessage>> {
+        self.store().front()
+    }
+
+    /// Remove the oldest spooled message once it has been sent
+    pub(crate) fn mark_flushed(&self) -> AmqpResult<()> {
+        let mut store = self.store();
+        if store.pop_front()?.is_some() {
+            let (depth, bytes) = (store.len(), store.bytes());
+            self.inner.stats.send_modify(|stats| {
+                stats.depth = depth;
+                stats.bytes = bytes;
+                stats.flushed += 1;
+            });
+        }
+        Ok(())
+    }
+
+    fn refuse(&self, size: usize) -> AmqpResult<()> {
+        self.inner.stats.send_modify(|stats| stats.rejected += 1);
+        Err(AmqpError::amqp_protocol(
+            AmqpCondition::AmqpErrorResourceLimitExceeded,
+            format!("Spool is full, refusing a message of {} bytes", size),
+        ))
+    }
+
+    fn store(&self) -> std::sync::MutexGuard<'_, Box<dyn SpoolStore>> {
+        self.inner.store.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::link::LinkBuilder;
+
+    fn config(max_messages: usize, overflow: SpoolOverflow) -> SpoolConfig {
+        SpoolConfig {
+            max_messages,
+            overflow,
+            ..Default::default()
+        }
+    }
+
+    fn texts(spool: &Spool) -> Vec<(u32, String)> {
+        let mut texts = Vec::new();
+        while let Some(spooled) = spool.front().unwrap() {
+            texts.push((spooled.delivery_id, spooled.message.body_as_text().unwrap().to_string()));
+            spool.mark_flushed().unwrap();
+        }
+        texts
+    }
+
+    #[test]
+    fn test_overflow_policies() {
+        let spool = Spool::memory(config(2, SpoolOverflow::RejectNew));
+        spool.push(1, Message::text("a")).unwrap();
+        spool.push(2, Message::text("b")).unwrap();
+        let error = spool.push(3, Message::text("c")).unwrap_err();
+        assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorResourceLimitExceeded));
+        assert_eq!(spool.stats().rejected, 1);
+
+        let spool = Spool::memory(config(2, SpoolOverflow::DropOldest));
+        let mut depth = spool.subscribe();
+        for (id, text) in [(1, "a"), (2, "b"), (3, "c")] {
+            spool.push(id, Message::text(text)).unwrap();
+        }
+        assert!(depth.has_changed().unwrap());
+        assert_eq!(depth.borrow_and_update().depth, 2);
+        assert_eq!(spool.stats().evicted, 1);
+        assert_eq!(texts(&spool), [(2, "b".to_string()), (3, "c".to_string())]);
+        assert_eq!(spool.stats(), SpoolStats { flushed: 2, evicted: 1, ..Default::default() });
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_file_spool_survives_reopen() {
+        let path = std::env::temp_dir().join(format!("dumq-spool-{}.bin", crate::ids::next_id()));
+        let spool = Spool::file(SpoolConfig::default(), &path).unwrap();
+        for (id, text) in [(7, "a"), (8, "b"), (9, "c")] {
+            spool.push(id, Message::text(text)).unwrap();
+        }
+        spool.mark_flushed().unwrap();
+        drop(spool);
+
+        let spool = Spool::file(SpoolConfig::default(), &path).unwrap();
+        assert_eq!(spool.stats().depth, 2);
+        assert_eq!(texts(&spool), [(8, "b".to_string()), (9, "c".to_string())]);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), FILE_HEADER_LEN);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sender_spools_until_attached() {
+        let spool = Spool::memory(SpoolConfig::default());
+        let mut sender = LinkBuilder::new()
+            .target("orders")
+            .spool(spool.clone())
+            .build_sender("session-1".to_string());
+
+        let first = sender.send(Message::text("first")).await.unwrap().delivery_id();
+        let second = sender.send(Message::text("second")).await.unwrap().delivery_id();
+        assert_eq!((first, second), (1, 2));
+        assert_eq!(spool.stats().depth, 2);
+
+        sender.attach().await.unwrap();
+        sender.add_credit(1);
+        // Older messages go first, so the new one waits behind "second"
+        let third = sender.send(Message::text("third")).await.unwrap().delivery_id();
+        assert_eq!(third, 3);
+        assert!(sender.receipt(first).is_some());
+        assert!(sender.receipt(second).is_none());
+        assert_eq!(spool.stats().depth, 2);
+
+        sender.add_credit(5);
+        assert_eq!(sender.flush_spool().await.unwrap(), 2);
+        assert_eq!(sender.settle(third).unwrap().body_as_text(), Some("third"));
+        assert_eq!(spool.stats().flushed, 3);
+
+        // Nothing spooled: sends go straight out
+        assert_eq!(sender.send(Message::text("fourth")).await.unwrap().delivery_id(), 4);
+        assert!(spool.is_empty());
+    }
+}
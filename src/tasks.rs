@@ -0,0 +1,226 @@
+//! AMQP 1.0 Background Tasks
+//!
+//! This module names and tracks the tasks the crate spawns on its own, such
+//! as connection keep-alives and the chaos proxy. Every task gets a stable
+//! name of the form `dumq-amqp/<kind>/<owner>`, and [`tasks`] returns a
+//! snapshot of those currently running together with their tokio task ids,
+//! so load seen in runtime metrics can be attributed to AMQP internals.
+//!
+//! With the `tokio-console` feature, tokio's `tracing` feature and
+//! `RUSTFLAGS="--cfg tokio_unstable"`, the names are also given to tokio
+//! itself and show up in tokio-console.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::tasks::{self, TaskKind};
+//!
+//! for task in tasks::tasks().iter().filter(|task| task.kind == TaskKind::KeepAlive) {
+//!     println!("{} ({:?}) running for {:?}", task.name, task.id, task.started_at.elapsed());
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+use tokio::task::{AbortHandle, JoinHandle, JoinSet};
+
+/// Role of a background task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TaskKind {
+    /// Drives frames between a connection and its sessions
+    Driver,
+    /// Sends heartbeats and watches the peer's
+    KeepAlive,
+    /// Re-establishes lost connections
+    Reconnector,
+    /// Flushes outstanding work before shutdown
+    Drain,
+    /// Forwards traffic for a proxy
+    Proxy,
+    /// Serves the connections of an embedded broker
+    Broker,
+    /// Runs the handshake of an accepted connection
+    Handshake,
+    /// Supervises other tasks
+    Watchdog,
+}
+
+impl TaskKind {
+    /// Get the kind as used in task names
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskKind::Driver => "driver",
+            TaskKind::KeepAlive => "keepalive",
+            TaskKind::Reconnector => "reconnector",
+            TaskKind::Drain => "drain",
+            TaskKind::Proxy => "proxy",
+            TaskKind::Broker => "broker",
+            TaskKind::Handshake => "handshake",
+            TaskKind::Watchdog => "watchdog",
+        }
+    }
+}
+
+impl fmt::Display for TaskKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A running background task
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskInfo {
+    /// Stable task name, `dumq-amqp/<kind>/<owner>`
+    pub name: String,
+    /// Role of the task
+    pub kind: TaskKind,
+    /// Tokio task id
+    pub id: Option<tokio::task::Id>,
+    /// Time the task was first polled
+    pub started_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    next_token: AtomicU64,
+    running: Mutex<HashMap<u64, TaskInfo>>,
+}
+
+impl Registry {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, TaskInfo>> {
+        self.running.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Registry::default)
+}
+
+/// Get a snapshot of the running background tasks, oldest first
+pub fn tasks() -> Vec<TaskInfo> {
+    let mut tasks: Vec<TaskInfo> = registry().lock().values().cloned().collect();
+    tasks.sort_by_key(|task| task.started_at);
+    tasks
+}
+
+/// Build the name of a task
+pub fn task_name(kind: TaskKind, owner: &str) -> String {
+    format!("dumq-amqp/{}/{}", kind, owner)
+}
+
+/// Registry entry removed when the task finishes or is aborted
+struct Registration(u64);
+
+impl Registration {
+    fn new(name: String, kind: TaskKind) -> Self {
+        let registry = registry();
+        let token = registry.next_token.fetch_add(1, Ordering::Relaxed);
+        let info = TaskInfo {
+            name,
+            kind,
+            id: tokio::task::try_id(),
+            started_at: Instant::now(),
+        };
+        registry.lock().insert(token, info);
+        Registration(token)
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        registry().lock().remove(&self.0);
+    }
+}
+
+async fn tracked<F: Future>(name: String, kind: TaskKind, future: F) -> F::Output {
+    let _registration = Registration::new(name, kind);
+    future.await
+}
+
+/// Spawn a named, tracked task
+pub(crate) fn spawn<F>(kind: TaskKind, owner: &str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let name = task_name(kind, owner);
+
+    #[cfg(all(tokio_unstable, feature = "tokio-console"))]
+    {
+        tokio::task::Builder::new()
+            .name(&name)
+            .spawn(tracked(name.clone(), kind, future))
+            .unwrap_or_else(|e| panic!("Failed to spawn task {}: {}", name, e))
+    }
+
+    #[cfg(not(all(tokio_unstable, feature = "tokio-console")))]
+    {
+        tokio::spawn(tracked(name, kind, future))
+    }
+}
+
+/// Spawn a named, tracked task onto a join set
+pub(crate) fn spawn_in<F>(set: &mut JoinSet<F::Output>, kind: TaskKind, owner: &str, future: F) -> AbortHandle
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let name = task_name(kind, owner);
+
+    #[cfg(all(tokio_unstable, feature = "tokio-console"))]
+    {
+        set.build_task()
+            .name(&name)
+            .spawn(tracked(name.clone(), kind, future))
+            .unwrap_or_else(|e| panic!("Failed to spawn task {}: {}", name, e))
+    }
+
+    #[cfg(not(all(tokio_unstable, feature = "tokio-console")))]
+    {
+        set.spawn(tracked(name, kind, future))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::oneshot;
+
+    fn find(name: &str) -> Option<TaskInfo> {
+        tasks().into_iter().find(|task| task.name == name)
+    }
+
+    #[tokio::test]
+    async fn test_spawned_task_tracked_until_finished() {
+        let (release, released) = oneshot::channel::<()>();
+        let handle = spawn(TaskKind::Drain, "tasks-test-1", async move {
+            let _ = released.await;
+        });
+        tokio::task::yield_now().await;
+
+        let info = find("dumq-amqp/drain/tasks-test-1").unwrap();
+        assert_eq!(info.kind, TaskKind::Drain);
+        assert_eq!(info.id, Some(handle.id()));
+
+        release.send(()).unwrap();
+        handle.await.unwrap();
+        assert!(find("dumq-amqp/drain/tasks-test-1").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_aborted_task_deregistered() {
+        let mut set = JoinSet::new();
+        let abort = spawn_in(&mut set, TaskKind::Proxy, "tasks-test-2", std::future::pending::<()>());
+        tokio::task::yield_now().await;
+        assert!(find("dumq-amqp/proxy/tasks-test-2").is_some());
+
+        abort.abort();
+        assert!(set.join_next().await.unwrap().unwrap_err().is_cancelled());
+        assert!(find("dumq-amqp/proxy/tasks-test-2").is_none());
+    }
+}
@@ -0,0 +1,1180 @@
+//! Test Utilities
+//!
+//! This module provides [`ChaosTransport`], a TCP proxy that sits between a
+//! client and a peer and degrades the byte stream on purpose: it adds latency,
+//! splits writes into small pieces, swaps chunks out of order and drops
+//! connections at random. Pointing a connection at the proxy instead of the
+//! peer exercises the frame parser, keepalive and reconnection logic under
+//! conditions that a local loopback connection never produces.
+//!
+//! A fixed seed makes the injected faults reproducible across runs.
+//!
+//! [`EmbeddedBroker`] is a minimal broker running in the test process, so
+//! integration tests do not need RabbitMQ or another broker installed. It
+//! keeps named queues in memory: a client's sender puts messages on the
+//! queue named by its target, and a client's receiver takes them from the
+//! queue named by its source, as far as its credit allows. Queues are
+//! created on first use. Messages the client releases or modifies, or does
+//! not settle before detaching, go back to the front of their queue. A link
+//! with a dynamic terminus gets a temporary queue, named in the broker's
+//! Attach and deleted when the link ends.
+//!
+//! A receiver whose source carries a selector filter (see
+//! [`selector`](crate::selector)) is only given the messages whose
+//! application properties match it; the others stay queued for other
+//! receivers. A receiver whose source asks for the `copy` distribution mode
+//! browses: it is given copies of the queued messages, each once, and the
+//! messages stay on the queue.
+//!
+//! The broker also coordinates local transactions. Messages a client sends
+//! within a transaction are held until it commits, and dropped if it rolls
+//! back or its coordinator link ends first. Outcomes a receiving client
+//! gives within a transaction take effect at once.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use dumq_amqp::connection::{Connection, ConnectionConfig};
+//! use dumq_amqp::testing::{ChaosConfig, ChaosTransport};
+//! use tokio::time::Duration;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let chaos = ChaosTransport::bind(
+//!     "127.0.0.1:5672".parse()?,
+//!     ChaosConfig {
+//!         latency: Duration::from_millis(5),
+//!         jitter: Duration::from_millis(20),
+//!         max_write_chunk: Some(3),
+//!         disconnect_probability: 0.01,
+//!         seed: Some(42),
+//!         ..Default::default()
+//!     },
+//! )
+//! .await?;
+//!
+//! let mut connection = Connection::new(ConnectionConfig {
+//!     hostname: chaos.local_addr().ip().to_string(),
+//!     port: chaos.local_addr().port(),
+//!     ..Default::default()
+//! });
+//! connection.open().await?;
+//! println!("Disconnects so far: {}", chaos.stats().disconnects);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ```rust,no_run
+//! use dumq_amqp::link::LinkConfig;
+//! use dumq_amqp::message::Message;
+//! use dumq_amqp::testing::EmbeddedBroker;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let broker = EmbeddedBroker::start().await?;
+//! let mut connection = broker.connection().build();
+//! connection.open().await?;
+//! let session = connection.create_session().await?;
+//!
+//! let mut sender = session.create_sender(LinkConfig { target: Some("orders".to_string()), ..Default::default() }).await?;
+//! sender.attach().await?;
+//! sender.wait_for_peer().await?;
+//! let delivery = sender.send(Message::text("order-1")).await?;
+//! sender.outcome(delivery).await?;
+//! assert_eq!(broker.queue_depth("orders"), Some(1));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::connection::ConnectionBuilder;
+use crate::link::{Delivery, DeliveryOutcome, LinkState, Receiver, Sender};
+use crate::logging;
+use crate::message::{Body, Message};
+use crate::performative::{descriptor, Outcome};
+use crate::selector::Selector;
+use crate::server::{AmqpListener, IncomingConnection, IncomingLink, IncomingSession};
+use crate::tasks::{self, TaskKind};
+use crate::types::Descriptor;
+use crate::{types, AmqpCondition, AmqpError, AmqpResult, AmqpValue, DistributionMode, Role};
+use futures::FutureExt;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{watch, Notify};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::Duration;
+
+/// Size of the buffer used to read from either side of the proxy
+const READ_CHUNK_SIZE: usize = 4096;
+
+/// Credit an embedded broker keeps granted to each sending client
+const BROKER_CREDIT_WINDOW: u32 = 100;
+
+/// Faults injected by a [`ChaosTransport`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChaosConfig {
+    /// Delay added before forwarding each chunk
+    pub latency: Duration,
+    /// Upper bound of a random delay added on top of the latency
+    pub jitter: Duration,
+    /// Largest piece a chunk is split into when written, if splitting
+    pub max_write_chunk: Option<usize>,
+    /// Chance of dropping the connection on each chunk read
+    pub disconnect_probability: f64,
+    /// Chance of holding a chunk back and forwarding it after the next one
+    pub reorder_probability: f64,
+    /// Seed for the fault generator; random if unset
+    pub seed: Option<u64>,
+}
+
+impl Default for ChaosConfig {
+    fn default() -> Self {
+        ChaosConfig {
+            latency: Duration::ZERO,
+            jitter: Duration::ZERO,
+            max_write_chunk: None,
+            disconnect_probability: 0.0,
+            reorder_probability: 0.0,
+            seed: None,
+        }
+    }
+}
+
+/// Counters of the traffic and faults seen by a [`ChaosTransport`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ChaosStats {
+    /// Connections accepted
+    pub connections: u64,
+    /// Bytes forwarded in both directions
+    pub bytes_forwarded: u64,
+    /// Connections dropped on purpose
+    pub disconnects: u64,
+    /// Chunks forwarded out of order
+    pub reordered: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    connections: AtomicU64,
+    bytes_forwarded: AtomicU64,
+    disconnects: AtomicU64,
+    reordered: AtomicU64,
+}
+
+/// TCP proxy that injects latency, partial writes, reordering and disconnects
+///
+/// Every accepted connection is forwarded to the upstream address. Dropping
+/// the proxy closes the listener and all forwarded connections.
+#[derive(Debug)]
+pub struct ChaosTransport {
+    local_addr: SocketAddr,
+    counters: Arc<Counters>,
+    kill: watch::Sender<u64>,
+    task: JoinHandle<()>,
+}
+
+impl ChaosTransport {
+    /// Listen on an ephemeral local port and forward to `upstream`
+    pub async fn bind(upstream: SocketAddr, config: ChaosConfig) -> AmqpResult<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .map_err(|e| AmqpError::transport(format!("Failed to bind chaos proxy: {}", e)))?;
+        let local_addr = listener
+            .local_addr()
+            .map_err(|e| AmqpError::transport(format!("Failed to get chaos proxy address: {}", e)))?;
+
+        let counters = Arc::new(Counters::default());
+        let (kill, kill_rx) = watch::channel(0);
+        let owner = format!("chaos-{}", local_addr);
+        let task = tasks::spawn(TaskKind::Proxy, &owner, accept_loop(listener, upstream, config, counters.clone(), kill_rx));
+
+        Ok(ChaosTransport {
+            local_addr,
+            counters,
+            kill,
+            task,
+        })
+    }
+
+    /// Get the address clients should connect to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Drop every connection currently forwarded, keeping the listener open
+    pub fn disconnect_all(&self) {
+        self.kill.send_modify(|generation| *generation += 1);
+    }
+
+    /// Get traffic and fault counters
+    pub fn stats(&self) -> ChaosStats {
+        ChaosStats {
+            connections: self.counters.connections.load(Ordering::Relaxed),
+            bytes_forwarded: self.counters.bytes_forwarded.load(Ordering::Relaxed),
+            disconnects: self.counters.disconnects.load(Ordering::Relaxed),
+            reordered: self.counters.reordered.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Drop for ChaosTransport {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn accept_loop(
+    listener: TcpListener,
+    upstream: SocketAddr,
+    config: ChaosConfig,
+    counters: Arc<Counters>,
+    kill: watch::Receiver<u64>,
+) {
+    let local_addr = listener.local_addr().map_or_else(|_| "unbound".to_string(), |addr| addr.to_string());
+    // Owned here so that aborting the loop also aborts every connection
+    let mut connections = JoinSet::new();
+    let mut seeds = match config.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+
+    loop {
+        let client = match listener.accept().await {
+            Ok((client, _)) => client,
+            Err(e) => {
+                logging::warn!("Chaos proxy failed to accept: {}", e);
+                continue;
+            }
+        };
+        counters.connections.fetch_add(1, Ordering::Relaxed);
+        let seed = seeds.gen();
+        let owner = format!("chaos-{}-{}", local_addr, counters.connections.load(Ordering::Relaxed));
+        let forwarder = forward(client, upstream, config.clone(), seed, counters.clone(), kill.clone());
+        tasks::spawn_in(&mut connections, TaskKind::Proxy, &owner, forwarder);
+        while connections.try_join_next().is_some() {}
+    }
+}
+
+async fn forward(
+    client: TcpStream,
+    upstream: SocketAddr,
+    config: ChaosConfig,
+    seed: u64,
+    counters: Arc<Counters>,
+    mut kill: watch::Receiver<u64>,
+) {
+    let server = match TcpStream::connect(upstream).await {
+        Ok(server) => server,
+        Err(e) => {
+            logging::warn!("Chaos proxy failed to reach {}: {}", upstream, e);
+            return;
+        }
+    };
+    let _ = client.set_nodelay(true);
+    let _ = server.set_nodelay(true);
+    kill.mark_unchanged();
+
+    let (client_read, client_write) = client.into_split();
+    let (server_read, server_write) = server.into_split();
+    let cut = Arc::new(Notify::new());
+    let outbound = pump(client_read, server_write, config.clone(), StdRng::seed_from_u64(seed), counters.clone(), cut.clone());
+    let inbound = pump(server_read, client_write, config, StdRng::seed_from_u64(!seed), counters.clone(), cut.clone());
+
+    tokio::select! {
+        _ = async { tokio::join!(outbound, inbound) } => {}
+        _ = cut.notified() => {
+            counters.disconnects.fetch_add(1, Ordering::Relaxed);
+        }
+        _ = kill.changed() => {
+            counters.disconnects.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Forward one direction of a connection, injecting faults
+async fn pump(
+    mut reader: OwnedReadHalf,
+    mut writer: OwnedWriteHalf,
+    config: ChaosConfig,
+    mut rng: StdRng,
+    counters: Arc<Counters>,
+    cut: Arc<Notify>,
+) {
+    let mut buffer = vec![0u8; READ_CHUNK_SIZE];
+    let mut held: Option<Vec<u8>> = None;
+
+    loop {
+        let read = match reader.read(&mut buffer).await {
+            Ok(0) | Err(_) => break,
+            Ok(read) => read,
+        };
+        if rng.gen_bool(config.disconnect_probability.clamp(0.0, 1.0)) {
+            cut.notify_one();
+            return;
+        }
+
+        let delay = config.latency + config.jitter.mul_f64(rng.gen::<f64>());
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+
+        let chunk = buffer[..read].to_vec();
+        let chunks = match held.take() {
+            Some(previous) => {
+                counters.reordered.fetch_add(1, Ordering::Relaxed);
+                vec![chunk, previous]
+            }
+            None if rng.gen_bool(config.reorder_probability.clamp(0.0, 1.0)) => {
+                held = Some(chunk);
+                continue;
+            }
+            None => vec![chunk],
+        };
+        for chunk in chunks {
+            if write_split(&mut writer, &chunk, config.max_write_chunk, &mut rng, &counters).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    if let Some(previous) = held {
+        let _ = write_split(&mut writer, &previous, config.max_write_chunk, &mut rng, &counters).await;
+    }
+    let _ = writer.shutdown().await;
+}
+
+/// Write a chunk, split into random pieces no larger than `max_piece`
+async fn write_split(
+    writer: &mut OwnedWriteHalf,
+    chunk: &[u8],
+    max_piece: Option<usize>,
+    rng: &mut StdRng,
+    counters: &Counters,
+) -> std::io::Result<()> {
+    let mut remaining = chunk;
+    while !remaining.is_empty() {
+        let piece = match max_piece {
+            Some(max) => rng.gen_range(1..=max.max(1)).min(remaining.len()),
+            None => remaining.len(),
+        };
+        writer.write_all(&remaining[..piece]).await?;
+        writer.flush().await?;
+        counters.bytes_forwarded.fetch_add(piece as u64, Ordering::Relaxed);
+        remaining = &remaining[piece..];
+        if !remaining.is_empty() {
+            tokio::task::yield_now().await;
+        }
+    }
+    Ok(())
+}
+
+/// Messages held for open transactions by transaction ID, with their queues
+type Held = HashMap<Vec<u8>, Vec<(String, Message)>>;
+
+/// A message on a queue, numbered in the order it was first queued
+#[derive(Debug, Clone)]
+struct Queued {
+    seq: u64,
+    message: Message,
+}
+
+/// Queues of an [`EmbeddedBroker`], shared with its connections
+#[derive(Debug, Default)]
+struct Queues {
+    queues: Mutex<HashMap<String, VecDeque<Queued>>>,
+    /// Woken whenever a message is queued
+    queued: Notify,
+    /// Number given to the last message queued
+    last_seq: AtomicU64,
+    transactions: Mutex<Held>,
+    /// Number of the last transaction declared
+    last_txn: AtomicU64,
+    /// Number of the last queue created for a dynamic terminus
+    last_temporary: AtomicU64,
+}
+
+impl Queues {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, VecDeque<Queued>>> {
+        self.queues.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn push(&self, queue: &str, message: Message) {
+        let seq = self.last_seq.fetch_add(1, Ordering::Relaxed) + 1;
+        self.lock().entry(queue.to_string()).or_default().push_back(Queued { seq, message });
+        self.queued.notify_waiters();
+    }
+
+    /// Put messages back in front of a queue, keeping their order
+    fn requeue(&self, queue: &str, messages: impl DoubleEndedIterator<Item = Queued>) {
+        let mut queues = self.lock();
+        let queue = queues.entry(queue.to_string()).or_default();
+        for message in messages.rev() {
+            queue.push_front(message);
+        }
+        drop(queues);
+        self.queued.notify_waiters();
+    }
+
+    /// Take the first message on a queue matching the selector, if any
+    fn pop(&self, queue: &str, selector: Option<&Selector>) -> Option<Queued> {
+        let mut queues = self.lock();
+        let queue = queues.get_mut(queue)?;
+        let position = match selector {
+            Some(selector) => queue.iter().position(|queued| selector.matches(&queued.message))?,
+            None => 0,
+        };
+        queue.remove(position)
+    }
+
+    /// Copy the first message on a queue matching the selector that is not in `seen`, adding it
+    fn browse(&self, queue: &str, selector: Option<&Selector>, seen: &mut HashSet<u64>) -> Option<Message> {
+        let queues = self.lock();
+        let queued = queues.get(queue)?.iter().find(|queued| {
+            !seen.contains(&queued.seq) && selector.is_none_or(|selector| selector.matches(&queued.message))
+        })?;
+        seen.insert(queued.seq);
+        Some(queued.message.clone())
+    }
+
+    /// Pick the name of a queue for a dynamic terminus
+    fn temporary_name(&self) -> String {
+        format!("temp-queue-{}", self.last_temporary.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn transactions(&self) -> std::sync::MutexGuard<'_, Held> {
+        self.transactions.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Open a transaction, returning its ID
+    fn declare(&self) -> Vec<u8> {
+        let txn_id = (self.last_txn.fetch_add(1, Ordering::Relaxed) + 1).to_be_bytes().to_vec();
+        self.transactions().insert(txn_id.clone(), Vec::new());
+        txn_id
+    }
+
+    /// Hold a message for a queue until its transaction ends, if the transaction is open
+    fn enlist(&self, txn_id: &[u8], queue: &str, message: Message) -> bool {
+        match self.transactions().get_mut(txn_id) {
+            Some(held) => {
+                held.push((queue.to_string(), message));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// End a transaction, queuing its messages unless it failed; false if it is not open
+    fn discharge(&self, txn_id: &[u8], fail: bool) -> bool {
+        let Some(held) = self.transactions().remove(txn_id) else {
+            return false;
+        };
+        if !fail {
+            for (queue, message) in held {
+                self.push(&queue, message);
+            }
+        }
+        true
+    }
+}
+
+/// In-process broker with named queues, for integration tests
+///
+/// Listens on an ephemeral local port without SASL. Dropping the broker
+/// closes the listener and every connection.
+#[derive(Debug)]
+pub struct EmbeddedBroker {
+    local_addr: SocketAddr,
+    queues: Arc<Queues>,
+    task: JoinHandle<()>,
+}
+
+impl EmbeddedBroker {
+    /// Start a broker on an ephemeral local port
+    pub async fn start() -> AmqpResult<Self> {
+        let listener = AmqpListener::bind("127.0.0.1:0").await?.container_id("dumq-embedded-broker");
+        let local_addr = listener.local_addr()?;
+        let queues = Arc::new(Queues::default());
+        let owner = format!("broker-{}", local_addr);
+        let task = tasks::spawn(TaskKind::Broker, &owner, serve_broker(listener, queues.clone()));
+        Ok(EmbeddedBroker { local_addr, queues, task })
+    }
+
+    /// Get the address clients should connect to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Get a connection builder pointed at the broker
+    pub fn connection(&self) -> ConnectionBuilder {
+        ConnectionBuilder::new()
+            .hostname(self.local_addr.ip().to_string())
+            .port(self.local_addr.port())
+    }
+
+    /// Create a queue if it does not exist yet
+    pub fn declare_queue(&self, name: impl Into<String>) {
+        self.queues.lock().entry(name.into()).or_default();
+    }
+
+    /// Get the names of the queues, sorted
+    pub fn queues(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.queues.lock().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Get the number of messages waiting on a queue, if it exists
+    pub fn queue_depth(&self, name: &str) -> Option<usize> {
+        self.queues.lock().get(name).map(VecDeque::len)
+    }
+
+    /// Put a message on a queue, creating it if needed
+    pub fn publish(&self, queue: &str, message: Message) {
+        self.queues.push(queue, message);
+    }
+
+    /// Get the number of transactions declared and not yet discharged
+    pub fn open_transactions(&self) -> usize {
+        self.queues.transactions().len()
+    }
+}
+
+impl Drop for EmbeddedBroker {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve_broker(listener: AmqpListener, queues: Arc<Queues>) {
+    // Owned here so that aborting the broker also aborts every connection
+    let mut connections = JoinSet::new();
+    loop {
+        let connection = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                logging::warn!("Embedded broker failed to accept: {}", e);
+                continue;
+            }
+        };
+        let owner = format!("broker-{}", connection.id());
+        tasks::spawn_in(&mut connections, TaskKind::Broker, &owner, serve_connection(connection, queues.clone()));
+        while connections.try_join_next().is_some() {}
+    }
+}
+
+async fn serve_connection(mut connection: IncomingConnection, queues: Arc<Queues>) {
+    let mut sessions = JoinSet::new();
+    while let Some(session) = connection.next_session().await {
+        sessions.spawn(serve_session(session, queues.clone()));
+    }
+    while sessions.join_next().await.is_some() {}
+    let id = connection.id().to_string();
+    if let Err(e) = connection.close().await {
+        logging::debug!("Embedded broker connection {} closed: {}", id, e);
+    }
+}
+
+async fn serve_session(mut session: IncomingSession, queues: Arc<Queues>) {
+    if let Err(e) = session.accept() {
+        logging::debug!("Embedded broker could not begin session: {}", e);
+        return;
+    }
+    let mut links = JoinSet::new();
+    while let Some(link) = session.next_link().await {
+        links.spawn(serve_link(link, queues.clone()));
+    }
+    // The links end with the session
+    while links.join_next().await.is_some() {}
+}
+
+async fn serve_link(link: IncomingLink, queues: Arc<Queues>) {
+    let mut config = link.link_config();
+    if config.coordinator.is_some() {
+        let result = match link.accept_receiver() {
+            Ok(receiver) => coordinate(receiver, &queues).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            logging::debug!("Embedded broker coordinator link ended: {}", e);
+        }
+        return;
+    }
+    let role = link.role();
+    let (address, terminus) = match role {
+        Role::Sender => (&mut config.target, config.target_config.as_ref()),
+        Role::Receiver => (&mut config.source, config.source_config.as_ref()),
+    };
+    let temporary = address.is_none() && terminus.is_some_and(|terminus| terminus.dynamic);
+    if temporary {
+        *address = Some(queues.temporary_name());
+    }
+    let Some(queue) = address.clone() else {
+        let error = types::AmqpError::new(AmqpCondition::AmqpErrorNotAllowed)
+            .with_description("The embedded broker routes by address; the link has none");
+        let _ = link.refuse(error);
+        return;
+    };
+    let selector = match config.source_config.as_ref().map(|source| Selector::from_filter(&source.filter)) {
+        Some(Err(e)) => {
+            let condition = e.condition().cloned().unwrap_or(AmqpCondition::AmqpErrorInvalidField);
+            let _ = link.refuse(types::AmqpError::new(condition).with_description(e.to_string()));
+            return;
+        }
+        Some(Ok(selector)) => selector,
+        None => None,
+    };
+    let browse = config
+        .source_config
+        .as_ref()
+        .is_some_and(|source| source.distribution_mode == Some(DistributionMode::Copy));
+    queues.lock().entry(queue.clone()).or_default();
+
+    let result = match role {
+        Role::Sender => match link.accept_receiver_with(config) {
+            Ok(receiver) => take_messages(receiver, &queue, &queues).await,
+            Err(e) => Err(e),
+        },
+        Role::Receiver => match link.accept_sender_with(config) {
+            Ok(sender) if browse => browse_messages(sender, &queue, &queues, selector.as_ref()).await,
+            Ok(sender) => give_messages(sender, &queue, &queues, selector.as_ref()).await,
+            Err(e) => Err(e),
+        },
+    };
+    if let Err(e) = result {
+        logging::debug!("Embedded broker link on '{}' ended: {}", queue, e);
+    }
+    if temporary {
+        queues.lock().remove(&queue);
+    }
+}
+
+/// Queue what a sending client transfers, keeping its credit topped up
+///
+/// A transfer within a transaction is held until the transaction ends, and
+/// rejected if the transaction is not open.
+async fn take_messages(mut receiver: Receiver, queue: &str, queues: &Queues) -> AmqpResult<()> {
+    receiver.set_prefetch(BROKER_CREDIT_WINDOW);
+    while let Some((delivery_id, message)) = receiver.next_delivery().await? {
+        let Some(txn_id) = receiver.delivery_txn(delivery_id).map(<[u8]>::to_vec) else {
+            queues.push(queue, message);
+            receiver.accept(delivery_id)?;
+            continue;
+        };
+        let outcome = if queues.enlist(&txn_id, queue, message) {
+            Outcome::Transactional { txn_id, outcome: Some(Box::new(Outcome::Accepted)) }
+        } else {
+            Outcome::Rejected { error: Some(unknown_transaction(&txn_id)) }
+        };
+        receiver.settle(&[delivery_id], outcome)?;
+    }
+    Ok(())
+}
+
+/// Declare and discharge transactions on a coordinator link
+///
+/// Transactions the link declared and did not discharge are rolled back
+/// when it ends.
+async fn coordinate(mut receiver: Receiver, queues: &Queues) -> AmqpResult<()> {
+    receiver.set_prefetch(BROKER_CREDIT_WINDOW);
+    let mut declared = Vec::new();
+    let result = loop {
+        let (delivery_id, message) = match receiver.next_delivery().await {
+            Ok(Some(delivery)) => delivery,
+            Ok(None) => break Ok(()),
+            Err(e) => break Err(e),
+        };
+        let outcome = match control(&message) {
+            Some((descriptor::DECLARE, _)) => {
+                let txn_id = queues.declare();
+                declared.push(txn_id.clone());
+                Outcome::Declared { txn_id }
+            }
+            Some((descriptor::DISCHARGE, [AmqpValue::Binary(txn_id), rest @ ..])) => {
+                let fail = matches!(rest.first(), Some(AmqpValue::Boolean(true)));
+                if queues.discharge(txn_id, fail) {
+                    Outcome::Accepted
+                } else {
+                    Outcome::Rejected { error: Some(unknown_transaction(txn_id)) }
+                }
+            }
+            _ => Outcome::Rejected {
+                error: Some(
+                    types::AmqpError::new(AmqpCondition::AmqpErrorNotImplemented)
+                        .with_description("The embedded broker only takes declare and discharge"),
+                ),
+            },
+        };
+        if let Err(e) = receiver.settle(&[delivery_id], outcome) {
+            break Err(e);
+        }
+    };
+    for txn_id in declared {
+        if queues.discharge(&txn_id, true) {
+            logging::debug!("Embedded broker rolled back transaction {:02x?}", txn_id);
+        }
+    }
+    result
+}
+
+/// Get the descriptor code and fields of a coordinator control
+fn control(message: &Message) -> Option<(u64, &[AmqpValue])> {
+    let Some(Body::Value(AmqpValue::Described(descriptor, fields))) = &message.body else {
+        return None;
+    };
+    match (descriptor.as_ref(), fields.as_ref()) {
+        (Descriptor::Code(code), AmqpValue::List(fields)) => Some((*code, fields.as_slice())),
+        _ => None,
+    }
+}
+
+fn unknown_transaction(txn_id: &[u8]) -> types::AmqpError {
+    types::AmqpError::new(AmqpCondition::from("amqp:transaction:unknown-id"))
+        .with_description(format!("Transaction {:02x?} is not open", txn_id))
+}
+
+/// Send queued messages matching the selector to a receiving client while it has credit
+///
+/// Released and modified messages go back on the queue, and so do those
+/// still unsettled when the link ends.
+async fn give_messages(mut sender: Sender, queue: &str, queues: &Queues, selector: Option<&Selector>) -> AmqpResult<()> {
+    let mut unsettled: Vec<(Delivery, Queued)> = Vec::new();
+    let result = loop {
+        let mut pending = Vec::with_capacity(unsettled.len());
+        for (mut delivery, message) in unsettled.drain(..) {
+            match (&mut delivery).now_or_never() {
+                None => pending.push((delivery, message)),
+                Some(Ok(DeliveryOutcome::Released | DeliveryOutcome::Modified { .. })) => {
+                    queues.requeue(queue, std::iter::once(message))
+                }
+                Some(_) => {}
+            }
+        }
+        unsettled = pending;
+        if sender.state() != &LinkState::Attached {
+            break Ok(());
+        }
+
+        let queued = queues.queued.notified();
+        tokio::pin!(queued);
+        queued.as_mut().enable();
+        if sender.credit() > 0 {
+            if let Some(queued) = queues.pop(queue, selector) {
+                match sender.send(queued.message.clone()).await {
+                    Ok(delivery) => unsettled.push((delivery, queued)),
+                    Err(e) => {
+                        queues.requeue(queue, std::iter::once(queued));
+                        break Err(e);
+                    }
+                }
+                continue;
+            }
+        }
+        tokio::select! {
+            _ = queued, if sender.credit() > 0 => {}
+            result = sender.wait_for_peer() => {
+                if let Err(e) = result {
+                    break Err(e);
+                }
+            }
+        }
+    };
+    queues.requeue(queue, unsettled.into_iter().map(|(_, queued)| queued));
+    result
+}
+
+/// Send copies of the queued messages matching the selector to a browsing client, each once
+///
+/// The messages stay on the queue whatever outcome the client gives.
+async fn browse_messages(mut sender: Sender, queue: &str, queues: &Queues, selector: Option<&Selector>) -> AmqpResult<()> {
+    let mut seen = HashSet::new();
+    while sender.state() == &LinkState::Attached {
+        let queued = queues.queued.notified();
+        tokio::pin!(queued);
+        queued.as_mut().enable();
+        if sender.credit() > 0 {
+            if let Some(message) = queues.browse(queue, selector, &mut seen) {
+                sender.send(message).await?;
+                continue;
+            }
+        }
+        tokio::select! {
+            _ = queued, if sender.credit() > 0 => {}
+            result = sender.wait_for_peer() => result?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{Frame, FrameHeader, FrameType, Transport};
+
+    /// Start a server that echoes every byte back to the sender
+    async fn echo_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (mut reader, mut writer) = socket.split();
+                    let _ = tokio::io::copy(&mut reader, &mut writer).await;
+                });
+            }
+        });
+        addr
+    }
+
+    async fn read_exactly(stream: &mut TcpStream, len: usize) -> Vec<u8> {
+        let mut data = vec![0u8; len];
+        stream.read_exact(&mut data).await.unwrap();
+        data
+    }
+
+    #[test]
+    fn test_chaos_config_default() {
+        let config = ChaosConfig::default();
+        assert_eq!(config.latency, Duration::ZERO);
+        assert!(config.max_write_chunk.is_none());
+        assert_eq!(config.disconnect_probability, 0.0);
+        assert_eq!(config.reorder_probability, 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_passthrough_without_faults() {
+        let chaos = ChaosTransport::bind(echo_server().await, ChaosConfig::default()).await.unwrap();
+        let mut client = TcpStream::connect(chaos.local_addr()).await.unwrap();
+
+        client.write_all(b"hello chaos").await.unwrap();
+        assert_eq!(read_exactly(&mut client, 11).await, b"hello chaos");
+
+        let stats = chaos.stats();
+        assert_eq!(stats.connections, 1);
+        assert_eq!(stats.bytes_forwarded, 22);
+    }
+
+    #[tokio::test]
+    async fn test_frames_survive_partial_writes_and_latency() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream = listener.local_addr().unwrap();
+        let chaos = ChaosTransport::bind(
+            upstream,
+            ChaosConfig {
+                jitter: Duration::from_millis(2),
+                max_write_chunk: Some(3),
+                seed: Some(7),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let (client, server) = tokio::join!(TcpStream::connect(chaos.local_addr()), listener.accept());
+        let mut client = Transport::new(client.unwrap());
+        let mut server = Transport::new(server.unwrap().0);
+
+        for channel in 0..5u16 {
+            let payload = vec![channel as u8; 100];
+            let header = FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, channel);
+            client.send_frame(Frame::new(header, payload)).await.unwrap();
+        }
+        for channel in 0..5u16 {
+            let frame = server.receive_frame().await.unwrap();
+            assert_eq!(frame.header.channel, channel);
+            assert_eq!(frame.payload, vec![channel as u8; 100]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reordering_swaps_chunks() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let chaos = ChaosTransport::bind(
+            listener.local_addr().unwrap(),
+            ChaosConfig {
+                reorder_probability: 1.0,
+                seed: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let (client, server) = tokio::join!(TcpStream::connect(chaos.local_addr()), listener.accept());
+        let mut client = client.unwrap();
+        let mut server = server.unwrap().0;
+
+        client.write_all(b"A").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        client.write_all(b"B").await.unwrap();
+
+        assert_eq!(read_exactly(&mut server, 2).await, b"BA");
+        assert_eq!(chaos.stats().reordered, 1);
+    }
+
+    #[tokio::test]
+    async fn test_random_disconnect() {
+        let chaos = ChaosTransport::bind(
+            echo_server().await,
+            ChaosConfig {
+                disconnect_probability: 1.0,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let mut client = TcpStream::connect(chaos.local_addr()).await.unwrap();
+
+        client.write_all(b"doomed").await.unwrap();
+        let mut buffer = [0u8; 16];
+        assert!(matches!(client.read(&mut buffer).await, Ok(0) | Err(_)));
+        assert_eq!(chaos.stats().disconnects, 1);
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_all_keeps_listening() {
+        let chaos = ChaosTransport::bind(echo_server().await, ChaosConfig::default()).await.unwrap();
+        let mut first = TcpStream::connect(chaos.local_addr()).await.unwrap();
+        first.write_all(b"ping").await.unwrap();
+        assert_eq!(read_exactly(&mut first, 4).await, b"ping");
+
+        chaos.disconnect_all();
+        let mut buffer = [0u8; 4];
+        assert!(matches!(first.read(&mut buffer).await, Ok(0) | Err(_)));
+
+        let mut second = TcpStream::connect(chaos.local_addr()).await.unwrap();
+        second.write_all(b"pong").await.unwrap();
+        assert_eq!(read_exactly(&mut second, 4).await, b"pong");
+        assert_eq!(chaos.stats().connections, 2);
+    }
+
+    /// Wait until the broker's queue holds `depth` messages
+    async fn queue_depth_reaches(broker: &EmbeddedBroker, queue: &str, depth: usize) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while broker.queue_depth(queue) != Some(depth) {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("queue '{}' holds {:?} messages", queue, broker.queue_depth(queue)));
+    }
+
+    /// Wait until the broker has deleted the queue
+    async fn queue_deleted(broker: &EmbeddedBroker, queue: &str) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while broker.queue_depth(queue).is_some() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("queue '{}' was not deleted", queue));
+    }
+
+    #[tokio::test]
+    async fn test_embedded_broker_routes_target_to_source() {
+        let broker = EmbeddedBroker::start().await.unwrap();
+        let mut connection = broker.connection().build();
+        connection.open().await.unwrap();
+        let session = connection.create_session().await.unwrap();
+
+        let target = crate::link::LinkConfig { target: Some("orders".to_string()), ..Default::default() };
+        let mut sender = session.create_sender(target).await.unwrap();
+        sender.attach().await.unwrap();
+        while sender.credit() == 0 {
+            sender.wait_for_peer().await.unwrap();
+        }
+        for i in 0..3 {
+            let delivery = sender.send(Message::text(format!("order-{}", i))).await.unwrap();
+            assert_eq!(sender.outcome(delivery).await.unwrap(), DeliveryOutcome::Accepted);
+        }
+        assert_eq!(broker.queue_depth("orders"), Some(3));
+
+        let source = crate::link::LinkConfig { source: Some("orders".to_string()), ..Default::default() };
+        let mut receiver = session.create_receiver(source).await.unwrap();
+        receiver.attach().await.unwrap();
+        receiver.add_credit(2);
+        for i in 0..2 {
+            let (delivery_id, message) = receiver.next_delivery().await.unwrap().unwrap();
+            assert_eq!(message, Message::text(format!("order-{}", i)));
+            receiver.accept(delivery_id).unwrap();
+        }
+        // Without more credit the last message stays queued
+        queue_depth_reaches(&broker, "orders", 1).await;
+        assert_eq!(broker.queues(), vec!["orders".to_string()]);
+        connection.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_embedded_broker_requeues_released_messages() {
+        let broker = EmbeddedBroker::start().await.unwrap();
+        broker.publish("jobs", Message::text("job-1"));
+        broker.publish("jobs", Message::text("job-2"));
+        let mut connection = broker.connection().build();
+        connection.open().await.unwrap();
+        let session = connection.create_session().await.unwrap();
+
+        let source = crate::link::LinkConfig { source: Some("jobs".to_string()), ..Default::default() };
+        let mut receiver = session.create_receiver(source).await.unwrap();
+        receiver.attach().await.unwrap();
+        receiver.add_credit(1);
+        let (delivery_id, message) = receiver.next_delivery().await.unwrap().unwrap();
+        assert_eq!(message, Message::text("job-1"));
+        receiver.release(delivery_id).unwrap();
+        queue_depth_reaches(&broker, "jobs", 2).await;
+
+        // The released message is first in line again
+        receiver.add_credit(1);
+        let (_, message) = receiver.next_delivery().await.unwrap().unwrap();
+        assert_eq!(message, Message::text("job-1"));
+        connection.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_embedded_broker_applies_selector_filter() {
+        use crate::link::{LinkConfig, TerminusConfig};
+        use crate::selector::SELECTOR_FILTER_NAME;
+        use crate::{AmqpSymbol, AmqpValue};
+
+        let broker = EmbeddedBroker::start().await.unwrap();
+        for (body, color) in [("apple", "red"), ("lime", "green"), ("cherry", "red")] {
+            let mut properties = crate::AmqpMap::new();
+            properties.insert(AmqpSymbol::from("color"), AmqpValue::String(color.to_string()));
+            let mut message = Message::text(body);
+            message.application_properties = Some(properties);
+            broker.publish("fruit", message);
+        }
+        let mut connection = broker.connection().build();
+        connection.open().await.unwrap();
+        let session = connection.create_session().await.unwrap();
+
+        let selector = Selector::parse("color = 'red'").unwrap();
+        let filtered = |selector: AmqpValue| LinkConfig {
+            source: Some("fruit".to_string()),
+            source_config: Some(TerminusConfig {
+                filter: [(SELECTOR_FILTER_NAME.to_string(), selector)].into_iter().collect(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut receiver = session.create_receiver(filtered(selector.filter_value())).await.unwrap();
+        receiver.attach().await.unwrap();
+        receiver.add_credit(3);
+        for expected in ["apple", "cherry"] {
+            let (delivery_id, message) = receiver.next_delivery().await.unwrap().unwrap();
+            assert_eq!(message.body_as_text(), Some(expected));
+            receiver.accept(delivery_id).unwrap();
+        }
+        // The green message is left for receivers without the selector
+        queue_depth_reaches(&broker, "fruit", 1).await;
+
+        let mut refused = session.create_receiver(filtered(AmqpValue::String("color =".to_string()))).await.unwrap();
+        assert!(!refused.attach().await.unwrap().is_attached());
+        connection.close().await.unwrap();
+    }
+    #[tokio::test]
+    async fn test_embedded_broker_browses_without_taking() {
+        use crate::link::{LinkConfig, TerminusConfig};
+
+        let broker = EmbeddedBroker::start().await.unwrap();
+        broker.publish("audit", Message::text("entry-1"));
+        broker.publish("audit", Message::text("entry-2"));
+        let mut connection = broker.connection().build();
+        connection.open().await.unwrap();
+        let session = connection.create_session().await.unwrap();
+
+        let browser = LinkConfig {
+            source: Some("audit".to_string()),
+            source_config: Some(TerminusConfig { distribution_mode: Some(DistributionMode::Copy), ..Default::default() }),
+            ..Default::default()
+        };
+        let mut receiver = session.create_receiver(browser).await.unwrap();
+        receiver.attach().await.unwrap();
+        receiver.add_credit(5);
+        for expected in ["entry-1", "entry-2"] {
+            let (delivery_id, message) = receiver.next_delivery().await.unwrap().unwrap();
+            assert_eq!(message.body_as_text(), Some(expected));
+            receiver.accept(delivery_id).unwrap();
+        }
+        // Messages queued later are browsed too, and each only once
+        broker.publish("audit", Message::text("entry-3"));
+        let (_, message) = receiver.next_delivery().await.unwrap().unwrap();
+        assert_eq!(message.body_as_text(), Some("entry-3"));
+        assert_eq!(broker.queue_depth("audit"), Some(3));
+        connection.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_embedded_broker_creates_temporary_queues() {
+        use crate::link::{AttachOutcome, LinkConfig, TerminusBuilder};
+
+        let broker = EmbeddedBroker::start().await.unwrap();
+        let mut connection = broker.connection().build();
+        connection.open().await.unwrap();
+        let session = connection.create_session().await.unwrap();
+
+        let dynamic = LinkConfig { source_config: Some(TerminusBuilder::new().dynamic().build()), ..Default::default() };
+        let mut receiver = session.create_receiver(dynamic).await.unwrap();
+        let address = match receiver.attach().await.unwrap() {
+            AttachOutcome::Attached { remote_source: Some(source), .. } => source.address.unwrap(),
+            other => panic!("unexpected attach outcome {:?}", other),
+        };
+        assert_eq!(address, "temp-queue-1");
+        assert_eq!(broker.queue_depth(&address), Some(0));
+
+        broker.publish(&address, Message::text("reply"));
+        receiver.add_credit(1);
+        let (_, message) = receiver.next_delivery().await.unwrap().unwrap();
+        assert_eq!(message.body_as_text(), Some("reply"));
+        receiver.close().await.unwrap();
+        queue_deleted(&broker, &address).await;
+        connection.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_embedded_broker_holds_transfers_until_commit() {
+        let broker = EmbeddedBroker::start().await.unwrap();
+        let mut connection = broker.connection().build();
+        connection.open().await.unwrap();
+        let session = connection.create_session().await.unwrap();
+
+        let target = crate::link::LinkConfig { target: Some("ledger".to_string()), ..Default::default() };
+        let mut sender = session.create_sender(target).await.unwrap();
+        sender.attach().await.unwrap();
+        while sender.credit() == 0 {
+            sender.wait_for_peer().await.unwrap();
+        }
+
+        let txn = session.begin_transaction().await.unwrap();
+        for i in 0..2 {
+            let delivery = sender.send_in_txn(&txn, Message::text(format!("entry-{}", i))).await.unwrap();
+            assert_eq!(sender.outcome(delivery).await.unwrap(), DeliveryOutcome::Accepted);
+        }
+        assert_eq!(broker.queue_depth("ledger"), Some(0));
+        txn.commit().await.unwrap();
+        assert_eq!(broker.queue_depth("ledger"), Some(2));
+
+        let txn = session.begin_transaction().await.unwrap();
+        let delivery = sender.send_in_txn(&txn, Message::text("entry-2")).await.unwrap();
+        sender.outcome(delivery).await.unwrap();
+        txn.rollback().await.unwrap();
+        assert_eq!(broker.queue_depth("ledger"), Some(2));
+
+        // A transaction left open is rolled back with its coordinator link
+        let txn = session.begin_transaction().await.unwrap();
+        let delivery = sender.send_in_txn(&txn, Message::text("entry-3")).await.unwrap();
+        sender.outcome(delivery).await.unwrap();
+        drop(txn);
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while broker.open_transactions() > 0 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(broker.queue_depth("ledger"), Some(2));
+        connection.close().await.unwrap();
+    }
+}
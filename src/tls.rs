@@ -0,0 +1,420 @@
+//! AMQP 1.0 TLS Termination
+//!
+//! This module secures connections with TLS in both roles. A [`TlsAcceptor`]
+//! is built from a PEM certificate chain and key, offers the `amqp`
+//! application protocol through ALPN and can ask for, or require, client
+//! certificates signed by a given set of roots. Each accepted connection
+//! comes with an [`IncomingConnection`] carrying the client's
+//! [`TlsIdentity`], ready to be handed to an [`Authorizer`]. Given to
+//! [`AmqpListener::tls`](crate::server::AmqpListener::tls), an acceptor
+//! secures every connection of the listener before its AMQP handshake.
+//!
+//! In the client role, a [`TlsConnector`] verifies the server against a set
+//! of PEM roots and can present a client certificate, as SASL EXTERNAL
+//! needs. Given to [`NetworkBuilder::tls`](crate::network::NetworkBuilder::tls),
+//! it secures the connection before the SASL or AMQP protocol header; a
+//! [`Failover`](crate::failover::Failover) uses it for `amqps` endpoints.
+//!
+//! Requires the `tls` feature.
+//!
+//! [`Authorizer`]: crate::listener::Authorizer
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use dumq_amqp::listener::{Authorizer, IncomingConnection};
+//! use dumq_amqp::tls::{ClientAuth, TlsAcceptorBuilder};
+//! use tokio::net::TcpListener;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let acceptor = TlsAcceptorBuilder::new(std::fs::read("server.pem")?, std::fs::read("server.key")?)
+//!     .client_auth(ClientAuth::Optional(std::fs::read("clients-ca.pem")?))
+//!     .build()?;
+//! let authorizer = |incoming: &IncomingConnection| {
+//!     println!("{} authenticated: {:?}", incoming.remote_addr, incoming.tls);
+//!     Ok(())
+//! };
+//!
+//! let listener = TcpListener::bind("0.0.0.0:5671").await?;
+//! let (stream, _) = listener.accept().await?;
+//! let (tls_stream, incoming) = acceptor.accept(stream).await?;
+//! authorizer.authorize(&incoming)?;
+//! # drop(tls_stream);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::listener::{IncomingConnection, TlsIdentity};
+use crate::logging;
+use crate::{AmqpError, AmqpResult};
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::crypto::{ring, CryptoProvider};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore, ServerConfig};
+use tokio_rustls::server::TlsStream;
+
+/// ALPN protocol id for AMQP
+pub const ALPN_AMQP: &[u8] = b"amqp";
+
+/// Client certificate verification
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ClientAuth {
+    /// Do not ask for client certificates
+    #[default]
+    None,
+    /// Verify a client certificate against these PEM roots if one is presented
+    Optional(Vec<u8>),
+    /// Refuse clients without a certificate signed by these PEM roots
+    Required(Vec<u8>),
+}
+
+/// Builder for a [`TlsAcceptor`]
+#[derive(Debug, Clone)]
+pub struct TlsAcceptorBuilder {
+    cert_chain: Vec<u8>,
+    key: Vec<u8>,
+    client_auth: ClientAuth,
+    alpn_protocols: Vec<Vec<u8>>,
+}
+
+impl TlsAcceptorBuilder {
+    /// Create a builder from a PEM certificate chain and PEM private key
+    pub fn new(cert_chain: impl Into<Vec<u8>>, key: impl Into<Vec<u8>>) -> Self {
+        TlsAcceptorBuilder {
+            cert_chain: cert_chain.into(),
+            key: key.into(),
+            client_auth: ClientAuth::None,
+            alpn_protocols: vec![ALPN_AMQP.to_vec()],
+        }
+    }
+
+    /// Set the client certificate verification
+    pub fn client_auth(mut self, client_auth: ClientAuth) -> Self {
+        self.client_auth = client_auth;
+        self
+    }
+
+    /// Set the ALPN protocols offered, `amqp` by default
+    ///
+    /// Clients offering ALPN without any of these are refused; clients not
+    /// using ALPN are accepted.
+    pub fn alpn_protocols(mut self, protocols: Vec<Vec<u8>>) -> Self {
+        self.alpn_protocols = protocols;
+        self
+    }
+
+    /// Build the acceptor
+    pub fn build(self) -> AmqpResult<TlsAcceptor> {
+        let provider = Arc::new(ring::default_provider());
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| AmqpError::transport(format!("Invalid TLS configuration: {}", e)))?;
+
+        let builder = match self.client_auth {
+            ClientAuth::None => builder.with_no_client_auth(),
+            ClientAuth::Optional(roots) => builder.with_client_cert_verifier(
+                client_verifier(&roots, provider, true)?,
+            ),
+            ClientAuth::Required(roots) => builder.with_client_cert_verifier(
+                client_verifier(&roots, provider, false)?,
+            ),
+        };
+
+        let mut config = builder
+            .with_single_cert(parse_certificates(&self.cert_chain)?, parse_key(&self.key)?)
+            .map_err(|e| AmqpError::transport(format!("Invalid TLS certificate or key: {}", e)))?;
+        config.alpn_protocols = self.alpn_protocols;
+
+        Ok(TlsAcceptor::from_config(Arc::new(config)))
+    }
+}
+
+/// Accepts TLS connections
+#[derive(Clone)]
+pub struct TlsAcceptor {
+    inner: tokio_rustls::TlsAcceptor,
+}
+
+impl TlsAcceptor {
+    /// Create an acceptor from a rustls configuration
+    pub fn from_config(config: Arc<ServerConfig>) -> Self {
+        TlsAcceptor {
+            inner: tokio_rustls::TlsAcceptor::from(config),
+        }
+    }
+
+    /// Run the TLS handshake on an accepted TCP stream
+    ///
+    /// Fails if the client offers no matching ALPN protocol or, when
+    /// required, presents no valid certificate.
+    pub async fn accept(&self, stream: TcpStream) -> AmqpResult<(TlsStream<TcpStream>, IncomingConnection)> {
+        let remote_addr = stream.peer_addr()?;
+        let stream = self.inner.accept(stream).await.map_err(|e| {
+            logging::warn!("TLS handshake with {} failed: {}", remote_addr, e);
+            AmqpError::tls(remote_addr.to_string(), e.to_string())
+        })?;
+
+        let (_, connection) = stream.get_ref();
+        let identity = TlsIdentity {
+            server_name: connection.server_name().map(str::to_string),
+            alpn_protocol: connection.alpn_protocol().map(<[u8]>::to_vec),
+            peer_certificates: connection
+                .peer_certificates()
+                .unwrap_or_default()
+                .iter()
+                .map(|certificate| certificate.to_vec())
+                .collect(),
+        };
+
+        let mut incoming = IncomingConnection::new(remote_addr);
+        incoming.tls = Some(identity);
+        Ok((stream, incoming))
+    }
+}
+
+impl std::fmt::Debug for TlsAcceptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsAcceptor").finish_non_exhaustive()
+    }
+}
+
+/// Builder for a [`TlsConnector`]
+#[derive(Debug, Clone)]
+pub struct TlsConnectorBuilder {
+    roots: Vec<u8>,
+    client_certificate: Option<(Vec<u8>, Vec<u8>)>,
+    alpn_protocols: Vec<Vec<u8>>,
+}
+
+impl TlsConnectorBuilder {
+    /// Create a builder trusting servers whose certificate chains to these PEM roots
+    pub fn new(roots: impl Into<Vec<u8>>) -> Self {
+        TlsConnectorBuilder {
+            roots: roots.into(),
+            client_certificate: None,
+            alpn_protocols: vec![ALPN_AMQP.to_vec()],
+        }
+    }
+
+    /// Present a PEM client certificate chain and PEM private key
+    pub fn client_certificate(mut self, cert_chain: impl Into<Vec<u8>>, key: impl Into<Vec<u8>>) -> Self {
+        self.client_certificate = Some((cert_chain.into(), key.into()));
+        self
+    }
+
+    /// Set the ALPN protocols offered, `amqp` by default
+    pub fn alpn_protocols(mut self, protocols: Vec<Vec<u8>>) -> Self {
+        self.alpn_protocols = protocols;
+        self
+    }
+
+    /// Build the connector
+    pub fn build(self) -> AmqpResult<TlsConnector> {
+        let mut roots = RootCertStore::empty();
+        for certificate in parse_certificates(&self.roots)? {
+            roots
+                .add(certificate)
+                .map_err(|e| AmqpError::transport(format!("Invalid root certificate: {}", e)))?;
+        }
+        let builder = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| AmqpError::transport(format!("Invalid TLS configuration: {}", e)))?
+            .with_root_certificates(roots);
+
+        let mut config = match self.client_certificate {
+            Some((cert_chain, key)) => builder
+                .with_client_auth_cert(parse_certificates(&cert_chain)?, parse_key(&key)?)
+                .map_err(|e| AmqpError::transport(format!("Invalid TLS certificate or key: {}", e)))?,
+            None => builder.with_no_client_auth(),
+        };
+        config.alpn_protocols = self.alpn_protocols;
+
+        Ok(TlsConnector::from_config(Arc::new(config)))
+    }
+}
+
+/// Opens TLS connections
+#[derive(Clone)]
+pub struct TlsConnector {
+    inner: tokio_rustls::TlsConnector,
+}
+
+impl TlsConnector {
+    /// Create a connector from a rustls configuration
+    pub fn from_config(config: Arc<ClientConfig>) -> Self {
+        TlsConnector {
+            inner: tokio_rustls::TlsConnector::from(config),
+        }
+    }
+
+    /// Run the TLS handshake on a connected TCP stream
+    ///
+    /// `server_name` is sent as SNI, unless it is an IP address, and the
+    /// server's certificate must be valid for it.
+    pub async fn connect(
+        &self,
+        server_name: &str,
+        stream: TcpStream,
+    ) -> AmqpResult<tokio_rustls::client::TlsStream<TcpStream>> {
+        let remote_addr = stream.peer_addr()?;
+        let name = ServerName::try_from(server_name.to_string())
+            .map_err(|e| AmqpError::tls(remote_addr.to_string(), format!("Invalid server name '{}': {}", server_name, e)))?;
+        self.inner.connect(name, stream).await.map_err(|e| {
+            logging::warn!("TLS handshake with {} ({}) failed: {}", server_name, remote_addr, e);
+            AmqpError::tls(remote_addr.to_string(), e.to_string())
+        })
+    }
+}
+
+impl std::fmt::Debug for TlsConnector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsConnector").finish_non_exhaustive()
+    }
+}
+
+fn client_verifier(
+    roots_pem: &[u8],
+    provider: Arc<CryptoProvider>,
+    optional: bool,
+) -> AmqpResult<Arc<dyn tokio_rustls::rustls::server::danger::ClientCertVerifier>> {
+    let mut roots = RootCertStore::empty();
+    for certificate in parse_certificates(roots_pem)? {
+        roots
+            .add(certificate)
+            .map_err(|e| AmqpError::transport(format!("Invalid client root certificate: {}", e)))?;
+    }
+
+    let builder = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+    let builder = if optional { builder.allow_unauthenticated() } else { builder };
+    builder
+        .build()
+        .map_err(|e| AmqpError::transport(format!("Invalid client verification: {}", e)))
+}
+
+fn parse_certificates(pem: &[u8]) -> AmqpResult<Vec<CertificateDer<'static>>> {
+    let certificates = rustls_pemfile::certs(&mut &pem[..])
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| AmqpError::transport(format!("Invalid PEM certificate: {}", e)))?;
+    if certificates.is_empty() {
+        return Err(AmqpError::transport("No certificate found in PEM data"));
+    }
+    Ok(certificates)
+}
+
+fn parse_key(pem: &[u8]) -> AmqpResult<PrivateKeyDer<'static>> {
+    rustls_pemfile::private_key(&mut &pem[..])
+        .map_err(|e| AmqpError::transport(format!("Invalid PEM private key: {}", e)))?
+        .ok_or_else(|| AmqpError::transport("No private key found in PEM data"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const CA: &str = include_str!("../testdata/tls/ca.pem");
+    const SERVER_CERT: &str = include_str!("../testdata/tls/server.pem");
+    const SERVER_KEY: &str = include_str!("../testdata/tls/server.key");
+    const CLIENT_CERT: &str = include_str!("../testdata/tls/client.pem");
+    const CLIENT_KEY: &str = include_str!("../testdata/tls/client.key");
+
+    fn connector(with_certificate: bool, alpn: &[u8]) -> TlsConnector {
+        let builder = TlsConnectorBuilder::new(CA).alpn_protocols(vec![alpn.to_vec()]);
+        let builder = if with_certificate { builder.client_certificate(CLIENT_CERT, CLIENT_KEY) } else { builder };
+        builder.build().unwrap()
+    }
+
+    /// Accept one connection with `acceptor` while `connector` connects to it
+    async fn handshake(acceptor: TlsAcceptor, connector: TlsConnector) -> AmqpResult<IncomingConnection> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = tokio::spawn(async move {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            if let Ok(mut stream) = connector.connect("localhost", stream).await {
+                let _ = stream.write_all(b"AMQP\x00\x01\x00\x00").await;
+                let _ = stream.flush().await;
+            }
+        });
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let (mut stream, incoming) = acceptor.accept(stream).await?;
+        let mut header = [0u8; 8];
+        stream.read_exact(&mut header).await?;
+        assert_eq!(&header, b"AMQP\x00\x01\x00\x00");
+        client.await.unwrap();
+        Ok(incoming)
+    }
+
+    #[tokio::test]
+    async fn test_accept_negotiates_alpn() {
+        let acceptor = TlsAcceptorBuilder::new(SERVER_CERT, SERVER_KEY).build().unwrap();
+        let incoming = handshake(acceptor, connector(false, ALPN_AMQP)).await.unwrap();
+
+        let tls = incoming.tls.unwrap();
+        assert_eq!(tls.alpn_protocol.as_deref(), Some(ALPN_AMQP));
+        assert_eq!(tls.server_name.as_deref(), Some("localhost"));
+        assert!(!tls.is_client_authenticated());
+    }
+
+    #[tokio::test]
+    async fn test_refuses_other_alpn_protocol() {
+        let acceptor = TlsAcceptorBuilder::new(SERVER_CERT, SERVER_KEY).build().unwrap();
+        let error = handshake(acceptor, connector(false, b"h2")).await.unwrap_err();
+        assert!(matches!(error, AmqpError::Tls { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_required_client_auth() {
+        let acceptor = TlsAcceptorBuilder::new(SERVER_CERT, SERVER_KEY)
+            .client_auth(ClientAuth::Required(CA.as_bytes().to_vec()))
+            .build()
+            .unwrap();
+
+        assert!(handshake(acceptor.clone(), connector(false, ALPN_AMQP)).await.is_err());
+
+        let incoming = handshake(acceptor, connector(true, ALPN_AMQP)).await.unwrap();
+        let tls = incoming.tls.unwrap();
+        assert!(tls.is_client_authenticated());
+        assert_eq!(
+            tls.peer_certificate(),
+            Some(parse_certificates(CLIENT_CERT.as_bytes()).unwrap()[0].as_ref())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connector_verifies_server() {
+        let acceptor = TlsAcceptorBuilder::new(SERVER_CERT, SERVER_KEY).build().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let _ = acceptor.accept(stream).await;
+            }
+        });
+
+        // The server certificate is for localhost and chains to the CA only
+        let untrusted = TlsConnectorBuilder::new(CLIENT_CERT).build().unwrap();
+        let error = untrusted.connect("localhost", TcpStream::connect(addr).await.unwrap()).await.unwrap_err();
+        assert!(matches!(error, AmqpError::Tls { .. }));
+        let error = connector(false, ALPN_AMQP)
+            .connect("broker.example.com", TcpStream::connect(addr).await.unwrap())
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AmqpError::Tls { .. }));
+
+        let stream = connector(false, ALPN_AMQP).connect("localhost", TcpStream::connect(addr).await.unwrap()).await.unwrap();
+        assert_eq!(stream.get_ref().1.alpn_protocol(), Some(ALPN_AMQP));
+    }
+
+    #[test]
+    fn test_build_rejects_missing_key() {
+        let error = TlsAcceptorBuilder::new(SERVER_CERT, "").build().unwrap_err();
+        assert_eq!(error.to_string(), "Transport error: No private key found in PEM data");
+    }
+}
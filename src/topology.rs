@@ -0,0 +1,604 @@
+//! AMQP 1.0 Topology Manager
+//!
+//! This module provides a higher-level manager that declares the senders and
+//! receivers an application needs across several connections and reconciles
+//! the actual state against that declaration. Connections that are down are
+//! reopened on the next reconciliation and their links are attached again.
+//!
+//! # Connection Affinity
+//!
+//! Each address is assigned to a connection by the longest matching affinity
+//! prefix. Addresses without a matching affinity use the default connection,
+//! which is the first connection declared unless set explicitly.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use dumq_amqp::connection::ConnectionConfig;
+//! use dumq_amqp::topology::TopologyBuilder;
+//! use tokio::time::Duration;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let mut topology = TopologyBuilder::new()
+//!         .connection("primary", ConnectionConfig::default())
+//!         .connection("audit", ConnectionConfig {
+//!             hostname: "audit.example.com".to_string(),
+//!             ..Default::default()
+//!         })
+//!         .affinity("audit.", "audit")
+//!         .sender("orders")
+//!         .sender("audit.orders")
+//!         .receiver("events")
+//!         .reconcile_interval(Duration::from_secs(5))
+//!         .build();
+//!
+//!     let report = topology.reconcile().await;
+//!     println!("Converged: {}", report.is_converged());
+//!
+//!     if let Some(sender) = topology.sender_mut("orders") {
+//!         println!("Sender state: {:?}", sender.state());
+//!     }
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use crate::logging;
+use crate::connection::{Connection, ConnectionConfig, ConnectionState};
+use crate::link::{LinkConfig, Receiver, Sender};
+use crate::session::{Session, SessionBuilder};
+use crate::{AmqpError, AmqpResult};
+use std::collections::{BTreeSet, HashMap};
+use std::future::Future;
+use tokio::time::Duration;
+
+/// Kind of link declared in a topology
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LinkKind {
+    /// Sending link targeting the address
+    Sender,
+    /// Receiving link sourced from the address
+    Receiver,
+}
+
+/// Link declared in a topology
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LinkSpec {
+    /// Kind of link
+    pub kind: LinkKind,
+    /// Node address
+    pub address: String,
+}
+
+impl LinkSpec {
+    /// Create a sender declaration
+    pub fn sender(address: impl Into<String>) -> Self {
+        LinkSpec {
+            kind: LinkKind::Sender,
+            address: address.into(),
+        }
+    }
+
+    /// Create a receiver declaration
+    pub fn receiver(address: impl Into<String>) -> Self {
+        LinkSpec {
+            kind: LinkKind::Receiver,
+            address: address.into(),
+        }
+    }
+}
+
+/// Topology configuration
+#[derive(Debug, Clone)]
+pub struct TopologyConfig {
+    /// Named connections, in declaration order
+    pub connections: Vec<(String, ConnectionConfig)>,
+    /// Address prefix to connection name assignments
+    pub affinities: Vec<(String, String)>,
+    /// Connection used for addresses without an affinity
+    pub default_connection: Option<String>,
+    /// Interval between reconciliations in [`Topology::run`]
+    pub reconcile_interval: Duration,
+}
+
+impl Default for TopologyConfig {
+    fn default() -> Self {
+        TopologyConfig {
+            connections: Vec::new(),
+            affinities: Vec::new(),
+            default_connection: None,
+            reconcile_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Result of a reconciliation pass
+#[derive(Debug, Default)]
+pub struct ReconcileReport {
+    /// Connections opened during this pass
+    pub connected: Vec<String>,
+    /// Links attached during this pass
+    pub attached: Vec<LinkSpec>,
+    /// Links detached because they are no longer declared
+    pub detached: Vec<LinkSpec>,
+    /// Connections or links that could not be brought up
+    pub failed: Vec<(String, AmqpError)>,
+}
+
+impl ReconcileReport {
+    /// Check if the actual state matches the declared state
+    pub fn is_converged(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Live connection with the links attached through it
+struct Node {
+    connection: Connection,
+    session: Session,
+    senders: HashMap<String, Sender>,
+    receivers: HashMap<String, Receiver>,
+}
+
+impl Node {
+    fn has(&self, spec: &LinkSpec) -> bool {
+        match spec.kind {
+            LinkKind::Sender => self.senders.contains_key(&spec.address),
+            LinkKind::Receiver => self.receivers.contains_key(&spec.address),
+        }
+    }
+
+    fn specs(&self) -> Vec<LinkSpec> {
+        self.senders
+            .keys()
+            .map(LinkSpec::sender)
+            .chain(self.receivers.keys().map(LinkSpec::receiver))
+            .collect()
+    }
+}
+
+/// AMQP 1.0 Topology Manager
+pub struct Topology {
+    /// Topology configuration
+    config: TopologyConfig,
+    /// Declared links
+    desired: BTreeSet<LinkSpec>,
+    /// Live connections by name
+    nodes: HashMap<String, Node>,
+}
+
+impl Topology {
+    /// Create a new topology
+    pub fn new(config: TopologyConfig) -> Self {
+        Topology {
+            config,
+            desired: BTreeSet::new(),
+            nodes: HashMap::new(),
+        }
+    }
+
+    /// Declare a link; it is attached on the next reconciliation
+    pub fn declare(&mut self, spec: LinkSpec) {
+        self.desired.insert(spec);
+    }
+
+    /// Remove a declared link; it is detached on the next reconciliation
+    pub fn undeclare(&mut self, spec: &LinkSpec) -> bool {
+        self.desired.remove(spec)
+    }
+
+    /// Get the connection name an address is assigned to
+    pub fn connection_for(&self, address: &str) -> Option<&str> {
+        self.config
+            .affinities
+            .iter()
+            .filter(|(prefix, _)| address.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, name)| name.as_str())
+            .or(self.config.default_connection.as_deref())
+            .or_else(|| self.config.connections.first().map(|(name, _)| name.as_str()))
+    }
+
+    /// Bring the actual state in line with the declared state
+    pub async fn reconcile(&mut self) -> ReconcileReport {
+        let mut report = ReconcileReport::default();
+
+        // Forget connections that went down so they are reopened below
+        self.nodes
+            .retain(|_, node| node.connection.state() == &ConnectionState::Open);
+
+        // Detach links that are no longer declared
+        for node in self.nodes.values_mut() {
+            for spec in node.specs() {
+                if self.desired.contains(&spec) {
+                    continue;
+                }
+                let result = match spec.kind {
+                    LinkKind::Sender => match node.senders.remove(&spec.address) {
+                        Some(mut sender) => sender.detach().await,
+                        None => Ok(()),
+                    },
+                    LinkKind::Receiver => match node.receivers.remove(&spec.address) {
+                        Some(mut receiver) => receiver.detach().await,
+                        None => Ok(()),
+                    },
+                };
+                match result {
+                    Ok(()) => report.detached.push(spec),
+                    Err(e) => report.failed.push((spec.address.clone(), e)),
+                }
+            }
+        }
+
+        // Attach declared links, opening their connections as needed
+        let desired: Vec<LinkSpec> = self.desired.iter().cloned().collect();
+        for spec in desired {
+            let name = match self.connection_for(&spec.address) {
+                Some(name) => name.to_string(),
+                None => {
+                    report.failed.push((
+                        spec.address.clone(),
+                        AmqpError::invalid_state("No connection declared for address"),
+                    ));
+                    continue;
+                }
+            };
+
+            if !self.nodes.contains_key(&name) {
+                match self.connect(&name).await {
+                    Ok(node) => {
+                        self.nodes.insert(name.clone(), node);
+                        report.connected.push(name.clone());
+                    }
+                    Err(e) => {
+                        report.failed.push((name.clone(), e));
+                        continue;
+                    }
+                }
+            }
+
+            let node = self.nodes.get_mut(&name).expect("node inserted above");
+            if node.has(&spec) {
+                continue;
+            }
+            match attach(node, &spec).await {
+                Ok(()) => report.attached.push(spec),
+                Err(e) => report.failed.push((spec.address.clone(), e)),
+            }
+        }
+
+        report
+    }
+
+    /// Reconcile at the configured interval until `shutdown` completes
+    pub async fn run<F>(&mut self, shutdown: F)
+    where
+        F: Future<Output = ()>,
+    {
+        tokio::pin!(shutdown);
+        loop {
+            let report = self.reconcile().await;
+            for (name, error) in &report.failed {
+                logging::warn!("Topology reconciliation failed for {}: {}", name, error);
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(self.config.reconcile_interval) => {}
+                _ = &mut shutdown => break,
+            }
+        }
+    }
+
+    async fn connect(&self, name: &str) -> AmqpResult<Node> {
+        let config = self
+            .config
+            .connections
+            .iter()
+            .find(|(candidate, _)| candidate == name)
+            .map(|(_, config)| config.clone())
+            .ok_or_else(|| AmqpError::invalid_state(format!("Unknown connection: {}", name)))?;
+
+        let mut connection = Connection::new(config);
+        connection.open().await?;
+
+        let mut session = SessionBuilder::new().build(0, connection.id().to_string());
+        session.begin().await?;
+
+        Ok(Node {
+            connection,
+            session,
+            senders: HashMap::new(),
+            receivers: HashMap::new(),
+        })
+    }
+
+    /// Get the sender declared for an address, if attached
+    pub fn sender_mut(&mut self, address: &str) -> Option<&mut Sender> {
+        let name = self.connection_for(address)?.to_string();
+        self.nodes.get_mut(&name)?.senders.get_mut(address)
+    }
+
+    /// Get the receiver declared for an address, if attached
+    pub fn receiver_mut(&mut self, address: &str) -> Option<&mut Receiver> {
+        let name = self.connection_for(address)?.to_string();
+        self.nodes.get_mut(&name)?.receivers.get_mut(address)
+    }
+
+    /// Get declared links that are not currently attached
+    pub fn pending(&self) -> Vec<LinkSpec> {
+        self.desired
+            .iter()
+            .filter(|spec| {
+                self.connection_for(&spec.address)
+                    .and_then(|name| self.nodes.get(name))
+                    .is_none_or(|node| !node.has(spec))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Check if a named connection is currently open
+    pub fn is_connected(&self, name: &str) -> bool {
+        self.nodes.contains_key(name)
+    }
+
+    /// Get declared links
+    pub fn declared(&self) -> impl Iterator<Item = &LinkSpec> {
+        self.desired.iter()
+    }
+
+    /// Get topology configuration
+    pub fn config(&self) -> &TopologyConfig {
+        &self.config
+    }
+}
+
+async fn attach(node: &mut Node, spec: &LinkSpec) -> AmqpResult<()> {
+    let name = format!("{}-{}", node.connection.id(), spec.address);
+    match spec.kind {
+        LinkKind::Sender => {
+            let config = LinkConfig {
+                name,
+                target: Some(spec.address.clone()),
+                ..Default::default()
+            };
+            let mut sender = node.session.create_sender(config).await?;
+            sender.attach().await?;
+            node.senders.insert(spec.address.clone(), sender);
+        }
+        LinkKind::Receiver => {
+            let config = LinkConfig {
+                name,
+                source: Some(spec.address.clone()),
+                ..Default::default()
+            };
+            let mut receiver = node.session.create_receiver(config).await?;
+            receiver.attach().await?;
+            node.receivers.insert(spec.address.clone(), receiver);
+        }
+    }
+    Ok(())
+}
+
+/// Topology Builder for constructing topologies
+#[derive(Debug, Clone)]
+pub struct TopologyBuilder {
+    config: TopologyConfig,
+    desired: Vec<LinkSpec>,
+}
+
+impl TopologyBuilder {
+    /// Create a new topology builder
+    pub fn new() -> Self {
+        TopologyBuilder {
+            config: TopologyConfig::default(),
+            desired: Vec::new(),
+        }
+    }
+
+    /// Add a named connection
+    pub fn connection(mut self, name: impl Into<String>, config: ConnectionConfig) -> Self {
+        self.config.connections.push((name.into(), config));
+        self
+    }
+
+    /// Assign addresses starting with `prefix` to a named connection
+    pub fn affinity(mut self, prefix: impl Into<String>, connection: impl Into<String>) -> Self {
+        self.config.affinities.push((prefix.into(), connection.into()));
+        self
+    }
+
+    /// Set the connection used for addresses without an affinity
+    pub fn default_connection(mut self, name: impl Into<String>) -> Self {
+        self.config.default_connection = Some(name.into());
+        self
+    }
+
+    /// Declare a sender for an address
+    pub fn sender(mut self, address: impl Into<String>) -> Self {
+        self.desired.push(LinkSpec::sender(address));
+        self
+    }
+
+    /// Declare a receiver for an address
+    pub fn receiver(mut self, address: impl Into<String>) -> Self {
+        self.desired.push(LinkSpec::receiver(address));
+        self
+    }
+
+    /// Set the reconciliation interval
+    pub fn reconcile_interval(mut self, interval: Duration) -> Self {
+        self.config.reconcile_interval = interval;
+        self
+    }
+
+    /// Build the topology
+    pub fn build(self) -> Topology {
+        let mut topology = Topology::new(self.config);
+        for spec in self.desired {
+            topology.declare(spec);
+        }
+        topology
+    }
+}
+
+impl Default for TopologyBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Start a listener that accepts and holds connections
+    async fn broker() -> ConnectionConfig {
+        crate::connection::tests::local_peer().await
+    }
+
+    async fn unreachable() -> ConnectionConfig {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        ConnectionConfig {
+            hostname: "127.0.0.1".to_string(),
+            port,
+            timeout: Duration::from_millis(500),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_topology_config_default() {
+        let config = TopologyConfig::default();
+        assert!(config.connections.is_empty());
+        assert!(config.affinities.is_empty());
+        assert!(config.default_connection.is_none());
+        assert_eq!(config.reconcile_interval, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_connection_affinity() {
+        let topology = TopologyBuilder::new()
+            .connection("primary", ConnectionConfig::default())
+            .connection("audit", ConnectionConfig::default())
+            .connection("audit-eu", ConnectionConfig::default())
+            .affinity("audit.", "audit")
+            .affinity("audit.eu.", "audit-eu")
+            .build();
+
+        assert_eq!(topology.connection_for("orders"), Some("primary"));
+        assert_eq!(topology.connection_for("audit.orders"), Some("audit"));
+        assert_eq!(topology.connection_for("audit.eu.orders"), Some("audit-eu"));
+    }
+
+    #[test]
+    fn test_default_connection() {
+        let topology = TopologyBuilder::new()
+            .connection("primary", ConnectionConfig::default())
+            .connection("secondary", ConnectionConfig::default())
+            .default_connection("secondary")
+            .build();
+
+        assert_eq!(topology.connection_for("orders"), Some("secondary"));
+        assert_eq!(TopologyBuilder::new().build().connection_for("orders"), None);
+    }
+
+    #[test]
+    fn test_declare_and_undeclare() {
+        let mut topology = TopologyBuilder::new().sender("orders").build();
+        topology.declare(LinkSpec::receiver("events"));
+
+        assert_eq!(topology.declared().count(), 2);
+        assert_eq!(topology.pending().len(), 2);
+        assert!(topology.undeclare(&LinkSpec::sender("orders")));
+        assert!(!topology.undeclare(&LinkSpec::sender("orders")));
+        assert_eq!(topology.declared().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_attaches_declared_links() {
+        let mut topology = TopologyBuilder::new()
+            .connection("primary", broker().await)
+            .sender("orders")
+            .receiver("events")
+            .build();
+
+        let report = topology.reconcile().await;
+        assert!(report.is_converged(), "{:?}", report.failed);
+        assert_eq!(report.connected, vec!["primary".to_string()]);
+        assert_eq!(report.attached.len(), 2);
+        assert!(topology.pending().is_empty());
+        assert!(topology.sender_mut("orders").is_some());
+        assert!(topology.receiver_mut("events").is_some());
+
+        // A second pass has nothing to do
+        let report = topology.reconcile().await;
+        assert!(report.connected.is_empty());
+        assert!(report.attached.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_detaches_undeclared_links() {
+        let mut topology = TopologyBuilder::new()
+            .connection("primary", broker().await)
+            .sender("orders")
+            .build();
+        topology.reconcile().await;
+
+        topology.undeclare(&LinkSpec::sender("orders"));
+        let report = topology.reconcile().await;
+        assert_eq!(report.detached, vec![LinkSpec::sender("orders")]);
+        assert!(topology.sender_mut("orders").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_reports_unreachable_connection() {
+        let mut topology = TopologyBuilder::new()
+            .connection("primary", broker().await)
+            .connection("audit", unreachable().await)
+            .affinity("audit.", "audit")
+            .sender("orders")
+            .sender("audit.orders")
+            .build();
+
+        let report = topology.reconcile().await;
+        assert!(!report.is_converged());
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, "audit");
+        assert!(topology.is_connected("primary"));
+        assert!(!topology.is_connected("audit"));
+        assert_eq!(topology.pending(), vec![LinkSpec::sender("audit.orders")]);
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_reopens_closed_connection() {
+        let mut topology = TopologyBuilder::new()
+            .connection("primary", broker().await)
+            .sender("orders")
+            .build();
+        topology.reconcile().await;
+
+        topology.nodes.get_mut("primary").unwrap().connection.close().await.unwrap();
+
+        let report = topology.reconcile().await;
+        assert_eq!(report.connected, vec!["primary".to_string()]);
+        assert_eq!(report.attached, vec![LinkSpec::sender("orders")]);
+    }
+
+    #[tokio::test]
+    async fn test_run_stops_on_shutdown() {
+        let mut topology = TopologyBuilder::new()
+            .connection("primary", broker().await)
+            .sender("orders")
+            .reconcile_interval(Duration::from_millis(10))
+            .build();
+
+        topology.run(tokio::time::sleep(Duration::from_millis(50))).await;
+        assert!(topology.sender_mut("orders").is_some());
+    }
+}
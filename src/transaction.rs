@@ -0,0 +1,382 @@
+//! AMQP 1.0 Transactions
+//!
+//! This module groups transfers and their outcomes into transactions that
+//! take effect together or not at all. A transaction is declared by sending
+//! a declare message to the peer's transaction coordinator over a link of
+//! its own, and ends with a discharge on the same link, committing or
+//! rolling back. Messages sent with [`Sender::send_in_txn`] are only
+//! delivered, and deliveries accepted with
+//! [`Receiver::accept_in_txn`](crate::link::Receiver::accept_in_txn) only
+//! consumed, once the transaction commits.
+//!
+//! [`Session::begin_transaction`](crate::session::Session::begin_transaction)
+//! attaches the coordinator link and declares the transaction. Dropping a
+//! [`Transaction`] without discharging it detaches that link, on which the
+//! coordinator rolls the transaction back.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use dumq_amqp::link::LinkConfig;
+//! use dumq_amqp::session::Session;
+//! use dumq_amqp::Message;
+//!
+//! # async fn example(session: &mut Session, receiver: &mut dumq_amqp::Receiver) -> dumq_amqp::AmqpResult<()> {
+//! let mut sender = session.create_sender(LinkConfig { target: Some("invoices".to_string()), ..Default::default() }).await?;
+//! sender.attach().await?;
+//!
+//! // Move an order to invoices: both happen, or neither
+//! let (delivery_id, order) = receiver.next_delivery().await?.expect("receiver closed");
+//! let txn = session.begin_transaction().await?;
+//! sender.send_in_txn(&txn, Message::text(order.body_as_text().unwrap_or_default())).await?;
+//! receiver.accept_in_txn(&txn, delivery_id)?;
+//! txn.commit().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::link::{AttachOutcome, DeliveryOutcome, Sender};
+use crate::logging;
+use crate::message::{Body, MessageBuilder};
+use crate::performative::{descriptor, Outcome};
+use crate::types::AmqpList;
+use crate::{AmqpCondition, AmqpError, AmqpResult, AmqpSymbol, AmqpValue, Message};
+
+/// Capability asking a coordinator for local transactions
+pub const LOCAL_TRANSACTIONS: &str = "amqp:local-transactions";
+
+/// A transaction declared with a coordinator
+///
+/// Ends with [`Transaction::commit`] or [`Transaction::rollback`], either
+/// of which detaches the coordinator link.
+#[derive(Debug)]
+pub struct Transaction {
+    id: Vec<u8>,
+    coordinator: Sender,
+}
+
+impl Transaction {
+    /// Declare a transaction over an attached coordinator link
+    pub(crate) async fn declare(mut coordinator: Sender) -> AmqpResult<Self> {
+        let declare = control(descriptor::DECLARE, Vec::new());
+        match request(&mut coordinator, declare).await? {
+            DeliveryOutcome::Declared { txn_id } => {
+                logging::debug!("Declared transaction {:02x?} on '{}'", txn_id, coordinator.name());
+                Ok(Transaction { id: txn_id, coordinator })
+            }
+            other => Err(refusal("declare", other)),
+        }
+    }
+
+    /// Get the identifier the coordinator gave the transaction
+    pub fn id(&self) -> &[u8] {
+        &self.id
+    }
+
+    /// Commit the transaction, making its transfers and outcomes take effect
+    ///
+    /// Fails with the coordinator's error if it cannot commit, e.g.
+    /// `amqp:transaction:rollback` when it rolled back instead.
+    pub async fn commit(self) -> AmqpResult<()> {
+        self.discharge(false).await
+    }
+
+    /// Roll the transaction back, discarding its transfers and outcomes
+    pub async fn rollback(self) -> AmqpResult<()> {
+        self.discharge(true).await
+    }
+
+    async fn discharge(mut self, fail: bool) -> AmqpResult<()> {
+        let discharge = control(
+            descriptor::DISCHARGE,
+            vec![AmqpValue::Binary(self.id.clone().into()), AmqpValue::Boolean(fail)],
+        );
+        match request(&mut self.coordinator, discharge).await? {
+            DeliveryOutcome::Accepted => {
+                logging::debug!(
+                    "{} transaction {:02x?}",
+                    if fail { "Rolled back" } else { "Committed" },
+                    self.id
+                );
+                self.coordinator.close().await
+            }
+            other => Err(refusal("discharge", other)),
+        }
+    }
+
+    /// Get the state tying a transfer or its outcome to this transaction
+    pub(crate) fn state(&self, outcome: Option<Outcome>) -> Outcome {
+        Outcome::Transactional {
+            txn_id: self.id.clone(),
+            outcome: outcome.map(Box::new),
+        }
+    }
+}
+
+/// Attach a coordinator link, failing if the peer refuses it
+pub(crate) async fn attach_coordinator(coordinator: &mut Sender) -> AmqpResult<()> {
+    match coordinator.attach().await? {
+        AttachOutcome::Attached { .. } => Ok(()),
+        AttachOutcome::Refused { error } => Err(match error {
+            Some(error) => AmqpError::amqp_protocol(
+                error.condition,
+                error.description.unwrap_or_else(|| "Coordinator refused the link".to_string()),
+            ),
+            None => AmqpError::amqp_protocol(AmqpCondition::AmqpErrorNotImplemented, "Peer has no transaction coordinator"),
+        }),
+    }
+}
+
+/// Send a declare or discharge once the coordinator grants credit, and wait for its outcome
+async fn request(coordinator: &mut Sender, message: Message) -> AmqpResult<DeliveryOutcome> {
+    let deadline = coordinator.config().attach_timeout;
+    tokio::time::timeout(deadline, async {
+        while coordinator.credit() == 0 {
+            coordinator.wait_for_peer().await?;
+        }
+        let delivery = coordinator.send(message).await?;
+        coordinator.outcome(delivery).await
+    })
+    .await
+    .map_err(|_| AmqpError::timeout(format!("Coordinator '{}' did not answer within {:?}", coordinator.name(), deadline)))?
+}
+
+/// Build a message whose body is a coordinator control, as a described list
+fn control(code: u64, fields: Vec<AmqpValue>) -> Message {
+    MessageBuilder::new()
+        .body(Body::Value(AmqpValue::described(code, AmqpValue::List(AmqpList::from(fields)))))
+        .build()
+}
+
+/// Turn the outcome of a refused declare or discharge into an error
+fn refusal(request: &str, outcome: DeliveryOutcome) -> AmqpError {
+    match outcome {
+        DeliveryOutcome::Rejected(error) => AmqpError::amqp_protocol(
+            error.condition,
+            error
+                .description
+                .unwrap_or_else(|| format!("Coordinator rejected the {}", request)),
+        ),
+        other => AmqpError::protocol(format!("Coordinator answered {} with {:?}", request, other)),
+    }
+}
+
+/// Get the capabilities a coordinator link asks for by default
+pub(crate) fn default_capabilities() -> Vec<AmqpSymbol> {
+    vec![AmqpSymbol::from(LOCAL_TRANSACTIONS)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::Decoder;
+    use crate::link::{LinkBuilder, LinkConfig};
+    use crate::performative::{Attach, Coordinator, Detach, Disposition, Endpoint, Flow, Performative};
+    use crate::types::{self, Descriptor, Role};
+    use tokio::time::Duration;
+
+    /// Answer the Attach of a link with credit, then each transfer with an
+    /// outcome, collecting the bodies until the link detaches
+    fn spawn_peer<F>(endpoint: Endpoint, mut answer: F) -> tokio::task::JoinHandle<Vec<Performative>>
+    where
+        F: FnMut(&AmqpValue) -> Option<Outcome> + Send + 'static,
+    {
+        tokio::spawn(async move {
+            let mut seen = Vec::new();
+            while let Some(performative) = endpoint.recv().await {
+                match &performative {
+                    Performative::Attach(attach) => {
+                        let handle = attach.handle;
+                        let (role, initial_delivery_count) = match attach.role {
+                            Role::Sender => (Role::Receiver, None),
+                            Role::Receiver => (Role::Sender, Some(0)),
+                        };
+                        endpoint.send(Performative::Attach(Attach { role, initial_delivery_count, ..attach.clone() })).unwrap();
+                        if role == Role::Receiver {
+                            let flow = Flow { handle: Some(handle), delivery_count: Some(0), link_credit: Some(10), ..Default::default() };
+                            endpoint.send(Performative::Flow(flow)).unwrap();
+                        }
+                    }
+                    Performative::Transfer(transfer) => {
+                        let message = Decoder::new(transfer.payload.clone()).decode_message().unwrap();
+                        let Some(Body::Value(body)) = &message.body else { panic!("unexpected body {:?}", message.body) };
+                        if let Some(outcome) = answer(body) {
+                            let first = transfer.delivery_id.unwrap();
+                            let settled = Disposition { role: Role::Receiver, first, last: None, settled: true, state: Some(outcome), batchable: false };
+                            endpoint.send(Performative::Disposition(settled)).unwrap();
+                        }
+                    }
+                    Performative::Detach(detach) => {
+                        let _ = endpoint.send(Performative::Detach(Detach { error: None, ..detach.clone() }));
+                        seen.push(performative);
+                        break;
+                    }
+                    _ => {}
+                }
+                seen.push(performative);
+            }
+            seen
+        })
+    }
+
+    fn coordinator_link() -> (Sender, Endpoint) {
+        let (local, remote) = Endpoint::pair();
+        let config = LinkConfig {
+            name: "coordinator".to_string(),
+            coordinator: Some(Coordinator { capabilities: default_capabilities() }),
+            attach_timeout: Duration::from_secs(5),
+            ..Default::default()
+        };
+        let mut sender = Sender::new(config, "session-1".to_string());
+        sender.set_endpoint(local);
+        (sender, remote)
+    }
+
+    fn control_code(body: &AmqpValue) -> (u64, Vec<AmqpValue>) {
+        match body {
+            AmqpValue::Described(descriptor, fields) => match (descriptor.as_ref(), fields.as_ref()) {
+                (Descriptor::Code(code), AmqpValue::List(fields)) => (*code, fields.iter().cloned().collect()),
+                other => panic!("unexpected control {:?}", other),
+            },
+            other => panic!("unexpected control {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_declare_and_commit_over_coordinator_link() {
+        let (mut coordinator, remote) = coordinator_link();
+        let peer = spawn_peer(remote, |body| match control_code(body) {
+            (descriptor::DECLARE, fields) => {
+                assert!(fields.is_empty());
+                Some(Outcome::Declared { txn_id: b"txn-1".to_vec() })
+            }
+            (descriptor::DISCHARGE, fields) => {
+                assert_eq!(fields, vec![AmqpValue::Binary(b"txn-1".to_vec().into()), AmqpValue::Boolean(false)]);
+                Some(Outcome::Accepted)
+            }
+            other => panic!("unexpected control {:?}", other),
+        });
+
+        attach_coordinator(&mut coordinator).await.unwrap();
+        let txn = Transaction::declare(coordinator).await.unwrap();
+        assert_eq!(txn.id(), b"txn-1");
+        assert_eq!(
+            txn.state(Some(Outcome::Accepted)),
+            Outcome::Transactional { txn_id: b"txn-1".to_vec(), outcome: Some(Box::new(Outcome::Accepted)) }
+        );
+        txn.commit().await.unwrap();
+
+        let seen = peer.await.unwrap();
+        match &seen[0] {
+            Performative::Attach(attach) => {
+                assert_eq!(attach.coordinator.as_ref().unwrap().capabilities, vec![AmqpSymbol::from(LOCAL_TRANSACTIONS)]);
+                assert!(attach.target.is_none());
+            }
+            other => panic!("Expected attach, got {:?}", other),
+        }
+        assert!(matches!(seen.last(), Some(Performative::Detach(Detach { closed: true, .. }))));
+    }
+
+    #[tokio::test]
+    async fn test_session_declares_with_served_coordinator() {
+        use crate::connection::ConnectionBuilder;
+        use crate::server::AmqpListener;
+
+        let listener = AmqpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let mut connection = listener.accept().await?;
+            let mut session = connection.next_session().await.unwrap();
+            session.accept()?;
+            let link = session.next_link().await.unwrap();
+            assert!(link.remote_attach().coordinator.is_some());
+            let mut coordinator = link.accept_receiver()?;
+            coordinator.add_credit(2);
+            let mut controls = Vec::new();
+            for outcome in [Outcome::Declared { txn_id: b"txn-1".to_vec() }, Outcome::Accepted] {
+                let (delivery_id, message) = coordinator.next_delivery().await?.unwrap();
+                let Some(Body::Value(body)) = message.body else { panic!("unexpected body") };
+                controls.push(control_code(&body));
+                coordinator.settle(&[delivery_id], outcome)?;
+            }
+            // Discharging detaches the coordinator link
+            assert!(coordinator.next_delivery().await.is_err());
+            assert_eq!(coordinator.state(), &crate::link::LinkState::Detached);
+            assert!(session.next_link().await.is_none());
+            connection.close().await?;
+            Ok::<_, AmqpError>(controls)
+        });
+
+        let mut connection = ConnectionBuilder::new().hostname("127.0.0.1").port(port).build();
+        connection.open().await.unwrap();
+        let session = connection.create_session().await.unwrap();
+        let txn = session.begin_transaction().await.unwrap();
+        assert_eq!(txn.id(), b"txn-1");
+        txn.rollback().await.unwrap();
+        connection.close().await.unwrap();
+
+        let controls = server.await.unwrap().unwrap();
+        assert_eq!(controls[0], (descriptor::DECLARE, Vec::new()));
+        assert_eq!(controls[1], (descriptor::DISCHARGE, vec![AmqpValue::Binary(b"txn-1".to_vec().into()), AmqpValue::Boolean(true)]));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_discharge_reports_coordinator_error() {
+        let (mut coordinator, remote) = coordinator_link();
+        let peer = spawn_peer(remote, |body| match control_code(body) {
+            (descriptor::DECLARE, _) => Some(Outcome::Declared { txn_id: vec![7] }),
+            (descriptor::DISCHARGE, _) => Some(Outcome::Rejected {
+                error: Some(
+                    types::AmqpError::new(AmqpCondition::from("amqp:transaction:rollback"))
+                        .with_description("Transaction timed out"),
+                ),
+            }),
+            other => panic!("unexpected control {:?}", other),
+        });
+
+        attach_coordinator(&mut coordinator).await.unwrap();
+        let txn = Transaction::declare(coordinator).await.unwrap();
+        let error = txn.commit().await.unwrap_err();
+        assert_eq!(error.condition(), Some(&AmqpCondition::from("amqp:transaction:rollback")));
+        assert!(error.to_string().contains("Transaction timed out"), "{}", error);
+        peer.abort();
+    }
+
+    #[tokio::test]
+    async fn test_transfers_and_acceptance_carry_transactional_state() {
+        let txn = Transaction { id: b"txn-9".to_vec(), coordinator: Sender::new(LinkConfig::default(), "session-1".to_string()) };
+
+        let (local, remote) = Endpoint::pair();
+        let mut sender = LinkBuilder::new().name("orders-sender").target("orders").build_sender("session-1".to_string());
+        sender.set_endpoint(local);
+        let peer = spawn_peer(remote, |_| {
+            Some(Outcome::Transactional { txn_id: b"txn-9".to_vec(), outcome: Some(Box::new(Outcome::Accepted)) })
+        });
+        sender.attach().await.unwrap();
+        while sender.credit() == 0 {
+            sender.wait_for_peer().await.unwrap();
+        }
+        let delivery = sender.send_in_txn(&txn, Message::text("order-1")).await.unwrap();
+        assert_eq!(sender.outcome(delivery).await.unwrap(), DeliveryOutcome::Accepted);
+        sender.close().await.unwrap();
+        let transfer = peer.await.unwrap().into_iter().find_map(|performative| match performative {
+            Performative::Transfer(transfer) => Some(transfer),
+            _ => None,
+        });
+        assert_eq!(transfer.unwrap().state, Some(txn.state(None)));
+
+        let (local, remote) = Endpoint::pair();
+        let mut receiver = LinkBuilder::new().name("orders-receiver").source("orders").build_receiver("session-1".to_string());
+        receiver.set_endpoint(local);
+        let peer = spawn_peer(remote, |_| None);
+        receiver.attach().await.unwrap();
+        let delivery_id = receiver.simulate_receive(Message::text("order-2"));
+        receiver.accept_in_txn(&txn, delivery_id).unwrap();
+        receiver.close().await.unwrap();
+        let disposition = peer.await.unwrap().into_iter().find_map(|performative| match performative {
+            Performative::Disposition(disposition) => Some(disposition),
+            _ => None,
+        });
+        assert_eq!(disposition.unwrap().state, Some(txn.state(Some(Outcome::Accepted))));
+    }
+}
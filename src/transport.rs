@@ -1,7 +1,15 @@
-use crate::{AmqpError, AmqpResult};
-use bytes::{Buf, BufMut, Bytes, BytesMut};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use crate::logging;
+use crate::{AmqpCondition, AmqpError, AmqpResult};
+use bytes::{Buf, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf, ReadHalf, WriteHalf};
 use tokio::net::TcpStream;
+use std::collections::VecDeque;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::Instant;
 
 /// AMQP 1.0 Frame types
 #[repr(u8)]
@@ -11,7 +19,7 @@ pub enum FrameType {
 }
 
 /// AMQP 1.0 Frame header
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FrameHeader {
     /// Frame size (excluding the size field itself)
     pub size: u32,
@@ -36,12 +44,18 @@ impl FrameHeader {
 
     /// Encode the frame header
     pub fn encode(&self) -> Vec<u8> {
-        let mut buffer = BytesMut::new();
-        buffer.put_u32(self.size);
-        buffer.put_u8(self.data_offset);
-        buffer.put_u8(self.frame_type);
-        buffer.put_u16(self.channel);
-        buffer.to_vec()
+        self.to_bytes().to_vec()
+    }
+
+    /// Encode the frame header without allocating
+    #[inline]
+    pub fn to_bytes(&self) -> [u8; 8] {
+        let mut bytes = [0u8; 8];
+        bytes[..4].copy_from_slice(&self.size.to_be_bytes());
+        bytes[4] = self.data_offset;
+        bytes[5] = self.frame_type;
+        bytes[6..].copy_from_slice(&self.channel.to_be_bytes());
+        bytes
     }
 
     /// Decode a frame header from bytes
@@ -50,7 +64,7 @@ impl FrameHeader {
             return Err(AmqpError::decoding("Insufficient data for frame header"));
         }
 
-        let mut buffer = Bytes::copy_from_slice(data);
+        let mut buffer = &data[..8];
         let size = buffer.get_u32();
         let data_offset = buffer.get_u8();
         let frame_type = buffer.get_u8();
@@ -82,10 +96,10 @@ impl Frame {
 
     /// Encode the frame
     pub fn encode(&self) -> Vec<u8> {
-        let mut buffer = BytesMut::new();
-        buffer.extend_from_slice(&self.header.encode());
+        let mut buffer = Vec::with_capacity(8 + self.payload.len());
+        buffer.extend_from_slice(&self.header.to_bytes());
         buffer.extend_from_slice(&self.payload);
-        buffer.to_vec()
+        buffer
     }
 
     /// Decode a frame from bytes
@@ -101,83 +115,505 @@ impl Frame {
     }
 }
 
+/// Transport read/write statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TransportStats {
+    /// Total bytes read
+    pub bytes_read: u64,
+    /// Total bytes written
+    pub bytes_written: u64,
+    /// Frames received
+    pub frames_in: u64,
+    /// Frames sent
+    pub frames_out: u64,
+    /// Time of the last successful read
+    pub last_read: Option<Instant>,
+    /// Time of the last successful write
+    pub last_write: Option<Instant>,
+}
+
+impl TransportStats {
+    /// Get the time of the last read or write
+    pub fn last_activity(&self) -> Option<Instant> {
+        match (self.last_read, self.last_write) {
+            (Some(read), Some(write)) => Some(read.max(write)),
+            (read, write) => read.or(write),
+        }
+    }
+
+    fn record_read(&mut self, bytes: usize) {
+        self.bytes_read += bytes as u64;
+        self.last_read = Some(Instant::now());
+    }
+
+    fn record_write(&mut self, bytes: usize) {
+        self.bytes_written += bytes as u64;
+        self.last_write = Some(Instant::now());
+    }
+}
+
+/// Direction of a frame on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameDirection {
+    /// Received from the peer
+    Incoming,
+    /// Sent to the peer
+    Outgoing,
+}
+
+impl fmt::Display for FrameDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FrameDirection::Incoming => "in",
+            FrameDirection::Outgoing => "out",
+        })
+    }
+}
+
+/// A frame captured by a [`FrameRecorder`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedFrame {
+    /// Sequence number of the frame on its transport, as logged
+    pub seq: u64,
+    /// Whether the frame was sent or received
+    pub direction: FrameDirection,
+    /// Frame header
+    pub header: FrameHeader,
+    /// Frame payload
+    pub payload: Vec<u8>,
+    /// Time the frame was sent or received
+    pub at: Instant,
+}
+
+/// Capture of the latest frames a transport sent and received
+///
+/// Every frame carries the sequence number its transport assigned it, which
+/// is also the `#N` in the transport's debug log lines, so a log line leads
+/// straight to the bytes on the wire. Clones share the capture, so one can
+/// be kept for inspection while another is given to
+/// [`Transport::record_frames`].
+#[derive(Debug, Clone)]
+pub struct FrameRecorder {
+    frames: Arc<Mutex<VecDeque<RecordedFrame>>>,
+    capacity: usize,
+}
+
+impl FrameRecorder {
+    /// Create a recorder keeping the latest `capacity` frames
+    pub fn new(capacity: usize) -> Self {
+        FrameRecorder {
+            frames: Arc::new(Mutex::new(VecDeque::new())),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Get the captured frames, oldest first
+    pub fn frames(&self) -> Vec<RecordedFrame> {
+        self.lock().iter().cloned().collect()
+    }
+
+    /// Get the captured frame with the given sequence number
+    pub fn find(&self, seq: u64) -> Option<RecordedFrame> {
+        self.lock().iter().find(|frame| frame.seq == seq).cloned()
+    }
+
+    /// Drop every captured frame
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn record(&self, frame: RecordedFrame) {
+        let mut frames = self.lock();
+        if frames.len() == self.capacity {
+            frames.pop_front();
+        }
+        frames.push_back(frame);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<RecordedFrame>> {
+        // A panic while holding the lock leaves the capture usable
+        self.frames.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Oversized frame being drained by [`Transport::poll_recv_frame`]
+#[derive(Debug, Clone, Copy)]
+struct Discard {
+    remaining: u64,
+    frame_size: u64,
+}
+
+/// Bytes a reader makes room for before each read
+const READ_CHUNK: usize = 4096;
+
+/// Take the next whole frame off a read buffer, if it holds one
+///
+/// An oversized frame is dropped from the buffer as its bytes arrive, and
+/// reported as `amqp:connection:framing-error` once it is gone.
+fn next_buffered_frame(
+    read_buffer: &mut BytesMut,
+    discarding: &mut Option<Discard>,
+    max_frame_size: u32,
+) -> Option<AmqpResult<(FrameHeader, Vec<u8>)>> {
+    loop {
+        if let Some(discard) = discarding {
+            let dropped = (discard.remaining as usize).min(read_buffer.len());
+            read_buffer.advance(dropped);
+            discard.remaining -= dropped as u64;
+            if discard.remaining > 0 {
+                return None;
+            }
+            let frame_size = discard.frame_size;
+            *discarding = None;
+            logging::warn!("Discarded oversized frame: {} bytes (max {})", frame_size, max_frame_size);
+            return Some(Err(AmqpError::amqp_protocol(
+                AmqpCondition::AmqpErrorFramingError,
+                format!("Frame size {} exceeds maximum {}", frame_size, max_frame_size),
+            )));
+        }
+        if read_buffer.len() < 8 {
+            return None;
+        }
+        let header = match FrameHeader::decode(&read_buffer[..8]) {
+            Ok(header) => header,
+            Err(e) => return Some(Err(e)),
+        };
+        let frame_size = 8 + header.size as u64;
+        if frame_size > max_frame_size as u64 {
+            read_buffer.advance(8);
+            *discarding = Some(Discard {
+                remaining: header.size as u64,
+                frame_size,
+            });
+            continue;
+        }
+        if (read_buffer.len() as u64) < frame_size {
+            return None;
+        }
+        // Copied out rather than split off, so the read buffer keeps its allocation
+        let payload = read_buffer[8..frame_size as usize].to_vec();
+        read_buffer.advance(frame_size as usize);
+        return Some(Ok((header, payload)));
+    }
+}
+
+/// The error for the peer closing with the read buffer in the given state
+fn closed_while_reading(read_buffer: &mut BytesMut, discarding: &mut Option<Discard>) -> AmqpError {
+    if let Some(discard) = discarding.take() {
+        return AmqpError::transport_closed_mid_frame(discard.frame_size, discard.frame_size - discard.remaining);
+    }
+    let received = read_buffer.len() as u64;
+    if received == 0 {
+        return AmqpError::transport("Connection closed while reading frame");
+    }
+    let expected = match FrameHeader::decode(read_buffer) {
+        Ok(header) if received >= 8 => 8 + header.size as u64,
+        _ => 8,
+    };
+    read_buffer.clear();
+    AmqpError::transport_closed_mid_frame(expected, received)
+}
+
+/// Log a numbered frame and hand it to the recorder, if any
+fn log_frame(
+    seq: u64,
+    recorder: Option<&FrameRecorder>,
+    direction: FrameDirection,
+    header: &FrameHeader,
+    payload: &[u8],
+) {
+    logging::debug!(
+        "Frame #{} {}: type {}, channel {}, {} bytes",
+        seq,
+        direction,
+        header.frame_type,
+        header.channel,
+        payload.len()
+    );
+    if let Some(recorder) = recorder {
+        recorder.record(RecordedFrame {
+            seq,
+            direction,
+            header: header.clone(),
+            payload: payload.to_vec(),
+            at: Instant::now(),
+        });
+    }
+}
+
+/// A byte stream a [`Transport`] runs over, such as a TCP or TLS stream
+pub trait TransportStream: AsyncRead + AsyncWrite + Send + Unpin + fmt::Debug + 'static {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin + fmt::Debug + 'static> TransportStream for T {}
+
 /// AMQP 1.0 Transport layer
+///
+/// Frames can be sent and received with async methods or, for select loops
+/// and custom executors, with [`Transport::poll_send_frame`],
+/// [`Transport::poll_flush`] and [`Transport::poll_recv_frame`]. The two
+/// styles can be mixed: bytes buffered by the poll methods are completed
+/// before the async methods touch the stream.
+///
+/// Every frame sent or received is numbered from 1, in one sequence for both
+/// directions, and logged at debug level with its number.
 #[derive(Debug)]
 pub struct Transport {
-    /// TCP stream
-    stream: TcpStream,
-    /// Read buffer
-    _read_buffer: BytesMut,
-    /// Write buffer
-    _write_buffer: BytesMut,
+    /// Underlying stream
+    stream: Box<dyn TransportStream>,
+    /// Bytes read by `poll_recv_frame` that do not form a whole frame yet
+    read_buffer: BytesMut,
+    /// Frames accepted by `poll_send_frame` but not yet written
+    write_buffer: BytesMut,
+    /// Oversized frame still being drained
+    discarding: Option<Discard>,
+    /// Read/write statistics
+    stats: TransportStats,
+    /// Largest frame accepted on the receive path
+    max_frame_size: u32,
+    /// Sequence number of the last frame sent or received
+    frame_seq: u64,
+    /// Capture of the frames sent and received, if any
+    recorder: Option<FrameRecorder>,
 }
 
 impl Transport {
-    /// Create a new transport from a TCP stream
-    pub fn new(stream: TcpStream) -> Self {
+    /// Create a new transport over a stream
+    pub fn new(stream: impl TransportStream) -> Self {
         Transport {
-            stream,
-            _read_buffer: BytesMut::new(),
-            _write_buffer: BytesMut::new(),
+            stream: Box::new(stream),
+            read_buffer: BytesMut::new(),
+            write_buffer: BytesMut::new(),
+            discarding: None,
+            stats: TransportStats::default(),
+            max_frame_size: constants::DEFAULT_MAX_FRAME_SIZE,
+            frame_seq: 0,
+            recorder: None,
+        }
+    }
+
+    /// Get the maximum accepted frame size
+    pub fn max_frame_size(&self) -> u32 {
+        self.max_frame_size
+    }
+
+    /// Set the maximum accepted frame size
+    pub fn set_max_frame_size(&mut self, max_frame_size: u32) {
+        self.max_frame_size = max_frame_size;
+    }
+
+    /// Get read/write statistics
+    pub fn stats(&self) -> TransportStats {
+        self.stats
+    }
+
+    /// Get the sequence number of the last frame sent or received, 0 if none
+    pub fn frame_seq(&self) -> u64 {
+        self.frame_seq
+    }
+
+    /// Capture every frame sent or received from now on
+    pub fn record_frames(&mut self, recorder: FrameRecorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Number a frame, log it and hand it to the recorder
+    fn track_frame(&mut self, direction: FrameDirection, header: &FrameHeader, payload: &[u8]) {
+        self.frame_seq += 1;
+        match direction {
+            FrameDirection::Incoming => self.stats.frames_in += 1,
+            FrameDirection::Outgoing => self.stats.frames_out += 1,
         }
+        log_frame(self.frame_seq, self.recorder.as_ref(), direction, header, payload);
     }
 
     /// Send a frame
+    ///
+    /// The frame is written through the transport's write buffer, so once
+    /// the buffer has grown to the usual frame size sending does not allocate.
     pub async fn send_frame(&mut self, frame: Frame) -> AmqpResult<()> {
-        let encoded = frame.encode();
-        self.stream.write_all(&encoded).await
+        self.flush_buffered().await?;
+        self.write_buffer.extend_from_slice(&frame.header.to_bytes());
+        self.write_buffer.extend_from_slice(&frame.payload);
+        self.track_frame(FrameDirection::Outgoing, &frame.header, &frame.payload);
+        self.flush_buffered().await
+    }
+
+    /// Send a frame that is already encoded (header followed by payload)
+    pub async fn send_encoded_frame(&mut self, data: &[u8]) -> AmqpResult<()> {
+        if data.len() < 8 {
+            return Err(AmqpError::encoding("Insufficient data for frame"));
+        }
+        let header = FrameHeader::decode(data)?;
+        self.flush_buffered().await?;
+        self.stream.write_all(data).await
             .map_err(|e| AmqpError::transport(format!("Failed to write frame: {}", e)))?;
         self.stream.flush().await
             .map_err(|e| AmqpError::transport(format!("Failed to flush stream: {}", e)))?;
+        self.stats.record_write(data.len());
+        self.track_frame(FrameDirection::Outgoing, &header, &data[8..]);
         Ok(())
     }
 
     /// Receive a frame
     pub async fn receive_frame(&mut self) -> AmqpResult<Frame> {
+        // Complete a frame partly read through poll_recv_frame
+        if !self.read_buffer.is_empty() || self.discarding.is_some() {
+            return std::future::poll_fn(|cx| self.poll_recv_frame(cx)).await;
+        }
+
         // Read frame header (8 bytes)
         let mut header_buffer = [0u8; 8];
-        self.stream.read_exact(&mut header_buffer).await
+        let read = self.read_full(&mut header_buffer).await
             .map_err(|e| AmqpError::transport(format!("Failed to read frame header: {}", e)))?;
+        self.stats.record_read(read);
+        match read {
+            0 => return Err(AmqpError::transport("Connection closed while reading frame")),
+            8 => {}
+            _ => return Err(AmqpError::transport_closed_mid_frame(8, read as u64)),
+        }
 
         let header = FrameHeader::decode(&header_buffer)?;
+
+        // Frames beyond the limit are drained without buffering, then rejected
+        let frame_size = header_buffer.len() as u64 + header.size as u64;
+        if frame_size > self.max_frame_size as u64 {
+            let discarded = self.discard(header.size as u64).await?;
+            logging::warn!(
+                "Discarded oversized frame: {} bytes (max {})",
+                header_buffer.len() as u64 + discarded,
+                self.max_frame_size
+            );
+            return Err(AmqpError::amqp_protocol(
+                AmqpCondition::AmqpErrorFramingError,
+                format!("Frame size {} exceeds maximum {}", frame_size, self.max_frame_size),
+            ));
+        }
         
         // Read frame payload
         let mut payload = vec![0u8; header.size as usize];
-        self.stream.read_exact(&mut payload).await
+        let read = self.read_full(&mut payload).await
             .map_err(|e| AmqpError::transport(format!("Failed to read frame payload: {}", e)))?;
+        self.stats.record_read(read);
+        if read < payload.len() {
+            return Err(AmqpError::transport_closed_mid_frame(8 + payload.len() as u64, 8 + read as u64));
+        }
+        self.track_frame(FrameDirection::Incoming, &header, &payload);
 
         Ok(Frame::new(header, payload))
     }
 
+    /// Read until the buffer is full or the peer closes, returning the bytes read
+    async fn read_full(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match self.stream.read(&mut buffer[filled..]).await? {
+                0 => break,
+                read => filled += read,
+            }
+        }
+        Ok(filled)
+    }
+
+    /// Read and throw away the payload of a frame with the given payload size
+    async fn discard(&mut self, len: u64) -> AmqpResult<u64> {
+        let mut limited = (&mut self.stream).take(len);
+        let discarded = tokio::io::copy(&mut limited, &mut tokio::io::sink()).await
+            .map_err(|e| AmqpError::transport(format!("Failed to discard frame payload: {}", e)))?;
+        self.stats.record_read(discarded as usize);
+        if discarded < len {
+            return Err(AmqpError::transport_closed_mid_frame(8 + len, 8 + discarded));
+        }
+        Ok(discarded)
+    }
+
     /// Send raw data
     pub async fn send_raw(&mut self, data: &[u8]) -> AmqpResult<()> {
+        self.flush_buffered().await?;
         self.stream.write_all(data).await
             .map_err(|e| AmqpError::transport(format!("Failed to write data: {}", e)))?;
         self.stream.flush().await
             .map_err(|e| AmqpError::transport(format!("Failed to flush stream: {}", e)))?;
+        self.stats.record_write(data.len());
         Ok(())
     }
 
     /// Receive raw data
     pub async fn receive_raw(&mut self, size: usize) -> AmqpResult<Vec<u8>> {
-        let mut buffer = vec![0u8; size];
-        self.stream.read_exact(&mut buffer).await
+        let buffered = self.read_buffer.len().min(size);
+        let mut buffer = self.read_buffer.split_to(buffered).to_vec();
+        buffer.resize(size, 0);
+        self.stream.read_exact(&mut buffer[buffered..]).await
             .map_err(|e| AmqpError::transport(format!("Failed to read data: {}", e)))?;
+        self.stats.record_read(size - buffered);
         Ok(buffer)
     }
 
-    /// Check if the transport is readable
-    pub async fn readable(&mut self) -> AmqpResult<()> {
-        self.stream.readable().await
-            .map_err(|e| AmqpError::transport(format!("Stream not readable: {}", e)))?;
-        Ok(())
+    /// Poll for the next frame
+    ///
+    /// Partial frames are kept between polls, so a `Pending` result loses
+    /// nothing. Oversized frames are drained and then reported as
+    /// `amqp:connection:framing-error`, as with [`Transport::receive_frame`].
+    pub fn poll_recv_frame(&mut self, cx: &mut Context<'_>) -> Poll<AmqpResult<Frame>> {
+        loop {
+            if let Some(frame) = next_buffered_frame(&mut self.read_buffer, &mut self.discarding, self.max_frame_size) {
+                let (header, payload) = frame?;
+                self.track_frame(FrameDirection::Incoming, &header, &payload);
+                return Poll::Ready(Ok(Frame::new(header, payload)));
+            }
+
+            let buffered = self.read_buffer.len();
+            self.read_buffer.resize(buffered + READ_CHUNK, 0);
+            let mut buf = ReadBuf::new(&mut self.read_buffer[buffered..]);
+            let polled = Pin::new(&mut self.stream).poll_read(cx, &mut buf);
+            let read = buf.filled().len();
+            self.read_buffer.truncate(buffered + read);
+            match ready!(polled) {
+                Ok(()) if read == 0 => return Poll::Ready(Err(closed_while_reading(&mut self.read_buffer, &mut self.discarding))),
+                Ok(()) => self.stats.record_read(read),
+                Err(e) => return Poll::Ready(Err(AmqpError::transport(format!("Failed to read frame: {}", e)))),
+            }
+        }
     }
 
-    /// Check if the transport is writable
-    pub async fn writable(&mut self) -> AmqpResult<()> {
-        self.stream.writable().await
-            .map_err(|e| AmqpError::transport(format!("Stream not writable: {}", e)))?;
-        Ok(())
+    /// Poll to queue a frame for sending
+    ///
+    /// Returns `Pending` while earlier frames are still being written; the
+    /// same frame should then be offered again. Once `Ready(Ok)`, the frame
+    /// is queued and as much of it written as the socket takes; call
+    /// [`Transport::poll_flush`] until it is ready to finish writing.
+    pub fn poll_send_frame(&mut self, cx: &mut Context<'_>, frame: &Frame) -> Poll<AmqpResult<()>> {
+        ready!(self.poll_flush(cx))?;
+        self.write_buffer.extend_from_slice(&frame.header.to_bytes());
+        self.write_buffer.extend_from_slice(&frame.payload);
+        self.track_frame(FrameDirection::Outgoing, &frame.header, &frame.payload);
+        match self.poll_flush(cx) {
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            _ => Poll::Ready(Ok(())),
+        }
+    }
+
+    /// Poll to write every queued frame
+    pub fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<AmqpResult<()>> {
+        while !self.write_buffer.is_empty() {
+            let written = ready!(Pin::new(&mut self.stream).poll_write(cx, &self.write_buffer))
+                .map_err(|e| AmqpError::transport(format!("Failed to write frame: {}", e)))?;
+            if written == 0 {
+                return Poll::Ready(Err(AmqpError::transport("Connection closed while writing frame")));
+            }
+            self.write_buffer.advance(written);
+            self.stats.record_write(written);
+        }
+        ready!(Pin::new(&mut self.stream).poll_flush(cx))
+            .map_err(|e| AmqpError::transport(format!("Failed to flush stream: {}", e)))?;
+        Poll::Ready(Ok(()))
+    }
+
+    /// Write frames queued by `poll_send_frame` before writing anything else
+    async fn flush_buffered(&mut self) -> AmqpResult<()> {
+        if self.write_buffer.is_empty() {
+            return Ok(());
+        }
+        std::future::poll_fn(|cx| self.poll_flush(cx)).await
     }
 
     /// Shutdown the transport
@@ -186,6 +622,161 @@ impl Transport {
             .map_err(|e| AmqpError::transport(format!("Failed to shutdown stream: {}", e)))?;
         Ok(())
     }
+
+    /// Split into halves that receive and send independently
+    ///
+    /// Bytes already buffered in either direction go with their half, and
+    /// both halves continue the one frame sequence. Each half keeps the
+    /// statistics of its own direction.
+    pub fn into_split(self) -> (TransportReader, TransportWriter) {
+        let (read, write) = tokio::io::split(self.stream);
+        let frame_seq = Arc::new(AtomicU64::new(self.frame_seq));
+        let reader = TransportReader {
+            stream: read,
+            read_buffer: self.read_buffer,
+            discarding: self.discarding,
+            stats: TransportStats {
+                bytes_written: 0,
+                frames_out: 0,
+                last_write: None,
+                ..self.stats
+            },
+            max_frame_size: self.max_frame_size,
+            frame_seq: frame_seq.clone(),
+            recorder: self.recorder.clone(),
+        };
+        let writer = TransportWriter {
+            stream: write,
+            write_buffer: self.write_buffer,
+            stats: TransportStats {
+                bytes_read: 0,
+                frames_in: 0,
+                last_read: None,
+                ..self.stats
+            },
+            frame_seq,
+            recorder: self.recorder,
+        };
+        (reader, writer)
+    }
+}
+
+/// Receiving half of a [`Transport`], see [`Transport::into_split`]
+#[derive(Debug)]
+pub struct TransportReader {
+    stream: ReadHalf<Box<dyn TransportStream>>,
+    read_buffer: BytesMut,
+    discarding: Option<Discard>,
+    stats: TransportStats,
+    max_frame_size: u32,
+    frame_seq: Arc<AtomicU64>,
+    recorder: Option<FrameRecorder>,
+}
+
+impl TransportReader {
+    /// Receive a frame
+    ///
+    /// Cancel safe: bytes read before the future is dropped stay buffered
+    /// for the next call.
+    pub async fn receive_frame(&mut self) -> AmqpResult<Frame> {
+        loop {
+            if let Some(frame) = next_buffered_frame(&mut self.read_buffer, &mut self.discarding, self.max_frame_size) {
+                let (header, payload) = frame?;
+                self.stats.frames_in += 1;
+                let seq = self.frame_seq.fetch_add(1, Ordering::Relaxed) + 1;
+                log_frame(seq, self.recorder.as_ref(), FrameDirection::Incoming, &header, &payload);
+                return Ok(Frame::new(header, payload));
+            }
+
+            self.read_buffer.reserve(READ_CHUNK);
+            match self.stream.read_buf(&mut self.read_buffer).await {
+                Ok(0) => return Err(closed_while_reading(&mut self.read_buffer, &mut self.discarding)),
+                Ok(read) => self.stats.record_read(read),
+                Err(e) => return Err(AmqpError::transport(format!("Failed to read frame: {}", e))),
+            }
+        }
+    }
+
+    /// Get the maximum accepted frame size
+    pub fn max_frame_size(&self) -> u32 {
+        self.max_frame_size
+    }
+
+    /// Set the maximum accepted frame size
+    pub fn set_max_frame_size(&mut self, max_frame_size: u32) {
+        self.max_frame_size = max_frame_size;
+    }
+
+    /// Get read statistics
+    pub fn stats(&self) -> TransportStats {
+        self.stats
+    }
+}
+
+/// Sending half of a [`Transport`], see [`Transport::into_split`]
+#[derive(Debug)]
+pub struct TransportWriter {
+    stream: WriteHalf<Box<dyn TransportStream>>,
+    write_buffer: BytesMut,
+    stats: TransportStats,
+    frame_seq: Arc<AtomicU64>,
+    recorder: Option<FrameRecorder>,
+}
+
+impl TransportWriter {
+    /// Send a frame
+    pub async fn send_frame(&mut self, frame: Frame) -> AmqpResult<()> {
+        self.flush().await?;
+        self.write_buffer.extend_from_slice(&frame.header.to_bytes());
+        self.write_buffer.extend_from_slice(&frame.payload);
+        self.track_outgoing(&frame.header, &frame.payload);
+        self.flush().await
+    }
+
+    /// Send a frame that is already encoded (header followed by payload)
+    pub async fn send_encoded_frame(&mut self, data: &[u8]) -> AmqpResult<()> {
+        if data.len() < 8 {
+            return Err(AmqpError::encoding("Insufficient data for frame"));
+        }
+        let header = FrameHeader::decode(data)?;
+        self.flush().await?;
+        self.write_buffer.extend_from_slice(data);
+        self.track_outgoing(&header, &data[8..]);
+        self.flush().await
+    }
+
+    /// Get write statistics
+    pub fn stats(&self) -> TransportStats {
+        self.stats
+    }
+
+    /// Finish writing and shut down the sending direction
+    pub async fn shutdown(&mut self) -> AmqpResult<()> {
+        self.flush().await?;
+        self.stream.shutdown().await
+            .map_err(|e| AmqpError::transport(format!("Failed to shutdown stream: {}", e)))
+    }
+
+    fn track_outgoing(&mut self, header: &FrameHeader, payload: &[u8]) {
+        self.stats.frames_out += 1;
+        let seq = self.frame_seq.fetch_add(1, Ordering::Relaxed) + 1;
+        log_frame(seq, self.recorder.as_ref(), FrameDirection::Outgoing, header, payload);
+    }
+
+    /// Write the buffered bytes, keeping what is left if cancelled
+    async fn flush(&mut self) -> AmqpResult<()> {
+        while !self.write_buffer.is_empty() {
+            let written = self.stream.write(&self.write_buffer).await
+                .map_err(|e| AmqpError::transport(format!("Failed to write frame: {}", e)))?;
+            if written == 0 {
+                return Err(AmqpError::transport("Connection closed while writing frame"));
+            }
+            self.write_buffer.advance(written);
+            self.stats.record_write(written);
+        }
+        self.stream.flush().await
+            .map_err(|e| AmqpError::transport(format!("Failed to flush stream: {}", e)))
+    }
 }
 
 /// AMQP 1.0 Transport Builder
@@ -194,6 +785,11 @@ pub struct TransportBuilder {
     hostname: String,
     port: u16,
     timeout: std::time::Duration,
+    max_frame_size: u32,
+    #[cfg(feature = "tls")]
+    tls: Option<crate::tls::TlsConnector>,
+    #[cfg(feature = "tls")]
+    server_name: Option<String>,
 }
 
 impl TransportBuilder {
@@ -203,6 +799,11 @@ impl TransportBuilder {
             hostname: "localhost".to_string(),
             port: 5672,
             timeout: std::time::Duration::from_secs(30),
+            max_frame_size: constants::DEFAULT_MAX_FRAME_SIZE,
+            #[cfg(feature = "tls")]
+            tls: None,
+            #[cfg(feature = "tls")]
+            server_name: None,
         }
     }
 
@@ -224,15 +825,47 @@ impl TransportBuilder {
         self
     }
 
+    /// Set the maximum accepted frame size
+    pub fn max_frame_size(mut self, max_frame_size: u32) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    /// Secure the connection with TLS, verifying the peer as the hostname
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, connector: crate::tls::TlsConnector) -> Self {
+        self.tls = Some(connector);
+        self
+    }
+
+    /// Send this name as TLS SNI and verify the peer as it, instead of the hostname
+    #[cfg(feature = "tls")]
+    pub fn server_name(mut self, server_name: impl Into<String>) -> Self {
+        self.server_name = Some(server_name.into());
+        self
+    }
+
     /// Connect and create a transport
+    ///
+    /// The timeout covers the TLS handshake too, if there is one.
     pub async fn connect(self) -> AmqpResult<Transport> {
         let addr = format!("{}:{}", self.hostname, self.port);
-        let stream = tokio::time::timeout(self.timeout, TcpStream::connect(&addr))
+        let connect = async {
+            let stream = TcpStream::connect(&addr)
+                .await
+                .map_err(|e| AmqpError::transport(format!("Failed to connect: {}", e)))?;
+            #[cfg(feature = "tls")]
+            if let Some(connector) = &self.tls {
+                let server_name = self.server_name.as_deref().unwrap_or(&self.hostname);
+                return Ok(Transport::new(connector.connect(server_name, stream).await?));
+            }
+            AmqpResult::Ok(Transport::new(stream))
+        };
+        let mut transport = tokio::time::timeout(self.timeout, connect)
             .await
-            .map_err(|_| AmqpError::timeout("Connection timeout"))?
-            .map_err(|e| AmqpError::transport(format!("Failed to connect: {}", e)))?;
-
-        Ok(Transport::new(stream))
+            .map_err(|_| AmqpError::timeout("Connection timeout"))??;
+        transport.set_max_frame_size(self.max_frame_size);
+        Ok(transport)
     }
 }
 
@@ -261,6 +894,55 @@ pub mod constants {
     
     /// SASL protocol header
     pub const SASL_HEADER: &[u8] = &[0x41, 0x4D, 0x51, 0x50, 0x03, 0x01, 0x00, 0x00];
+
+    /// Default maximum frame size
+    pub const DEFAULT_MAX_FRAME_SIZE: u32 = 65536;
+}
+
+/// Protocol header exchanged before any frames
+///
+/// Holds the raw eight bytes, so a peer that answers with something other
+/// than an AMQP header can still be reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProtocolHeader([u8; 8]);
+
+impl ProtocolHeader {
+    /// AMQP 1.0 header (protocol id 0)
+    pub const AMQP: ProtocolHeader = ProtocolHeader([0x41, 0x4D, 0x51, 0x50, 0x00, 0x01, 0x00, 0x00]);
+    /// TLS header (protocol id 2)
+    pub const TLS: ProtocolHeader = ProtocolHeader([0x41, 0x4D, 0x51, 0x50, 0x02, 0x01, 0x00, 0x00]);
+    /// SASL header (protocol id 3)
+    pub const SASL: ProtocolHeader = ProtocolHeader([0x41, 0x4D, 0x51, 0x50, 0x03, 0x01, 0x00, 0x00]);
+
+    /// Create a header from the bytes read off the wire
+    pub fn from_bytes(bytes: [u8; 8]) -> Self {
+        ProtocolHeader(bytes)
+    }
+
+    /// Get the raw header bytes
+    pub fn as_bytes(&self) -> &[u8; 8] {
+        &self.0
+    }
+
+    /// Get the protocol id, if the header starts with `AMQP`
+    pub fn protocol_id(&self) -> Option<u8> {
+        (&self.0[..4] == constants::AMQP_PROTOCOL_ID).then_some(self.0[4])
+    }
+}
+
+impl std::fmt::Display for ProtocolHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.protocol_id() {
+            Some(id) => write!(f, "AMQP{} {}.{}.{}", id, self.0[5], self.0[6], self.0[7]),
+            None => {
+                write!(f, "non-AMQP bytes")?;
+                for byte in self.0 {
+                    write!(f, " {:02x}", byte)?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 /// AMQP 1.0 Protocol negotiation
@@ -269,38 +951,49 @@ pub struct ProtocolNegotiator;
 impl ProtocolNegotiator {
     /// Negotiate AMQP protocol
     pub async fn negotiate_amqp(transport: &mut Transport) -> AmqpResult<()> {
-        // Send AMQP protocol header
-        transport.send_raw(constants::AMQP_HEADER).await?;
-        
-        // Receive protocol header response
-        let response = transport.receive_raw(8).await?;
-        
-        if response != constants::AMQP_HEADER {
-            return Err(AmqpError::protocol("Protocol negotiation failed"));
-        }
-        
-        Ok(())
+        Self::exchange_header(transport, ProtocolHeader::AMQP).await
     }
 
     /// Negotiate SASL protocol
     pub async fn negotiate_sasl(transport: &mut Transport) -> AmqpResult<()> {
-        // Send SASL protocol header
-        transport.send_raw(constants::SASL_HEADER).await?;
-        
-        // Receive protocol header response
-        let response = transport.receive_raw(8).await?;
-        
-        if response != constants::SASL_HEADER {
-            return Err(AmqpError::protocol("SASL protocol negotiation failed"));
+        Self::exchange_header(transport, ProtocolHeader::SASL).await
+    }
+
+    /// Send a protocol header and check that the peer answers with the same one
+    ///
+    /// Fails with [`AmqpError::ProtocolMismatch`] if the peer answers with any
+    /// other header.
+    pub async fn exchange_header(transport: &mut Transport, header: ProtocolHeader) -> AmqpResult<()> {
+        transport.send_raw(header.as_bytes()).await?;
+
+        let mut response = [0u8; 8];
+        response.copy_from_slice(&transport.receive_raw(8).await?);
+        let response = ProtocolHeader::from_bytes(response);
+
+        if response != header {
+            return Err(AmqpError::protocol_mismatch(header, response));
         }
-        
+
         Ok(())
     }
+
+    /// Build the initial response for the SASL PLAIN mechanism
+    ///
+    /// The response is `authzid NUL authcid NUL password`, where an absent
+    /// authorization identity means "act as the authenticated identity".
+    pub fn sasl_plain_response(authzid: Option<&str>, authcid: &str, password: &str) -> AmqpResult<Vec<u8>> {
+        let fields = [authzid.unwrap_or(""), authcid, password];
+        if fields.iter().any(|field| field.contains('\0')) {
+            return Err(AmqpError::encoding("SASL PLAIN fields must not contain NUL"));
+        }
+        Ok(fields.join("\0").into_bytes())
+    }
 } 
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Duration;
 
 
     #[test]
@@ -417,7 +1110,7 @@ mod tests {
         // We can't easily test Transport::new with TcpStream in a sync test
         // since TcpStream::connect is async. This test verifies the struct can be created
         // when given a valid TcpStream.
-        assert!(true); // Transport::new exists and can be called
+        let _new: fn(TcpStream) -> Transport = Transport::new;
     }
 
     #[test]
@@ -477,10 +1170,21 @@ mod tests {
         assert_eq!(constants::SASL_HEADER, &[0x41, 0x4D, 0x51, 0x50, 0x03, 0x01, 0x00, 0x00]);
     }
 
+    #[test]
+    fn test_protocol_header_display() {
+        assert_eq!(ProtocolHeader::AMQP.as_bytes(), constants::AMQP_HEADER);
+        assert_eq!(ProtocolHeader::SASL.as_bytes(), constants::SASL_HEADER);
+        assert_eq!(ProtocolHeader::SASL.protocol_id(), Some(3));
+        assert_eq!(ProtocolHeader::TLS.to_string(), "AMQP2 1.0.0");
+
+        let http = ProtocolHeader::from_bytes(*b"HTTP/1.1");
+        assert_eq!(http.protocol_id(), None);
+        assert_eq!(http.to_string(), "non-AMQP bytes 48 54 54 50 2f 31 2e 31");
+    }
+
     #[test]
     fn test_protocol_negotiator_creation() {
-        let _negotiator = ProtocolNegotiator;
-        assert!(true); // ProtocolNegotiator was created successfully
+        let _negotiator = ProtocolNegotiator; // ProtocolNegotiator was created successfully
     }
 
     // Integration tests for async functionality
@@ -580,4 +1284,281 @@ mod tests {
         assert_eq!(decoded.payload.len(), payload_size);
         assert_eq!(decoded.payload, vec![0x42; payload_size]);
     }
-} 
\ No newline at end of file
+
+    async fn transport_pair() -> (Transport, Transport) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (client, server) = tokio::join!(TcpStream::connect(addr), listener.accept());
+        (Transport::new(client.unwrap()), Transport::new(server.unwrap().0))
+    }
+
+    #[test]
+    fn test_transport_stats_default() {
+        let stats = TransportStats::default();
+        assert_eq!(stats.bytes_read, 0);
+        assert_eq!(stats.bytes_written, 0);
+        assert_eq!(stats.frames_in, 0);
+        assert_eq!(stats.frames_out, 0);
+        assert!(stats.last_activity().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_transport_stats_frames() {
+        let (mut client, mut server) = transport_pair().await;
+
+        let payload = vec![1, 2, 3, 4];
+        let frame = Frame::new(FrameHeader::new(payload.len() as u32, FrameType::AMQP as u8, 0), payload);
+        client.send_frame(frame).await.unwrap();
+        let received = server.receive_frame().await.unwrap();
+        assert_eq!(received.payload, vec![1, 2, 3, 4]);
+
+        let client_stats = client.stats();
+        assert_eq!(client_stats.bytes_written, 12);
+        assert_eq!(client_stats.frames_out, 1);
+        assert_eq!(client_stats.frames_in, 0);
+        assert!(client_stats.last_write.is_some());
+        assert!(client_stats.last_read.is_none());
+
+        let server_stats = server.stats();
+        assert_eq!(server_stats.bytes_read, 12);
+        assert_eq!(server_stats.frames_in, 1);
+        assert!(server_stats.last_read.is_some());
+        assert_eq!(server_stats.last_activity(), server_stats.last_read);
+    }
+
+    #[tokio::test]
+    async fn test_frame_seq_and_recorder() {
+        let (mut client, mut server) = transport_pair().await;
+        let recorder = FrameRecorder::new(2);
+        client.record_frames(recorder.clone());
+
+        for channel in 0..2u16 {
+            let frame = Frame::new(FrameHeader::new(1, FrameType::AMQP as u8, channel), vec![channel as u8]);
+            client.send_frame(frame).await.unwrap();
+        }
+        server.send_encoded_frame(&Frame::new(FrameHeader::new(1, 0, 9), vec![9]).encode()).await.unwrap();
+        for _ in 0..2 {
+            server.receive_frame().await.unwrap();
+        }
+        let reply = std::future::poll_fn(|cx| client.poll_recv_frame(cx)).await.unwrap();
+        assert_eq!(reply.header.channel, 9);
+
+        // One sequence for both directions
+        assert_eq!(client.frame_seq(), 3);
+        assert_eq!(server.frame_seq(), 3);
+
+        // Only the latest frames are kept
+        let frames = recorder.frames();
+        assert_eq!(frames.iter().map(|frame| frame.seq).collect::<Vec<_>>(), vec![2, 3]);
+        let incoming = recorder.find(3).unwrap();
+        assert_eq!(incoming.direction, FrameDirection::Incoming);
+        assert_eq!((incoming.header.channel, incoming.payload), (9, vec![9]));
+        assert_eq!(recorder.find(2).unwrap().direction, FrameDirection::Outgoing);
+        assert!(recorder.find(1).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_frame_round_trip_allocations() {
+        let (mut client, mut server) = transport_pair().await;
+        let frame = Frame::new(FrameHeader::new(64, FrameType::AMQP as u8, 0), vec![7; 64]);
+
+        // The first frame grows the write buffer
+        client.send_frame(frame.clone()).await.unwrap();
+        server.receive_frame().await.unwrap();
+
+        for _ in 0..10 {
+            let frame = frame.clone();
+            let before = crate::allocations::current();
+            client.send_frame(frame).await.unwrap();
+            let received = server.receive_frame().await.unwrap();
+            // Only the received payload is allocated
+            assert_eq!(crate::allocations::current() - before, 1);
+            assert_eq!(received.payload.len(), 64);
+        }
+        assert_eq!(FrameHeader::new(64, 0, 3).to_bytes(), [0, 0, 0, 64, 2, 0, 0, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_transport_stats_raw() {
+        let (mut client, mut server) = transport_pair().await;
+
+        client.send_raw(constants::AMQP_HEADER).await.unwrap();
+        server.receive_raw(8).await.unwrap();
+
+        assert_eq!(client.stats().bytes_written, 8);
+        assert_eq!(client.stats().frames_out, 0);
+        assert_eq!(server.stats().bytes_read, 8);
+        assert_eq!(server.stats().frames_in, 0);
+    }
+
+    #[tokio::test]
+    async fn test_transport_default_max_frame_size() {
+        let (client, _server) = transport_pair().await;
+        assert_eq!(client.max_frame_size(), constants::DEFAULT_MAX_FRAME_SIZE);
+    }
+
+    #[tokio::test]
+    async fn test_receive_frame_discards_oversized_frame() {
+        let (mut client, mut server) = transport_pair().await;
+        server.set_max_frame_size(512);
+
+        let big = vec![0xAB; 1024 * 1024];
+        let small = vec![1, 2, 3];
+        let sender = tokio::spawn(async move {
+            let frame = Frame::new(FrameHeader::new(big.len() as u32, FrameType::AMQP as u8, 0), big);
+            client.send_frame(frame).await.unwrap();
+            let frame = Frame::new(FrameHeader::new(small.len() as u32, FrameType::AMQP as u8, 0), small);
+            client.send_frame(frame).await.unwrap();
+            client
+        });
+
+        let result = server.receive_frame().await;
+        match result {
+            Err(error) => assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorFramingError)),
+            Ok(_) => panic!("Expected framing error"),
+        }
+        assert_eq!(server.stats().bytes_read, 8 + 1024 * 1024);
+        assert_eq!(server.stats().frames_in, 0);
+
+        // The stream stays in sync after the discard
+        let frame = server.receive_frame().await.unwrap();
+        assert_eq!(frame.payload, vec![1, 2, 3]);
+        sender.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_receive_frame_oversized_frame_truncated() {
+        let (mut client, mut server) = transport_pair().await;
+        server.set_max_frame_size(512);
+
+        let header = FrameHeader::new(u32::MAX - 8, FrameType::AMQP as u8, 0);
+        client.send_raw(&header.encode()).await.unwrap();
+        client.send_raw(&[0u8; 100]).await.unwrap();
+        client.shutdown().await.unwrap();
+        drop(client);
+
+        let result = server.receive_frame().await;
+        assert!(matches!(result, Err(AmqpError::TransportClosedMidFrame { expected: 0xFFFF_FFFF, received: 108 })));
+        assert_eq!(server.stats().bytes_read, 108);
+    }
+
+    /// A peer that wrote the first `cut` bytes of `encoded` and died
+    async fn truncated_peer(encoded: &[u8], cut: usize) -> Transport {
+        let (mut client, server) = transport_pair().await;
+        client.send_raw(&encoded[..cut]).await.unwrap();
+        client.shutdown().await.unwrap();
+        server
+    }
+
+    #[tokio::test]
+    async fn test_receive_frame_peer_closed_at_every_offset() {
+        let encoded = Frame::new(FrameHeader::new(12, FrameType::AMQP as u8, 3), (0..12).collect()).encode();
+
+        for cut in 0..encoded.len() {
+            let mut server = truncated_peer(&encoded, cut).await;
+            let async_result = server.receive_frame().await;
+            let mut server = truncated_peer(&encoded, cut).await;
+            let poll_result = std::future::poll_fn(|cx| server.poll_recv_frame(cx)).await;
+
+            for result in [async_result, poll_result] {
+                match result.unwrap_err() {
+                    // Closing between frames is not a truncation
+                    AmqpError::Transport(_) if cut == 0 => {}
+                    AmqpError::TransportClosedMidFrame { expected, received } => {
+                        assert_eq!(expected, if cut < 8 { 8 } else { encoded.len() as u64 }, "cut at {}", cut);
+                        assert_eq!(received, cut as u64, "cut at {}", cut);
+                    }
+                    error => panic!("cut at {}: {}", cut, error),
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_poll_send_and_recv_frame() {
+        let (mut client, mut server) = transport_pair().await;
+
+        let frames: Vec<Frame> = (0..3u16)
+            .map(|channel| Frame::new(FrameHeader::new(2, FrameType::AMQP as u8, channel), vec![0xAA, channel as u8]))
+            .collect();
+        for frame in &frames {
+            std::future::poll_fn(|cx| client.poll_send_frame(cx, frame)).await.unwrap();
+        }
+        std::future::poll_fn(|cx| client.poll_flush(cx)).await.unwrap();
+        assert_eq!(client.stats().frames_out, 3);
+        assert_eq!(client.stats().bytes_written, 30);
+
+        for channel in 0..3u16 {
+            let frame = std::future::poll_fn(|cx| server.poll_recv_frame(cx)).await.unwrap();
+            assert_eq!((frame.header.channel, frame.payload), (channel, vec![0xAA, channel as u8]));
+        }
+        assert_eq!(server.stats().frames_in, 3);
+        assert_eq!(server.stats().bytes_read, 30);
+    }
+
+    #[tokio::test]
+    async fn test_poll_recv_frame_keeps_partial_frame() {
+        let (mut client, mut server) = transport_pair().await;
+        let waker = futures::task::noop_waker();
+        assert!(server.poll_recv_frame(&mut Context::from_waker(&waker)).is_pending());
+
+        let encoded = Frame::new(FrameHeader::new(4, FrameType::AMQP as u8, 9), vec![1, 2, 3, 4]).encode();
+        client.send_raw(&encoded[..5]).await.unwrap();
+        let partial = tokio::time::timeout(
+            Duration::from_millis(50),
+            std::future::poll_fn(|cx| server.poll_recv_frame(cx)),
+        );
+        assert!(partial.await.is_err());
+
+        // The async path picks up where polling stopped
+        client.send_raw(&encoded[5..]).await.unwrap();
+        let frame = server.receive_frame().await.unwrap();
+        assert_eq!((frame.header.channel, frame.payload), (9, vec![1, 2, 3, 4]));
+        assert_eq!(server.stats().frames_in, 1);
+    }
+
+    #[tokio::test]
+    async fn test_poll_recv_frame_discards_oversized_frame() {
+        let (mut client, mut server) = transport_pair().await;
+        server.set_max_frame_size(16);
+
+        client.send_frame(Frame::new(FrameHeader::new(64, FrameType::AMQP as u8, 0), vec![0; 64])).await.unwrap();
+        client.send_frame(Frame::new(FrameHeader::new(1, FrameType::AMQP as u8, 0), vec![7])).await.unwrap();
+
+        let error = std::future::poll_fn(|cx| server.poll_recv_frame(cx)).await.unwrap_err();
+        assert_eq!(error.condition(), Some(&AmqpCondition::AmqpErrorFramingError));
+        let frame = std::future::poll_fn(|cx| server.poll_recv_frame(cx)).await.unwrap();
+        assert_eq!(frame.payload, vec![7]);
+    }
+
+    #[tokio::test]
+    async fn test_send_encoded_frame() {
+        let (mut client, mut server) = transport_pair().await;
+
+        let frame = Frame::new(FrameHeader::new(2, FrameType::AMQP as u8, 5), vec![7, 8]);
+        client.send_encoded_frame(&frame.encode()).await.unwrap();
+        assert_eq!(client.stats().frames_out, 1);
+
+        let received = server.receive_frame().await.unwrap();
+        assert_eq!(received.header.channel, 5);
+        assert_eq!(received.payload, vec![7, 8]);
+    }
+
+    #[tokio::test]
+    async fn test_send_encoded_frame_too_short() {
+        let (mut client, _server) = transport_pair().await;
+        let result = client.send_encoded_frame(&[0, 0, 0]).await;
+        assert!(matches!(result, Err(AmqpError::Encoding { .. })));
+    }
+
+    #[test]
+    fn test_sasl_plain_response() {
+        let response = ProtocolNegotiator::sasl_plain_response(Some("tenant-a"), "gateway", "secret").unwrap();
+        assert_eq!(response, b"tenant-a\0gateway\0secret");
+
+        let response = ProtocolNegotiator::sasl_plain_response(None, "user", "pw").unwrap();
+        assert_eq!(response, b"\0user\0pw");
+
+        assert!(ProtocolNegotiator::sasl_plain_response(Some("a\0b"), "user", "pw").is_err());
+    }
+}
@@ -0,0 +1,244 @@
+//! AMQP 1.0 Runtime Tuning
+//!
+//! This module provides knobs that can be changed on a live connection
+//! without reconnecting: receiver credit and prefetch, sender rate limits,
+//! log verbosity and the heartbeat interval. A [`TuningHandle`] holds the
+//! current [`Tunables`]; updates are validated and then swapped in as a
+//! whole, so readers never observe a half-applied change. Links opt in with
+//! `follow_tuning` and pick up changes on their next operation.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::connection::ConnectionBuilder;
+//! use tokio::time::Duration;
+//!
+//! let connection = ConnectionBuilder::new()
+//!     .idle_timeout(Duration::from_secs(60))
+//!     .build();
+//!
+//! let tuning = connection.tuning();
+//! tuning
+//!     .update(|knobs| {
+//!         knobs.credit_window = 500;
+//!         knobs.max_send_rate = Some(1000);
+//!     })
+//!     .unwrap();
+//!
+//! // The heartbeat must stay within half the idle timeout
+//! assert!(tuning.update(|knobs| knobs.heartbeat_interval = Duration::from_secs(45)).is_err());
+//! assert_eq!(tuning.get().credit_window, 500);
+//! ```
+
+use crate::{AmqpError, AmqpResult};
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tokio::time::Duration;
+
+/// Runtime-tunable knobs
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tunables {
+    /// Link credit a receiver keeps granted to the peer
+    pub credit_window: u32,
+    /// Maximum messages a receiver buffers locally, capping its credit
+    pub prefetch: u32,
+    /// Maximum messages per second a sender transmits, if limited
+    pub max_send_rate: Option<u32>,
+    /// Maximum log level for the process, if overridden
+    #[cfg(feature = "logging")]
+    pub log_level: Option<log::LevelFilter>,
+    /// Interval between heartbeat frames
+    pub heartbeat_interval: Duration,
+}
+
+impl Default for Tunables {
+    fn default() -> Self {
+        Tunables {
+            credit_window: 100,
+            prefetch: 100,
+            max_send_rate: None,
+            #[cfg(feature = "logging")]
+            log_level: None,
+            heartbeat_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+impl Tunables {
+    /// Get the credit a receiver should grant with `buffered` messages queued
+    pub fn link_credit(&self, buffered: usize) -> u32 {
+        let room = (self.prefetch as usize).saturating_sub(buffered) as u32;
+        self.credit_window.min(room)
+    }
+
+    /// Get the minimum spacing between sends under the rate limit
+    pub fn send_interval(&self) -> Option<Duration> {
+        self.max_send_rate
+            .map(|rate| Duration::from_secs(1) / rate.max(1))
+    }
+}
+
+/// Shared handle to the current tunables of a connection
+#[derive(Debug, Clone)]
+pub struct TuningHandle {
+    inner: Arc<TuningInner>,
+}
+
+#[derive(Debug)]
+struct TuningInner {
+    current: watch::Sender<Tunables>,
+    heartbeat_limit: Mutex<Option<Duration>>,
+}
+
+impl TuningHandle {
+    /// Create a handle with initial tunables
+    pub fn new(initial: Tunables) -> Self {
+        TuningHandle {
+            inner: Arc::new(TuningInner {
+                current: watch::channel(initial).0,
+                heartbeat_limit: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Get a snapshot of the current tunables
+    pub fn get(&self) -> Tunables {
+        self.inner.current.borrow().clone()
+    }
+
+    /// Apply a change to the tunables
+    ///
+    /// The change is validated as a whole and either applied entirely or
+    /// rejected, leaving the current tunables untouched.
+    pub fn update(&self, change: impl FnOnce(&mut Tunables)) -> AmqpResult<()> {
+        let mut next = self.get();
+        change(&mut next);
+        self.validate(&next)?;
+
+        #[cfg(feature = "logging")]
+        if let Some(level) = next.log_level {
+            log::set_max_level(level);
+        }
+        self.inner.current.send_replace(next);
+        Ok(())
+    }
+
+    /// Watch the tunables for changes
+    pub fn subscribe(&self) -> watch::Receiver<Tunables> {
+        self.inner.current.subscribe()
+    }
+
+    /// Get the longest heartbeat interval the connection allows
+    pub fn heartbeat_limit(&self) -> Option<Duration> {
+        *self.lock_limit()
+    }
+
+    /// Bound the heartbeat interval, clamping the current one if needed
+    pub(crate) fn set_heartbeat_limit(&self, limit: Option<Duration>) {
+        *self.lock_limit() = limit;
+        if let Some(limit) = limit {
+            self.inner.current.send_if_modified(|knobs| {
+                let clamped = knobs.heartbeat_interval > limit;
+                if clamped {
+                    knobs.heartbeat_interval = limit;
+                }
+                clamped
+            });
+        }
+    }
+
+    fn validate(&self, knobs: &Tunables) -> AmqpResult<()> {
+        if knobs.max_send_rate == Some(0) {
+            return Err(AmqpError::connection("Send rate limit must be positive"));
+        }
+        if knobs.heartbeat_interval.is_zero() {
+            return Err(AmqpError::connection("Heartbeat interval must be positive"));
+        }
+        if let Some(limit) = self.heartbeat_limit() {
+            if knobs.heartbeat_interval > limit {
+                return Err(AmqpError::connection(format!(
+                    "Heartbeat interval {:?} exceeds the negotiated limit of {:?}",
+                    knobs.heartbeat_interval, limit
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn lock_limit(&self) -> std::sync::MutexGuard<'_, Option<Duration>> {
+        self.inner
+            .heartbeat_limit
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for TuningHandle {
+    fn default() -> Self {
+        Self::new(Tunables::default())
+    }
+}
+
+/// Heartbeat limit for an idle timeout: half of it, or none if disabled
+pub(crate) fn heartbeat_limit_for(idle_timeout: Duration) -> Option<Duration> {
+    (!idle_timeout.is_zero()).then(|| idle_timeout / 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_credit_capped_by_prefetch() {
+        let knobs = Tunables {
+            credit_window: 50,
+            prefetch: 80,
+            ..Default::default()
+        };
+        assert_eq!(knobs.link_credit(0), 50);
+        assert_eq!(knobs.link_credit(70), 10);
+        assert_eq!(knobs.link_credit(100), 0);
+    }
+
+    #[test]
+    fn test_send_interval() {
+        let knobs = Tunables {
+            max_send_rate: Some(4),
+            ..Default::default()
+        };
+        assert_eq!(knobs.send_interval(), Some(Duration::from_millis(250)));
+        assert!(Tunables::default().send_interval().is_none());
+    }
+
+    #[test]
+    fn test_update_is_all_or_nothing() {
+        let handle = TuningHandle::default();
+        let result = handle.update(|knobs| {
+            knobs.credit_window = 7;
+            knobs.max_send_rate = Some(0);
+        });
+        assert!(result.is_err());
+        assert_eq!(handle.get().credit_window, 100);
+
+        handle.update(|knobs| knobs.credit_window = 7).unwrap();
+        assert_eq!(handle.get().credit_window, 7);
+    }
+
+    #[test]
+    fn test_heartbeat_limit() {
+        let handle = TuningHandle::default();
+        let mut watcher = handle.subscribe();
+        handle.set_heartbeat_limit(heartbeat_limit_for(Duration::from_secs(20)));
+
+        assert_eq!(handle.get().heartbeat_interval, Duration::from_secs(10));
+        assert!(watcher.has_changed().unwrap());
+        assert!(handle.update(|knobs| knobs.heartbeat_interval = Duration::from_secs(11)).is_err());
+        assert!(handle.update(|knobs| knobs.heartbeat_interval = Duration::from_secs(5)).is_ok());
+        assert_eq!(watcher.borrow_and_update().heartbeat_interval, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_no_heartbeat_limit_without_idle_timeout() {
+        assert!(heartbeat_limit_for(Duration::ZERO).is_none());
+    }
+}
@@ -15,7 +15,7 @@
 //! The main enum representing all possible AMQP 1.0 value types:
 //!
 //! ```rust
-//! use dumq_amqp::types::AmqpValue;
+//! use dumq_amqp::types::{AmqpValue, AmqpSymbol};
 //!
 //! // Primitive types
 //! let null = AmqpValue::Null;
@@ -25,8 +25,8 @@
 //! let string = AmqpValue::String("Hello".to_string());
 //!
 //! // Complex types
-//! let uuid = AmqpValue::Uuid(uuid::Uuid::new_v4());
-//! let binary = AmqpValue::Binary(vec![1, 2, 3, 4]);
+//! let uuid = AmqpValue::Uuid(dumq_amqp::types::Uuid::new_v4());
+//! let binary = AmqpValue::Binary(vec![1, 2, 3, 4].into());
 //! let symbol = AmqpValue::Symbol(AmqpSymbol::from("my-symbol"));
 //! ```
 //!
@@ -44,7 +44,9 @@
 //!
 //! ## AmqpList and AmqpMap
 //!
-//! Composite types for structured data:
+//! Composite types for structured data. Clones share their contents until
+//! one of them is changed, so cloning a message is cheap however large its
+//! collections are:
 //!
 //! ```rust
 //! use dumq_amqp::types::{AmqpList, AmqpMap, AmqpValue, AmqpSymbol};
@@ -62,12 +64,81 @@
 //! map_data.insert(AmqpSymbol::from("key1"), AmqpValue::String("value1".to_string()));
 //! map_data.insert(AmqpSymbol::from("key2"), AmqpValue::Int(123));
 //! let map = AmqpMap::from(map_data);
+//!
+//! let mut copy = list.clone();
+//! assert!(copy.ptr_eq(&list));
+//! copy.push(AmqpValue::Null);
+//! assert_eq!((list.len(), copy.len()), (3, 4));
 //! ```
 
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[cfg(feature = "uuid")]
+pub use uuid::Uuid;
+
+/// 128-bit UUID, standing in for `uuid::Uuid` in builds without the `uuid` feature
+///
+/// Only the subset of the `uuid::Uuid` API used with AMQP values is provided.
+#[cfg(not(feature = "uuid"))]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Uuid([u8; 16]);
+
+#[cfg(not(feature = "uuid"))]
+impl Uuid {
+    /// The all-zero UUID
+    pub const fn nil() -> Self {
+        Uuid([0; 16])
+    }
+
+    /// Create a random version 4 UUID
+    pub fn new_v4() -> Self {
+        Uuid(crate::ids::random_uuid_bytes())
+    }
+
+    /// Create a UUID from its big-endian bytes
+    pub const fn from_bytes(bytes: [u8; 16]) -> Self {
+        Uuid(bytes)
+    }
+
+    /// Create a UUID from a 128-bit value
+    pub const fn from_u128(value: u128) -> Self {
+        Uuid(value.to_be_bytes())
+    }
+
+    /// Get the big-endian bytes
+    pub const fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    /// Get the 128-bit value
+    pub const fn as_u128(&self) -> u128 {
+        u128::from_be_bytes(self.0)
+    }
+}
+
+#[cfg(not(feature = "uuid"))]
+impl std::fmt::Display for Uuid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&crate::ids::format_uuid(&self.0))
+    }
+}
+
+#[cfg(not(feature = "uuid"))]
+impl std::fmt::Debug for Uuid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
+}
+
 /// AMQP Symbol type
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct AmqpSymbol(pub String);
 
 impl AmqpSymbol {
@@ -95,13 +166,288 @@ impl std::fmt::Display for AmqpSymbol {
 }
 
 /// AMQP List type
-pub type AmqpList = Vec<AmqpValue>;
+///
+/// The items are shared between clones, so cloning a list is O(1). Mutable
+/// access through [`DerefMut`](std::ops::DerefMut) copies them first if
+/// another clone still holds them.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct AmqpList(Arc<Vec<AmqpValue>>);
+
+impl AmqpList {
+    /// Create an empty list
+    pub fn new() -> Self {
+        AmqpList::default()
+    }
+
+    /// Take the items out, copying them if they are shared
+    pub fn into_vec(self) -> Vec<AmqpValue> {
+        Arc::try_unwrap(self.0).unwrap_or_else(|items| (*items).clone())
+    }
+
+    /// Whether both lists share the same items
+    pub fn ptr_eq(&self, other: &AmqpList) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl std::ops::Deref for AmqpList {
+    type Target = Vec<AmqpValue>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for AmqpList {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        Arc::make_mut(&mut self.0)
+    }
+}
+
+impl std::fmt::Debug for AmqpList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<Vec<AmqpValue>> for AmqpList {
+    fn from(items: Vec<AmqpValue>) -> Self {
+        AmqpList(Arc::new(items))
+    }
+}
+
+impl From<AmqpList> for Vec<AmqpValue> {
+    fn from(list: AmqpList) -> Self {
+        list.into_vec()
+    }
+}
+
+impl FromIterator<AmqpValue> for AmqpList {
+    fn from_iter<I: IntoIterator<Item = AmqpValue>>(iter: I) -> Self {
+        AmqpList::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl IntoIterator for AmqpList {
+    type Item = AmqpValue;
+    type IntoIter = std::vec::IntoIter<AmqpValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_vec().into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a AmqpList {
+    type Item = &'a AmqpValue;
+    type IntoIter = std::slice::Iter<'a, AmqpValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for AmqpList {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for AmqpList {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::deserialize(deserializer).map(AmqpList::from)
+    }
+}
 
 /// AMQP Map type
-pub type AmqpMap = std::collections::HashMap<AmqpSymbol, AmqpValue>;
+///
+/// Like [`AmqpList`], the entries are shared between clones and copied on
+/// the first mutable access to a shared map.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct AmqpMap(Arc<HashMap<AmqpSymbol, AmqpValue>>);
+
+impl AmqpMap {
+    /// Create an empty map
+    pub fn new() -> Self {
+        AmqpMap::default()
+    }
+
+    /// Take the entries out, copying them if they are shared
+    pub fn into_map(self) -> HashMap<AmqpSymbol, AmqpValue> {
+        Arc::try_unwrap(self.0).unwrap_or_else(|entries| (*entries).clone())
+    }
+
+    /// Whether both maps share the same entries
+    pub fn ptr_eq(&self, other: &AmqpMap) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl std::ops::Deref for AmqpMap {
+    type Target = HashMap<AmqpSymbol, AmqpValue>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for AmqpMap {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        Arc::make_mut(&mut self.0)
+    }
+}
+
+impl std::fmt::Debug for AmqpMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<HashMap<AmqpSymbol, AmqpValue>> for AmqpMap {
+    fn from(entries: HashMap<AmqpSymbol, AmqpValue>) -> Self {
+        AmqpMap(Arc::new(entries))
+    }
+}
+
+impl From<AmqpMap> for HashMap<AmqpSymbol, AmqpValue> {
+    fn from(map: AmqpMap) -> Self {
+        map.into_map()
+    }
+}
+
+impl FromIterator<(AmqpSymbol, AmqpValue)> for AmqpMap {
+    fn from_iter<I: IntoIterator<Item = (AmqpSymbol, AmqpValue)>>(iter: I) -> Self {
+        AmqpMap::from(iter.into_iter().collect::<HashMap<_, _>>())
+    }
+}
+
+impl IntoIterator for AmqpMap {
+    type Item = (AmqpSymbol, AmqpValue);
+    type IntoIter = std::collections::hash_map::IntoIter<AmqpSymbol, AmqpValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_map().into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a AmqpMap {
+    type Item = (&'a AmqpSymbol, &'a AmqpValue);
+    type IntoIter = std::collections::hash_map::Iter<'a, AmqpSymbol, AmqpValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for AmqpMap {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for AmqpMap {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        HashMap::deserialize(deserializer).map(AmqpMap::from)
+    }
+}
+
+/// Key of an annotations map
+///
+/// Delivery and message annotations may be keyed by symbols or by ulong
+/// codes registered with the AMQP specification.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum AnnotationKey {
+    Symbol(AmqpSymbol),
+    Ulong(u64),
+}
+
+impl From<AmqpSymbol> for AnnotationKey {
+    fn from(symbol: AmqpSymbol) -> Self {
+        AnnotationKey::Symbol(symbol)
+    }
+}
+
+impl From<&str> for AnnotationKey {
+    fn from(s: &str) -> Self {
+        AnnotationKey::Symbol(AmqpSymbol::from(s))
+    }
+}
+
+impl From<String> for AnnotationKey {
+    fn from(s: String) -> Self {
+        AnnotationKey::Symbol(AmqpSymbol::from(s))
+    }
+}
+
+impl From<u64> for AnnotationKey {
+    fn from(code: u64) -> Self {
+        AnnotationKey::Ulong(code)
+    }
+}
+
+impl std::fmt::Display for AnnotationKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnnotationKey::Symbol(symbol) => write!(f, "{}", symbol),
+            AnnotationKey::Ulong(code) => write!(f, "0x{:016x}", code),
+        }
+    }
+}
+
+/// Descriptor of a described value
+///
+/// Types defined by the AMQP specification, such as message sections, are
+/// described by ulong codes. Application-defined types are usually
+/// described by a symbol such as `com.example:order`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Descriptor {
+    Code(u64),
+    Symbol(AmqpSymbol),
+}
+
+impl From<u64> for Descriptor {
+    fn from(code: u64) -> Self {
+        Descriptor::Code(code)
+    }
+}
+
+impl From<AmqpSymbol> for Descriptor {
+    fn from(symbol: AmqpSymbol) -> Self {
+        Descriptor::Symbol(symbol)
+    }
+}
+
+impl From<&str> for Descriptor {
+    fn from(s: &str) -> Self {
+        Descriptor::Symbol(AmqpSymbol::from(s))
+    }
+}
+
+impl std::fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Descriptor::Code(code) => write!(f, "0x{:016x}", code),
+            Descriptor::Symbol(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+/// AMQP annotations map, keyed by symbols or ulong codes
+pub type Annotations = std::collections::HashMap<AnnotationKey, AmqpValue>;
 
 /// AMQP Value type
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+///
+/// Equality and hashing treat floats by value with two exceptions that keep
+/// them usable as map keys: every NaN equals every other NaN, and `-0.0`
+/// equals `0.0`. Use [`AmqpValue::approx_eq`] to compare with a tolerance.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum AmqpValue {
     Null,
     Boolean(bool),
@@ -120,17 +466,171 @@ pub enum AmqpValue {
     Decimal128(u128),
     Char(char),
     Timestamp(i64),
-    Uuid(uuid::Uuid),
-    Binary(Vec<u8>),
+    Uuid(Uuid),
+    Binary(#[cfg_attr(feature = "serde", serde(with = "serde_bytes"))] Bytes),
     String(String),
     Symbol(AmqpSymbol),
     List(AmqpList),
     Map(AmqpMap),
-    Array(Vec<AmqpValue>),
+    Array(AmqpList),
+    Described(Box<Descriptor>, Box<AmqpValue>),
+}
+
+/// Serde support for [`Bytes`], as a plain byte sequence
+#[cfg(feature = "serde")]
+pub(crate) mod serde_bytes {
+    use bytes::Bytes;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        Vec::<u8>::deserialize(deserializer).map(Bytes::from)
+    }
+}
+
+/// Float bits with all NaNs and both zeros collapsed
+fn canonical_f32(value: f32) -> u32 {
+    if value.is_nan() {
+        f32::NAN.to_bits()
+    } else if value == 0.0 {
+        0
+    } else {
+        value.to_bits()
+    }
+}
+
+fn canonical_f64(value: f64) -> u64 {
+    if value.is_nan() {
+        f64::NAN.to_bits()
+    } else if value == 0.0 {
+        0
+    } else {
+        value.to_bits()
+    }
+}
+
+fn approx_f64(a: f64, b: f64, epsilon: f64) -> bool {
+    canonical_f64(a) == canonical_f64(b) || (a - b).abs() <= epsilon
+}
+
+impl AmqpValue {
+    /// Create a described value
+    pub fn described(descriptor: impl Into<Descriptor>, value: AmqpValue) -> Self {
+        AmqpValue::Described(Box::new(descriptor.into()), Box::new(value))
+    }
+
+    /// Compare deeply, allowing floats to differ by up to `epsilon`
+    ///
+    /// Lists, arrays and maps are compared element by element. Floats only
+    /// match floats of the same width; NaN matches NaN.
+    pub fn approx_eq(&self, other: &AmqpValue, epsilon: f64) -> bool {
+        match (self, other) {
+            (AmqpValue::Float(a), AmqpValue::Float(b)) => approx_f64(*a as f64, *b as f64, epsilon),
+            (AmqpValue::Double(a), AmqpValue::Double(b)) => approx_f64(*a, *b, epsilon),
+            (AmqpValue::List(a), AmqpValue::List(b)) | (AmqpValue::Array(a), AmqpValue::Array(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.approx_eq(b, epsilon))
+            }
+            (AmqpValue::Map(a), AmqpValue::Map(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .all(|(key, a)| b.get(key).is_some_and(|b| a.approx_eq(b, epsilon)))
+            }
+            (AmqpValue::Described(a, a_value), AmqpValue::Described(b, b_value)) => {
+                a == b && a_value.approx_eq(b_value, epsilon)
+            }
+            _ => self == other,
+        }
+    }
+}
+
+impl PartialEq for AmqpValue {
+    fn eq(&self, other: &Self) -> bool {
+        use AmqpValue::*;
+        match (self, other) {
+            (Null, Null) => true,
+            (Boolean(a), Boolean(b)) => a == b,
+            (Ubyte(a), Ubyte(b)) => a == b,
+            (Ushort(a), Ushort(b)) => a == b,
+            (Uint(a), Uint(b)) => a == b,
+            (Ulong(a), Ulong(b)) => a == b,
+            (Byte(a), Byte(b)) => a == b,
+            (Short(a), Short(b)) => a == b,
+            (Int(a), Int(b)) => a == b,
+            (Long(a), Long(b)) => a == b,
+            (Float(a), Float(b)) => canonical_f32(*a) == canonical_f32(*b),
+            (Double(a), Double(b)) => canonical_f64(*a) == canonical_f64(*b),
+            (Decimal32(a), Decimal32(b)) => a == b,
+            (Decimal64(a), Decimal64(b)) => a == b,
+            (Decimal128(a), Decimal128(b)) => a == b,
+            (Char(a), Char(b)) => a == b,
+            (Timestamp(a), Timestamp(b)) => a == b,
+            (Uuid(a), Uuid(b)) => a == b,
+            (Binary(a), Binary(b)) => a == b,
+            (String(a), String(b)) => a == b,
+            (Symbol(a), Symbol(b)) => a == b,
+            (List(a), List(b)) | (Array(a), Array(b)) => a == b,
+            (Map(a), Map(b)) => a == b,
+            (Described(a, a_value), Described(b, b_value)) => a == b && a_value == b_value,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for AmqpValue {}
+
+impl std::hash::Hash for AmqpValue {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        use std::hash::Hasher;
+        use AmqpValue::*;
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Null => {}
+            Boolean(v) => v.hash(state),
+            Ubyte(v) => v.hash(state),
+            Ushort(v) => v.hash(state),
+            Uint(v) => v.hash(state),
+            Ulong(v) => v.hash(state),
+            Byte(v) => v.hash(state),
+            Short(v) => v.hash(state),
+            Int(v) => v.hash(state),
+            Long(v) => v.hash(state),
+            Float(v) => canonical_f32(*v).hash(state),
+            Double(v) => canonical_f64(*v).hash(state),
+            Decimal32(v) => v.hash(state),
+            Decimal64(v) => v.hash(state),
+            Decimal128(v) => v.hash(state),
+            Char(v) => v.hash(state),
+            Timestamp(v) => v.hash(state),
+            Uuid(v) => v.hash(state),
+            Binary(v) => v.hash(state),
+            String(v) => v.hash(state),
+            Symbol(v) => v.hash(state),
+            List(v) | Array(v) => v.hash(state),
+            Map(map) => {
+                // Entries are hashed independently and combined so that
+                // iteration order does not matter
+                let combined = map.iter().fold(0u64, |acc, entry| {
+                    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                    entry.hash(&mut hasher);
+                    acc.wrapping_add(hasher.finish())
+                });
+                map.len().hash(state);
+                combined.hash(state);
+            }
+            Described(descriptor, value) => {
+                descriptor.hash(state);
+                value.hash(state);
+            }
+        }
+    }
 }
 
 /// AMQP Error
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct AmqpError {
     pub condition: crate::condition::AmqpCondition,
     pub description: Option<String>,
@@ -151,14 +651,15 @@ impl AmqpError {
         self
     }
 
-    pub fn with_info(mut self, info: AmqpMap) -> Self {
-        self.info = Some(info);
+    pub fn with_info(mut self, info: impl Into<AmqpMap>) -> Self {
+        self.info = Some(info.into());
         self
     }
 }
 
 /// Sender Settle Mode
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum SenderSettleMode {
     Unsettled = 0,
     Settled = 1,
@@ -166,14 +667,24 @@ pub enum SenderSettleMode {
 }
 
 /// Receiver Settle Mode
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ReceiverSettleMode {
     First = 0,
     Second = 1,
 }
 
+/// Link Role
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Role {
+    Sender,
+    Receiver,
+}
+
 /// Terminus Durability
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TerminusDurability {
     None = 0,
     Configuration = 1,
@@ -181,15 +692,38 @@ pub enum TerminusDurability {
 }
 
 /// Terminus Expiry Policy
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TerminusExpiryPolicy {
     SessionEnd = 0,
     ConnectionClose = 1,
     Never = 2,
 }
 
+/// Distribution Mode of a source
+///
+/// Whether messages sent from the source are taken off it, or only copied
+/// so that other receivers get them too, as a queue browser does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DistributionMode {
+    Move,
+    Copy,
+}
+
+impl DistributionMode {
+    /// Get the symbol of the mode on the wire
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DistributionMode::Move => "move",
+            DistributionMode::Copy => "copy",
+        }
+    }
+}
+
 /// Message Properties
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MessageProperties {
     pub message_id: Option<AmqpValue>,
     pub user_id: Option<Vec<u8>>,
@@ -206,8 +740,11 @@ pub struct MessageProperties {
     pub reply_to_group_id: Option<String>,
 }
 
+/// Delivery Annotations
+pub type DeliveryAnnotations = Annotations;
+
 /// Message Annotations
-pub type MessageAnnotations = AmqpMap;
+pub type MessageAnnotations = Annotations;
 
 /// Application Properties
 pub type ApplicationProperties = AmqpMap; 
@@ -259,12 +796,12 @@ mod tests {
         let short_value = AmqpValue::Short(-32768);
         let int_value = AmqpValue::Int(-2147483648);
         let long_value = AmqpValue::Long(-9223372036854775808);
-        let float_value = AmqpValue::Float(3.14);
-        let double_value = AmqpValue::Double(3.14159);
+        let float_value = AmqpValue::Float(2.5);
+        let double_value = AmqpValue::Double(1.2345);
         let char_value = AmqpValue::Char('A');
         let timestamp_value = AmqpValue::Timestamp(1234567890);
-        let uuid_value = AmqpValue::Uuid(uuid::Uuid::new_v4());
-        let binary_value = AmqpValue::Binary(vec![1, 2, 3, 4]);
+        let uuid_value = AmqpValue::Uuid(Uuid::new_v4());
+        let binary_value = AmqpValue::Binary(vec![1, 2, 3, 4].into());
         let string_value = AmqpValue::String("Hello, AMQP!".to_string());
         let symbol_value = AmqpValue::Symbol(AmqpSymbol::from("test-symbol"));
 
@@ -279,8 +816,8 @@ mod tests {
         assert!(matches!(short_value, AmqpValue::Short(-32768)));
         assert!(matches!(int_value, AmqpValue::Int(-2147483648)));
         assert!(matches!(long_value, AmqpValue::Long(-9223372036854775808)));
-        assert!(matches!(float_value, AmqpValue::Float(3.14)));
-        assert!(matches!(double_value, AmqpValue::Double(3.14159)));
+        assert!(matches!(float_value, AmqpValue::Float(2.5)));
+        assert!(matches!(double_value, AmqpValue::Double(1.2345)));
         assert!(matches!(char_value, AmqpValue::Char('A')));
         assert!(matches!(timestamp_value, AmqpValue::Timestamp(1234567890)));
         assert!(matches!(uuid_value, AmqpValue::Uuid(_)));
@@ -296,19 +833,19 @@ mod tests {
             AmqpValue::String("item1".to_string()),
             AmqpValue::Int(42),
             AmqpValue::Boolean(true),
-        ]);
+        ].into());
 
         // Map
         let mut map_data = HashMap::new();
         map_data.insert(AmqpSymbol::from("key1"), AmqpValue::String("value1".to_string()));
         map_data.insert(AmqpSymbol::from("key2"), AmqpValue::Int(123));
-        let map_value = AmqpValue::Map(map_data);
+        let map_value = AmqpValue::Map(map_data.into());
 
         // Array
         let array_value = AmqpValue::Array(vec![
             AmqpValue::String("array-item".to_string()),
-            AmqpValue::Double(3.14),
-        ]);
+            AmqpValue::Double(2.5),
+        ].into());
 
         assert!(matches!(list_value, AmqpValue::List(_)));
         assert!(matches!(map_value, AmqpValue::Map(_)));
@@ -382,7 +919,7 @@ mod tests {
     fn test_amqp_error_with_info() {
         let condition = crate::condition::AmqpCondition::AmqpErrorInternalError;
         
-        let mut info = HashMap::new();
+        let mut info = AmqpMap::new();
         info.insert(AmqpSymbol::from("key"), AmqpValue::String("value".to_string()));
         
         let error = AmqpError::new(condition)
@@ -456,7 +993,7 @@ mod tests {
         let list: AmqpList = vec![
             AmqpValue::String("test".to_string()),
             AmqpValue::Int(42),
-        ];
+        ].into();
         
         assert_eq!(list.len(), 2);
         assert!(matches!(&list[0], AmqpValue::String(_)));
@@ -465,7 +1002,7 @@ mod tests {
 
     #[test]
     fn test_amqp_map_type_alias() {
-        let mut map: AmqpMap = HashMap::new();
+        let mut map = AmqpMap::new();
         map.insert(AmqpSymbol::from("key"), AmqpValue::String("value".to_string()));
         
         assert_eq!(map.len(), 1);
@@ -474,6 +1011,69 @@ mod tests {
         assert_eq!(map.get(&key), Some(&AmqpValue::String("value".to_string())));
     }
 
+    #[test]
+    fn test_annotation_key_conversions() {
+        let mut annotations: Annotations = HashMap::new();
+        annotations.insert(AnnotationKey::from("x-opt-partition"), AmqpValue::Int(3));
+        annotations.insert(AnnotationKey::from(0x0000_0137_0000_0001u64), AmqpValue::Boolean(true));
+
+        assert_eq!(annotations.get(&"x-opt-partition".into()), Some(&AmqpValue::Int(3)));
+        assert_eq!(annotations.get(&AnnotationKey::Ulong(0x0000_0137_0000_0001)), Some(&AmqpValue::Boolean(true)));
+        assert_eq!(AnnotationKey::from(AmqpSymbol::from("a")), AnnotationKey::Symbol(AmqpSymbol::from("a")));
+        assert_eq!(AnnotationKey::Ulong(0x137).to_string(), "0x0000000000000137");
+    }
+
+    fn hash_of(value: &AmqpValue) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_float_equality_semantics() {
+        assert_eq!(AmqpValue::Double(f64::NAN), AmqpValue::Double(-f64::NAN));
+        assert_eq!(AmqpValue::Float(-0.0), AmqpValue::Float(0.0));
+        assert_ne!(AmqpValue::Float(1.0), AmqpValue::Double(1.0));
+        assert_eq!(hash_of(&AmqpValue::Double(f64::NAN)), hash_of(&AmqpValue::Double(-f64::NAN)));
+        assert_eq!(hash_of(&AmqpValue::Double(-0.0)), hash_of(&AmqpValue::Double(0.0)));
+
+        let mut set = std::collections::HashSet::new();
+        set.insert(AmqpValue::List(vec![AmqpValue::Double(f64::NAN)].into()));
+        assert!(set.contains(&AmqpValue::List(vec![AmqpValue::Double(f64::NAN)].into())));
+    }
+
+    #[test]
+    fn test_map_hash_ignores_order() {
+        let mut a = AmqpMap::new();
+        let mut b = AmqpMap::new();
+        for i in 0..16 {
+            a.insert(AmqpSymbol::from(format!("k{}", i)), AmqpValue::Int(i));
+        }
+        for i in (0..16).rev() {
+            b.insert(AmqpSymbol::from(format!("k{}", i)), AmqpValue::Int(i));
+        }
+        assert_eq!(AmqpValue::Map(a.clone()), AmqpValue::Map(b.clone()));
+        assert_eq!(hash_of(&AmqpValue::Map(a)), hash_of(&AmqpValue::Map(b)));
+    }
+
+    #[test]
+    fn test_approx_eq() {
+        let mut map = AmqpMap::new();
+        map.insert(AmqpSymbol::from("ratio"), AmqpValue::Double(0.1 + 0.2));
+        let mut expected = AmqpMap::new();
+        expected.insert(AmqpSymbol::from("ratio"), AmqpValue::Double(0.3));
+
+        let actual = AmqpValue::List(vec![AmqpValue::Map(map), AmqpValue::Float(1.0)].into());
+        let close = AmqpValue::List(vec![AmqpValue::Map(expected), AmqpValue::Float(1.000_001)].into());
+        assert_ne!(actual, close);
+        assert!(actual.approx_eq(&close, 1e-5));
+        assert!(!actual.approx_eq(&close, 1e-9));
+        assert!(AmqpValue::Double(f64::NAN).approx_eq(&AmqpValue::Double(f64::NAN), 0.0));
+        assert!(!AmqpValue::Float(1.0).approx_eq(&AmqpValue::Double(1.0), 1.0));
+    }
+
+    #[cfg(feature = "serde")]
     #[test]
     fn test_serde_serialization() {
         let value = AmqpValue::String("test".to_string());
@@ -483,6 +1083,7 @@ mod tests {
         assert_eq!(value, deserialized);
     }
 
+    #[cfg(feature = "serde")]
     #[test]
     fn test_serde_deserialization() {
         let json = r#"{"String": "test"}"#;
@@ -493,4 +1094,14 @@ mod tests {
             assert_eq!(s, "test");
         }
     }
+
+    #[cfg(not(feature = "uuid"))]
+    #[test]
+    fn test_builtin_uuid() {
+        let uuid = Uuid::from_u128(0x12345678_9abc_4def_8001_020304050607);
+        assert_eq!(uuid.to_string(), "12345678-9abc-4def-8001-020304050607");
+        assert_eq!(Uuid::from_bytes(*uuid.as_bytes()).as_u128(), uuid.as_u128());
+        assert_ne!(Uuid::new_v4(), Uuid::new_v4());
+    }
+
 } 
\ No newline at end of file
@@ -0,0 +1,396 @@
+//! AMQP 1.0 Task Watchdog
+//!
+//! This module supervises the background tasks a connection cannot work
+//! without. A panicking task or one that stops making progress would
+//! otherwise leave the connection hanging silently; the [`Watchdog`] turns
+//! both into incidents reported as [`WatchdogEvent`]s and recovers:
+//!
+//! - Tasks without state of their own, such as the keep-alive, are restarted
+//!   after a panic, up to [`WatchdogConfig::max_restarts`] times.
+//! - The connection driver owns the transport and the routing of every
+//!   session, so it cannot be restarted. When it panics, or has work queued
+//!   but makes no [`Progress`] for [`WatchdogConfig::stall_timeout`], it is
+//!   aborted and the connection fails with an error instead of hanging.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use dumq_amqp::watchdog::{Watchdog, WatchdogConfig, WatchdogEvent};
+//! use std::time::Duration;
+//!
+//! let watchdog = Watchdog::new(WatchdogConfig {
+//!     stall_timeout: Duration::from_secs(10),
+//!     ..Default::default()
+//! });
+//! let mut events = watchdog.subscribe();
+//! # let _ = async move {
+//! while let Ok(event) = events.recv().await {
+//!     if let WatchdogEvent::Failed { task, reason } = event {
+//!         eprintln!("{} failed: {}", task, reason);
+//!     }
+//! }
+//! # };
+//! ```
+
+use crate::logging;
+use crate::tasks::{self, TaskKind};
+use crate::{AmqpError, AmqpResult};
+use std::any::Any;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tokio::task::{AbortHandle, JoinError, JoinHandle};
+
+/// Capacity of the watchdog event channel
+const EVENT_CAPACITY: usize = 16;
+
+/// Watchdog configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchdogConfig {
+    /// Time a task may go without progress while work is queued
+    pub stall_timeout: Duration,
+    /// How often progress is checked
+    pub check_interval: Duration,
+    /// Restarts of a panicking task before giving up on it
+    pub max_restarts: u32,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        WatchdogConfig {
+            stall_timeout: Duration::from_secs(30),
+            check_interval: Duration::from_secs(1),
+            max_restarts: 3,
+        }
+    }
+}
+
+/// Incident in a supervised task
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchdogEvent {
+    /// The task panicked
+    Panicked {
+        /// Name of the task
+        task: String,
+        /// Panic message
+        message: String,
+    },
+    /// The task made no progress while work was queued
+    Stalled {
+        /// Name of the task
+        task: String,
+        /// Operations queued for the task
+        pending: u64,
+        /// Time since the task last made progress
+        idle: Duration,
+    },
+    /// The task was restarted after a panic
+    Restarted {
+        /// Name of the task
+        task: String,
+        /// Restarts so far
+        restarts: u32,
+    },
+    /// The task was given up on, failing what depends on it
+    Failed {
+        /// Name of the task
+        task: String,
+        /// Why the task was given up on
+        reason: String,
+    },
+}
+
+#[derive(Debug)]
+struct ProgressState {
+    pending: u64,
+    last_progress: Instant,
+}
+
+/// Work queued for a task and when it last completed some
+///
+/// Shared between the producers queueing work, the task completing it and
+/// the watchdog checking on it.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    state: Arc<Mutex<ProgressState>>,
+}
+
+impl Progress {
+    /// Create a progress tracker with no work queued
+    pub fn new() -> Self {
+        Progress {
+            state: Arc::new(Mutex::new(ProgressState {
+                pending: 0,
+                last_progress: Instant::now(),
+            })),
+        }
+    }
+
+    /// Record an operation queued for the task
+    pub fn queued(&self) {
+        let mut state = self.lock();
+        // An idle task has not stalled, so only count from the first queued operation
+        if state.pending == 0 {
+            state.last_progress = Instant::now();
+        }
+        state.pending += 1;
+    }
+
+    /// Record an operation completed by the task
+    pub fn completed(&self) {
+        let mut state = self.lock();
+        state.pending = state.pending.saturating_sub(1);
+        state.last_progress = Instant::now();
+    }
+
+    /// Get the number of operations queued
+    pub fn pending(&self) -> u64 {
+        self.lock().pending
+    }
+
+    /// Get the time without progress, if work is queued
+    pub fn stalled_for(&self) -> Option<Duration> {
+        let state = self.lock();
+        (state.pending > 0).then(|| state.last_progress.elapsed())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ProgressState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Supervisor of a connection's background tasks
+#[derive(Debug, Clone)]
+pub struct Watchdog {
+    config: WatchdogConfig,
+    events: broadcast::Sender<WatchdogEvent>,
+}
+
+impl Watchdog {
+    /// Create a watchdog
+    pub fn new(config: WatchdogConfig) -> Self {
+        Watchdog {
+            config,
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+
+    /// Get the configuration
+    pub fn config(&self) -> &WatchdogConfig {
+        &self.config
+    }
+
+    /// Subscribe to incidents in supervised tasks
+    pub fn subscribe(&self) -> broadcast::Receiver<WatchdogEvent> {
+        self.events.subscribe()
+    }
+
+    fn report(&self, event: WatchdogEvent) {
+        logging::warn!("Watchdog: {:?}", event);
+        let _ = self.events.send(event);
+    }
+
+    /// Supervise a task that cannot be restarted
+    ///
+    /// The returned task ends with the supervised task's result, or with an
+    /// error once it panicked or stalled; a stalled task is aborted. Aborting
+    /// the returned task aborts the supervised one too.
+    pub(crate) fn supervise<T>(
+        &self,
+        kind: TaskKind,
+        owner: &str,
+        mut task: JoinHandle<AmqpResult<T>>,
+        progress: Progress,
+    ) -> JoinHandle<AmqpResult<T>>
+    where
+        T: Send + 'static,
+    {
+        let watchdog = self.clone();
+        let name = tasks::task_name(kind, owner);
+        tasks::spawn(TaskKind::Watchdog, owner, async move {
+            let _guard = AbortOnDrop(task.abort_handle());
+            let period = watchdog.config.check_interval;
+            let mut checks = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            loop {
+                tokio::select! {
+                    result = &mut task => {
+                        return match result {
+                            Ok(result) => result,
+                            Err(e) => {
+                                let reason = watchdog.task_lost(&name, e);
+                                watchdog.report(WatchdogEvent::Failed { task: name.clone(), reason: reason.clone() });
+                                Err(AmqpError::connection(format!("{} {}", name, reason)))
+                            }
+                        };
+                    }
+                    _ = checks.tick() => {
+                        let idle = match progress.stalled_for() {
+                            Some(idle) if idle >= watchdog.config.stall_timeout => idle,
+                            _ => continue,
+                        };
+                        task.abort();
+                        let pending = progress.pending();
+                        watchdog.report(WatchdogEvent::Stalled { task: name.clone(), pending, idle });
+                        let reason = format!("made no progress for {:?} with {} operations queued", idle, pending);
+                        watchdog.report(WatchdogEvent::Failed { task: name.clone(), reason: reason.clone() });
+                        return Err(AmqpError::timeout(format!("{} {}", name, reason)));
+                    }
+                }
+            }
+        })
+    }
+
+    /// Supervise a task that can be restarted, spawning it from `start`
+    ///
+    /// The task is spawned again each time it panics, until
+    /// [`WatchdogConfig::max_restarts`] is exceeded. Aborting the returned
+    /// task aborts the supervised one too.
+    pub(crate) fn supervise_restartable<F, Fut>(&self, kind: TaskKind, owner: &str, mut start: F) -> JoinHandle<()>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let watchdog = self.clone();
+        let task_owner = owner.to_string();
+        let name = tasks::task_name(kind, owner);
+        tasks::spawn(TaskKind::Watchdog, owner, async move {
+            let mut restarts = 0;
+            loop {
+                let mut task = tasks::spawn(kind, &task_owner, start());
+                let _guard = AbortOnDrop(task.abort_handle());
+                let e = match (&mut task).await {
+                    Ok(()) => return,
+                    Err(e) if e.is_panic() => e,
+                    Err(_) => return,
+                };
+                let reason = watchdog.task_lost(&name, e);
+                if restarts >= watchdog.config.max_restarts {
+                    watchdog.report(WatchdogEvent::Failed {
+                        task: name.clone(),
+                        reason: format!("{} after {} restarts", reason, restarts),
+                    });
+                    return;
+                }
+                restarts += 1;
+                watchdog.report(WatchdogEvent::Restarted { task: name.clone(), restarts });
+            }
+        })
+    }
+
+    /// Report how a task ended without a result
+    fn task_lost(&self, name: &str, error: JoinError) -> String {
+        if !error.is_panic() {
+            return "was cancelled".to_string();
+        }
+        let message = panic_message(error.into_panic());
+        self.report(WatchdogEvent::Panicked {
+            task: name.to_string(),
+            message: message.clone(),
+        });
+        format!("panicked: {}", message)
+    }
+}
+
+impl Default for Watchdog {
+    fn default() -> Self {
+        Self::new(WatchdogConfig::default())
+    }
+}
+
+/// Aborts a supervised task when its supervisor goes away
+struct AbortOnDrop(AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .unwrap_or_else(|| "unknown panic".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn quick() -> WatchdogConfig {
+        WatchdogConfig {
+            stall_timeout: Duration::from_millis(50),
+            check_interval: Duration::from_millis(10),
+            max_restarts: 2,
+        }
+    }
+
+    #[test]
+    fn test_idle_task_not_stalled() {
+        let progress = Progress::new();
+        assert_eq!(progress.stalled_for(), None);
+
+        progress.queued();
+        progress.queued();
+        assert!(progress.stalled_for().is_some());
+        progress.completed();
+        progress.completed();
+        assert_eq!(progress.pending(), 0);
+        assert_eq!(progress.stalled_for(), None);
+    }
+
+    #[tokio::test]
+    async fn test_stalled_task_aborted_and_reported() {
+        let watchdog = Watchdog::new(quick());
+        let mut events = watchdog.subscribe();
+        let progress = Progress::new();
+        let task = tokio::spawn(std::future::pending::<AmqpResult<()>>());
+        let abort = task.abort_handle();
+
+        progress.queued();
+        let result = watchdog.supervise(TaskKind::Driver, "watchdog-test-1", task, progress).await.unwrap();
+        assert!(matches!(result, Err(AmqpError::Timeout(_))));
+        assert!(abort.is_finished());
+        assert!(matches!(events.recv().await.unwrap(), WatchdogEvent::Stalled { pending: 1, .. }));
+        assert!(matches!(events.recv().await.unwrap(), WatchdogEvent::Failed { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_panicking_task_restarted_until_limit() {
+        let watchdog = Watchdog::new(quick());
+        let mut events = watchdog.subscribe();
+        let started = Arc::new(AtomicU32::new(0));
+
+        let counter = started.clone();
+        let supervisor = watchdog.supervise_restartable(TaskKind::KeepAlive, "watchdog-test-2", move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async { panic!("keep-alive broke") }
+        });
+        supervisor.await.unwrap();
+
+        assert_eq!(started.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            events.recv().await.unwrap(),
+            WatchdogEvent::Panicked {
+                task: "dumq-amqp/keepalive/watchdog-test-2".to_string(),
+                message: "keep-alive broke".to_string(),
+            }
+        );
+        assert!(matches!(events.recv().await.unwrap(), WatchdogEvent::Restarted { restarts: 1, .. }));
+        assert!(matches!(events.recv().await.unwrap(), WatchdogEvent::Panicked { .. }));
+        assert!(matches!(events.recv().await.unwrap(), WatchdogEvent::Restarted { restarts: 2, .. }));
+        assert!(matches!(events.recv().await.unwrap(), WatchdogEvent::Panicked { .. }));
+        assert!(matches!(events.recv().await.unwrap(), WatchdogEvent::Failed { reason, .. } if reason.contains("after 2 restarts")));
+    }
+}